@@ -20,6 +20,11 @@ class ControlMessageType:
     RESET_TO_INITIAL = 0x03
     START_SESSION = 0x04
     INTERRUPT = 0x05
+    HANDSHAKE = 0x08
+    HEARTBEAT = 0x0B  # 客户端空闲时定期发送，无负载
+
+# 本后端支持的音频上行协议版本（v1: 静音事件只携带静音时长）
+PROTOCOL_VERSION = 1
 
 # 控制消息数据模型
 class ControlMessage(BaseModel):
@@ -105,6 +110,11 @@ class ControlMessageHandler:
                 await ControlMessageHandler._handle_start_session(client, client_id, loop)
             elif msg_type == ControlMessageType.INTERRUPT:
                 await ControlMessageHandler._handle_interrupt(client, client_id, loop)
+            elif msg_type == ControlMessageType.HANDSHAKE:
+                await ControlMessageHandler._handle_handshake(client, client_id, loop)
+            elif msg_type == ControlMessageType.HEARTBEAT:
+                # 心跳只用于客户端探测连接是否仍然可用，无需处理
+                pass
             else:
                 print(f"【警告】未知的控制消息类型: 0x{msg_type:02x}，客户端 {client_id}")
                 
@@ -129,6 +139,24 @@ class ControlMessageHandler:
         except Exception as e:
             print(f"【错误】处理静音事件失败: {e}")
     
+    @staticmethod
+    async def _handle_handshake(client: socket.socket, client_id: str, loop) -> None:
+        """处理协议握手：读取客户端协议版本与采样率，回复本后端的协议版本"""
+        try:
+            # 读取协议版本（u32）与采样率（u32）
+            payload = await loop.sock_recv(client, 8)
+            if len(payload) != 8:
+                print(f"【警告】握手数据不完整，客户端 {client_id}")
+                return
+            client_version, sample_rate = struct.unpack("<II", payload)
+            print(f"【重要】收到协议握手 (客户端 {client_id}): 版本 v{client_version}, 采样率 {sample_rate}Hz")
+
+            # 回复：控制消息头 + 消息类型 + 本后端协议版本
+            ack = struct.pack("<IBI", 0xFFFFFFFF, ControlMessageType.HANDSHAKE, PROTOCOL_VERSION)
+            await loop.sock_sendall(client, ack)
+        except Exception as e:
+            print(f"【错误】处理协议握手失败: {e}")
+
     @staticmethod
     async def _handle_end_session(client: socket.socket, client_id: str, loop) -> None:
         """处理会话结束事件"""
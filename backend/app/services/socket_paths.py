@@ -0,0 +1,55 @@
+# app/services/socket_paths.py
+# 与前端通信的Unix Socket路径
+# 前端在启动后端前把实际使用的路径导出到环境变量，这里按同样的优先级解析，保证两端连到同一组socket：
+#   LUMINA_STT_SOCKET / LUMINA_STT_RESULT_SOCKET / LUMINA_TTS_SOCKET（单个路径）
+#   > $LUMINA_SOCKET_DIR/<名称>
+#   > $XDG_RUNTIME_DIR/lumina/<名称>
+#   > <临时目录>/lumina-<uid>/<名称>
+# 目录权限收紧为0700，避免多用户机器上被其他用户进程连上。
+
+import os
+import tempfile
+
+SOCKET_DIR_ENV = "LUMINA_SOCKET_DIR"
+STT_SOCKET_ENV = "LUMINA_STT_SOCKET"
+STT_RESULT_SOCKET_ENV = "LUMINA_STT_RESULT_SOCKET"
+TTS_SOCKET_ENV = "LUMINA_TTS_SOCKET"
+
+SOCKET_DIR_NAME = "lumina"
+STT_SOCKET_NAME = "stt.sock"
+STT_RESULT_SOCKET_NAME = "stt_result.sock"
+TTS_SOCKET_NAME = "tts.sock"
+
+
+def default_socket_dir() -> str:
+    """每用户socket目录，与前端 endpoints::default_socket_dir 的规则一致"""
+    socket_dir = os.environ.get(SOCKET_DIR_ENV)
+    if socket_dir:
+        return socket_dir
+    runtime_dir = os.environ.get("XDG_RUNTIME_DIR")
+    if runtime_dir:
+        return os.path.join(runtime_dir, SOCKET_DIR_NAME)
+    return os.path.join(tempfile.gettempdir(), f"{SOCKET_DIR_NAME}-{os.getuid()}")
+
+
+def _resolve(env_name: str, file_name: str) -> str:
+    path = os.environ.get(env_name) or os.path.join(default_socket_dir(), file_name)
+    parent = os.path.dirname(path)
+    if parent:
+        os.makedirs(parent, mode=0o700, exist_ok=True)
+    return path
+
+
+def stt_socket_path() -> str:
+    """接收前端音频的socket路径"""
+    return _resolve(STT_SOCKET_ENV, STT_SOCKET_NAME)
+
+
+def stt_result_socket_path() -> str:
+    """向前端发送识别结果的socket路径"""
+    return _resolve(STT_RESULT_SOCKET_ENV, STT_RESULT_SOCKET_NAME)
+
+
+def tts_socket_path() -> str:
+    """向前端发送TTS音频的socket路径"""
+    return _resolve(TTS_SOCKET_ENV, TTS_SOCKET_NAME)
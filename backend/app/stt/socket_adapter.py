@@ -7,11 +7,12 @@ import asyncio
 import socket
 import struct
 import platform
-from typing import List
+from typing import List, Optional
 
 from app.protocols.stt import AudioData, STTResponse
 from app.stt.alicloud_client import AliCloudSTTAdapter
 from app.api.v1.control import ControlMessageHandler
+from app.services.socket_paths import stt_socket_path, stt_result_socket_path
 
 
 class SocketSTTHandler:
@@ -28,8 +29,8 @@ class SocketSTTHandler:
         self, 
         stt_client: AliCloudSTTAdapter, 
         is_windows: bool = platform.system() == "Windows",
-        unix_socket_path: str = "/tmp/lumina_stt.sock",
-        unix_result_path: str = "/tmp/lumina_stt_result.sock",
+        unix_socket_path: Optional[str] = None,
+        unix_result_path: Optional[str] = None,
         tcp_host: str = "127.0.0.1",
         tcp_port: int = 8765,
         tcp_result_port: int = 8766
@@ -39,8 +40,8 @@ class SocketSTTHandler:
         Args:
             stt_client: 阿里云语音识别客户端实例
             is_windows: 是否为Windows系统
-            unix_socket_path: Unix Socket路径，用于接收音频数据（非Windows）
-            unix_result_path: Unix Socket路径，用于发送识别结果（非Windows）
+            unix_socket_path: Unix Socket路径，用于接收音频数据（非Windows），默认按前端导出的环境变量解析
+            unix_result_path: Unix Socket路径，用于发送识别结果（非Windows），默认按前端导出的环境变量解析
             tcp_host: TCP主机地址（Windows）
             tcp_port: TCP端口，用于接收音频数据（Windows）
             tcp_result_port: TCP端口，用于发送识别结果（Windows）
@@ -59,8 +60,8 @@ class SocketSTTHandler:
             # )
         else:
             # print("【调试】检测到UNIX系统，使用Unix Socket")
-            self.unix_socket_path = unix_socket_path
-            self.unix_result_path = unix_result_path
+            self.unix_socket_path = unix_socket_path or stt_socket_path()
+            self.unix_result_path = unix_result_path or stt_result_socket_path()
             # print(
             #     f"【调试】初始化Unix Socket STT处理器: "
             #     f"socket_path={unix_socket_path}, result_path={unix_result_path}"
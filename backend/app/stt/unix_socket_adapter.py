@@ -11,6 +11,7 @@ from typing import List, Optional
 from app.protocols.stt import AudioData, STTResponse
 from app.stt.alicloud_client import AliCloudSTTAdapter
 from app.api.v1.control import ControlMessageHandler
+from app.services.socket_paths import stt_socket_path, stt_result_socket_path
 
 
 class UnixSocketSTTHandler:
@@ -26,10 +27,12 @@ class UnixSocketSTTHandler:
     def __init__(
         self, 
         stt_client: AliCloudSTTAdapter, 
-        socket_path: str = "/tmp/lumina_stt.sock",
-        result_socket_path: str = "/tmp/lumina_stt_result.sock"
+        socket_path: Optional[str] = None,
+        result_socket_path: Optional[str] = None
     ):
-        """初始化Unix Socket语音识别处理器"""
+        """初始化Unix Socket语音识别处理器，路径默认按前端导出的环境变量解析"""
+        socket_path = socket_path or stt_socket_path()
+        result_socket_path = result_socket_path or stt_result_socket_path()
         print(
             f"【调试】初始化UnixSocketSTTHandler (重构版): "
             f"socket_path={socket_path}, result_socket_path={result_socket_path}"
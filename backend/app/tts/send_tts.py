@@ -5,13 +5,14 @@ import wave
 from typing import AsyncGenerator, Union, Any, AsyncIterator
 
 from app.services.socket import UnifiedSocket
+from app.services.socket_paths import tts_socket_path
 from app.protocols.tts import TTSResponse
 
 # 为TTS音频定义套接字路径
 if sys.platform == 'win32':
     TTS_SOCKET_PATH = "127.0.0.1:8767"
 else:
-    TTS_SOCKET_PATH = "/tmp/lumina_tts.sock"
+    TTS_SOCKET_PATH = tts_socket_path()
 
 # TTS套接字的单例实例
 tts_socket_server = UnifiedSocket(TTS_SOCKET_PATH, name="TTS_Socket")
@@ -0,0 +1,111 @@
+// 命令注册宏
+// #[command] 在 tauri::command 之外把每次调用接入命令审计（crate::audit）：
+// 进入时记下命令名与参数摘要，退出时记下耗时与结果，Future 被丢弃时记为取消。
+// 审计缓冲由 AppState 持有：命令已有 State 参数时直接使用，没有时在签名末尾注入一个 State<AppState> 参数（由 Tauri 提供，前端无需传入）。
+// lib.rs 用它替代 tauri::command，新增的命令无需额外处理即会被审计。
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, FnArg, Ident, ItemFn, Pat, ReturnType, Type};
+
+// 由 Tauri 注入、不来自前端的参数类型，不计入参数摘要
+const INJECTED_TYPES: [&str; 8] = [
+    "State",
+    "AppHandle",
+    "Window",
+    "WebviewWindow",
+    "Webview",
+    "Channel",
+    "Request",
+    "InvokeMessage",
+];
+
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = TokenStream2::from(attr);
+    let function = parse_macro_input!(item as ItemFn);
+    let ItemFn { attrs, vis, mut sig, block } = function;
+    let name = sig.ident.to_string();
+
+    let state = match state_arg(&sig) {
+        Some(ident) => ident,
+        None => {
+            sig.inputs.push(parse_quote! { __audit_state: ::tauri::State<'_, crate::AppState> });
+            Ident::new("__audit_state", proc_macro2::Span::call_site())
+        }
+    };
+
+    let summaries: Vec<TokenStream2> = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(arg) if !is_injected(&arg.ty) => match arg.pat.as_ref() {
+                Pat::Ident(pat) => {
+                    let ident = &pat.ident;
+                    Some(quote! { crate::audit::summarize_arg(stringify!(#ident), &#ident) })
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    let output = match &sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    let call = if sig.asyncness.is_some() {
+        quote! { async move #block.await }
+    } else {
+        quote! { (move || -> #output #block)() }
+    };
+    let attr = if attr.is_empty() { quote! {} } else { quote! { (#attr) } };
+
+    quote! {
+        #(#attrs)*
+        #[::tauri::command #attr]
+        #vis #sig {
+            let __audit = crate::audit::enter(&#state.audit, #name, || vec![#(#summaries),*]);
+            let __result: #output = #call;
+            let __audit_error = {
+                #[allow(unused_imports)]
+                use crate::audit::{ResultError as _, NoError as _};
+                (&__result).audit_error()
+            };
+            __audit.finish(__audit_error);
+            __result
+        }
+    }
+    .into()
+}
+
+// 已有的 State 参数（命令中的 State 均为 State<AppState>）
+fn state_arg(sig: &syn::Signature) -> Option<Ident> {
+    sig.inputs.iter().find_map(|input| match input {
+        FnArg::Typed(arg) if last_segment(&arg.ty).as_deref() == Some("State") => match arg.pat.as_ref() {
+            Pat::Ident(pat) => Some(pat.ident.clone()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn last_segment(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn is_injected(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| INJECTED_TYPES.contains(&segment.ident.to_string().as_str())),
+        Type::Reference(reference) => is_injected(&reference.elem),
+        _ => false,
+    }
+}
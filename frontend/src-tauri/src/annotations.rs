@@ -0,0 +1,149 @@
+// VAD 标注导出
+// 以已处理的样本数作为音频时间轴记录语音活动区间，并导出为 Praat TextGrid 或 NIST RTTM。
+// VAD 需要连续若干帧才能判定开始/结束，记录时会按判定用掉的帧数回溯到真正的起止位置。
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SpeechInterval {
+    pub start_ms: f64,
+    pub end_ms: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationFormat {
+    TextGrid,
+    Rttm,
+}
+
+impl AnnotationFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "textgrid" => Some(AnnotationFormat::TextGrid),
+            "rttm" => Some(AnnotationFormat::Rttm),
+            _ => None,
+        }
+    }
+}
+
+pub struct AnnotationRecorder {
+    sample_rate: u32,
+    position_samples: u64, // 已处理的样本总数
+    open_start_samples: Option<u64>,
+    intervals: Vec<(u64, u64)>, // 以样本为单位的已闭合区间
+}
+
+impl AnnotationRecorder {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            position_samples: 0,
+            open_start_samples: None,
+            intervals: Vec::new(),
+        }
+    }
+
+    // 推进时间轴，在每帧处理完成后调用
+    pub fn advance(&mut self, samples: usize) {
+        self.position_samples += samples as u64;
+    }
+
+    // 语音开始：lookback_samples 为判定开始所用的语音样本数
+    pub fn speech_start(&mut self, lookback_samples: u64) {
+        let start = self.position_samples.saturating_sub(lookback_samples);
+        // 不与上一个区间重叠
+        let start = match self.intervals.last() {
+            Some(&(_, last_end)) => start.max(last_end),
+            None => start,
+        };
+        self.open_start_samples = Some(start);
+    }
+
+    // 语音结束：lookback_samples 为判定结束所用的静音样本数
+    pub fn speech_end(&mut self, lookback_samples: u64) {
+        if let Some(start) = self.open_start_samples.take() {
+            let end = self.position_samples.saturating_sub(lookback_samples).max(start);
+            self.intervals.push((start, end));
+        }
+    }
+
+    pub fn duration_ms(&self) -> f64 {
+        self.samples_to_ms(self.position_samples)
+    }
+
+    // 当前所有区间，未结束的区间截止到当前位置
+    pub fn intervals(&self) -> Vec<SpeechInterval> {
+        let mut result: Vec<SpeechInterval> = self
+            .intervals
+            .iter()
+            .map(|&(start, end)| SpeechInterval {
+                start_ms: self.samples_to_ms(start),
+                end_ms: self.samples_to_ms(end),
+            })
+            .collect();
+        if let Some(start) = self.open_start_samples {
+            result.push(SpeechInterval {
+                start_ms: self.samples_to_ms(start),
+                end_ms: self.duration_ms(),
+            });
+        }
+        result
+    }
+
+    fn samples_to_ms(&self, samples: u64) -> f64 {
+        samples as f64 * 1000.0 / self.sample_rate as f64
+    }
+}
+
+// 生成 Praat TextGrid（长格式），区间需铺满整个时间轴，静音区间文本为空
+pub fn to_textgrid(intervals: &[SpeechInterval], duration_ms: f64) -> String {
+    let xmax = duration_ms / 1000.0;
+
+    let mut tiles: Vec<(f64, f64, &str)> = Vec::new();
+    let mut cursor = 0.0;
+    for interval in intervals {
+        let start = interval.start_ms / 1000.0;
+        let end = interval.end_ms / 1000.0;
+        if start > cursor {
+            tiles.push((cursor, start, ""));
+        }
+        tiles.push((start, end, "speech"));
+        cursor = end;
+    }
+    if xmax > cursor || tiles.is_empty() {
+        tiles.push((cursor, xmax.max(cursor), ""));
+    }
+
+    let mut out = String::new();
+    out.push_str("File type = \"ooTextFile\"\n");
+    out.push_str("Object class = \"TextGrid\"\n\n");
+    out.push_str(&format!("xmin = 0\nxmax = {:.3}\n", xmax));
+    out.push_str("tiers? <exists>\nsize = 1\nitem []:\n");
+    out.push_str("    item [1]:\n");
+    out.push_str("        class = \"IntervalTier\"\n");
+    out.push_str("        name = \"vad\"\n");
+    out.push_str(&format!("        xmin = 0\n        xmax = {:.3}\n", xmax));
+    out.push_str(&format!("        intervals: size = {}\n", tiles.len()));
+    for (i, (start, end, text)) in tiles.iter().enumerate() {
+        out.push_str(&format!("        intervals [{}]:\n", i + 1));
+        out.push_str(&format!("            xmin = {:.3}\n", start));
+        out.push_str(&format!("            xmax = {:.3}\n", end));
+        out.push_str(&format!("            text = \"{}\"\n", text));
+    }
+    out
+}
+
+// 生成 NIST RTTM，每个语音区间一行 SPEAKER 记录
+pub fn to_rttm(intervals: &[SpeechInterval], file_id: &str) -> String {
+    let mut out = String::new();
+    for interval in intervals {
+        let start = interval.start_ms / 1000.0;
+        let duration = (interval.end_ms - interval.start_ms) / 1000.0;
+        out.push_str(&format!(
+            "SPEAKER {} 1 {:.3} {:.3} <NA> <NA> speech <NA> <NA>\n",
+            file_id, start, duration
+        ));
+    }
+    out
+}
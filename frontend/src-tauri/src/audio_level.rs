@@ -0,0 +1,72 @@
+// 麦克风电平表
+// 前端的音量表只需要每 100ms 左右刷新一次，逐帧发事件（每秒50次）会挤占 IPC 通道。
+// 这里在 Rust 侧按时间窗口汇总：窗口内取 RMS 与峰值的最大值，有任一语音帧即标记为语音，窗口结束时产出一次电平。
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::audio_utils::to_dbfs;
+
+pub const DEFAULT_AUDIO_LEVEL_INTERVAL_MS: u64 = 100;
+pub const MIN_AUDIO_LEVEL_INTERVAL_MS: u64 = 20;
+
+// audio-level 事件内容
+#[derive(Serialize, Clone, Debug)]
+pub struct AudioLevel {
+    pub rms: f32,  // 归一化到 0.0–1.0，便于前端直接画电平条
+    pub peak: f32,
+    pub rms_db: f32,
+    pub peak_db: f32,
+    pub is_voice: bool,
+}
+
+pub struct LevelMeter {
+    interval: Option<Duration>, // None 表示不发送电平事件
+    window_start: Option<Instant>,
+    max_rms: f32,
+    max_peak: f32,
+    is_voice: bool,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self {
+            interval: Some(Duration::from_millis(DEFAULT_AUDIO_LEVEL_INTERVAL_MS)),
+            window_start: None,
+            max_rms: 0.0,
+            max_peak: 0.0,
+            is_voice: false,
+        }
+    }
+
+    // 0 表示关闭；新间隔从下一个窗口开始生效
+    pub fn set_interval_ms(&mut self, interval_ms: u64) {
+        self.interval = (interval_ms > 0).then(|| Duration::from_millis(interval_ms));
+        self.window_start = None;
+    }
+
+    // 计入一帧，窗口结束时返回该窗口的电平
+    pub fn observe(&mut self, now: Instant, rms: f32, peak: f32, is_voice: bool) -> Option<AudioLevel> {
+        let interval = self.interval?;
+        let window_start = *self.window_start.get_or_insert(now);
+        self.max_rms = self.max_rms.max(rms);
+        self.max_peak = self.max_peak.max(peak);
+        self.is_voice |= is_voice;
+
+        if now.saturating_duration_since(window_start) < interval {
+            return None;
+        }
+        let level = AudioLevel {
+            rms: self.max_rms.clamp(0.0, 1.0),
+            peak: self.max_peak.clamp(0.0, 1.0),
+            rms_db: to_dbfs(self.max_rms),
+            peak_db: to_dbfs(self.max_peak),
+            is_voice: self.is_voice,
+        };
+        self.window_start = Some(now);
+        self.max_rms = 0.0;
+        self.max_peak = 0.0;
+        self.is_voice = false;
+        Some(level)
+    }
+}
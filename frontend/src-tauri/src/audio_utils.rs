@@ -0,0 +1,150 @@
+// 音频工具函数
+
+use std::time::{Duration, Instant};
+
+// 帧RMS，归一化到 0.0~1.0
+pub fn frame_rms(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let sum_squares: f64 = samples
+        .iter()
+        .map(|&s| {
+            let v = s as f64 / 32768.0;
+            v * v
+        })
+        .sum();
+    (sum_squares / samples.len() as f64).sqrt() as f32
+}
+
+// 帧峰值，归一化到 0.0~1.0
+pub fn frame_peak(samples: &[i16]) -> f32 {
+    samples
+        .iter()
+        .map(|&s| (s as f32 / 32768.0).abs())
+        .fold(0.0, f32::max)
+}
+
+// 归一化幅度换算为dBFS，静音时取下限 MIN_DBFS
+pub const MIN_DBFS: f32 = -100.0;
+
+pub fn to_dbfs(level: f32) -> f32 {
+    if level <= 0.0 {
+        return MIN_DBFS;
+    }
+    (20.0 * level.log10()).max(MIN_DBFS)
+}
+
+// f32样本转换为i16：先限幅到[-1,1]，避免AGC放大后的越界样本在转换时产生爆音
+pub fn f32_to_i16(sample: f32) -> i16 {
+    let clamped = sample.clamp(-1.0, 1.0);
+    if clamped >= 0.0 {
+        (clamped * i16::MAX as f32) as i16
+    } else {
+        (-clamped * i16::MIN as f32) as i16
+    }
+}
+
+// 统计达到或超出满幅的样本数（输入已削波）
+pub fn count_clipped(samples: &[f32]) -> usize {
+    samples.iter().filter(|s| s.abs() >= 1.0).count()
+}
+
+// 对连续的多帧做线性淡入：前 fade_samples 个样本的增益从0升到1，之后保持原样
+pub fn fade_in_frames(frames: &mut [Vec<i16>], fade_samples: usize) {
+    if fade_samples == 0 {
+        return;
+    }
+
+    let mut position = 0;
+    for frame in frames.iter_mut() {
+        for sample in frame.iter_mut() {
+            if position >= fade_samples {
+                return;
+            }
+            let gain = position as f32 / fade_samples as f32;
+            *sample = (*sample as f32 * gain) as i16;
+            position += 1;
+        }
+    }
+}
+
+// 按各段开始时刻拼接成一条连续音频：段间按上一段结尾到本段开始的时长填充静音（不超过 max_gap），
+// 开始时刻缺失或与上一段重叠时紧接上一段
+pub fn timeline(segments: &[(Option<Instant>, &[i16])], sample_rate: u32, max_gap: Duration) -> Vec<i16> {
+    let mut out = Vec::new();
+    let mut previous_end: Option<Instant> = None; // 上一段结尾对应的采集时刻
+    for &(started_at, samples) in segments {
+        if let (Some(started_at), Some(previous_end)) = (started_at, previous_end) {
+            let gap = started_at.saturating_duration_since(previous_end).min(max_gap);
+            let gap_samples = (gap.as_secs_f64() * sample_rate as f64) as usize;
+            out.resize(out.len() + gap_samples, 0);
+        }
+        out.extend_from_slice(samples);
+        let duration = Duration::from_secs_f64(samples.len() as f64 / sample_rate.max(1) as f64);
+        previous_end = started_at.or(previous_end).map(|start| start + duration);
+    }
+    out
+}
+
+// 编码为16位单声道PCM WAV（44字节 RIFF/fmt/data 头）
+pub fn encode_wav_pcm16(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // fmt 块长度
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // 单声道
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // 字节率
+    wav.extend_from_slice(&2u16.to_le_bytes()); // 块对齐
+    wav.extend_from_slice(&16u16.to_le_bytes()); // 位深
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 满幅正负值分别映射到 i16 的两端，不能回绕
+    #[test]
+    fn full_scale_maps_to_i16_bounds() {
+        assert_eq!(f32_to_i16(1.0), i16::MAX);
+        assert_eq!(f32_to_i16(-1.0), i16::MIN);
+        assert_eq!(f32_to_i16(0.0), 0);
+    }
+
+    // 超出满幅的输入（削波）钳位到两端
+    #[test]
+    fn out_of_range_samples_are_clamped() {
+        assert_eq!(f32_to_i16(1.5), i16::MAX);
+        assert_eq!(f32_to_i16(100.0), i16::MAX);
+        assert_eq!(f32_to_i16(-1.5), i16::MIN);
+        assert_eq!(f32_to_i16(-100.0), i16::MIN);
+    }
+
+    #[test]
+    fn half_scale_keeps_sign() {
+        assert_eq!(f32_to_i16(0.5), i16::MAX / 2);
+        assert_eq!(f32_to_i16(-0.5), i16::MIN / 2);
+    }
+
+    // 达到满幅的样本也计为削波；NaN 不计入
+    #[test]
+    fn count_clipped_includes_full_scale() {
+        assert_eq!(count_clipped(&[0.0, 0.99, 1.0, -1.0, 1.2, -3.0, f32::NAN]), 4);
+        assert_eq!(count_clipped(&[]), 0);
+    }
+}
@@ -0,0 +1,229 @@
+// 命令调用审计
+// 前端调试时常需要确认某次 invoke 是否进入了 Rust、花了多久、结果如何。
+// 所有命令经 lumina_macros::command 注册，进入/退出时在这里记一条：命令名、参数摘要（截断、脱敏）、耗时、结果，
+// 保存在内存环形缓冲中，由 get_command_audit 查询；耗时超过阈值的命令额外打警告日志并计数。
+// 热路径命令（process_audio_frame 每秒数十次）按采样间隔只记录其中一部分，超时的调用始终记录。
+// 审计只在内存中，不写盘；缓冲由 AppState 持有，不带 AppState 参数的命令由 command 宏注入一个。
+
+use log::warn;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::journal;
+
+pub const DEFAULT_SLOW_COMMAND_MS: u64 = 100;
+// 环形缓冲保留的记录数
+const MAX_ENTRIES: usize = 500;
+// 单个参数摘要的最大字符数
+const MAX_ARG_CHARS: usize = 80;
+// 参数名包含这些词时只记录占位符
+const SENSITIVE_ARG_NAMES: [&str; 5] = ["token", "password", "secret", "key", "credential"];
+// 热路径命令的缺省采样间隔：每N次调用记录一次
+const HOT_PATH_COMMANDS: [(&str, u32); 1] = [("process_audio_frame", 100)];
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandStatus {
+    Ok,
+    Error,
+    Cancelled, // 调用方放弃等待，Future 在完成前被丢弃
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandAuditEntry {
+    pub seq: u64, // 该命令的第几次调用，采样时不连续
+    pub command: &'static str,
+    pub args: Option<String>, // 未采样的调用（只因超时被记录）为None
+    pub started_unix_ms: u64,
+    pub duration_ms: f64,
+    pub status: CommandStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandAuditReport {
+    pub entries: Vec<CommandAuditEntry>, // 从旧到新
+    pub slow_threshold_ms: u64,
+    pub slow_counts: BTreeMap<&'static str, u64>, // 各命令超时的次数
+    pub sampling: BTreeMap<String, u32>,          // 各命令的采样间隔，未列出的命令每次都记录
+}
+
+pub struct CommandAudit {
+    entries: VecDeque<CommandAuditEntry>,
+    slow_threshold_ms: u64,
+    slow_counts: BTreeMap<&'static str, u64>,
+    call_counts: BTreeMap<&'static str, u64>,
+    sampling: Option<BTreeMap<String, u32>>, // 首次使用时填入热路径的缺省值
+}
+
+impl CommandAudit {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            slow_threshold_ms: DEFAULT_SLOW_COMMAND_MS,
+            slow_counts: BTreeMap::new(),
+            call_counts: BTreeMap::new(),
+            sampling: None,
+        }
+    }
+
+    fn sampling(&mut self) -> &mut BTreeMap<String, u32> {
+        self.sampling.get_or_insert_with(|| {
+            HOT_PATH_COMMANDS
+                .iter()
+                .map(|(command, every)| (command.to_string(), *every))
+                .collect()
+        })
+    }
+
+    // 记下一次调用，返回调用序号与本次是否采样
+    fn next_call(&mut self, command: &'static str) -> (u64, bool) {
+        let count = self.call_counts.entry(command).or_insert(0);
+        *count += 1;
+        let seq = *count;
+        let every = self.sampling().get(command).copied().unwrap_or(1).max(1);
+        (seq, (seq - 1).is_multiple_of(every as u64))
+    }
+
+    fn push(&mut self, entry: CommandAuditEntry) {
+        self.entries.push_back(entry);
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+}
+
+// 一次命令调用，由 command 宏在进入时创建、退出时结束
+pub struct CommandSpan {
+    audit: Arc<Mutex<CommandAudit>>,
+    command: &'static str,
+    seq: u64,
+    args: Option<String>,
+    started: Instant,
+    started_unix_ms: u64,
+    finished: bool,
+}
+
+pub fn enter(audit: &Arc<Mutex<CommandAudit>>, command: &'static str, summarize: impl FnOnce() -> Vec<String>) -> CommandSpan {
+    let (seq, sampled) = match audit.lock() {
+        Ok(mut audit) => audit.next_call(command),
+        Err(_) => (0, false),
+    };
+    CommandSpan {
+        audit: Arc::clone(audit),
+        command,
+        seq,
+        args: sampled.then(|| summarize().join(", ")),
+        started: Instant::now(),
+        started_unix_ms: journal::now_unix_ms(),
+        finished: false,
+    }
+}
+
+impl CommandSpan {
+    pub fn finish(mut self, error: Option<String>) {
+        self.finished = true;
+        let status = if error.is_some() { CommandStatus::Error } else { CommandStatus::Ok };
+        self.record(status, error);
+    }
+
+    fn record(&mut self, status: CommandStatus, error: Option<String>) {
+        let duration_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        let audit = Arc::clone(&self.audit);
+        let mut audit = match audit.lock() {
+            Ok(audit) => audit,
+            Err(_) => return,
+        };
+        let slow = duration_ms > audit.slow_threshold_ms as f64;
+        if slow {
+            warn!("[警告] 命令 {} 耗时 {:.1}ms，超过{}ms", self.command, duration_ms, audit.slow_threshold_ms);
+            *audit.slow_counts.entry(self.command).or_insert(0) += 1;
+        }
+        if self.args.is_none() && !slow {
+            return;
+        }
+        audit.push(CommandAuditEntry {
+            seq: self.seq,
+            command: self.command,
+            args: self.args.take(),
+            started_unix_ms: self.started_unix_ms,
+            duration_ms,
+            status,
+            error: error.map(|e| truncate(&e)),
+        });
+    }
+}
+
+impl Drop for CommandSpan {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.record(CommandStatus::Cancelled, None);
+        }
+    }
+}
+
+// 参数摘要：Debug 输出截断到 MAX_ARG_CHARS，敏感参数只记占位符
+pub fn summarize_arg<T: Debug>(name: &str, value: &T) -> String {
+    let lower = name.to_lowercase();
+    if SENSITIVE_ARG_NAMES.iter().any(|word| lower.contains(word)) {
+        return format!("{}=***", name);
+    }
+    format!("{}={}", name, truncate(&format!("{:?}", value)))
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_ARG_CHARS) {
+        Some((end, _)) => format!("{}…（共{}字符）", &text[..end], text.chars().count()),
+        None => text.to_string(),
+    }
+}
+
+// 命令返回值的错误信息：Result 取 Err，其他类型视为成功（由 command 宏按自动引用选择实现）
+pub trait ResultError {
+    fn audit_error(&self) -> Option<String>;
+}
+
+impl<T, E: std::fmt::Display> ResultError for Result<T, E> {
+    fn audit_error(&self) -> Option<String> {
+        self.as_ref().err().map(|e| e.to_string())
+    }
+}
+
+pub trait NoError {
+    fn audit_error(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<T> NoError for &T {}
+
+// 最近 last_n 条记录（缺省为全部）
+pub fn report(audit: &Mutex<CommandAudit>, last_n: Option<usize>) -> Result<CommandAuditReport, String> {
+    let mut audit = audit.lock().map_err(|e| format!("获取命令审计失败: {}", e))?;
+    let skip = last_n.map_or(0, |n| audit.entries.len().saturating_sub(n));
+    Ok(CommandAuditReport {
+        entries: audit.entries.iter().skip(skip).cloned().collect(),
+        slow_threshold_ms: audit.slow_threshold_ms,
+        slow_counts: audit.slow_counts.clone(),
+        sampling: audit.sampling().clone(),
+    })
+}
+
+pub fn set_slow_threshold_ms(audit: &Mutex<CommandAudit>, threshold_ms: u64) -> Result<(), String> {
+    let mut audit = audit.lock().map_err(|e| format!("获取命令审计失败: {}", e))?;
+    audit.slow_threshold_ms = threshold_ms;
+    Ok(())
+}
+
+// 设置命令的采样间隔，1为每次都记录
+pub fn set_sampling(audit: &Mutex<CommandAudit>, command: &str, every: u32) -> Result<(), String> {
+    if every == 0 {
+        return Err("采样间隔必须大于0".to_string());
+    }
+    let mut audit = audit.lock().map_err(|e| format!("获取命令审计失败: {}", e))?;
+    audit.sampling().insert(command.to_string(), every);
+    Ok(())
+}
@@ -0,0 +1,463 @@
+// 开机自启
+// 按平台注册当前用户的登录启动项，不需要管理员权限：
+// - Linux：XDG autostart 目录（~/.config/autostart）下的 desktop 文件；
+// - macOS：~/Library/LaunchAgents 下的 LaunchAgent plist（RunAtLoad）；
+// - Windows：HKCU\Software\Microsoft\Windows\CurrentVersion\Run 下的字符串值。
+// 选择最小化启动时启动项带 --minimized 参数，run() 据此不显示主窗口、只建托盘，
+// 并推迟监听器连接直到后端可达。注册状态以系统中实际的启动项为准，不另存配置；
+// 最近一次注册/取消失败的原因（如权限不足）保留在内存中供查询。
+
+use serde::Serialize;
+use std::path::PathBuf;
+
+// 最小化启动参数
+pub const MINIMIZED_ARG: &str = "--minimized";
+const SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "macos", target_os = "windows"));
+// 启动项名称
+#[cfg(any(target_os = "linux", target_os = "windows", test))]
+const ENTRY_NAME: &str = "Lumina";
+#[cfg(any(target_os = "macos", test))]
+const LAUNCH_AGENT_LABEL: &str = "io.github.drake.lumina";
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AutostartStatus {
+    pub supported: bool,
+    pub enabled: bool,
+    pub start_minimized: bool,
+    pub location: Option<String>,   // 启动项所在的文件或注册表键
+    pub launched_minimized: bool,   // 本次进程是否以最小化方式启动
+    pub last_error: Option<String>, // 最近一次注册/取消失败的原因，成功后清除
+}
+
+// 本次启动的参数与注册结果
+pub struct Autostart {
+    launched_minimized: bool,
+    awaiting_backend: bool, // 最小化启动后尚未确认后端可达，监听器暂不连接
+    last_error: Option<String>,
+}
+
+impl Autostart {
+    pub fn from_args() -> Self {
+        let launched_minimized = std::env::args().skip(1).any(|arg| arg == MINIMIZED_ARG);
+        Self {
+            launched_minimized,
+            awaiting_backend: launched_minimized,
+            last_error: None,
+        }
+    }
+
+    pub fn launched_minimized(&self) -> bool {
+        self.launched_minimized
+    }
+
+    pub fn awaiting_backend(&self) -> bool {
+        self.awaiting_backend
+    }
+
+    pub fn backend_reachable(&mut self) {
+        self.awaiting_backend = false;
+    }
+
+    // 注册或取消启动项，失败原因记下供 status 查询
+    pub fn set_enabled(&mut self, enabled: bool, start_minimized: bool) -> Result<(), String> {
+        let result = if enabled { register(start_minimized) } else { unregister() };
+        self.last_error = result.as_ref().err().cloned();
+        result
+    }
+
+    pub fn status(&self) -> AutostartStatus {
+        // 读取启动项失败时按未注册报告，没有更早的失败原因时报告读取失败的原因
+        let (entry, query_error) = match query() {
+            Ok(entry) => (entry, None),
+            Err(e) => (None, Some(e)),
+        };
+        AutostartStatus {
+            supported: SUPPORTED,
+            enabled: entry.is_some(),
+            start_minimized: entry.as_deref().is_some_and(|command| command.contains(MINIMIZED_ARG)),
+            location: entry_location(),
+            launched_minimized: self.launched_minimized,
+            last_error: self.last_error.clone().or(query_error),
+        }
+    }
+}
+
+// 启动项要执行的程序：AppImage 运行时取 AppImage 文件本身，而不是挂载点里的临时路径
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn launch_target() -> Result<PathBuf, String> {
+    #[cfg(target_os = "linux")]
+    if let Some(appimage) = std::env::var_os("APPIMAGE") {
+        return Ok(PathBuf::from(appimage));
+    }
+    std::env::current_exe().map_err(|e| format!("获取程序路径失败: {}", e))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", test))]
+fn describe_io_error(action: &str, path: &str, e: std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::PermissionDenied => format!("{}失败，权限不足: {} ({})", action, path, e),
+        _ => format!("{}失败: {} ({})", action, path, e),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", test))]
+fn write_entry(path: &std::path::Path, content: &str) -> Result<(), String> {
+    let display = path.display().to_string();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| describe_io_error("创建启动项目录", &dir.display().to_string(), e))?;
+    }
+    std::fs::write(path, content).map_err(|e| describe_io_error("写入启动项", &display, e))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", test))]
+fn remove_entry(path: &std::path::Path) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(describe_io_error("删除启动项", &path.display().to_string(), e)),
+    }
+}
+
+// 已注册时返回启动项内容，未注册时为 None
+#[cfg(any(target_os = "linux", target_os = "macos", test))]
+fn read_entry(path: &std::path::Path) -> Result<Option<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(describe_io_error("读取启动项", &path.display().to_string(), e)),
+    }
+}
+
+// ---------- Linux：XDG autostart ----------
+
+#[cfg(target_os = "linux")]
+fn entry_path() -> Result<PathBuf, String> {
+    let dir = dirs::config_dir().ok_or_else(|| "无法确定用户配置目录".to_string())?;
+    Ok(dir.join("autostart").join("lumina.desktop"))
+}
+
+#[cfg(target_os = "linux")]
+fn entry_location() -> Option<String> {
+    entry_path().ok().map(|path| path.display().to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn register(start_minimized: bool) -> Result<(), String> {
+    let target = launch_target()?;
+    write_entry(&entry_path()?, &desktop_entry(&target.to_string_lossy(), start_minimized))
+}
+
+#[cfg(target_os = "linux")]
+fn unregister() -> Result<(), String> {
+    remove_entry(&entry_path()?)
+}
+
+#[cfg(target_os = "linux")]
+fn query() -> Result<Option<String>, String> {
+    read_entry(&entry_path()?)
+}
+
+// 生成 desktop 文件内容（Desktop Entry Specification 1.5）
+#[cfg(any(target_os = "linux", test))]
+pub fn desktop_entry(program: &str, start_minimized: bool) -> String {
+    let mut exec = desktop_exec_arg(program);
+    if start_minimized {
+        exec.push(' ');
+        exec.push_str(MINIMIZED_ARG);
+    }
+    format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec={}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+        ENTRY_NAME, exec
+    )
+}
+
+// Exec 字段的参数：含空格或保留字符时加双引号，引号内的 " ` $ \ 用反斜杠转义；
+// desktop 文件的字符串值本身还会做一次反斜杠转义，因此反斜杠写成两个
+#[cfg(any(target_os = "linux", test))]
+fn desktop_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')', '`', '%'];
+    if !arg.is_empty() && !arg.contains(RESERVED) {
+        return arg.to_string();
+    }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' => {
+                quoted.push_str("\\\\");
+                quoted.push(c);
+            }
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// ---------- macOS：LaunchAgent ----------
+
+#[cfg(target_os = "macos")]
+fn entry_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or_else(|| "无法确定用户主目录".to_string())?;
+    Ok(home.join("Library").join("LaunchAgents").join(format!("{}.plist", LAUNCH_AGENT_LABEL)))
+}
+
+#[cfg(target_os = "macos")]
+fn entry_location() -> Option<String> {
+    entry_path().ok().map(|path| path.display().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn register(start_minimized: bool) -> Result<(), String> {
+    let target = launch_target()?;
+    write_entry(&entry_path()?, &launch_agent_plist(&target.to_string_lossy(), start_minimized))
+}
+
+#[cfg(target_os = "macos")]
+fn unregister() -> Result<(), String> {
+    remove_entry(&entry_path()?)
+}
+
+#[cfg(target_os = "macos")]
+fn query() -> Result<Option<String>, String> {
+    read_entry(&entry_path()?)
+}
+
+// 生成 LaunchAgent plist：登录时运行一次，不保活
+#[cfg(any(target_os = "macos", test))]
+pub fn launch_agent_plist(program: &str, start_minimized: bool) -> String {
+    let mut arguments = format!("        <string>{}</string>\n", xml_escape(program));
+    if start_minimized {
+        arguments.push_str(&format!("        <string>{}</string>\n", MINIMIZED_ARG));
+    }
+    format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+            "<plist version=\"1.0\">\n",
+            "<dict>\n",
+            "    <key>Label</key>\n",
+            "    <string>{}</string>\n",
+            "    <key>ProgramArguments</key>\n",
+            "    <array>\n",
+            "{}",
+            "    </array>\n",
+            "    <key>RunAtLoad</key>\n",
+            "    <true/>\n",
+            "    <key>KeepAlive</key>\n",
+            "    <false/>\n",
+            "</dict>\n",
+            "</plist>\n"
+        ),
+        LAUNCH_AGENT_LABEL, arguments
+    )
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// ---------- Windows：注册表 Run 键 ----------
+
+#[cfg(target_os = "windows")]
+fn entry_location() -> Option<String> {
+    Some(format!("{}\\{}", RUN_KEY, ENTRY_NAME))
+}
+
+#[cfg(target_os = "windows")]
+fn register(start_minimized: bool) -> Result<(), String> {
+    let target = launch_target()?;
+    let command = run_command(&target.to_string_lossy(), start_minimized);
+    run_reg(&["add", RUN_KEY, "/v", ENTRY_NAME, "/t", "REG_SZ", "/d", &command, "/f"], "写入启动项").map(|_| ())
+}
+
+#[cfg(target_os = "windows")]
+fn unregister() -> Result<(), String> {
+    if query()?.is_none() {
+        return Ok(());
+    }
+    run_reg(&["delete", RUN_KEY, "/v", ENTRY_NAME, "/f"], "删除启动项").map(|_| ())
+}
+
+// 值不存在时 reg query 以非零状态退出，视为未注册
+#[cfg(target_os = "windows")]
+fn query() -> Result<Option<String>, String> {
+    let output = std::process::Command::new("reg")
+        .args(["query", RUN_KEY, "/v", ENTRY_NAME])
+        .output()
+        .map_err(|e| format!("执行 reg 失败: {}", e))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(parse_query_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// 从 reg query 的输出中取出启动项的命令行；值所在行形如 `Lumina    REG_SZ    "C:\...\lumina.exe" --minimized`
+#[cfg(any(target_os = "windows", test))]
+fn parse_query_output(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(ENTRY_NAME))
+        .and_then(|rest| rest.trim_start().strip_prefix("REG_SZ"))
+        .map(|command| command.trim().to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn run_reg(args: &[&str], action: &str) -> Result<String, String> {
+    let output = std::process::Command::new("reg")
+        .args(args)
+        .output()
+        .map_err(|e| format!("{}失败，执行 reg 出错: {}", action, e))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    // reg 的错误信息随系统语言变化，按中英文的常见关键字识别权限问题
+    let denied = stderr.contains("Access is denied") || stderr.contains("拒绝访问");
+    if denied {
+        Err(format!("{}失败，权限不足: {} ({})", action, RUN_KEY, stderr))
+    } else {
+        Err(format!("{}失败: {} ({})", action, RUN_KEY, stderr))
+    }
+}
+
+// Run 键的命令行：程序路径加引号，避免路径中的空格被拆开
+#[cfg(any(target_os = "windows", test))]
+pub fn run_command(program: &str, start_minimized: bool) -> String {
+    if start_minimized {
+        format!("\"{}\" {}", program, MINIMIZED_ARG)
+    } else {
+        format!("\"{}\"", program)
+    }
+}
+
+// ---------- 其他平台 ----------
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn entry_location() -> Option<String> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn register(_start_minimized: bool) -> Result<(), String> {
+    Err("当前平台不支持开机自启".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn unregister() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn query() -> Result<Option<String>, String> {
+    Err("当前平台不支持开机自启".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exec 行的内容
+    fn exec_line(entry: &str) -> &str {
+        entry.lines().find_map(|line| line.strip_prefix("Exec=")).expect("缺少 Exec 行")
+    }
+
+    #[test]
+    fn desktop_entry_contains_exec_and_minimized_flag() {
+        let entry = desktop_entry("/usr/bin/lumina", false);
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("Type=Application\n"));
+        assert!(entry.contains("Name=Lumina\n"));
+        assert_eq!(exec_line(&entry), "/usr/bin/lumina");
+
+        let entry = desktop_entry("/usr/bin/lumina", true);
+        assert_eq!(exec_line(&entry), "/usr/bin/lumina --minimized");
+    }
+
+    // 含空格与保留字符的路径加引号，引号内的 " ` $ \ 转义后再按字符串值转义一次反斜杠，% 写成 %%
+    #[test]
+    fn desktop_exec_quotes_reserved_characters() {
+        let cases = [
+            ("/home/user/Lumina.AppImage", "/home/user/Lumina.AppImage"),
+            ("/home/my user/Lumina.AppImage", "\"/home/my user/Lumina.AppImage\""),
+            ("/opt/$HOME/lumina", "\"/opt/\\\\$HOME/lumina\""),
+            ("/opt/a\"b/lumina", "\"/opt/a\\\\\"b/lumina\""),
+            ("/opt/a`b/lumina", "\"/opt/a\\\\`b/lumina\""),
+            ("/opt/a\\b/lumina", "\"/opt/a\\\\\\\\b/lumina\""),
+            ("/opt/100%/lumina", "\"/opt/100%%/lumina\""),
+            ("", "\"\""),
+        ];
+        for (program, expected) in cases {
+            assert_eq!(desktop_exec_arg(program), expected, "{}", program);
+        }
+        let entry = desktop_entry("/home/my user/Lumina.AppImage", true);
+        assert_eq!(exec_line(&entry), "\"/home/my user/Lumina.AppImage\" --minimized");
+    }
+
+    #[test]
+    fn launch_agent_plist_lists_program_arguments() {
+        let plist = launch_agent_plist("/Applications/Lumina.app/Contents/MacOS/Lumina", false);
+        assert!(plist.contains("<string>io.github.drake.lumina</string>"));
+        assert!(plist.contains("<string>/Applications/Lumina.app/Contents/MacOS/Lumina</string>"));
+        assert!(plist.contains("<key>RunAtLoad</key>\n    <true/>"));
+        assert!(plist.contains("<key>KeepAlive</key>\n    <false/>"));
+        assert!(!plist.contains(MINIMIZED_ARG));
+
+        let plist = launch_agent_plist("/Applications/Lumina.app/Contents/MacOS/Lumina", true);
+        assert!(plist.contains("Lumina</string>\n        <string>--minimized</string>\n    </array>"));
+    }
+
+    #[test]
+    fn launch_agent_plist_escapes_xml() {
+        let plist = launch_agent_plist("/Users/a&b/<Lumina>'s \"app\"", false);
+        assert!(plist.contains("<string>/Users/a&amp;b/&lt;Lumina&gt;&apos;s &quot;app&quot;</string>"));
+    }
+
+    #[test]
+    fn run_command_quotes_program_path() {
+        let program = r"C:\Program Files\Lumina\lumina.exe";
+        assert_eq!(run_command(program, false), r#""C:\Program Files\Lumina\lumina.exe""#);
+        assert_eq!(run_command(program, true), r#""C:\Program Files\Lumina\lumina.exe" --minimized"#);
+    }
+
+    // reg query 的输出中取回写入的命令行；值不存在时为 None
+    #[test]
+    fn registry_query_output_round_trips_run_command() {
+        let command = run_command(r"C:\Program Files\Lumina\lumina.exe", true);
+        let stdout = format!(
+            "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Run\r\n    Lumina    REG_SZ    {}\r\n\r\n",
+            command
+        );
+        assert_eq!(parse_query_output(&stdout), Some(command));
+
+        let other = "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Run\r\n    OneDrive    REG_SZ    \"C:\\onedrive.exe\"\r\n";
+        assert_eq!(parse_query_output(other), None);
+    }
+
+    // 在临时目录中模拟启动项文件的注册、读取与取消
+    #[test]
+    fn entry_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("lumina-autostart-{}", std::process::id()));
+        let path = dir.join("autostart").join("lumina.desktop");
+        let content = desktop_entry("/usr/bin/lumina", true);
+
+        assert_eq!(read_entry(&path).unwrap(), None);
+        write_entry(&path, &content).unwrap();
+        assert_eq!(read_entry(&path).unwrap().as_deref(), Some(content.as_str()));
+        remove_entry(&path).unwrap();
+        assert_eq!(read_entry(&path).unwrap(), None);
+        // 未注册时取消也算成功
+        remove_entry(&path).unwrap();
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
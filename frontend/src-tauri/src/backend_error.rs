@@ -0,0 +1,72 @@
+// 后端错误事件
+// 连接断开、发送失败、消息无法解析等故障除了打印日志，还通过 backend-error 事件通知前端，便于界面提示。
+// code 为稳定的机器可读标识；recoverable 表示无需用户干预（会自动重连或跳过该消息），
+// 为 false 时相关功能已停止工作，需要用户处理或重启。
+
+use log::error;
+use serde::Serialize;
+use tauri::Emitter;
+
+pub const CONNECT_FAILED: &str = "connect_failed"; // 连接后端失败，按退避间隔重连
+pub const SEND_FAILED: &str = "send_failed"; // 向后端写入失败，连接已断开
+pub const PROTOCOL_MISMATCH: &str = "protocol_mismatch"; // 后端协议版本不兼容或未确认握手，不发送音频
+pub const STT_RESULT_DISCONNECTED: &str = "stt_result_disconnected"; // 识别结果通道断开
+pub const STT_RESULT_PARSE_FAILED: &str = "stt_result_parse_failed"; // 识别结果无法解析，已跳过
+pub const TTS_DISCONNECTED: &str = "tts_disconnected"; // TTS音频通道断开
+pub const TTS_READ_FAILED: &str = "tts_read_failed"; // 读取TTS音频失败
+pub const STATE_UNAVAILABLE: &str = "state_unavailable"; // 内部状态锁失效
+pub const TASK_FAILED: &str = "task_failed"; // 后台任务异常退出
+
+// 连续产生的错误在被取走之前最多保留的条数
+const MAX_PENDING_ERRORS: usize = 32;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BackendError {
+    pub code: String,
+    pub message: String,
+    pub recoverable: bool,
+}
+
+impl BackendError {
+    pub fn new(code: &str, message: impl Into<String>, recoverable: bool) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            recoverable,
+        }
+    }
+}
+
+// 暂存没有 app_handle 的地方（如 SocketManager 内部）产生的错误，由持有 app_handle 的调用方取走后发出
+pub struct PendingErrors {
+    errors: Vec<BackendError>,
+}
+
+impl PendingErrors {
+    pub fn new() -> Self {
+        Self { errors: Vec::new() }
+    }
+
+    pub fn push(&mut self, error: BackendError) {
+        if self.errors.len() >= MAX_PENDING_ERRORS {
+            self.errors.remove(0);
+        }
+        self.errors.push(error);
+    }
+
+    pub fn take(&mut self) -> Vec<BackendError> {
+        std::mem::take(&mut self.errors)
+    }
+}
+
+pub fn emit(app_handle: &tauri::AppHandle, error: &BackendError) {
+    if let Err(e) = app_handle.emit("backend-error", error) {
+        error!("[错误] 发送后端错误事件到前端失败: {}", e);
+    }
+}
+
+pub fn emit_all(app_handle: &tauri::AppHandle, errors: Vec<BackendError>) {
+    for error in errors {
+        emit(app_handle, &error);
+    }
+}
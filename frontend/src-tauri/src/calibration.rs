@@ -0,0 +1,282 @@
+// 麦克风校准
+// 校准期间的帧只统计能量与单帧语音判定，不进入状态机，也不发往后端。分两种：
+// - 底噪校准（calibrate_noise_floor）：采集一段环境音，按帧RMS的分布给出推荐的能量阈值与VAD灵敏度档位；
+//   语音帧占比过高说明环境太吵或有人在说话，校准失败。
+// - 语音检测（check_speech_detection）：用户对着麦克风说话，统计语音帧占比（整体与逐秒）、平均/峰值电平，
+//   用语音帧与非语音帧的能量估计信噪比，给出噪声评价与灵敏度建议；只返回结果，不修改设置。
+
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::audio_utils;
+use crate::VadAggressiveness;
+
+pub const MIN_CALIBRATION_MS: u64 = 500;
+pub const MAX_CALIBRATION_MS: u64 = 10_000;
+// 语音帧占比超过该值时校准失败
+const MAX_VOICE_RATIO: f32 = 0.2;
+// 推荐能量阈值为底噪P95的倍数（约+6dB）
+const THRESHOLD_MARGIN: f32 = 2.0;
+const MIN_ENERGY_THRESHOLD: f32 = 0.005;
+const MAX_ENERGY_THRESHOLD: f32 = 0.2;
+// 按底噪选择灵敏度档位与噪声评价：(底噪上限, 档位, 评价)，底噪越高越激进
+const MODE_BY_NOISE: [(f32, VadAggressiveness, &str); 3] = [
+    (0.003, VadAggressiveness::Quality, "很安静"),
+    (0.008, VadAggressiveness::LowBitrate, "较安静"),
+    (0.02, VadAggressiveness::Aggressive, "适中"),
+];
+// 语音检测：语音帧占比低于该值时认为漏检，建议降低一档灵敏度
+const MIN_EXPECTED_VOICE_RATIO: f32 = 0.5;
+// 语音检测：非语音帧少于该数量时不足以估计底噪，改用处理器跟踪的底噪
+const MIN_NOISE_FRAMES: usize = 10;
+// 信噪比低于该值时提示靠近麦克风
+const LOW_SNR_DB: f32 = 10.0;
+// 峰值达到该值视为削波
+const CLIPPING_PEAK: f32 = 0.98;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CalibrationResult {
+    pub frames: usize,
+    pub duration_ms: u64,
+    pub rms_mean: f32, // 以下均为归一化RMS（0~1）
+    pub rms_median: f32,
+    pub rms_p95: f32,
+    pub rms_max: f32,
+    pub voice_ratio: f32,         // 被判为语音的帧占比
+    pub energy_threshold: f32,    // 推荐的能量阈值（energy_fallback_threshold）
+    pub vad_mode: VadAggressiveness, // 推荐的灵敏度档位
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SpeechCheckResult {
+    pub frames: usize,
+    pub duration_ms: u64,
+    pub voice_ratio: f32,                 // 被判为语音的帧占比
+    pub voice_ratio_per_second: Vec<f32>, // 逐秒的语音帧占比（按时长计），最后一项可能不足一秒
+    pub mean_level_db: f32,               // 平均电平（dBFS）
+    pub peak_level_db: f32,               // 峰值电平（dBFS）
+    pub speech_rms: f32,                  // 语音帧平均RMS，没有语音帧时为0
+    pub noise_rms: f32,                   // 估计的底噪RMS
+    pub snr_db: Option<f32>,              // 估计信噪比，没有语音帧时为None
+    pub noise_level: String,              // 环境噪声评价：很安静 / 较安静 / 适中 / 嘈杂
+    pub clipping: bool,                   // 峰值接近满幅，输入增益过高
+    pub suggested_mode: VadAggressiveness,
+    pub summary: String, // 给用户看的结论，如“检测到80%是语音，环境噪声适中，建议使用Aggressive模式”
+    pub input_level_suggestion: Option<String>, // 此前连续多个会话输入音量偏低时的调整建议，由调用方填写
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalibrationKind {
+    NoiseFloor,
+    SpeechCheck,
+}
+
+impl CalibrationKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            CalibrationKind::NoiseFloor => "底噪校准",
+            CalibrationKind::SpeechCheck => "语音检测",
+        }
+    }
+}
+
+struct ObservedFrame {
+    rms: f32,
+    is_voice: bool,
+}
+
+pub struct Calibration {
+    pub kind: CalibrationKind,
+    target: Duration,
+    collected: Duration,
+    frames: Vec<ObservedFrame>,
+    voice_frames: usize,
+    peak: f32,
+    // 逐秒统计：已完成的各秒占比，以及当前这一秒内的语音时长与总时长
+    per_second: Vec<f32>,
+    second_voice: Duration,
+    second_total: Duration,
+}
+
+pub fn validate_duration(duration_ms: u64) -> Result<(), String> {
+    if !(MIN_CALIBRATION_MS..=MAX_CALIBRATION_MS).contains(&duration_ms) {
+        return Err(format!(
+            "校准时长必须在{}~{}ms之间: {}",
+            MIN_CALIBRATION_MS, MAX_CALIBRATION_MS, duration_ms
+        ));
+    }
+    Ok(())
+}
+
+// 按底噪查表得到灵敏度档位与噪声评价
+fn classify_noise(noise_rms: f32) -> (VadAggressiveness, &'static str) {
+    MODE_BY_NOISE
+        .iter()
+        .find(|(limit, _, _)| noise_rms < *limit)
+        .map_or((VadAggressiveness::VeryAggressive, "嘈杂"), |(_, mode, label)| (*mode, *label))
+}
+
+// 低一档（更灵敏）的档位
+fn less_aggressive(mode: VadAggressiveness) -> VadAggressiveness {
+    match mode {
+        VadAggressiveness::VeryAggressive => VadAggressiveness::Aggressive,
+        VadAggressiveness::Aggressive => VadAggressiveness::LowBitrate,
+        VadAggressiveness::LowBitrate | VadAggressiveness::Quality => VadAggressiveness::Quality,
+    }
+}
+
+impl Calibration {
+    pub fn new(kind: CalibrationKind, duration_ms: u64) -> Self {
+        Self {
+            kind,
+            target: Duration::from_millis(duration_ms),
+            collected: Duration::ZERO,
+            frames: Vec::new(),
+            voice_frames: 0,
+            peak: 0.0,
+            per_second: Vec::new(),
+            second_voice: Duration::ZERO,
+            second_total: Duration::ZERO,
+        }
+    }
+
+    // 记录一帧；采集已满时忽略
+    pub fn observe(&mut self, frame_rms: f32, frame_peak: f32, is_voice: bool, frame_duration: Duration) {
+        if self.is_complete() {
+            return;
+        }
+        self.frames.push(ObservedFrame { rms: frame_rms, is_voice });
+        if is_voice {
+            self.voice_frames += 1;
+            self.second_voice += frame_duration;
+        }
+        self.peak = self.peak.max(frame_peak);
+        self.collected += frame_duration;
+
+        self.second_total += frame_duration;
+        if self.second_total >= Duration::from_secs(1) {
+            self.per_second.push(self.second_voice.as_secs_f32() / self.second_total.as_secs_f32());
+            self.second_voice = Duration::ZERO;
+            self.second_total = Duration::ZERO;
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.collected >= self.target
+    }
+
+    pub fn collected_ms(&self) -> u64 {
+        self.collected.as_millis() as u64
+    }
+
+    // 底噪校准：计算分布与推荐值；语音帧过多时返回错误
+    pub fn finish_noise_floor(self) -> Result<CalibrationResult, String> {
+        if self.frames.is_empty() {
+            return Err("校准期间没有收到音频帧".to_string());
+        }
+        let frames = self.frames.len();
+        let voice_ratio = self.voice_frames as f32 / frames as f32;
+        if voice_ratio > MAX_VOICE_RATIO {
+            return Err(format!(
+                "环境太吵或检测到说话（{:.0}%的帧被判为语音），请保持安静后重试",
+                voice_ratio * 100.0
+            ));
+        }
+
+        let mut sorted: Vec<f32> = self.frames.iter().map(|frame| frame.rms).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f32| sorted[((frames - 1) as f32 * p).round() as usize];
+        let rms_median = percentile(0.5);
+        let rms_p95 = percentile(0.95);
+        let (vad_mode, _) = classify_noise(rms_median);
+
+        Ok(CalibrationResult {
+            frames,
+            duration_ms: self.collected.as_millis() as u64,
+            rms_mean: sorted.iter().sum::<f32>() / frames as f32,
+            rms_median,
+            rms_p95,
+            rms_max: sorted[frames - 1],
+            voice_ratio,
+            energy_threshold: (rms_p95 * THRESHOLD_MARGIN).clamp(MIN_ENERGY_THRESHOLD, MAX_ENERGY_THRESHOLD),
+            vad_mode,
+        })
+    }
+
+    // 语音检测：统计占比、电平与信噪比并给出建议。
+    // 底噪取非语音帧RMS的中位数；非语音帧太少（用户一直在说话）时用 fallback_noise_floor，即处理器此前跟踪的底噪
+    pub fn finish_speech_check(self, fallback_noise_floor: Option<f32>) -> Result<SpeechCheckResult, String> {
+        if self.frames.is_empty() {
+            return Err("检测期间没有收到音频帧".to_string());
+        }
+        let frames = self.frames.len();
+        let voice_ratio = self.voice_frames as f32 / frames as f32;
+        let mut voice_ratio_per_second = self.per_second;
+        if !self.second_total.is_zero() {
+            voice_ratio_per_second.push(self.second_voice.as_secs_f32() / self.second_total.as_secs_f32());
+        }
+
+        let rms_mean = self.frames.iter().map(|frame| frame.rms).sum::<f32>() / frames as f32;
+        let speech_rms = if self.voice_frames > 0 {
+            self.frames.iter().filter(|frame| frame.is_voice).map(|frame| frame.rms).sum::<f32>() / self.voice_frames as f32
+        } else {
+            0.0
+        };
+        let mut noise: Vec<f32> = self.frames.iter().filter(|frame| !frame.is_voice).map(|frame| frame.rms).collect();
+        let noise_rms = if noise.len() >= MIN_NOISE_FRAMES {
+            noise.sort_by(|a, b| a.total_cmp(b));
+            noise[noise.len() / 2]
+        } else {
+            match fallback_noise_floor {
+                Some(floor) => floor,
+                // 没有可用的底噪估计时退而取全部帧中最安静的一帧
+                None => self.frames.iter().map(|frame| frame.rms).fold(f32::MAX, f32::min),
+            }
+        };
+        let snr_db = (self.voice_frames > 0).then(|| audio_utils::to_dbfs(speech_rms) - audio_utils::to_dbfs(noise_rms));
+        let clipping = self.peak >= CLIPPING_PEAK;
+
+        let (noise_mode, noise_level) = classify_noise(noise_rms);
+        // 说了话却只有一小半被判为语音，说明当前环境下该档位过严，降一档
+        let suggested_mode = if voice_ratio < MIN_EXPECTED_VOICE_RATIO {
+            less_aggressive(noise_mode)
+        } else {
+            noise_mode
+        };
+
+        let mut summary = format!(
+            "检测到{:.0}%是语音，环境噪声{}，建议使用{:?}模式",
+            voice_ratio * 100.0,
+            noise_level,
+            suggested_mode
+        );
+        if self.voice_frames == 0 {
+            summary.push_str("；没有检测到语音，请确认麦克风选择正确并对着麦克风说话");
+        } else if voice_ratio < MIN_EXPECTED_VOICE_RATIO {
+            summary.push_str("；语音检出偏少，请说话时靠近麦克风");
+        }
+        if snr_db.is_some_and(|snr| snr < LOW_SNR_DB) {
+            summary.push_str("；信噪比偏低，建议靠近麦克风或降低环境噪声");
+        }
+        if clipping {
+            summary.push_str("；输入出现削波，建议调低麦克风增益");
+        }
+
+        Ok(SpeechCheckResult {
+            frames,
+            duration_ms: self.collected.as_millis() as u64,
+            voice_ratio,
+            voice_ratio_per_second,
+            mean_level_db: audio_utils::to_dbfs(rms_mean),
+            peak_level_db: audio_utils::to_dbfs(self.peak),
+            speech_rms,
+            noise_rms,
+            snr_db,
+            noise_level: noise_level.to_string(),
+            clipping,
+            suggested_mode,
+            summary,
+            input_level_suggestion: None,
+        })
+    }
+}
@@ -0,0 +1,152 @@
+// 前端采集时钟校正
+// 前端采集回调拿到帧的时刻与 Rust 收到 invoke 的时刻之间有不固定的 IPC 延迟。
+// 前端随帧带上 performance.now() 基准的采集时刻，这里用最近若干帧的
+// (本地到达时刻 - 前端采集时刻) 的滑动中位数估计两个时钟的偏移，把帧时间换算到本地单调时钟上，
+// 个别被 IPC 拖慢的帧只影响一个样本，不会拉偏估计。
+
+use log::warn;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// 参与中位数的最近样本数
+const OFFSET_WINDOW: usize = 101;
+
+pub struct CaptureClock {
+    origin: Instant,           // 本地时间轴原点
+    offsets_ms: VecDeque<f64>, // 最近的偏移样本
+    last_capture_ms: Option<f64>,
+}
+
+impl CaptureClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            offsets_ms: VecDeque::new(),
+            last_capture_ms: None,
+        }
+    }
+
+    // 记录一帧的前端采集时刻，返回该帧在本地单调时钟上的采集时刻
+    pub fn observe(&mut self, capture_ts_ms: f64, arrival: Instant) -> Instant {
+        if !capture_ts_ms.is_finite() {
+            return arrival;
+        }
+
+        // 前端时钟回退说明页面重新加载，旧的偏移不再适用
+        if matches!(self.last_capture_ms, Some(last) if capture_ts_ms < last) {
+            warn!("[警告] 前端采集时钟回退，重新估计时钟偏移");
+            self.offsets_ms.clear();
+        }
+        self.last_capture_ms = Some(capture_ts_ms);
+
+        let arrival_ms = arrival.saturating_duration_since(self.origin).as_secs_f64() * 1000.0;
+        if self.offsets_ms.len() >= OFFSET_WINDOW {
+            self.offsets_ms.pop_front();
+        }
+        self.offsets_ms.push_back(arrival_ms - capture_ts_ms);
+
+        let mut sorted: Vec<f64> = self.offsets_ms.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let offset_ms = sorted[sorted.len() / 2];
+
+        // 采集时刻不会晚于到达时刻
+        let local_ms = (capture_ts_ms + offset_ms).max(0.0);
+        (self.origin + Duration::from_secs_f64(local_ms / 1000.0)).min(arrival)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 固定种子的线性同余发生器，产生 [0, 1) 的抖动，结果可复现
+    struct Jitter(u64);
+
+    impl Jitter {
+        fn next(&mut self) -> f64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    fn at(origin: Instant, ms: f64) -> Instant {
+        origin + Duration::from_secs_f64(ms / 1000.0)
+    }
+
+    fn error_ms(estimate: Instant, truth: Instant) -> f64 {
+        if estimate > truth {
+            (estimate - truth).as_secs_f64() * 1000.0
+        } else {
+            (truth - estimate).as_secs_f64() * 1000.0
+        }
+    }
+
+    // 前端时钟比本地时间轴快 4000ms；每帧 20ms，IPC 延迟为 3ms 加上 0~4ms 的抖动，
+    // 约三成的帧被额外拖慢 80~200ms。估计值收敛后误差不超过抖动幅度，离群帧不拉偏估计
+    #[test]
+    fn offset_converges_and_rejects_outliers() {
+        let mut clock = CaptureClock::new();
+        let origin = clock.origin;
+        let mut jitter = Jitter(42);
+
+        let mut worst_after_warmup: f64 = 0.0;
+        let mut outliers = 0;
+        for i in 0..400 {
+            let local_ms = 1000.0 + 20.0 * i as f64;
+            let capture_ts_ms = local_ms + 4000.0;
+            let mut delay_ms = 3.0 + 4.0 * jitter.next();
+            if jitter.next() < 0.3 {
+                delay_ms += 80.0 + 120.0 * jitter.next();
+                outliers += 1;
+            }
+
+            let estimate = clock.observe(capture_ts_ms, at(origin, local_ms + delay_ms));
+            if i >= 20 {
+                worst_after_warmup = worst_after_warmup.max(error_ms(estimate, at(origin, local_ms)));
+            }
+        }
+        assert!(outliers > 80, "离群帧太少，测试没有覆盖到: {}", outliers);
+        assert!(worst_after_warmup <= 7.0, "收敛后的最大误差 {}ms", worst_after_warmup);
+    }
+
+    // 估计值不会晚于帧的到达时刻，即使该帧比中位数到得更早
+    #[test]
+    fn estimate_never_exceeds_arrival() {
+        let mut clock = CaptureClock::new();
+        let origin = clock.origin;
+        for i in 0..20 {
+            let local_ms = 500.0 + 20.0 * i as f64;
+            clock.observe(local_ms, at(origin, local_ms + 10.0));
+        }
+        // 这一帧几乎没有 IPC 延迟
+        let arrival = at(origin, 1000.5);
+        assert!(clock.observe(1000.0, arrival) <= arrival);
+    }
+
+    // 页面重新加载后前端时钟从头计，偏移重新估计，不受旧样本影响
+    #[test]
+    fn clock_regression_restarts_estimation() {
+        let mut clock = CaptureClock::new();
+        let origin = clock.origin;
+        for i in 0..50 {
+            let local_ms = 1000.0 + 20.0 * i as f64;
+            clock.observe(local_ms + 90_000.0, at(origin, local_ms + 5.0));
+        }
+
+        // 重新加载后前端时钟从 0 开始，此时本地时间轴在 3000ms
+        for i in 0..5 {
+            let local_ms = 3000.0 + 20.0 * i as f64;
+            let estimate = clock.observe(20.0 * i as f64, at(origin, local_ms + 5.0));
+            assert!(error_ms(estimate, at(origin, local_ms)) <= 5.0 + 1e-6, "第{}帧", i);
+        }
+    }
+
+    #[test]
+    fn non_finite_capture_time_uses_arrival() {
+        let mut clock = CaptureClock::new();
+        let arrival = at(clock.origin, 100.0);
+        assert_eq!(clock.observe(f64::NAN, arrival), arrival);
+        assert_eq!(clock.observe(f64::INFINITY, arrival), arrival);
+        assert!(clock.offsets_ms.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+// 发往后端的音频编码
+// 缺省为 PCM（按 sample_format 发送原始样本），适合本地 socket；后端在远程主机、链路较慢时可以切到 Opus 压缩。
+// Opus 只接受 2.5/5/10/20/40/60ms 的帧，而送来的段长度由 VAD 帧长决定（可能是30ms），
+// 因此编码器按20ms重新切帧，不足一帧的样本留到下一段，输出比输入最多滞后20ms。
+// Opus 支持 8/12/16/24/48kHz，管线采样率为 32kHz 时无法使用。
+// 需要启用 codec-opus feature（opus crate，链接 libopus），未启用时无法切换到 Opus。
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioCodec {
+    Pcm,
+    Opus,
+}
+
+impl AudioCodec {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pcm" => Some(AudioCodec::Pcm),
+            "opus" => Some(AudioCodec::Opus),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AudioCodec::Pcm => "pcm",
+            AudioCodec::Opus => "opus",
+        }
+    }
+
+    // 数据包中标识编码的字节
+    pub fn wire_id(self) -> u8 {
+        match self {
+            AudioCodec::Pcm => 0x00,
+            AudioCodec::Opus => 0x01,
+        }
+    }
+}
+
+// 每个 Opus 包的时长
+#[cfg(feature = "codec-opus")]
+const OPUS_FRAME_MS: u32 = 20;
+// 单个 Opus 包的最大字节数（libopus 推荐的上限）
+#[cfg(feature = "codec-opus")]
+const MAX_OPUS_PACKET_BYTES: usize = 4000;
+
+#[cfg(feature = "codec-opus")]
+pub struct OpusEncoder {
+    encoder: opus::Encoder,
+    frame_samples: usize,
+    pending: Vec<i16>, // 尚未凑满一帧的样本
+}
+
+#[cfg(feature = "codec-opus")]
+impl OpusEncoder {
+    pub fn new(sample_rate: u32) -> Result<Self, String> {
+        let encoder = opus::Encoder::new(sample_rate, opus::Channels::Mono, opus::Application::Voip)
+            .map_err(|e| format!("创建Opus编码器失败（{}Hz）: {}", sample_rate, e))?;
+        let frame_samples = (sample_rate * OPUS_FRAME_MS / 1000) as usize;
+        Ok(Self {
+            encoder,
+            frame_samples,
+            pending: Vec::with_capacity(frame_samples * 2),
+        })
+    }
+
+    // 编码一段样本，返回凑满的若干个包，每个包为 (样本数, 编码后的字节)
+    pub fn encode(&mut self, samples: &[i16]) -> Result<Vec<(usize, Vec<u8>)>, String> {
+        self.pending.extend_from_slice(samples);
+        let whole = self.pending.len() / self.frame_samples * self.frame_samples;
+        let mut packets = Vec::with_capacity(whole / self.frame_samples);
+        let mut output = vec![0u8; MAX_OPUS_PACKET_BYTES];
+        for frame in self.pending[..whole].chunks_exact(self.frame_samples) {
+            let len = self
+                .encoder
+                .encode(frame, &mut output)
+                .map_err(|e| format!("Opus编码失败: {}", e))?;
+            packets.push((frame.len(), output[..len].to_vec()));
+        }
+        self.pending.drain(..whole);
+        Ok(packets)
+    }
+}
+
+// 未启用 codec-opus feature 时的占位：无法构造，切换到 Opus 的命令返回 FeatureDisabled
+#[cfg(not(feature = "codec-opus"))]
+pub struct OpusEncoder {
+    _private: (),
+}
+
+#[cfg(not(feature = "codec-opus"))]
+impl OpusEncoder {
+    pub fn new(_sample_rate: u32) -> Result<Self, String> {
+        Err(crate::features::disabled_error(crate::features::Feature::CodecOpus))
+    }
+
+    pub fn encode(&mut self, _samples: &[i16]) -> Result<Vec<(usize, Vec<u8>)>, String> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(all(test, feature = "codec-opus"))]
+mod tests {
+    use super::*;
+    use crate::audio_utils::frame_rms;
+
+    // 440Hz 正弦按30ms一段送入编码器，解码后稳态 RMS 与原信号相近，样本数与送出的整帧一致
+    #[test]
+    fn sine_round_trip_preserves_rms() {
+        let sample_rate = 16_000u32;
+        let samples: Vec<i16> = (0..sample_rate as usize)
+            .map(|i| ((2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin() * 8_000.0) as i16)
+            .collect();
+
+        let mut encoder = OpusEncoder::new(sample_rate).unwrap();
+        let mut decoder = opus::Decoder::new(sample_rate, opus::Channels::Mono).unwrap();
+        let mut decoded = Vec::new();
+        for segment in samples.chunks(480) {
+            for (count, packet) in encoder.encode(segment).unwrap() {
+                let mut output = vec![0i16; count];
+                let len = decoder.decode(&packet, &mut output, false).unwrap();
+                assert_eq!(len, count);
+                decoded.extend_from_slice(&output[..len]);
+            }
+        }
+        let frame_samples = (sample_rate * OPUS_FRAME_MS / 1000) as usize;
+        assert_eq!(decoded.len(), samples.len() / frame_samples * frame_samples);
+
+        // 跳过编码器起始的预读延迟
+        let skip = frame_samples * 5;
+        let ratio = frame_rms(&decoded[skip..]) / frame_rms(&samples[skip..decoded.len()]);
+        assert!((0.8..1.25).contains(&ratio), "RMS比值 {}", ratio);
+    }
+}
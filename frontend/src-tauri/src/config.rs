@@ -0,0 +1,154 @@
+// 磁盘配置
+// 以 JSON 形式保存在应用配置目录下的 config.json，所有字段均可缺省，缺省即保持当前值/默认值。
+// 启动时读取并应用；文件缺失时使用默认配置，无法解析时改名为 config.json.corrupt 留存后使用默认配置。
+// set_config 以 JSON merge patch 的方式做部分更新：对象逐字段合并，null 表示删除该字段（回到默认值），其余值直接替换。
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use crate::codec::AudioCodec;
+use crate::CaptureMode;
+use crate::endpoints::BackendEndpoints;
+use crate::focus::FocusPolicy;
+use crate::params::{InteractionMode, TimingProfile};
+use crate::pipeline::StageConfig;
+use crate::telemetry::TelemetrySettings;
+use crate::vad_backend::{SileroSettings, VadBackendKind};
+use crate::tts_flow::FlowSettings;
+use crate::tts_gap_fill::GapFillSettings;
+use crate::tts_queue::InterruptPolicy;
+use crate::{SampleFormat, VadAggressiveness};
+
+const CONFIG_FILE_NAME: &str = "config.json";
+const CORRUPT_EXTENSION: &str = "json.corrupt";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LuminaConfig {
+    pub parameters: BTreeMap<String, f64>, // 用户配置层参数，键为参数名（见 params::ParamKey）
+    pub timing_profile: Option<TimingProfile>,
+    pub interaction_mode: Option<InteractionMode>, // 语音结束阈值预设，缺省时不使用预设
+    pub sample_format: Option<SampleFormat>,
+    pub audio_codec: Option<AudioCodec>, // 发往后端的音频编码，缺省为PCM
+    pub pre_context_fade_ms: Option<u64>,
+    pub tts_interrupt_policy: Option<InterruptPolicy>,
+    pub tts_gap_fill: Option<GapFillSettings>,
+    pub tts_flow_control: Option<FlowSettings>, // TTS接收速率保护，缺省为4倍实时速率、60秒缓冲
+    pub focus_policy: Option<FocusPolicy>,
+    pub vad_mode: Option<VadAggressiveness>,
+    pub vad_voting: Option<bool>, // webrtc_vad与能量检测双重判定，缺省为关闭
+    pub vad_high_pass: Option<bool>, // VAD判定前的80Hz高通滤波（同时作用于发往后端的音频），缺省为开启
+    pub denoise: Option<bool>,       // VAD判定前的RNNoise降噪（需要denoise feature），缺省为关闭
+    pub audio_level_interval_ms: Option<u64>, // audio-level 电平事件间隔，0为关闭，缺省为100ms
+    pub vad_backend: Option<VadBackendKind>, // 单帧语音判定后端，缺省为webrtc
+    pub silero: Option<SileroSettings>,
+    pub backend_endpoints: Option<BackendEndpoints>, // 缺省时使用每用户socket目录
+    pub legacy_socket_fallback: Option<bool>,         // 默认端点连不上时是否尝试旧版/tmp路径
+    pub finalize_on_silence: Option<bool>,            // 因静音结束说话时请求最终识别，缺省为开启
+    pub legacy_state_events: Option<bool>,            // 继续发送只含新状态名的 vad-state-changed 事件，缺省为开启
+    pub max_utterance_seconds: Option<u64>,           // 单段发言的最长时长，0为不限制，缺省为60秒
+    pub persistent_send_queue: Option<bool>,          // 音频段发送前先落盘，崩溃后补发未送达的段，缺省为关闭
+    pub local_only_mode: Option<bool>,                // 仅本地VAD，不连接后端、音频不出设备，缺省为关闭
+    pub heartbeat_interval_secs: Option<u64>,         // 向后端发送心跳的间隔，0为关闭，缺省为15秒
+    pub capture_mode: Option<CaptureMode>,            // vad / push_to_talk，缺省为vad
+    pub stt_partial_throttle_ms: Option<u64>,         // stt-result 中间结果节流间隔，0为不节流，缺省为100ms
+    pub state_mirror_path: Option<String>,            // 本地状态镜像文件路径，缺省为关闭
+    pub telemetry: Option<TelemetrySettings>,         // 匿名遥测的开关、端点与安装 id，缺省为关闭
+    pub sample_rate: Option<u32>,                     // 管线采样率，缺省为16kHz
+    pub sample_rate_auto_correct: Option<bool>,       // 实测输入采样率与声明不符时按实测值重采样，缺省为关闭
+    pub audio_pipeline: Option<Vec<StageConfig>>,     // 发送前的处理环节及顺序，如 [{"stage": "dc_removal"}, {"stage": "agc"}]
+    pub vad_input_filters: Option<Vec<StageConfig>>,  // VAD判定前的滤波环节（high_pass / low_pass / biquad），缺省为不滤波
+}
+
+pub fn config_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("获取应用配置目录失败: {}", e))?;
+    Ok(dir.join(CONFIG_FILE_NAME))
+}
+
+// 从磁盘读取配置；文件不存在时返回默认配置
+pub fn load_from_disk(path: &Path) -> Result<LuminaConfig, String> {
+    if !path.exists() {
+        return Ok(LuminaConfig::default());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取配置文件 {} 失败: {}", path.display(), e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("解析配置文件 {} 失败: {}", path.display(), e))
+}
+
+// 启动时读取配置：读取或解析失败时不中断启动，损坏的文件移到一旁，避免之后的写回覆盖掉它
+pub fn load_or_default(path: &Path) -> LuminaConfig {
+    match load_from_disk(path) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("[警告] {}，使用默认配置", e);
+            if path.exists() {
+                let corrupt_path = path.with_extension(CORRUPT_EXTENSION);
+                match std::fs::rename(path, &corrupt_path) {
+                    Ok(()) => info!("[信息] 损坏的配置文件已移至 {}", corrupt_path.display()),
+                    Err(e) => error!("[错误] 移走损坏的配置文件 {} 失败: {}", path.display(), e),
+                }
+            }
+            LuminaConfig::default()
+        }
+    }
+}
+
+// 把部分更新合并进配置；顶层出现未知字段时报错，避免拼错的字段被静默忽略
+pub fn merge_patch(config: &LuminaConfig, patch: serde_json::Value) -> Result<LuminaConfig, String> {
+    let patch = match patch {
+        serde_json::Value::Object(fields) => fields,
+        other => return Err(format!("配置更新必须是JSON对象: {}", other)),
+    };
+    let mut merged = serde_json::to_value(config).map_err(|e| format!("序列化配置失败: {}", e))?;
+    if let serde_json::Value::Object(known) = &merged {
+        if let Some(unknown) = patch.keys().find(|key| !known.contains_key(*key)) {
+            return Err(format!("未知的配置字段: {}", unknown));
+        }
+    }
+    apply_merge_patch(&mut merged, serde_json::Value::Object(patch));
+    serde_path_to_error::deserialize(merged).map_err(|e| format!("配置字段 {} 无效: {}", e.path(), e.inner()))
+}
+
+// RFC 7386 JSON merge patch
+fn apply_merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    let patch = match patch {
+        serde_json::Value::Object(fields) => fields,
+        other => {
+            *target = other;
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let serde_json::Value::Object(fields) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                fields.remove(&key);
+            } else {
+                apply_merge_patch(fields.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+    }
+}
+
+// 写回配置：先写临时文件再 rename，避免中途退出留下半个文件
+pub fn save_to_disk(path: &Path, config: &LuminaConfig) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("创建配置目录 {} 失败: {}", dir.display(), e))?;
+    }
+
+    let content = serde_json::to_string_pretty(config).map_err(|e| format!("序列化配置失败: {}", e))?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)
+        .map_err(|e| format!("写入临时配置文件 {} 失败: {}", tmp_path.display(), e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("替换配置文件 {} 失败: {}", path.display(), e))
+}
@@ -0,0 +1,253 @@
+// 后端控制动作的负载解析与校验
+// handle_backend_control 的 data 是 JSON 值；为兼容旧调用方，字符串形式的 data 先尝试按 JSON 解析，
+// 解析不了时按 JSON 字符串处理（如 set_interaction_mode 的 "command"）。
+// 每个动作用自己的负载结构体反序列化，校验失败时错误中带出字段路径；ACTIONS 列出支持的动作与负载格式，
+// 由 get_control_actions 返回给后端与前端对照。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub enum ControlAction {
+    SetVadHint(VadHintPayload),
+    TtsCaption(CaptionPayload),
+    WakeWordDetected,
+    SetInteractionMode(String),
+    ClearVadHints,
+    ResetToInitial,
+    EndSession,
+    Interrupt,
+}
+
+// set_vad_hint：参数名到取值的映射，null 撤销该参数的hint；ttl_ms 为这批hint的生效时长
+#[derive(Deserialize, Debug)]
+pub struct VadHintPayload {
+    #[serde(default)]
+    pub ttl_ms: Option<f64>,
+    #[serde(flatten)]
+    pub params: BTreeMap<String, Option<f64>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CaptionPayload {
+    pub text: String,
+    #[serde(default)]
+    pub utterance_id: Option<u64>,
+}
+
+// set_interaction_mode 接受模式名字符串，或 {"mode": "..."}
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum InteractionModePayload {
+    Name(String),
+    Object { mode: String },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PayloadField {
+    pub name: &'static str,
+    pub kind: &'static str, // JSON 类型，如 string / number / number | null
+    pub required: bool,
+    pub description: &'static str,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ControlActionInfo {
+    pub action: &'static str,
+    pub description: &'static str,
+    pub payload: &'static [PayloadField], // 为空表示不需要负载，传入的 data 被忽略
+}
+
+pub const ACTIONS: &[ControlActionInfo] = &[
+    ControlActionInfo {
+        action: "set_vad_hint",
+        description: "临时调整VAD参数（后端hint层）",
+        payload: &[
+            PayloadField {
+                name: "<参数名>",
+                kind: "number | null",
+                required: false,
+                description: "参数名见 get_effective_parameters，如 speech_end_silence_ms；null 撤销该参数的hint",
+            },
+            PayloadField {
+                name: "ttl_ms",
+                kind: "number",
+                required: false,
+                description: "hint的生效时长，缺省为一直生效",
+            },
+        ],
+    },
+    ControlActionInfo {
+        action: "clear_vad_hints",
+        description: "撤销所有后端hint",
+        payload: &[],
+    },
+    ControlActionInfo {
+        action: "set_interaction_mode",
+        description: "切换交互模式预设，data 也可以直接是模式名字符串",
+        payload: &[PayloadField {
+            name: "mode",
+            kind: "string",
+            required: true,
+            description: "command / conversation / dictation / none",
+        }],
+    },
+    ControlActionInfo {
+        action: "tts_caption",
+        description: "为TTS utterance关联字幕",
+        payload: &[
+            PayloadField {
+                name: "text",
+                kind: "string",
+                required: true,
+                description: "字幕文本",
+            },
+            PayloadField {
+                name: "utterance_id",
+                kind: "number",
+                required: false,
+                description: "关联的utterance，缺省时关联到正在播放或下一条utterance",
+            },
+        ],
+    },
+    ControlActionInfo {
+        action: "wake_word_detected",
+        description: "唤醒词命中，打开唤醒门控（需要wake-word feature）",
+        payload: &[],
+    },
+    ControlActionInfo {
+        action: "reset_to_initial",
+        description: "状态机重置到初始状态",
+        payload: &[],
+    },
+    ControlActionInfo {
+        action: "end_session",
+        description: "结束当前会话",
+        payload: &[],
+    },
+    ControlActionInfo {
+        action: "interrupt",
+        description: "打断TTS播放并重置到初始状态",
+        payload: &[],
+    },
+];
+
+// 字符串形式的 data 先尝试按 JSON 解析，兼容把 JSON 嵌在字符串里的旧后端
+fn normalize_data(data: Value) -> Value {
+    match data {
+        Value::String(text) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        other => other,
+    }
+}
+
+pub fn parse(action: &str, data: Value) -> Result<ControlAction, String> {
+    let data = normalize_data(data);
+    match action {
+        "set_vad_hint" => Ok(ControlAction::SetVadHint(deserialize(action, data)?)),
+        "tts_caption" => Ok(ControlAction::TtsCaption(deserialize(action, data)?)),
+        "set_interaction_mode" => {
+            let mode = match deserialize::<InteractionModePayload>(action, data)? {
+                InteractionModePayload::Name(mode) | InteractionModePayload::Object { mode } => mode,
+            };
+            Ok(ControlAction::SetInteractionMode(mode))
+        },
+        "wake_word_detected" => Ok(ControlAction::WakeWordDetected),
+        "clear_vad_hints" => Ok(ControlAction::ClearVadHints),
+        "reset_to_initial" => Ok(ControlAction::ResetToInitial),
+        "end_session" => Ok(ControlAction::EndSession),
+        "interrupt" => Ok(ControlAction::Interrupt),
+        _ => Err(format!("未知的控制动作: {}", action)),
+    }
+}
+
+// 反序列化负载，失败时带上出错字段的路径（根为 "."）
+fn deserialize<T: serde::de::DeserializeOwned>(action: &str, data: Value) -> Result<T, String> {
+    serde_path_to_error::deserialize(data)
+        .map_err(|e| format!("动作 {} 的负载无效，字段 {}: {}", action, e.path(), e.inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error_of(action: &str, data: Value) -> String {
+        parse(action, data).err().expect("负载应校验失败")
+    }
+
+    #[test]
+    fn set_vad_hint_accepts_params_and_ttl() {
+        match parse("set_vad_hint", json!({"speech_end_silence_ms": 800, "waiting_ms": null, "ttl_ms": 5000})) {
+            Ok(ControlAction::SetVadHint(payload)) => {
+                assert_eq!(payload.ttl_ms, Some(5000.0));
+                assert_eq!(payload.params.get("speech_end_silence_ms"), Some(&Some(800.0)));
+                assert_eq!(payload.params.get("waiting_ms"), Some(&None));
+                assert!(!payload.params.contains_key("ttl_ms"));
+            },
+            _ => panic!("set_vad_hint 应解析成功"),
+        }
+    }
+
+    // 参数值经 flatten 收集，出错字段的路径只能定位到负载根部，错误中带出动作名
+    #[test]
+    fn set_vad_hint_rejects_non_numeric_values() {
+        let error = error_of("set_vad_hint", json!({"speech_end_silence_ms": "slow"}));
+        assert!(error.contains("set_vad_hint"), "{}", error);
+        assert!(error_of("set_vad_hint", json!({"ttl_ms": "long"})).contains("set_vad_hint"));
+        assert!(error_of("set_vad_hint", json!([1, 2])).contains("set_vad_hint"));
+    }
+
+    #[test]
+    fn tts_caption_requires_text() {
+        match parse("tts_caption", json!({"text": "你好", "utterance_id": 3})) {
+            Ok(ControlAction::TtsCaption(payload)) => {
+                assert_eq!(payload.text, "你好");
+                assert_eq!(payload.utterance_id, Some(3));
+            },
+            _ => panic!("tts_caption 应解析成功"),
+        }
+        assert!(matches!(parse("tts_caption", json!({"text": "hi"})), Ok(ControlAction::TtsCaption(CaptionPayload { utterance_id: None, .. }))));
+
+        assert!(error_of("tts_caption", json!({"utterance_id": 3})).contains("text"));
+        assert!(error_of("tts_caption", json!({"text": "hi", "utterance_id": -1})).contains("utterance_id"));
+    }
+
+    // 字符串与对象两种写法，以及嵌在字符串里的 JSON
+    #[test]
+    fn set_interaction_mode_accepts_name_or_object() {
+        for data in [json!("dictation"), json!({"mode": "dictation"}), json!("{\"mode\": \"dictation\"}")] {
+            match parse("set_interaction_mode", data.clone()) {
+                Ok(ControlAction::SetInteractionMode(mode)) => assert_eq!(mode, "dictation", "{}", data),
+                _ => panic!("set_interaction_mode 应解析成功: {}", data),
+            }
+        }
+        error_of("set_interaction_mode", json!({"name": "dictation"}));
+        error_of("set_interaction_mode", json!(42));
+    }
+
+    // 不需要负载的动作忽略传入的 data
+    #[test]
+    fn payloadless_actions_ignore_data() {
+        for data in [Value::Null, json!({"unexpected": true}), json!("text")] {
+            assert!(matches!(parse("clear_vad_hints", data.clone()), Ok(ControlAction::ClearVadHints)));
+            assert!(matches!(parse("wake_word_detected", data.clone()), Ok(ControlAction::WakeWordDetected)));
+            assert!(matches!(parse("reset_to_initial", data.clone()), Ok(ControlAction::ResetToInitial)));
+            assert!(matches!(parse("end_session", data.clone()), Ok(ControlAction::EndSession)));
+            assert!(matches!(parse("interrupt", data), Ok(ControlAction::Interrupt)));
+        }
+    }
+
+    // ACTIONS 中列出的每个动作都能被解析，未列出的动作报错
+    #[test]
+    fn every_listed_action_is_parsed() {
+        for info in ACTIONS {
+            let data = match info.action {
+                "tts_caption" => json!({"text": "hi"}),
+                "set_interaction_mode" => json!("command"),
+                _ => json!({}),
+            };
+            assert!(parse(info.action, data).is_ok(), "{}", info.action);
+        }
+        assert!(error_of("launch_rockets", Value::Null).contains("launch_rockets"));
+    }
+}
@@ -0,0 +1,79 @@
+// 对话历史
+// 按发生顺序记录用户的最终识别结果和助手的回复（来自 TTS 字幕），被打断的回复会标注未播完。
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::journal::now_unix_ms;
+
+// 最多保留的历史条数
+const MAX_HISTORY_ENTRIES: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Speaker {
+    User,
+    Assistant,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub speaker: Speaker,
+    pub text: String,
+    pub utterance_id: Option<u64>,
+    #[serde(default)]
+    pub turn_id: Option<u64>,            // 用户记录所属的对话轮次
+    pub interrupted: bool,               // 助手回复未播完
+    pub remaining_text: Option<String>,  // 未播出的字幕
+    pub remaining_ms: Option<u64>,       // 未播出的时长
+    pub timestamp_ms: u64,
+}
+
+pub struct ConversationHistory {
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl ConversationHistory {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.entries.len() >= MAX_HISTORY_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn record_user(&mut self, text: &str, turn_id: u64) {
+        self.push(HistoryEntry {
+            speaker: Speaker::User,
+            text: text.to_string(),
+            utterance_id: None,
+            turn_id: Some(turn_id),
+            interrupted: false,
+            remaining_text: None,
+            remaining_ms: None,
+            timestamp_ms: now_unix_ms(),
+        });
+    }
+
+    // 删除某一轮的用户记录：一轮的转写由若干条最终识别结果拼接而成，按记录时登记的轮次 id 匹配，
+    // 其他轮次中文本相同的记录不受影响。返回删除的条数
+    pub fn remove_user_turn(&mut self, turn_id: u64) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|entry| entry.speaker != Speaker::User || entry.turn_id != Some(turn_id));
+        before - self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
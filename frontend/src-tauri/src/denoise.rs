@@ -0,0 +1,96 @@
+// RNNoise 降噪
+// 位于重采样之后、i16转换与VAD判定之前，VAD与发往后端的音频都使用降噪后的数据。
+// RNNoise 固定按 48kHz、每帧480样本（10ms）处理：管线采样率不是48kHz时先升采样、处理后再降回管线采样率；
+// 前端送来的块长与480不对齐，不足一帧的样本留在缓冲中等下一块，因此输出比输入最多滞后10ms。
+// 需要启用 denoise feature（nnnoiseless，RNNoise 的纯 Rust 实现），未启用时无法开启。
+
+use std::time::Duration;
+
+#[cfg(feature = "denoise")]
+use crate::resample::Resampler;
+
+// RNNoise 的工作采样率
+#[cfg(feature = "denoise")]
+const DENOISE_SAMPLE_RATE: u32 = 48000;
+
+#[cfg(feature = "denoise")]
+pub struct Denoiser {
+    state: Box<nnnoiseless::DenoiseState<'static>>,
+    upsampler: Option<Resampler>,   // 管线采样率 → 48kHz，管线本身为48kHz时为None
+    downsampler: Option<Resampler>, // 48kHz → 管线采样率
+    pending: Vec<f32>,              // 48kHz下尚未凑满一帧的样本
+}
+
+#[cfg(feature = "denoise")]
+impl Denoiser {
+    pub fn new(sample_rate: u32) -> Result<Self, String> {
+        let (upsampler, downsampler) = if sample_rate == DENOISE_SAMPLE_RATE {
+            (None, None)
+        } else {
+            (
+                Some(Resampler::new(sample_rate, DENOISE_SAMPLE_RATE)),
+                Some(Resampler::new(DENOISE_SAMPLE_RATE, sample_rate)),
+            )
+        };
+        Ok(Self {
+            state: nnnoiseless::DenoiseState::new(),
+            upsampler,
+            downsampler,
+            pending: Vec::with_capacity(nnnoiseless::DenoiseState::FRAME_SIZE * 2),
+        })
+    }
+
+    // 输入输出均为管线采样率下 [-1, 1] 的样本；输出长度随缓冲情况变化，可能为空
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let frame_size = nnnoiseless::DenoiseState::FRAME_SIZE;
+        match self.upsampler.as_mut() {
+            Some(upsampler) => self.pending.extend(upsampler.process(samples)),
+            None => self.pending.extend_from_slice(samples),
+        }
+
+        let whole = self.pending.len() / frame_size * frame_size;
+        let mut denoised = Vec::with_capacity(whole);
+        // RNNoise 期望 i16 量程的浮点样本
+        let mut input = vec![0.0f32; frame_size];
+        let mut output = vec![0.0f32; frame_size];
+        for frame in self.pending[..whole].chunks_exact(frame_size) {
+            for (scaled, &sample) in input.iter_mut().zip(frame) {
+                *scaled = sample * 32767.0;
+            }
+            self.state.process_frame(&mut output, &input);
+            denoised.extend(output.iter().map(|&sample| (sample / 32767.0).clamp(-1.0, 1.0)));
+        }
+        self.pending.drain(..whole);
+
+        match self.downsampler.as_mut() {
+            Some(downsampler) => downsampler.process(&denoised),
+            None => denoised,
+        }
+    }
+
+    // 缓冲中尚未输出的样本对应的时长
+    pub fn latency(&self) -> Duration {
+        Duration::from_secs_f64(self.pending.len() as f64 / DENOISE_SAMPLE_RATE as f64)
+    }
+}
+
+// 未启用 denoise feature 时的占位：无法构造，开启降噪的命令返回 FeatureDisabled
+#[cfg(not(feature = "denoise"))]
+pub struct Denoiser {
+    _private: (),
+}
+
+#[cfg(not(feature = "denoise"))]
+impl Denoiser {
+    pub fn new(_sample_rate: u32) -> Result<Self, String> {
+        Err(crate::features::disabled_error(crate::features::Feature::Denoise))
+    }
+
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        samples.to_vec()
+    }
+
+    pub fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+}
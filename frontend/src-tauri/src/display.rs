@@ -0,0 +1,343 @@
+// 显示器布局与截图选区的坐标换算
+// 两种坐标系：
+// - physical_pixels：虚拟桌面上的设备像素，各显示器的 physical 边界都在这个坐标系中；
+// - css_pixels：前端在窗口 webview 内拿到的坐标（clientX / clientY），以窗口内容区左上角为原点。
+//   乘以窗口当前的缩放比得到物理长度，再加上内容区在虚拟桌面上的物理位置即为 physical_pixels。
+// 选区跨越多台显示器时裁剪到与之重叠面积最大的那一台（一次只截一台显示器），并返回实际截取的区域。
+// 逻辑边界按各自的缩放比由物理边界折算，供前端按 CSS 像素绘制选区遮罩。
+// 本模块只做换算，不访问窗口系统，显示器信息由调用方传入。
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinateSpace {
+    CssPixels,
+    PhysicalPixels,
+}
+
+impl CoordinateSpace {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "css_pixels" => Some(CoordinateSpace::CssPixels),
+            "physical_pixels" => Some(CoordinateSpace::PhysicalPixels),
+            _ => None,
+        }
+    }
+}
+
+// 前端传入的选区，单位由 CoordinateSpace 决定
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// 物理像素矩形
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct PhysicalRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PhysicalRect {
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    // 与另一矩形的交集，不相交时为None
+    fn intersect(&self, other: &PhysicalRect) -> Option<PhysicalRect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= left as i64 || bottom <= top as i64 {
+            return None;
+        }
+        Some(PhysicalRect {
+            x: left,
+            y: top,
+            width: (right - left as i64) as u32,
+            height: (bottom - top as i64) as u32,
+        })
+    }
+
+    fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DisplayInfo {
+    pub index: usize, // 在 get_display_layout 返回列表中的序号，capture_region 的结果以此标明所截的显示器
+    pub name: Option<String>,
+    pub physical: PhysicalRect,
+    pub logical: Region, // 物理边界按该显示器缩放比折算出的逻辑边界
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+impl DisplayInfo {
+    pub fn new(index: usize, name: Option<String>, physical: PhysicalRect, scale_factor: f64, primary: bool) -> Self {
+        let scale = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+        Self {
+            index,
+            name,
+            physical,
+            logical: Region {
+                x: physical.x as f64 / scale,
+                y: physical.y as f64 / scale,
+                width: physical.width as f64 / scale,
+                height: physical.height as f64 / scale,
+            },
+            scale_factor: scale,
+            primary,
+        }
+    }
+}
+
+// 窗口内容区在虚拟桌面上的物理位置与当前缩放比，css_pixels 换算时使用
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct WindowGeometry {
+    pub inner_x: i32,
+    pub inner_y: i32,
+    pub scale_factor: f64,
+}
+
+// 换算与裁剪后的选区
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ResolvedRegion {
+    pub display_index: usize,
+    pub desktop: PhysicalRect, // 虚拟桌面上的物理像素区域
+    pub local: PhysicalRect,   // 相对所截显示器左上角的物理像素区域
+    pub clipped: bool,         // 选区超出了该显示器，已被裁剪
+}
+
+pub fn validate_region(region: &Region) -> Result<(), String> {
+    let values = [region.x, region.y, region.width, region.height];
+    if values.iter().any(|value| !value.is_finite()) {
+        return Err(format!("选区坐标必须是有限数值: {:?}", region));
+    }
+    if region.width <= 0.0 || region.height <= 0.0 {
+        return Err(format!("选区宽高必须为正数: {}x{}", region.width, region.height));
+    }
+    Ok(())
+}
+
+// 换算到虚拟桌面的物理像素；边界向外取整，保证选中的像素都被包含
+pub fn to_physical(region: &Region, space: CoordinateSpace, window: &WindowGeometry) -> Result<PhysicalRect, String> {
+    validate_region(region)?;
+    let (left, top, right, bottom) = match space {
+        CoordinateSpace::PhysicalPixels => (region.x, region.y, region.x + region.width, region.y + region.height),
+        CoordinateSpace::CssPixels => {
+            if !window.scale_factor.is_finite() || window.scale_factor <= 0.0 {
+                return Err(format!("窗口缩放比无效: {}", window.scale_factor));
+            }
+            let scale = window.scale_factor;
+            (
+                window.inner_x as f64 + region.x * scale,
+                window.inner_y as f64 + region.y * scale,
+                window.inner_x as f64 + (region.x + region.width) * scale,
+                window.inner_y as f64 + (region.y + region.height) * scale,
+            )
+        }
+    };
+    let (left, top) = (left.floor(), top.floor());
+    let (right, bottom) = (right.ceil(), bottom.ceil());
+    if left < i32::MIN as f64 || top < i32::MIN as f64 || right > i32::MAX as f64 || bottom > i32::MAX as f64 {
+        return Err(format!("选区超出坐标范围: {:?}", region));
+    }
+    Ok(PhysicalRect {
+        x: left as i32,
+        y: top as i32,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    })
+}
+
+// 选出与选区重叠面积最大的显示器并裁剪到其边界；面积相同时取靠前的显示器
+pub fn resolve_region(rect: &PhysicalRect, displays: &[DisplayInfo]) -> Result<ResolvedRegion, String> {
+    let (display, desktop) = displays
+        .iter()
+        .filter_map(|display| rect.intersect(&display.physical).map(|overlap| (display, overlap)))
+        .fold(None, |best: Option<(&DisplayInfo, PhysicalRect)>, candidate| match best {
+            Some(best) if best.1.area() >= candidate.1.area() => Some(best),
+            _ => Some(candidate),
+        })
+        .ok_or_else(|| format!("选区 {:?} 不在任何显示器上", rect))?;
+
+    Ok(ResolvedRegion {
+        display_index: display.index,
+        desktop,
+        local: PhysicalRect {
+            x: desktop.x - display.physical.x,
+            y: desktop.y - display.physical.y,
+            width: desktop.width,
+            height: desktop.height,
+        },
+        clipped: desktop != *rect,
+    })
+}
+
+// 截图的像素尺寸与显示器物理尺寸不一致时（部分平台按逻辑尺寸截图），把显示器内的物理区域按比例映射到图像像素
+pub fn scale_to_image(local: &PhysicalRect, display: &PhysicalRect, image_width: u32, image_height: u32) -> PhysicalRect {
+    let scale_x = image_width as f64 / display.width.max(1) as f64;
+    let scale_y = image_height as f64 / display.height.max(1) as f64;
+    let left = (local.x as f64 * scale_x).floor().clamp(0.0, image_width as f64);
+    let top = (local.y as f64 * scale_y).floor().clamp(0.0, image_height as f64);
+    let right = ((local.x as f64 + local.width as f64) * scale_x).ceil().clamp(left, image_width as f64);
+    let bottom = ((local.y as f64 + local.height as f64) * scale_y).ceil().clamp(top, image_height as f64);
+    PhysicalRect {
+        x: left as i32,
+        y: top as i32,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> PhysicalRect {
+        PhysicalRect { x, y, width, height }
+    }
+
+    fn region(x: f64, y: f64, width: f64, height: f64) -> Region {
+        Region { x, y, width, height }
+    }
+
+    // 混合缩放的三屏布局：
+    // 0 主屏 2560x1440 @2.0 位于原点；1 副屏 1920x1080 @1.0 在主屏右侧；2 副屏 1920x1080 @1.5 在主屏左侧并下移 200
+    fn layout() -> Vec<DisplayInfo> {
+        vec![
+            DisplayInfo::new(0, Some("primary".to_string()), rect(0, 0, 2560, 1440), 2.0, true),
+            DisplayInfo::new(1, Some("right".to_string()), rect(2560, 0, 1920, 1080), 1.0, false),
+            DisplayInfo::new(2, Some("left".to_string()), rect(-1920, 200, 1920, 1080), 1.5, false),
+        ]
+    }
+
+    #[test]
+    fn logical_bounds_follow_each_display_scale() {
+        let displays = layout();
+        assert_eq!(displays[0].logical, region(0.0, 0.0, 1280.0, 720.0));
+        assert_eq!(displays[1].logical, region(2560.0, 0.0, 1920.0, 1080.0));
+        assert_eq!(displays[2].logical, region(-1280.0, 200.0 / 1.5, 1280.0, 720.0));
+        // 无效缩放比按 1.0 处理
+        assert_eq!(DisplayInfo::new(3, None, rect(0, 0, 100, 100), 0.0, false).scale_factor, 1.0);
+    }
+
+    // css 像素乘以窗口缩放比再加上内容区位置，边界向外取整
+    #[test]
+    fn css_pixels_scale_by_window_factor() {
+        let on_primary = WindowGeometry { inner_x: 100, inner_y: 100, scale_factor: 2.0 };
+        assert_eq!(
+            to_physical(&region(10.25, 10.0, 50.0, 25.0), CoordinateSpace::CssPixels, &on_primary).unwrap(),
+            rect(120, 120, 101, 50)
+        );
+
+        let on_right = WindowGeometry { inner_x: 2600, inner_y: 40, scale_factor: 1.0 };
+        assert_eq!(
+            to_physical(&region(10.0, 20.0, 100.0, 50.0), CoordinateSpace::CssPixels, &on_right).unwrap(),
+            rect(2610, 60, 100, 50)
+        );
+
+        let on_left = WindowGeometry { inner_x: -1800, inner_y: 300, scale_factor: 1.5 };
+        assert_eq!(
+            to_physical(&region(100.0, 100.0, 200.0, 100.0), CoordinateSpace::CssPixels, &on_left).unwrap(),
+            rect(-1650, 450, 300, 150)
+        );
+    }
+
+    #[test]
+    fn physical_pixels_ignore_window_geometry() {
+        let window = WindowGeometry { inner_x: 500, inner_y: 500, scale_factor: 2.0 };
+        assert_eq!(
+            to_physical(&region(-100.5, 10.0, 50.0, 20.0), CoordinateSpace::PhysicalPixels, &window).unwrap(),
+            rect(-101, 10, 51, 20)
+        );
+    }
+
+    #[test]
+    fn invalid_regions_are_rejected() {
+        let window = WindowGeometry { inner_x: 0, inner_y: 0, scale_factor: 1.0 };
+        assert!(to_physical(&region(0.0, 0.0, 0.0, 10.0), CoordinateSpace::PhysicalPixels, &window).is_err());
+        assert!(to_physical(&region(f64::NAN, 0.0, 10.0, 10.0), CoordinateSpace::PhysicalPixels, &window).is_err());
+        assert!(to_physical(&region(0.0, 0.0, 1e12, 10.0), CoordinateSpace::PhysicalPixels, &window).is_err());
+        let broken = WindowGeometry { inner_x: 0, inner_y: 0, scale_factor: 0.0 };
+        assert!(to_physical(&region(0.0, 0.0, 10.0, 10.0), CoordinateSpace::CssPixels, &broken).is_err());
+    }
+
+    // 跨屏选区裁剪到重叠面积最大的显示器，local 相对该显示器左上角
+    #[test]
+    fn spanning_region_picks_largest_overlap() {
+        let displays = layout();
+
+        let mostly_primary = resolve_region(&rect(2400, 100, 300, 100), &displays).unwrap();
+        assert_eq!(mostly_primary.display_index, 0);
+        assert_eq!(mostly_primary.desktop, rect(2400, 100, 160, 100));
+        assert_eq!(mostly_primary.local, rect(2400, 100, 160, 100));
+        assert!(mostly_primary.clipped);
+
+        let mostly_right = resolve_region(&rect(2500, 100, 400, 100), &displays).unwrap();
+        assert_eq!(mostly_right.display_index, 1);
+        assert_eq!(mostly_right.desktop, rect(2560, 100, 340, 100));
+        assert_eq!(mostly_right.local, rect(0, 100, 340, 100));
+    }
+
+    // 负坐标上的显示器同样按其左上角换算 local
+    #[test]
+    fn negative_origin_display_maps_to_local() {
+        let resolved = resolve_region(&rect(-1000, 300, 200, 200), &layout()).unwrap();
+        assert_eq!(resolved.display_index, 2);
+        assert_eq!(resolved.local, rect(920, 100, 200, 200));
+        assert!(!resolved.clipped);
+    }
+
+    #[test]
+    fn equal_overlap_prefers_earlier_display() {
+        let resolved = resolve_region(&rect(2460, 0, 200, 100), &layout()).unwrap();
+        assert_eq!(resolved.display_index, 0);
+    }
+
+    #[test]
+    fn region_outside_all_displays_is_an_error() {
+        assert!(resolve_region(&rect(0, 5000, 100, 100), &layout()).is_err());
+        // 主屏左侧、左屏上方的空白区域
+        assert!(resolve_region(&rect(-500, 0, 100, 100), &layout()).is_err());
+    }
+
+    // 部分平台按逻辑尺寸截图：2x 显示器上图像只有物理尺寸的一半，边界向外取整
+    #[test]
+    fn scale_to_image_maps_hidpi_capture() {
+        let display = rect(0, 0, 2560, 1440);
+        let local = rect(101, 51, 201, 101);
+        assert_eq!(scale_to_image(&local, &display, 1280, 720), rect(50, 25, 101, 51));
+        assert_eq!(scale_to_image(&local, &display, 2560, 1440), local);
+        // 超出图像的部分被截掉
+        assert_eq!(scale_to_image(&rect(2500, 1400, 200, 200), &display, 1280, 720), rect(1250, 700, 30, 20));
+    }
+
+    // 从 1.5 倍屏上窗口的 css 选区一路换算到截图像素
+    #[test]
+    fn css_region_on_scaled_display_resolves_end_to_end() {
+        let displays = layout();
+        let window = WindowGeometry { inner_x: -1800, inner_y: 300, scale_factor: 1.5 };
+        let physical = to_physical(&region(0.0, 0.0, 400.0, 200.0), CoordinateSpace::CssPixels, &window).unwrap();
+        let resolved = resolve_region(&physical, &displays).unwrap();
+        assert_eq!(resolved.display_index, 2);
+        assert_eq!(resolved.local, rect(120, 100, 600, 300));
+
+        // 该平台按逻辑尺寸截得 1280x720 的图像
+        let image = scale_to_image(&resolved.local, &displays[2].physical, 1280, 720);
+        assert_eq!(image, rect(80, 66, 400, 201));
+    }
+}
@@ -0,0 +1,590 @@
+// 后端通信端点
+// Unix 下 socket 放在每用户私有目录 $XDG_RUNTIME_DIR/lumina/（回退到 /tmp/lumina-$UID/），
+// 目录权限 0700、socket 文件 0600，避免多用户机器上路径冲突或被其他用户进程连上。
+// 实际使用的路径通过环境变量导出，后端 sidecar 启动时继承同一组路径。
+// 迁移期间默认端点连不上时会再尝试旧的 /tmp/lumina_*.sock，并打印弃用警告。
+// Windows 下默认使用命名管道（\\.\pipe\lumina_*），避免本机 TCP 端口触发防火墙提示或与其他程序冲突；
+// 仍可通过 set_transport 切回本机 TCP 端口。端点以 \\.\pipe\ 开头即按命名管道连接，否则按 TCP 地址连接，
+// 两种传输上的长度前缀帧格式完全相同。
+// Windows 下本机 TCP 端口常被防火墙、代理软件或系统保留端口段干扰，连接失败时按错误类型给出可能原因；
+// diagnose 对每个端点做一次可达性探测（建立一次立即关闭的连接），供用户排查连不上后端的问题。
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(windows)]
+use std::fs::File;
+#[cfg(windows)]
+use std::io::{Read, Write};
+#[cfg(windows)]
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(windows)]
+use std::time::Duration;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+// 与后端协商用的环境变量
+pub const SOCKET_DIR_ENV: &str = "LUMINA_SOCKET_DIR";
+pub const STT_ENDPOINT_ENV: &str = "LUMINA_STT_SOCKET";
+pub const STT_RESULT_ENDPOINT_ENV: &str = "LUMINA_STT_RESULT_SOCKET";
+pub const TTS_ENDPOINT_ENV: &str = "LUMINA_TTS_SOCKET";
+
+#[cfg(unix)]
+const SOCKET_DIR_NAME: &str = "lumina";
+#[cfg(unix)]
+const STT_SOCKET_NAME: &str = "stt.sock";
+#[cfg(unix)]
+const STT_RESULT_SOCKET_NAME: &str = "stt_result.sock";
+#[cfg(unix)]
+const TTS_SOCKET_NAME: &str = "tts.sock";
+
+// 旧版后端使用的全局共享路径，仅在迁移期间作为回退
+#[cfg(unix)]
+const LEGACY_STT_SOCKET_PATH: &str = "/tmp/lumina_stt.sock";
+#[cfg(unix)]
+const LEGACY_STT_RESULT_SOCKET_PATH: &str = "/tmp/lumina_stt_result.sock";
+#[cfg(unix)]
+const LEGACY_TTS_SOCKET_PATH: &str = "/tmp/lumina_tts.sock";
+
+#[cfg(windows)]
+const PIPE_PREFIX: &str = r"\\.\pipe\";
+#[cfg(windows)]
+const DEFAULT_STT_PIPE_NAME: &str = r"\\.\pipe\lumina_stt";
+#[cfg(windows)]
+const DEFAULT_STT_RESULT_PIPE_NAME: &str = r"\\.\pipe\lumina_stt_result";
+#[cfg(windows)]
+const DEFAULT_TTS_PIPE_NAME: &str = r"\\.\pipe\lumina_tts";
+#[cfg(windows)]
+const DEFAULT_STT_TCP_ADDRESS: &str = "127.0.0.1:8765";
+#[cfg(windows)]
+const DEFAULT_STT_RESULT_TCP_ADDRESS: &str = "127.0.0.1:8766";
+#[cfg(windows)]
+const DEFAULT_TTS_TCP_ADDRESS: &str = "127.0.0.1:8767";
+
+// 三条通道的端点：Unix 下为 socket 路径，Windows 下为命名管道名或 TCP 地址
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BackendEndpoints {
+    pub stt: String,        // 音频上行
+    pub stt_result: String, // 识别结果与控制消息
+    pub tts: String,        // TTS 音频
+}
+
+// 与后端通信的传输方式（仅 Windows 可切换）
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Pipe, // 命名管道
+    Tcp,  // 本机 TCP 端口
+}
+
+impl Transport {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pipe" => Some(Transport::Pipe),
+            "tcp" => Some(Transport::Tcp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EndpointKind {
+    Stt,
+    SttResult,
+    Tts,
+}
+
+pub const ALL_ENDPOINT_KINDS: [EndpointKind; 3] = [EndpointKind::Stt, EndpointKind::SttResult, EndpointKind::Tts];
+
+impl EndpointKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EndpointKind::Stt => "stt",
+            EndpointKind::SttResult => "stt_result",
+            EndpointKind::Tts => "tts",
+        }
+    }
+}
+
+// 连接失败的可能原因；端口、防火墙与管道相关的原因只在 Windows 下出现
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(unix, allow(dead_code))]
+pub enum ConnectProblem {
+    NotListening,     // 后端未启动或未监听该端点
+    PortInUse,        // 端口被其他程序占用或处于系统保留端口段
+    Blocked,          // 连接被防火墙、安全软件或代理拦截
+    ResolveFailed,    // 地址解析失败
+    PermissionDenied, // 无权访问socket文件或命名管道
+    Busy,             // 命名管道实例全忙
+    Other,
+}
+
+impl ConnectProblem {
+    pub fn describe(self) -> &'static str {
+        match self {
+            ConnectProblem::NotListening => "后端未启动或未监听该端点",
+            ConnectProblem::PortInUse => "端口被其他程序占用或处于系统保留端口段，请更换端口",
+            ConnectProblem::Blocked => "连接被防火墙、安全软件或代理拦截，请将本机环回地址加入例外",
+            ConnectProblem::ResolveFailed => "地址解析失败，请检查端点配置",
+            ConnectProblem::PermissionDenied => "无权访问该端点，后端可能以其他用户身份运行",
+            ConnectProblem::Busy => "后端的管道实例已全部被占用",
+            ConnectProblem::Other => "未知错误",
+        }
+    }
+}
+
+// 单个端点的探测结果
+#[derive(Serialize, Clone, Debug)]
+pub struct EndpointDiagnosis {
+    pub channel: &'static str,
+    pub endpoint: String,
+    pub reachable: bool,
+    pub problem: Option<ConnectProblem>,
+    pub detail: String,
+}
+
+impl EndpointDiagnosis {
+    fn new(kind: EndpointKind, endpoint: &str, result: Result<(), (ConnectProblem, String)>) -> Self {
+        match result {
+            Ok(()) => Self {
+                channel: kind.name(),
+                endpoint: endpoint.to_string(),
+                reachable: true,
+                problem: None,
+                detail: "可以连接".to_string(),
+            },
+            Err((problem, error)) => Self {
+                channel: kind.name(),
+                endpoint: endpoint.to_string(),
+                reachable: false,
+                problem: Some(problem),
+                detail: format!("{}: {}", problem.describe(), error),
+            },
+        }
+    }
+}
+
+impl BackendEndpoints {
+    #[cfg(unix)]
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            stt: dir.join(STT_SOCKET_NAME).to_string_lossy().into_owned(),
+            stt_result: dir.join(STT_RESULT_SOCKET_NAME).to_string_lossy().into_owned(),
+            tts: dir.join(TTS_SOCKET_NAME).to_string_lossy().into_owned(),
+        }
+    }
+
+    // 默认端点：环境变量中已有的值优先（由启动器下发），否则使用每用户目录
+    #[cfg(unix)]
+    pub fn default_for_platform() -> Self {
+        let defaults = Self::in_dir(&default_socket_dir());
+        Self {
+            stt: std::env::var(STT_ENDPOINT_ENV).unwrap_or(defaults.stt),
+            stt_result: std::env::var(STT_RESULT_ENDPOINT_ENV).unwrap_or(defaults.stt_result),
+            tts: std::env::var(TTS_ENDPOINT_ENV).unwrap_or(defaults.tts),
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn default_for_platform() -> Self {
+        let defaults = Self::default_for_transport(Transport::Pipe);
+        Self {
+            stt: std::env::var(STT_ENDPOINT_ENV).unwrap_or(defaults.stt),
+            stt_result: std::env::var(STT_RESULT_ENDPOINT_ENV).unwrap_or(defaults.stt_result),
+            tts: std::env::var(TTS_ENDPOINT_ENV).unwrap_or(defaults.tts),
+        }
+    }
+
+    // 某种传输方式的默认端点
+    #[cfg(windows)]
+    pub fn default_for_transport(transport: Transport) -> Self {
+        let (stt, stt_result, tts) = match transport {
+            Transport::Pipe => (DEFAULT_STT_PIPE_NAME, DEFAULT_STT_RESULT_PIPE_NAME, DEFAULT_TTS_PIPE_NAME),
+            Transport::Tcp => (DEFAULT_STT_TCP_ADDRESS, DEFAULT_STT_RESULT_TCP_ADDRESS, DEFAULT_TTS_TCP_ADDRESS),
+        };
+        Self {
+            stt: stt.to_string(),
+            stt_result: stt_result.to_string(),
+            tts: tts.to_string(),
+        }
+    }
+
+    // 切换传输方式时使用的端点：Unix 下固定使用 Unix socket，不支持切换
+    pub fn for_transport(transport: Transport) -> Result<Self, String> {
+        #[cfg(windows)]
+        {
+            Ok(Self::default_for_transport(transport))
+        }
+        #[cfg(unix)]
+        {
+            Err(format!("当前平台使用Unix socket与后端通信，不支持切换传输方式: {:?}", transport))
+        }
+    }
+
+    pub fn get(&self, kind: EndpointKind) -> &str {
+        match kind {
+            EndpointKind::Stt => &self.stt,
+            EndpointKind::SttResult => &self.stt_result,
+            EndpointKind::Tts => &self.tts,
+        }
+    }
+
+    // 导出到本进程环境变量，之后启动的后端 sidecar 会继承同一组端点
+    pub fn export_to_env(&self) {
+        std::env::set_var(STT_ENDPOINT_ENV, &self.stt);
+        std::env::set_var(STT_RESULT_ENDPOINT_ENV, &self.stt_result);
+        std::env::set_var(TTS_ENDPOINT_ENV, &self.tts);
+        #[cfg(unix)]
+        if let Some(dir) = Path::new(&self.stt).parent() {
+            std::env::set_var(SOCKET_DIR_ENV, dir);
+        }
+    }
+}
+
+// 旧版后端的端点
+#[cfg(unix)]
+pub fn legacy_endpoint(kind: EndpointKind) -> &'static str {
+    match kind {
+        EndpointKind::Stt => LEGACY_STT_SOCKET_PATH,
+        EndpointKind::SttResult => LEGACY_STT_RESULT_SOCKET_PATH,
+        EndpointKind::Tts => LEGACY_TTS_SOCKET_PATH,
+    }
+}
+
+// 每用户 socket 目录：$LUMINA_SOCKET_DIR > $XDG_RUNTIME_DIR/lumina > /tmp/lumina-$UID
+#[cfg(unix)]
+pub fn default_socket_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(SOCKET_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) if !runtime_dir.is_empty() => PathBuf::from(runtime_dir).join(SOCKET_DIR_NAME),
+        _ => match current_uid() {
+            Some(uid) => std::env::temp_dir().join(format!("{}-{}", SOCKET_DIR_NAME, uid)),
+            None => std::env::temp_dir().join(SOCKET_DIR_NAME),
+        },
+    }
+}
+
+// 当前进程的有效 uid，首次调用时探测并缓存
+#[cfg(unix)]
+fn current_uid() -> Option<u32> {
+    static UID: OnceLock<Option<u32>> = OnceLock::new();
+    *UID.get_or_init(probe_uid)
+}
+
+// 本进程新建的文件属主即有效 uid，这样不需要通过 FFI 调用 getuid。
+// 探测文件以 create_new 新建，属主从已打开的句柄读取：临时目录带粘滞位，其他用户既不能预先占用后被我们打开，也不能替换该文件
+#[cfg(unix)]
+fn probe_uid() -> Option<u32> {
+    let dir = std::env::temp_dir();
+    for attempt in 0..8 {
+        let path = dir.join(format!(".{}-uid-{}-{}", SOCKET_DIR_NAME, std::process::id(), attempt));
+        let file = match std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        let uid = file.metadata().map(|metadata| metadata.uid());
+        let _ = std::fs::remove_file(&path);
+        match uid {
+            Ok(uid) => return Some(uid),
+            Err(e) => warn!("[警告] 读取探测文件 {} 属主失败: {}", path.display(), e),
+        }
+    }
+    warn!("[警告] 无法在 {} 中确定当前用户的uid", dir.display());
+    None
+}
+
+// 创建 socket 目录并收紧为 0700；目录已存在但属于其他用户时拒绝使用
+#[cfg(unix)]
+pub fn prepare_socket_dir(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("创建socket目录 {} 失败: {}", dir.display(), e))?;
+
+    let metadata = std::fs::metadata(dir).map_err(|e| format!("读取socket目录 {} 失败: {}", dir.display(), e))?;
+    let uid = current_uid().ok_or_else(|| format!("无法确定当前用户，拒绝使用socket目录 {}", dir.display()))?;
+    if metadata.uid() != uid {
+        return Err(format!("socket目录 {} 属于其他用户 (uid {})", dir.display(), metadata.uid()));
+    }
+    if metadata.permissions().mode() & 0o777 != 0o700 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("设置socket目录 {} 权限失败: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+// 清理目录中没有进程监听的陈旧 socket 文件，返回被删除的路径
+#[cfg(unix)]
+pub fn cleanup_stale_sockets(dir: &Path) -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return removed,
+    };
+
+    for entry in entries.flatten() {
+        let is_socket = entry.file_type().map(|t| t.is_socket()).unwrap_or(false);
+        if !is_socket {
+            continue;
+        }
+        let path = entry.path();
+        // 只有连接被拒绝才说明没有监听者；其他错误（如权限）保持原样
+        if let Err(e) = UnixStream::connect(&path) {
+            if e.kind() == std::io::ErrorKind::ConnectionRefused && std::fs::remove_file(&path).is_ok() {
+                removed.push(path);
+            }
+        }
+    }
+    removed
+}
+
+// 后端创建的 socket 文件若权限过宽，收紧为 0600（只对本用户拥有的文件生效）
+#[cfg(unix)]
+pub fn tighten_socket_permissions(path: &Path) {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+    if Some(metadata.uid()) == current_uid() && metadata.permissions().mode() & 0o777 != 0o600 {
+        if let Err(e) = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)) {
+            warn!("[警告] 设置socket {} 权限失败: {}", path.display(), e);
+        }
+    }
+}
+
+// 连接某条通道；允许回退时主端点失败后再尝试旧路径。返回连接与实际连上的路径
+#[cfg(unix)]
+pub fn connect(
+    endpoints: &BackendEndpoints,
+    kind: EndpointKind,
+    legacy_fallback: bool,
+) -> std::io::Result<(UnixStream, String)> {
+    let primary = endpoints.get(kind);
+    let primary_error = match UnixStream::connect(primary) {
+        Ok(stream) => {
+            tighten_socket_permissions(Path::new(primary));
+            return Ok((stream, primary.to_string()));
+        }
+        Err(e) => e,
+    };
+
+    let legacy = legacy_endpoint(kind);
+    if !legacy_fallback || legacy == primary {
+        return Err(primary_error);
+    }
+    match UnixStream::connect(legacy) {
+        Ok(stream) => {
+            warn!(
+                "[警告] 已连接到旧版Socket路径 {}，该路径为全局共享路径已弃用，请升级后端以使用 {}",
+                legacy, primary
+            );
+            Ok((stream, legacy.to_string()))
+        }
+        Err(_) => Err(primary_error),
+    }
+}
+
+// 探测某条通道：socket 文件不存在或无人监听视为后端未启动
+#[cfg(unix)]
+pub fn diagnose(endpoints: &BackendEndpoints, kind: EndpointKind) -> EndpointDiagnosis {
+    let endpoint = endpoints.get(kind);
+    let result = UnixStream::connect(endpoint).map(|_| ()).map_err(|e| {
+        let problem = match e.kind() {
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused => ConnectProblem::NotListening,
+            std::io::ErrorKind::PermissionDenied => ConnectProblem::PermissionDenied,
+            _ => ConnectProblem::Other,
+        };
+        (problem, e.to_string())
+    });
+    EndpointDiagnosis::new(kind, endpoint, result)
+}
+
+// 从其他线程关闭连接，用于唤醒阻塞在读取上的监听器
+#[cfg(unix)]
+pub struct StreamCloser(UnixStream);
+
+#[cfg(unix)]
+impl StreamCloser {
+    pub fn new(stream: &UnixStream) -> Option<Self> {
+        stream.try_clone().ok().map(Self)
+    }
+
+    pub fn close(&self) {
+        let _ = self.0.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+// Windows 下的连接：命名管道以文件方式打开，读写与 TCP 一样按字节流处理
+#[cfg(windows)]
+pub enum WindowsStream {
+    Pipe(File),
+    Tcp(TcpStream),
+}
+
+#[cfg(windows)]
+impl WindowsStream {
+    // 同步打开的命名管道句柄不支持非阻塞与超时，这两项设置只对 TCP 生效
+    pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        match self {
+            WindowsStream::Pipe(_) => Ok(()),
+            WindowsStream::Tcp(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            WindowsStream::Pipe(_) => Ok(()),
+            WindowsStream::Tcp(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Read for WindowsStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            WindowsStream::Pipe(pipe) => pipe.read(buf),
+            WindowsStream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Write for WindowsStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            WindowsStream::Pipe(pipe) => pipe.write(buf),
+            WindowsStream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            WindowsStream::Pipe(pipe) => pipe.flush(),
+            WindowsStream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+// 同步打开的命名管道句柄无法从其他线程中断读取，只有 TCP 连接可以关闭；
+// 管道上的监听器在下一次读取返回后察觉关闭请求
+#[cfg(windows)]
+pub struct StreamCloser(TcpStream);
+
+#[cfg(windows)]
+impl StreamCloser {
+    pub fn new(stream: &WindowsStream) -> Option<Self> {
+        match stream {
+            WindowsStream::Pipe(_) => None,
+            WindowsStream::Tcp(stream) => stream.try_clone().ok().map(Self),
+        }
+    }
+
+    pub fn close(&self) {
+        let _ = self.0.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+#[cfg(windows)]
+pub fn is_pipe_name(endpoint: &str) -> bool {
+    endpoint.starts_with(PIPE_PREFIX)
+}
+
+// Windows 下没有需要回退的旧路径；管道实例全忙或后端未启动时返回错误，由调用方按重连间隔重试
+#[cfg(windows)]
+pub fn connect(
+    endpoints: &BackendEndpoints,
+    kind: EndpointKind,
+    _legacy_fallback: bool,
+) -> std::io::Result<(WindowsStream, String)> {
+    let address = endpoints.get(kind);
+    if is_pipe_name(address) {
+        let pipe = std::fs::OpenOptions::new().read(true).write(true).open(address)?;
+        return Ok((WindowsStream::Pipe(pipe), address.to_string()));
+    }
+
+    let addr = resolve_tcp_address(address)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let stream = TcpStream::connect_timeout(&addr, Duration::from_millis(TCP_CONNECT_TIMEOUT_MS)).map_err(|e| {
+        // 保留原错误类型，消息中附上可能原因，便于用户直接看出是防火墙还是端口问题
+        let problem = classify_tcp_error(&addr, &e);
+        std::io::Error::new(e.kind(), format!("{} ({})", e, problem.describe()))
+    })?;
+    Ok((WindowsStream::Tcp(stream), address.to_string()))
+}
+
+#[cfg(windows)]
+const TCP_CONNECT_TIMEOUT_MS: u64 = 500;
+
+// 解析 TCP 端点，支持 localhost 等主机名；优先使用 IPv4 地址，避免 localhost 解析到未监听的 ::1
+#[cfg(windows)]
+fn resolve_tcp_address(address: &str) -> Result<SocketAddr, String> {
+    let addrs: Vec<SocketAddr> = address
+        .to_socket_addrs()
+        .map_err(|e| format!("解析TCP地址 {} 失败: {}", address, e))?
+        .collect();
+    addrs
+        .iter()
+        .find(|addr| addr.is_ipv4())
+        .or_else(|| addrs.first())
+        .copied()
+        .ok_or_else(|| format!("TCP地址 {} 没有解析到任何地址", address))
+}
+
+// 按错误类型推断连接失败的原因。本机环回上没有监听者时连接会被立即拒绝，
+// 超时或拒绝访问通常说明被防火墙/代理拦截；被拒绝时再尝试绑定该端口，区分"后端未启动"与"端口被占用"
+#[cfg(windows)]
+fn classify_tcp_error(addr: &SocketAddr, error: &std::io::Error) -> ConnectProblem {
+    match error.kind() {
+        std::io::ErrorKind::ConnectionRefused => match TcpListener::bind(addr) {
+            Ok(_) => ConnectProblem::NotListening,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => ConnectProblem::PortInUse,
+            // WSAEACCES：端口处于系统保留端口段（如 Hyper-V 排除的端口范围）
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => ConnectProblem::PortInUse,
+            Err(_) => ConnectProblem::NotListening,
+        },
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock | std::io::ErrorKind::PermissionDenied => {
+            ConnectProblem::Blocked
+        }
+        std::io::ErrorKind::AddrNotAvailable => ConnectProblem::ResolveFailed,
+        _ => ConnectProblem::Other,
+    }
+}
+
+// 管道打开失败时按错误码推断原因：ERROR_FILE_NOT_FOUND(2) 后端未创建管道，ERROR_PIPE_BUSY(231) 实例全忙
+#[cfg(windows)]
+fn classify_pipe_error(error: &std::io::Error) -> ConnectProblem {
+    match (error.kind(), error.raw_os_error()) {
+        (std::io::ErrorKind::NotFound, _) => ConnectProblem::NotListening,
+        (std::io::ErrorKind::PermissionDenied, _) => ConnectProblem::PermissionDenied,
+        (_, Some(231)) => ConnectProblem::Busy,
+        _ => ConnectProblem::Other,
+    }
+}
+
+// 探测某条通道：解析地址并做一次连接，失败时给出可能原因
+#[cfg(windows)]
+pub fn diagnose(endpoints: &BackendEndpoints, kind: EndpointKind) -> EndpointDiagnosis {
+    let endpoint = endpoints.get(kind);
+    let result = if is_pipe_name(endpoint) {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(endpoint)
+            .map(|_| ())
+            .map_err(|e| (classify_pipe_error(&e), e.to_string()))
+    } else {
+        match resolve_tcp_address(endpoint) {
+            Ok(addr) => TcpStream::connect_timeout(&addr, Duration::from_millis(TCP_CONNECT_TIMEOUT_MS))
+                .map(|_| ())
+                .map_err(|e| (classify_tcp_error(&addr, &e), e.to_string())),
+            Err(e) => Err((ConnectProblem::ResolveFailed, e)),
+        }
+    };
+    EndpointDiagnosis::new(kind, endpoint, result)
+}
@@ -0,0 +1,73 @@
+// 可选能力的编译期开关
+// 各能力由同名 Cargo feature 控制，集成方可以只编译 VAD + socket 内核。
+// 未启用的能力对应的命令仍然注册，调用时返回以 FeatureDisabled 开头的错误，前端据此提示而不是 invoke 失败。
+
+use serde::{Deserialize, Serialize};
+
+// 能力未启用时错误信息的前缀（错误码）
+pub const FEATURE_DISABLED: &str = "FeatureDisabled";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Feature {
+    Denoise,
+    SileroVad,
+    CodecOpus,
+    NativeAudio,
+    WakeWord,
+    Telemetry,
+    Otel,
+}
+
+pub const ALL_FEATURES: [Feature; 7] = [
+    Feature::Denoise,
+    Feature::SileroVad,
+    Feature::CodecOpus,
+    Feature::NativeAudio,
+    Feature::WakeWord,
+    Feature::Telemetry,
+    Feature::Otel,
+];
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Denoise => "denoise",
+            Feature::SileroVad => "silero-vad",
+            Feature::CodecOpus => "codec-opus",
+            Feature::NativeAudio => "native-audio",
+            Feature::WakeWord => "wake-word",
+            Feature::Telemetry => "telemetry",
+            Feature::Otel => "otel",
+        }
+    }
+
+    pub fn enabled(self) -> bool {
+        match self {
+            Feature::Denoise => cfg!(feature = "denoise"),
+            Feature::SileroVad => cfg!(feature = "silero-vad"),
+            Feature::CodecOpus => cfg!(feature = "codec-opus"),
+            Feature::NativeAudio => cfg!(feature = "native-audio"),
+            Feature::WakeWord => cfg!(feature = "wake-word"),
+            Feature::Telemetry => cfg!(feature = "telemetry"),
+            Feature::Otel => cfg!(feature = "otel"),
+        }
+    }
+}
+
+pub fn enabled_features() -> Vec<Feature> {
+    ALL_FEATURES.iter().copied().filter(|feature| feature.enabled()).collect()
+}
+
+// 要求某能力已编译进来，否则返回 FeatureDisabled 错误
+pub fn require(feature: Feature) -> Result<(), String> {
+    if feature.enabled() {
+        Ok(())
+    } else {
+        Err(disabled_error(feature))
+    }
+}
+
+pub fn disabled_error(feature: Feature) -> String {
+    format!("{}: 功能 {} 未在本次构建中启用", FEATURE_DISABLED, feature.name())
+}
@@ -0,0 +1,151 @@
+// 窗口焦点与聆听档位
+// 窗口在前台时正常走 VAD 流程；切到后台时按策略只响应唤醒词或直接静音。
+// 焦点变化需稳定 FOCUS_DEBOUNCE_MS 后才切换档位，避免 alt-tab 时来回抖动。
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub const FOCUS_DEBOUNCE_MS: u64 = 2000;
+// 唤醒后门控保持打开的时长，会话进行中会持续续期
+pub const WAKE_UNLOCK_WINDOW_MS: u64 = 8000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusPolicy {
+    AlwaysListen,
+    WakeWordWhenBlurred,
+    MuteWhenBlurred,
+}
+
+impl FocusPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "always_listen" => Some(FocusPolicy::AlwaysListen),
+            "wake_word_when_blurred" => Some(FocusPolicy::WakeWordWhenBlurred),
+            "mute_when_blurred" => Some(FocusPolicy::MuteWhenBlurred),
+            _ => None,
+        }
+    }
+}
+
+// 当前聆听档位
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListeningMode {
+    Active,       // 主动聆听：正常 VAD 流程
+    WakeWordOnly, // 仅唤醒词：门控上锁，收到唤醒信号后短时打开
+    Muted,        // 静音：丢弃所有输入帧
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ListeningModeChange {
+    pub mode: ListeningMode,
+    pub reason: String,
+}
+
+pub struct FocusGate {
+    policy: FocusPolicy,
+    focused: bool,
+    mode: ListeningMode,
+    reason: String,
+    pending_focus: Option<(bool, Instant)>, // 尚未稳定的焦点变化
+    wake_unlocked_until: Option<Instant>,
+}
+
+impl FocusGate {
+    pub fn new() -> Self {
+        Self {
+            policy: FocusPolicy::AlwaysListen,
+            focused: true,
+            mode: ListeningMode::Active,
+            reason: "initial".to_string(),
+            pending_focus: None,
+            wake_unlocked_until: None,
+        }
+    }
+
+    pub fn policy(&self) -> FocusPolicy {
+        self.policy
+    }
+
+    pub fn mode(&self) -> ListeningMode {
+        self.mode
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    // 修改策略立即生效，返回档位变化
+    pub fn set_policy(&mut self, policy: FocusPolicy) -> Option<ListeningModeChange> {
+        self.policy = policy;
+        self.update_mode(format!("policy_changed:{:?}", policy))
+    }
+
+    // 记录一次焦点变化，等待防抖结束后由 settle 生效
+    pub fn observe_focus(&mut self, focused: bool, now: Instant) {
+        if focused == self.focused {
+            // 回到原焦点，放弃尚未生效的变化
+            self.pending_focus = None;
+        } else {
+            self.pending_focus = Some((focused, now));
+        }
+    }
+
+    // 焦点变化稳定超过防抖时长后应用，返回档位变化
+    pub fn settle(&mut self, now: Instant) -> Option<ListeningModeChange> {
+        let (focused, since) = self.pending_focus?;
+        if now.duration_since(since) < Duration::from_millis(FOCUS_DEBOUNCE_MS) {
+            return None;
+        }
+
+        self.pending_focus = None;
+        self.focused = focused;
+        let reason = if focused { "window_focused" } else { "window_blurred" };
+        self.update_mode(reason.to_string())
+    }
+
+    // 收到唤醒信号，短时打开门控
+    pub fn unlock_wake(&mut self, now: Instant) {
+        self.wake_unlocked_until = Some(now + Duration::from_millis(WAKE_UNLOCK_WINDOW_MS));
+    }
+
+    // 会话进行中续期唤醒门控
+    pub fn extend_wake(&mut self, now: Instant) {
+        if self.mode == ListeningMode::WakeWordOnly && self.allows_audio(now) {
+            self.unlock_wake(now);
+        }
+    }
+
+    // 当前档位是否允许音频进入 VAD 流程
+    pub fn allows_audio(&self, now: Instant) -> bool {
+        match self.mode {
+            ListeningMode::Active => true,
+            ListeningMode::Muted => false,
+            ListeningMode::WakeWordOnly => matches!(self.wake_unlocked_until, Some(until) if until > now),
+        }
+    }
+
+    fn update_mode(&mut self, reason: String) -> Option<ListeningModeChange> {
+        let mode = if self.focused {
+            ListeningMode::Active
+        } else {
+            match self.policy {
+                FocusPolicy::AlwaysListen => ListeningMode::Active,
+                FocusPolicy::WakeWordWhenBlurred => ListeningMode::WakeWordOnly,
+                FocusPolicy::MuteWhenBlurred => ListeningMode::Muted,
+            }
+        };
+
+        if mode == self.mode {
+            return None;
+        }
+        self.mode = mode;
+        self.reason = reason;
+        self.wake_unlocked_until = None;
+        Some(ListeningModeChange {
+            mode,
+            reason: self.reason.clone(),
+        })
+    }
+}
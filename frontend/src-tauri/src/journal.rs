@@ -0,0 +1,366 @@
+// 崩溃恢复 journal
+// 运行期间周期性地把关键数据 checkpoint 到应用数据目录，并用一个脏标记文件表示"正在运行"；
+// 正常退出时删除脏标记。启动时若发现脏标记，说明上次是异常退出，加载最近的 checkpoint 进行恢复。
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metrics::MetricsSnapshot;
+use crate::privacy::Suspendable;
+use crate::turns::Turn;
+use crate::SttResult;
+
+pub const JOURNAL_CHECKPOINT_INTERVAL_SECS: u64 = 5;
+const JOURNAL_DIR_NAME: &str = "journal";
+const CHECKPOINT_FILE_NAME: &str = "checkpoint.json";
+const DIRTY_MARKER_FILE_NAME: &str = "running.marker";
+// 转写缓冲最多保留的最终识别结果条数
+const MAX_JOURNAL_TRANSCRIPTS: usize = 50;
+
+// 当前会话的元数据
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionMeta {
+    pub vad_state: String,
+    pub started_at_ms: u64, // 本次运行的启动时间（Unix毫秒）
+    pub metrics: MetricsSnapshot,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Checkpoint {
+    pub saved_at_ms: u64,
+    pub session: SessionMeta,
+    pub pending_segments: Vec<Vec<i16>>, // 尚未成功发往后端的语音段
+    pub transcripts: Vec<SttResult>,
+    #[serde(default)]
+    pub turns: Vec<Turn>, // 对话轮次，旧版checkpoint中没有该字段
+}
+
+// 恢复出的一类数据
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveredItem {
+    pub kind: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveryReport {
+    pub checkpoint_saved_at_ms: Option<u64>,
+    pub previous_session: Option<SessionMeta>,
+    pub recovered_items: Vec<RecoveredItem>,
+    pub transcripts: Vec<SttResult>,
+    pub error: Option<String>, // checkpoint 缺失或损坏时的原因
+}
+
+pub struct Journal {
+    dir: Option<PathBuf>, // 未打开时只在内存中收集数据
+    started_at_ms: u64,
+    transcripts: VecDeque<SttResult>,
+    last_recovery: Option<RecoveryReport>,
+    suspended: bool, // 不留痕模式：不收集转写，checkpoint 只写元数据
+}
+
+pub fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn journal_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(JOURNAL_DIR_NAME)
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self {
+            dir: None,
+            started_at_ms: now_unix_ms(),
+            transcripts: VecDeque::new(),
+            last_recovery: None,
+            suspended: false,
+        }
+    }
+
+    // 打开 journal 目录并写入脏标记；上次异常退出时返回其 checkpoint 的加载结果
+    pub fn open(&mut self, dir: PathBuf) -> Result<Option<Result<Checkpoint, String>>, String> {
+        fs::create_dir_all(&dir).map_err(|e| format!("创建journal目录 {} 失败: {}", dir.display(), e))?;
+
+        let marker = dir.join(DIRTY_MARKER_FILE_NAME);
+        let previous = if marker.exists() {
+            Some(load_checkpoint(&dir.join(CHECKPOINT_FILE_NAME)))
+        } else {
+            None
+        };
+
+        fs::write(&marker, self.started_at_ms.to_string())
+            .map_err(|e| format!("写入脏标记 {} 失败: {}", marker.display(), e))?;
+        self.dir = Some(dir);
+        Ok(previous)
+    }
+
+    pub fn started_at_ms(&self) -> u64 {
+        self.started_at_ms
+    }
+
+    pub fn record_transcript(&mut self, result: SttResult) {
+        if self.suspended {
+            return;
+        }
+        if self.transcripts.len() >= MAX_JOURNAL_TRANSCRIPTS {
+            self.transcripts.pop_front();
+        }
+        self.transcripts.push_back(result);
+    }
+
+    pub fn transcripts(&self) -> Vec<SttResult> {
+        self.transcripts.iter().cloned().collect()
+    }
+
+    // 删除某一轮的最终识别结果（含上次恢复报告中的），按结果登记的轮次 id 匹配，返回删除的条数；
+    // 磁盘上的 checkpoint 由调用方随后重写
+    pub fn remove_transcripts(&mut self, turn_id: u64) -> usize {
+        let matches = |result: &SttResult| result.turn_id == Some(turn_id);
+        let before = self.transcripts.len();
+        self.transcripts.retain(|result| !matches(result));
+        let mut removed = before - self.transcripts.len();
+        if let Some(report) = self.last_recovery.as_mut() {
+            let before = report.transcripts.len();
+            report.transcripts.retain(|result| !matches(result));
+            removed += before - report.transcripts.len();
+        }
+        removed
+    }
+
+    pub fn set_last_recovery(&mut self, report: RecoveryReport) {
+        self.last_recovery = Some(report);
+    }
+
+    pub fn last_recovery(&self) -> Option<RecoveryReport> {
+        self.last_recovery.clone()
+    }
+
+    // 原子写入 checkpoint：先写临时文件并落盘，再 rename 覆盖
+    pub fn write_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        // 挂起期间去掉音频与文字，只保留会话元数据，崩溃后依然能看到上次运行的状态
+        let stripped;
+        let checkpoint = if self.suspended {
+            stripped = Checkpoint {
+                saved_at_ms: checkpoint.saved_at_ms,
+                session: checkpoint.session.clone(),
+                pending_segments: Vec::new(),
+                transcripts: Vec::new(),
+                turns: Vec::new(),
+            };
+            &stripped
+        } else {
+            checkpoint
+        };
+        let content = serde_json::to_vec(checkpoint).map_err(|e| format!("序列化checkpoint失败: {}", e))?;
+        let path = dir.join(CHECKPOINT_FILE_NAME);
+        let tmp_path = dir.join(format!("{}.tmp", CHECKPOINT_FILE_NAME));
+
+        let mut file = fs::File::create(&tmp_path)
+            .map_err(|e| format!("创建临时checkpoint {} 失败: {}", tmp_path.display(), e))?;
+        file.write_all(&content)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("写入临时checkpoint {} 失败: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| format!("替换checkpoint {} 失败: {}", path.display(), e))
+    }
+
+    // 正常退出：删除脏标记
+    // 关闭后重新启动时恢复脏标记，之后的异常退出仍能被检测到
+    pub fn mark_dirty(&self) -> Result<(), String> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        let marker = dir.join(DIRTY_MARKER_FILE_NAME);
+        fs::write(&marker, self.started_at_ms.to_string())
+            .map_err(|e| format!("写入脏标记 {} 失败: {}", marker.display(), e))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    pub fn mark_clean(&self) -> Result<(), String> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        let marker = dir.join(DIRTY_MARKER_FILE_NAME);
+        if marker.exists() {
+            fs::remove_file(&marker).map_err(|e| format!("删除脏标记 {} 失败: {}", marker.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Suspendable for Journal {
+    fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+}
+
+// 读取 checkpoint；文件缺失、截断或损坏都返回错误说明，由调用方决定如何提示
+pub fn load_checkpoint(path: &Path) -> Result<Checkpoint, String> {
+    let content = fs::read(path).map_err(|e| format!("读取checkpoint {} 失败: {}", path.display(), e))?;
+    serde_json::from_slice(&content).map_err(|e| format!("checkpoint {} 已损坏: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lumina-journal-{}-{}-{}", name, std::process::id(), now_unix_ms()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn checkpoint(transcript: &str) -> Checkpoint {
+        Checkpoint {
+            saved_at_ms: 1_700_000_000_000,
+            session: SessionMeta {
+                vad_state: "Speaking".to_string(),
+                started_at_ms: 1_699_999_990_000,
+                metrics: crate::metrics::Metrics::new().report().cumulative,
+            },
+            pending_segments: vec![vec![1, -2, 3], vec![4]],
+            transcripts: vec![SttResult {
+                text: transcript.to_string(),
+                is_final: true,
+                session_id: Some(3),
+                trace_id: None,
+                turn_id: None,
+            }],
+            turns: Vec::new(),
+        }
+    }
+
+    // 模拟一次运行：打开 journal、写入 checkpoint，clean 为 false 时不删除脏标记（异常退出）
+    fn run_once(dir: &Path, clean: bool) {
+        let mut journal = Journal::new();
+        assert!(journal.open(dir.to_path_buf()).unwrap().is_none());
+        journal.write_checkpoint(&checkpoint("上次没说完的话")).unwrap();
+        if clean {
+            journal.mark_clean().unwrap();
+        }
+    }
+
+    // 下一次启动时 open 的返回值
+    fn reopen(dir: &Path) -> Option<Result<Checkpoint, String>> {
+        Journal::new().open(dir.to_path_buf()).unwrap()
+    }
+
+    #[test]
+    fn clean_shutdown_skips_recovery() {
+        let dir = temp_dir("clean");
+        run_once(&dir, true);
+        assert!(reopen(&dir).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn crash_recovers_last_checkpoint() {
+        let dir = temp_dir("crash");
+        run_once(&dir, false);
+
+        let recovered = reopen(&dir).expect("应检测到异常退出").expect("checkpoint 应能加载");
+        assert_eq!(recovered.saved_at_ms, 1_700_000_000_000);
+        assert_eq!(recovered.session.vad_state, "Speaking");
+        assert_eq!(recovered.pending_segments, vec![vec![1, -2, 3], vec![4]]);
+        assert_eq!(recovered.transcripts.len(), 1);
+        assert_eq!(recovered.transcripts[0].text, "上次没说完的话");
+        // 原子写入不留下临时文件
+        assert!(!dir.join(format!("{}.tmp", CHECKPOINT_FILE_NAME)).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 写到一半被截断的 checkpoint：检测到异常退出，但加载返回错误说明，不会 panic
+    #[test]
+    fn truncated_checkpoint_is_reported_as_corrupt() {
+        let dir = temp_dir("truncated");
+        run_once(&dir, false);
+        let path = dir.join(CHECKPOINT_FILE_NAME);
+        let full = fs::read(&path).unwrap();
+
+        for len in [0, 1, full.len() / 2, full.len() - 1] {
+            fs::write(&path, &full[..len]).unwrap();
+            let error = reopen(&dir).expect("应检测到异常退出").expect_err("截断的checkpoint不应加载成功");
+            assert!(error.contains("已损坏"), "截断到{}字节: {}", len, error);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupted_checkpoint_is_reported_as_corrupt() {
+        let dir = temp_dir("corrupted");
+        run_once(&dir, false);
+        let path = dir.join(CHECKPOINT_FILE_NAME);
+
+        let mut garbage = fs::read(&path).unwrap();
+        for byte in garbage.iter_mut().step_by(7) {
+            *byte = 0xff;
+        }
+        for content in [garbage, b"{\"saved_at_ms\": \"yesterday\"}".to_vec(), b"[]".to_vec()] {
+            fs::write(&path, &content).unwrap();
+            let error = reopen(&dir).expect("应检测到异常退出").expect_err("损坏的checkpoint不应加载成功");
+            assert!(error.contains("已损坏"), "{}", error);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 第一次写 checkpoint 之前就崩溃：只有脏标记，没有 checkpoint
+    #[test]
+    fn crash_before_first_checkpoint_reports_missing_file() {
+        let dir = temp_dir("missing");
+        Journal::new().open(dir.clone()).unwrap();
+        let error = reopen(&dir).expect("应检测到异常退出").expect_err("没有checkpoint可加载");
+        assert!(error.contains("读取checkpoint"), "{}", error);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // rename 之前崩溃留下的临时文件不影响已有的 checkpoint
+    #[test]
+    fn leftover_temp_file_does_not_affect_checkpoint() {
+        let dir = temp_dir("leftover");
+        run_once(&dir, false);
+        fs::write(dir.join(format!("{}.tmp", CHECKPOINT_FILE_NAME)), b"{\"saved_at").unwrap();
+
+        let recovered = reopen(&dir).unwrap().expect("已有的checkpoint应能加载");
+        assert_eq!(recovered.transcripts[0].text, "上次没说完的话");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 旧版 checkpoint 没有 turns 字段，按空列表加载
+    #[test]
+    fn checkpoint_without_turns_still_loads() {
+        let dir = temp_dir("legacy");
+        fs::create_dir_all(&dir).unwrap();
+        let mut value = serde_json::to_value(checkpoint("旧版")).unwrap();
+        value.as_object_mut().unwrap().remove("turns");
+        let path = dir.join(CHECKPOINT_FILE_NAME);
+        fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+        let loaded = load_checkpoint(&path).unwrap();
+        assert!(loaded.turns.is_empty());
+        assert_eq!(loaded.transcripts[0].text, "旧版");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,18 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-use tauri::{command, Emitter};
-use webrtc_vad::{Vad, VadMode, SampleRate};
+use tauri::{ipc::Channel, Emitter, Manager, State};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use log::{debug, error, info, warn};
+// 替代 tauri::command，注册命令的同时接入命令审计
+use lumina_macros::command;
+use webrtc_vad::{VadMode, SampleRate};
 use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use std::thread;
-use tokio;
+use tokio::sync::watch;
 use base64::{Engine as _, engine::general_purpose};
 // use tauri::Manager;
 // use tauri_plugin_screenshots::PluginBuilder;
@@ -13,30 +20,184 @@ use base64::{Engine as _, engine::general_purpose};
 // use std::path::PathBuf;
 // use anyhow;
 
+mod annotations;
+mod audit;
+mod autostart;
+mod audio_level;
+mod audio_utils;
+mod backend_error;
+mod capture_clock;
+mod calibration;
+mod codec;
+mod config;
+mod control_actions;
+mod conversation;
+mod denoise;
+mod display;
+mod endpoints;
+mod features;
+mod focus;
+mod journal;
+mod lifecycle;
+mod logging;
+mod lookahead;
+mod metrics;
+mod params;
+mod pipeline;
+mod privacy;
+mod protocol;
+mod protocol_trace;
+mod rate_check;
+mod resample;
+mod send_queue;
+mod state_diagram;
+mod state_mirror;
+mod stt_merge;
+mod telemetry;
+mod trace_context;
+mod tts_flow;
+mod tts_gap_fill;
+mod tts_queue;
+mod tts_stall;
+mod turns;
+mod vad_backend;
+mod vad_stats;
+mod wav_stream;
+mod waveform;
+use annotations::{AnnotationFormat, AnnotationRecorder};
+use audit::{CommandAudit, CommandAuditReport};
+use autostart::{Autostart, AutostartStatus};
+use audio_level::LevelMeter;
+use backend_error::{BackendError, PendingErrors};
+use calibration::{Calibration, CalibrationKind, CalibrationResult, SpeechCheckResult};
+use capture_clock::CaptureClock;
+use codec::{AudioCodec, OpusEncoder};
+use config::LuminaConfig;
+use control_actions::{CaptionPayload, ControlAction, ControlActionInfo, VadHintPayload};
+use conversation::{ConversationHistory, HistoryEntry, Speaker};
+use denoise::Denoiser;
+use display::{CoordinateSpace, DisplayInfo, PhysicalRect, Region, ResolvedRegion, WindowGeometry};
+use endpoints::{BackendEndpoints, EndpointDiagnosis, EndpointKind, StreamCloser, Transport};
+use features::Feature;
+use focus::{FocusGate, FocusPolicy, ListeningMode, ListeningModeChange};
+use journal::{Checkpoint, Journal, RecoveredItem, RecoveryReport, SessionMeta};
+use lifecycle::{Lifecycle, ShutdownReport};
+use lookahead::LookAhead;
+use metrics::{Metrics, MetricsReport};
+use params::{EffectiveParameter, InteractionMode, ParamKey, ParameterLayers, TimingProfile};
+use pipeline::{AudioStage, DcRemoval, Pipeline, PipelineStatus, StageConfig};
+use privacy::{DeletionReport, PrivacyMode, PrivacyStatus, Suspendable};
+use protocol::SilenceContext;
+use protocol_trace::{Direction, ProtocolTrace, ProtocolTraceReport, TraceChannel};
+use rate_check::{RateCheck, RateCheckStatus};
+use resample::Resampler;
+use send_queue::{SendQueue, SendQueueStatus};
+use state_mirror::{MirroredState, StateMirror};
+use stt_merge::{PartialEmit, PartialTranscriptMerger};
+use telemetry::{LatencyTelemetry, PipelineTelemetry, Telemetry, TelemetryReport, TelemetrySettings, TelemetryStatus, VadTelemetry};
+use trace_context::{SessionTracer, TraceStatus};
+use tts_flow::{FloodReason, FloodWarning, FlowSettings, FlowStatus};
+use tts_gap_fill::{GapFillSettings, GapFillStatus, GapFillStrategy};
+use tts_queue::{InterruptOutcome, InterruptPolicy, TtsPlaybackQueue, TtsPlaybackStatus};
+use tts_stall::{StallEvent, StallSettings, StallStatus};
+use turns::{AssistantReply, Turn, TurnTracker};
+use vad_backend::{SileroSettings, VadBackend, VadBackendKind};
+use vad_stats::{VadStatistics, VadStats};
+use wav_stream::{StreamingWav, StreamingWavStatus};
+use waveform::{ThumbnailCache, ThumbnailSource};
+
 // 平台特定导入
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
 #[cfg(unix)]
 use std::io::{Write, Read};
 
-// Windows平台使用TCP Socket替代UnixSocket
-#[cfg(windows)]
-use std::net::{TcpStream, SocketAddr};
+// Windows平台使用命名管道或TCP Socket替代UnixSocket
 #[cfg(windows)]
 use std::io::{Write, Read};
 
 // 常量定义
-const SAMPLE_RATE: u32 = 16000; // 16kHz
+const SAMPLE_RATE: u32 = 16000; // 默认管线采样率16kHz，可通过set_sample_rate修改
+// webrtc_vad 支持的采样率
+const SUPPORTED_SAMPLE_RATES: [u32; 4] = [8000, 16000, 32000, 48000];
 // const FRAME_DURATION_MS: u32 = 20; // 20ms
 // const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE * FRAME_DURATION_MS / 1000) as usize;
-#[cfg(unix)]
-const SOCKET_PATH: &str = "/tmp/lumina_stt.sock";
-#[cfg(windows)]
-const TCP_ADDRESS: &str = "127.0.0.1:8765"; // Windows下使用TCP端口
-const RECONNECT_INTERVAL_MS: u64 = 500;
-const SEND_BUFFER_THRESHOLD: usize = 3200; // 200ms的音频@16kHz (10帧 * 320样本/帧)
+const RECONNECT_INTERVAL_MS: u64 = 500; // 重连退避的初始间隔
+const MAX_RECONNECT_INTERVAL_MS: u64 = 10_000; // 重连退避的间隔上限
+const RECONNECT_JITTER_RATIO: f64 = 0.2; // 每次间隔随机加上不超过该比例的抖动
 const SILENCE_REPORT_INTERVAL_MS: u64 = 20; // 20ms间隔发送静音事件
+const DEFAULT_MAX_UTTERANCE_SECONDS: u64 = 60; // 连续说话超过该时长时强制结束本段发言
+const HANDSHAKE_TIMEOUT_MS: u64 = 3000; // 连接后等待后端确认握手的最长时间
+const MAX_PENDING_SPEECH_SEGMENTS: usize = 3000; // 内存中等待补发的语音段上限（20ms一帧约1分钟音频），超出时丢弃最旧的
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 15; // 心跳间隔，尽早发现被系统静默关闭的连接
 const TRANSITION_BUFFER_TIMEOUT_MS: u64 = 500; // 临界状态超时时间
+const LOCAL_CONFIRM_MS: u64 = 200; // 本地确认：临界态持续有语音超过该时长即视为有效语音，需短于临界状态超时
+const FINALIZE_TIMEOUT_MS: u64 = 1500; // 请求最终识别后等待结果的超时时间
+const TTS_FLOW_POLL_INTERVAL_MS: u64 = 50; // TTS接收暂停期间检查是否可以恢复读取的间隔
+const TTS_CHUNK_HEAD_BYTES: usize = 4096; // 超大TTS音频块先读入的开头长度，需容纳完整的WAV头
+const MAX_TIMELINE_GAP_MS: u64 = 30_000; // 会话时间轴音频中段间静音的上限，避免长时间空闲撑大音频
+const BACKEND_WAIT_INTERVAL_SECS: u64 = 3; // 最小化启动后探测后端是否可达的间隔
+const MAIN_WINDOW_LABEL: &str = "main";
+const TRAY_MENU_SHOW: &str = "show";
+const TRAY_MENU_QUIT: &str = "quit";
+const DEFAULT_FRAME_MS: f64 = 20.0; // 尚未收到音频帧时按20ms一帧把毫秒阈值换算为帧数
+const DEFAULT_SPEECH_START_MS: u64 = 40; // 连续40ms语音判定语音开始
+const DEFAULT_SPEECH_END_SILENCE_MS: u64 = 2000; // 2秒静音判定语音结束
+const DEFAULT_WAITING_SILENCE_MS: u64 = 100; // 100ms无声音后进入等待状态
+const MIN_SILENCE_THRESHOLD_FRAMES: usize = 1; // 按帧数设置进入等待状态的静音阈值时的下限
+const MAX_SILENCE_THRESHOLD_FRAMES: usize = 100; // 上限，20ms一帧时为2秒
+const DEFAULT_PRE_CONTEXT_MS: u64 = 100; // 100ms作为上下文
+const DEFAULT_PRE_CONTEXT_FADE_MS: u64 = 10; // 前置上下文起始处的淡入时长，0表示不淡入
+const DEFAULT_LOOKAHEAD_MS: u64 = 0; // VAD判定前瞻时长，默认不延迟，语音开始前的音频由前置上下文补齐
+const MAX_LOOKAHEAD_MS: u64 = 500; // 前瞻最多延迟500ms
+const DEFAULT_MIN_SPEECH_MS: u64 = 200; // 语音持续200ms后才报告给状态机，短促的按键声、咳嗽不触发会话
+const DEFAULT_REFERENCE_SPEECH_RMS: f32 = 0.05; // 未校准时正常距离说话的参考响度
+const DEFAULT_LOW_INPUT_LEVEL_RATIO: f32 = 0.4; // 会话响度低于参考值该比例时提示麦克风太远
+const DEFAULT_ENERGY_FALLBACK_THRESHOLD: f32 = 0.02; // 判定后端出错时，帧RMS超过该值按语音处理
+const CALIBRATION_GRACE_MS: u64 = 2000; // 底噪校准/语音检测在采集时长之外最多多等的时间
+const VAD_ERROR_LOG_INTERVAL_MS: u64 = 1000; // webrtc_vad错误日志的最小间隔
+// 双重判定模式：帧RMS需超过底噪的该倍数（约6dB）才算语音
+const VOTING_NOISE_FLOOR_MARGIN: f32 = 2.0;
+// 底噪滑动平均的更新系数，20ms帧下约2秒收敛
+const NOISE_FLOOR_SMOOTHING: f32 = 0.01;
+// VAD判定前高通滤波的截止频率，去除直流偏置与低频隆隆声（否则VeryAggressive档位会一直判为语音）
+const VAD_HIGH_PASS_CUTOFF_HZ: f32 = 80.0;
+const MIC_CLIPPING_WARNING_RATIO: f32 = 0.05; // 一帧中削波样本超过该比例时提示降低输入增益
+const MIN_LOUDNESS_SESSION_FRAMES: usize = 10; // 语音帧太少的会话不做响度判断
+const LOW_INPUT_LEVEL_SUGGEST_SESSIONS: usize = 3; // 连续多少个会话偏低时给出调整建议
+
+// 发送到后端的音频样本格式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SampleFormat {
+    Pcm16, // 长度头(样本数) + i16小端样本，旧后端默认格式
+    F32,   // 控制消息头 + protocol::CONTROL_AUDIO_F32 + 样本数 + [-1,1] f32小端样本
+}
+
+// 单帧语音判定的来源
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VadSource {
+    Backend,        // 判定后端（webrtc_vad 或 Silero）正常给出结论
+    EnergyFallback, // 判定后端出错，按帧能量判定
+}
+
+// 采集方式：由VAD判定语音起止，或按键说话（按下即说话、松开即结束，不经过VAD判定）
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    Vad,
+    PushToTalk,
+}
+
+impl CaptureMode {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vad" => Some(CaptureMode::Vad),
+            "push_to_talk" => Some(CaptureMode::PushToTalk),
+            _ => None,
+        }
+    }
+}
 
 // VAD 事件类型
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -47,7 +208,7 @@ pub enum VadEvent {
 }
 
 // 状态机状态定义
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 enum VadState {
     Initial,    // 初始：什么都不干，只是激活 vad 组件
     Speaking,   // 说话中：发送音频帧给后端，vad 计时保持清零
@@ -57,7 +218,7 @@ enum VadState {
 }
 
 // 状态机事件定义
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 enum VadStateMachineEvent {
     VoiceFrame,      // 麦克风一帧有声音
     SilenceFrame,    // 麦克风一帧无声音
@@ -67,12 +228,78 @@ enum VadStateMachineEvent {
     AudioPlaybackEnd,   // 后端音频播放结束
     BackendReturnText,  // 后端返回任意非空识别文本
     TransitionTimeout,  // 临界状态超时
+    PttPressed,         // 按键说话：按下
+    PttReleased,        // 按键说话：松开
 }
 
 // 静音上报事件
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SilenceEvent {
-    silence_ms: u64,
+    #[serde(flatten)]
+    context: SilenceContext, // 与发往后端的静音事件相同的状态机上下文
+    threshold_ms: u64,       // 当前生效的语音结束静音阈值
+    progress_percent: f64,   // silence_ms 占阈值的百分比，最大100
+}
+
+// 状态转移事件：vad-state-transition 只包含对用户可见的转移，from 为转移前前端看到的状态；
+// vad-state-debug 包含全部转移（含进出临界态），from 为实际的上一个状态
+#[derive(Serialize, Clone, Debug)]
+struct StateTransition {
+    from: String,
+    to: String,
+    trigger: String,       // 引起转移的状态机事件
+    at_ms: u64,            // 事件发生时刻（Unix 毫秒）
+    silence_frames: usize, // 转移后的连续静音帧计数
+}
+
+// 连续说话达到最长发言时长、被强制结束时的事件
+#[derive(Serialize, Clone, Debug)]
+pub struct MaxUtteranceReached {
+    duration_ms: u64,
+    max_utterance_seconds: u64,
+    session_id: u64,
+    turn_index: u32,
+}
+
+// 对话节奏变化事件：语音结束静音阈值因后端hint被设置、撤销或到期还原
+#[derive(Serialize, Clone, Debug)]
+pub struct DialoguePacing {
+    threshold_ms: u64,
+    source: &'static str,       // 生效值所在的参数层，如 backend_hint / interaction_mode / default
+    duration_ms: Option<u64>,   // 来自带有效期的后端hint时的生效时长，到期后自动还原
+    reason: &'static str,       // hint_updated / hint_cleared / hint_expired
+}
+
+// 输入削波提示
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MicClippingEvent {
+    clipped_samples: usize,
+    total_samples: usize,
+    ratio: f32,
+}
+
+impl MicClippingEvent {
+    // 一帧中削波样本的比例超过 MIC_CLIPPING_WARNING_RATIO 时返回提示
+    fn check(audio_data: &[f32]) -> Option<Self> {
+        if audio_data.is_empty() {
+            return None;
+        }
+        let clipped_samples = audio_utils::count_clipped(audio_data);
+        let ratio = clipped_samples as f32 / audio_data.len() as f32;
+        (ratio > MIC_CLIPPING_WARNING_RATIO).then_some(Self {
+            clipped_samples,
+            total_samples: audio_data.len(),
+            ratio,
+        })
+    }
+}
+
+// 一次最终识别请求的结果：收到最终文本或等待超时
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FinalizeOutcome {
+    finalize_id: u64,
+    received_final: bool,
+    waited_ms: u64,
 }
 
 // STT 识别结果
@@ -80,29 +307,190 @@ pub struct SilenceEvent {
 pub struct SttResult {
     text: String,
     is_final: bool,
+    #[serde(default)]
+    session_id: Option<u64>, // 结果所属的会话序号，由后端回传；旧后端不回传时按当前会话补上
+    #[serde(default)]
+    trace_id: Option<String>, // 后端回传的 trace_id，用于核对追踪上下文是否贯穿往返
+    #[serde(default)]
+    turn_id: Option<u64>, // 最终结果归入的对话轮次，记录时由前端填写，按轮次删除转写时据此匹配
+}
+
+// 单个会话的输入响度统计
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionLoudnessSummary {
+    voice_frames: usize,
+    mean_rms: f32,
+    median_rms: f32,
+    reference_rms: f32,
+    ratio_to_reference: f32,
+    is_low: bool,
+    consecutive_low_sessions: usize,
+}
+
+// 输入响度监测状态
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InputLevelStatus {
+    reference_rms: f32,
+    low_level_ratio: f32,
+    consecutive_low_sessions: usize,
+    last_session: Option<SessionLoudnessSummary>,
+    suggestion: Option<String>,
+}
+
+// 按会话累计语音帧响度，会话结束时与参考响度比较
+struct InputLevelMonitor {
+    session_rms: Vec<f32>,     // 当前会话语音帧的RMS（复用逐帧能量计算结果）
+    reference_rms: f32,        // 参考响度（校准值或默认值）
+    low_level_ratio: f32,      // 低于 reference_rms * low_level_ratio 视为偏低
+    consecutive_low_sessions: usize,
+    last_session: Option<SessionLoudnessSummary>,
+}
+
+impl InputLevelMonitor {
+    fn new() -> Self {
+        Self {
+            session_rms: Vec::new(),
+            reference_rms: DEFAULT_REFERENCE_SPEECH_RMS,
+            low_level_ratio: DEFAULT_LOW_INPUT_LEVEL_RATIO,
+            consecutive_low_sessions: 0,
+            last_session: None,
+        }
+    }
+    
+    fn record_voice_frame(&mut self, rms: f32) {
+        self.session_rms.push(rms);
+    }
+    
+    fn discard_session(&mut self) {
+        self.session_rms.clear();
+    }
+    
+    // 结束当前会话，语音帧足够时返回统计结果
+    fn finish_session(&mut self) -> Option<SessionLoudnessSummary> {
+        let mut values = std::mem::take(&mut self.session_rms);
+        if values.len() < MIN_LOUDNESS_SESSION_FRAMES {
+            return None;
+        }
+        
+        let mean_rms = values.iter().sum::<f32>() / values.len() as f32;
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let median_rms = values[values.len() / 2];
+        let ratio_to_reference = if self.reference_rms > 0.0 { median_rms / self.reference_rms } else { 1.0 };
+        let is_low = ratio_to_reference < self.low_level_ratio;
+        
+        if is_low {
+            self.consecutive_low_sessions += 1;
+        } else {
+            self.consecutive_low_sessions = 0;
+        }
+        
+        let summary = SessionLoudnessSummary {
+            voice_frames: values.len(),
+            mean_rms,
+            median_rms,
+            reference_rms: self.reference_rms,
+            ratio_to_reference,
+            is_low,
+            consecutive_low_sessions: self.consecutive_low_sessions,
+        };
+        self.last_session = Some(summary.clone());
+        Some(summary)
+    }
+    
+    // 以语音检测测得的正常说话响度作为参考值；之前的偏低计数是按旧参考值判断的，一并清零
+    fn calibrate_reference(&mut self, speech_rms: f32) {
+        self.reference_rms = speech_rms;
+        self.consecutive_low_sessions = 0;
+    }
+    
+    // 连续多个会话偏低时的调整建议
+    fn suggestion(&self) -> Option<String> {
+        if self.consecutive_low_sessions >= LOW_INPUT_LEVEL_SUGGEST_SESSIONS {
+            Some(format!(
+                "最近{}次会话输入音量均偏低，建议靠近麦克风或调高系统输入增益",
+                self.consecutive_low_sessions
+            ))
+        } else {
+            None
+        }
+    }
+    
+    fn status(&self) -> InputLevelStatus {
+        InputLevelStatus {
+            reference_rms: self.reference_rms,
+            low_level_ratio: self.low_level_ratio,
+            consecutive_low_sessions: self.consecutive_low_sessions,
+            last_session: self.last_session.clone(),
+            suggestion: self.suggestion(),
+        }
+    }
+}
+
+// 与后端的连接状态
+#[derive(Serialize, Clone, Debug)]
+struct ConnectionStatus {
+    connected: bool,                    // 已连接且后端已确认协议握手
+    protocol_version: Option<u32>,      // 协商出的协议版本，握手完成之前为None
+    audio_codec: AudioCodec,            // 发往后端的音频编码
+    endpoints: BackendEndpoints,        // 配置的端点
+    connected_endpoint: Option<String>, // 音频上行实际连上的端点
+    using_legacy_endpoint: bool,        // 是否连在已弃用的旧路径上
+    legacy_fallback: bool,
+    reconnect_attempts: u32,            // 连续失败的重连次数，连上后清零
+    reconnect_delay_ms: u64,            // 当前的重连间隔（含抖动）
+    next_retry_in_ms: Option<u64>,      // 未连接时距下一次重连的时间；需要发送时才会真正重连
+    local_only: bool,                   // 仅本地模式，不连接后端
+    heartbeat_interval_secs: u64,       // 心跳间隔，0为关闭
+}
+
+// [0, 1) 之间的伪随机数，只用于重连抖动，取当前时间的纳秒部分即可
+fn jitter_fraction() -> f64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    nanos as f64 / 1_000_000_000.0
 }
 
 // 跨平台通用Stream类型
 #[cfg(unix)]
 type PlatformStream = UnixStream;
 #[cfg(windows)]
-type PlatformStream = TcpStream;
+type PlatformStream = endpoints::WindowsStream;
 
 // 状态机管理器
 struct VadStateMachine {
     current_state: VadState,
     last_user_visible_state: VadState, // 用于在临界态时保存上一个对用户可见的状态
     silence_start_time: Option<Instant>,
-    transition_start_time: Option<Instant>, // 临界状态开始时间
+    transition_start_time: Option<Instant>, // 进入临界状态的时刻，超时判定的唯一依据
     app_handle: Option<tauri::AppHandle>,
     silence_timer_handle: Option<tokio::task::JoinHandle<()>>,
     silence_frames_count: usize,          // 连续静音帧计数
     max_silence_frames: usize,            // 进入等待状态所需的静音帧数
-    transition_buffer_enter_time: Option<Instant>, // 记录进入临界状态的时间
+    transition_timeout_ms: u64,           // 临界状态超时时间，可通过 transition_timeout_ms 参数调整
+    silence_report_interval_ms: u64,      // 静音事件上报间隔
+    input_level: InputLevelMonitor,       // 会话输入响度统计
+    socket_manager: Arc<Mutex<SocketManager>>, // 静音上报定时任务向后端发送静音事件时使用
+    finalize_on_silence: bool,            // 因静音结束说话时请求后端输出最终识别结果
+    finalize_timeout_ms: u64,             // 等待最终识别结果的超时时间
+    next_finalize_id: u64,
+    pending_finalize: Option<(u64, Instant)>, // 尚未收到最终结果的请求：序号与发出时刻
+    speech_end_threshold_ms: Arc<AtomicU64>, // 当前语音结束静音阈值，静音上报任务据此计算进度
+    session_id: u64,                      // 会话序号，从初始/等待中/听音中进入临界态（或按下按键说话）时递增
+    turn_index: u32,                      // 本次对话（离开初始状态以来）已结束的发言段数
+    utterance_start_time: Option<Instant>, // 当前发言开始（进入临界态）的时刻
+    last_utterance_duration_ms: u64,      // 最近一段结束的发言时长
+    max_utterance_seconds: u64,           // 单段发言的最长时长，0为不限制
+    local_confirmation: bool,             // 临界态不等后端识别文本，按本地规则确认有效语音（仅本地模式）
+    capture_mode: CaptureMode,            // 采集方式，运行中可随时切换
+    ptt_held: bool,                       // 按键说话模式下按键是否处于按下状态
+    muted: bool,                          // 麦克风静音：音频帧在VAD之前丢弃，不会有任何音频发往后端
+    legacy_state_events: bool,            // 是否继续发送只含新状态名的 vad-state-changed 事件
 }
 
 impl VadStateMachine {
-    fn new() -> Self {
+    fn new(socket_manager: Arc<Mutex<SocketManager>>) -> Self {
         Self {
             current_state: VadState::Initial,
             last_user_visible_state: VadState::Initial,
@@ -111,24 +499,60 @@ impl VadStateMachine {
             app_handle: None,
             silence_timer_handle: None,
             silence_frames_count: 0,
-            max_silence_frames: 5, // 5帧无声音后进入等待状态
-            transition_buffer_enter_time: None, // 初始化进入时间
+            max_silence_frames: params::ms_to_frames(DEFAULT_WAITING_SILENCE_MS as f64, DEFAULT_FRAME_MS),
+            transition_timeout_ms: TRANSITION_BUFFER_TIMEOUT_MS,
+            silence_report_interval_ms: SILENCE_REPORT_INTERVAL_MS,
+            input_level: InputLevelMonitor::new(),
+            socket_manager,
+            finalize_on_silence: true,
+            finalize_timeout_ms: FINALIZE_TIMEOUT_MS,
+            next_finalize_id: 0,
+            pending_finalize: None,
+            speech_end_threshold_ms: Arc::new(AtomicU64::new(DEFAULT_SPEECH_END_SILENCE_MS)),
+            session_id: 0,
+            turn_index: 0,
+            utterance_start_time: None,
+            last_utterance_duration_ms: 0,
+            max_utterance_seconds: DEFAULT_MAX_UTTERANCE_SECONDS,
+            local_confirmation: false,
+            capture_mode: CaptureMode::Vad,
+            ptt_held: false,
+            muted: false,
+            legacy_state_events: true,
         }
     }
     
     // 向后端发送静音事件
-    fn send_silence_to_backend(silence_duration: u64) {
-        // 通过Socket管理器发送静音事件到后端
-        let socket_manager = get_socket_manager();
+    fn send_silence_to_backend(app_handle: &tauri::AppHandle, socket_manager: &Arc<Mutex<SocketManager>>, context: &SilenceContext) {
+        // 通过Socket管理器发送静音事件到后端，发送中产生的错误释放锁后通知前端
         let result = socket_manager.lock();
-        match result {
+        let errors = match result {
             Ok(mut manager) => {
-                manager.send_silence_event(silence_duration);
+                manager.send_silence_event(context);
+                manager.errors.take()
             },
             Err(e) => {
-                println!("[错误] 获取Socket管理器锁失败: {}", e);
+                error!("[错误] 获取Socket管理器锁失败: {}", e);
+                vec![BackendError::new(backend_error::STATE_UNAVAILABLE, format!("获取Socket管理器失败: {}", e), false)]
             }
-        }
+        };
+        backend_error::emit_all(app_handle, errors);
+    }
+    
+    // 处于临界状态且停留时间超过 transition_timeout_ms
+    fn transition_expired(&self, now: Instant) -> bool {
+        self.current_state == VadState::TransitionBuffer
+            && self.transition_start_time.is_some_and(|start| {
+                now.saturating_duration_since(start) > Duration::from_millis(self.transition_timeout_ms)
+            })
+    }
+    
+    // 开始新的会话：随后发往后端的音频都带上新的会话序号，上一会话迟到的识别结果将被丢弃
+    fn begin_session(&mut self, socket_manager: &mut SocketManager) {
+        self.session_id += 1;
+        socket_manager.session_id = self.session_id;
+        socket_manager.spans.begin(self.session_id);
+        socket_manager.first_response = FirstResponse::AwaitingAudio;
     }
     
     fn set_app_handle(&mut self, handle: tauri::AppHandle) {
@@ -136,30 +560,33 @@ impl VadStateMachine {
     }
     
     fn process_event(&mut self, event: VadStateMachineEvent, socket_manager: &mut SocketManager) -> bool {
+        self.process_event_at(event, socket_manager, Instant::now())
+    }
+    
+    // now 为事件发生的时刻：音频帧事件传入校正后的帧采集时刻，其余事件为当前时刻
+    fn process_event_at(&mut self, event: VadStateMachineEvent, socket_manager: &mut SocketManager, now: Instant) -> bool {
         let old_state = self.current_state.clone();
+        let old_visible_state = self.visible_state().clone();
 
-        // 临界状态超时检查
-        if self.current_state == VadState::TransitionBuffer {
-            if let Some(start_time) = self.transition_start_time {
-                if start_time.elapsed() > Duration::from_millis(TRANSITION_BUFFER_TIMEOUT_MS) {
-                    // //println!("[状态机] 临界转移 -> {:?} (超时)", self.last_user_visible_state);
-                    self.current_state = self.last_user_visible_state.clone();
-                    self.transition_start_time = None;
-                    self.stop_silence_reporting();
-                    // 恢复到之前的状态时，通常不应该再发送音频
-                    return false;
-                }
-            }
-        }
+        // 临界状态超时：无论收到什么事件都按 TransitionTimeout 处理，回到进入临界态之前的可见状态；
+        // 回退后计时清除，同一次临界态只会超时一次
+        let event = if self.transition_expired(now) {
+            VadStateMachineEvent::TransitionTimeout
+        } else {
+            event
+        };
         
         let should_send_to_python = match (&self.current_state, &event) {
             // ========== 初始状态的转移 ==========
             // 状态转移规则：on(麦克风一帧有声音) from(初始) to(临界转移)
             (VadState::Initial, VadStateMachineEvent::VoiceFrame) => {
-                // //println!("[状态机] 初始 -> 临界转移 (检测到语音)");
+                // //debug!("[状态机] 初始 -> 临界转移 (检测到语音)");
                 self.last_user_visible_state = self.current_state.clone(); // 保存上一个可见状态
                 self.current_state = VadState::TransitionBuffer;
-                self.transition_start_time = Some(Instant::now()); // 记录进入临界态的时间
+                self.transition_start_time = Some(now); // 记录进入临界态的时间
+                self.begin_session(socket_manager);
+                self.turn_index = 0;
+                self.utterance_start_time = Some(now);
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
                 true // 开始发送音频帧到Python，尝试获取识别结果
@@ -167,7 +594,7 @@ impl VadStateMachine {
             
             // 状态转移规则：on(后端音频开始播放) from(初始) to(听音中)
             (VadState::Initial, VadStateMachineEvent::AudioPlaybackStart) => {
-                // //println!("[状态机] 初始 -> 听音中 (后端音频开始播放)");
+                // //debug!("[状态机] 初始 -> 听音中 (后端音频开始播放)");
                 self.current_state = VadState::Listening;
                 self.stop_silence_reporting();
                 false // 不发送音频帧
@@ -175,7 +602,7 @@ impl VadStateMachine {
             
             // ========== 临界转移状态的转移 ==========
             (VadState::TransitionBuffer, &VadStateMachineEvent::BackendReturnText) => {
-                // //println!("[状态机] 临界转移 -> 说话中 (后端返回识别文本，确认有效语音)");
+                // //debug!("[状态机] 临界转移 -> 说话中 (后端返回识别文本，确认有效语音)");
                 self.current_state = VadState::Speaking;
                 self.transition_start_time = None; // 退出临界态，清除计时器
                 self.silence_frames_count = 0;
@@ -183,28 +610,36 @@ impl VadStateMachine {
             },
             (VadState::TransitionBuffer, &VadStateMachineEvent::BackendEndSession) |
             (VadState::TransitionBuffer, &VadStateMachineEvent::BackendResetToInitial) => {
-                //println!("[状态机] 临界转移 -> 初始 (会话重置)");
+                //debug!("[状态机] 临界转移 -> 初始 (会话重置)");
                 self.current_state = VadState::Initial;
                 self.transition_start_time = None;
                 false
             },
             (VadState::TransitionBuffer, &VadStateMachineEvent::AudioPlaybackStart) => {
-                //println!("[状态机] 临界转移 -> 听音中 (后端音频开始播放)");
+                //debug!("[状态机] 临界转移 -> 听音中 (后端音频开始播放)");
                 self.current_state = VadState::Listening;
                 self.transition_start_time = None;
                 self.stop_silence_reporting();
                 false
             },
+            // 没有后端确认时按本地规则：临界态持续有语音超过 LOCAL_CONFIRM_MS 即进入说话中
+            (VadState::TransitionBuffer, &VadStateMachineEvent::VoiceFrame) if self.local_confirmed(now) => {
+                self.current_state = VadState::Speaking;
+                self.transition_start_time = None;
+                self.silence_frames_count = 0;
+                true
+            },
             // 在临界状态时，对于语音和静音帧，保持当前状态并继续发送音频
             (VadState::TransitionBuffer, &VadStateMachineEvent::VoiceFrame) | 
             (VadState::TransitionBuffer, &VadStateMachineEvent::SilenceFrame) => {
                 true // 继续发送音频帧到Python，等待识别结果或超时
             },
             (VadState::TransitionBuffer, &VadStateMachineEvent::TransitionTimeout) => {
-                //println!("[状态机] 临界转移 -> {:?} (收到超时事件，恢复到原状态)", self.last_user_visible_state);
+                //debug!("[状态机] 临界转移 -> {:?} (超时，恢复到原状态)", self.last_user_visible_state);
                 self.current_state = self.last_user_visible_state.clone();
                 self.transition_start_time = None;
-                false // 停止发送音频帧
+                self.stop_silence_reporting();
+                false // 恢复到之前的状态时不再发送音频帧
             },
             (VadState::TransitionBuffer, &VadStateMachineEvent::AudioPlaybackEnd) => {
                 // 在临界态收到音频播放结束事件，保持状态
@@ -216,26 +651,36 @@ impl VadStateMachine {
             (VadState::Speaking, VadStateMachineEvent::SilenceFrame) => {
                 self.silence_frames_count += 1;
                 if self.silence_frames_count >= self.max_silence_frames {
-                    //println!("[状态机] 说话中 -> 等待中 (检测到{}帧连续静音)", self.silence_frames_count);
+                    //debug!("[状态机] 说话中 -> 等待中 (检测到{}帧连续静音)", self.silence_frames_count);
                     self.current_state = VadState::Waiting;
                     self.silence_frames_count = 0;
-                    self.start_silence_reporting();
+                    self.finish_utterance(now);
+                    self.request_finalize(socket_manager, now);
+                    self.start_silence_reporting(now);
                     false // 停止发送音频帧
+                } else if self.max_utterance_reached(now) {
+                    self.force_end_utterance(socket_manager, now);
+                    false
                 } else {
-                    //println!("[状态机] 说话中，静音帧计数: {}/{}", self.silence_frames_count, self.max_silence_frames);
+                    //debug!("[状态机] 说话中，静音帧计数: {}/{}", self.silence_frames_count, self.max_silence_frames);
                     true // 继续发送音频帧(包括静音帧以保持连续性)
                 }
             },
             
             // 在说话中状态继续有语音帧
             (VadState::Speaking, VadStateMachineEvent::VoiceFrame) => {
-                self.silence_frames_count = 0; // 重置静音帧计数
-                true // 继续发送音频帧到Python
+                if self.max_utterance_reached(now) {
+                    self.force_end_utterance(socket_manager, now);
+                    false
+                } else {
+                    self.silence_frames_count = 0; // 重置静音帧计数
+                    true // 继续发送音频帧到Python
+                }
             },
             
             // 在说话中状态收到后端结束session事件
             (VadState::Speaking, VadStateMachineEvent::BackendEndSession) => {
-                //println!("[状态机] 说话中 -> 初始 (后端结束session)");
+                //debug!("[状态机] 说话中 -> 初始 (后端结束session)");
                 self.current_state = VadState::Initial;
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
@@ -244,7 +689,7 @@ impl VadStateMachine {
             
             // 在说话中状态收到后端重置请求
             (VadState::Speaking, VadStateMachineEvent::BackendResetToInitial) => {
-                //println!("[状态机] 说话中 -> 初始 (后端请求重置到初始状态)");
+                //debug!("[状态机] 说话中 -> 初始 (后端请求重置到初始状态)");
                 self.current_state = VadState::Initial;
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
@@ -253,7 +698,7 @@ impl VadStateMachine {
             
             // 在说话中状态收到音频播放事件
             (VadState::Speaking, VadStateMachineEvent::AudioPlaybackStart) => {
-                //println!("[状态机] 说话中 -> 听音中 (后端音频开始播放)");
+                //debug!("[状态机] 说话中 -> 听音中 (后端音频开始播放)");
                 self.current_state = VadState::Listening;
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
@@ -262,19 +707,21 @@ impl VadStateMachine {
             
             // 说话中状态忽略TransitionTimeout事件
             (VadState::Speaking, VadStateMachineEvent::TransitionTimeout) => {
-                //println!("[状态机] 说话中状态忽略超时事件");
+                //debug!("[状态机] 说话中状态忽略超时事件");
                 true // 继续发送音频帧
             },
             
             // ========== 等待中状态的转移 ==========
             // 状态转移规则：on(麦克风一帧有声音) from(等待中) to(临界转移)
             (VadState::Waiting, VadStateMachineEvent::VoiceFrame) => {
-                //println!("[状态机] 等待中 -> 临界转移 (重新检测到语音，发送前置上下文帧)");
+                //debug!("[状态机] 等待中 -> 临界转移 (重新检测到语音，发送前置上下文帧)");
                 // 发送前置上下文帧
+                self.begin_session(socket_manager);
                 socket_manager.send_pre_context_frames();
                 self.last_user_visible_state = self.current_state.clone(); // 保存上一个可见状态
                 self.current_state = VadState::TransitionBuffer;
-                self.transition_start_time = Some(Instant::now()); // 记录进入临界态的时间
+                self.transition_start_time = Some(now); // 记录进入临界态的时间
+                self.utterance_start_time = Some(now);
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
                 true // 重新开始发送音频帧到Python
@@ -287,7 +734,7 @@ impl VadStateMachine {
             
             // 状态转移规则：on(后端结束session) from(等待中) to(初始)
             (VadState::Waiting, VadStateMachineEvent::BackendEndSession) => {
-                //println!("[状态机] 等待中 -> 初始 (后端结束session)");
+                //debug!("[状态机] 等待中 -> 初始 (后端结束session)");
                 self.current_state = VadState::Initial;
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
@@ -296,7 +743,7 @@ impl VadStateMachine {
             
             // 等待中状态收到后端重置请求
             (VadState::Waiting, VadStateMachineEvent::BackendResetToInitial) => {
-                //println!("[状态机] 等待中 -> 初始 (后端请求重置到初始状态)");
+                //debug!("[状态机] 等待中 -> 初始 (后端请求重置到初始状态)");
                 self.current_state = VadState::Initial;
                 self.silence_frames_count = 0;
                 self.stop_silence_reporting();
@@ -305,7 +752,7 @@ impl VadStateMachine {
             
             // 等待中状态收到音频播放开始
             (VadState::Waiting, VadStateMachineEvent::AudioPlaybackStart) => {
-                //println!("[状态机] 等待中 -> 听音中 (后端音频开始播放)");
+                //debug!("[状态机] 等待中 -> 听音中 (后端音频开始播放)");
                 self.current_state = VadState::Listening;
                 self.stop_silence_reporting();
                 false // 不发送音频帧
@@ -313,19 +760,21 @@ impl VadStateMachine {
             
             // 等待中状态忽略TransitionTimeout事件
             (VadState::Waiting, VadStateMachineEvent::TransitionTimeout) => {
-                //println!("[状态机] 等待中状态忽略超时事件");
+                //debug!("[状态机] 等待中状态忽略超时事件");
                 true // 继续静音上报
             },
             
             // ========== 听音中状态的转移 ==========
             // 状态转移规则：on(麦克风一帧有声音) from(听音中) to(临界转移) - 用户打断
             (VadState::Listening, VadStateMachineEvent::VoiceFrame) => {
-                //println!("[状态机] 听音中 -> 临界转移 (用户打断，检测到语音)");
+                //debug!("[状态机] 听音中 -> 临界转移 (用户打断，检测到语音)");
                 self.last_user_visible_state = self.current_state.clone(); // 保存上一个可见状态
                 self.current_state = VadState::TransitionBuffer;
-                self.transition_start_time = Some(Instant::now()); // 记录进入临界态的时间
+                self.transition_start_time = Some(now); // 记录进入临界态的时间
+                self.utterance_start_time = Some(now);
                 self.silence_frames_count = 0;
                 // 发送前置上下文帧
+                self.begin_session(socket_manager);
                 socket_manager.send_pre_context_frames();
                 true // 开始发送音频帧
             },
@@ -337,34 +786,34 @@ impl VadStateMachine {
             
             // 状态转移规则：on(后端音频播放结束) from(听音中) to(初始)
             (VadState::Listening, VadStateMachineEvent::AudioPlaybackEnd) => {
-                //println!("[状态机] 听音中 -> 初始 (后端音频播放结束)");
+                //debug!("[状态机] 听音中 -> 初始 (后端音频播放结束)");
                 self.current_state = VadState::Initial;
                 false // 不发送音频帧
             },
             
             // 在听音中状态的后端结束session
             (VadState::Listening, VadStateMachineEvent::BackendEndSession) => {
-                //println!("[状态机] 听音中 -> 初始 (后端结束session)");
+                //debug!("[状态机] 听音中 -> 初始 (后端结束session)");
                 self.current_state = VadState::Initial;
                 false // 停止所有处理
             },
             
             // 在听音中状态的后端重置请求
             (VadState::Listening, VadStateMachineEvent::BackendResetToInitial) => {
-                //println!("[状态机] 听音中 -> 初始 (后端请求重置)");
+                //debug!("[状态机] 听音中 -> 初始 (后端请求重置)");
                 self.current_state = VadState::Initial;
                 false // 停止所有处理
             },
             
             // 在听音中状态收到音频播放开始 - 保持状态
             (VadState::Listening, VadStateMachineEvent::AudioPlaybackStart) => {
-                //println!("[状态机] 保持听音中状态 (音频已在播放)");
+                //debug!("[状态机] 保持听音中状态 (音频已在播放)");
                 false // 继续不发送音频帧
             },
             
             // 听音中状态忽略TransitionTimeout事件
             (VadState::Listening, VadStateMachineEvent::TransitionTimeout) => {
-                //println!("[状态机] 听音中状态忽略超时事件");
+                //debug!("[状态机] 听音中状态忽略超时事件");
                 false // 继续不发送音频帧
             },
             
@@ -381,28 +830,58 @@ impl VadStateMachine {
             
             // 后端请求重置到初始状态事件 - 从初始状态
             (VadState::Initial, VadStateMachineEvent::BackendResetToInitial) => {
-                //println!("[状态机] 初始 -> 初始 (后端请求重置，已在初始状态)");
+                //debug!("[状态机] 初始 -> 初始 (后端请求重置，已在初始状态)");
                 false // 已在初始状态，无需处理
             },
             
             // 初始状态忽略TransitionTimeout事件
             (VadState::Initial, VadStateMachineEvent::TransitionTimeout) => {
-                //println!("[状态机] 初始状态忽略超时事件");
+                //debug!("[状态机] 初始状态忽略超时事件");
                 false // 保持初始状态
             },
             
             // 其他状态收到音频播放结束事件 - 忽略
             (state, VadStateMachineEvent::AudioPlaybackEnd) => {
                 if *state != VadState::Listening && *state != VadState::TransitionBuffer {
-                    //println!("[状态机] 状态 {:?} 忽略音频播放结束事件", state);
+                    //debug!("[状态机] 状态 {:?} 忽略音频播放结束事件", state);
                 }
                 false // 保持当前状态的行为
             },
             
+            // ========== 按键说话 ==========
+            // 状态转移规则：on(按下) from(任意) to(说话中)，不经过临界态，立即发送前置上下文帧
+            (VadState::Speaking, VadStateMachineEvent::PttPressed) => true,
+            (state, VadStateMachineEvent::PttPressed) => {
+                if *state == VadState::Initial {
+                    self.turn_index = 0;
+                }
+                self.begin_session(socket_manager);
+                self.current_state = VadState::Speaking;
+                self.transition_start_time = None;
+                self.utterance_start_time = Some(now);
+                self.silence_frames_count = 0;
+                self.stop_silence_reporting();
+                socket_manager.send_pre_context_frames();
+                true
+            },
+            // 状态转移规则：on(松开) from(说话中/临界转移) to(等待中)
+            (VadState::Speaking, VadStateMachineEvent::PttReleased) |
+            (VadState::TransitionBuffer, VadStateMachineEvent::PttReleased) => {
+                self.current_state = VadState::Waiting;
+                self.transition_start_time = None;
+                self.silence_frames_count = 0;
+                self.finish_utterance(now);
+                self.request_finalize(socket_manager, now);
+                self.start_silence_reporting(now);
+                false
+            },
+            (VadState::Waiting, VadStateMachineEvent::PttReleased) => true,
+            (_, VadStateMachineEvent::PttReleased) => false,
+            
             // 处理其他状态收到后端返回文本事件 - 只有临界转移状态关心此事件
             (state, VadStateMachineEvent::BackendReturnText) => {
                 if *state != VadState::TransitionBuffer {
-                    //println!("[状态机] 忽略后端返回文本事件 (当前状态: {:?})", state);
+                    //debug!("[状态机] 忽略后端返回文本事件 (当前状态: {:?})", state);
                 }
                 match state {
                     VadState::Speaking => true, // 在说话状态继续发送
@@ -412,13 +891,41 @@ impl VadStateMachine {
         };
         
         if old_state != self.current_state {
-            //println!("[状态机] 状态变更: {:?} -> {:?}", old_state, self.current_state);
+            debug!("[状态机] 状态变更: {:?} -> {:?}", old_state, self.current_state);
+
+            // 调试构建下核对转移表，保证导出的状态图与实现一致
+            #[cfg(debug_assertions)]
+            if !state_diagram::is_listed(&old_state, &event, &self.current_state) {
+                warn!("[警告] 状态转移 {:?} --{:?}--> {:?} 未登记在状态图转移表中", old_state, event, self.current_state);
+            }
             
             // 通知前端状态变化，但对临界态特殊处理
             if let Some(app_handle) = &self.app_handle {
+                let elapsed_ms = Instant::now().saturating_duration_since(now).as_millis() as u64;
+                let transition = StateTransition {
+                    from: format!("{:?}", old_state),
+                    to: format!("{:?}", self.current_state),
+                    trigger: format!("{:?}", event),
+                    at_ms: journal::now_unix_ms().saturating_sub(elapsed_ms),
+                    silence_frames: self.silence_frames_count,
+                };
+                if let Err(e) = app_handle.emit("vad-state-debug", transition.clone()) {
+                    error!("[错误] 发送状态转移调试事件到前端失败: {}", e);
+                }
+                
                 // 如果新状态是临界态，不向前端发送状态变更通知
                 // 这样前端会保持显示上一个状态，对临界态无感知
                 if self.current_state != VadState::TransitionBuffer {
+                    let visible_transition = StateTransition {
+                        from: format!("{:?}", old_visible_state),
+                        ..transition
+                    };
+                    if let Err(e) = app_handle.emit("vad-state-transition", visible_transition) {
+                        error!("[错误] 发送状态转移事件到前端失败: {}", e);
+                    }
+                }
+                
+                if self.current_state != VadState::TransitionBuffer && self.legacy_state_events {
                     let state_str = match self.current_state {
                         VadState::Initial => "Initial",
                         VadState::Speaking => "Speaking",
@@ -428,86 +935,284 @@ impl VadStateMachine {
                     };
                     
                     if let Err(e) = app_handle.emit("vad-state-changed", state_str) {
-                        println!("[错误] 发送状态变化事件到前端失败: {}", e);
+                        error!("[错误] 发送状态变化事件到前端失败: {}", e);
                     }
                 }
             }
+            
+            // 回到初始状态即一个会话结束，统计本次会话的输入响度
+            if self.current_state == VadState::Initial {
+                self.finish_loudness_session();
+            }
         }
         
         should_send_to_python
     }
     
-    fn start_silence_reporting(&mut self) {
-        self.silence_start_time = Some(Instant::now());
+    // silence_start 为静音开始的时刻，上报的静音时长从该时刻起算
+    fn start_silence_reporting(&mut self, silence_start: Instant) {
+        self.silence_start_time = Some(silence_start);
         
         if let Some(app_handle) = &self.app_handle {
             let app_handle_clone = app_handle.clone();
+            let report_interval_ms = self.silence_report_interval_ms;
+            let socket_manager = Arc::clone(&self.socket_manager);
+            let threshold = Arc::clone(&self.speech_end_threshold_ms);
+            // 上下文在静音开始时由状态机填充，上报时只更新静音时长
+            let mut context = self.silence_context(0);
             let handle = tokio::spawn(async move {
-                let mut interval = tokio::time::interval(Duration::from_millis(SILENCE_REPORT_INTERVAL_MS));
-                let start_time = Instant::now();
+                let mut interval = tokio::time::interval(Duration::from_millis(report_interval_ms));
+                let start_time = silence_start;
                 
                 loop {
                     interval.tick().await;
                     let silence_duration = start_time.elapsed().as_millis() as u64;
+                    context.silence_ms = silence_duration;
                     
+                    // 阈值可能在静音期间被后端hint调整，每次上报时重新读取
+                    let threshold_ms = threshold.load(Ordering::Relaxed).max(1);
                     let silence_event = SilenceEvent {
-                        silence_ms: silence_duration,
+                        context: context.clone(),
+                        threshold_ms,
+                        progress_percent: (silence_duration as f64 * 100.0 / threshold_ms as f64).min(100.0),
                     };
                     
                     // 发送到前端
                     if let Err(e) = app_handle_clone.emit("silence-event", &silence_event) {
-                        println!("[错误] 发送静音事件到前端失败: {}", e);
+                        error!("[错误] 发送静音事件到前端失败: {}", e);
                         break;
                     }
                     
                     // 同时发送到后端
-                    Self::send_silence_to_backend(silence_duration);
+                    Self::send_silence_to_backend(&app_handle_clone, &socket_manager, &context);
                     
-                    // //println!("[状态机] 发送静音事件: {}ms", silence_duration);
+                    // //debug!("[状态机] 发送静音事件: {}ms", silence_duration);
                 }
             });
             
             self.silence_timer_handle = Some(handle);
-            //println!("[状态机] 开始静音上报定时器");
+            //debug!("[状态机] 开始静音上报定时器");
+        }
+    }
+    
+    // 一段发言结束（进入等待状态），记录时长
+    fn finish_utterance(&mut self, now: Instant) {
+        self.turn_index += 1;
+        self.last_utterance_duration_ms = self.utterance_start_time
+            .map_or(0, |start| now.saturating_duration_since(start).as_millis() as u64);
+    }
+    
+    fn max_utterance_reached(&self, now: Instant) -> bool {
+        self.max_utterance_seconds > 0
+            && matches!(
+                self.utterance_start_time,
+                Some(start) if now.saturating_duration_since(start) >= Duration::from_secs(self.max_utterance_seconds)
+            )
+    }
+    
+    // 连续说话达到最长发言时长（如持续的电视人声）时强制结束本段发言，进入等待状态，
+    // 通知后端本段已结束，避免音频无限期地发往后端
+    // 本地规则确认临界态中的语音有效，供不连接后端的模式使用
+    fn local_confirmed(&self, now: Instant) -> bool {
+        self.local_confirmation
+            && matches!(
+                self.transition_start_time,
+                Some(start) if now.saturating_duration_since(start) >= Duration::from_millis(LOCAL_CONFIRM_MS)
+            )
+    }
+    
+    fn force_end_utterance(&mut self, socket_manager: &mut SocketManager, now: Instant) {
+        self.current_state = VadState::Waiting;
+        self.silence_frames_count = 0;
+        self.finish_utterance(now);
+        let duration_ms = self.last_utterance_duration_ms;
+        warn!("[警告] 连续说话{}ms，达到最长发言时长{}秒，强制结束本段发言", duration_ms, self.max_utterance_seconds);
+        
+        // 协商版本不支持发言结束消息时只在本地结束；支持最终识别请求的版本一定支持发言结束消息，无需退回
+        if socket_manager.supports_end_of_utterance() {
+            socket_manager.send_end_of_utterance(protocol::END_OF_UTTERANCE_MAX_DURATION, duration_ms);
+        } else {
+            debug!("[调试] 后端协议版本不支持发言结束消息，仅在本地结束本段发言");
+        }
+        
+        if let Some(app_handle) = &self.app_handle {
+            let event = MaxUtteranceReached {
+                duration_ms,
+                max_utterance_seconds: self.max_utterance_seconds,
+                session_id: self.session_id,
+                turn_index: self.turn_index,
+            };
+            if let Err(e) = app_handle.emit("max-utterance-reached", &event) {
+                error!("[错误] 发送最长发言时长事件到前端失败: {}", e);
+            }
+        }
+        self.start_silence_reporting(now);
+    }
+    
+    fn silence_context(&self, silence_ms: u64) -> SilenceContext {
+        SilenceContext {
+            silence_ms,
+            state: self.current_state.clone(),
+            session_id: self.session_id,
+            last_utterance_duration_ms: self.last_utterance_duration_ms,
+            turn_index: self.turn_index,
         }
     }
     
     fn stop_silence_reporting(&mut self) {
         if let Some(handle) = self.silence_timer_handle.take() {
             handle.abort();
-            //println!("[状态机] 停止静音上报定时器");
+            //debug!("[状态机] 停止静音上报定时器");
         }
         self.silence_start_time = None;
     }
     
     fn reset_to_initial(&mut self) {
-        //println!("[状态机] 重置到初始状态");
+        //debug!("[状态机] 重置到初始状态");
         self.current_state = VadState::Initial;
         self.stop_silence_reporting();
         self.silence_frames_count = 0;
         self.transition_start_time = None;
+        self.pending_finalize = None;
+        self.input_level.discard_session();
+    }
+    
+    // 说话因静音结束，音频流停止前请求后端立即输出最终识别结果
+    fn request_finalize(&mut self, socket_manager: &mut SocketManager, now: Instant) {
+        if !self.finalize_on_silence || socket_manager.local_only {
+            return;
+        }
+        
+        self.next_finalize_id += 1;
+        if socket_manager.send_finalize_request(self.next_finalize_id) {
+            self.pending_finalize = Some((self.next_finalize_id, now));
+        } else if socket_manager.supports_finalize() {
+            warn!("[警告] 发送最终识别请求失败");
+        }
+    }
+    
+    // 收到最终识别结果，结束等待
+    fn finalize_received(&mut self, now: Instant) -> Option<FinalizeOutcome> {
+        let (finalize_id, requested_at) = self.pending_finalize.take()?;
+        Some(FinalizeOutcome {
+            finalize_id,
+            received_final: true,
+            waited_ms: now.saturating_duration_since(requested_at).as_millis() as u64,
+        })
+    }
+    
+    // 等待最终识别结果超时，放弃等待
+    fn check_finalize_timeout(&mut self, now: Instant) -> Option<FinalizeOutcome> {
+        let (finalize_id, requested_at) = self.pending_finalize?;
+        let waited = now.saturating_duration_since(requested_at);
+        if waited <= Duration::from_millis(self.finalize_timeout_ms) {
+            return None;
+        }
+        
+        self.pending_finalize = None;
+        warn!("[警告] 等待最终识别结果超时 ({}ms)", waited.as_millis());
+        Some(FinalizeOutcome {
+            finalize_id,
+            received_final: false,
+            waited_ms: waited.as_millis() as u64,
+        })
+    }
+    
+    fn finish_loudness_session(&mut self) {
+        let summary = match self.input_level.finish_session() {
+            Some(summary) => summary,
+            None => return,
+        };
+        
+        if let Some(app_handle) = &self.app_handle {
+            if let Err(e) = app_handle.emit("session-loudness-summary", &summary) {
+                error!("[错误] 发送会话响度统计到前端失败: {}", e);
+            }
+            
+            if summary.is_low {
+                warn!("[警告] 本次会话输入音量偏低: 中位RMS {:.4}，参考值 {:.4}", summary.median_rms, summary.reference_rms);
+                if let Err(e) = app_handle.emit("low-input-level-hint", &summary) {
+                    error!("[错误] 发送低输入音量提示到前端失败: {}", e);
+                }
+            }
+        }
     }
     
     fn get_current_state(&self) -> &VadState {
         &self.current_state
     }
+    
+    // 对用户可见的状态：临界态对前端透明，返回上一个可见状态
+    fn visible_state(&self) -> &VadState {
+        match self.current_state {
+            VadState::TransitionBuffer => &self.last_user_visible_state,
+            ref state => state,
+        }
+    }
+}
+
+// 一个完整的语音段
+struct VoiceSegment {
+    id: u64,                     // 语音段序号，与对话轮次中的 audio_segment_ids 对应
+    samples: Vec<i16>,
+    started_at: Option<Instant>, // 第一帧的采集时刻，未记录时为None
+}
+
+// 一个发送到后端的音频段
+struct SentSegment {
+    samples: Vec<i16>,
+    voice_segment_id: u64, // 发送时正在收集的语音段序号，用于级联删除；手动创建的测试段为0
 }
 
 // 线程安全的Socket连接管理器
+// 首响应延迟：从本会话送出第一帧音频到收到第一个非空识别文本
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FirstResponse {
+    Idle,          // 尚未开始会话
+    AwaitingAudio, // 会话已开始，还没有音频发出
+    Sent(Instant), // 第一帧音频发出的时刻
+    Responded,     // 本会话已统计过
+}
+
 struct SocketManager {
     stream: Option<PlatformStream>,
     last_reconnect_attempt: Instant,
-    buffer: Vec<i16>,
-    is_buffering: bool,
-    speech_segments: Vec<Vec<i16>>,
-    samples_since_last_send: usize, // 跟踪自上次发送后累积的样本数
-    complete_speech_segments: Vec<Vec<i16>>, // 存储完整的语音段，用于回放功能
+    reconnect_delay_ms: u64,     // 距上次尝试多久后才允许再次重连，连接失败时指数增长
+    reconnect_attempts: u32,     // 连续失败的重连次数
+    speech_segments: Vec<Vec<i16>>, // 发送失败、等待按原顺序补发的段（已经过处理链并保存）
+    complete_speech_segments: VecDeque<VoiceSegment>, // 存储完整的语音段，用于回放功能
     current_voice_segment: Vec<i16>, // 用于收集当前的语音帧
+    current_voice_segment_start: Option<Instant>, // 当前语音段第一帧的采集时刻
     frames_without_voice: usize,     // 跟踪连续无语音的帧数
-    sent_to_python_segments: Vec<Vec<i16>>, // 存储发送到Python的音频段
+    sent_to_python_segments: VecDeque<SentSegment>, // 存储发送到Python的音频段
+    first_sent_segment_id: u64,             // sent_to_python_segments[0] 的段id，删除段时递增
+    thumbnails: ThumbnailCache,             // 回放段的波形缩略图缓存
     // 新增：前置缓冲区，用于保存语音开始前的几帧
-    pre_context_frames: Vec<Vec<i16>>,
+    pre_context_frames: VecDeque<Vec<i16>>,
     max_pre_context_frames: usize,
+    sample_format: SampleFormat, // 发送到后端的样本格式，编码为PCM时生效
+    opus: Option<OpusEncoder>,   // 编码为Opus时的编码器，None为PCM
+    sample_rate: u32,            // 缓存的语音段的采样率，与VAD处理器一致
+    pipeline: Pipeline,          // 发送前对音频帧做的处理链
+    pre_context_fade_ms: u64,    // 前置上下文淡入时长
+    endpoints: BackendEndpoints, // 后端各通道端点
+    legacy_fallback: bool,       // 默认端点连不上时是否回退到旧版/tmp路径
+    connected_endpoint: Option<String>, // 当前连接实际使用的端点
+    next_voice_segment_id: u64,
+    finished_segment_ids: Vec<u64>, // 新完成的语音段序号，等待归入对话轮次
+    trace: ProtocolTrace,           // 协议级收发记录，默认关闭
+    send_queue: Option<SendQueue>,  // 持久化待发队列，None为关闭
+    wav_stream: Option<StreamingWav>, // 流式WAV录制，None为未录制
+    protocol_version: Option<u32>,  // 与后端协商出的协议版本，握手确认之前为None
+    handshake_sent_at: Option<Instant>, // 握手已发出、尚未收到确认
+    handshake_ack: Vec<u8>,         // 已收到的握手确认字节
+    errors: PendingErrors,          // 连接与发送错误，由持有app_handle的调用方取走后通知前端
+    local_only: bool,               // 仅本地模式：不建立连接，所有发送路径都被禁用，音频不出设备
+    heartbeat_interval_secs: u64,   // 心跳间隔，0为关闭
+    last_heartbeat: Instant,
+    session_id: u64,                // 当前音频所属的会话序号，由状态机在开始新会话时设置
+    spans: SessionTracer,           // 当前会话的链路追踪，未启用otel feature时为空操作
+    first_response: FirstResponse,  // 本会话首响应延迟的计时
 }
 
 impl SocketManager {
@@ -515,47 +1220,80 @@ impl SocketManager {
         Self {
             stream: None,
             last_reconnect_attempt: Instant::now(),
-            buffer: Vec::with_capacity(8000), // 约0.5秒的音频
-            is_buffering: false,
+            reconnect_delay_ms: RECONNECT_INTERVAL_MS,
+            reconnect_attempts: 0,
             speech_segments: Vec::new(),
-            samples_since_last_send: 0,
-            complete_speech_segments: Vec::new(), // 初始化完整语音段存储
+            complete_speech_segments: VecDeque::new(), // 初始化完整语音段存储
             current_voice_segment: Vec::new(),  // 初始化当前语音段
+            current_voice_segment_start: None,
             frames_without_voice: 0,            // 初始化无语音帧计数器
-            sent_to_python_segments: Vec::new(), // 初始化发送到Python的音频段
-            pre_context_frames: Vec::new(),     // 前置缓冲区
-            max_pre_context_frames: 5,         // 5(100ms)作为上下文
+            sent_to_python_segments: VecDeque::new(), // 初始化发送到Python的音频段
+            first_sent_segment_id: 0,
+            thumbnails: ThumbnailCache::new(),
+            pre_context_frames: VecDeque::new(), // 前置缓冲区
+            max_pre_context_frames: params::ms_to_frames((DEFAULT_PRE_CONTEXT_MS + DEFAULT_MIN_SPEECH_MS) as f64, DEFAULT_FRAME_MS),
+            sample_format: SampleFormat::Pcm16,
+            opus: None,
+            sample_rate: SAMPLE_RATE,
+            pipeline: Pipeline::new(SAMPLE_RATE),
+            pre_context_fade_ms: DEFAULT_PRE_CONTEXT_FADE_MS,
+            endpoints: BackendEndpoints::default_for_platform(),
+            legacy_fallback: true,
+            connected_endpoint: None,
+            next_voice_segment_id: 1,
+            finished_segment_ids: Vec::new(),
+            trace: ProtocolTrace::new(),
+            wav_stream: None,
+            send_queue: None,
+            protocol_version: None,
+            handshake_sent_at: None,
+            handshake_ack: Vec::new(),
+            errors: PendingErrors::new(),
+            local_only: false,
+            heartbeat_interval_secs: DEFAULT_HEARTBEAT_INTERVAL_SECS,
+            last_heartbeat: Instant::now(),
+            session_id: 0,
+            spans: SessionTracer::new(),
+            first_response: FirstResponse::Idle,
         }
     }
 
     #[cfg(unix)]
     fn connect(&mut self) -> bool {
+        // 所有写入都要先连上，在这里拦下即禁用了全部发送路径
+        if self.local_only {
+            return false;
+        }
         if self.stream.is_some() {
-            return true;
+            return self.poll_handshake();
         }
 
         // 控制重连频率
-        let now = Instant::now();
-        if now.duration_since(self.last_reconnect_attempt) < Duration::from_millis(RECONNECT_INTERVAL_MS) {
+        if !self.reconnect_due() {
             return false;
         }
-        self.last_reconnect_attempt = now;
 
-        println!("[调试] 尝试连接UnixSocket: {}", SOCKET_PATH);
-        match UnixStream::connect(SOCKET_PATH) {
-            Ok(stream) => {
-                println!("[重要] UnixSocket连接成功到Python后端！");
+        debug!("[调试] 尝试连接UnixSocket: {}", self.endpoints.stt);
+        match endpoints::connect(&self.endpoints, EndpointKind::Stt, self.legacy_fallback) {
+            Ok((stream, endpoint)) => {
+                info!("[重要] UnixSocket连接成功到Python后端！");
                 stream.set_nonblocking(true).unwrap_or_else(|e| {
-                    println!("[警告] 设置非阻塞模式失败: {}", e);
+                    warn!("[警告] 设置非阻塞模式失败: {}", e);
                 });
                 stream.set_write_timeout(Some(Duration::from_millis(50))).unwrap_or_else(|e| {
-                    println!("[警告] 设置写入超时失败: {}", e);
+                    warn!("[警告] 设置写入超时失败: {}", e);
                 });
                 self.stream = Some(stream);
-                true
+                self.connected_endpoint = Some(endpoint);
+                self.start_handshake()
             },
             Err(e) => {
-                println!("[错误] UnixSocket连接失败: {} (Python后端可能未启动或Socket权限问题)", e);
+                self.back_off();
+                error!(
+                    "[错误] UnixSocket连接失败: {} (Python后端可能未启动或Socket权限问题)，{}ms后重试",
+                    e, self.reconnect_delay_ms
+                );
+                self.report_connect_failure(&e);
                 self.stream = None;
                 false
             }
@@ -564,220 +1302,644 @@ impl SocketManager {
     
     #[cfg(windows)]
     fn connect(&mut self) -> bool {
+        // 所有写入都要先连上，在这里拦下即禁用了全部发送路径
+        if self.local_only {
+            return false;
+        }
         if self.stream.is_some() {
-            return true;
+            return self.poll_handshake();
         }
 
         // 控制重连频率
-        let now = Instant::now();
-        if now.duration_since(self.last_reconnect_attempt) < Duration::from_millis(RECONNECT_INTERVAL_MS) {
+        if !self.reconnect_due() {
             return false;
         }
-        self.last_reconnect_attempt = now;
 
-        println!("[调试] 尝试连接TCP服务器: {}", TCP_ADDRESS);
-        match TCP_ADDRESS.parse::<SocketAddr>() {
-            Ok(addr) => {
-                match TcpStream::connect_timeout(&addr, Duration::from_millis(500)) {
-                    Ok(stream) => {
-                        println!("[调试] TCP连接成功");
-                        stream.set_nonblocking(true).unwrap_or_else(|e| {
-                            println!("[警告] 设置非阻塞模式失败: {}", e);
-                        });
-                        stream.set_write_timeout(Some(Duration::from_millis(50))).unwrap_or_else(|e| {
-                            println!("[警告] 设置写入超时失败: {}", e);
-                        });
-                        self.stream = Some(stream);
-                        true
-                    },
-                    Err(e) => {
-                        println!("[错误] TCP连接失败: {}", e);
-                        self.stream = None;
-                        false
-                    }
-                }
+        debug!("[调试] 尝试连接后端: {}", self.endpoints.stt);
+        match endpoints::connect(&self.endpoints, EndpointKind::Stt, self.legacy_fallback) {
+            Ok((stream, endpoint)) => {
+                debug!("[调试] 连接成功: {}", endpoint);
+                stream.set_nonblocking(true).unwrap_or_else(|e| {
+                    warn!("[警告] 设置非阻塞模式失败: {}", e);
+                });
+                stream.set_write_timeout(Some(Duration::from_millis(50))).unwrap_or_else(|e| {
+                    warn!("[警告] 设置写入超时失败: {}", e);
+                });
+                self.stream = Some(stream);
+                self.connected_endpoint = Some(endpoint);
+                self.start_handshake()
             },
             Err(e) => {
-                println!("[错误] 解析TCP地址失败: {}", e);
+                self.back_off();
+                error!("[错误] 连接后端失败: {}，{}ms后重试", e, self.reconnect_delay_ms);
+                self.report_connect_failure(&e);
+                self.stream = None;
                 false
             }
         }
     }
 
-    fn start_buffering(&mut self) {
-        if !self.is_buffering {
-            println!("[调试] 开始缓冲语音");
-            self.is_buffering = true;
-            self.buffer.clear();
-            self.samples_since_last_send = 0;
-        }
-    }
-
-    fn stop_buffering(&mut self) -> bool {
-        if self.is_buffering && !self.buffer.is_empty() {
-            println!("[调试] 停止缓冲语音，已缓冲{}个样本", self.buffer.len());
-            self.is_buffering = false;
-            
-            // 注意：此处不再将整体缓冲区添加到语音段，因为语音段现在由add_voice_frame专门处理
-            // 以下操作只用于完整录音的功能
-            
-            // 分批发送，每批不超过SEND_BUFFER_THRESHOLD个样本
-            let mut all_success = true;
-            let total_samples = self.buffer.len();
-            let mut samples_sent = 0;
-            
-            while samples_sent < total_samples {
-                // 计算当前批次的范围
-                let batch_size = std::cmp::min(SEND_BUFFER_THRESHOLD, total_samples - samples_sent);
-                let end_idx = samples_sent + batch_size;
-                
-                // 提取当前批次
-                let speech_segment = self.buffer[samples_sent..end_idx].to_vec();
-                
-                println!("[调试] 分批发送最终语音段 ({}/{}): {}个样本", 
-                    samples_sent + batch_size, total_samples, speech_segment.len());
-                
-                // 发送当前批次
-                if self.send_speech_segment(&speech_segment) {
-                    println!("[调试] 批次发送成功 ({}个样本)", speech_segment.len());
-                } else {
-                    println!("[警告] 批次发送失败，放入队列稍后重试");
-                    self.speech_segments.push(speech_segment);
-                    all_success = false;
-                }
-                
-                samples_sent += batch_size;
-            }
-            
-            // 清空缓冲区并重置计数器
-            self.buffer.clear();
-            self.samples_since_last_send = 0;
-            
-            println!("[调试] 最终语音段分批发送完成，总共{}个样本", total_samples);
-            return all_success;
+    // 新连接上先发握手，退避在握手确认后才清零，握手被拒绝时按连接失败退避重试
+    fn start_handshake(&mut self) -> bool {
+        self.protocol_version = None;
+        self.handshake_ack.clear();
+        self.handshake_sent_at = Some(Instant::now());
+        
+        let packet = protocol::encode_handshake(self.sample_rate);
+        let stream = match &mut self.stream {
+            Some(s) => s,
+            None => return false,
+        };
+        if let Err(e) = stream.write_all(&packet) {
+            error!("[错误] 发送协议握手失败: {}", e);
+            self.trace.record(Direction::Outgoing, TraceChannel::Stt, "handshake", packet.len(), false);
+            self.stream = None;
+            self.handshake_sent_at = None;
+            self.back_off();
+            self.report_connect_failure(&e);
+            return false;
         }
-        false
-    }
-
-    fn add_audio_samples(&mut self, samples: &[i16]) {
-        if self.is_buffering {
-            self.buffer.extend_from_slice(samples);
-            self.samples_since_last_send += samples.len();
-            
-            // 如果累积的样本数超过阈值，发送一部分并继续缓冲
-            if self.samples_since_last_send >= SEND_BUFFER_THRESHOLD {
-                // 只发送新累积的部分，不是整个缓冲区
-                let buffer_len = self.buffer.len();
-                let start_idx = buffer_len - self.samples_since_last_send;
-                let speech_segment = self.buffer[start_idx..].to_vec();
-                
-                println!("[调试] 累积样本数({}个)达到阈值，发送中间语音段", speech_segment.len());
-                
-                if self.send_speech_segment(&speech_segment) {
-                    // println!("[调试] 中间语音段发送成功 ({}个样本)", speech_segment.len());
-                } else {
-                    // 如果发送失败，将语音段放入队列，后续再尝试发送
-                    println!("[警告] 中间语音段发送失败，放入队列稍后重试");
-                    self.speech_segments.push(speech_segment);
-                }
-                
-                // 重置计数器并清空缓冲区
-                self.samples_since_last_send = 0;
-                self.buffer.clear();
-            }
+        self.trace.record(Direction::Outgoing, TraceChannel::Stt, "handshake", packet.len(), true);
+        if let Err(e) = stream.flush() {
+            warn!("[警告] 刷新协议握手缓冲区失败: {}", e);
         }
+        self.poll_handshake()
     }
-
-    fn send_speech_segment(&mut self, segment: &[i16]) -> bool {
-        if !self.connect() {
-            return false;
+    
+    // 非阻塞地读取握手确认，返回握手是否已完成；确认之前不发送任何音频与控制消息
+    fn poll_handshake(&mut self) -> bool {
+        if self.protocol_version.is_some() {
+            return true;
         }
-
+        let sent_at = match self.handshake_sent_at {
+            Some(t) => t,
+            None => return false,
+        };
         let stream = match &mut self.stream {
             Some(s) => s,
             None => return false,
         };
-
-        // println!("[调试] 发送语音段到Python ({}个样本)", segment.len());
         
-        // 保存发送到Python的音频段
-        if segment.len() > 0 {
-            // 克隆一份数据保存
-            let segment_clone = segment.to_vec();
-            self.sent_to_python_segments.push(segment_clone);
-            
-            // 限制保存的段数，防止内存占用过大
-            if self.sent_to_python_segments.len() > 50 {
-                self.sent_to_python_segments.remove(0);
+        let mut buf = [0u8; protocol::HANDSHAKE_ACK_LEN];
+        let mut read_error = None;
+        while self.handshake_ack.len() < protocol::HANDSHAKE_ACK_LEN {
+            let wanted = protocol::HANDSHAKE_ACK_LEN - self.handshake_ack.len();
+            match stream.read(&mut buf[..wanted]) {
+                Ok(0) => {
+                    read_error = Some("后端在握手期间关闭了连接".to_string());
+                    break;
+                },
+                Ok(n) => self.handshake_ack.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    read_error = Some(format!("读取握手确认失败: {}", e));
+                    break;
+                }
             }
-            
-            // println!("[调试] 已保存发送到Python的音频段，当前共有{}个段", self.sent_to_python_segments.len());
         }
-        
-        // 准备完整的数据包（长度头 + 音频数据）以确保原子性发送
-        let len_bytes = (segment.len() as u32).to_le_bytes();
-        let sample_bytes: Vec<u8> = segment.iter()
-            .flat_map(|&sample| sample.to_le_bytes().to_vec())
-            .collect();
-        
-        // 创建完整的数据包
-        let mut full_packet = Vec::with_capacity(4 + sample_bytes.len());
-        full_packet.extend_from_slice(&len_bytes);
-        full_packet.extend_from_slice(&sample_bytes);
-        
-        // 原子性发送完整数据包，避免部分写入导致的乱序
-        if let Err(e) = stream.write_all(&full_packet) {
-            // println!("[错误] 发送音频数据包失败: {}", e);
-            self.stream = None;
+        if let Some(reason) = read_error {
+            self.reject_handshake(reason);
             return false;
         }
         
+        if self.handshake_ack.len() < protocol::HANDSHAKE_ACK_LEN {
+            if sent_at.elapsed() > Duration::from_millis(HANDSHAKE_TIMEOUT_MS) {
+                self.reject_handshake(format!("后端未在{}ms内确认协议握手，可能是不支持握手的旧版后端", HANDSHAKE_TIMEOUT_MS));
+            }
+            return false;
+        }
+        
+        let negotiated = protocol::decode_handshake_ack(&self.handshake_ack).and_then(protocol::negotiate);
+        self.trace.record(Direction::Incoming, TraceChannel::Stt, "handshake_ack", self.handshake_ack.len(), negotiated.is_ok());
+        match negotiated {
+            Ok(version) => {
+                info!("[重要] 后端已确认协议握手，协商版本 v{}", version);
+                self.protocol_version = Some(version);
+                if self.effective_sample_format() != self.sample_format {
+                    warn!("[警告] 后端协议版本 v{} 不支持f32样本格式，改按PCM16发送", version);
+                }
+                if self.opus.is_some() && !self.supports_coded_audio() {
+                    warn!("[警告] 后端协议版本 v{} 不支持Opus编码的音频，改按PCM发送", version);
+                }
+                self.handshake_sent_at = None;
+                self.reset_backoff();
+                true
+            },
+            Err(e) => {
+                self.reject_handshake(e);
+                false
+            }
+        }
+    }
+    
+    // 握手失败：断开连接，不向无法正确解析的后端发送数据
+    fn reject_handshake(&mut self, reason: String) {
+        error!("[错误] 协议握手失败: {}", reason);
+        self.stream = None;
+        self.handshake_sent_at = None;
+        self.back_off();
+        // 与连接失败一样，同一故障只在第一次时通知前端
+        if self.reconnect_attempts == 1 {
+            self.errors.push(BackendError::new(backend_error::PROTOCOL_MISMATCH, reason, false));
+        }
+    }
+    
+    // 一次断线期间只在第一次连接失败时通知前端，之后的重试失败只打日志
+    fn report_connect_failure(&mut self, error: &impl std::fmt::Display) {
+        if self.reconnect_attempts == 1 {
+            self.errors.push(BackendError::new(
+                backend_error::CONNECT_FAILED,
+                format!("连接后端 {} 失败: {}", self.endpoints.stt, error),
+                true,
+            ));
+        }
+    }
+    
+    // 距上次尝试已超过当前重连间隔时返回 true 并记下本次尝试的时刻
+    fn reconnect_due(&mut self) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.last_reconnect_attempt) < Duration::from_millis(self.reconnect_delay_ms) {
+            return false;
+        }
+        self.last_reconnect_attempt = now;
+        true
+    }
+    
+    // 连接失败：间隔翻倍直到上限，再加随机抖动，避免后端长时间不在时按固定频率刷日志
+    fn back_off(&mut self) {
+        self.reconnect_attempts = self.reconnect_attempts.saturating_add(1);
+        let base = RECONNECT_INTERVAL_MS
+            .saturating_mul(1u64 << self.reconnect_attempts.min(16))
+            .min(MAX_RECONNECT_INTERVAL_MS);
+        self.reconnect_delay_ms = base + (base as f64 * RECONNECT_JITTER_RATIO * jitter_fraction()) as u64;
+    }
+    
+    fn reset_backoff(&mut self) {
+        self.reconnect_attempts = 0;
+        self.reconnect_delay_ms = RECONNECT_INTERVAL_MS;
+    }
+    
+    // 开关仅本地模式；开启时立即断开当前连接，关闭后下一次发送时重新连接并握手
+    fn set_local_only(&mut self, enabled: bool) {
+        self.local_only = enabled;
+        if enabled {
+            self.disconnect();
+        } else {
+            self.reset_backoff();
+        }
+    }
+    
+    // 定期发送心跳：连接被对端或系统静默关闭时写入会失败，此时立即断开并重新连接，
+    // 避免等到下一段语音写入失败才发现而丢掉开头。由补发线程每秒调用，未连接或握手未完成时不发送
+    fn heartbeat(&mut self, now: Instant) {
+        if self.heartbeat_interval_secs == 0 || self.protocol_version.is_none() {
+            return;
+        }
+        if now.duration_since(self.last_heartbeat) < Duration::from_secs(self.heartbeat_interval_secs) {
+            return;
+        }
+        self.last_heartbeat = now;
+        
+        let stream = match &mut self.stream {
+            Some(s) => s,
+            None => return,
+        };
+        let packet = protocol::encode_heartbeat();
+        match stream.write_all(&packet).and_then(|_| stream.flush()) {
+            Ok(()) => self.trace.record(Direction::Outgoing, TraceChannel::Stt, "heartbeat", packet.len(), true),
+            // 发送缓冲已满说明后端读得慢，但连接仍在
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => {
+                warn!("[警告] 发送心跳失败，连接已失效: {}，重新连接", e);
+                self.trace.record(Direction::Outgoing, TraceChannel::Stt, "heartbeat", packet.len(), false);
+                self.disconnect();
+                if self.connect() {
+                    info!("[信息] 心跳失败后已重新连接");
+                }
+            }
+        }
+    }
+    
+    // 断开当前连接并清除握手状态，下一次发送时重新连接
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.connected_endpoint = None;
+        self.protocol_version = None;
+        self.handshake_sent_at = None;
+        self.handshake_ack.clear();
+    }
+    
+    // 切换端点：断开当前连接，下一次发送时按新端点重连
+    fn set_endpoints(&mut self, endpoints: BackendEndpoints, legacy_fallback: bool) {
+        endpoints.export_to_env();
+        if endpoints != self.endpoints {
+            self.stream = None;
+            self.connected_endpoint = None;
+            // 新端点不沿用旧端点累积的退避
+            self.reset_backoff();
+        }
+        self.endpoints = endpoints;
+        self.legacy_fallback = legacy_fallback;
+    }
+    
+    fn connection_status(&self) -> ConnectionStatus {
+        let connected_endpoint = if self.stream.is_some() && self.protocol_version.is_some() {
+            self.connected_endpoint.clone()
+        } else {
+            None
+        };
+        ConnectionStatus {
+            connected: connected_endpoint.is_some(),
+            using_legacy_endpoint: matches!(&connected_endpoint, Some(endpoint) if *endpoint != self.endpoints.stt),
+            next_retry_in_ms: match &connected_endpoint {
+                Some(_) => None,
+                None if self.local_only => None,
+                None => {
+                    let due = self.last_reconnect_attempt + Duration::from_millis(self.reconnect_delay_ms);
+                    Some(due.saturating_duration_since(Instant::now()).as_millis() as u64)
+                }
+            },
+            connected_endpoint,
+            protocol_version: self.protocol_version.filter(|_| self.stream.is_some()),
+            audio_codec: self.codec(),
+            endpoints: self.endpoints.clone(),
+            legacy_fallback: self.legacy_fallback,
+            reconnect_attempts: self.reconnect_attempts,
+            reconnect_delay_ms: self.reconnect_delay_ms,
+            local_only: self.local_only,
+            heartbeat_interval_secs: self.heartbeat_interval_secs,
+        }
+    }
+
+    fn send_speech_segment(&mut self, segment: &[i16]) -> bool {
+        // 仅本地模式下也不写入待发队列，避免退出该模式后补发
+        if self.local_only {
+            return false;
+        }
+        
+        // 连接不上的段同样处理并保存，落盘到待发队列或留在内存中，等连接恢复后按原顺序补发
+        let connected = self.connect();
+        
+        // 经过处理链后再保存与发送，回放听到的就是后端收到的音频
+        let mut segment = segment.to_vec();
+        self.pipeline.process(&mut segment);
+
+        // debug!("[调试] 发送语音段到Python ({}个样本)", segment.len());
+        
+        // 保存发送到Python的音频段
+        if !segment.is_empty() {
+            // 克隆一份数据保存
+            self.sent_to_python_segments.push_back(SentSegment {
+                samples: segment.to_vec(),
+                voice_segment_id: self.next_voice_segment_id,
+            });
+            
+            // 限制保存的段数，防止内存占用过大
+            if self.sent_to_python_segments.len() > 50 {
+                self.drop_sent_segments(1);
+            }
+            
+            // debug!("[调试] 已保存发送到Python的音频段，当前共有{}个段", self.sent_to_python_segments.len());
+        }
+        self.record_to_wav(&segment);
+        
+        let queued = self.enqueue_for_send(&segment);
+        // 之前失败的段还在等待补发时先补发，保证后端按原顺序收到
+        let sent = connected && self.send_speech_segments() && self.write_audio_packet(&segment);
+        self.settle_queued(queued, sent);
+        // 没有落盘的段发送失败时留在内存中等待补发，已落盘的段由待发队列补发
+        if !sent && queued.is_none() && !segment.is_empty() {
+            self.requeue_speech_segment(segment);
+        }
+        sent
+    }
+    
+    // 发送失败的段放入内存补发队列，超出上限时丢弃最旧的
+    fn requeue_speech_segment(&mut self, segment: Vec<i16>) {
+        if self.speech_segments.len() >= MAX_PENDING_SPEECH_SEGMENTS {
+            warn!("[警告] 待补发的语音段已达上限{}，丢弃最旧的一段", MAX_PENDING_SPEECH_SEGMENTS);
+            self.speech_segments.remove(0);
+        }
+        self.speech_segments.push(segment);
+    }
+    
+    // 流式WAV录制中时追加该段；写入失败时结束录制，避免每帧重复报错
+    fn record_to_wav(&mut self, segment: &[i16]) {
+        let recorder = match self.wav_stream.as_mut() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+        if let Err(e) = recorder.append(segment) {
+            error!("[错误] {}，流式WAV录制已停止", e);
+            self.wav_stream = None;
+        }
+    }
+    
+    // 结束流式WAV录制并回填长度，未在录制时返回None
+    fn finish_wav_stream(&mut self) -> Option<Result<StreamingWavStatus, String>> {
+        self.wav_stream.take().map(StreamingWav::finish)
+    }
+    
+    // 写入socket前把段落盘到待发队列，返回队列序号；未开启或落盘失败时为None
+    fn enqueue_for_send(&mut self, segment: &[i16]) -> Option<u64> {
+        let sample_rate = self.sample_rate;
+        let queue = self.send_queue.as_mut()?;
+        match queue.append(segment, sample_rate) {
+            Ok(seq) => seq,
+            Err(e) => {
+                error!("[错误] 写入持久化待发队列失败: {}", e);
+                None
+            }
+        }
+    }
+    
+    // 发送成功的段从待发队列确认删除，失败的段等待补发
+    fn settle_queued(&mut self, queued: Option<u64>, sent: bool) {
+        if let (Some(seq), Some(queue)) = (queued, self.send_queue.as_mut()) {
+            if !sent {
+                queue.mark_unsent(seq);
+            } else if let Err(e) = queue.ack(seq) {
+                error!("[错误] 确认持久化待发队列失败: {}", e);
+            }
+        }
+    }
+    
+    // 按原顺序补发待发队列中未送达的段（含启动时从磁盘恢复的），遇到发送失败即停止，返回补发的段数。
+    // 采样率与当前管线不一致的段无法被后端正确解释，直接丢弃
+    fn resend_queued(&mut self) -> usize {
+        if !matches!(&self.send_queue, Some(queue) if queue.has_unsent()) || !self.connect() {
+            return 0;
+        }
+        
+        let mut resent = 0;
+        while let Some((seq, sample_rate, samples)) = self.send_queue.as_ref().and_then(|queue| queue.next_unsent()) {
+            if sample_rate == self.sample_rate {
+                if !self.write_audio_packet(&samples) {
+                    break;
+                }
+                resent += 1;
+            } else {
+                warn!("[警告] 待发段采样率({}Hz)与当前管线({}Hz)不一致，丢弃", sample_rate, self.sample_rate);
+            }
+            self.settle_queued(Some(seq), true);
+        }
+        resent
+    }
+    
+    // 把音频段编码为数据包写入socket；Opus编码器缓冲中不足一帧时本次可能不产生数据包
+    fn write_audio_packet(&mut self, segment: &[i16]) -> bool {
+        if self.stream.is_none() {
+            return false;
+        }
+        
+        // 准备完整的数据包（包头 + 音频数据）以确保原子性发送
+        // 协商版本不支持编码音频时按PCM发送，旧后端无法解析0x09
+        let coded = self.supports_coded_audio();
+        let (packets, kind) = match self.opus.as_mut().filter(|_| coded) {
+            Some(encoder) => match encoder.encode(segment) {
+                Ok(frames) => (
+                    frames
+                        .iter()
+                        .map(|(samples, bytes)| protocol::encode_coded_audio(AudioCodec::Opus, *samples, bytes))
+                        .collect(),
+                    "audio_opus",
+                ),
+                Err(e) => {
+                    error!("[错误] {}", e);
+                    return false;
+                }
+            },
+            None => {
+                let sample_format = self.effective_sample_format();
+                let kind = match sample_format {
+                    SampleFormat::Pcm16 => "audio_pcm16",
+                    SampleFormat::F32 => "audio_f32",
+                };
+                (vec![encode_audio_packet(segment, sample_format)], kind)
+            }
+        };
+        if packets.is_empty() {
+            return true;
+        }
+        // 会话标记（及trace上下文）与第一个音频包一起写入，后端不会看到没有后续音频的标记
+        let mut packets = packets;
+        if self.supports_session_tag() {
+            let mut tagged = protocol::encode_session_tag(self.session_id);
+            if let Some(context) = self.spans.wire_context().filter(|_| self.supports_trace_context()) {
+                tagged.extend_from_slice(&context);
+            }
+            tagged.extend_from_slice(&packets[0]);
+            packets[0] = tagged;
+        }
+        
+        let stream = match &mut self.stream {
+            Some(s) => s,
+            None => return false,
+        };
+        for full_packet in &packets {
+            // 原子性发送完整数据包，避免部分写入导致的乱序
+            if let Err(e) = stream.write_all(full_packet) {
+                // error!("[错误] 发送音频数据包失败: {}", e);
+                self.trace.record(Direction::Outgoing, TraceChannel::Stt, kind, full_packet.len(), false);
+                self.errors.push(BackendError::new(backend_error::SEND_FAILED, format!("发送音频数据包失败: {}", e), true));
+                self.stream = None;
+                return false;
+            }
+            self.trace.record(Direction::Outgoing, TraceChannel::Stt, kind, full_packet.len(), true);
+        }
+        
         // 强制刷新缓冲区确保立即发送
         if let Err(e) = stream.flush() {
-            println!("[警告] 刷新Socket缓冲区失败: {}", e);
+            warn!("[警告] 刷新Socket缓冲区失败: {}", e);
             // 不断开连接，因为flush失败不一定意味着数据没有发送
         }
+        self.spans.audio_sent();
+        if self.first_response == FirstResponse::AwaitingAudio {
+            self.first_response = FirstResponse::Sent(Instant::now());
+        }
 
         true
     }
     
-    // 发送静音事件到后端
-    fn send_silence_event(&mut self, silence_duration: u64) -> bool {
+    // 本会话收到第一个非空识别文本：返回自送出第一帧音频以来的延迟，每个会话只返回一次
+    fn take_first_response_latency(&mut self, now: Instant) -> Option<Duration> {
+        match self.first_response {
+            FirstResponse::Sent(sent_at) => {
+                self.first_response = FirstResponse::Responded;
+                Some(now.saturating_duration_since(sent_at))
+            }
+            _ => None,
+        }
+    }
+    
+    fn supports_session_tag(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_SESSION_TAG)
+    }
+    
+    fn supports_trace_context(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_TRACE_CONTEXT)
+    }
+    
+    fn supports_playback_inquiry(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_PLAYBACK_INQUIRY)
+    }
+    
+    fn supports_end_of_utterance(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_END_OF_UTTERANCE)
+    }
+    
+    fn supports_finalize(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_FINALIZE)
+    }
+    
+    fn supports_coded_audio(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_CODED_AUDIO)
+    }
+    
+    fn supports_f32_audio(&self) -> bool {
+        matches!(self.protocol_version, Some(version) if version >= protocol::PROTOCOL_VERSION_AUDIO_F32)
+    }
+    
+    // 实际发送的样本格式：协商版本不支持f32时按PCM16发送，旧后端无法解析0x06
+    fn effective_sample_format(&self) -> SampleFormat {
+        match self.sample_format {
+            SampleFormat::F32 if !self.supports_f32_audio() => SampleFormat::Pcm16,
+            format => format,
+        }
+    }
+    
+    // 设置样本格式；已完成握手且协商版本不支持时拒绝，尚未握手时先记下，发送时再按协商结果决定
+    fn set_sample_format(&mut self, sample_format: SampleFormat) -> Result<(), String> {
+        if let Some(version) = self.protocol_version {
+            if sample_format == SampleFormat::F32 && !self.supports_f32_audio() {
+                return Err(format!(
+                    "后端协议版本 v{} 不支持f32样本格式（需要 v{}）",
+                    version,
+                    protocol::PROTOCOL_VERSION_AUDIO_F32
+                ));
+            }
+        }
+        self.sample_format = sample_format;
+        Ok(())
+    }
+    
+    // 通知后端本段发言已结束
+    fn send_end_of_utterance(&mut self, reason: u8, duration_ms: u64) -> bool {
         if !self.connect() {
             return false;
         }
-
+        
+        let stream = match &mut self.stream {
+            Some(s) => s,
+            None => return false,
+        };
+        
+        let packet = protocol::encode_end_of_utterance(reason, duration_ms);
+        if let Err(e) = stream.write_all(&packet) {
+            error!("[错误] 发送发言结束消息失败: {}", e);
+            self.trace.record(Direction::Outgoing, TraceChannel::Stt, "end_of_utterance", packet.len(), false);
+            self.errors.push(BackendError::new(backend_error::SEND_FAILED, format!("发送发言结束消息失败: {}", e), true));
+            self.stream = None;
+            return false;
+        }
+        self.trace.record(Direction::Outgoing, TraceChannel::Stt, "end_of_utterance", packet.len(), true);
+        if let Err(e) = stream.flush() {
+            warn!("[警告] 刷新发言结束消息缓冲区失败: {}", e);
+        }
+        true
+    }
+    
+    // 回复播放中断流超时，询问后端该回复是否还有后续音频；协商版本不支持时不发送
+    fn send_playback_inquiry(&mut self, utterance_id: u64, stalled_ms: u64) -> bool {
+        if !self.connect() {
+            return false;
+        }
+        if !self.supports_playback_inquiry() {
+            debug!("[调试] 后端协议版本不支持播放询问，跳过");
+            return false;
+        }
+        
         let stream = match &mut self.stream {
             Some(s) => s,
             None => return false,
         };
-
-        // 创建静音事件数据包
-        // 格式：特殊长度头(0xFFFFFFFF) + 消息类型(0x01) + 静音时长(u64)
-        let mut silence_packet = Vec::with_capacity(4 + 1 + 8);
         
-        // 特殊长度头，标识这是控制消息
-        silence_packet.extend_from_slice(&0xFFFFFFFFu32.to_le_bytes());
+        let packet = protocol::encode_playback_inquiry(self.session_id, utterance_id, stalled_ms);
+        if let Err(e) = stream.write_all(&packet) {
+            error!("[错误] 发送播放询问失败: {}", e);
+            self.trace.record(Direction::Outgoing, TraceChannel::Stt, "playback_inquiry", packet.len(), false);
+            self.errors.push(BackendError::new(backend_error::SEND_FAILED, format!("发送播放询问失败: {}", e), true));
+            self.stream = None;
+            return false;
+        }
+        self.trace.record(Direction::Outgoing, TraceChannel::Stt, "playback_inquiry", packet.len(), true);
+        if let Err(e) = stream.flush() {
+            warn!("[警告] 刷新播放询问缓冲区失败: {}", e);
+        }
+        true
+    }
+    
+    // 请求后端立即输出最终识别结果；协商版本不支持时不发送，旧后端无法解析0x07
+    fn send_finalize_request(&mut self, finalize_id: u64) -> bool {
+        if !self.connect() {
+            return false;
+        }
+        if !self.supports_finalize() {
+            debug!("[调试] 后端协议版本不支持最终识别请求，跳过");
+            return false;
+        }
+        
+        let stream = match &mut self.stream {
+            Some(s) => s,
+            None => return false,
+        };
         
-        // 消息类型：0x01表示静音事件
-        silence_packet.push(0x01);
+        let packet = protocol::encode_finalize(finalize_id);
         
-        // 静音时长（毫秒）
-        silence_packet.extend_from_slice(&silence_duration.to_le_bytes());
+        if let Err(e) = stream.write_all(&packet) {
+            error!("[错误] 发送最终识别请求失败: {}", e);
+            self.trace.record(Direction::Outgoing, TraceChannel::Stt, "finalize", packet.len(), false);
+            self.errors.push(BackendError::new(backend_error::SEND_FAILED, format!("发送最终识别请求失败: {}", e), true));
+            self.stream = None;
+            return false;
+        }
+        self.trace.record(Direction::Outgoing, TraceChannel::Stt, "finalize", packet.len(), true);
+        if let Err(e) = stream.flush() {
+            warn!("[警告] 刷新最终识别请求缓冲区失败: {}", e);
+        }
+        true
+    }
+    
+    // 发送静音事件到后端，按协商出的协议版本选择旧格式或带上下文的新格式
+    fn send_silence_event(&mut self, context: &SilenceContext) -> bool {
+        if !self.connect() {
+            return false;
+        }
+
+        let stream = match &mut self.stream {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let version = self.protocol_version.unwrap_or(protocol::PROTOCOL_VERSION_LEGACY);
+        let silence_packet = protocol::encode_silence_event(context, version);
         
         // 发送静音事件数据包
         if let Err(e) = stream.write_all(&silence_packet) {
-            println!("[错误] 发送静音事件失败: {}", e);
+            error!("[错误] 发送静音事件失败: {}", e);
+            self.trace.record(Direction::Outgoing, TraceChannel::Stt, "silence", silence_packet.len(), false);
+            self.errors.push(BackendError::new(backend_error::SEND_FAILED, format!("发送静音事件失败: {}", e), true));
             self.stream = None;
             return false;
         }
+        self.trace.record(Direction::Outgoing, TraceChannel::Stt, "silence", silence_packet.len(), true);
         
         // 刷新缓冲区
         if let Err(e) = stream.flush() {
-            println!("[警告] 刷新静音事件缓冲区失败: {}", e);
+            warn!("[警告] 刷新静音事件缓冲区失败: {}", e);
         }
 
-        // println!("[调试] 已发送静音事件到后端: {}ms", silence_duration);
+        // debug!("[调试] 已发送静音事件到后端: {}ms", context.silence_ms);
         true
     }
 
@@ -790,28 +1952,24 @@ impl SocketManager {
             return false;
         }
 
-        // 发送所有待处理的语音段
-        let success = true;
-        let _segments_to_send = self.speech_segments.clone();
-        self.speech_segments.clear();
-
-        // for (i, segment) in segments_to_send.iter().enumerate() {
-        //     if !self.send_speech_segment(segment) {
-        //         println!("[错误] 发送之前失败的语音段失败");
-        //         success = false;
-        //         // 将未发送的语音段放回队列
-        //         self.speech_segments.extend_from_slice(&segments_to_send[i..]);
-        //         break;
-        //     }
-        // }
+        // 队列中的段已经过处理链并保存，补发时只写入socket。
+        // 按原顺序逐段补发；某段失败时连接多半已断开，该段及其后的段按原顺序放回队列，等下次重试
+        let segments_to_send = std::mem::take(&mut self.speech_segments);
+        for (i, segment) in segments_to_send.iter().enumerate() {
+            if !self.write_audio_packet(segment) {
+                self.speech_segments = segments_to_send[i..].to_vec();
+                error!("[错误] 补发之前失败的语音段失败，{}段留待下次重试", self.speech_segments.len());
+                return false;
+            }
+        }
 
-        success
+        true
     }
 
     #[allow(dead_code)]
     // 获取所有存储的完整语音段
     fn get_complete_speech_segments(&self) -> Vec<Vec<i16>> {
-        self.complete_speech_segments.clone()
+        self.complete_speech_segments.iter().map(|segment| segment.samples.clone()).collect()
     }
     
     #[allow(dead_code)]
@@ -821,11 +1979,12 @@ impl SocketManager {
     }
 
     // 新增方法：添加语音帧到当前语音段
-    fn add_voice_frame(&mut self, samples: &[i16], is_voice: bool) {
+    fn add_voice_frame(&mut self, samples: &[i16], is_voice: bool, frame_time: Instant) {
         if is_voice {
             // 如果是语音帧，添加到当前语音段
             if self.current_voice_segment.is_empty() {
-                println!("[调试] 开始新的语音段收集");
+                debug!("[调试] 开始新的语音段收集");
+                self.current_voice_segment_start = Some(frame_time);
             }
             self.current_voice_segment.extend_from_slice(samples);
             self.frames_without_voice = 0; // 重置无语音帧计数
@@ -835,23 +1994,7 @@ impl SocketManager {
             
             // 如果当前语音段不为空，并且已经连续5帧无语音，认为一个语音段结束
             if !self.current_voice_segment.is_empty() && self.frames_without_voice >= 5 {
-                if self.current_voice_segment.len() > 320 { // 只保存大于一定长度的语音段
-                    println!("[调试] 完成一个语音段收集，长度: {}", self.current_voice_segment.len());
-                    // 将当前语音段加入完整语音段列表
-                    self.complete_speech_segments.push(self.current_voice_segment.clone());
-                    
-                    // 限制保存的语音段数量，防止内存占用过大
-                    if self.complete_speech_segments.len() > 50 {
-                        self.complete_speech_segments.remove(0);
-                    }
-                    
-                    // println!("[调试] 当前已保存{}个语音段", self.complete_speech_segments.len());
-                } else {
-                    println!("[调试] 语音段太短，丢弃 (长度: {})", self.current_voice_segment.len());
-                }
-                
-                // 清空当前语音段以准备下一个
-                self.current_voice_segment.clear();
+                self.finish_voice_segment();
             }
             
             // 如果已经在收集语音段，添加少量非语音帧以保持连贯性
@@ -860,40 +2003,206 @@ impl SocketManager {
             }
         }
     }
+    
+    // 结束当前语音段的收集，足够长的段存入完整语音段列表
+    fn finish_voice_segment(&mut self) {
+        if self.current_voice_segment.is_empty() {
+            return;
+        }
+        
+        if self.current_voice_segment.len() > 320 { // 只保存大于一定长度的语音段
+            debug!("[调试] 完成一个语音段收集，长度: {}", self.current_voice_segment.len());
+            // 将当前语音段加入完整语音段列表
+            self.complete_speech_segments.push_back(VoiceSegment {
+                id: self.next_voice_segment_id,
+                samples: self.current_voice_segment.clone(),
+                started_at: self.current_voice_segment_start,
+            });
+            self.finished_segment_ids.push(self.next_voice_segment_id);
+            self.next_voice_segment_id += 1;
+            
+            // 限制保存的语音段数量，防止内存占用过大
+            if self.complete_speech_segments.len() > 50 {
+                self.complete_speech_segments.pop_front();
+            }
+            
+            // debug!("[调试] 当前已保存{}个语音段", self.complete_speech_segments.len());
+        } else {
+            debug!("[调试] 语音段太短，丢弃 (长度: {})", self.current_voice_segment.len());
+        }
+        
+        // 清空当前语音段以准备下一个
+        self.current_voice_segment.clear();
+        self.current_voice_segment_start = None;
+    }
+    
+    // 按真实时间轴拼接所有完整语音段，段间按采集时刻的间隔填充静音
+    fn session_timeline(&self) -> Vec<i16> {
+        let segments: Vec<(Option<Instant>, &[i16])> = self
+            .complete_speech_segments
+            .iter()
+            .map(|segment| (segment.started_at, segment.samples.as_slice()))
+            .collect();
+        audio_utils::timeline(&segments, self.sample_rate, Duration::from_millis(MAX_TIMELINE_GAP_MS))
+    }
+    
+    // 取出新完成的语音段序号
+    fn take_finished_segment_ids(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.finished_segment_ids)
+    }
+    
+    // 停止处理时收尾：结束正在收集的语音段，并尝试发送之前失败的语音段
+    fn flush(&mut self) -> bool {
+        self.finish_voice_segment();
+        self.frames_without_voice = 0;
+        self.send_speech_segments()
+    }
 
     // 获取发送到Python的音频段
     fn get_sent_to_python_segments(&self) -> Vec<Vec<i16>> {
-        self.sent_to_python_segments.clone()
+        self.sent_to_python_segments.iter().map(|segment| segment.samples.clone()).collect()
     }
     
     // 清空发送到Python的音频段
     fn clear_sent_to_python_segments(&mut self) {
-        self.sent_to_python_segments.clear();
+        self.drop_sent_segments(self.sent_to_python_segments.len());
     }
-
-    // 添加音频帧到前置缓冲区
-    fn add_to_pre_context(&mut self, samples: &[i16]) {
-        self.pre_context_frames.push(samples.to_vec());
-        
-        // 保持缓冲区大小
-        while self.pre_context_frames.len() > self.max_pre_context_frames {
-            self.pre_context_frames.remove(0);
-        }
+    
+    // 删除最早的count个发送到Python的音频段，对应的缩略图缓存失效
+    fn drop_sent_segments(&mut self, count: usize) {
+        let count = count.min(self.sent_to_python_segments.len());
+        self.sent_to_python_segments.drain(..count);
+        self.first_sent_segment_id += count as u64;
+        self.thumbnails.invalidate_before(self.first_sent_segment_id);
     }
     
-    // 发送前置缓冲区中的所有帧
-    fn send_pre_context_frames(&mut self) -> bool {
-        println!("[重要] 发送前置上下文帧: {}帧", self.pre_context_frames.len());
-        let mut all_success = true;
-        
-        // 克隆前置帧数据避免借用冲突
-        let frames_to_send = self.pre_context_frames.clone();
+    // 删除属于给定语音段的完整语音段与发送段，返回(删除的语音段数, 删除的发送段数)。
+    // 从中间删除发送段会让其后各段的位置序号前移，缩略图缓存整体失效
+    fn remove_voice_segments(&mut self, ids: &[u64]) -> (usize, usize) {
+        let voice_before = self.complete_speech_segments.len();
+        self.complete_speech_segments.retain(|segment| !ids.contains(&segment.id));
+        let sent_before = self.sent_to_python_segments.len();
+        self.sent_to_python_segments.retain(|segment| !ids.contains(&segment.voice_segment_id));
         
-        for frame in frames_to_send {
-            if !self.send_speech_segment(&frame) {
-                all_success = false;
-                println!("[警告] 前置帧发送失败");
-            }
+        let removed_sent = sent_before - self.sent_to_python_segments.len();
+        if removed_sent > 0 {
+            self.thumbnails.clear();
+        }
+        (voice_before - self.complete_speech_segments.len(), removed_sent)
+    }
+    
+    // 波形缩略图：index 为段在列表中的位置，None 表示合并段
+    fn waveform_thumbnail(&mut self, index: Option<usize>, width: usize) -> Result<Vec<i8>, String> {
+        match index {
+            Some(index) => {
+                let samples = self.sent_to_python_segments.get(index).map(|segment| &segment.samples).ok_or_else(|| {
+                    format!("语音段序号超出范围: {}，当前共有{}个段", index, self.sent_to_python_segments.len())
+                })?;
+                let source = ThumbnailSource::Segment(self.first_sent_segment_id + index as u64);
+                Ok(self.thumbnails.get_or_compute(source, width, samples))
+            },
+            None => {
+                let end_id = self.first_sent_segment_id + self.sent_to_python_segments.len() as u64;
+                let source = ThumbnailSource::Combined(self.first_sent_segment_id, end_id);
+                let combined = self.get_combined_speech_segment();
+                Ok(self.thumbnails.get_or_compute(source, width, &combined))
+            },
+        }
+    }
+    
+    fn codec(&self) -> AudioCodec {
+        if self.opus.is_some() {
+            AudioCodec::Opus
+        } else {
+            AudioCodec::Pcm
+        }
+    }
+    
+    // 设置音频编码；已完成握手且协商版本不支持编码音频时拒绝Opus，尚未握手时先记下，发送时再按协商结果决定
+    fn set_codec(&mut self, codec: AudioCodec) -> Result<(), String> {
+        if let Some(version) = self.protocol_version {
+            if codec == AudioCodec::Opus && !self.supports_coded_audio() {
+                return Err(format!(
+                    "后端协议版本 v{} 不支持Opus编码的音频（需要 v{}）",
+                    version,
+                    protocol::PROTOCOL_VERSION_CODED_AUDIO
+                ));
+            }
+        }
+        self.opus = match codec {
+            AudioCodec::Pcm => None,
+            AudioCodec::Opus => Some(OpusEncoder::new(self.sample_rate)?),
+        };
+        Ok(())
+    }
+    
+    // 采样率变化后，已缓存的语音段与前置帧按旧采样率录制，一并丢弃
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        if self.sample_rate == sample_rate {
+            return;
+        }
+        self.sample_rate = sample_rate;
+        self.pipeline.set_sample_rate(sample_rate);
+        // Opus编码器按新采样率重建，新采样率不受Opus支持时退回PCM
+        if self.opus.is_some() {
+            if let Err(e) = self.set_codec(AudioCodec::Opus) {
+                warn!("[警告] {}，音频编码退回PCM", e);
+                self.opus = None;
+            }
+        }
+        // WAV头中的采样率已失效，结束录制
+        if let Some(result) = self.finish_wav_stream() {
+            match result {
+                Ok(status) => warn!("[警告] 采样率变化，流式WAV录制已结束: {}", status.path),
+                Err(e) => error!("[错误] {}", e),
+            }
+        }
+        // 握手中声明的采样率已失效，断开后按新采样率重新握手
+        self.stream = None;
+        self.speech_segments.clear();
+        self.complete_speech_segments.clear();
+        self.current_voice_segment.clear();
+        self.current_voice_segment_start = None;
+        self.clear_sent_to_python_segments();
+        self.pre_context_frames.clear();
+    }
+
+    // 丢弃正在收集的语音段与前置缓冲区：静音前后的音频不能拼到同一段中，
+    // 解除静音后前置缓冲区从新的帧重新填充
+    fn discard_capture(&mut self) {
+        self.current_voice_segment.clear();
+        self.current_voice_segment_start = None;
+        self.frames_without_voice = 0;
+        self.pre_context_frames.clear();
+    }
+    
+    // 添加音频帧到前置缓冲区
+    fn add_to_pre_context(&mut self, samples: &[i16]) {
+        self.pre_context_frames.push_back(samples.to_vec());
+        
+        // 保持缓冲区大小
+        while self.pre_context_frames.len() > self.max_pre_context_frames {
+            self.pre_context_frames.pop_front();
+        }
+    }
+    
+    // 发送前置缓冲区中的所有帧
+    fn send_pre_context_frames(&mut self) -> bool {
+        info!("[重要] 发送前置上下文帧: {}帧", self.pre_context_frames.len());
+        let mut all_success = true;
+        
+        // 克隆前置帧数据避免借用冲突，按从旧到新的顺序发送
+        let mut frames_to_send: Vec<Vec<i16>> = self.pre_context_frames.iter().cloned().collect();
+        
+        // 前置帧可能从一个音节中间截断，起始处做很短的淡入避免突兀
+        let fade_samples = (self.sample_rate as u64 * self.pre_context_fade_ms / 1000) as usize;
+        audio_utils::fade_in_frames(&mut frames_to_send, fade_samples);
+        
+        for frame in frames_to_send {
+            if !self.send_speech_segment(&frame) {
+                all_success = false;
+                warn!("[警告] 前置帧发送失败");
+            }
         }
         
         all_success
@@ -908,10 +2217,10 @@ impl SocketManager {
 
         // 计算总长度
         let total_length: usize = self.sent_to_python_segments.iter()
-            .map(|segment| segment.len())
+            .map(|segment| segment.samples.len())
             .sum();
         
-        println!("[调试] 开始合并{}个语音识别段，总样本数: {}", 
+        debug!("[调试] 开始合并{}个语音识别段，总样本数: {}", 
                 self.sent_to_python_segments.len(), total_length);
 
         // 创建合并后的数组
@@ -919,968 +2228,6078 @@ impl SocketManager {
         
         // 合并所有语音段
         for segment in &self.sent_to_python_segments {
-            combined.extend_from_slice(segment);
+            combined.extend_from_slice(&segment.samples);
         }
 
-        println!("[调试] 语音识别段合并完成，总长度: {}个样本", combined.len());
+        debug!("[调试] 语音识别段合并完成，总长度: {}个样本", combined.len());
         combined
     }
 }
 
+// i16样本转换为归一化到[-1,1]的f32
+fn i16_to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+// 按样本格式编码一个音频数据包
+fn encode_audio_packet(segment: &[i16], sample_format: SampleFormat) -> Vec<u8> {
+    match sample_format {
+        SampleFormat::Pcm16 => {
+            // 长度头（样本数） + i16小端样本
+            let mut packet = Vec::with_capacity(4 + segment.len() * 2);
+            packet.extend_from_slice(&(segment.len() as u32).to_le_bytes());
+            for &sample in segment {
+                packet.extend_from_slice(&sample.to_le_bytes());
+            }
+            packet
+        },
+        SampleFormat::F32 => {
+            let samples: Vec<f32> = segment.iter().map(|&sample| i16_to_f32(sample)).collect();
+            protocol::encode_audio_f32(&samples)
+        },
+    }
+}
+
+// VAD灵敏度档位，对应webrtc VAD的四种模式，越激进越不容易把噪声判为语音
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VadAggressiveness {
+    Quality,
+    LowBitrate,
+    Aggressive,
+    VeryAggressive,
+}
+
+impl VadAggressiveness {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quality" => Some(VadAggressiveness::Quality),
+            "low_bitrate" => Some(VadAggressiveness::LowBitrate),
+            "aggressive" => Some(VadAggressiveness::Aggressive),
+            "very_aggressive" => Some(VadAggressiveness::VeryAggressive),
+            _ => None,
+        }
+    }
+    
+    fn to_vad_mode(self) -> VadMode {
+        match self {
+            VadAggressiveness::Quality => VadMode::Quality,
+            VadAggressiveness::LowBitrate => VadMode::LowBitrate,
+            VadAggressiveness::Aggressive => VadMode::Aggressive,
+            VadAggressiveness::VeryAggressive => VadMode::VeryAggressive,
+        }
+    }
+}
+
+// 当前VAD配置
+#[derive(Serialize, Clone, Debug)]
+struct VadConfig {
+    backend: VadBackendKind,
+    silero: SileroSettings,
+    mode: VadAggressiveness,
+    voting: bool,
+    high_pass: bool,
+    input_filters: Vec<StageConfig>,
+    denoise: bool,
+    noise_floor: Option<f32>,
+    sample_rate: u32,
+    speech_start_frames: usize,
+    speech_end_silence_frames: usize,
+    waiting_silence_frames: usize, // 状态机从说话中进入等待中所需的连续静音帧数
+}
+
 // VAD处理器
 struct VadProcessor {
-    vad: Vad,
+    backend: Box<dyn VadBackend>,     // 单帧语音判定
+    backend_kind: VadBackendKind,
+    silero: SileroSettings,           // 切换到Silero后端时使用的设置
+    mode: VadAggressiveness,
+    voting: bool,                     // 双重判定：webrtc_vad判为语音且帧RMS超过自适应底噪才算语音
+    high_pass: Option<DcRemoval>,     // 判定前的高通滤波，None为关闭
+    input_filters: Pipeline,          // 判定前的二阶滤波环节，在 high_pass 之后，默认为空
+    denoise: Option<Denoiser>,        // 判定前的RNNoise降噪，None为关闭（需要denoise feature）
+    session: VadSession,              // 本次会话的检测状态，reset_session 时整体重建
+    speech_start_frames: usize,       // 判定语音开始所需的连续语音帧数
+    speech_end_silence_frames: usize, // 判定语音结束所需的连续静音帧数
+    min_speech_frames: usize,         // 语音持续的最少帧数，不足时不向状态机报告为语音
+    waiting_silence_frames: usize,    // 状态机进入等待中所需的静音帧数，由状态机使用，此处仅供 VadConfig 报告
+    last_frame_samples: usize,        // 最近一帧的样本数，用于毫秒与帧数换算
+    sample_rate: u32,                 // VAD与后端使用的采样率
+    energy_fallback_threshold: f32,   // 判定后端出错时的能量判定阈值，也是能量后端的最低阈值（归一化RMS）
+    resampler: Option<Resampler>,     // 输入采样率与sample_rate不同时使用
+    rate_check: RateCheck,            // 按帧到达节奏核对前端声明的输入采样率
+    look_ahead: LookAhead,            // 判定后、送往状态机前的前瞻延迟线
+    stats: VadStats,                  // 累计处理统计，重置检测状态时保留
+    level_meter: LevelMeter,          // 按时间窗口汇总电平，节流 audio-level 事件
+    calibration: Option<Calibration>, // 进行中的底噪校准或语音检测，None为未在校准
+}
+
+// VAD处理器中随会话变化的检测状态；用户设置、输入核对与累计统计留在 VadProcessor 上
+struct VadSession {
+    noise_floor: Option<f32>,            // Initial状态下按帧RMS滑动平均估计的底噪，尚无样本时为None
     is_speaking: bool,
     silence_frames: usize,
     speech_frames: usize,
+    residual: Vec<i16>,                  // 尚未凑满一帧的样本，留到下一次调用
+    annotations: AnnotationRecorder,     // 语音活动区间记录，用于导出标注
+    last_vad_error_log: Option<Instant>, // 上次打印webrtc_vad错误的时刻，用于限流
+    suppressed_vad_errors: u64,          // 限流期间未打印的错误数
 }
 
-impl VadProcessor {
-    fn new() -> Self {
-        println!("[调试] 创建新的VAD处理器实例");
+impl VadSession {
+    fn new(sample_rate: u32) -> Self {
         Self {
-            vad: Vad::new_with_rate_and_mode(
-                match SAMPLE_RATE {
-                    8000 => SampleRate::Rate8kHz,
-                    16000 => SampleRate::Rate16kHz,
-                    32000 => SampleRate::Rate32kHz,
-                    48000 => SampleRate::Rate48kHz,
-                    _ => SampleRate::Rate16kHz,
-                },
-                VadMode::VeryAggressive
-            ),
+            noise_floor: None,
             is_speaking: false,
             silence_frames: 0,
             speech_frames: 0,
+            residual: Vec::new(),
+            annotations: AnnotationRecorder::new(sample_rate),
+            last_vad_error_log: None,
+            suppressed_vad_errors: 0,
         }
     }
+}
 
-    fn process_frame(&mut self, samples: &[i16]) -> Option<(VadEvent, bool)> {
-        if samples.is_empty() {
-            println!("[错误] 音频样本为空");
-            return None;
-        }
+// webrtc_vad 的采样率枚举，不支持的采样率返回 None
+fn webrtc_sample_rate(sample_rate: u32) -> Option<SampleRate> {
+    match sample_rate {
+        8000 => Some(SampleRate::Rate8kHz),
+        16000 => Some(SampleRate::Rate16kHz),
+        32000 => Some(SampleRate::Rate32kHz),
+        48000 => Some(SampleRate::Rate48kHz),
+        _ => None,
+    }
+}
 
-        // 验证和调整帧大小
-        let valid_sizes = match SAMPLE_RATE {
-            8000 => vec![80, 160, 240],
-            16000 => vec![160, 320, 480],
-            32000 => vec![320, 640, 960],
-            48000 => vec![480, 960, 1440],
-            _ => vec![160, 320, 480],
+impl VadProcessor {
+    fn new() -> Self {
+        debug!("[调试] 创建新的VAD处理器实例");
+        let silero = SileroSettings::default();
+        // 精简构建中webrtc_vad可能无法初始化，此时退回能量判定，保证处理器可用
+        let (backend, backend_kind) = match vad_backend::build(VadBackendKind::Webrtc, SAMPLE_RATE, VadAggressiveness::VeryAggressive, &silero, DEFAULT_ENERGY_FALLBACK_THRESHOLD) {
+            Ok(backend) => (backend, VadBackendKind::Webrtc),
+            Err(e) => {
+                warn!("[警告] 创建webrtc_vad失败，改用能量判定: {}", e);
+                let backend: Box<dyn VadBackend> = Box::new(vad_backend::EnergyBackend::new(DEFAULT_ENERGY_FALLBACK_THRESHOLD));
+                (backend, VadBackendKind::Energy)
+            }
         };
+        Self {
+            backend,
+            backend_kind,
+            silero,
+            mode: VadAggressiveness::VeryAggressive,
+            voting: false,
+            high_pass: Some(DcRemoval::new(VAD_HIGH_PASS_CUTOFF_HZ, SAMPLE_RATE)),
+            input_filters: Pipeline::new(SAMPLE_RATE),
+            denoise: None,
+            session: VadSession::new(SAMPLE_RATE),
+            speech_start_frames: params::ms_to_frames(DEFAULT_SPEECH_START_MS as f64, DEFAULT_FRAME_MS),
+            speech_end_silence_frames: params::ms_to_frames(DEFAULT_SPEECH_END_SILENCE_MS as f64, DEFAULT_FRAME_MS),
+            min_speech_frames: params::ms_to_frames(DEFAULT_MIN_SPEECH_MS as f64, DEFAULT_FRAME_MS),
+            waiting_silence_frames: params::ms_to_frames(DEFAULT_WAITING_SILENCE_MS as f64, DEFAULT_FRAME_MS),
+            last_frame_samples: (SAMPLE_RATE / 50) as usize,
+            sample_rate: SAMPLE_RATE,
+            energy_fallback_threshold: DEFAULT_ENERGY_FALLBACK_THRESHOLD,
+            resampler: None,
+            rate_check: RateCheck::new(),
+            look_ahead: LookAhead::new(params::ms_to_frames(DEFAULT_LOOKAHEAD_MS as f64, DEFAULT_FRAME_MS)),
+            stats: VadStats::new(),
+            level_meter: LevelMeter::new(),
+            calibration: None,
+        }
+    }
+    
+    // 当前实际帧时长（毫秒）
+    fn frame_duration_ms(&self) -> f64 {
+        self.last_frame_samples as f64 * 1000.0 / self.sample_rate as f64
+    }
+
+    // 切换管线采样率：重建判定后端，丢弃按旧采样率累积的残余样本、计数与标注时间轴
+    fn set_sample_rate(&mut self, sample_rate: u32) -> Result<(), String> {
+        if webrtc_sample_rate(sample_rate).is_none() {
+            return Err(format!("不支持的采样率: {}Hz，可选值: {:?}", sample_rate, SUPPORTED_SAMPLE_RATES));
+        }
+        if sample_rate == self.sample_rate {
+            return Ok(());
+        }
         
-        let processed_samples = if !valid_sizes.contains(&samples.len()) {
-            println!("[警告] 调整音频帧大小到320样本");
-            let mut adjusted = Vec::with_capacity(320);
-            
-            adjusted.extend_from_slice(if samples.len() > 320 {
-                &samples[0..320]
-            } else {
-                samples
-            });
-            
-            while adjusted.len() < 320 {
-                adjusted.push(0);
+        self.backend = vad_backend::build(self.backend_kind, sample_rate, self.mode, &self.silero, self.energy_fallback_threshold)?;
+        self.sample_rate = sample_rate;
+        self.session.is_speaking = false;
+        self.session.silence_frames = 0;
+        self.session.speech_frames = 0;
+        self.session.annotations = AnnotationRecorder::new(sample_rate);
+        self.last_frame_samples = self.default_frame_samples();
+        self.session.residual.clear();
+        self.resampler = None;
+        self.look_ahead.clear();
+        if self.high_pass.is_some() {
+            self.high_pass = Some(DcRemoval::new(VAD_HIGH_PASS_CUTOFF_HZ, sample_rate));
+        }
+        self.input_filters.set_sample_rate(sample_rate);
+        if self.denoise.is_some() {
+            self.denoise = Some(Denoiser::new(sample_rate)?);
+        }
+        Ok(())
+    }
+
+    // 静音前后的音频不连续：清除语音判定的进行中状态、残余样本与前瞻延迟线，底噪与用户设置保留
+    fn discard_pending(&mut self) {
+        self.session.is_speaking = false;
+        self.session.speech_frames = 0;
+        self.session.silence_frames = 0;
+        self.session.residual.clear();
+        self.look_ahead.clear();
+    }
+    
+    // 切换灵敏度档位，保留 is_speaking 等计数，下一帧起生效
+    fn set_mode(&mut self, mode: VadAggressiveness) {
+        self.backend.set_aggressiveness(mode);
+        self.mode = mode;
+    }
+    
+    // 切换判定后端：新后端从零开始，连续语音/静音帧计数清零；is_speaking 保留，
+    // 进行中的语音由新后端的判定自然结束。构建失败时保留原后端
+    fn set_backend(&mut self, kind: VadBackendKind, silero: SileroSettings) -> Result<(), String> {
+        self.backend = vad_backend::build(kind, self.sample_rate, self.mode, &silero, self.energy_fallback_threshold)?;
+        self.backend_kind = kind;
+        self.silero = silero;
+        self.session.speech_frames = 0;
+        self.session.silence_frames = 0;
+        Ok(())
+    }
+    
+    // 取出尚未参与检测的音频：前瞻延迟线中尚未送出的帧在前，不足一帧的残余样本在后，
+    // 都已经过重采样与滤波，与已发送给后端的音频格式一致
+    fn take_pending_audio(&mut self) -> Vec<i16> {
+        let mut pending: Vec<i16> = self.look_ahead.drain().into_iter().flat_map(|frame| frame.samples).collect();
+        pending.append(&mut self.session.residual);
+        pending
+    }
+    
+    // 重置本次会话的检测状态：连续语音/静音帧计数、残余样本、前瞻延迟线、底噪估计、标注时间轴与进行中的校准，
+    // 判定后端、高通滤波器与降噪器按当前设置重建以清空内部历史。
+    // 用户设置（灵敏度档位、判定模式、能量阈值、起止帧数、前瞻深度、采样率、判定后端、开关、电平事件间隔）、
+    // 输入采样率核对与累计统计保留
+    fn reset_session(&mut self) {
+        self.session = VadSession::new(self.sample_rate);
+        self.last_frame_samples = self.default_frame_samples();
+        self.resampler = None;
+        self.look_ahead.clear();
+        self.calibration = None;
+        match vad_backend::build(self.backend_kind, self.sample_rate, self.mode, &self.silero, self.energy_fallback_threshold) {
+            Ok(backend) => self.backend = backend,
+            Err(e) => warn!("[警告] 重建VAD判定后端失败，沿用现有实例: {}", e),
+        }
+        if self.high_pass.is_some() {
+            self.high_pass = Some(DcRemoval::new(VAD_HIGH_PASS_CUTOFF_HZ, self.sample_rate));
+        }
+        self.input_filters.reset();
+        if self.denoise.is_some() {
+            match Denoiser::new(self.sample_rate) {
+                Ok(denoiser) => self.denoise = Some(denoiser),
+                Err(e) => warn!("[警告] 重建降噪器失败，沿用现有实例: {}", e),
             }
-            
-            adjusted
-        } else {
-            samples.to_vec()
-        };
+        }
+    }
+    
+    // 能量阈值同时用于判定后端出错时的兜底与能量后端的最低判定阈值
+    fn set_energy_threshold(&mut self, threshold: f32) {
+        self.energy_fallback_threshold = threshold;
+        self.backend.set_energy_threshold(threshold);
+    }
+    
+    // 开关判定前的高通滤波；重新开启时滤波器从零开始
+    fn set_high_pass(&mut self, enabled: bool) {
+        if enabled && self.high_pass.is_none() {
+            self.high_pass = Some(DcRemoval::new(VAD_HIGH_PASS_CUTOFF_HZ, self.sample_rate));
+        } else if !enabled {
+            self.high_pass = None;
+        }
+    }
+    
+    // 替换判定前的滤波环节，只允许 high_pass / low_pass / biquad；校验失败时保持原设置
+    fn set_input_filters(&mut self, stages: Vec<StageConfig>) -> Result<(), String> {
+        pipeline::validate_filters(&stages, self.sample_rate)?;
+        self.input_filters.configure(stages)
+    }
+    
+    // 开关判定前的降噪；重新开启时降噪状态从零开始，未启用denoise feature时开启返回错误
+    fn set_denoise(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled && self.denoise.is_none() {
+            self.denoise = Some(Denoiser::new(self.sample_rate)?);
+        } else if !enabled {
+            self.denoise = None;
+        }
+        Ok(())
+    }
+    
+    // 开关双重判定模式，已估计的底噪保留
+    fn set_voting(&mut self, voting: bool) {
+        self.voting = voting;
+    }
+    
+    // 用一帧的RMS更新底噪估计，只在状态机处于Initial（用户未说话）时调用
+    fn update_noise_floor(&mut self, frame_rms: f32) {
+        self.session.noise_floor = Some(match self.session.noise_floor {
+            Some(floor) => floor + (frame_rms - floor) * NOISE_FLOOR_SMOOTHING,
+            None => frame_rms,
+        });
+    }
+    
+    fn config(&self) -> VadConfig {
+        VadConfig {
+            backend: self.backend_kind,
+            silero: self.silero.clone(),
+            mode: self.mode,
+            voting: self.voting,
+            high_pass: self.high_pass.is_some(),
+            input_filters: self.input_filters.configs().to_vec(),
+            denoise: self.denoise.is_some(),
+            noise_floor: self.session.noise_floor,
+            sample_rate: self.sample_rate,
+            speech_start_frames: self.speech_start_frames,
+            speech_end_silence_frames: self.speech_end_silence_frames,
+            waiting_silence_frames: self.waiting_silence_frames,
+        }
+    }
+    
+    // 把输入重采样到sample_rate；输入采样率变化时重建重采样器
+    fn resample_input(&mut self, samples: &[f32], input_rate: u32) -> Vec<f32> {
+        if input_rate == self.sample_rate {
+            self.resampler = None;
+            return samples.to_vec();
+        }
+        
+        if self.resampler.as_ref().map(|r| r.input_rate()) != Some(input_rate) {
+            info!("[信息] 输入采样率为{}Hz，重采样到{}Hz", input_rate, self.sample_rate);
+            self.resampler = Some(Resampler::new(input_rate, self.sample_rate));
+        }
+        match self.resampler.as_mut() {
+            Some(resampler) => resampler.process(samples),
+            None => samples.to_vec(),
+        }
+    }
+    
+    // webrtc_vad 接受的帧长（10/20/30ms）
+    fn valid_frame_sizes(&self) -> [usize; 3] {
+        let samples_per_10ms = (self.sample_rate / 100) as usize;
+        [samples_per_10ms, samples_per_10ms * 2, samples_per_10ms * 3]
+    }
+    
+    // 前端送来的块不是合法帧长时，按20ms切帧
+    fn default_frame_samples(&self) -> usize {
+        (self.sample_rate / 50) as usize
+    }
+    
+    // 把新到的样本接在残余样本之后，切出完整的帧；不足一帧的部分留到下一次。
+    // 没有残余且输入本身就是合法帧长时原样作为一帧，否则按20ms切帧
+    fn take_frames(&mut self, samples: &[i16]) -> Vec<Vec<i16>> {
+        if self.session.residual.is_empty() && self.valid_frame_sizes().contains(&samples.len()) {
+            return vec![samples.to_vec()];
+        }
+        
+        self.stats.record_size_adjusted();
+        let frame_samples = self.default_frame_samples();
+        self.session.residual.extend_from_slice(samples);
+        let frame_count = self.session.residual.len() / frame_samples;
+        let ready: Vec<i16> = self.session.residual.drain(..frame_count * frame_samples).collect();
+        ready.chunks(frame_samples).map(|chunk| chunk.to_vec()).collect()
+    }
+    
+    // 返回(VAD事件, 是否是语音, 判定来源)；判定后端出错时退回按帧能量判定，帧不会被丢弃。
+    // 语音开始前，连续语音不足 min_speech_frames 的帧按静音返回，语音开始也推迟到越过该阈值
+    fn process_frame(&mut self, samples: &[i16]) -> Option<(VadEvent, bool, VadSource)> {
+        if !self.valid_frame_sizes().contains(&samples.len()) {
+            error!("[错误] 音频帧长度不合法: {}个样本", samples.len());
+            return None;
+        }
+        let frame_len = samples.len() as u64;
+        self.last_frame_samples = samples.len();
+        self.session.annotations.advance(samples.len());
         
-        // 使用VAD检测语音
-        let is_voice = match self.vad.is_voice_segment(&processed_samples) {
+        // 使用VAD检测语音，只对判定后端本身计时
+        let detect_started = Instant::now();
+        let detected = self.backend.is_voice(samples);
+        let detect_time = detect_started.elapsed();
+        let (is_voice, source) = match detected {
             Ok(result) => {
                 if result {
-                    // println!("[调试] VAD检测结果: 有语音");
+                    // debug!("[调试] VAD检测结果: 有语音");
                 }
-                result
+                // 双重判定：噪声环境下webrtc_vad会把噪声帧判为语音，再要求能量明显高于底噪
+                let result = match self.session.noise_floor {
+                    Some(floor) if self.voting && result => {
+                        audio_utils::frame_rms(samples) > floor * VOTING_NOISE_FLOOR_MARGIN
+                    }
+                    _ => result,
+                };
+                (result, VadSource::Backend)
             },
             Err(e) => {
-                println!("[错误] VAD处理失败: {:?}", e);
-                return None;
+                self.log_vad_error(&e);
+                let is_voice = audio_utils::frame_rms(samples) > self.energy_fallback_threshold;
+                (is_voice, VadSource::EnergyFallback)
             }
         };
         
+        self.stats.record_frame(is_voice, detect_time);
         let mut event = VadEvent::Processing;
         
         if is_voice {
-            self.speech_frames += 1;
-            self.silence_frames = 0;
+            self.session.speech_frames += 1;
+            self.session.silence_frames = 0;
             
-            if self.speech_frames >= 2 && !self.is_speaking {
-                self.is_speaking = true;
-                println!("[重要] 检测到语音开始 (累计语音帧: {})", self.speech_frames);
+            if self.session.speech_frames >= self.speech_start_frames.max(self.min_speech_frames) && !self.session.is_speaking {
+                self.session.is_speaking = true;
+                info!("[重要] 检测到语音开始 (累计语音帧: {})", self.session.speech_frames);
                 event = VadEvent::SpeechStart;
+                self.stats.record_speech_started();
+                self.session.annotations.speech_start(self.session.speech_frames as u64 * frame_len);
             }
         } else {
-            self.silence_frames += 1;
-            self.speech_frames = 0;
-            if self.is_speaking {
-                // println!("[调试] 检测到静音 (累计静音帧: {}), is_speaking: {}", self.silence_frames, self.is_speaking);
-            }
-            if self.silence_frames >= 100 && self.is_speaking {  // 增加到100帧(2秒)避免过早结束
-                self.is_speaking = false;
-                println!("[重要] ====== 检测到语音结束 (累计静音帧: {}) ======", self.silence_frames);
+            self.session.silence_frames += 1;
+            self.session.speech_frames = 0;
+            if self.session.is_speaking {
+                // debug!("[调试] 检测到静音 (累计静音帧: {}), is_speaking: {}", self.session.silence_frames, self.session.is_speaking);
+            }
+            if self.session.silence_frames >= self.speech_end_silence_frames && self.session.is_speaking {  // 默认100帧(2秒)避免过早结束
+                self.session.is_speaking = false;
+                info!("[重要] ====== 检测到语音结束 (累计静音帧: {}) ======", self.session.silence_frames);
                 event = VadEvent::SpeechEnd;
+                self.stats.record_speech_ended();
+                self.session.annotations.speech_end(self.session.silence_frames as u64 * frame_len);
             }
         }
         
-        // 返回VAD事件和是否包含语音的标志
-        Some((event, is_voice))
+        // 短促声响不报告为语音：这些帧照常进入前置上下文，越过阈值进入会话时随前置上下文一并发送
+        let reported_voice = is_voice && (self.session.is_speaking || self.session.speech_frames >= self.min_speech_frames);
+        
+        // 返回VAD事件、是否包含语音的标志与判定来源
+        Some((event, reported_voice, source))
+    }
+    
+    // 判定后端出错时每帧都会失败，日志每秒最多打印一次
+    fn log_vad_error(&mut self, error: &vad_backend::VadError) {
+        let now = Instant::now();
+        let due = match self.session.last_vad_error_log {
+            Some(last) => now.duration_since(last) >= Duration::from_millis(VAD_ERROR_LOG_INTERVAL_MS),
+            None => true,
+        };
+        if !due {
+            self.session.suppressed_vad_errors += 1;
+            return;
+        }
+        
+        error!(
+            "[错误] VAD处理失败，改用能量判定: {}（此前1秒内另有{}次）",
+            error, self.session.suppressed_vad_errors
+        );
+        self.session.last_vad_error_log = Some(now);
+        self.session.suppressed_vad_errors = 0;
     }
 }
 
-// 全局状态
-static mut SOCKET_MANAGER: Option<Arc<Mutex<SocketManager>>> = None;
-static mut VAD_PROCESSOR: Option<Arc<Mutex<VadProcessor>>> = None;
-static mut VAD_STATE_MACHINE: Option<Arc<Mutex<VadStateMachine>>> = None;
+// 应用全局状态，在 run() 中通过 .manage() 注册，命令通过 tauri::State 获取；
+// 后台线程与定时任务持有克隆出的 Arc，而不是访问全局变量
+#[derive(Clone)]
+struct AppState {
+    socket: Arc<Mutex<SocketManager>>,
+    vad: Arc<Mutex<VadProcessor>>,
+    sm: Arc<Mutex<VadStateMachine>>,
+    tts_queue: Arc<Mutex<TtsPlaybackQueue>>,
+    metrics: Arc<Mutex<Metrics>>,
+    params: Arc<Mutex<ParameterLayers>>,
+    journal: Arc<Mutex<Journal>>,
+    focus: Arc<Mutex<FocusGate>>,
+    history: Arc<Mutex<ConversationHistory>>,
+    turns: Arc<Mutex<TurnTracker>>,
+    partials: Arc<Mutex<PartialTranscriptMerger>>,
+    clock: Arc<Mutex<CaptureClock>>,
+    privacy: Arc<Mutex<PrivacyMode>>,
+    vad_state_stream: Arc<watch::Sender<VadStateSnapshot>>, // 状态快照，供 subscribe_vad_state 的订阅者
+    lifecycle: Arc<Lifecycle>, // 后台任务登记与关闭标志
+    mirror: Arc<Mutex<StateMirror>>, // 本地状态镜像，供其他本地程序读取
+    telemetry: Arc<Mutex<Telemetry>>, // 匿名遥测，默认关闭
+    autostart: Arc<Mutex<Autostart>>, // 开机自启注册与本次启动方式
+    audit: Arc<Mutex<CommandAudit>>, // 命令调用审计，由 command 宏在每次调用进入/退出时记录
+}
+
+impl AppState {
+    fn new() -> Self {
+        let lifecycle = Arc::new(Lifecycle::new());
+        let socket = init_socket_manager(&lifecycle);
+        
+        debug!("[调试] 初始化全局VAD处理器");
+        let vad = Arc::new(Mutex::new(VadProcessor::new()));
+        
+        debug!("[调试] 初始化VAD状态机");
+        let sm = Arc::new(Mutex::new(VadStateMachine::new(Arc::clone(&socket))));
+        
+        Self {
+            socket,
+            vad,
+            sm,
+            tts_queue: Arc::new(Mutex::new(TtsPlaybackQueue::new())),
+            metrics: Arc::new(Mutex::new(Metrics::new())),
+            params: Arc::new(Mutex::new(ParameterLayers::default())),
+            journal: Arc::new(Mutex::new(Journal::new())),
+            focus: Arc::new(Mutex::new(FocusGate::new())),
+            history: Arc::new(Mutex::new(ConversationHistory::new())),
+            turns: Arc::new(Mutex::new(TurnTracker::new())),
+            partials: Arc::new(Mutex::new(PartialTranscriptMerger::new())),
+            clock: Arc::new(Mutex::new(CaptureClock::new())),
+            privacy: Arc::new(Mutex::new(PrivacyMode::new())),
+            vad_state_stream: Arc::new(watch::channel(VadStateSnapshot::initial()).0),
+            lifecycle,
+            mirror: Arc::new(Mutex::new(StateMirror::new())),
+            telemetry: Arc::new(Mutex::new(Telemetry::new())),
+            autostart: Arc::new(Mutex::new(Autostart::from_args())),
+            audit: Arc::new(Mutex::new(CommandAudit::new())),
+        }
+    }
+}
 
 // 初始化Socket管理器
-fn init_socket_manager() -> Arc<Mutex<SocketManager>> {
+fn init_socket_manager(lifecycle: &Arc<Lifecycle>) -> Arc<Mutex<SocketManager>> {
     let manager = Arc::new(Mutex::new(SocketManager::new()));
-    
-    // 启动后台线程清理失败的语音段发送
-    let manager_clone = Arc::clone(&manager);
+    spawn_segment_resend(&manager, lifecycle);
+    manager
+}
+
+// 启动后台线程清理失败的语音段发送；收到关闭请求时退出，已在运行时不重复启动
+fn spawn_segment_resend(manager: &Arc<Mutex<SocketManager>>, lifecycle: &Arc<Lifecycle>) {
+    if lifecycle.is_running("segment_resend") {
+        return;
+    }
+    let guard = lifecycle.register("segment_resend");
+    let lifecycle = Arc::clone(lifecycle);
+    let manager_clone = Arc::clone(manager);
     thread::spawn(move || {
+        let _guard = guard;
         loop {
-            thread::sleep(Duration::from_secs(1));  // 每秒检查一次
+            if !lifecycle.sleep(Duration::from_secs(1)) {  // 每秒检查一次
+                break;
+            }
             
             let mut socket_manager = match manager_clone.lock() {
                 Ok(guard) => guard,
                 Err(e) => {
-                    println!("[错误] 获取SocketManager锁失败: {}", e);
+                    error!("[错误] 获取SocketManager锁失败: {}", e);
                     continue;
                 }
             };
             
             // 如果有失败的语音段，尝试重新发送
             if !socket_manager.speech_segments.is_empty() {
-                println!("[调试] 尝试重新发送之前失败的{}个语音段", socket_manager.speech_segments.len());
+                debug!("[调试] 尝试重新发送之前失败的{}个语音段", socket_manager.speech_segments.len());
                 socket_manager.send_speech_segments();
             }
+            
+            socket_manager.heartbeat(Instant::now());
+            
+            // 持久化待发队列中未送达的段
+            let resent = socket_manager.resend_queued();
+            if resent > 0 {
+                info!("[信息] 已补发待发队列中的{}个语音段", resent);
+            }
         }
+        info!("[信息] 语音段补发线程已停止");
     });
-    
-    manager
 }
 
-// 初始化VAD处理器
-fn init_vad_processor() -> Arc<Mutex<VadProcessor>> {
-    println!("[调试] 初始化全局VAD处理器");
-    let processor = Arc::new(Mutex::new(VadProcessor::new()));
-    processor
-}
-
-// 初始化VAD状态机
-fn init_vad_state_machine() -> Arc<Mutex<VadStateMachine>> {
-    println!("[调试] 初始化VAD状态机");
-    let state_machine = Arc::new(Mutex::new(VadStateMachine::new()));
-    state_machine
-}
-
-// 获取SocketManager实例
-fn get_socket_manager() -> Arc<Mutex<SocketManager>> {
-    unsafe {
-        if SOCKET_MANAGER.is_none() {
-            SOCKET_MANAGER = Some(init_socket_manager());
+// 聆听档位变化后的处理：离开主动聆听时结束进行中的会话，并通知前端
+fn apply_listening_mode_change(app_handle: Option<&tauri::AppHandle>, state: &AppState, change: &ListeningModeChange) {
+    info!("[信息] 聆听档位切换为 {:?}，原因: {}", change.mode, change.reason);
+    
+    if change.mode != ListeningMode::Active {
+        match state.sm.lock() {
+            Ok(mut state_machine) => {
+                if matches!(state_machine.get_current_state(), VadState::Speaking | VadState::Waiting | VadState::TransitionBuffer) {
+                    state_machine.reset_to_initial();
+                }
+            },
+            Err(e) => error!("[错误] 获取VAD状态机锁失败: {}", e),
         }
-        Arc::clone(SOCKET_MANAGER.as_ref().unwrap())
     }
-}
-
-// 获取VAD处理器实例
-fn get_vad_processor() -> Arc<Mutex<VadProcessor>> {
-    unsafe {
-        if VAD_PROCESSOR.is_none() {
-            VAD_PROCESSOR = Some(init_vad_processor());
+    
+    if let Some(app_handle) = app_handle {
+        if let Err(e) = app_handle.emit("listening-mode-changed", change) {
+            error!("[错误] 发送聆听档位变化事件到前端失败: {}", e);
         }
-        Arc::clone(VAD_PROCESSOR.as_ref().unwrap())
     }
+    publish_vad_state(state);
 }
 
-// 获取VAD状态机实例
-fn get_vad_state_machine() -> Arc<Mutex<VadStateMachine>> {
-    unsafe {
-        if VAD_STATE_MACHINE.is_none() {
-            VAD_STATE_MACHINE = Some(init_vad_state_machine());
+// 窗口焦点变化：先记录，防抖时长后仍保持该焦点才切换档位
+fn handle_window_focus(app_handle: &tauri::AppHandle, state: &AppState, focused: bool) {
+    match state.focus.lock() {
+        Ok(mut gate) => gate.observe_focus(focused, Instant::now()),
+        Err(e) => {
+            error!("[错误] 获取焦点门控锁失败: {}", e);
+            return;
         }
-        Arc::clone(VAD_STATE_MACHINE.as_ref().unwrap())
     }
+    
+    let app_handle = app_handle.clone();
+    let state = state.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(focus::FOCUS_DEBOUNCE_MS));
+        
+        let change = match state.focus.lock() {
+            Ok(mut gate) => gate.settle(Instant::now()),
+            Err(e) => {
+                error!("[错误] 获取焦点门控锁失败: {}", e);
+                None
+            }
+        };
+        if let Some(change) = change {
+            apply_listening_mode_change(Some(&app_handle), &state, &change);
+        }
+    });
 }
 
-#[command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+// 汇总当前可恢复的数据
+fn collect_checkpoint(state: &AppState) -> Result<Checkpoint, String> {
+    let vad_state = {
+        let vad_state_machine = &state.sm;
+        let state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        format!("{:?}", state_machine.current_state)
+    };
+    let pending_segments = {
+        let socket_manager = &state.socket;
+        let socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.speech_segments.clone()
+    };
+    let metrics = {
+        let metrics = &state.metrics;
+        let metrics_guard = metrics.lock().map_err(|e| format!("获取运行时指标失败: {}", e))?;
+        metrics_guard.report().cumulative
+    };
+    
+    let turns = {
+        let tracker = &state.turns;
+        let tracker_guard = tracker.lock().map_err(|e| format!("获取对话轮次失败: {}", e))?;
+        tracker_guard.snapshot()
+    };
+    
+    let journal = &state.journal;
+    let journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+    Ok(Checkpoint {
+        saved_at_ms: journal::now_unix_ms(),
+        session: SessionMeta {
+            vad_state,
+            started_at_ms: journal_guard.started_at_ms(),
+            metrics,
+        },
+        pending_segments,
+        transcripts: journal_guard.transcripts(),
+        turns,
+    })
 }
 
-#[command]
-async fn process_audio_frame(
-    app_handle: tauri::AppHandle,
-    audio_data: Vec<f32>
-) -> Result<VadEvent, String> {
-    // println!("[调试] 收到音频帧数据: 长度={}", audio_data.len());
+// 把checkpoint中可恢复的数据归位：待发语音段放回重发队列，转写结果放回转写缓冲
+fn restore_checkpoint(state: &AppState, checkpoint: &Checkpoint) -> Result<Vec<RecoveredItem>, String> {
+    let mut items = Vec::new();
     
-    if audio_data.len() < 10 {
-        return Err(format!("音频数据太短: {}", audio_data.len()));
+    if !checkpoint.pending_segments.is_empty() {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.speech_segments.extend(checkpoint.pending_segments.iter().cloned());
+        items.push(RecoveredItem {
+            kind: "pending_segments".to_string(),
+            count: checkpoint.pending_segments.len(),
+        });
     }
     
-    // 转换为i16格式
-    let i16_samples: Vec<i16> = audio_data
-        .iter()
-        .map(|&sample| (sample * 32767.0) as i16)
-        .collect();
-    
-    // 获取全局VAD处理器实例
-    let vad_processor = get_vad_processor();
-    let mut processor = match vad_processor.lock() {
-        Ok(guard) => guard,
-        Err(e) => {
-            println!("[错误] 获取VAD处理器锁失败: {}", e);
-            return Err(format!("获取VAD处理器失败: {}", e));
+    if !checkpoint.transcripts.is_empty() {
+        let journal = &state.journal;
+        let mut journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+        for result in checkpoint.transcripts.iter() {
+            journal_guard.record_transcript(result.clone());
         }
-    };
+        items.push(RecoveredItem {
+            kind: "transcripts".to_string(),
+            count: checkpoint.transcripts.len(),
+        });
+    }
     
-    let vad_state_machine = get_vad_state_machine();
-    let socket_manager = get_socket_manager();
+    if !checkpoint.turns.is_empty() {
+        let tracker = &state.turns;
+        let mut tracker_guard = tracker.lock().map_err(|e| format!("获取对话轮次失败: {}", e))?;
+        tracker_guard.restore(&checkpoint.turns);
+        items.push(RecoveredItem {
+            kind: "turns".to_string(),
+            count: checkpoint.turns.len(),
+        });
+    }
     
-    // 处理音频帧，返回(VAD事件, 是否是语音)
-    if let Some((event, is_voice)) = processor.process_frame(&i16_samples) {
-        
-        // 确定要发送给状态机的事件
-        let mut sm_event = if is_voice {
-            VadStateMachineEvent::VoiceFrame
-        } else {
-            VadStateMachineEvent::SilenceFrame
-        };
+    items.push(RecoveredItem {
+        kind: "session".to_string(),
+        count: 1,
+    });
+    Ok(items)
+}
+
+// 开关持久化待发队列：开启时打开并回放磁盘日志，未确认的段由重发线程在连接可用后补发；
+// 关闭时停止落盘，日志保留在磁盘上，再次开启时照常恢复
+fn apply_send_queue_persistence(app_handle: Option<&tauri::AppHandle>, state: &AppState, enabled: bool) -> Result<SendQueueStatus, String> {
+    let ephemeral = is_ephemeral(state);
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+    if !enabled {
+        socket_manager_guard.send_queue = None;
+        return Ok(SendQueueStatus::disabled());
+    }
+    
+    if socket_manager_guard.send_queue.is_none() {
+        let data_dir = app_handle
+            .ok_or("没有应用句柄，无法确定待发队列目录")?
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("获取应用数据目录失败: {}", e))?;
+        let mut queue = SendQueue::open(&send_queue::send_queue_dir(&data_dir))?;
+        if ephemeral {
+            queue.suspend();
+        }
+        let status = queue.status();
+        if status.recovered > 0 {
+            info!("[重要] 从持久化待发队列恢复{}个未送达的语音段，连接可用后补发", status.recovered);
+        }
+        socket_manager_guard.send_queue = Some(queue);
+    }
+    Ok(socket_manager_guard.send_queue.as_ref().map_or_else(SendQueueStatus::disabled, |queue| queue.status()))
+}
+
+// 启动时读取配置并应用到处理器、状态机与Socket管理器，在任何监听器启动之前执行。
+// 配置缺失或损坏时使用默认值；校验或应用失败只记录错误，不中断启动
+fn init_config(app_handle: &tauri::AppHandle, state: &AppState) -> Result<(), String> {
+    let path = config::config_path(app_handle)?;
+    let stored = config::load_or_default(&path);
+    apply_config(Some(app_handle), state, &stored)?;
+    info!("[信息] 已应用配置: {}", path.display());
+    Ok(())
+}
 
-        // 获取状态机锁
-        let mut state_machine = vad_state_machine.lock().unwrap();
+// 启动时按配置打开持久化待发队列（默认关闭）
+fn init_send_queue(app_handle: &tauri::AppHandle, state: &AppState) -> Result<(), String> {
+    let stored = config::load_from_disk(&config::config_path(app_handle)?)?;
+    if stored.persistent_send_queue == Some(true) {
+        apply_send_queue_persistence(Some(app_handle), state, true)?;
+    }
+    Ok(())
+}
 
-        // 检查临界状态是否超时
-        if *state_machine.get_current_state() == VadState::TransitionBuffer {
-            if let Some(enter_time) = state_machine.transition_buffer_enter_time {
-                if enter_time.elapsed() > Duration::from_millis(500) {
-                    //println!("[状态机] 临界状态超时，覆盖事件为TransitionTimeout");
-                    sm_event = VadStateMachineEvent::TransitionTimeout;
+// 启动时打开journal：上次异常退出则执行恢复，然后开始周期性checkpoint
+fn init_journal(app_handle: &tauri::AppHandle, state: &AppState) -> Result<(), String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("获取应用数据目录失败: {}", e))?;
+    
+    let previous = {
+        let journal = &state.journal;
+        let mut journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+        journal_guard.open(journal::journal_dir(&data_dir))?
+    };
+    
+    if let Some(loaded) = previous {
+        warn!("[警告] 检测到上次异常退出，开始恢复会话数据");
+        if let Ok(mut telemetry) = state.telemetry.lock() {
+            telemetry.record_crash();
+        }
+        let report = match loaded {
+            Ok(checkpoint) => RecoveryReport {
+                checkpoint_saved_at_ms: Some(checkpoint.saved_at_ms),
+                previous_session: Some(checkpoint.session.clone()),
+                recovered_items: restore_checkpoint(state, &checkpoint)?,
+                transcripts: checkpoint.transcripts.clone(),
+                error: None,
+            },
+            Err(e) => {
+                warn!("[警告] 无法加载checkpoint，跳过恢复: {}", e);
+                RecoveryReport {
+                    checkpoint_saved_at_ms: None,
+                    previous_session: None,
+                    recovered_items: Vec::new(),
+                    transcripts: Vec::new(),
+                    error: Some(e),
                 }
             }
-        }
-        
-        // 确保状态机有app_handle
-        state_machine.set_app_handle(app_handle.clone());
-        
-        // 根据VAD结果控制缓冲
-        let mut socket_manager_guard = socket_manager.lock().unwrap();
-        
-        // 始终更新前置缓冲区（无论是否在发送状态）
-        socket_manager_guard.add_to_pre_context(&i16_samples);
-        
-        // 使用新方法添加语音帧到当前语音段 - 这是保存VAD语音段的主要方法
-        socket_manager_guard.add_voice_frame(&i16_samples, is_voice);
-        
-        // 获取当前状态以检测状态变化
-        let old_should_send = match state_machine.get_current_state() {
-            VadState::Speaking | VadState::TransitionBuffer => true,
-            _ => false,
         };
         
-        // 处理状态机，获取是否应该发送到Python
-        let should_send_to_python = state_machine.process_event(sm_event, &mut socket_manager_guard);
-        
-        // 检测状态机从非发送状态转为发送状态（语音开始）
-        let is_speech_starting = !old_should_send && should_send_to_python;
+        info!("[信息] 恢复完成: {:?}", report.recovered_items);
+        if let Err(e) = app_handle.emit("recovered-from-crash", &report) {
+            error!("[错误] 发送崩溃恢复事件到前端失败: {}", e);
+        }
         
-        if should_send_to_python {
-            if is_speech_starting {
-                // println!("[重要] 语音开始！前置上下文帧已在状态机中发送");
+        let journal = &state.journal;
+        let mut journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+        journal_guard.set_last_recovery(report);
+    }
+    
+    spawn_checkpoint_loop(state);
+    Ok(())
+}
+
+// 周期性写入checkpoint；收到关闭请求时退出，已在运行时不重复启动
+fn spawn_checkpoint_loop(state: &AppState) {
+    if state.lifecycle.is_running("journal_checkpoint") {
+        return;
+    }
+    let guard = state.lifecycle.register("journal_checkpoint");
+    let state = state.clone();
+    thread::spawn(move || {
+        let _guard = guard;
+        while state.lifecycle.sleep(Duration::from_secs(journal::JOURNAL_CHECKPOINT_INTERVAL_SECS)) {
+            let result = collect_checkpoint(&state).and_then(|checkpoint| {
+                let journal = &state.journal;
+                let journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+                journal_guard.write_checkpoint(&checkpoint)
+            });
+            if let Err(e) = result {
+                error!("[错误] 写入checkpoint失败: {}", e);
             }
         }
-        
-        // 根据状态机决定是否处理音频
-        match event {
-            VadEvent::SpeechStart => {
-                println!("[重要] 检测到语音开始，开始发送音频帧");
-            },
-            VadEvent::SpeechEnd => {
-                println!("[重要] 检测到语音结束，停止发送音频帧");
-                
-                // 获取当前保存的语音段数量
-                let segment_count = socket_manager_guard.complete_speech_segments.len();
-                println!("[调试] 当前已保存{}个VAD语音段", segment_count);
-            },
-            _ => {}
+        info!("[信息] checkpoint线程已停止");
+    });
+}
+
+// 关闭后重新启动补发与checkpoint线程，并恢复脏标记；任务仍在运行时不做任何事
+fn ensure_background_tasks(state: &AppState) {
+    spawn_segment_resend(&state.socket, &state.lifecycle);
+    
+    let journal_open = match state.journal.lock() {
+        Ok(journal) => journal.is_open(),
+        Err(e) => {
+            error!("[错误] 获取journal锁失败: {}", e);
+            false
         }
-        
-        // 在语音会话期间发送所有音频帧（包括静音帧），保证STT获得完整上下文
-        if should_send_to_python {
-            // 发送当前音频帧（无论是否包含语音）
-            if socket_manager_guard.send_speech_segment(&i16_samples) {
-                if is_voice {
-                    // println!("[成功] 语音帧已发送到Python ({}个样本)", i16_samples.len());
-                } else {
-                    // println!("[成功] 静音帧已发送到Python ({}个样本) - 保持上下文", i16_samples.len());
-                }
-            } else {
-                // println!("[警告] 音频帧发送失败");
+    };
+    if journal_open && !state.lifecycle.is_running("journal_checkpoint") {
+        if let Ok(journal) = state.journal.lock() {
+            if let Err(e) = journal.mark_dirty() {
+                error!("[错误] {}", e);
             }
         }
-        
-        // 发送事件到前端
-        if let Err(e) = app_handle.emit("vad-event", &event) {
-                println!("[错误] 事件发送失败: {}", e);
-                return Err(format!("发送事件失败: {}", e));
-        }
-        
-        Ok(event)
-    } else {
-        Err("处理音频帧失败，可能是音频格式不兼容".into())
+        spawn_checkpoint_loop(state);
+    }
+    
+    let telemetry_enabled = state.telemetry.lock().map(|telemetry| telemetry.is_enabled()).unwrap_or(false);
+    if telemetry_enabled {
+        spawn_telemetry_loop(state);
     }
 }
 
-// 接收并转发STT结果到前端
-#[command]
-async fn start_stt_result_listener(app_handle: tauri::AppHandle) -> Result<(), String> {
-    println!("[调试] 启动STT结果监听器");
-    
-    // 先等待一小段时间让后端Socket启动
-    tokio::time::sleep(Duration::from_millis(500)).await;
-    
-    // 启动后台线程接收STT结果
-    let app_handle_clone = app_handle.clone();
-    tauri::async_runtime::spawn(async move {
-        #[cfg(unix)]
-        let result_socket_path = "/tmp/lumina_stt_result.sock";
-        #[cfg(windows)]
-        let result_tcp_address = "127.0.0.1:8766"; // Windows下使用不同的TCP端口接收结果
-        
-        loop {
-            // 尝试连接结果Socket（平台特定实现）
-            #[cfg(unix)]
-            let connection_result = UnixStream::connect(result_socket_path);
-            #[cfg(windows)]
-            let connection_result = match result_tcp_address.parse::<SocketAddr>() {
-                Ok(addr) => TcpStream::connect_timeout(&addr, Duration::from_millis(500)),
-                Err(_) => {
-                    println!("[错误] 解析TCP地址失败");
-                    tokio::time::sleep(Duration::from_secs(1)).await;
-                    continue;
+// 周期性上报匿名遥测；遥测被关闭或收到关闭请求时退出，已在运行时不重复启动。
+// 不留痕模式与仅本地模式下跳过上报，失败静默丢弃
+fn spawn_telemetry_loop(state: &AppState) {
+    if state.lifecycle.is_running("telemetry") {
+        return;
+    }
+    let guard = state.lifecycle.register("telemetry");
+    let state = state.clone();
+    thread::spawn(move || {
+        let _guard = guard;
+        while state.lifecycle.sleep(Duration::from_secs(telemetry::REPORT_INTERVAL_SECS)) {
+            let endpoint = match state.telemetry.lock() {
+                Ok(telemetry) if telemetry.is_enabled() => telemetry.endpoint().map(str::to_string),
+                Ok(_) => break,
+                Err(e) => {
+                    error!("[错误] 获取遥测状态锁失败: {}", e);
+                    break;
                 }
             };
+            let endpoint = match endpoint {
+                Some(endpoint) => endpoint,
+                None => break,
+            };
+            if is_ephemeral(&state) || is_local_only(&state) {
+                continue;
+            }
             
-            match connection_result {
-                Ok(mut stream) => {
-                    #[cfg(unix)]
-                    println!("[重要] STT结果监听器已成功连接到Socket: {}", result_socket_path);
-                    #[cfg(windows)]
-                    println!("[重要] STT结果监听器已成功连接到TCP服务器: {}", result_tcp_address);
-                    
-                    // 读取结果并转发 - 支持换行符分隔的JSON消息
-                    let mut buffer = Vec::new();
-                    let mut temp_buffer = [0; 1024];
-                    
-                    loop {
-                        match stream.read(&mut temp_buffer) {
-                            Ok(size) if size > 0 => {
-                                // println!("[调试] 从STT结果Socket接收到{}字节数据", size);
-                                buffer.extend_from_slice(&temp_buffer[0..size]);
-                                
-                                // 处理缓冲区中的完整消息（以换行符分隔）
-                                while let Some(newline_pos) = buffer.iter().position(|&b| b == b'\n') {
-                                    // 复制消息字节以避免借用冲突
-                                    let message_bytes = buffer[0..newline_pos].to_vec();
-                                    buffer.drain(0..=newline_pos); // 移除已处理的消息和换行符
-                                    
-                                    println!("[调试] 检测到完整JSON消息，长度: {}字节", message_bytes.len());
-                                    let message_str = String::from_utf8_lossy(&message_bytes);
-                                    println!("[调试] 原始JSON消息: {}", message_str);
-                                    
-                                    // 尝试解析JSON消息
-                                    match serde_json::from_slice::<SttResult>(&message_bytes) {
-                                        Ok(result) => {
-                                            if result.is_final {
-                                                // println!("[重要] 收到STT最终结果: '{}'", result.text);
-                                            } else {
-                                                // println!("[重要] 收到STT中间结果: '{}'", result.text);
-                                            }
-                                            
-                                            // 当收到非空文本时，向状态机发送BackendReturnText事件
-                                            if !result.text.is_empty() {
-                                                // 获取VAD状态机
-                                                let vad_state_machine = get_vad_state_machine();
-                                                let mut state_machine = match vad_state_machine.lock() {
-                                                    Ok(guard) => guard,
-                                                    Err(e) => {
-                                                        println!("[错误] 获取VAD状态机锁失败: {}", e);
-                                                        continue;
-                                                    }
-                                                };
-                                                
-                                                // 获取SocketManager
-                                                let socket_manager = get_socket_manager();
-                                                let mut socket_manager_guard = match socket_manager.lock() {
-                                                    Ok(guard) => guard,
-                                                    Err(e) => {
-                                                        println!("[错误] 获取SocketManager锁失败: {}", e);
-                                                        continue;
-                                                    }
-                                                };
-                                                
-                                                // 发送BackendReturnText事件到状态机
-                                                //println!("[状态机] 收到非空STT结果文本，触发BackendReturnText事件: '{}'", result.text);
-                                                let _should_send_to_python = state_machine.process_event(
-                                                    VadStateMachineEvent::BackendReturnText, 
-                                                    &mut socket_manager_guard
-                                                );
-                                            }
-                                            
-                                            // 发送到前端
-                                            // println!("[调试] 正在发送STT结果到前端: '{}' (最终: {})", 
-                                            //         result.text, result.is_final);
-                                            if let Err(e) = app_handle_clone.emit("stt-result", &result) {
-                                                println!("[错误] 发送STT结果到前端失败: {}", e);
-                                            } else {
-                                                // println!("[调试] 已成功发送STT结果到前端");
-                                            }
-                                        },
-                                        Err(e) => {
-                                            println!("[错误] 解析STT结果失败: {}", e);
-                                            println!("[调试] 原始消息: {:?}", String::from_utf8_lossy(&message_bytes));
-                                        }
-                                    }
-                                }
-                            },
-                            Ok(_) => {
-                                println!("[信息] STT结果连接关闭");
-                                break;
-                            },
-                            Err(e) => {
-                                println!("[错误] 读取STT结果失败: {}", e);
-                                break;
-                            }
-                        }
+            let result = collect_telemetry_report(&state).and_then(|report| {
+                let payload = telemetry::serialize_report(&report)?;
+                telemetry::send(&endpoint, &payload)?;
+                Ok(report.crashes)
+            });
+            match result {
+                Ok(crashes) => {
+                    if let Ok(mut telemetry) = state.telemetry.lock() {
+                        telemetry.mark_sent(crashes);
                     }
-                },
-                Err(e) => {
-                    // println!("[错误] 连接STT结果服务器失败: {}", e);
-                    tokio::time::sleep(Duration::from_secs(1)).await;
                 }
+                Err(e) => debug!("[调试] 遥测上报已丢弃: {}", e),
             }
         }
+        info!("[信息] 遥测线程已停止");
     });
+}
+
+// 从各模块汇总遥测报告。只取计数与耗时，轮次内容在这里就地折算成数量，不进入报告
+fn collect_telemetry_report(state: &AppState) -> Result<TelemetryReport, String> {
+    let (metrics_report, timing_histogram) = {
+        let metrics = state.metrics.lock().map_err(|e| format!("获取指标失败: {}", e))?;
+        (metrics.report(), metrics.timing_histogram())
+    };
+    let vad_stats = state.vad.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?.stats.snapshot();
+    let (completed_turns, empty_turns) = {
+        let turns = state.turns.lock().map_err(|e| format!("获取对话轮次失败: {}", e))?;
+        let completed: Vec<_> = turns.snapshot().into_iter().filter(|turn| turn.completed && !turn.user.deleted).collect();
+        let empty = completed
+            .iter()
+            .filter(|turn| turn.user.transcript.as_deref().is_none_or(|text| text.trim().is_empty()))
+            .count();
+        (completed.len() as u64, empty as u64)
+    };
+    let (install_id, crashes) = {
+        let telemetry = state.telemetry.lock().map_err(|e| format!("获取遥测状态失败: {}", e))?;
+        (telemetry.install_id(), telemetry.pending_crashes())
+    };
+    
+    let cumulative = &metrics_report.cumulative;
+    Ok(telemetry::build_report(
+        install_id,
+        cumulative.duration_secs as u64,
+        PipelineTelemetry {
+            frames_processed: cumulative.frames_processed,
+            voice_frames: cumulative.voice_frames,
+            frames_dropped: cumulative.frames_dropped,
+            frames_sent: cumulative.frames_sent,
+            send_failures: cumulative.send_failures,
+            fallback_frames: cumulative.fallback_frames,
+            speech_started: vad_stats.speech_started,
+            speech_ended: vad_stats.speech_ended,
+        },
+        LatencyTelemetry {
+            frame_timing_histogram: timing_histogram,
+            frame_timing_p50_ms: metrics_report.frame_timing.p50_ms,
+            frame_timing_p99_ms: metrics_report.frame_timing.p99_ms,
+            frame_interval_jitter_ms: metrics_report.frame_interval.jitter_ms,
+        },
+        VadTelemetry {
+            completed_turns,
+            empty_turns,
+            false_trigger_ratio: if completed_turns > 0 { empty_turns as f64 / completed_turns as f64 } else { 0.0 },
+        },
+        crashes,
+    ))
+}
+
+// 启动时确定后端端点：读取已保存的端点，准备每用户socket目录并清理陈旧socket，
+// 最后把实际端点导出到环境变量供后端sidecar继承
+fn init_backend_endpoints(app_handle: &tauri::AppHandle, state: &AppState) -> Result<(), String> {
+    let stored = config::load_from_disk(&config::config_path(app_handle)?)?;
+    let use_default_dir = stored.backend_endpoints.is_none();
+    let endpoints = stored.backend_endpoints.unwrap_or_else(BackendEndpoints::default_for_platform);
+    let legacy_fallback = stored.legacy_socket_fallback.unwrap_or(true);
+    
+    #[cfg(unix)]
+    if use_default_dir {
+        let dir = endpoints::default_socket_dir();
+        endpoints::prepare_socket_dir(&dir)?;
+        for path in endpoints::cleanup_stale_sockets(&dir) {
+            info!("[信息] 已清理陈旧socket: {}", path.display());
+        }
+    }
     
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+    socket_manager_guard.set_endpoints(endpoints, legacy_fallback);
+    info!("[信息] 后端端点: {:?}", socket_manager_guard.endpoints);
     Ok(())
 }
 
-#[command]
-async fn start_tts_audio_listener(app_handle: tauri::AppHandle) -> Result<(), String> {
-    println!("[调试] 启动TTS音频监听器");
+// 读取当前端点与旧路径回退开关，供各监听器重连时使用
+fn socket_endpoints(state: &AppState) -> (BackendEndpoints, bool) {
+    match state.socket.lock() {
+        Ok(socket_manager) => (socket_manager.endpoints.clone(), socket_manager.legacy_fallback),
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            (BackendEndpoints::default_for_platform(), true)
+        }
+    }
+}
+
+// 是否处于仅本地模式，锁不可用时按未开启处理
+fn is_local_only(state: &AppState) -> bool {
+    match state.socket.lock() {
+        Ok(socket_manager) => socket_manager.local_only,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            false
+        }
+    }
+}
 
+// 监听器的连接被主动关闭（退出或开启仅本地模式）时读取失败属正常，直接断开不上报
+fn listener_stream_closed(state: &AppState) -> bool {
+    state.lifecycle.is_shutting_down() || is_local_only(state)
+}
+
+// 启动后台任务；任务异常退出（panic）时通过 backend-error 通知前端，避免监听器静默停止
+fn spawn_reporting<F>(app_handle: tauri::AppHandle, task: &'static str, future: F)
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    let handle = tauri::async_runtime::spawn(future);
     tauri::async_runtime::spawn(async move {
-        #[cfg(unix)]
-        let tts_socket_path = "/tmp/lumina_tts.sock";
-        #[cfg(windows)]
-        let tts_tcp_address = "127.0.0.1:8767";
+        if let Err(e) = handle.await {
+            error!("[错误] 后台任务 {} 异常退出: {}", task, e);
+            backend_error::emit(&app_handle, &BackendError::new(
+                backend_error::TASK_FAILED,
+                format!("后台任务 {} 异常退出: {}", task, e),
+                false,
+            ));
+        }
+    });
+}
 
-        loop {
-            // Platform-specific connection
-            #[cfg(unix)]
-            let connection_result = UnixStream::connect(tts_socket_path);
-            #[cfg(windows)]
-            let connection_result = match tts_tcp_address.parse::<SocketAddr>() {
-                Ok(addr) => TcpStream::connect_timeout(&addr, Duration::from_millis(500)),
-                Err(_) => {
-                    // println!("[错误] 解析TTS TCP地址失败"); // This can be noisy
-                    tokio::time::sleep(Duration::from_secs(1)).await;
-                    continue;
-                }
-            };
+// 当前的TTS接收速率保护设置
+fn tts_flow_settings(state: &AppState) -> FlowSettings {
+    match state.tts_queue.lock() {
+        Ok(queue) => queue.flow.settings(),
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            FlowSettings::default()
+        }
+    }
+}
 
-            match connection_result {
-                Ok(mut stream) => {
-                    #[cfg(unix)]
-                    println!("[重要] TTS音频监听器已成功连接到Socket: {}", tts_socket_path);
-                    #[cfg(windows)]
-                    println!("[重要] TTS音频监听器已成功连接到TCP服务器: {}", tts_tcp_address);
+// 收到的一块TTS音频（或超大块切出的一段）：播放已取消时丢弃，否则入队并按顺序放行，返回分配的utterance_id
+fn accept_tts_chunk(app_handle: &tauri::AppHandle, state: &AppState, audio_chunk: Vec<u8>) -> Option<u64> {
+    let (utterance_id, resumed) = match state.tts_queue.lock() {
+        Ok(mut queue) => {
+            // 播放已取消：整帧已读完，分帧保持对齐，直接丢弃
+            if queue.is_cancelled() {
+                debug!("[TTS音频] 播放已取消，丢弃音频块({}字节)", audio_chunk.len());
+                return None;
+            }
+            let duration_ms = tts_queue::estimate_duration_ms(&audio_chunk);
+            let now = Instant::now();
+            queue.flow.on_chunk(now, duration_ms);
+            let resumed = queue.stall.on_chunk(now, duration_ms);
+            (queue.enqueue(audio_chunk), resumed)
+        },
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            backend_error::emit(app_handle, &BackendError::new(
+                backend_error::STATE_UNAVAILABLE,
+                format!("获取TTS播放队列失败，音频块已丢弃: {}", e),
+                false,
+            ));
+            return None;
+        }
+    };
+    if let Some(event) = resumed {
+        handle_tts_stall_event(app_handle, state, event, Some(utterance_id));
+    }
+    release_next_tts_utterance(Some(app_handle), state);
+    Some(utterance_id)
+}
 
-                    // 通知前端状态机准备好接收TTS音频
-                    // if let Err(e) = app_handle.emit("vad-state-changed", "Listening") {
-                    //     println!("[错误] 发送VAD状态变更事件失败: {}", e);
-                    // }
+// 单块超过缓冲上限时记一次切分并告警
+fn report_split_tts_chunk(app_handle: &tauri::AppHandle, state: &AppState, chunk_ms: u64, pieces: usize) {
+    let warning = match state.tts_queue.lock() {
+        Ok(mut queue) => {
+            queue.flow.record_split();
+            FloodWarning {
+                reason: FloodReason::OversizedChunk,
+                buffered_ms: queue.queued_duration_ms(),
+                chunk_ms: Some(chunk_ms),
+                settings: queue.flow.settings(),
+            }
+        },
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return;
+        }
+    };
+    warn!("[警告] TTS音频块时长{}ms超过缓冲上限，切分为{}段按节奏入队", chunk_ms, pieces);
+    emit_tts_flood_warning(app_handle, &warning);
+}
 
-                    let mut len_buffer = [0; 4];
-                    let mut audio_chunks_count = 0;
+// 字节数超过上限的TTS音频块：先读入开头识别格式，之后每读满一段即入队，段与段之间等待整形放行，
+// 排队音频达到上限时停止读取，内存占用不超过一段。data 之后的尾部字节读掉丢弃
+async fn receive_oversized_tts_chunk<R: Read>(
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    stream: &mut R,
+    len: usize,
+    piece_ms: u64,
+) -> Result<(), String> {
+    let mut head = vec![0; len.min(TTS_CHUNK_HEAD_BYTES)];
+    stream.read_exact(&mut head).map_err(|e| format!("读取TTS音频块({}字节)失败: {}", len, e))?;
+    let splitter = tts_queue::ChunkSplitter::new(&head, len, piece_ms);
+    let data_len = splitter.data_end() - splitter.data_offset();
+    let chunk_ms = (data_len as u64 * 1000)
+        .checked_div(tts_queue::chunk_info(&head).bytes_per_second())
+        .unwrap_or(0);
+    report_split_tts_chunk(app_handle, state, chunk_ms, data_len.div_ceil(splitter.piece_bytes()));
 
-                    loop {
-                        // Read length prefix
-                        match stream.read_exact(&mut len_buffer) {
-                            Ok(_) => {
-                                let len = u32::from_le_bytes(len_buffer) as usize;
-                                if len > 0 {
-                                    let mut audio_chunk = vec![0; len];
-                                    // Read audio data
-                                    if let Ok(_) = stream.read_exact(&mut audio_chunk) {
-                                        // 计数并定期报告收到的音频块数量
-                                        audio_chunks_count += 1;
-                                        if audio_chunks_count % 10 == 0 {
-                                            println!("[TTS音频] 已收到并处理 {} 个音频块", audio_chunks_count);
-                                        }
-                                        
-                                        // Base64 encode
-                                        let b64_audio = general_purpose::STANDARD.encode(&audio_chunk);
-                                        
-                                        #[derive(Serialize)]
-                                        struct AudioPayload<'a> {
-                                            data: &'a str,
-                                            format: &'a str,
-                                        }
+    let data_end = splitter.data_end();
+    let mut read = head.len();
+    let mut pcm = head[splitter.data_offset().min(read)..data_end.min(read)].to_vec();
+    let mut first = true;
+    loop {
+        while pcm.len() < splitter.piece_bytes() && read < data_end {
+            let wanted = (splitter.piece_bytes() - pcm.len()).min(data_end - read);
+            let filled = pcm.len();
+            pcm.resize(filled + wanted, 0);
+            stream.read_exact(&mut pcm[filled..]).map_err(|e| format!("读取TTS音频块({}字节)失败: {}", len, e))?;
+            read += wanted;
+        }
+        if pcm.is_empty() {
+            break;
+        }
+        if !first {
+            wait_for_tts_flow(app_handle, state).await;
+        }
+        first = false;
+        let piece_len = pcm.len().min(splitter.piece_bytes());
+        let rest = pcm.split_off(piece_len);
+        accept_tts_chunk(app_handle, state, splitter.wrap(&pcm));
+        pcm = rest;
+    }
+    if read < len {
+        std::io::copy(&mut stream.take((len - read) as u64), &mut std::io::sink())
+            .map_err(|e| format!("读取TTS音频块尾部失败: {}", e))?;
+    }
+    Ok(())
+}
 
-                                        // Emit to frontend
-                                        let payload = AudioPayload {
-                                            data: &b64_audio,
-                                            format: "pcm", // Assuming PCM, we might need to get this from backend
-                                        };
-                                        
-                                        if let Err(e) = app_handle.emit("backend-audio-data", &payload) {
-                                            println!("[错误] 发送TTS音频数据到前端失败: {}", e);
-                                        } else if audio_chunks_count == 1 {
-                                            // 第一个音频块特殊处理，确保前端知道音频开始播放
-                                            println!("[重要] 收到首个TTS音频块，已发送到前端");
-                                        }
-                                    } else {
-                                        println!("[错误] 读取TTS音频块失败");
-                                        break;
-                                    }
-                                }
-                            },
-                            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                                // println!("[TTS] 对端正常结束，EOF 收到");
-                                // break;        // 不再触发「错误-重连」逻辑
-                            }
-                            Err(e) => {
-                                eprintln!("[TTS] 读取长度出错: {e}");
-                                // reconnect_with_backoff(&mut retry_state).await?;
-                                continue;
-                            }
-                        }
-                    }
+// 读取下一块TTS音频前，按速率保护的要求等待：接收过快时等额度回填，排队过多时等前端播放消耗
+async fn wait_for_tts_flow(app_handle: &tauri::AppHandle, state: &AppState) {
+    while !state.lifecycle.is_shutting_down() {
+        let (reason, warning) = match state.tts_queue.lock() {
+            Ok(mut queue) => {
+                let buffered_ms = queue.queued_duration_ms();
+                let (reason, warn) = queue.flow.hold(Instant::now(), buffered_ms);
+                let settings = queue.flow.settings();
+                let warning = reason.filter(|_| warn).map(|reason| FloodWarning {
+                    reason,
+                    buffered_ms,
+                    chunk_ms: None,
+                    settings,
+                });
+                (reason, warning)
+            },
+            Err(e) => {
+                error!("[错误] 获取TTS播放队列锁失败: {}", e);
+                return;
+            }
+        };
+        if let Some(warning) = warning {
+            warn!("[警告] TTS接收暂停: {:?}，排队音频 {}ms", warning.reason, warning.buffered_ms);
+            emit_tts_flood_warning(app_handle, &warning);
+        }
+        if reason.is_none() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(TTS_FLOW_POLL_INTERVAL_MS)).await;
+    }
+}
+
+fn emit_tts_flood_warning(app_handle: &tauri::AppHandle, warning: &FloodWarning) {
+    if let Err(e) = app_handle.emit("tts-flood-warning", warning) {
+        error!("[错误] 发送TTS洪泛告警到前端失败: {}", e);
+    }
+}
+
+// 开机自启（最小化启动）时后端通常还没起来：确认后端可达之前监听器不进入连接循环，只安静地探测，
+// 避免开机早期刷重连日志。首次确认可达后所有监听器照常连接，之后的断线重连不受影响
+async fn wait_for_backend_at_login(state: &AppState) {
+    let mut logged = false;
+    while !state.lifecycle.is_shutting_down() {
+        let awaiting = match state.autostart.lock() {
+            Ok(autostart) => autostart.awaiting_backend(),
+            Err(e) => {
+                error!("[错误] 获取开机自启状态锁失败: {}", e);
+                return;
+            }
+        };
+        if !awaiting {
+            return;
+        }
+        if !logged {
+            info!("[信息] 最小化启动，等待后端可达后再连接");
+            logged = true;
+        }
+        
+        if !is_local_only(state) {
+            let (backend_endpoints, _) = socket_endpoints(state);
+            if endpoints::diagnose(&backend_endpoints, EndpointKind::Stt).reachable {
+                if let Ok(mut autostart) = state.autostart.lock() {
+                    autostart.backend_reachable();
+                }
+                info!("[信息] 后端已可达，开始连接");
+                return;
+            }
+        }
+        state.lifecycle.sleep_async(Duration::from_secs(BACKEND_WAIT_INTERVAL_SECS)).await;
+    }
+}
+
+// 启动后台线程检测 Listening 期间TTS音频是否中断流；收到关闭请求时退出，已在运行时不重复启动
+fn spawn_tts_stall_watch(app_handle: &tauri::AppHandle, state: &AppState) {
+    if state.lifecycle.is_running("tts_stall_watch") {
+        return;
+    }
+    let guard = state.lifecycle.register("tts_stall_watch");
+    let app_handle = app_handle.clone();
+    let state = state.clone();
+    thread::spawn(move || {
+        let _guard = guard;
+        while state.lifecycle.sleep(Duration::from_millis(tts_stall::STALL_CHECK_INTERVAL_MS)) {
+            let listening = match state.sm.lock() {
+                Ok(sm) => sm.current_state == VadState::Listening,
+                Err(e) => {
+                    error!("[错误] 获取VAD状态机锁失败: {}", e);
+                    continue;
+                }
+            };
+            let checked = match state.tts_queue.lock() {
+                Ok(mut queue) => {
+                    let queue_pending = queue.has_pending();
+                    let event = queue.stall.check(Instant::now(), listening, queue_pending);
+                    event.map(|event| (event, queue.playing_id()))
                 },
-                Err(_e) => {
-                    // This can be noisy if backend is not ready, so commented out for now.
-                    // println!("[错误] 连接TTS音频服务器失败: {}", e);
-                    tokio::time::sleep(Duration::from_secs(1)).await;
+                Err(e) => {
+                    error!("[错误] 获取TTS播放队列锁失败: {}", e);
+                    continue;
                 }
+            };
+            if let Some((event, utterance_id)) = checked {
+                handle_tts_stall_event(&app_handle, &state, event, utterance_id);
             }
         }
+        info!("[信息] TTS中断流检测线程已停止");
     });
+}
 
-    Ok(())
+// tts-stalled 事件内容
+#[derive(Serialize, Clone, Debug)]
+struct TtsStalled {
+    utterance_id: Option<u64>,
+    silent_ms: u64,
+    gave_up: bool, // 中断流超时，已视为播放结束
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct AudioSegment {
-    samples: Vec<i16>,
-    sample_rate: u32,
+// tts-resumed 事件内容
+#[derive(Serialize, Clone, Debug)]
+struct TtsResumed {
+    utterance_id: Option<u64>,
+    stalled_ms: u64,
+}
+
+// 处理中断流检测结果：通知前端；超时放弃时向后端询问并注入播放结束事件。调用时不能持有任何锁
+fn handle_tts_stall_event(app_handle: &tauri::AppHandle, state: &AppState, event: StallEvent, utterance_id: Option<u64>) {
+    match event {
+        StallEvent::Stalled { silent_ms } => {
+            warn!("[警告] TTS音频已{}ms没有新数据，回复可能中断", silent_ms);
+            let notice = TtsStalled { utterance_id, silent_ms, gave_up: false };
+            if let Err(e) = app_handle.emit("tts-stalled", notice) {
+                error!("[错误] 发送TTS中断流事件到前端失败: {}", e);
+            }
+        }
+        StallEvent::Resumed { stalled_ms } => {
+            info!("[信息] TTS音频中断{}ms后恢复", stalled_ms);
+            let notice = TtsResumed { utterance_id, stalled_ms };
+            if let Err(e) = app_handle.emit("tts-resumed", notice) {
+                error!("[错误] 发送TTS恢复事件到前端失败: {}", e);
+            }
+        }
+        StallEvent::GaveUp { stalled_ms } => {
+            warn!("[警告] TTS音频中断已超过{}ms，视为播放结束", stalled_ms);
+            let notice = TtsStalled { utterance_id, silent_ms: stalled_ms, gave_up: true };
+            if let Err(e) = app_handle.emit("tts-stalled", notice) {
+                error!("[错误] 发送TTS中断流事件到前端失败: {}", e);
+            }
+            match state.socket.lock() {
+                Ok(mut socket) => {
+                    socket.send_playback_inquiry(utterance_id.unwrap_or(0), stalled_ms);
+                }
+                Err(e) => error!("[错误] 获取SocketManager锁失败: {}", e),
+            }
+            if let Err(e) = dispatch_incoming_message(Some(app_handle), state, IncomingMessage::TtsEnd) {
+                error!("[错误] 注入音频播放结束事件失败: {}", e);
+            }
+        }
+    }
+}
+
+// 记录一条从后端收到的消息；调用时不能持有 socket 锁
+fn trace_incoming(state: &AppState, channel: TraceChannel, kind: &'static str, bytes: usize, ok: bool) {
+    match state.socket.lock() {
+        Ok(mut socket) => socket.trace.record(Direction::Incoming, channel, kind, bytes, ok),
+        Err(e) => error!("[错误] 获取SocketManager锁失败: {}", e),
+    }
+}
+
+// 正常退出时写入干净标记
+fn shutdown_journal(state: &AppState) {
+    let journal = &state.journal;
+    let journal_guard = match journal.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取journal锁失败: {}", e);
+            return;
+        }
+    };
+    
+    if let Err(e) = journal_guard.mark_clean() {
+        error!("[错误] {}", e);
+    }
+}
+
+// 把生效参数下发到VAD处理器、状态机与Socket管理器
+// 注意：调用方不能持有这三者中任何一个的锁
+fn apply_effective_parameters(state: &AppState, params: &[EffectiveParameter]) {
+    let value_of = |key: ParamKey| {
+        params.iter()
+            .find(|p| p.key == key)
+            .map(|p| p.value)
+            .unwrap_or_else(|| key.default_value())
+    };
+    let millis_of = |key: ParamKey| std::cmp::max(1, value_of(key).round() as u64);
+    
+    // 毫秒参数按当前实际帧长换算为帧数
+    let mut frame_ms = DEFAULT_FRAME_MS;
+    let mut speech_end_threshold_ms = None;
+    let mut min_speech_frames = 0;
+    if let Ok(mut processor) = state.vad.lock() {
+        frame_ms = processor.frame_duration_ms();
+        processor.speech_start_frames = std::cmp::max(1, params::ms_to_frames(value_of(ParamKey::SpeechStartMs), frame_ms));
+        processor.speech_end_silence_frames = std::cmp::max(1, params::ms_to_frames(value_of(ParamKey::SpeechEndSilenceMs), frame_ms));
+        speech_end_threshold_ms = Some(params::frames_to_ms(processor.speech_end_silence_frames, frame_ms).round() as u64);
+        processor.set_energy_threshold(value_of(ParamKey::EnergyFallbackThreshold) as f32);
+        processor.look_ahead.set_depth(params::ms_to_frames(value_of(ParamKey::LookaheadMs), frame_ms));
+        processor.min_speech_frames = params::ms_to_frames(value_of(ParamKey::MinSpeechMs), frame_ms);
+        min_speech_frames = processor.min_speech_frames;
+        processor.waiting_silence_frames = std::cmp::max(1, params::ms_to_frames(value_of(ParamKey::WaitingMs), frame_ms));
+    }
+    let frames_of = |key: ParamKey| std::cmp::max(1, params::ms_to_frames(value_of(key), frame_ms));
+    if let Ok(mut state_machine) = state.sm.lock() {
+        state_machine.max_silence_frames = frames_of(ParamKey::WaitingMs);
+        state_machine.transition_timeout_ms = millis_of(ParamKey::TransitionTimeoutMs);
+        state_machine.silence_report_interval_ms = millis_of(ParamKey::SilenceReportIntervalMs);
+        state_machine.finalize_timeout_ms = millis_of(ParamKey::FinalizeTimeoutMs);
+        if let Some(threshold_ms) = speech_end_threshold_ms {
+            state_machine.speech_end_threshold_ms.store(threshold_ms, Ordering::Relaxed);
+        }
+    }
+    if let Ok(mut socket_manager) = state.socket.lock() {
+        // 未达到最短语音时长而未报告的帧也在前置缓冲区中，缓冲区要额外容纳这部分
+        socket_manager.max_pre_context_frames = frames_of(ParamKey::PreContextMs) + min_speech_frames;
+    }
+}
+
+// 任一参数层变化后重新合成、下发并通知前端
+fn refresh_effective_parameters(app_handle: Option<&tauri::AppHandle>, state: &AppState) -> Result<Vec<EffectiveParameter>, String> {
+    let params = match state.params.lock() {
+        Ok(layers) => params::resolve_all(&layers),
+        Err(e) => {
+            error!("[错误] 获取参数解析层锁失败: {}", e);
+            return Err(format!("获取参数解析层失败: {}", e));
+        }
+    };
+    
+    apply_effective_parameters(state, &params);
+    
+    if let Some(app_handle) = app_handle {
+        if let Err(e) = app_handle.emit("parameters-changed", &params) {
+            error!("[错误] 发送参数变更事件到前端失败: {}", e);
+        }
+    }
+    
+    Ok(params)
+}
+
+// 把配置应用到各运行时组件（与单项设置命令相同的热切换路径）
+// 只改参数与开关，不重建Socket连接，也不改变状态机当前状态
+fn apply_config(app_handle: Option<&tauri::AppHandle>, state: &AppState, config: &LuminaConfig) -> Result<(), String> {
+    // 先整体校验，避免配置只被应用一部分
+    let mut user_params = std::collections::BTreeMap::new();
+    for (name, &value) in &config.parameters {
+        let (key, scale) = params::lookup(name).ok_or_else(|| format!("配置中存在未知的参数: {}", name))?;
+        let value = value * scale;
+        params::validate_value(key, value)?;
+        user_params.insert(key, value);
+    }
+    if let Some(p) = &config.timing_profile {
+        if !p.multiplier.is_finite() || p.multiplier <= 0.0 {
+            return Err(format!("profile倍率必须为正数: {}", p.multiplier));
+        }
+    }
+    if let Some(rate) = config.sample_rate {
+        if webrtc_sample_rate(rate).is_none() {
+            return Err(format!("配置中的采样率不受支持: {}Hz，可选值: {:?}", rate, SUPPORTED_SAMPLE_RATES));
+        }
+    }
+    if let Some(stages) = &config.audio_pipeline {
+        pipeline::validate(stages, config.sample_rate.unwrap_or(SAMPLE_RATE))?;
+    }
+    if let Some(stages) = &config.vad_input_filters {
+        pipeline::validate_filters(stages, config.sample_rate.unwrap_or(SAMPLE_RATE))?;
+    }
+    if config.focus_policy == Some(FocusPolicy::WakeWordWhenBlurred) {
+        features::require(Feature::WakeWord)?;
+    }
+    if let Some(silero) = &config.silero {
+        silero.validate()?;
+    }
+    if let Some(flow) = &config.tts_flow_control {
+        flow.validate()?;
+    }
+    if let Some(interval_ms) = config.audio_level_interval_ms {
+        validate_audio_level_interval(interval_ms)?;
+    }
+    if config.vad_backend == Some(VadBackendKind::Silero) {
+        features::require(Feature::SileroVad)?;
+    }
+    if config.denoise == Some(true) {
+        features::require(Feature::Denoise)?;
+    }
+    if config.audio_codec == Some(AudioCodec::Opus) {
+        features::require(Feature::CodecOpus)?;
+    }
+    
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        layers_guard.user = user_params;
+        layers_guard.mode = config.interaction_mode;
+        layers_guard.profile = config.timing_profile.clone();
+        // 后端hint属于运行时状态，不随配置重载清除
+    }
+    refresh_effective_parameters(app_handle, state)?;
+    
+    if let Some(sample_format) = config.sample_format {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.sample_format = sample_format;
+    }
+    
+    if let Some(codec) = config.audio_codec {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.set_codec(codec)?;
+    }
+    
+    if let Some(fade_ms) = config.pre_context_fade_ms {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.pre_context_fade_ms = fade_ms;
+    }
+    
+    if let Some(policy) = config.tts_interrupt_policy {
+        let queue = &state.tts_queue;
+        let mut queue_guard = queue.lock().map_err(|e| format!("获取TTS播放队列失败: {}", e))?;
+        queue_guard.set_interrupt_policy(policy);
+    }
+    
+    if let Some(policy) = config.focus_policy {
+        let change = {
+            let gate = &state.focus;
+            let mut gate_guard = gate.lock().map_err(|e| format!("获取焦点门控失败: {}", e))?;
+            gate_guard.set_policy(policy)
+        };
+        if let Some(change) = change {
+            apply_listening_mode_change(app_handle, state, &change);
+        }
+    }
+    
+    if let Some(mode) = config.vad_mode {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.set_mode(mode);
+    }
+    
+    if let Some(interval_ms) = config.audio_level_interval_ms {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.level_meter.set_interval_ms(interval_ms);
+    }
+    
+    if let Some(voting) = config.vad_voting {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.set_voting(voting);
+    }
+    
+    if let Some(enabled) = config.vad_high_pass {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.set_high_pass(enabled);
+    }
+    
+    if let Some(enabled) = config.denoise {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.set_denoise(enabled)?;
+    }
+    
+    if let Some(rate) = config.sample_rate {
+        apply_sample_rate(state, rate)?;
+    }
+    
+    if let Some(stages) = &config.vad_input_filters {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.set_input_filters(stages.clone())?;
+    }
+    
+    if let Some(enabled) = config.sample_rate_auto_correct {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.rate_check.set_auto_correct(enabled);
+    }
+    
+    if let Some(stages) = &config.audio_pipeline {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.pipeline.configure(stages.clone())?;
+    }
+    
+    if config.backend_endpoints.is_some() || config.legacy_socket_fallback.is_some() {
+        // 端点变化会断开当前连接，下一次发送时重连
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        let endpoints = config.backend_endpoints.clone().unwrap_or_else(|| socket_manager_guard.endpoints.clone());
+        let legacy_fallback = config.legacy_socket_fallback.unwrap_or(socket_manager_guard.legacy_fallback);
+        socket_manager_guard.set_endpoints(endpoints, legacy_fallback);
+    }
+    
+    if let Some(enabled) = config.finalize_on_silence {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        state_machine.finalize_on_silence = enabled;
+    }
+    
+    if let Some(enabled) = config.legacy_state_events {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        state_machine.legacy_state_events = enabled;
+    }
+    
+    if let Some(seconds) = config.max_utterance_seconds {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        state_machine.max_utterance_seconds = seconds;
+    }
+    
+    if let Some(enabled) = config.persistent_send_queue {
+        apply_send_queue_persistence(app_handle, state, enabled)?;
+    }
+    
+    if let Some(enabled) = config.local_only_mode {
+        apply_local_only_mode(state, enabled)?;
+    }
+    
+    if let Some(interval_secs) = config.heartbeat_interval_secs {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.heartbeat_interval_secs = interval_secs;
+    }
+    
+    if let Some(mode) = config.capture_mode {
+        apply_capture_mode(app_handle, state, mode)?;
+    }
+    
+    if let Some(path) = &config.state_mirror_path {
+        apply_state_mirror(state, Some(std::path::PathBuf::from(path)))?;
+    }
+    
+    if let Some(settings) = &config.telemetry {
+        apply_telemetry(state, settings)?;
+    }
+    
+    if let Some(interval_ms) = config.stt_partial_throttle_ms {
+        let partials = &state.partials;
+        let mut merger = partials.lock().map_err(|e| format!("获取中间结果合并器失败: {}", e))?;
+        merger.set_throttle_ms(interval_ms);
+    }
+    
+    if let Some(settings) = config.tts_gap_fill {
+        let queue = &state.tts_queue;
+        let mut queue_guard = queue.lock().map_err(|e| format!("获取TTS播放队列失败: {}", e))?;
+        queue_guard.gap_filler.set_settings(settings);
+    }
+    
+    if let Some(settings) = config.tts_flow_control {
+        let queue = &state.tts_queue;
+        let mut queue_guard = queue.lock().map_err(|e| format!("获取TTS播放队列失败: {}", e))?;
+        queue_guard.flow.set_settings(settings);
+    }
+    
+    // 判定后端最后切换：模型加载可能失败，失败时其余配置已生效，仍使用原后端
+    if config.vad_backend.is_some() || config.silero.is_some() {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        let kind = config.vad_backend.unwrap_or(processor.backend_kind);
+        let silero = config.silero.clone().unwrap_or_else(|| processor.silero.clone());
+        processor.set_backend(kind, silero)?;
+    }
+    
+    Ok(())
+}
+
+// 解析后端hint的data: {"参数名": 数值或null}，null表示撤销该参数的hint；
+// 可选的 "ttl_ms" 为本次设置的hint的生效时长，到期后自动撤销，回落到下层的值
+fn apply_vad_hint(app_handle: Option<&tauri::AppHandle>, state: &AppState, hint: VadHintPayload) -> Result<String, String> {
+    let hints = hint.params;
+    let ttl_ms = match hint.ttl_ms {
+        Some(ttl) if !ttl.is_finite() || ttl <= 0.0 => return Err(format!("hint生效时长必须为正数: {}", ttl)),
+        Some(ttl) => Some(ttl.round() as u64),
+        None => None,
+    };
+    
+    let mut pacing_changed = false;
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        let now = Instant::now();
+        for (name, value) in hints {
+            let (key, scale) = params::lookup(&name).ok_or_else(|| format!("未知的参数: {}", name))?;
+            pacing_changed |= key == ParamKey::SpeechEndSilenceMs;
+            match value.map(|v| v * scale) {
+                Some(v) => {
+                    params::validate_value(key, v)?;
+                    layers_guard.hints.insert(key, v);
+                    match ttl_ms {
+                        Some(ttl) => layers_guard.hint_expiry.insert(key, now + Duration::from_millis(ttl)),
+                        None => layers_guard.hint_expiry.remove(&key),
+                    };
+                },
+                None => {
+                    layers_guard.hints.remove(&key);
+                    layers_guard.hint_expiry.remove(&key);
+                },
+            }
+        }
+    }
+    
+    refresh_effective_parameters(app_handle, state)?;
+    if let Some(app_handle) = app_handle {
+        if pacing_changed {
+            emit_dialogue_pacing(app_handle, state, "hint_updated");
+        }
+        if let Some(ttl) = ttl_ms {
+            schedule_vad_hint_expiry(app_handle, Duration::from_millis(ttl));
+        }
+    }
+    Ok("VAD hint已应用".to_string())
+}
+
+// 到期时撤销已过期的hint；同一参数在此期间被重新设置时按新的到期时刻判断，不会被提前撤销
+fn schedule_vad_hint_expiry(app_handle: &tauri::AppHandle, ttl: Duration) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(ttl).await;
+        let state = app_handle.state::<AppState>();
+        let expired = match state.params.lock() {
+            Ok(mut layers) => layers.expire_hints(Instant::now()),
+            Err(e) => {
+                error!("[错误] 获取参数解析层锁失败: {}", e);
+                return;
+            }
+        };
+        if expired.is_empty() {
+            return;
+        }
+        
+        info!("[信息] VAD hint已到期还原: {:?}", expired);
+        if let Err(e) = refresh_effective_parameters(Some(&app_handle), &state) {
+            error!("[错误] 还原VAD hint失败: {}", e);
+            return;
+        }
+        if expired.contains(&ParamKey::SpeechEndSilenceMs) {
+            emit_dialogue_pacing(&app_handle, &state, "hint_expired");
+        }
+    });
+}
+
+// 把当前生效的语音结束静音阈值及其来源通知前端
+fn emit_dialogue_pacing(app_handle: &tauri::AppHandle, state: &AppState, reason: &'static str) {
+    let (param, duration_ms) = match state.params.lock() {
+        Ok(layers) => {
+            let param = params::resolve_param(&layers, ParamKey::SpeechEndSilenceMs);
+            let duration_ms = match (layers.hint_expiry.get(&ParamKey::SpeechEndSilenceMs), param.sources.last()) {
+                (Some(expires_at), Some(params::ParamSource::BackendHint { .. })) => {
+                    Some(expires_at.saturating_duration_since(Instant::now()).as_millis() as u64)
+                },
+                _ => None,
+            };
+            (param, duration_ms)
+        },
+        Err(e) => {
+            error!("[错误] 获取参数解析层锁失败: {}", e);
+            return;
+        }
+    };
+    let frame_duration_ms = match state.vad.lock() {
+        Ok(processor) => processor.frame_duration_ms(),
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return;
+        }
+    };
+    
+    let pacing = DialoguePacing {
+        threshold_ms: params::frames_to_ms(params::ms_to_frames(param.value, frame_duration_ms).max(1), frame_duration_ms).round() as u64,
+        source: param.sources.last().map(|source| source.layer_name()).unwrap_or("default"),
+        duration_ms,
+        reason,
+    };
+    if let Err(e) = app_handle.emit("dialogue-pacing-changed", &pacing) {
+        error!("[错误] 发送对话节奏变化事件到前端失败: {}", e);
+    }
+}
+
+// 发送到前端的TTS音频数据
+#[derive(Serialize, Clone, Debug)]
+struct AudioPayload<'a> {
+    data: &'a str,
+    format: &'a str,
+    utterance_id: u64,
+    duration_ms: u64,
+}
+
+// 若当前没有正在播放的utterance，则放行队首一条到前端，并广播队列状态
+fn release_next_tts_utterance(app_handle: Option<&tauri::AppHandle>, state: &AppState) {
+    let queue = &state.tts_queue;
+    let mut queue_guard = match queue.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return;
+        }
+    };
+
+    if let Some(utterance) = queue_guard.release_next() {
+        let b64_audio = general_purpose::STANDARD.encode(&utterance.data);
+        let payload = AudioPayload {
+            data: &b64_audio,
+            format: "pcm", // Assuming PCM, we might need to get this from backend
+            utterance_id: utterance.utterance_id,
+            duration_ms: utterance.duration_ms,
+        };
+
+        match app_handle.map(|app_handle| app_handle.emit("backend-audio-data", &payload)) {
+            Some(Err(e)) => error!("[错误] 发送TTS音频数据到前端失败: {}", e),
+            _ => debug!("[TTS音频] 放行utterance #{} ({}ms)", utterance.utterance_id, utterance.duration_ms),
+        }
+    }
+
+    if let Some(app_handle) = app_handle {
+        emit_tts_queue_status(app_handle, &queue_guard.status());
+    }
+}
+
+// 广播TTS播放队列状态
+fn emit_tts_queue_status(app_handle: &tauri::AppHandle, status: &TtsPlaybackStatus) {
+    if let Err(e) = app_handle.emit("tts-playback-status", status) {
+        error!("[错误] 发送TTS播放队列状态到前端失败: {}", e);
+    }
+}
+
+// 应用信息：版本与本次构建启用的可选能力
+#[derive(Serialize, Clone, Debug)]
+struct AppInfo {
+    name: &'static str,
+    version: &'static str,
+    features: Vec<Feature>,           // 已启用
+    available_features: Vec<Feature>, // 全部可选能力
+    privacy: Option<PrivacyStatus>,   // 不留痕模式状态，状态不可读时为None
+    local_only: Option<bool>,         // 仅本地模式，状态不可读时为None
+}
+
+#[command]
+fn get_app_info(state: State<'_, AppState>) -> AppInfo {
+    app_info(&state)
+}
+
+fn app_info(state: &AppState) -> AppInfo {
+    AppInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        features: features::enabled_features(),
+        available_features: features::ALL_FEATURES.to_vec(),
+        privacy: state.privacy.lock().ok().map(|privacy| privacy.status()),
+        local_only: state.socket.lock().ok().map(|socket_manager| socket_manager.local_only),
+    }
+}
+
+#[command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+// 处理前端送来的一块音频，返回这一块中按顺序发生的全部VAD事件（不含Processing），没有状态变化时为空
+#[command]
+async fn process_audio_frame(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    audio_data: Vec<f32>,
+    input_sample_rate: Option<u32>,
+    capture_ts_ms: Option<f64>,
+    frame_id: Option<u64>,
+) -> Result<Vec<VadEvent>, String> {
+    // debug!("[调试] 收到音频帧数据: 长度={}", audio_data.len());
+    
+    if audio_data.len() < 10 {
+        return Err(format!("音频数据太短: {}", audio_data.len()));
+    }
+    
+    // 前端带了帧序号时只处理严格递增的帧：重试导致的重复或回退帧直接跳过，
+    // 不计入帧间隔、VAD计数，也不进入状态机
+    if let Some(frame_id) = frame_id {
+        let accepted = match state.metrics.lock() {
+            Ok(mut metrics_guard) => metrics_guard.accept_frame_id(frame_id, Instant::now()),
+            Err(_) => true,
+        };
+        if !accepted {
+            debug!("[调试] 跳过重复或回退的音频帧 #{}", frame_id);
+            return Ok(Vec::new());
+        }
+    }
+    
+    // 未声明采样率时按当前管线采样率处理
+    let input_rate = match input_sample_rate {
+        Some(rate) => rate,
+        None => match state.vad.lock() {
+            Ok(processor) => processor.sample_rate,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        },
+    };
+    resample::validate_input(audio_data.len(), input_rate)?;
+    
+    // 前端带了采集时刻（performance.now基准）时换算到本地时钟，消除IPC延迟的抖动；
+    // 之后的帧间隔统计、静音计时都使用这个时间
+    let arrival = Instant::now();
+    let capture_time = match capture_ts_ms {
+        Some(ts) => match state.clock.lock() {
+            Ok(mut clock) => clock.observe(ts, arrival),
+            Err(_) => arrival,
+        },
+        None => arrival,
+    };
+    
+    // 记录帧间隔，调度抖动过大时告警
+    let jitter_warning = match state.metrics.lock() {
+        Ok(mut metrics_guard) => metrics_guard.record_frame_arrival(capture_time),
+        Err(_) => None,
+    };
+    if let Some(warning) = jitter_warning {
+        warn!("[警告] 音频帧到达间隔异常: {:.1}ms (平均 {:.1}ms)", warning.interval_ms, warning.mean_ms);
+        if let Err(e) = app_handle.emit("frame-jitter-warning", &warning) {
+            error!("[错误] 发送帧抖动告警到前端失败: {}", e);
+        }
+    }
+    
+    // 当前聆听档位不允许输入时直接丢弃该帧
+    let audio_allowed = match state.focus.lock() {
+        Ok(gate) => gate.allows_audio(Instant::now()),
+        Err(_) => true,
+    };
+    if !audio_allowed {
+        return Ok(Vec::new());
+    }
+    
+    // 麦克风静音：只在本地计算电平供界面显示，不做VAD判定、不进入状态机，也不写入任何发送缓冲；
+    // 状态机锁不可用时按静音处理
+    let muted = match state.sm.lock() {
+        Ok(state_machine) => state_machine.muted,
+        Err(_) => true,
+    };
+    if muted {
+        if let Ok(mut processor) = state.vad.lock() {
+            let samples: Vec<i16> = audio_data.iter().map(|&sample| audio_utils::f32_to_i16(sample)).collect();
+            let (rms, peak) = (audio_utils::frame_rms(&samples), audio_utils::frame_peak(&samples));
+            if let Some(level) = processor.level_meter.observe(capture_time, rms, peak, false) {
+                if let Err(e) = app_handle.emit("audio-level", &level) {
+                    error!("[错误] 发送电平事件到前端失败: {}", e);
+                }
+            }
+        }
+        return Ok(Vec::new());
+    }
+    
+    let frame_started = Instant::now();
+    
+    // 削波样本在转换时会被限幅，比例过高时提示用户降低输入增益
+    if let Some(clipping) = MicClippingEvent::check(&audio_data) {
+        if let Err(e) = app_handle.emit("mic-clipping", &clipping) {
+            error!("[错误] 发送削波提示到前端失败: {}", e);
+        }
+    }
+    
+    // 获取全局VAD处理器实例
+    let vad_processor = &state.vad;
+    let mut processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    // 按帧到达节奏核对声明的输入采样率，不匹配时告警；开启自动纠正时按检测到的采样率重采样
+    let (input_rate, rate_mismatch) = processor.rate_check.observe(audio_data.len(), input_rate, capture_time);
+    if let Some(mismatch) = rate_mismatch {
+        warn!(
+            "[警告] 输入采样率与声明不符: 声明{}Hz，实测约{:.0}Hz（接近{}Hz）{}",
+            mismatch.declared_rate,
+            mismatch.measured_rate,
+            mismatch.detected_rate,
+            if mismatch.corrected { "，已按检测到的采样率重采样" } else { "" }
+        );
+        if let Err(e) = app_handle.emit("sample-rate-mismatch", &mismatch) {
+            error!("[错误] 发送采样率不匹配事件到前端失败: {}", e);
+        }
+    }
+    
+    // 重采样到管线采样率后再转换为i16格式，VAD与后端收到的都是重采样后的数据
+    let mut resampled = processor.resample_input(&audio_data, input_rate);
+    // 降噪在重采样之后、转换与切帧之前进行，VAD与后端收到的都是降噪后的数据；
+    // 降噪缓冲中滞留的样本计入帧时间的回推
+    let mut denoise_latency = Duration::ZERO;
+    if let Some(denoiser) = processor.denoise.as_mut() {
+        resampled = denoiser.process(&resampled);
+        denoise_latency = denoiser.latency();
+    }
+    let mut i16_samples: Vec<i16> = resampled
+        .iter()
+        .map(|&sample| audio_utils::f32_to_i16(sample))
+        .collect();
+    // 高通滤波在切帧前进行，滤波器状态跨块连续；VAD与后端收到的都是滤波后的数据
+    if let Some(filter) = processor.high_pass.as_mut() {
+        filter.process(&mut i16_samples);
+    }
+    processor.input_filters.process(&mut i16_samples);
+    
+    // 前端的块长不一定是合法帧长（如AudioWorklet的256样本），切成完整帧逐帧处理，余下的样本留到下一次
+    // 第一帧可能以上一块留下的残余样本开头，帧时间从残余样本的采集时刻起算
+    let sample_rate = processor.sample_rate;
+    let frame_duration_before = processor.frame_duration_ms();
+    let carried = samples_to_duration(processor.session.residual.len(), sample_rate) + denoise_latency;
+    let mut frame_time = capture_time.checked_sub(carried).unwrap_or(capture_time);
+    let frames = processor.take_frames(&i16_samples);
+    // 一块可能切出多帧，语音开始与结束可能落在同一块中，逐个保留
+    let mut events = Vec::new();
+    for frame in &frames {
+        let event = process_vad_frame(&app_handle, &state, &mut processor, frame, frame_time)?;
+        frame_time += samples_to_duration(frame.len(), sample_rate);
+        if !matches!(event, VadEvent::Processing) {
+            events.push(event);
+        }
+    }
+    let frame_duration_after = processor.frame_duration_ms();
+    drop(processor);
+    publish_vad_state(&state);
+    
+    // 前端改变了块长导致帧长变化时，按新帧长重新换算毫秒阈值，保持时间语义不变
+    if frame_duration_after != frame_duration_before {
+        info!("[信息] 帧长由{:.1}ms变为{:.1}ms，重新换算VAD阈值", frame_duration_before, frame_duration_after);
+        refresh_effective_parameters(None, &state)?;
+    }
+    
+    // 记录本次调用的核心处理耗时，慢帧告警
+    let slow_frame = match state.metrics.lock() {
+        Ok(mut metrics_guard) => metrics_guard.record_frame_timing(frame_started.elapsed()),
+        Err(_) => None,
+    };
+    if let Some(warning) = slow_frame {
+        warn!("[警告] 音频帧处理耗时过长: {:.1}ms", warning.elapsed_ms);
+        if let Err(e) = app_handle.emit("slow-frame-warning", &warning) {
+            error!("[错误] 发送慢帧告警到前端失败: {}", e);
+        }
+    }
+    
+    Ok(events)
+}
+
+// 给定采样率下若干样本对应的时长
+fn samples_to_duration(samples: usize, sample_rate: u32) -> Duration {
+    Duration::from_secs_f64(samples as f64 / sample_rate as f64)
+}
+
+// 处理一个完整的VAD帧：驱动状态机、更新语音段并按需发送到后端
+// frame_time 为该帧在本地时钟上的采集时刻
+fn process_vad_frame(
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    processor: &mut VadProcessor,
+    i16_samples: &[i16],
+    frame_time: Instant,
+) -> Result<VadEvent, String> {
+    // 逐帧能量，只计算一次供各处复用
+    let frame_rms = audio_utils::frame_rms(i16_samples);
+    
+    let vad_state_machine = &state.sm;
+    let socket_manager = &state.socket;
+    let metrics = &state.metrics;
+    
+    // 用户未说话时跟踪底噪，供双重判定模式使用；按键说话模式下取按键状态
+    let (idle, push_to_talk) = match vad_state_machine.lock() {
+        Ok(state_machine) => (
+            *state_machine.get_current_state() == VadState::Initial,
+            (state_machine.capture_mode == CaptureMode::PushToTalk).then_some(state_machine.ptt_held),
+        ),
+        Err(_) => (false, None),
+    };
+    
+    // 校准期间只统计能量与单帧判定，帧不进入状态机，也不发往后端
+    if processor.calibration.is_some() {
+        let is_voice = processor.backend.is_voice(i16_samples).unwrap_or(false);
+        let frame_duration = samples_to_duration(i16_samples.len(), processor.sample_rate);
+        let peak = audio_utils::frame_peak(i16_samples);
+        if let Some(calibration) = processor.calibration.as_mut() {
+            calibration.observe(frame_rms, peak, is_voice, frame_duration);
+        }
+        if let Some(level) = processor.level_meter.observe(frame_time, frame_rms, peak, is_voice) {
+            if let Err(e) = app_handle.emit("audio-level", &level) {
+                error!("[错误] 发送电平事件到前端失败: {}", e);
+            }
+        }
+        return Ok(VadEvent::Processing);
+    }
+    
+    // 按键说话模式跳过VAD判定，按下期间的帧都作为语音直接送往下游
+    if let Some(held) = push_to_talk {
+        if let Ok(mut metrics_guard) = metrics.lock() {
+            metrics_guard.record_frame(held, false);
+        }
+        let peak = audio_utils::frame_peak(i16_samples);
+        if let Some(level) = processor.level_meter.observe(frame_time, frame_rms, peak, held) {
+            if let Err(e) = app_handle.emit("audio-level", &level) {
+                error!("[错误] 发送电平事件到前端失败: {}", e);
+            }
+        }
+        forward_vad_frame(Some(app_handle), state, i16_samples, held, frame_time);
+        return Ok(VadEvent::Processing);
+    }
+    
+    if idle {
+        processor.update_noise_floor(frame_rms);
+    }
+    
+    // 处理音频帧，返回(VAD事件, 是否是语音)
+    if let Some((event, is_voice, source)) = processor.process_frame(i16_samples) {
+        if let Ok(mut metrics_guard) = metrics.lock() {
+            metrics_guard.record_frame(is_voice, false);
+            if source == VadSource::EnergyFallback {
+                metrics_guard.record_fallback_frame();
+            }
+        }
+        
+        // 电平表按窗口节流，窗口结束时才发事件
+        let peak = audio_utils::frame_peak(i16_samples);
+        if let Some(level) = processor.level_meter.observe(frame_time, frame_rms, peak, is_voice) {
+            if let Err(e) = app_handle.emit("audio-level", &level) {
+                error!("[错误] 发送电平事件到前端失败: {}", e);
+            }
+        }
+        
+        // 经过前瞻延迟线后再驱动状态机与发送；判定语音开始时延迟线中尚未送出的帧一并作为语音
+        let ready = processor.look_ahead.push(i16_samples.to_vec(), frame_time, is_voice, matches!(event, VadEvent::SpeechStart));
+        for frame in &ready {
+            forward_vad_frame(Some(app_handle), state, &frame.samples, frame.is_voice, frame.captured_at);
+        }
+        
+        // 根据状态机决定是否处理音频
+        match event {
+            VadEvent::SpeechStart => {
+                info!("[重要] 检测到语音开始，开始发送音频帧");
+            },
+            VadEvent::SpeechEnd => {
+                info!("[重要] 检测到语音结束，停止发送音频帧");
+                
+                // 用户说完新的一句，之后到达的TTS音频属于新一轮回复
+                clear_tts_cancellation(state);
+                
+                // 获取当前保存的语音段数量
+                if let Ok(socket_manager_guard) = socket_manager.lock() {
+                    let segment_count = socket_manager_guard.complete_speech_segments.len();
+                    debug!("[调试] 当前已保存{}个VAD语音段", segment_count);
+                }
+            },
+            _ => {}
+        }
+        
+        // 发送事件到前端
+        if let Err(e) = app_handle.emit("vad-event", &event) {
+                error!("[错误] 事件发送失败: {}", e);
+                return Err(format!("发送事件失败: {}", e));
+        }
+        
+        Ok(event)
+    } else {
+        if let Ok(mut metrics_guard) = metrics.lock() {
+            metrics_guard.record_frame(false, true);
+        }
+        Err("处理音频帧失败，可能是音频格式不兼容".into())
+    }
+}
+
+// 把一帧送往下游：驱动状态机、更新语音段并按需发送到后端
+// frame_time 为该帧在本地时钟上的采集时刻；开启前瞻时帧比判定晚K帧到达这里；app_handle 为 None 时不向前端发事件
+fn forward_vad_frame(
+    app_handle: Option<&tauri::AppHandle>,
+    state: &AppState,
+    i16_samples: &[i16],
+    is_voice: bool,
+    frame_time: Instant,
+) {
+    let frame_rms = audio_utils::frame_rms(i16_samples);
+    let vad_state_machine = &state.sm;
+    let socket_manager = &state.socket;
+    let metrics = &state.metrics;
+    
+    // 确定要发送给状态机的事件；临界状态超时由状态机按帧时刻统一判定
+    let sm_event = if is_voice {
+        VadStateMachineEvent::VoiceFrame
+    } else {
+        VadStateMachineEvent::SilenceFrame
+    };
+
+    // 获取状态机锁
+    let mut state_machine = vad_state_machine.lock().unwrap();
+
+    // 确保状态机有app_handle
+    if let Some(app_handle) = app_handle {
+        state_machine.set_app_handle(app_handle.clone());
+    }
+    
+    // 根据VAD结果控制缓冲
+    let mut socket_manager_guard = socket_manager.lock().unwrap();
+    
+    // 始终更新前置缓冲区（无论是否在发送状态）
+    socket_manager_guard.add_to_pre_context(i16_samples);
+    
+    // 使用新方法添加语音帧到当前语音段 - 这是保存VAD语音段的主要方法
+    socket_manager_guard.add_voice_frame(i16_samples, is_voice, frame_time);
+    
+    // 获取当前状态以检测状态变化
+    let old_should_send = matches!(state_machine.get_current_state(), VadState::Speaking | VadState::TransitionBuffer);
+    
+    // 处理状态机，获取是否应该发送到Python
+    let should_send_to_python = state_machine.process_event_at(sm_event, &mut socket_manager_guard, frame_time);
+    
+    if let (Some(outcome), Some(app_handle)) = (state_machine.check_finalize_timeout(frame_time), app_handle) {
+        if let Err(e) = app_handle.emit("utterance-finalized", &outcome) {
+            error!("[错误] 发送最终识别结果事件到前端失败: {}", e);
+        }
+    }
+    
+    // 检测状态机从非发送状态转为发送状态（语音开始）
+    let is_speech_starting = !old_should_send && should_send_to_python;
+    let is_speech_ending = old_should_send && !should_send_to_python;
+    if is_speech_ending {
+        socket_manager_guard.spans.speech_ended();
+    }
+    
+    // 用户输入的起止与语音段归入对话轮次
+    let finished_segments = socket_manager_guard.take_finished_segment_ids();
+    let completed_turns = match state.turns.lock() {
+        Ok(mut tracker) => {
+            let mut completed = Vec::new();
+            if is_speech_starting {
+                completed = tracker.user_speech_started(unix_ms_at(frame_time));
+            }
+            tracker.audio_segments_finished(&finished_segments);
+            if is_speech_ending {
+                tracker.user_speech_ended(unix_ms_at(frame_time));
+            }
+            completed
+        },
+        Err(e) => {
+            error!("[错误] 获取对话轮次锁失败: {}", e);
+            Vec::new()
+        }
+    };
+    if let Some(app_handle) = app_handle {
+        emit_completed_turns(app_handle, completed_turns);
+    }
+    
+    // 会话中的语音帧计入输入响度统计
+    if is_voice && should_send_to_python {
+        state_machine.input_level.record_voice_frame(frame_rms);
+    }
+    
+    if should_send_to_python && is_speech_starting {
+        // info!("[重要] 语音开始！前置上下文帧已在状态机中发送");
+    }
+    
+    // 在语音会话期间发送所有音频帧（包括静音帧），保证STT获得完整上下文
+    if should_send_to_python {
+        // 发送当前音频帧（无论是否包含语音）
+        let sent = socket_manager_guard.send_speech_segment(i16_samples);
+        if sent {
+            if is_voice {
+                // info!("[成功] 语音帧已发送到Python ({}个样本)", i16_samples.len());
+            } else {
+                // info!("[成功] 静音帧已发送到Python ({}个样本) - 保持上下文", i16_samples.len());
+            }
+        } else {
+            // warn!("[警告] 音频帧发送失败");
+        }
+        if let Ok(mut metrics_guard) = metrics.lock() {
+            metrics_guard.record_send(sent);
+        }
+        // 仅唤醒词档位下，会话进行中保持门控打开
+        if let Ok(mut gate) = state.focus.lock() {
+            gate.extend_wake(Instant::now());
+        }
+    }
+    
+    // 本帧发送（含状态机触发的前置上下文与控制消息）中产生的错误
+    let errors = socket_manager_guard.errors.take();
+    if let Some(app_handle) = app_handle {
+        backend_error::emit_all(app_handle, errors);
+    }
+}
+
+// 本地单调时钟上的时刻换算为Unix毫秒
+fn unix_ms_at(instant: Instant) -> u64 {
+    let elapsed = Instant::now().saturating_duration_since(instant).as_millis() as u64;
+    journal::now_unix_ms().saturating_sub(elapsed)
+}
+
+// 通知前端新完成的对话轮次
+// 单次会话的首响应延迟，随 first-response-latency 事件发给前端
+#[derive(Serialize, Clone, Debug)]
+struct FirstResponseLatency {
+    session_id: u64,
+    mode: String, // 当时的交互模式，未设置时为 none
+    latency_ms: f64,
+}
+
+// 首响应延迟按交互模式计入指标分布，并通知前端
+fn record_first_response(app_handle: Option<&tauri::AppHandle>, state: &AppState, session_id: u64, latency: Duration) {
+    let mode = match state.params.lock() {
+        Ok(layers) => layers.mode.map_or("none", InteractionMode::name),
+        Err(e) => {
+            error!("[错误] 获取参数解析层锁失败: {}", e);
+            "none"
+        }
+    };
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    if let Ok(mut metrics_guard) = state.metrics.lock() {
+        metrics_guard.record_first_response(mode, latency_ms);
+    }
+    debug!("[调试] 会话{}首响应延迟: {:.0}ms（{}）", session_id, latency_ms, mode);
+    
+    let payload = FirstResponseLatency {
+        session_id,
+        mode: mode.to_string(),
+        latency_ms,
+    };
+    if let Some(app_handle) = app_handle {
+        if let Err(e) = app_handle.emit("first-response-latency", &payload) {
+            error!("[错误] 发送首响应延迟事件到前端失败: {}", e);
+        }
+    }
+}
+
+fn emit_completed_turns(app_handle: &tauri::AppHandle, completed: Vec<Turn>) {
+    for turn in completed {
+        if let Err(e) = app_handle.emit("turn-completed", &turn) {
+            error!("[错误] 发送轮次完成事件到前端失败: {}", e);
+        }
+    }
+}
+
+// 来自后端的消息，真实Socket与模拟注入共用同一分发路径
+#[derive(Debug, Clone)]
+enum IncomingMessage {
+    SttResult(SttResult),                      // STT识别结果（中间或最终）
+    Control { action: String, data: serde_json::Value }, // 后端控制消息，data 的格式见 control_actions
+    TtsBegin,                                  // TTS音频开始播放
+    TtsEnd,                                    // TTS音频播放结束
+}
+
+// 后端控制消息的处理回显，每条控制消息处理完后通过 backend-control 事件发给前端调试面板
+#[derive(Serialize, Clone, Debug)]
+struct BackendControlEcho {
+    action: String,
+    data: serde_json::Value,
+    ok: bool,
+    result: String, // 处理结果，失败时为错误信息
+    received_at_ms: u64,
+}
+
+// 分发一条来自后端的消息；app_handle 为 None 时只更新状态不发事件
+fn dispatch_incoming_message(app_handle: Option<&tauri::AppHandle>, state: &AppState, message: IncomingMessage) -> Result<String, String> {
+    let result = match message {
+        IncomingMessage::SttResult(result) => apply_stt_result(app_handle, state, result),
+        IncomingMessage::Control { action, data } => {
+            let received_at_ms = journal::now_unix_ms();
+            let result = apply_backend_control(app_handle, state, &action, data.clone());
+            let echo = BackendControlEcho {
+                action,
+                data,
+                ok: result.is_ok(),
+                result: match &result {
+                    Ok(message) | Err(message) => message.clone(),
+                },
+                received_at_ms,
+            };
+            if let Some(app_handle) = app_handle {
+                if let Err(e) = app_handle.emit("backend-control", &echo) {
+                    error!("[错误] 发送后端控制回显事件到前端失败: {}", e);
+                }
+            }
+            result
+        },
+        IncomingMessage::TtsBegin => apply_audio_playback_started(state),
+        IncomingMessage::TtsEnd => apply_audio_playback_ended(app_handle, state),
+    };
+    publish_vad_state(state);
+    result
+}
+
+// 处理一条STT识别结果：非空文本驱动状态机，并转发到前端
+fn apply_stt_result(app_handle: Option<&tauri::AppHandle>, state: &AppState, mut result: SttResult) -> Result<String, String> {
+    // 后端回传的会话序号落后于当前会话时，这是上一会话迟到的结果（如用户打断之后），直接丢弃；
+    // 旧后端不回传序号，结果一律归入当前会话
+    let current_session = match state.sm.lock() {
+        Ok(state_machine) => state_machine.session_id,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    match result.session_id {
+        Some(session_id) if session_id < current_session => {
+            debug!("[调试] 丢弃会话{}迟到的STT结果（当前会话{}）", session_id, current_session);
+            return Ok("过期会话的STT结果，已丢弃".to_string());
+        }
+        Some(_) => {}
+        None => result.session_id = Some(current_session),
+    }
+    
+    if result.is_final {
+        // info!("[重要] 收到STT最终结果: '{}'", result.text);
+    } else {
+        // info!("[重要] 收到STT中间结果: '{}'", result.text);
+    }
+    
+    if result.is_final && !result.text.is_empty() {
+        // 先归入对话轮次，journal 与对话历史按轮次 id 登记，删除转写时据此匹配
+        let completed_turns = match state.turns.lock() {
+            Ok(mut tracker) => {
+                let (turn_id, completed) = tracker.transcript(&result.text, journal::now_unix_ms());
+                result.turn_id = Some(turn_id);
+                completed
+            }
+            Err(e) => {
+                error!("[错误] 获取对话轮次锁失败: {}", e);
+                Vec::new()
+            }
+        };
+        match state.journal.lock() {
+            Ok(mut journal) => journal.record_transcript(result.clone()),
+            Err(e) => error!("[错误] 获取journal锁失败: {}", e),
+        }
+        if let Some(turn_id) = result.turn_id {
+            match state.history.lock() {
+                Ok(mut history) => history.record_user(&result.text, turn_id),
+                Err(e) => error!("[错误] 获取对话历史锁失败: {}", e),
+            }
+        }
+        if let Some(app_handle) = app_handle {
+            emit_completed_turns(app_handle, completed_turns);
+        }
+    }
+    
+    // 当收到非空文本时，向状态机发送BackendReturnText事件；本会话的第一个非空文本同时给出首响应延迟
+    let first_response = if !result.text.is_empty() {
+        // 获取VAD状态机
+        let vad_state_machine = &state.sm;
+        let mut state_machine = match vad_state_machine.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD状态机锁失败: {}", e);
+                return Err(format!("获取VAD状态机失败: {}", e));
+            }
+        };
+        
+        // 获取SocketManager
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        
+        // 发送BackendReturnText事件到状态机
+        //debug!("[状态机] 收到非空STT结果文本，触发BackendReturnText事件: '{}'", result.text);
+        let _should_send_to_python = state_machine.process_event(
+            VadStateMachineEvent::BackendReturnText, 
+            &mut socket_manager_guard
+        );
+        socket_manager_guard
+            .take_first_response_latency(Instant::now())
+            .map(|latency| (state_machine.session_id, latency))
+    } else {
+        None
+    };
+    if let Some((session_id, latency)) = first_response {
+        record_first_response(app_handle, state, session_id, latency);
+    }
+    
+    // 最终结果到达，结束对最终识别请求的等待
+    if result.is_final {
+        let outcome = match state.sm.lock() {
+            Ok(mut state_machine) => state_machine.finalize_received(Instant::now()),
+            Err(e) => {
+                error!("[错误] 获取VAD状态机锁失败: {}", e);
+                None
+            }
+        };
+        if let (Some(outcome), Some(app_handle)) = (outcome, app_handle) {
+            if let Err(e) = app_handle.emit("utterance-finalized", &outcome) {
+                error!("[错误] 发送最终识别结果事件到前端失败: {}", e);
+            }
+        }
+        match state.socket.lock() {
+            Ok(mut socket_manager) => socket_manager.spans.transcript_final(result.trace_id.as_deref()),
+            Err(e) => error!("[错误] 获取SocketManager锁失败: {}", e),
+        }
+    }
+    
+    // 中间结果去重合并，重复或回退的版本不再发给前端；合并后按时间窗口节流
+    let result = match state.partials.lock() {
+        Ok(mut merger) => match merger.ingest(result) {
+            Some(merged) => match merger.throttle(merged, Instant::now()) {
+                PartialEmit::Now(result) => result,
+                PartialEmit::Later(delay) => {
+                    if let Some(app_handle) = app_handle {
+                        schedule_partial_flush(app_handle, state, delay);
+                    }
+                    return Ok("STT中间结果已节流，稍后发送".to_string());
+                },
+                PartialEmit::Held => return Ok("STT中间结果已节流，稍后发送".to_string()),
+            },
+            None => return Ok("STT中间结果重复，已忽略".to_string()),
+        },
+        Err(e) => {
+            error!("[错误] 获取中间结果合并器锁失败: {}", e);
+            result
+        }
+    };
+    
+    // 发送到前端
+    // debug!("[调试] 正在发送STT结果到前端: '{}' (最终: {})", 
+    //         result.text, result.is_final);
+    if let Some(app_handle) = app_handle {
+        if let Err(e) = app_handle.emit("stt-result", &result) {
+            error!("[错误] 发送STT结果到前端失败: {}", e);
+            return Err(format!("发送STT结果到前端失败: {}", e));
+        }
+    }
+    
+    Ok("STT结果处理完成".to_string())
+}
+
+// 节流窗口结束时发出暂存的最新中间结果
+fn schedule_partial_flush(app_handle: &tauri::AppHandle, state: &AppState, delay: Duration) {
+    let app_handle = app_handle.clone();
+    let state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let due = match state.partials.lock() {
+            Ok(mut merger) => merger.take_due(Instant::now()),
+            Err(e) => {
+                error!("[错误] 获取中间结果合并器锁失败: {}", e);
+                None
+            }
+        };
+        if let Some(result) = due {
+            if let Err(e) = app_handle.emit("stt-result", &result) {
+                error!("[错误] 发送STT结果到前端失败: {}", e);
+            }
+        }
+    });
+}
+
+// 把模拟消息解析为IncomingMessage
+// kind: stt_partial / stt_final 的payload为 {"text": "...", "session_id": 可选, "trace_id": 可选}；control 为 {"action": "...", "data": "..."}；
+// tts_begin / tts_end 忽略payload
+fn parse_simulated_message(kind: &str, payload_json: &str) -> Result<IncomingMessage, String> {
+    #[derive(Deserialize)]
+    struct TextPayload {
+        text: String,
+        #[serde(default)]
+        session_id: Option<u64>,
+        #[serde(default)]
+        trace_id: Option<String>,
+    }
+    
+    #[derive(Deserialize)]
+    struct ControlPayload {
+        action: String,
+        #[serde(default)]
+        data: serde_json::Value,
+    }
+    
+    match kind {
+        "stt_partial" | "stt_final" => {
+            let payload: TextPayload = serde_json::from_str(payload_json)
+                .map_err(|e| format!("解析{}负载失败: {}", kind, e))?;
+            Ok(IncomingMessage::SttResult(SttResult {
+                text: payload.text,
+                is_final: kind == "stt_final",
+                session_id: payload.session_id,
+                trace_id: payload.trace_id,
+                turn_id: None,
+            }))
+        },
+        "control" => {
+            let payload: ControlPayload = serde_json::from_str(payload_json)
+                .map_err(|e| format!("解析control负载失败: {}", e))?;
+            Ok(IncomingMessage::Control {
+                action: payload.action,
+                data: payload.data,
+            })
+        },
+        "tts_begin" => Ok(IncomingMessage::TtsBegin),
+        "tts_end" => Ok(IncomingMessage::TtsEnd),
+        _ => Err(format!(
+            "未知的模拟消息类型: {}，可选值: stt_partial, stt_final, control, tts_begin, tts_end",
+            kind
+        )),
+    }
+}
+
+// 模拟后端消息（仅debug构建可用），与真实Socket到达走完全相同的分发路径
+// release构建下命令仍然注册，但始终返回disabled错误，保证invoke handler列表跨构建一致
+#[command]
+async fn simulate_backend_message(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    kind: String,
+    payload_json: String
+) -> Result<String, String> {
+    if !cfg!(debug_assertions) {
+        return Err("disabled: simulate_backend_message 仅在debug构建下可用".into());
+    }
+    
+    debug!("[调试] 注入模拟后端消息: kind={}, payload={}", kind, payload_json);
+    let message = parse_simulated_message(&kind, &payload_json)?;
+    dispatch_incoming_message(Some(&app_handle), &state, message)
+}
+
+// 接收并转发STT结果到前端
+#[command]
+async fn start_stt_result_listener(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    debug!("[调试] 启动STT结果监听器");
+    
+    // shutdown 之后再次启动时一并恢复补发与checkpoint线程
+    ensure_background_tasks(&state);
+    if state.lifecycle.is_running("stt_result_listener") {
+        info!("[信息] STT结果监听器已在运行");
+        return Ok(());
+    }
+    let guard = state.lifecycle.register("stt_result_listener");
+    
+    // 先等待一小段时间让后端Socket启动
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    
+    // 启动后台线程接收STT结果
+    let app_handle_clone = app_handle.clone();
+    let state = state.inner().clone();
+    spawn_reporting(app_handle, "stt_result_listener", async move {
+        wait_for_backend_at_login(&state).await;
+        while !state.lifecycle.is_shutting_down() {
+            // 仅本地模式下不连接后端，退出该模式后再开始连接
+            if is_local_only(&state) {
+                state.lifecycle.sleep_async(Duration::from_secs(1)).await;
+                continue;
+            }
+            
+            // 每次重连都读取当前端点，set_backend_endpoints 修改后下一次重连生效
+            let (backend_endpoints, legacy_fallback) = socket_endpoints(&state);
+            
+            // 尝试连接结果Socket（平台特定实现）
+            let connection_result = endpoints::connect(&backend_endpoints, EndpointKind::SttResult, legacy_fallback);
+            
+            match connection_result {
+                Ok((mut stream, endpoint)) => {
+                    info!("[重要] STT结果监听器已成功连接到: {}", endpoint);
+                    guard.watch_stream(StreamCloser::new(&stream));
+                    // 连接期间刚开启仅本地模式时，这条连接没来得及登记、不会被关闭，在这里放弃
+                    if is_local_only(&state) {
+                        guard.unwatch_stream();
+                        continue;
+                    }
+                    
+                    // 读取结果并转发 - 支持换行符分隔的JSON消息
+                    let mut buffer = Vec::new();
+                    let mut temp_buffer = [0; 1024];
+                    
+                    loop {
+                        match stream.read(&mut temp_buffer) {
+                            Ok(size) if size > 0 => {
+                                // debug!("[调试] 从STT结果Socket接收到{}字节数据", size);
+                                buffer.extend_from_slice(&temp_buffer[0..size]);
+                                
+                                // 处理缓冲区中的完整消息（以换行符分隔）
+                                while let Some(newline_pos) = buffer.iter().position(|&b| b == b'\n') {
+                                    // 复制消息字节以避免借用冲突
+                                    let message_bytes = buffer[0..newline_pos].to_vec();
+                                    buffer.drain(0..=newline_pos); // 移除已处理的消息和换行符
+                                    
+                                    debug!("[调试] 检测到完整JSON消息，长度: {}字节", message_bytes.len());
+                                    let message_str = String::from_utf8_lossy(&message_bytes);
+                                    debug!("[调试] 原始JSON消息: {}", message_str);
+                                    
+                                    // 尝试解析JSON消息
+                                    let parsed = serde_json::from_slice::<SttResult>(&message_bytes);
+                                    // 线上字节数含换行符
+                                    trace_incoming(&state, TraceChannel::SttResult, "stt_result", message_bytes.len() + 1, parsed.is_ok());
+                                    match parsed {
+                                        Ok(result) => {
+                                            if let Err(e) = dispatch_incoming_message(Some(&app_handle_clone), &state, IncomingMessage::SttResult(result)) {
+                                                error!("[错误] 处理STT结果失败: {}", e);
+                                            }
+                                        },
+                                        Err(e) => {
+                                            error!("[错误] 解析STT结果失败: {}", e);
+                                            debug!("[调试] 原始消息: {:?}", String::from_utf8_lossy(&message_bytes));
+                                            backend_error::emit(&app_handle_clone, &BackendError::new(
+                                                backend_error::STT_RESULT_PARSE_FAILED,
+                                                format!("解析STT结果失败: {}", e),
+                                                true,
+                                            ));
+                                        }
+                                    }
+                                }
+                            },
+                            Ok(_) => {
+                                info!("[信息] STT结果连接关闭");
+                                if listener_stream_closed(&state) {
+                                    break;
+                                }
+                                backend_error::emit(&app_handle_clone, &BackendError::new(
+                                    backend_error::STT_RESULT_DISCONNECTED,
+                                    "STT结果连接已被后端关闭",
+                                    true,
+                                ));
+                                break;
+                            },
+                            Err(e) => {
+                                if listener_stream_closed(&state) {
+                                    break;
+                                }
+                                error!("[错误] 读取STT结果失败: {}", e);
+                                backend_error::emit(&app_handle_clone, &BackendError::new(
+                                    backend_error::STT_RESULT_DISCONNECTED,
+                                    format!("读取STT结果失败: {}", e),
+                                    true,
+                                ));
+                                break;
+                            }
+                        }
+                    }
+                    guard.unwatch_stream();
+                },
+                Err(_) => {
+                    // error!("[错误] 连接STT结果服务器失败: {}", e);
+                    state.lifecycle.sleep_async(Duration::from_secs(1)).await;
+                }
+            }
+        }
+        info!("[信息] STT结果监听器已停止");
+        drop(guard);
+    });
+    
+    Ok(())
+}
+
+#[command]
+async fn start_tts_audio_listener(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    debug!("[调试] 启动TTS音频监听器");
+
+    ensure_background_tasks(&state);
+    if state.lifecycle.is_running("tts_audio_listener") {
+        info!("[信息] TTS音频监听器已在运行");
+        return Ok(());
+    }
+    let guard = state.lifecycle.register("tts_audio_listener");
+    spawn_tts_stall_watch(&app_handle, &state);
+
+    let state = state.inner().clone();
+    spawn_reporting(app_handle.clone(), "tts_audio_listener", async move {
+        wait_for_backend_at_login(&state).await;
+        while !state.lifecycle.is_shutting_down() {
+            // 仅本地模式下不连接后端，退出该模式后再开始连接
+            if is_local_only(&state) {
+                state.lifecycle.sleep_async(Duration::from_secs(1)).await;
+                continue;
+            }
+            
+            // 每次重连都读取当前端点
+            let (backend_endpoints, legacy_fallback) = socket_endpoints(&state);
+            let connection_result = endpoints::connect(&backend_endpoints, EndpointKind::Tts, legacy_fallback);
+
+            match connection_result {
+                Ok((mut stream, endpoint)) => {
+                    info!("[重要] TTS音频监听器已成功连接到: {}", endpoint);
+                    guard.watch_stream(StreamCloser::new(&stream));
+                    // 连接期间刚开启仅本地模式时，这条连接没来得及登记、不会被关闭，在这里放弃
+                    if is_local_only(&state) {
+                        guard.unwatch_stream();
+                        continue;
+                    }
+                    // 新连接上不会再有被取消回复的残余音频
+                    clear_tts_cancellation(&state);
+
+                    // 通知前端状态机准备好接收TTS音频
+                    // if let Err(e) = app_handle.emit("vad-state-changed", "Listening") {
+                    //     error!("[错误] 发送VAD状态变更事件失败: {}", e);
+                    // }
+
+                    let mut len_buffer = [0; 4];
+                    let mut audio_chunks_count = 0;
+
+                    loop {
+                        // 排队音频过多或接收过快时暂停读取，让背压传回后端
+                        wait_for_tts_flow(&app_handle, &state).await;
+
+                        // Read length prefix
+                        match stream.read_exact(&mut len_buffer) {
+                            Ok(_) => {
+                                let len = u32::from_le_bytes(len_buffer) as usize;
+                                let settings = tts_flow_settings(&state);
+                                if len > settings.max_chunk_bytes() {
+                                    // 不整块分配内存，边读边切段入队
+                                    audio_chunks_count += 1;
+                                    if let Err(e) = receive_oversized_tts_chunk(&app_handle, &state, &mut stream, len, settings.piece_ms()).await {
+                                        if listener_stream_closed(&state) {
+                                            break;
+                                        }
+                                        trace_incoming(&state, TraceChannel::Tts, "tts_audio", len + 4, false);
+                                        error!("[错误] {}", e);
+                                        backend_error::emit(&app_handle, &BackendError::new(backend_error::TTS_READ_FAILED, e, true));
+                                        break;
+                                    }
+                                    trace_incoming(&state, TraceChannel::Tts, "tts_audio", len + 4, true);
+                                    continue;
+                                }
+                                if len > 0 {
+                                    let mut audio_chunk = vec![0; len];
+                                    // Read audio data
+                                    if stream.read_exact(&mut audio_chunk).is_ok() {
+                                        trace_incoming(&state, TraceChannel::Tts, "tts_audio", len + 4, true);
+
+                                        // 计数并定期报告收到的音频块数量
+                                        audio_chunks_count += 1;
+                                        if audio_chunks_count % 10 == 0 {
+                                            debug!("[TTS音频] 已收到并处理 {} 个音频块", audio_chunks_count);
+                                        }
+                                        
+                                        // 单条超过缓冲上限时切分为多段，逐段等待整形放行后入队
+                                        let duration_ms = tts_queue::estimate_duration_ms(&audio_chunk);
+                                        if duration_ms > settings.max_buffered_ms {
+                                            let pieces = tts_queue::split_chunk(&audio_chunk, settings.piece_ms());
+                                            report_split_tts_chunk(&app_handle, &state, duration_ms, pieces.len());
+                                            for (index, piece) in pieces.into_iter().enumerate() {
+                                                if index > 0 {
+                                                    wait_for_tts_flow(&app_handle, &state).await;
+                                                }
+                                                accept_tts_chunk(&app_handle, &state, piece);
+                                            }
+                                            continue;
+                                        }
+                                        
+                                        if let Some(utterance_id) = accept_tts_chunk(&app_handle, &state, audio_chunk) {
+                                            if audio_chunks_count == 1 {
+                                                info!("[重要] 收到首个TTS音频块，已加入播放队列 (utterance #{})", utterance_id);
+                                            }
+                                        }
+                                    } else if listener_stream_closed(&state) {
+                                        break;
+                                    } else {
+                                        trace_incoming(&state, TraceChannel::Tts, "tts_audio", len + 4, false);
+                                        error!("[错误] 读取TTS音频块失败");
+                                        backend_error::emit(&app_handle, &BackendError::new(
+                                            backend_error::TTS_READ_FAILED,
+                                            format!("读取TTS音频块({}字节)失败", len),
+                                            true,
+                                        ));
+                                        break;
+                                    }
+                                }
+                            },
+                            Err(_) if listener_stream_closed(&state) => break,
+                            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                                // 对端关闭连接，通知前端后重连
+                                info!("[信息] TTS音频连接关闭");
+                                backend_error::emit(&app_handle, &BackendError::new(
+                                    backend_error::TTS_DISCONNECTED,
+                                    "TTS音频连接已被后端关闭",
+                                    true,
+                                ));
+                                break;
+                            }
+                            Err(e) => {
+                                error!("[TTS] 读取长度出错: {e}");
+                                backend_error::emit(&app_handle, &BackendError::new(
+                                    backend_error::TTS_READ_FAILED,
+                                    format!("读取TTS音频长度失败: {}", e),
+                                    true,
+                                ));
+                                break;
+                            }
+                        }
+                    }
+                    guard.unwatch_stream();
+                },
+                Err(_e) => {
+                    // This can be noisy if backend is not ready, so commented out for now.
+                    // error!("[错误] 连接TTS音频服务器失败: {}", e);
+                    state.lifecycle.sleep_async(Duration::from_secs(1)).await;
+                }
+            }
+        }
+        info!("[信息] TTS音频监听器已停止");
+        drop(guard);
+    });
+
+    Ok(())
+}
+
+// 停止所有后台任务并断开后端连接：置位关闭标志、关闭监听器持有的连接、停止静音计时器，
+// 等待各任务确认退出后写入干净标记。之后再调用 start_* 或发送音频会重新启动任务与连接
+#[command]
+async fn shutdown(state: State<'_, AppState>) -> Result<ShutdownReport, String> {
+    info!("[信息] 开始关闭后台任务");
+    let started = Instant::now();
+    let initial_tasks = state.lifecycle.running_tasks();
+    let closed_streams = state.lifecycle.begin_shutdown();
+    
+    {
+        let mut state_machine = match state.sm.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD状态机锁失败: {}", e);
+                state.lifecycle.finish_shutdown();
+                return Err(format!("获取VAD状态机失败: {}", e));
+            }
+        };
+        state_machine.stop_silence_reporting();
+    }
+    
+    let deadline = Duration::from_millis(lifecycle::SHUTDOWN_TIMEOUT_MS);
+    while !state.lifecycle.running_tasks().is_empty() && started.elapsed() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let timed_out_tasks = state.lifecycle.running_tasks();
+    
+    match state.socket.lock() {
+        Ok(mut socket_manager) => {
+            socket_manager.disconnect();
+            if let Some(Err(e)) = socket_manager.finish_wav_stream() {
+                error!("[错误] {}", e);
+            }
+        },
+        Err(e) => error!("[错误] 获取SocketManager锁失败: {}", e),
+    }
+    shutdown_journal(&state);
+    state.lifecycle.finish_shutdown();
+    
+    let mut stopped_tasks = initial_tasks;
+    for task in &timed_out_tasks {
+        if let Some(pos) = stopped_tasks.iter().position(|t| t == task) {
+            stopped_tasks.remove(pos);
+        }
+    }
+    let report = ShutdownReport {
+        stopped_tasks,
+        timed_out_tasks,
+        closed_streams,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+    if report.timed_out_tasks.is_empty() {
+        info!("[信息] 后台任务已全部停止，耗时{}ms", report.elapsed_ms);
+    } else {
+        warn!("[警告] 以下后台任务在{}ms内未退出: {:?}", lifecycle::SHUTDOWN_TIMEOUT_MS, report.timed_out_tasks);
+    }
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AudioSegment {
+    samples: Vec<i16>,
+    sample_rate: u32,
+}
+
+// 把发送到Python的语音段逐个导出为16位单声道WAV，返回写入的文件路径
+// 文件名包含序号和导出时间戳，多次导出不会互相覆盖
+#[command]
+async fn export_speech_segments_wav(state: State<'_, AppState>, dir: String) -> Result<Vec<String>, String> {
+    let (segments, sample_rate) = {
+        let socket_manager = &state.socket;
+        let socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        (socket_manager_guard.get_sent_to_python_segments(), socket_manager_guard.sample_rate)
+    };
+    
+    if segments.is_empty() {
+        debug!("[调试] 没有可导出的语音段");
+        return Ok(Vec::new());
+    }
+    
+    ensure_persistence_allowed(&state, "导出语音段")?;
+    let dir = std::path::PathBuf::from(dir);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建导出目录 {} 失败: {}", dir.display(), e))?;
+    
+    let timestamp = journal::now_unix_ms();
+    let mut paths = Vec::with_capacity(segments.len());
+    for (index, samples) in segments.iter().enumerate() {
+        let path = dir.join(format!("segment_{:03}_{}.wav", index + 1, timestamp));
+        let wav = audio_utils::encode_wav_pcm16(samples, sample_rate);
+        std::fs::write(&path, wav).map_err(|e| format!("写入WAV文件 {} 失败: {}", path.display(), e))?;
+        paths.push(path.to_string_lossy().into_owned());
+    }
+    
+    info!("[信息] 已导出{}个语音段到: {}", paths.len(), dir.display());
+    Ok(paths)
+}
+
+// 开始把发往后端的音频（经过处理链之后）边录边写入 path 指向的单个WAV文件，文件已存在时覆盖。
+// 长度字段每秒回填一次，崩溃后文件仍可播放；不留痕模式下拒绝开始，录制中开启该模式则挂起写入
+#[command]
+async fn start_streaming_wav(state: State<'_, AppState>, path: String) -> Result<StreamingWavStatus, String> {
+    ensure_persistence_allowed(&state, "录制会话音频")?;
+    
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    if let Some(recorder) = socket_manager_guard.wav_stream.as_ref() {
+        return Err(format!("已在录制到 {}，请先调用 stop_streaming_wav", recorder.status().path));
+    }
+    
+    let recorder = StreamingWav::create(std::path::Path::new(&path), socket_manager_guard.sample_rate)?;
+    let status = recorder.status();
+    socket_manager_guard.wav_stream = Some(recorder);
+    info!("[信息] 开始流式录制WAV: {} ({}Hz)", status.path, status.sample_rate);
+    Ok(status)
+}
+
+// 结束流式WAV录制，回填最终长度并刷盘
+#[command]
+async fn stop_streaming_wav(state: State<'_, AppState>) -> Result<StreamingWavStatus, String> {
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    let status = socket_manager_guard
+        .finish_wav_stream()
+        .unwrap_or_else(|| Err("当前没有进行中的流式WAV录制".to_string()))?;
+    info!("[信息] 流式WAV录制已结束: {}，共{}ms", status.path, status.duration_ms);
+    Ok(status)
+}
+
+#[command]
+async fn get_speech_segments(state: State<'_, AppState>) -> Result<Vec<AudioSegment>, String> {
+    debug!("[调试] 获取发送到Python的语音段用于回放");
+    
+    let socket_manager = &state.socket;
+    let socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 获取所有发送到Python的语音段
+    let segments = socket_manager_guard.get_sent_to_python_segments();
+    let sample_rate = socket_manager_guard.sample_rate;
+    
+    info!("[重要] 获取到{}个发送到Python的语音段", segments.len());
+    
+    if segments.is_empty() {
+        debug!("[调试] 没有可用的语音段");
+        return Ok(Vec::new());
+    }
+    
+    // 转换为带有采样率的音频段
+    let audio_segments: Vec<AudioSegment> = segments
+        .into_iter()
+        .map(|samples| {
+            // info!("[重要] 语音段: 长度={}个样本", samples.len());
+            AudioSegment {
+                samples,
+                sample_rate,
+            }
+        })
+        .collect();
+    
+    debug!("[调试] 返回{}个音频段用于回放", audio_segments.len());
+    Ok(audio_segments)
+}
+
+#[command]
+async fn clear_speech_segments(state: State<'_, AppState>) -> Result<(), String> {
+    debug!("[调试] 清空存储的语音段");
+    
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    socket_manager_guard.clear_sent_to_python_segments();
+    debug!("[调试] 发送到Python的语音段已清空");
+    
+    Ok(())
+}
+
+#[command]
+async fn create_test_speech_segment(state: State<'_, AppState>) -> Result<(), String> {
+    info!("[重要] 手动创建测试语音段");
+    
+    // 获取SocketManager实例
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 创建一个小的测试音频段 - 1秒的正弦波
+    let mut test_samples = Vec::with_capacity(16000);
+    for i in 0..16000 {
+        let t = i as f32 / 16000.0;
+        let sample = (t * 440.0 * 2.0 * std::f32::consts::PI).sin() * 10000.0;
+        test_samples.push(sample as i16);
+    }
+    
+    // 保存测试音频段到发送到Python的语音段
+    socket_manager_guard.sent_to_python_segments.push_back(SentSegment {
+        samples: test_samples,
+        voice_segment_id: 0,
+    });
+    info!("[重要] 测试语音段已创建，当前共有{}个发送到Python的语音段", 
+             socket_manager_guard.sent_to_python_segments.len());
+    
+    Ok(())
+}
+
+// 重置VAD检测状态，保留已调整的灵敏度、阈值等设置
+#[command]
+fn reset_vad_state(state: State<'_, AppState>) -> Result<String, String> {
+    info!("[信息] 重置VAD状态");
+    
+    // 只清除本次会话的检测状态，底噪重新估计
+    let vad_processor = &state.vad;
+    let result = match vad_processor.lock() {
+        Ok(mut processor) => {
+            processor.reset_session();
+            info!("[信息] VAD状态已重置");
+            Ok("VAD状态已重置".to_string())
+        },
+        Err(e) => {
+            let error_msg = format!("获取VAD处理器锁失败: {}", e);
+            error!("[错误] {}", error_msg);
+            Err(error_msg)
+        }
+    };
+    
+    // 同时重置状态机
+    let vad_state_machine = &state.sm;
+    if let Ok(mut state_machine) = vad_state_machine.lock() {
+        state_machine.reset_to_initial();
+        info!("[信息] VAD状态机已重置到初始状态");
+    }
+    
+    if let Ok(mut merger) = state.partials.lock() {
+        merger.reset();
+    }
+    
+    publish_vad_state(&state);
+    result
+}
+
+// 把VAD处理器恢复为默认设置：重建处理器实例，已调整的灵敏度、阈值、判定后端等全部丢弃，累计统计清零。
+// 参数分层中的设置不受影响，下次参数变更或重新应用配置时重新下发
+#[command]
+fn reset_vad_to_defaults(state: State<'_, AppState>) -> Result<String, String> {
+    info!("[信息] 恢复VAD默认设置");
+    
+    match state.vad.lock() {
+        Ok(mut processor) => *processor = VadProcessor::new(),
+        Err(e) => {
+            let error_msg = format!("获取VAD处理器锁失败: {}", e);
+            error!("[错误] {}", error_msg);
+            return Err(error_msg);
+        }
+    }
+    
+    if let Ok(mut state_machine) = state.sm.lock() {
+        state_machine.reset_to_initial();
+        info!("[信息] VAD状态机已重置到初始状态");
+    }
+    
+    if let Ok(mut merger) = state.partials.lock() {
+        merger.reset();
+    }
+    
+    publish_vad_state(&state);
+    info!("[信息] VAD已恢复默认设置");
+    Ok("VAD已恢复默认设置".to_string())
+}
+
+// 停止VAD处理
+#[command]
+fn stop_vad_processing(state: State<'_, AppState>) -> Result<String, String> {
+    info!("[信息] 停止VAD处理");
+    
+    // 状态机此时仍处于停止前的状态，决定残余音频是否还属于进行中的发言
+    let sending = match state.sm.lock() {
+        Ok(state_machine) => matches!(state_machine.get_current_state(), VadState::Speaking | VadState::TransitionBuffer),
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            false
+        }
+    };
+    
+    // 获取VAD处理器
+    let vad_processor = &state.vad;
+    let result = match vad_processor.lock() {
+        Ok(mut processor) => {
+            // 结束进行中的语音，不足一帧的残余样本与前瞻延迟线中的帧不再参与检测；已调整的设置保留
+            if processor.session.is_speaking {
+                info!("[信息] 手动触发语音结束事件");
+            }
+            let pending = processor.take_pending_audio();
+            processor.reset_session();
+            
+            // 获取SocketManager
+            let socket_manager = &state.socket;
+            let mut socket_manager_guard = match socket_manager.lock() {
+                Ok(guard) => guard,
+                Err(e) => {
+                    let error_msg = format!("获取Socket管理器锁失败: {}", e);
+                    error!("[错误] {}", error_msg);
+                    return Err(error_msg);
+                }
+            };
+            
+            // 发言进行中时，尚未参与检测的音频属于这段发言的结尾，先发给后端再收尾
+            if sending && !pending.is_empty() {
+                debug!("[调试] 停止前发送未满一帧的残余音频({}个样本)", pending.len());
+                socket_manager_guard.send_speech_segment(&pending);
+            }
+            
+            // 收尾当前语音段与待重发队列，但不要清除已保存的发送到Python的语音段
+            socket_manager_guard.flush();
+            
+            // 保存发送到Python的语音段数量
+            let sent_segments_count = socket_manager_guard.sent_to_python_segments.len();
+            info!("[信息] 当前已保存{}个发送到Python的语音段", sent_segments_count);
+            
+            info!("[信息] VAD处理已停止");
+            Ok(format!("VAD处理已停止，有{}个语音段可供播放", sent_segments_count))
+        },
+        Err(e) => {
+            let error_msg = format!("获取VAD处理器锁失败: {}", e);
+            error!("[错误] {}", error_msg);
+            Err(error_msg)
+        }
+    };
+    
+    // 同时重置状态机
+    let vad_state_machine = &state.sm;
+    if let Ok(mut state_machine) = vad_state_machine.lock() {
+        state_machine.reset_to_initial();
+        info!("[信息] VAD状态机已重置到初始状态");
+    }
+    
+    publish_vad_state(&state);
+    result
+}
+
+// 添加新命令获取合并后的语音段
+#[command]
+async fn get_combined_speech_segment(state: State<'_, AppState>) -> Result<AudioSegment, String> {
+    debug!("[调试] 获取合并后的语音识别段");
+    
+    let socket_manager = &state.socket;
+    let socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 获取合并后的语音段
+    let combined = socket_manager_guard.get_combined_speech_segment();
+    
+    if combined.is_empty() {
+        debug!("[调试] 没有可用的语音识别段可合并");
+        return Err("没有可用的语音识别段可合并".into());
+    }
+    
+    info!("[重要] 合并后的语音识别段长度: {}个样本", combined.len());
+    
+    // 创建AudioSegment
+    let audio_segment = AudioSegment {
+        samples: combined,
+        sample_rate: socket_manager_guard.sample_rate,
+    };
+    
+    Ok(audio_segment)
+}
+
+// 回放段列表的波形缩略图：source 为 "segment"（缺省，按 index 取单段）或 "combined"（合并段，忽略 index）。
+// 返回长度为 width_px×2 的 [min, max, ...]，取值 -127..127；空段与超短段返回全零
+#[command]
+async fn get_segment_waveform_thumbnail(
+    state: State<'_, AppState>,
+    index: Option<usize>,
+    width_px: usize,
+    source: Option<String>,
+) -> Result<Vec<i8>, String> {
+    if width_px == 0 || width_px > waveform::MAX_THUMBNAIL_WIDTH {
+        return Err(format!("width_px必须在1~{}之间: {}", waveform::MAX_THUMBNAIL_WIDTH, width_px));
+    }
+    let index = match source.as_deref().unwrap_or("segment") {
+        "segment" => Some(index.ok_or("获取单段缩略图时必须指定index")?),
+        "combined" => None,
+        other => return Err(format!("未知的缩略图来源: {}，可选值: segment, combined", other)),
+    };
+    
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    socket_manager_guard.waveform_thumbnail(index, width_px)
+}
+
+// 把本次会话用户说的所有语音段按真实时间轴（含停顿静音）拼成一条音频回放；
+// 段间静音最长 MAX_TIMELINE_GAP_MS，没有采集时刻的段紧接上一段
+#[command]
+async fn get_session_timeline_audio(state: State<'_, AppState>) -> Result<AudioSegment, String> {
+    let socket_manager = &state.socket;
+    let socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    let samples = socket_manager_guard.session_timeline();
+    debug!("[调试] 会话时间轴音频: {}个语音段，共{}个样本",
+        socket_manager_guard.complete_speech_segments.len(), samples.len());
+    
+    Ok(AudioSegment {
+        samples,
+        sample_rate: socket_manager_guard.sample_rate,
+    })
+}
+
+// 新增：前端重置事件处理命令
+#[command]
+async fn reset_vad_session(state: State<'_, AppState>) -> Result<String, String> {
+    //debug!("[状态机] 收到前端重置事件，执行后端结束session");
+    
+    // 获取VAD状态机
+    let vad_state_machine = &state.sm;
+    let mut state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    
+    // 获取SocketManager
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 发送后端结束session事件到状态机
+    let _should_send_to_python = state_machine.process_event(
+        VadStateMachineEvent::BackendEndSession, 
+        &mut socket_manager_guard
+    );
+    
+    // 未确认的中间结果随会话一起丢弃
+    if let Ok(mut merger) = state.partials.lock() {
+        merger.reset();
+    }
+    
+    drop(socket_manager_guard);
+    drop(state_machine);
+    if is_ephemeral(&state) {
+        purge_session_content(&state);
+    }
+    publish_vad_state(&state);
+    
+    //debug!("[状态机] 前端重置事件处理完成，状态机已重置到初始状态");
+    Ok("VAD session已重置".to_string())
+}
+
+// 新增：处理后端控制消息的命令，data 可以是 JSON 值，也可以是旧版的字符串（先尝试按 JSON 解析）
+#[command]
+async fn handle_backend_control(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    action: String,
+    data: serde_json::Value
+) -> Result<String, String> {
+    dispatch_incoming_message(Some(&app_handle), &state, IncomingMessage::Control { action, data })
+}
+
+// 支持的后端控制动作及其负载格式
+#[command]
+async fn get_control_actions() -> Result<Vec<ControlActionInfo>, String> {
+    Ok(control_actions::ACTIONS.to_vec())
+}
+
+// 应用一条后端控制消息
+fn apply_backend_control(app_handle: Option<&tauri::AppHandle>, state: &AppState, action: &str, data: serde_json::Value) -> Result<String, String> {
+    //debug!("[状态机] 收到后端控制消息: action={}, data={}", action, data);
+    
+    let parsed = match control_actions::parse(action, data) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!("[警告] 后端控制消息无效: {}", e);
+            return Err(e);
+        }
+    };
+    
+    // 参数hint类控制不涉及状态机事件，需在获取状态机锁之前处理
+    let (event, interrupt) = match parsed {
+        ControlAction::SetVadHint(hint) => return apply_vad_hint(app_handle, state, hint),
+        ControlAction::TtsCaption(caption) => return apply_tts_caption(state, caption),
+        ControlAction::WakeWordDetected => {
+            features::require(Feature::WakeWord)?;
+            if let Ok(mut gate) = state.focus.lock() {
+                gate.unlock_wake(Instant::now());
+            }
+            info!("[信息] 收到唤醒信号，门控已打开");
+            return Ok("唤醒门控已打开".to_string());
+        },
+        ControlAction::SetInteractionMode(name) => {
+            let mode = parse_interaction_mode(name.trim())?;
+            if let Ok(mut layers) = state.params.lock() {
+                layers.mode = mode;
+            }
+            refresh_effective_parameters(app_handle, state)?;
+            info!("[信息] 后端切换交互模式: {}", name.trim());
+            return Ok(format!("交互模式已切换为: {}", name.trim()));
+        },
+        ControlAction::ClearVadHints => {
+            let mut pacing_changed = false;
+            if let Ok(mut layers) = state.params.lock() {
+                pacing_changed = layers.hints.contains_key(&ParamKey::SpeechEndSilenceMs);
+                layers.hints.clear();
+                layers.hint_expiry.clear();
+            }
+            refresh_effective_parameters(app_handle, state)?;
+            if let (true, Some(app_handle)) = (pacing_changed, app_handle) {
+                emit_dialogue_pacing(app_handle, state, "hint_cleared");
+            }
+            return Ok("VAD hint已清除".to_string());
+        },
+        ControlAction::ResetToInitial => {
+            //debug!("[状态机] 执行后端请求的重置到初始状态");
+            (VadStateMachineEvent::BackendResetToInitial, false)
+        },
+        ControlAction::EndSession => {
+            //debug!("[状态机] 执行后端请求的结束session");
+            (VadStateMachineEvent::BackendEndSession, false)
+        },
+        ControlAction::Interrupt => {
+            debug!("[状态机] 执行用户打断操作");
+            // 按打断策略处理TTS播放队列，之后重置到初始状态
+            interrupt_tts_playback(app_handle, state);
+            (VadStateMachineEvent::BackendResetToInitial, true)
+        },
+    };
+    
+    // 获取VAD状态机
+    let vad_state_machine = &state.sm;
+    let mut state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    
+    // 获取SocketManager
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 打断时如果在播放音频状态，先发送AudioPlaybackEnd事件
+    if interrupt && *state_machine.get_current_state() == VadState::Listening {
+        state_machine.process_event(VadStateMachineEvent::AudioPlaybackEnd, &mut socket_manager_guard);
+    }
+    
+    // 发送事件到状态机
+    let ends_session = matches!(event, VadStateMachineEvent::BackendEndSession);
+    let _should_send_to_python = state_machine.process_event(event, &mut socket_manager_guard);
+    drop(socket_manager_guard);
+    drop(state_machine);
+    if ends_session && is_ephemeral(state) {
+        purge_session_content(state);
+    }
+    
+    //debug!("[状态机] 后端控制消息处理完成");
+    Ok(format!("后端控制消息 '{}' 处理完成", action))
+}
+
+// 新增：音频播放开始事件处理
+#[command]
+async fn audio_playback_started(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    dispatch_incoming_message(Some(&app_handle), &state, IncomingMessage::TtsBegin)
+}
+
+// 应用音频播放开始事件
+fn apply_audio_playback_started(state: &AppState) -> Result<String, String> {
+    //debug!("[状态机] 收到音频播放开始事件");
+    
+    // 记录当前utterance的开始播放时刻，打断时据此计算已播部分
+    let playing_id = match state.tts_queue.lock() {
+        Ok(mut queue) => {
+            queue.mark_playback_started(Instant::now());
+            queue.playing_id()
+        },
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            None
+        }
+    };
+    
+    // 回复归属开始播放时的轮次
+    if let Some(utterance_id) = playing_id {
+        if let Ok(mut tracker) = state.turns.lock() {
+            tracker.reply_started(utterance_id);
+        }
+    }
+    
+    // 获取VAD状态机
+    let vad_state_machine = &state.sm;
+    let mut state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    
+    // 获取SocketManager
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 发送音频播放开始事件到状态机
+    let _should_send_to_python = state_machine.process_event(
+        VadStateMachineEvent::AudioPlaybackStart, 
+        &mut socket_manager_guard
+    );
+    socket_manager_guard.spans.playback_started();
+    
+    //debug!("[状态机] 音频播放开始事件处理完成");
+    Ok("音频播放开始".to_string())
+}
+
+// 新增：音频播放结束事件处理
+#[command]
+async fn audio_playback_ended(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    dispatch_incoming_message(Some(&app_handle), &state, IncomingMessage::TtsEnd)
+}
+
+// 应用音频播放结束事件
+fn apply_audio_playback_ended(app_handle: Option<&tauri::AppHandle>, state: &AppState) -> Result<String, String> {
+    //debug!("[状态机] 收到音频播放结束事件");
+    
+    // 当前utterance播完，结束后再放行队列中的下一条
+    let finished_reply = match state.tts_queue.lock() {
+        Ok(mut queue) => {
+            let finished = queue.finish_current();
+            if let Some(info) = &finished {
+                debug!("[TTS音频] utterance #{} 播放结束", info.utterance_id);
+                if let Some(caption) = info.caption.clone() {
+                    if let Ok(mut history) = state.history.lock() {
+                        history.push(HistoryEntry {
+                            speaker: Speaker::Assistant,
+                            text: caption,
+                            utterance_id: Some(info.utterance_id),
+                            turn_id: None,
+                            interrupted: false,
+                            remaining_text: None,
+                            remaining_ms: None,
+                            timestamp_ms: journal::now_unix_ms(),
+                        });
+                    }
+                }
+            }
+            finished.map(|info| (info, queue.has_pending()))
+        },
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            None
+        }
+    };
+    
+    // 本会话的回复全部播完时结束追踪
+    let replies_pending = finished_reply.as_ref().map(|(_, more_pending)| *more_pending);
+    if let Some((info, more_pending)) = finished_reply {
+        let reply = AssistantReply {
+            text: info.caption.unwrap_or_default(),
+            utterance_id: Some(info.utterance_id),
+            interrupted: false,
+        };
+        let completed_turns = match state.turns.lock() {
+            Ok(mut tracker) => tracker.reply(reply, more_pending, journal::now_unix_ms()),
+            Err(e) => {
+                error!("[错误] 获取对话轮次锁失败: {}", e);
+                Vec::new()
+            }
+        };
+        if let Some(app_handle) = app_handle {
+            emit_completed_turns(app_handle, completed_turns);
+        }
+    }
+    
+    // 获取VAD状态机
+    let vad_state_machine = &state.sm;
+    let mut state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    
+    // 获取SocketManager
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 发送音频播放结束事件到状态机
+    let _should_send_to_python = state_machine.process_event(
+        VadStateMachineEvent::AudioPlaybackEnd, 
+        &mut socket_manager_guard
+    );
+    if let Some(more_pending) = replies_pending {
+        socket_manager_guard.spans.playback_finished(more_pending);
+    }
+    
+    // 释放锁后再放行下一条，避免与状态机锁交叉
+    drop(state_machine);
+    drop(socket_manager_guard);
+    release_next_tts_utterance(app_handle, state);
+    
+    //debug!("[状态机] 音频播放结束事件处理完成");
+    Ok("音频播放结束".to_string())
+}
+
+// 按当前打断策略打断TTS播放队列，并广播被打断的utterance
+fn interrupt_tts_playback(app_handle: Option<&tauri::AppHandle>, state: &AppState) {
+    let (outcome, more_pending) = match state.tts_queue.lock() {
+        Ok(mut queue) => {
+            let outcome = queue.interrupt(Instant::now());
+            (outcome, queue.has_pending())
+        },
+        Err(e) => {
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return;
+        }
+    };
+    apply_interrupt_outcome(app_handle, state, outcome, more_pending);
+}
+
+// 打断后的公共处理：记录被打断的回复、广播被丢弃的utterance，并放行队列中剩余的下一条
+fn apply_interrupt_outcome(app_handle: Option<&tauri::AppHandle>, state: &AppState, outcome: InterruptOutcome, more_pending: bool) {
+    let dropped = outcome.dropped;
+
+    // 被打断的当前条：报告未播完的部分，并以"未播完"写入对话历史
+    if let Some(remainder) = outcome.remainder {
+        debug!("[TTS音频] utterance #{} 被打断，剩余 {}ms", remainder.utterance_id, remainder.remaining_ms);
+        if let Ok(mut history) = state.history.lock() {
+            history.push(HistoryEntry {
+                speaker: Speaker::Assistant,
+                text: remainder.spoken_text.clone().unwrap_or_default(),
+                utterance_id: Some(remainder.utterance_id),
+                turn_id: None,
+                interrupted: true,
+                remaining_text: remainder.remaining_text.clone(),
+                remaining_ms: Some(remainder.remaining_ms),
+                timestamp_ms: journal::now_unix_ms(),
+            });
+        }
+        let reply = AssistantReply {
+            text: remainder.spoken_text.clone().unwrap_or_default(),
+            utterance_id: Some(remainder.utterance_id),
+            interrupted: true,
+        };
+        let completed_turns = match state.turns.lock() {
+            Ok(mut tracker) => tracker.reply(reply, more_pending, journal::now_unix_ms()),
+            Err(e) => {
+                error!("[错误] 获取对话轮次锁失败: {}", e);
+                Vec::new()
+            }
+        };
+        if let Some(app_handle) = app_handle {
+            emit_completed_turns(app_handle, completed_turns);
+            if let Err(e) = app_handle.emit("interrupted-reply-remainder", &remainder) {
+                error!("[错误] 发送被打断回复剩余内容到前端失败: {}", e);
+            }
+        }
+    }
+
+    if !dropped.is_empty() {
+        debug!("[TTS音频] 打断并丢弃utterance: {:?}", dropped);
+        if let Some(app_handle) = app_handle {
+            if let Err(e) = app_handle.emit("tts-utterances-interrupted", &dropped) {
+                error!("[错误] 发送TTS打断事件到前端失败: {}", e);
+            }
+        }
+    }
+
+    // 策略为只打断当前条时，队列中的下一条继续放行
+    release_next_tts_utterance(app_handle, state);
+}
+
+// 取消TTS播放（用户插话时由前端调用）：打断当前条并清空队列，不受打断策略影响；
+// 之后到达的音频属于被取消的回复，直接丢弃，直到用户说完下一句或TTS连接重建。返回被丢弃的utterance
+#[command]
+async fn cancel_tts_playback(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<Vec<u64>, String> {
+    let outcome = {
+        let queue = &state.tts_queue;
+        let mut queue_guard = match queue.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取TTS播放队列锁失败: {}", e);
+                return Err(format!("获取TTS播放队列失败: {}", e));
+            }
+        };
+        queue_guard.cancel(Instant::now())
+    };
+    let dropped = outcome.dropped.clone();
+    apply_interrupt_outcome(Some(&app_handle), &state, outcome, false);
+    
+    debug!("[TTS音频] 已取消播放，丢弃utterance: {:?}", dropped);
+    if let Err(e) = app_handle.emit("tts-cancelled", &dropped) {
+        error!("[错误] 发送TTS取消事件到前端失败: {}", e);
+    }
+    Ok(dropped)
+}
+
+// 新一轮回复开始时恢复接收TTS音频
+fn clear_tts_cancellation(state: &AppState) {
+    match state.tts_queue.lock() {
+        Ok(mut queue) => {
+            if queue.clear_cancelled() {
+                debug!("[TTS音频] 恢复接收TTS音频");
+            }
+        },
+        Err(e) => error!("[错误] 获取TTS播放队列锁失败: {}", e),
+    }
+}
+
+// 后端下发的TTS字幕: {"text": "...", "utterance_id": 可选}
+fn apply_tts_caption(state: &AppState, caption: CaptionPayload) -> Result<String, String> {
+    let queue = &state.tts_queue;
+    let mut queue_guard = queue.lock().map_err(|e| format!("获取TTS播放队列失败: {}", e))?;
+    match queue_guard.set_caption(caption.utterance_id, caption.text) {
+        Some(id) => Ok(format!("字幕已关联到utterance #{}", id)),
+        None => Ok("字幕已暂存，等待下一条utterance".to_string()),
+    }
+}
+
+// 获取对话历史（按时间顺序）
+#[command]
+async fn get_conversation_history_entries(state: State<'_, AppState>) -> Result<Vec<HistoryEntry>, String> {
+    let history = &state.history;
+    let history_guard = match history.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取对话历史锁失败: {}", e);
+            return Err(format!("获取对话历史失败: {}", e));
+        }
+    };
+    
+    Ok(history_guard.entries())
+}
+
+// 分页获取对话轮次（按时间顺序），offset 从最早的轮次起算，limit 缺省为全部
+#[command]
+async fn get_conversation_turns(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<Turn>, String> {
+    let tracker = &state.turns;
+    let tracker_guard = match tracker.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取对话轮次锁失败: {}", e);
+            return Err(format!("获取对话轮次失败: {}", e));
+        }
+    };
+    
+    Ok(tracker_guard.page(offset.unwrap_or(0), limit.unwrap_or(usize::MAX)))
+}
+
+// 会话报告：会话元数据与全部对话轮次
+#[derive(Serialize, Clone, Debug)]
+struct ConversationReport {
+    exported_at_ms: u64,
+    session: SessionMeta,
+    turns: Vec<Turn>,
+}
+
+// 去掉诊断排除列表中的轮次（用户删除过转写的轮次）
+fn excluding_deleted_turns(state: &AppState, turns: Vec<Turn>) -> Result<Vec<Turn>, String> {
+    let privacy = &state.privacy;
+    let privacy_guard = privacy.lock().map_err(|e| format!("获取隐私模式失败: {}", e))?;
+    Ok(turns
+        .into_iter()
+        .filter(|turn| !privacy_guard.is_excluded_from_diagnostics(turn.turn_id))
+        .collect())
+}
+
+// 导出会话报告为JSON文件，诊断排除列表中的轮次不导出
+#[command]
+async fn export_conversation_report(state: State<'_, AppState>, path: String) -> Result<String, String> {
+    ensure_persistence_allowed(&state, "导出会话报告")?;
+    let checkpoint = collect_checkpoint(&state)?;
+    let turns = excluding_deleted_turns(&state, checkpoint.turns)?;
+    let report = ConversationReport {
+        exported_at_ms: checkpoint.saved_at_ms,
+        session: checkpoint.session,
+        turns,
+    };
+    
+    let content = serde_json::to_string_pretty(&report).map_err(|e| format!("序列化会话报告失败: {}", e))?;
+    std::fs::write(&path, content).map_err(|e| format!("写入会话报告 {} 失败: {}", path, e))?;
+    
+    info!("[信息] 已导出会话报告({}个轮次)到: {}", report.turns.len(), path);
+    Ok(path)
+}
+
+// 从磁盘重新读取配置并热应用，保持Socket连接与当前会话不中断
+#[command]
+async fn reload_config(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<LuminaConfig, String> {
+    let path = config::config_path(&app_handle)?;
+    info!("[信息] 重新加载配置: {}", path.display());
+    
+    let loaded = reload_config_from(Some(&app_handle), &state, &path)?;
+    
+    if let Err(e) = app_handle.emit("config-reloaded", &loaded) {
+        error!("[错误] 发送配置重载事件到前端失败: {}", e);
+    }
+    
+    info!("[信息] 配置已重新加载");
+    Ok(loaded)
+}
+
+// 读取 path 处的配置并热应用，返回读取到的配置；app_handle 为 None 时不向前端发事件
+fn reload_config_from(app_handle: Option<&tauri::AppHandle>, state: &AppState, path: &std::path::Path) -> Result<LuminaConfig, String> {
+    let loaded = config::load_from_disk(path)?;
+    apply_config(app_handle, state, &loaded)?;
+    Ok(loaded)
+}
+
+// get_config 的返回：已保存的配置字段原样展开，另附不写入配置的不留痕模式状态
+#[derive(Serialize, Clone, Debug)]
+struct ConfigView {
+    #[serde(flatten)]
+    config: LuminaConfig,
+    privacy: Option<PrivacyStatus>, // 不留痕模式状态，状态不可读时为None
+}
+
+fn config_view(state: &AppState, config: LuminaConfig) -> ConfigView {
+    ConfigView {
+        config,
+        privacy: state.privacy.lock().ok().map(|privacy| privacy.status()),
+    }
+}
+
+// 获取已保存的配置，并附带当前的不留痕模式状态（该模式只在本次运行内有效，不写入配置）
+#[command]
+async fn get_config(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<ConfigView, String> {
+    let stored = config::load_from_disk(&config::config_path(&app_handle)?)?;
+    Ok(config_view(&state, stored))
+}
+
+// 部分更新配置：patch 为 JSON merge patch，如 {"vad_mode": "aggressive", "parameters": {"pre_context_ms": 300}}，
+// 字段为 null 时删除（回到默认值）。合并结果先整体校验并应用，成功后原子写回磁盘，返回更新后的完整配置
+#[command]
+async fn set_config(app_handle: tauri::AppHandle, state: State<'_, AppState>, patch: serde_json::Value) -> Result<LuminaConfig, String> {
+    let path = config::config_path(&app_handle)?;
+    let stored = config::load_from_disk(&path)?;
+    let updated = config::merge_patch(&stored, patch)?;
+    apply_config(Some(&app_handle), &state, &updated)?;
+    config::save_to_disk(&path, &updated)?;
+    
+    info!("[信息] 配置已更新并写入: {}", path.display());
+    Ok(updated)
+}
+
+// 开关"因静音结束说话时请求最终识别"，等待超时通过 finalize_timeout_ms 参数调整
+#[command]
+async fn set_finalize_on_silence(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<String, String> {
+    {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = match vad_state_machine.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD状态机锁失败: {}", e);
+                return Err(format!("获取VAD状态机失败: {}", e));
+            }
+        };
+        state_machine.finalize_on_silence = enabled;
+        if !enabled {
+            state_machine.pending_finalize = None;
+        }
+    }
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.finalize_on_silence = Some(enabled);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 静音结束时请求最终识别: {}", enabled);
+    Ok(format!("静音结束时请求最终识别: {}", enabled))
+}
+
+// 设置单段发言的最长时长（秒），连续说话达到该时长时强制结束本段发言，0为不限制；设置成功后写入配置
+#[command]
+async fn set_max_utterance_seconds(app_handle: tauri::AppHandle, state: State<'_, AppState>, seconds: u64) -> Result<String, String> {
+    {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = match vad_state_machine.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD状态机锁失败: {}", e);
+                return Err(format!("获取VAD状态机失败: {}", e));
+            }
+        };
+        state_machine.max_utterance_seconds = seconds;
+    }
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.max_utterance_seconds = Some(seconds);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 最长发言时长已设置为: {}秒", seconds);
+    Ok(format!("最长发言时长已设置为: {}秒", seconds))
+}
+
+// 设置采集方式: "vad" / "push_to_talk"，运行中切换即可，不需要重启监听器。
+// 按键说话时离开该模式视为松开按键，正在进行的发言就此结束
+#[command]
+async fn set_capture_mode(app_handle: tauri::AppHandle, state: State<'_, AppState>, mode: String) -> Result<String, String> {
+    store_capture_mode(&app_handle, &state, &mode)
+}
+
+// set_capture_mode 的别名，供按 ptt_start/ptt_stop 接入的前端使用
+#[command]
+async fn set_input_mode(app_handle: tauri::AppHandle, state: State<'_, AppState>, mode: String) -> Result<String, String> {
+    store_capture_mode(&app_handle, &state, &mode)
+}
+
+// 切换采集方式并写入配置
+fn store_capture_mode(app_handle: &tauri::AppHandle, state: &AppState, mode: &str) -> Result<String, String> {
+    let capture_mode = CaptureMode::from_name(mode)
+        .ok_or_else(|| format!("未知的采集方式: {}，可选值: vad, push_to_talk", mode))?;
+    apply_capture_mode(Some(app_handle), state, capture_mode)?;
+    
+    let path = config::config_path(app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.capture_mode = Some(capture_mode);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 采集方式已设置为: {}", mode);
+    Ok(format!("采集方式已设置为: {}", mode))
+}
+
+fn apply_capture_mode(app_handle: Option<&tauri::AppHandle>, state: &AppState, capture_mode: CaptureMode) -> Result<(), String> {
+    // 前瞻延迟线中滞留的帧属于切换之前的判定方式，丢弃
+    state.vad.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?.look_ahead.clear();
+    
+    let held = {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        state_machine.capture_mode = capture_mode;
+        state_machine.ptt_held
+    };
+    if held && capture_mode == CaptureMode::Vad {
+        push_to_talk(app_handle, state, false)?;
+    }
+    Ok(())
+}
+
+// 按键说话：按下，直接进入说话中并发送前置上下文帧
+#[command]
+async fn ptt_pressed(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    push_to_talk(Some(&app_handle), &state, true)?;
+    Ok("按键说话：开始发送".to_string())
+}
+
+// 按键说话：松开，进入等待中并收尾当前语音段
+#[command]
+async fn ptt_released(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    push_to_talk(Some(&app_handle), &state, false)?;
+    Ok("按键说话：停止发送".to_string())
+}
+
+// ptt_pressed 的别名
+#[command]
+async fn ptt_start(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    push_to_talk(Some(&app_handle), &state, true)?;
+    Ok("按键说话：开始发送".to_string())
+}
+
+// ptt_released 的别名
+#[command]
+async fn ptt_stop(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    push_to_talk(Some(&app_handle), &state, false)?;
+    Ok("按键说话：停止发送".to_string())
+}
+
+// app_handle 为 None 时只更新状态不发事件
+fn push_to_talk(app_handle: Option<&tauri::AppHandle>, state: &AppState, pressed: bool) -> Result<(), String> {
+    let now = Instant::now();
+    let (started, ended, completed_turns) = {
+        let vad_state_machine = &state.sm;
+        let mut state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        if pressed && state_machine.capture_mode != CaptureMode::PushToTalk {
+            return Err("当前采集方式不是按键说话，请先调用 set_capture_mode(\"push_to_talk\")".to_string());
+        }
+        if pressed && state_machine.muted {
+            return Err("麦克风已静音，请先解除静音".to_string());
+        }
+        if state_machine.ptt_held == pressed {
+            return Ok(());
+        }
+        state_machine.ptt_held = pressed;
+        if let Some(app_handle) = app_handle {
+            state_machine.set_app_handle(app_handle.clone());
+        }
+        
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        let was_sending = matches!(state_machine.get_current_state(), VadState::Speaking | VadState::TransitionBuffer);
+        let event = if pressed { VadStateMachineEvent::PttPressed } else { VadStateMachineEvent::PttReleased };
+        let sending = state_machine.process_event_at(event, &mut socket_manager_guard, now);
+        if !pressed {
+            // 松开即结束当前语音段，并尝试补发之前失败的语音段
+            socket_manager_guard.flush();
+        }
+        
+        let (started, ended) = (!was_sending && sending, was_sending && !sending);
+        if ended {
+            socket_manager_guard.spans.speech_ended();
+        }
+        let finished_segments = socket_manager_guard.take_finished_segment_ids();
+        let errors = socket_manager_guard.errors.take();
+        if let Some(app_handle) = app_handle {
+            backend_error::emit_all(app_handle, errors);
+        }
+        let completed_turns = match state.turns.lock() {
+            Ok(mut tracker) => {
+                let mut completed = Vec::new();
+                if started {
+                    completed = tracker.user_speech_started(unix_ms_at(now));
+                }
+                tracker.audio_segments_finished(&finished_segments);
+                if ended {
+                    tracker.user_speech_ended(unix_ms_at(now));
+                }
+                completed
+            },
+            Err(e) => {
+                error!("[错误] 获取对话轮次锁失败: {}", e);
+                Vec::new()
+            }
+        };
+        (started, ended, completed_turns)
+    };
+    if let Some(app_handle) = app_handle {
+        emit_completed_turns(app_handle, completed_turns);
+    }
+    
+    // 与VAD判定的语音起止一样通知前端
+    let event = if started {
+        Some(VadEvent::SpeechStart)
+    } else if ended {
+        // 用户说完新的一句，之后到达的TTS音频属于新一轮回复
+        clear_tts_cancellation(state);
+        Some(VadEvent::SpeechEnd)
+    } else {
+        None
+    };
+    if let (Some(event), Some(app_handle)) = (event, app_handle) {
+        if let Err(e) = app_handle.emit("vad-event", &event) {
+            error!("[错误] 事件发送失败: {}", e);
+        }
+    }
+    publish_vad_state(state);
+    Ok(())
+}
+
+// 仅本地模式：VAD、状态机与本地事件照常工作，但不连接后端、不发送任何音频与控制消息，
+// 已连上的STT结果与TTS监听器立即断开、不再重连；临界态按本地规则确认有效语音。开关成功后写入配置
+#[command]
+async fn set_local_only_mode(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<String, String> {
+    apply_local_only_mode(&state, enabled)?;
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.local_only_mode = Some(enabled);
+    config::save_to_disk(&path, &stored)?;
+    
+    let message = format!("仅本地模式已{}", if enabled { "开启" } else { "关闭" });
+    info!("[信息] {}", message);
+    Ok(message)
+}
+
+// 设置心跳间隔（秒），0为关闭；修改后写入配置
+#[command]
+async fn set_heartbeat_interval(app_handle: tauri::AppHandle, state: State<'_, AppState>, interval_secs: u64) -> Result<String, String> {
+    {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        socket_manager_guard.heartbeat_interval_secs = interval_secs;
+    }
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.heartbeat_interval_secs = Some(interval_secs);
+    config::save_to_disk(&path, &stored)?;
+    
+    let message = if interval_secs == 0 {
+        "心跳已关闭".to_string()
+    } else {
+        format!("心跳间隔已设置为{}秒", interval_secs)
+    };
+    info!("[信息] {}", message);
+    Ok(message)
+}
+
+fn apply_local_only_mode(state: &AppState, enabled: bool) -> Result<(), String> {
+    // 锁顺序：状态机 -> socket
+    let vad_state_machine = &state.sm;
+    let mut state_machine = vad_state_machine.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+    state_machine.local_confirmation = enabled;
+    socket_manager_guard.set_local_only(enabled);
+    drop(socket_manager_guard);
+    drop(state_machine);
+    // 已连上的 STT 结果与 TTS 监听器同样断开，监听器看到仅本地模式后不再重连
+    if enabled {
+        let closed = state.lifecycle.close_streams();
+        debug!("[调试] 开启仅本地模式，断开{}个监听器连接", closed);
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct MicMutedChange {
+    muted: bool,
+}
+
+// 麦克风静音：静音期间 process_audio_frame 只计算本地电平，不做VAD判定，不发送任何音频，
+// 状态机回到初始状态；解除静音后前置缓冲区从新的帧重新填充。只在本次运行中有效，不写入配置
+#[command]
+async fn set_microphone_muted(app_handle: tauri::AppHandle, state: State<'_, AppState>, muted: bool) -> Result<String, String> {
+    let changed = apply_microphone_mute(&state, muted)?;
+    if changed {
+        if let Err(e) = app_handle.emit("mic-muted-changed", &MicMutedChange { muted }) {
+            error!("[错误] 发送麦克风静音事件到前端失败: {}", e);
+        }
+        publish_vad_state(&state);
+    }
+    
+    let message = format!("麦克风已{}", if muted { "静音" } else { "解除静音" });
+    info!("[信息] {}", message);
+    Ok(message)
+}
+
+// 返回静音状态是否发生了变化
+fn apply_microphone_mute(state: &AppState, muted: bool) -> Result<bool, String> {
+    // 锁顺序：VAD处理器 -> 状态机 -> socket
+    let mut processor = state.vad.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+    let mut state_machine = state.sm.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+    if state_machine.muted == muted {
+        return Ok(false);
+    }
+    let mut socket_manager_guard = state.socket.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+    
+    state_machine.muted = muted;
+    if muted {
+        state_machine.ptt_held = false;
+        state_machine.reset_to_initial();
+    }
+    processor.discard_pending();
+    socket_manager_guard.discard_capture();
+    Ok(true)
+}
+
+// 开关持久化待发队列（WAL）：开启后音频段写入socket前先落盘，发送确认后删除，崩溃重启后补发未送达的段。
+// 每段多一次磁盘写入，默认关闭；开关成功后写入配置
+#[command]
+async fn set_send_queue_persistence(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<SendQueueStatus, String> {
+    let status = apply_send_queue_persistence(Some(&app_handle), &state, enabled)?;
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.persistent_send_queue = Some(enabled);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 持久化待发队列已{}", if enabled { "开启" } else { "关闭" });
+    Ok(status)
+}
+
+// 在初始状态下启动一次校准并等待采集完成，返回采集到的数据；期间的帧不进入状态机，也不发往后端
+async fn run_calibration(state: &AppState, kind: CalibrationKind, duration_ms: u64) -> Result<Calibration, String> {
+    calibration::validate_duration(duration_ms)?;
+    let what = kind.label();
+    
+    {
+        let vad_processor = &state.vad;
+        let mut processor = match vad_processor.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        };
+        let vad_state_machine = &state.sm;
+        let state_machine = match vad_state_machine.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD状态机锁失败: {}", e);
+                return Err(format!("获取VAD状态机失败: {}", e));
+            }
+        };
+        if *state_machine.get_current_state() != VadState::Initial {
+            return Err(format!("只能在初始状态下进行{}，当前状态: {:?}", what, state_machine.get_current_state()));
+        }
+        if state_machine.muted {
+            return Err(format!("麦克风已静音，无法进行{}", what));
+        }
+        if let Some(running) = processor.calibration.as_ref() {
+            return Err(format!("{}已在进行中", running.kind.label()));
+        }
+        processor.calibration = Some(Calibration::new(kind, duration_ms));
+    }
+    info!("[信息] 开始{}，采集{}ms", what, duration_ms);
+    
+    // 采集按音频时长计，前端未送帧时最多多等 CALIBRATION_GRACE_MS
+    let deadline = Instant::now() + Duration::from_millis(duration_ms + CALIBRATION_GRACE_MS);
+    let calibration = loop {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut processor = state.vad.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        let complete = processor.calibration.as_ref().is_none_or(|calibration| calibration.is_complete());
+        if complete || Instant::now() >= deadline {
+            break processor.calibration.take();
+        }
+    };
+    // reset_vad_state 等操作会重建处理器，进行中的校准随之取消
+    let calibration = calibration.ok_or_else(|| format!("{}已被取消", what))?;
+    if !calibration.is_complete() {
+        return Err(format!(
+            "{}超时：只收到{}ms音频，请确认麦克风正在采集",
+            what,
+            calibration.collected_ms()
+        ));
+    }
+    Ok(calibration)
+}
+
+// 校准环境底噪：在初始状态下采集 duration_ms 的环境音（期间不发送任何音频），按能量分布推荐能量阈值与灵敏度档位，
+// 推荐值立即生效并写入配置。期间语音帧超过20%时失败，由界面提示用户保持安静后重试
+#[command]
+async fn calibrate_noise_floor(app_handle: tauri::AppHandle, state: State<'_, AppState>, duration_ms: u64) -> Result<CalibrationResult, String> {
+    let calibration = run_calibration(&state, CalibrationKind::NoiseFloor, duration_ms).await?;
+    let result = calibration.finish_noise_floor().map_err(|e| {
+        warn!("[警告] 底噪校准失败: {}", e);
+        e
+    })?;
+    
+    // 推荐值写入用户配置层并下发，灵敏度档位直接切换
+    params::validate_value(ParamKey::EnergyFallbackThreshold, result.energy_threshold as f64)?;
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        layers_guard.user.insert(ParamKey::EnergyFallbackThreshold, result.energy_threshold as f64);
+    }
+    refresh_effective_parameters(Some(&app_handle), &state)?;
+    state.vad.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?.set_mode(result.vad_mode);
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.parameters.insert(ParamKey::EnergyFallbackThreshold.name().to_string(), result.energy_threshold as f64);
+    stored.vad_mode = Some(result.vad_mode);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!(
+        "[信息] 底噪校准完成: 中位数{:.4}，P95 {:.4}，推荐能量阈值{:.4}，灵敏度{:?}",
+        result.rms_median, result.rms_p95, result.energy_threshold, result.vad_mode
+    );
+    Ok(result)
+}
+
+// 语音检测诊断：用户在初始状态下对着麦克风说话 duration_ms（期间不发送任何音频），
+// 返回语音帧占比（整体与逐秒）、平均/峰值电平、估计信噪比、噪声评价与灵敏度建议。
+// 检测到语音时，语音帧平均RMS记为会话输入响度的参考值（即"正常距离说话"的响度），此前连续会话偏低的建议一并写入报告；
+// 其余设置不做修改，界面可据 suggested_mode 调用 set_vad_mode
+#[command]
+async fn check_speech_detection(state: State<'_, AppState>, duration_ms: u64) -> Result<SpeechCheckResult, String> {
+    let calibration = run_calibration(&state, CalibrationKind::SpeechCheck, duration_ms).await?;
+    let noise_floor = state.vad.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?.session.noise_floor;
+    let mut result = calibration.finish_speech_check(noise_floor)?;
+    
+    {
+        let mut state_machine = state.sm.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        result.input_level_suggestion = state_machine.input_level.suggestion();
+        if let Some(suggestion) = &result.input_level_suggestion {
+            result.summary.push_str(&format!("；{}", suggestion));
+        }
+        if result.speech_rms > 0.0 {
+            state_machine.input_level.calibrate_reference(result.speech_rms);
+            info!("[信息] 输入响度参考值已校准为: {:.4}", result.speech_rms);
+        }
+    }
+    
+    info!(
+        "[信息] 语音检测完成: 语音占比{:.0}%，平均电平{:.1}dBFS，峰值{:.1}dBFS，信噪比{}，建议{:?}",
+        result.voice_ratio * 100.0,
+        result.mean_level_db,
+        result.peak_level_db,
+        result.snr_db.map_or("未知".to_string(), |snr| format!("{:.1}dB", snr)),
+        result.suggested_mode
+    );
+    Ok(result)
+}
+
+// 设置VAD灵敏度: "quality" / "low_bitrate" / "aggressive" / "very_aggressive"
+// voting 开关双重判定模式（webrtc_vad与能量检测都判为语音才算语音），缺省保持不变
+// 会话进行中修改也安全，下一帧起生效
+#[command]
+async fn set_vad_mode(state: State<'_, AppState>, mode: String, voting: Option<bool>) -> Result<String, String> {
+    let vad_mode = match VadAggressiveness::from_name(&mode) {
+        Some(m) => m,
+        None => return Err(format!("未知的VAD模式: {}，可选值: quality, low_bitrate, aggressive, very_aggressive", mode)),
+    };
+    
+    let vad_processor = &state.vad;
+    let mut processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    processor.set_mode(vad_mode);
+    if let Some(voting) = voting {
+        processor.set_voting(voting);
+    }
+    info!("[信息] VAD模式已设置为: {:?}，双重判定: {}", vad_mode, processor.voting);
+    Ok(format!("VAD模式已设置为: {}，双重判定: {}", mode, processor.voting))
+}
+
+// 切换VAD判定后端: "webrtc"（默认）/ "silero"（需启用 silero-vad feature）/ "energy"（按帧能量与自适应底噪判定）
+// model_path 缺省时沿用已保存的路径或环境变量 LUMINA_SILERO_MODEL，threshold 为判为语音的概率阈值。
+// 新后端从零开始判定，状态机与发送流程不受影响；切换成功后写入配置
+#[command]
+async fn set_vad_backend(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    backend: String,
+    model_path: Option<String>,
+    threshold: Option<f32>,
+) -> Result<VadConfig, String> {
+    let kind = VadBackendKind::from_name(&backend)
+        .ok_or_else(|| format!("未知的VAD后端: {}，可选值: webrtc, silero, energy", backend))?;
+    
+    let config = {
+        let vad_processor = &state.vad;
+        let mut processor = match vad_processor.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        };
+        let current = processor.silero.clone();
+        let silero = SileroSettings {
+            model_path: model_path.or(current.model_path),
+            threshold: threshold.unwrap_or(current.threshold),
+        };
+        silero.validate()?;
+        processor.set_backend(kind, silero)?;
+        processor.config()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.vad_backend = Some(config.backend);
+    stored.silero = Some(config.silero.clone());
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] VAD判定后端已切换为: {:?}", config.backend);
+    Ok(config)
+}
+
+// 开关VAD判定前的RNNoise降噪（需要denoise feature），VAD与发往后端的音频都使用降噪后的数据；
+// 开关成功后写入配置
+#[command]
+async fn set_denoise(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<VadConfig, String> {
+    let config = {
+        let vad_processor = &state.vad;
+        let mut processor = match vad_processor.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        };
+        processor.set_denoise(enabled)?;
+        processor.config()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.denoise = Some(enabled);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] VAD判定前降噪已{}", if enabled { "开启" } else { "关闭" });
+    Ok(config)
+}
+
+// 输入采样率核对的状态：最近的实测值与判定出的不匹配
+#[command]
+async fn get_sample_rate_check(state: State<'_, AppState>) -> Result<RateCheckStatus, String> {
+    let vad_processor = &state.vad;
+    let processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    Ok(processor.rate_check.status())
+}
+
+// 开关输入采样率自动纠正：开启后实测采样率与声明不符时按检测到的采样率重采样；开关成功后写入配置
+#[command]
+async fn set_sample_rate_auto_correct(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<RateCheckStatus, String> {
+    let status = {
+        let vad_processor = &state.vad;
+        let mut processor = match vad_processor.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        };
+        processor.rate_check.set_auto_correct(enabled);
+        processor.rate_check.status()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.sample_rate_auto_correct = Some(enabled);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 输入采样率自动纠正已{}", if enabled { "开启" } else { "关闭" });
+    Ok(status)
+}
+
+// 设置管线采样率: 8000 / 16000 / 32000 / 48000（webrtc_vad 支持的采样率）
+// VAD以新采样率重建，已缓存的语音段被清空；未声明输入采样率的帧按新采样率解释
+#[command]
+async fn set_sample_rate(app_handle: tauri::AppHandle, state: State<'_, AppState>, rate: u32) -> Result<String, String> {
+    apply_sample_rate(&state, rate)?;
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.sample_rate = Some(rate);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 管线采样率已设置为: {}Hz", rate);
+    Ok(format!("管线采样率已设置为: {}Hz", rate))
+}
+
+// 切换管线采样率，VAD处理器与SocketManager保持一致
+fn apply_sample_rate(state: &AppState, sample_rate: u32) -> Result<(), String> {
+    {
+        let vad_processor = &state.vad;
+        let mut processor = vad_processor.lock().map_err(|e| format!("获取VAD处理器失败: {}", e))?;
+        processor.set_sample_rate(sample_rate)?;
+    }
+    
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+    socket_manager_guard.set_sample_rate(sample_rate);
+    Ok(())
+}
+
+// 获取当前生效的发送处理链：各环节顺序、参数与自适应增益
+#[command]
+async fn get_pipeline(state: State<'_, AppState>) -> Result<PipelineStatus, String> {
+    let socket_manager = &state.socket;
+    let socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    Ok(socket_manager_guard.pipeline.status())
+}
+
+// 重建发送处理链，如 [{"stage": "dc_removal"}, {"stage": "agc", "target_rms": 0.1}]
+// 新链在发送锁内整体替换，正在进行的会话不中断；任一环节非法时保留原处理链
+#[command]
+async fn set_pipeline(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    stages: Vec<StageConfig>,
+) -> Result<PipelineStatus, String> {
+    let status = {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        socket_manager_guard.pipeline.configure(stages.clone())?;
+        socket_manager_guard.pipeline.status()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.audio_pipeline = Some(stages);
+    config::save_to_disk(&path, &stored)?;
+    
+    let names: Vec<&str> = status.stages.iter().map(|stage| stage.config.name()).collect();
+    info!("[信息] 发送处理链已更新: {:?}", names);
+    Ok(status)
+}
+
+// 设置VAD判定前的滤波环节，如 [{"stage": "high_pass", "cutoff_hz": 80}, {"stage": "low_pass", "cutoff_hz": 7000}]，
+// 空列表为关闭。滤波后的音频同时发往后端；任一环节非法时保留原设置，设置成功后写入配置
+#[command]
+async fn set_vad_input_filters(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    stages: Vec<StageConfig>,
+) -> Result<VadConfig, String> {
+    let config = {
+        let vad_processor = &state.vad;
+        let mut processor = match vad_processor.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        };
+        processor.set_input_filters(stages.clone())?;
+        processor.config()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.vad_input_filters = Some(stages);
+    config::save_to_disk(&path, &stored)?;
+    
+    let names: Vec<&str> = config.input_filters.iter().map(|stage| stage.name()).collect();
+    info!("[信息] VAD判定前滤波已更新: {:?}", names);
+    Ok(config)
+}
+
+// 获取当前VAD配置
+#[command]
+async fn get_vad_config(state: State<'_, AppState>) -> Result<VadConfig, String> {
+    let vad_processor = &state.vad;
+    let processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    Ok(processor.config())
+}
+
+fn validate_audio_level_interval(interval_ms: u64) -> Result<(), String> {
+    if interval_ms != 0 && !(audio_level::MIN_AUDIO_LEVEL_INTERVAL_MS..=5000).contains(&interval_ms) {
+        return Err(format!(
+            "电平事件间隔必须为0（关闭）或在{}~5000ms之间: {}",
+            audio_level::MIN_AUDIO_LEVEL_INTERVAL_MS, interval_ms
+        ));
+    }
+    Ok(())
+}
+
+// 设置 audio-level 电平事件的发送间隔，0 表示关闭
+#[command]
+async fn set_audio_level_interval(state: State<'_, AppState>, interval_ms: u64) -> Result<String, String> {
+    validate_audio_level_interval(interval_ms)?;
+    
+    let vad_processor = &state.vad;
+    let mut processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    processor.level_meter.set_interval_ms(interval_ms);
+    info!("[信息] 电平事件间隔已设置为: {}ms", interval_ms);
+    Ok(format!("电平事件间隔已设置为: {}ms", interval_ms))
+}
+
+// 设置 stt-result 中间结果的节流间隔（毫秒），0 表示不节流；最终结果始终立即发送。设置成功后写入配置
+#[command]
+async fn set_stt_partial_throttle(app_handle: tauri::AppHandle, state: State<'_, AppState>, interval_ms: u64) -> Result<String, String> {
+    {
+        let partials = &state.partials;
+        let mut merger = match partials.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取中间结果合并器锁失败: {}", e);
+                return Err(format!("获取中间结果合并器失败: {}", e));
+            }
+        };
+        merger.set_throttle_ms(interval_ms);
+    }
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.stt_partial_throttle_ms = Some(interval_ms);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] STT中间结果节流间隔已设置为: {}ms", interval_ms);
+    Ok(format!("STT中间结果节流间隔已设置为: {}ms", interval_ms))
+}
+
+// 获取VAD处理统计：帧数、语音段起止次数、判定耗时等
+#[command]
+async fn get_vad_statistics(state: State<'_, AppState>) -> Result<VadStatistics, String> {
+    let vad_processor = &state.vad;
+    let processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    Ok(processor.stats.snapshot())
+}
+
+// 清零VAD处理统计，不影响检测状态
+#[command]
+async fn reset_vad_statistics(state: State<'_, AppState>) -> Result<(), String> {
+    let vad_processor = &state.vad;
+    let mut processor = match vad_processor.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    processor.stats = VadStats::new();
+    info!("[信息] VAD处理统计已清零");
+    Ok(())
+}
+
+// VAD状态快照：get_vad_state_detail 的返回值，也是 subscribe_vad_state 推送的内容
+#[derive(Serialize, Clone, Debug)]
+struct VadStateSnapshot {
+    seq: u64,              // 内容每变化一次加一，订阅者收到的序号单调递增
+    state: String,         // 对用户可见的状态，临界态时为上一个可见状态
+    internal_state: String, // 状态机实际所处的状态
+    listening_mode: ListeningMode,
+    connected: bool,       // 音频上行是否已连接后端
+    muted: bool,           // 麦克风是否静音
+    session_id: u64,       // 会话序号，每次开始新的发言时递增
+}
+
+impl VadStateSnapshot {
+    fn initial() -> Self {
+        Self {
+            seq: 0,
+            state: format!("{:?}", VadState::Initial),
+            internal_state: format!("{:?}", VadState::Initial),
+            listening_mode: ListeningMode::Active,
+            connected: false,
+            muted: false,
+            session_id: 0,
+        }
+    }
+    
+    // 比较除序号外的内容
+    fn same_content(&self, other: &Self) -> bool {
+        self.state == other.state
+            && self.internal_state == other.internal_state
+            && self.listening_mode == other.listening_mode
+            && self.connected == other.connected
+            && self.muted == other.muted
+            && self.session_id == other.session_id
+    }
+}
+
+// 读取当前状态生成快照（序号由 publish_vad_state 填写）
+fn current_vad_state(state: &AppState) -> Result<VadStateSnapshot, String> {
+    let listening_mode = {
+        let gate = state.focus.lock().map_err(|e| format!("获取焦点门控失败: {}", e))?;
+        gate.mode()
+    };
+    let (visible_state, internal_state, muted, session_id) = {
+        let state_machine = state.sm.lock().map_err(|e| format!("获取VAD状态机失败: {}", e))?;
+        (
+            format!("{:?}", state_machine.visible_state()),
+            format!("{:?}", state_machine.get_current_state()),
+            state_machine.muted,
+            state_machine.session_id,
+        )
+    };
+    let connected = {
+        let socket_manager_guard = state.socket.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        socket_manager_guard.stream.is_some() && socket_manager_guard.protocol_version.is_some()
+    };
+    Ok(VadStateSnapshot {
+        seq: 0,
+        state: visible_state,
+        internal_state,
+        listening_mode,
+        connected,
+        muted,
+        session_id,
+    })
+}
+
+// 状态可能已变化时调用：内容有变化才推送给订阅者。
+// 在 watch 的写锁内读取状态，并发调用按顺序生效，订阅者最终收到的总是最新状态。
+// 调用时不能持有焦点门控、状态机或SocketManager的锁
+fn publish_vad_state(state: &AppState) {
+    let changed = state.vad_state_stream.send_if_modified(|current| match current_vad_state(state) {
+        Ok(mut snapshot) if !snapshot.same_content(current) => {
+            snapshot.seq = current.seq + 1;
+            *current = snapshot;
+            true
+        }
+        Ok(_) => false,
+        Err(e) => {
+            error!("[错误] 生成VAD状态快照失败: {}", e);
+            false
+        }
+    });
+    if changed {
+        mirror_vad_state(state);
+    }
+}
+
+// 把最新快照写入本地状态镜像，未开启时不做任何事；写入过于频繁时安排一次补写
+fn mirror_vad_state(state: &AppState) {
+    let snapshot = state.vad_state_stream.borrow().clone();
+    let mirrored = MirroredState {
+        state: snapshot.state,
+        mic_muted: snapshot.muted,
+        session_id: snapshot.session_id,
+        updated_at: 0, // 写入时填写
+    };
+    let delay = match state.mirror.lock() {
+        Ok(mut mirror) => mirror.offer(mirrored, Instant::now()),
+        Err(e) => {
+            error!("[错误] 获取状态镜像锁失败: {}", e);
+            None
+        }
+    };
+    if let Some(delay) = delay {
+        let state = state.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(delay).await;
+            match state.mirror.lock() {
+                Ok(mut mirror) => mirror.flush_due(Instant::now()),
+                Err(e) => error!("[错误] 获取状态镜像锁失败: {}", e),
+            }
+        });
+    }
+}
+
+// 开关本地状态镜像：开启后把 { state, mic_muted, session_id, updated_at } 写入 path（缺省为每用户socket目录下的
+// lumina_vad_state.json，Windows 为用户临时目录），状态变化时更新，每秒最多10次；关闭时删除文件。返回当前镜像路径，关闭时为None。
+// 开关成功后写入配置
+#[command]
+async fn set_state_mirror(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool, path: Option<String>) -> Result<Option<String>, String> {
+    let path = match (enabled, path) {
+        (false, _) => None,
+        (true, Some(path)) => Some(std::path::PathBuf::from(path)),
+        (true, None) => Some(state_mirror::default_path()?),
+    };
+    let mirror_path = apply_state_mirror(&state, path)?;
+    
+    let config_path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&config_path)?;
+    stored.state_mirror_path = mirror_path.clone();
+    config::save_to_disk(&config_path, &stored)?;
+    
+    match &mirror_path {
+        Some(path) => info!("[信息] 本地状态镜像已开启: {}", path),
+        None => info!("[信息] 本地状态镜像已关闭"),
+    }
+    Ok(mirror_path)
+}
+
+// path 为None时关闭；开启后立即写入一次当前状态
+fn apply_state_mirror(state: &AppState, path: Option<std::path::PathBuf>) -> Result<Option<String>, String> {
+    let mirror_path = {
+        let mut mirror = state.mirror.lock().map_err(|e| format!("获取状态镜像失败: {}", e))?;
+        match path {
+            Some(path) => mirror.enable(path),
+            None => mirror.disable(),
+        }
+        mirror.path().map(|path| path.display().to_string())
+    };
+    if mirror_path.is_some() {
+        mirror_vad_state(state);
+    }
+    Ok(mirror_path)
+}
+
+// 开关匿名遥测（默认关闭，需用户显式开启）：开启后每5分钟把纯数值的聚合指标与匿名安装 id POST 到 endpoint，
+// 绝不包含音频或文本；endpoint 缺省时沿用已保存的端点。开启需要 telemetry feature，设置写入配置
+#[command]
+async fn set_telemetry(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool, endpoint: Option<String>) -> Result<TelemetryStatus, String> {
+    if enabled {
+        features::require(Feature::Telemetry)?;
+    }
+    
+    let config_path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&config_path)?;
+    let mut settings = stored.telemetry.clone().unwrap_or_default();
+    settings.enabled = enabled;
+    if endpoint.is_some() {
+        settings.endpoint = endpoint;
+    }
+    let settings = apply_telemetry(&state, &settings)?;
+    stored.telemetry = Some(settings);
+    config::save_to_disk(&config_path, &stored)?;
+    
+    let status = state.telemetry.lock().map_err(|e| format!("获取遥测状态失败: {}", e))?.status();
+    match &status.endpoint {
+        Some(endpoint) if status.enabled => info!("[信息] 匿名遥测已开启，上报到: {}", endpoint),
+        _ => info!("[信息] 匿名遥测已关闭"),
+    }
+    Ok(status)
+}
+
+// 应用遥测设置，开启时启动上报线程；返回应写回配置的设置（含首次开启时生成的安装 id）
+fn apply_telemetry(state: &AppState, settings: &TelemetrySettings) -> Result<TelemetrySettings, String> {
+    // 未启用 feature 的构建忽略配置中的开启状态
+    let mut settings = settings.clone();
+    settings.enabled &= Feature::Telemetry.enabled();
+    let applied = {
+        let mut telemetry = state.telemetry.lock().map_err(|e| format!("获取遥测状态失败: {}", e))?;
+        telemetry.configure(&settings)?
+    };
+    if applied.enabled {
+        spawn_telemetry_loop(state);
+    }
+    Ok(applied)
+}
+
+// 预览下一次将要上报的遥测内容（与实际发送的 JSON 完全一致），供用户开启前审查；未开启时也可调用
+#[command]
+async fn get_telemetry_preview(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let report = collect_telemetry_report(&state)?;
+    telemetry::serialize_report(&report)
+}
+
+// 查询当前会话的链路追踪：trace_id 与后端回传核对结果；未启用otel feature时 enabled 为false
+#[command]
+fn get_trace_status(state: State<'_, AppState>) -> Result<TraceStatus, String> {
+    let socket_manager = match state.socket.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    Ok(socket_manager.spans.status())
+}
+
+// 获取VAD状态快照
+#[command]
+async fn get_vad_state_detail(state: State<'_, AppState>) -> Result<VadStateSnapshot, String> {
+    publish_vad_state(&state);
+    Ok(state.vad_state_stream.borrow().clone())
+}
+
+// 订阅VAD状态：立即推送一次当前快照，之后只在内容变化时推送，替代轮询 get_vad_state。
+// 每个订阅者独立接收；前端断开后推送失败，订阅随之结束
+#[command]
+async fn subscribe_vad_state(state: State<'_, AppState>, on_state: Channel<VadStateSnapshot>) -> Result<(), String> {
+    publish_vad_state(&state);
+    let mut receiver = state.vad_state_stream.subscribe();
+    let snapshot = receiver.borrow_and_update().clone();
+    on_state.send(snapshot).map_err(|e| format!("推送VAD状态失败: {}", e))?;
+    
+    tauri::async_runtime::spawn(async move {
+        // 连续多次变化时 watch 只保留最新值，订阅者直接拿到最终状态
+        while receiver.changed().await.is_ok() {
+            let snapshot = receiver.borrow_and_update().clone();
+            if let Err(e) = on_state.send(snapshot) {
+                info!("[信息] VAD状态订阅已结束: {}", e);
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+// 录音指示的合成状态
+#[derive(Serialize, Clone, Debug)]
+struct RecordingIndicator {
+    recording: bool, // 麦克风输入是否进入VAD流程
+    sending: bool,   // 是否正在向后端发送音频
+    vad_state: String,
+    listening_mode: ListeningMode,
+    reason: String,
+    focus_policy: FocusPolicy,
+    ephemeral: bool, // 不留痕模式：音频与文字不落盘
+    local_only: bool, // 仅本地模式：音频不出设备
+    muted: bool,      // 麦克风静音
+}
+
+// 获取录音指示状态（当前档位与切换原因）
+#[command]
+async fn get_recording_indicator(state: State<'_, AppState>) -> Result<RecordingIndicator, String> {
+    let (recording, listening_mode, reason, focus_policy) = {
+        let gate = &state.focus;
+        let gate_guard = match gate.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取焦点门控锁失败: {}", e);
+                return Err(format!("获取焦点门控失败: {}", e));
+            }
+        };
+        (gate_guard.allows_audio(Instant::now()), gate_guard.mode(), gate_guard.reason().to_string(), gate_guard.policy())
+    };
+    let ephemeral = is_ephemeral(&state);
+    let local_only = is_local_only(&state);
+    
+    let vad_state_machine = &state.sm;
+    let state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    let state = state_machine.get_current_state();
+    let muted = state_machine.muted;
+    let recording = recording && !muted;
+    
+    Ok(RecordingIndicator {
+        recording,
+        sending: recording && !local_only && matches!(state, VadState::Speaking | VadState::TransitionBuffer),
+        vad_state: format!("{:?}", state),
+        listening_mode,
+        reason,
+        focus_policy,
+        ephemeral,
+        local_only,
+        muted,
+    })
+}
+
+// 设置窗口失焦时的聆听策略并写入配置文件
+// policy: "always_listen" / "wake_word_when_blurred" / "mute_when_blurred"
+#[command]
+async fn set_focus_policy(app_handle: tauri::AppHandle, state: State<'_, AppState>, policy: String) -> Result<String, String> {
+    let focus_policy = match FocusPolicy::from_name(&policy) {
+        Some(p) => p,
+        None => return Err(format!("未知的焦点策略: {}，可选值: always_listen, wake_word_when_blurred, mute_when_blurred", policy)),
+    };
+    if focus_policy == FocusPolicy::WakeWordWhenBlurred {
+        features::require(Feature::WakeWord)?;
+    }
+    
+    let change = {
+        let gate = &state.focus;
+        let mut gate_guard = match gate.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取焦点门控锁失败: {}", e);
+                return Err(format!("获取焦点门控失败: {}", e));
+            }
+        };
+        gate_guard.set_policy(focus_policy)
+    };
+    if let Some(change) = change {
+        apply_listening_mode_change(Some(&app_handle), &state, &change);
+    }
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.focus_policy = Some(focus_policy);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 焦点策略已设置为: {:?}", focus_policy);
+    Ok(format!("焦点策略已设置为: {}", policy))
+}
+
+// 导出自上次重置VAD以来的语音活动区间: format 为 "textgrid" 或 "rttm"
+#[command]
+async fn export_vad_annotations(state: State<'_, AppState>, path: String, format: String) -> Result<String, String> {
+    let annotation_format = match AnnotationFormat::from_name(&format) {
+        Some(f) => f,
+        None => return Err(format!("未知的标注格式: {}，可选值: textgrid, rttm", format)),
+    };
+    ensure_persistence_allowed(&state, "导出VAD标注")?;
+    
+    let (intervals, duration_ms) = {
+        let vad_processor = &state.vad;
+        let processor = match vad_processor.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取VAD处理器锁失败: {}", e);
+                return Err(format!("获取VAD处理器失败: {}", e));
+            }
+        };
+        (processor.session.annotations.intervals(), processor.session.annotations.duration_ms())
+    };
+    
+    let path = std::path::PathBuf::from(path);
+    let content = match annotation_format {
+        AnnotationFormat::TextGrid => annotations::to_textgrid(&intervals, duration_ms),
+        AnnotationFormat::Rttm => {
+            let file_id = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("lumina");
+            annotations::to_rttm(&intervals, file_id)
+        }
+    };
+    
+    std::fs::write(&path, content).map_err(|e| format!("写入标注文件 {} 失败: {}", path.display(), e))?;
+    info!("[信息] 已导出{}个语音区间到: {}", intervals.len(), path.display());
+    Ok(format!("已导出{}个语音区间到: {}", intervals.len(), path.display()))
+}
+
+// 获取最近一次崩溃恢复的结果，启动后未发生恢复时返回null
+#[command]
+async fn get_crash_recovery_report(state: State<'_, AppState>) -> Result<Option<RecoveryReport>, String> {
+    let journal = &state.journal;
+    let journal_guard = match journal.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取journal锁失败: {}", e);
+            return Err(format!("获取journal失败: {}", e));
+        }
+    };
+    
+    Ok(journal_guard.last_recovery())
+}
+
+// 获取输入响度监测状态（最近一次会话统计与调整建议）
+#[command]
+async fn get_input_level_status(state: State<'_, AppState>) -> Result<InputLevelStatus, String> {
+    let vad_state_machine = &state.sm;
+    let state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    
+    Ok(state_machine.input_level.status())
+}
+
+// 设置输入响度的参考值与偏低判定比例，传null保持原值
+#[command]
+async fn set_input_level_thresholds(
+    state: State<'_, AppState>,
+    reference_rms: Option<f32>,
+    low_level_ratio: Option<f32>
+) -> Result<InputLevelStatus, String> {
+    if let Some(r) = reference_rms {
+        if !(r > 0.0 && r <= 1.0) {
+            return Err(format!("参考响度必须在(0, 1]范围内: {}", r));
+        }
+    }
+    if let Some(r) = low_level_ratio {
+        if !(r > 0.0 && r < 1.0) {
+            return Err(format!("偏低判定比例必须在(0, 1)范围内: {}", r));
+        }
+    }
+    
+    let vad_state_machine = &state.sm;
+    let mut state_machine = match vad_state_machine.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
+            return Err(format!("获取VAD状态机失败: {}", e));
+        }
+    };
+    
+    if let Some(r) = reference_rms {
+        state_machine.input_level.reference_rms = r;
+    }
+    if let Some(r) = low_level_ratio {
+        state_machine.input_level.low_level_ratio = r;
+    }
+    
+    Ok(state_machine.input_level.status())
+}
+
+// 获取所有参数的生效值及其来源链
+#[command]
+async fn get_effective_parameters(state: State<'_, AppState>) -> Result<Vec<EffectiveParameter>, String> {
+    let layers = &state.params;
+    let layers_guard = match layers.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取参数解析层锁失败: {}", e);
+            return Err(format!("获取参数解析层失败: {}", e));
+        }
+    };
+    
+    Ok(params::resolve_all(&layers_guard))
+}
+
+// 设置用户配置层的参数，value为null时撤销用户配置恢复默认值
+#[command]
+async fn set_user_parameter(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    key: String,
+    value: Option<f64>
+) -> Result<Vec<EffectiveParameter>, String> {
+    let (param_key, scale) = params::lookup(&key).ok_or_else(|| format!("未知的参数: {}", key))?;
+    
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        match value.map(|v| v * scale) {
+            Some(v) => {
+                params::validate_value(param_key, v)?;
+                layers_guard.user.insert(param_key, v);
+            },
+            None => {
+                layers_guard.user.remove(&param_key);
+            },
+        }
+    }
+    
+    refresh_effective_parameters(Some(&app_handle), &state)
+}
+
+// 设置语音结束静音阈值与进入等待中所需的静音时长（毫秒）
+// 写入用户配置层，reset_vad_state 后依然保留；下发时按当前实际帧时长换算为帧数
+#[command]
+async fn set_vad_thresholds(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    speech_end_silence_ms: u64,
+    waiting_ms: u64
+) -> Result<Vec<EffectiveParameter>, String> {
+    params::validate_value(ParamKey::SpeechEndSilenceMs, speech_end_silence_ms as f64)?;
+    params::validate_value(ParamKey::WaitingMs, waiting_ms as f64)?;
+    
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        layers_guard.user.insert(ParamKey::SpeechEndSilenceMs, speech_end_silence_ms as f64);
+        layers_guard.user.insert(ParamKey::WaitingMs, waiting_ms as f64);
+    }
+    
+    info!("[信息] VAD阈值已设置: 语音结束静音 {}ms，等待转移 {}ms", speech_end_silence_ms, waiting_ms);
+    refresh_effective_parameters(Some(&app_handle), &state)
+}
+
+// 按帧数设置进入等待中所需的连续静音帧数（1~100）
+// 阈值越小越灵敏，说话停顿后更快进入等待、停止向后端发送音频；越大越能容忍自然停顿，
+// 语速慢或边想边说的用户不会在句中被切断，代价是进入等待与后续响应更晚。
+// 按当前实际帧长换算为毫秒写入用户配置层（WaitingMs），帧长变化后保持相同的时间语义
+#[command]
+async fn set_silence_threshold_frames(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    frames: usize
+) -> Result<Vec<EffectiveParameter>, String> {
+    if !(MIN_SILENCE_THRESHOLD_FRAMES..=MAX_SILENCE_THRESHOLD_FRAMES).contains(&frames) {
+        return Err(format!(
+            "静音帧数阈值必须在{}~{}之间: {}",
+            MIN_SILENCE_THRESHOLD_FRAMES, MAX_SILENCE_THRESHOLD_FRAMES, frames
+        ));
+    }
+    
+    let frame_ms = match state.vad.lock() {
+        Ok(processor) => processor.frame_duration_ms(),
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    let waiting_ms = params::frames_to_ms(frames, frame_ms);
+    params::validate_value(ParamKey::WaitingMs, waiting_ms)?;
+    
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        layers_guard.user.insert(ParamKey::WaitingMs, waiting_ms);
+    }
+    
+    info!("[信息] 进入等待的静音阈值已设置为: {}帧（{}ms）", frames, waiting_ms);
+    refresh_effective_parameters(Some(&app_handle), &state)
+}
+
+// 设置时序profile（对时长类参数整体乘倍率），传null清除
+#[command]
+async fn set_timing_profile(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    profile: Option<TimingProfile>
+) -> Result<Vec<EffectiveParameter>, String> {
+    if let Some(p) = &profile {
+        if !p.multiplier.is_finite() || p.multiplier <= 0.0 {
+            return Err(format!("profile倍率必须为正数: {}", p.multiplier));
+        }
+    }
+    
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        layers_guard.profile = profile;
+    }
+    
+    refresh_effective_parameters(Some(&app_handle), &state)
+}
+
+// 解析交互模式名称，"none" 表示不使用预设
+fn parse_interaction_mode(name: &str) -> Result<Option<InteractionMode>, String> {
+    if name == "none" {
+        return Ok(None);
+    }
+    InteractionMode::from_name(name)
+        .map(Some)
+        .ok_or_else(|| format!("未知的交互模式: {}，可选值: command, conversation, dictation, none", name))
+}
+
+// 切换交互模式：一键切换语音结束相关阈值的预设，"none" 取消预设
+#[command]
+async fn set_interaction_mode(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    mode: String
+) -> Result<Vec<EffectiveParameter>, String> {
+    let mode = parse_interaction_mode(&mode)?;
+    
+    {
+        let layers = &state.params;
+        let mut layers_guard = layers.lock().map_err(|e| format!("获取参数解析层失败: {}", e))?;
+        layers_guard.mode = mode;
+    }
+    
+    info!("[信息] 交互模式已切换为: {}", mode.map_or("none", InteractionMode::name));
+    refresh_effective_parameters(Some(&app_handle), &state)
+}
+
+// 获取运行时指标（累计值与最近窗口值）
+#[command]
+async fn get_metrics(state: State<'_, AppState>) -> Result<MetricsReport, String> {
+    let metrics = &state.metrics;
+    let metrics_guard = match metrics.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取运行时指标锁失败: {}", e);
+            return Err(format!("获取运行时指标失败: {}", e));
+        }
+    };
+    
+    Ok(metrics_guard.report())
+}
+
+// 设置发送到后端的样本格式: "pcm16" 或 "f32"
+#[command]
+async fn set_sample_format(state: State<'_, AppState>, format: String) -> Result<String, String> {
+    let sample_format = match format.as_str() {
+        "pcm16" => SampleFormat::Pcm16,
+        "f32" => SampleFormat::F32,
+        _ => return Err(format!("未知的样本格式: {}，可选值: pcm16, f32", format)),
+    };
+    
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    socket_manager_guard.set_sample_format(sample_format)?;
+    info!("[信息] 发送样本格式已设置为: {:?}", sample_format);
+    Ok(format!("发送样本格式已设置为: {}", format))
+}
+
+// 设置发送到后端的音频编码: "pcm"（缺省，按样本格式发送原始样本）或 "opus"（需要codec-opus feature）；
+// 设置成功后写入配置
+#[command]
+async fn set_audio_codec(app_handle: tauri::AppHandle, state: State<'_, AppState>, codec: String) -> Result<String, String> {
+    let audio_codec = AudioCodec::from_name(&codec)
+        .ok_or_else(|| format!("未知的音频编码: {}，可选值: pcm, opus", codec))?;
+    if audio_codec == AudioCodec::Opus {
+        features::require(Feature::CodecOpus)?;
+    }
+    
+    {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        socket_manager_guard.set_codec(audio_codec)?;
+    }
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.audio_codec = Some(audio_codec);
+    config::save_to_disk(&path, &stored)?;
+    
+    info!("[信息] 发送音频编码已设置为: {}", audio_codec.name());
+    Ok(format!("发送音频编码已设置为: {}", audio_codec.name()))
+}
+
+// 设置前置上下文的淡入时长（毫秒），0表示关闭淡入
+#[command]
+async fn set_pre_context_fade(state: State<'_, AppState>, fade_ms: u64) -> Result<String, String> {
+    let frame_duration_ms = match state.vad.lock() {
+        Ok(processor) => processor.frame_duration_ms(),
+        Err(e) => {
+            error!("[错误] 获取VAD处理器锁失败: {}", e);
+            return Err(format!("获取VAD处理器失败: {}", e));
+        }
+    };
+    
+    let socket_manager = &state.socket;
+    let mut socket_manager_guard = match socket_manager.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取SocketManager锁失败: {}", e);
+            return Err(format!("获取SocketManager失败: {}", e));
+        }
+    };
+    
+    // 按当前实际帧时长计算前置上下文总时长
+    let pre_context_ms = params::frames_to_ms(socket_manager_guard.max_pre_context_frames, frame_duration_ms).round() as u64;
+    if fade_ms > pre_context_ms {
+        return Err(format!("淡入时长不能超过前置上下文时长({}ms): {}", pre_context_ms, fade_ms));
+    }
+    
+    socket_manager_guard.pre_context_fade_ms = fade_ms;
+    info!("[信息] 前置上下文淡入时长已设置为: {}ms", fade_ms);
+    Ok(format!("前置上下文淡入时长已设置为: {}ms", fade_ms))
+}
+
+// 修改后端端点（Unix下为socket路径，Windows下为TCP地址），缺省的字段保持不变；
+// 修改会写入配置并导出到环境变量，当前连接断开后按新端点重连
+#[command]
+async fn set_backend_endpoints(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    stt: Option<String>,
+    stt_result: Option<String>,
+    tts: Option<String>,
+    legacy_fallback: Option<bool>,
+) -> Result<ConnectionStatus, String> {
+    let status = {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        
+        let current = socket_manager_guard.endpoints.clone();
+        let endpoints = BackendEndpoints {
+            stt: stt.unwrap_or(current.stt),
+            stt_result: stt_result.unwrap_or(current.stt_result),
+            tts: tts.unwrap_or(current.tts),
+        };
+        if endpoints.stt.is_empty() || endpoints.stt_result.is_empty() || endpoints.tts.is_empty() {
+            return Err("端点不能为空".to_string());
+        }
+        let legacy_fallback = legacy_fallback.unwrap_or(socket_manager_guard.legacy_fallback);
+        socket_manager_guard.set_endpoints(endpoints, legacy_fallback);
+        socket_manager_guard.connection_status()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.backend_endpoints = Some(status.endpoints.clone());
+    stored.legacy_socket_fallback = Some(status.legacy_fallback);
+    config::save_to_disk(&path, &stored)?;
+    publish_vad_state(&state);
+    
+    info!("[信息] 后端端点已设置为: {:?}", status.endpoints);
+    Ok(status)
+}
+
+// 逐个探测后端端点能否连上，连不上时给出可能原因（后端未启动、端口被占用、被防火墙拦截、地址解析失败等）。
+// 探测会建立一次立即关闭的连接，不影响当前连接
+#[command]
+async fn diagnose(state: State<'_, AppState>) -> Result<Vec<EndpointDiagnosis>, String> {
+    let endpoints = {
+        let socket_manager = &state.socket;
+        let socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        socket_manager_guard.endpoints.clone()
+    };
+    
+    let report: Vec<EndpointDiagnosis> = endpoints::ALL_ENDPOINT_KINDS
+        .iter()
+        .map(|&kind| endpoints::diagnose(&endpoints, kind))
+        .collect();
+    for diagnosis in &report {
+        info!("[信息] 端点诊断 {} ({}): {}", diagnosis.channel, diagnosis.endpoint, diagnosis.detail);
+    }
+    Ok(report)
+}
+
+// 切换与后端通信的传输方式（仅Windows）：pipe 为命名管道（默认），tcp 为本机TCP端口 8765~8767。
+// 三条通道都换成该方式的默认端点，写入配置并导出到环境变量，当前连接断开后按新端点重连
+#[command]
+async fn set_transport(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    kind: String,
+) -> Result<ConnectionStatus, String> {
+    let transport = Transport::from_name(&kind)
+        .ok_or_else(|| format!("未知的传输方式: {}，可选值: pipe, tcp", kind))?;
+    let endpoints = BackendEndpoints::for_transport(transport)?;
+    
+    let status = {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = match socket_manager.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取SocketManager锁失败: {}", e);
+                return Err(format!("获取SocketManager失败: {}", e));
+            }
+        };
+        let legacy_fallback = socket_manager_guard.legacy_fallback;
+        socket_manager_guard.set_endpoints(endpoints, legacy_fallback);
+        socket_manager_guard.connection_status()
+    };
+    
+    let path = config::config_path(&app_handle)?;
+    let mut stored = config::load_from_disk(&path)?;
+    stored.backend_endpoints = Some(status.endpoints.clone());
+    config::save_to_disk(&path, &stored)?;
+    publish_vad_state(&state);
+    
+    info!("[信息] 传输方式已切换为: {:?}，端点: {:?}", transport, status.endpoints);
+    Ok(status)
 }
 
+// 获取与后端的连接状态及实际使用的端点
 #[command]
-async fn get_speech_segments() -> Result<Vec<AudioSegment>, String> {
-    println!("[调试] 获取发送到Python的语音段用于回放");
-    
-    let socket_manager = get_socket_manager();
+async fn get_connection_status(state: State<'_, AppState>) -> Result<ConnectionStatus, String> {
+    let socket_manager = &state.socket;
     let socket_manager_guard = match socket_manager.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
+            error!("[错误] 获取SocketManager锁失败: {}", e);
             return Err(format!("获取SocketManager失败: {}", e));
         }
     };
     
-    // 获取所有发送到Python的语音段
-    let segments = socket_manager_guard.get_sent_to_python_segments();
+    Ok(socket_manager_guard.connection_status())
+}
+
+fn is_ephemeral(state: &AppState) -> bool {
+    match state.privacy.lock() {
+        Ok(privacy) => privacy.is_ephemeral(),
+        Err(e) => {
+            error!("[错误] 获取隐私模式锁失败: {}", e);
+            // 状态不可读时按开启处理，宁可少写
+            true
+        }
+    }
+}
+
+fn ensure_persistence_allowed(state: &AppState, what: &str) -> Result<(), String> {
+    let privacy = state.privacy.lock().map_err(|e| format!("获取隐私模式失败: {}", e))?;
+    privacy.ensure_persistence_allowed(what)
+}
+
+// 清除内存中本次会话的音频与文字：语音段、对话历史与轮次、未确认的中间结果。
+// 不留痕模式下会话结束时调用，关闭该模式时也调用一次，避免模式期间的内容在恢复写盘后被写入checkpoint
+fn purge_session_content(state: &AppState) {
+    if let Ok(mut socket_manager) = state.socket.lock() {
+        socket_manager.complete_speech_segments.clear();
+        socket_manager.current_voice_segment.clear();
+        socket_manager.current_voice_segment_start = None;
+        socket_manager.clear_sent_to_python_segments();
+    }
+    if let Ok(mut history) = state.history.lock() {
+        history.clear();
+    }
+    if let Ok(mut tracker) = state.turns.lock() {
+        tracker.clear();
+    }
+    if let Ok(mut merger) = state.partials.lock() {
+        merger.reset();
+    }
+    info!("[信息] 已清除本次会话的音频与文字");
+}
+
+fn set_suspended(module: &mut dyn Suspendable, suspended: bool) {
+    if suspended {
+        module.suspend();
+    } else {
+        module.resume();
+    }
+}
+
+// 开关不留痕模式：开启后段导出、会话报告导出、崩溃journal中的音频与文字、协议记录全部挂起，
+// 会话结束即清除内存中的内容；关闭后恢复原有行为。模式不写入配置，切换记入审计日志（只含时间与开关值）
+#[command]
+async fn set_ephemeral_mode(app_handle: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<PrivacyStatus, String> {
+    let (changed, status) = {
+        let privacy = &state.privacy;
+        let mut privacy_guard = match privacy.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取隐私模式锁失败: {}", e);
+                return Err(format!("获取隐私模式失败: {}", e));
+            }
+        };
+        (privacy_guard.set_ephemeral(enabled), privacy_guard.status())
+    };
+    if !changed {
+        return Ok(status);
+    }
+    
+    // 各持久化模块只挂起写盘，各自的开关与配置保持不变
+    {
+        let journal = &state.journal;
+        let mut journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+        set_suspended(&mut *journal_guard, enabled);
+    }
+    {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        set_suspended(&mut socket_manager_guard.trace, enabled);
+        if let Some(queue) = socket_manager_guard.send_queue.as_mut() {
+            set_suspended(queue, enabled);
+        }
+        if let Some(recorder) = socket_manager_guard.wav_stream.as_mut() {
+            set_suspended(recorder, enabled);
+        }
+    }
+    if !enabled {
+        purge_session_content(&state);
+    }
     
-    println!("[重要] 获取到{}个发送到Python的语音段", segments.len());
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("获取应用数据目录失败: {}", e))?;
+    if let Err(e) = privacy::append_audit(&data_dir, enabled) {
+        error!("[错误] 写入隐私模式审计日志失败: {}", e);
+    }
     
-    if segments.is_empty() {
-        println!("[调试] 没有可用的语音段");
-        return Ok(Vec::new());
+    info!("[信息] 不留痕模式已{}", if enabled { "开启" } else { "关闭" });
+    Ok(status)
+}
+
+// 删除一轮对话中用户的转写（id 为轮次 id）：对话历史与崩溃journal中的文本一并删除，轮次保留并标记为已删除。
+// cascade 为 true 时沿轮次记录的语音段序号继续删除关联的语音段、发送段与波形缩略图缓存。
+// 轮次登记到诊断排除列表；删除后立即重写磁盘上的 checkpoint，操作记入审计日志（不含内容）并通知前端 data-deleted
+#[command]
+async fn delete_transcript(app_handle: tauri::AppHandle, state: State<'_, AppState>, id: u64, cascade: bool) -> Result<DeletionReport, String> {
+    let report = delete_transcript_data(&state, id, cascade)?;
+    
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("获取应用数据目录失败: {}", e))?;
+    if let Err(e) = privacy::append_deletion_audit(&data_dir, &report) {
+        error!("[错误] 写入删除审计日志失败: {}", e);
     }
     
-    // 转换为带有采样率的音频段
-    let audio_segments: Vec<AudioSegment> = segments
-        .into_iter()
-        .map(|samples| {
-            // println!("[重要] 语音段: 长度={}个样本", samples.len());
-            AudioSegment {
-                samples,
-                sample_rate: SAMPLE_RATE,
+    info!("[信息] 已删除轮次{}的转写: {:?}", id, report);
+    if let Err(e) = app_handle.emit("data-deleted", &report) {
+        error!("[错误] 发送数据删除事件到前端失败: {}", e);
+    }
+    Ok(report)
+}
+
+// 从各存储位置删除转写（及 cascade 时关联的音频），并立即重写磁盘上的checkpoint
+fn delete_transcript_data(state: &AppState, id: u64, cascade: bool) -> Result<DeletionReport, String> {
+    let (transcript, segment_ids) = {
+        let tracker = &state.turns;
+        let mut tracker_guard = match tracker.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("[错误] 获取对话轮次锁失败: {}", e);
+                return Err(format!("获取对话轮次失败: {}", e));
             }
-        })
-        .collect();
+        };
+        tracker_guard
+            .delete_transcript(id, cascade)
+            .ok_or_else(|| format!("对话轮次不存在: {}", id))?
+    };
     
-    println!("[调试] 返回{}个音频段用于回放", audio_segments.len());
-    Ok(audio_segments)
+    let mut report = DeletionReport {
+        transcript_id: id,
+        cascade,
+        history_entries: 0,
+        journal_transcripts: 0,
+        voice_segments: 0,
+        sent_segments: 0,
+        thumbnails_invalidated: false,
+        checkpoint_rewritten: false,
+        diagnostics_excluded: false,
+    };
+    
+    if transcript.is_some() {
+        {
+            let history = &state.history;
+            let mut history_guard = history.lock().map_err(|e| format!("获取对话历史失败: {}", e))?;
+            report.history_entries = history_guard.remove_user_turn(id);
+        }
+        {
+            let journal = &state.journal;
+            let mut journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+            report.journal_transcripts = journal_guard.remove_transcripts(id);
+        }
+    }
+    
+    // 登记到诊断排除列表，之后导出的诊断数据不再包含这一轮
+    {
+        let privacy = &state.privacy;
+        let mut privacy_guard = privacy.lock().map_err(|e| format!("获取隐私模式失败: {}", e))?;
+        privacy_guard.exclude_from_diagnostics(id);
+        report.diagnostics_excluded = true;
+    }
+    
+    if !segment_ids.is_empty() {
+        let socket_manager = &state.socket;
+        let mut socket_manager_guard = socket_manager.lock().map_err(|e| format!("获取SocketManager失败: {}", e))?;
+        let (voice_segments, sent_segments) = socket_manager_guard.remove_voice_segments(&segment_ids);
+        report.voice_segments = voice_segments;
+        report.sent_segments = sent_segments;
+        report.thumbnails_invalidated = sent_segments > 0;
+    }
+    
+    // 不等下一次周期写入，立即用删除后的内容覆盖磁盘上的checkpoint
+    match collect_checkpoint(state).and_then(|checkpoint| {
+        let journal = &state.journal;
+        let journal_guard = journal.lock().map_err(|e| format!("获取journal失败: {}", e))?;
+        journal_guard.write_checkpoint(&checkpoint)
+    }) {
+        Ok(()) => report.checkpoint_rewritten = true,
+        Err(e) => error!("[错误] 删除转写后重写checkpoint失败: {}", e),
+    }
+    Ok(report)
 }
 
+// 开关协议级收发记录；仅用于调试，不写入配置，重启后恢复关闭
 #[command]
-async fn clear_speech_segments() -> Result<(), String> {
-    println!("[调试] 清空存储的语音段");
-    
-    let socket_manager = get_socket_manager();
+async fn set_protocol_trace(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let socket_manager = &state.socket;
     let mut socket_manager_guard = match socket_manager.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
+            error!("[错误] 获取SocketManager锁失败: {}", e);
             return Err(format!("获取SocketManager失败: {}", e));
         }
     };
-    
-    socket_manager_guard.clear_sent_to_python_segments();
-    println!("[调试] 发送到Python的语音段已清空");
-    
+
+    socket_manager_guard.trace.set_enabled(enabled);
+    info!("[信息] 协议级收发记录已{}", if enabled { "开启" } else { "关闭" });
     Ok(())
 }
 
+// 命令调用审计：最近 last_n 条调用记录（缺省为全部）、超时阈值与各命令超时次数
 #[command]
-async fn create_test_speech_segment() -> Result<(), String> {
-    println!("[重要] 手动创建测试语音段");
-    
-    // 获取SocketManager实例
-    let socket_manager = get_socket_manager();
-    let mut socket_manager_guard = match socket_manager.lock() {
+async fn get_command_audit(state: State<'_, AppState>, last_n: Option<usize>) -> Result<CommandAuditReport, String> {
+    audit::report(&state.audit, last_n)
+}
+
+// 设置命令耗时告警阈值（毫秒）；仅用于调试，不写入配置
+#[command]
+async fn set_command_audit_slow_threshold(state: State<'_, AppState>, threshold_ms: u64) -> Result<(), String> {
+    audit::set_slow_threshold_ms(&state.audit, threshold_ms)?;
+    info!("[信息] 命令耗时告警阈值已设置为: {}ms", threshold_ms);
+    Ok(())
+}
+
+// 设置命令的审计采样间隔：每 every 次调用记录一次，1为每次都记录；用于 process_audio_frame 等热路径命令
+#[command]
+async fn set_command_audit_sampling(state: State<'_, AppState>, command: String, every: u32) -> Result<(), String> {
+    audit::set_sampling(&state.audit, &command, every)?;
+    info!("[信息] 命令 {} 的审计采样间隔已设置为: 每{}次", command, every);
+    Ok(())
+}
+
+// 导出协议级收发记录，按全局序号从旧到新排列
+#[command]
+async fn get_protocol_trace(state: State<'_, AppState>) -> Result<ProtocolTraceReport, String> {
+    let socket_manager = &state.socket;
+    let socket_manager_guard = match socket_manager.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
+            error!("[错误] 获取SocketManager锁失败: {}", e);
             return Err(format!("获取SocketManager失败: {}", e));
         }
     };
-    
-    // 创建一个小的测试音频段 - 1秒的正弦波
-    let mut test_samples = Vec::with_capacity(16000);
-    for i in 0..16000 {
-        let t = i as f32 / 16000.0;
-        let sample = (t * 440.0 * 2.0 * std::f32::consts::PI).sin() * 10000.0;
-        test_samples.push(sample as i16);
-    }
-    
-    // 保存测试音频段到发送到Python的语音段
-    socket_manager_guard.sent_to_python_segments.push(test_samples);
-    println!("[重要] 测试语音段已创建，当前共有{}个发送到Python的语音段", 
-             socket_manager_guard.sent_to_python_segments.len());
-    
-    Ok(())
+
+    Ok(socket_manager_guard.trace.report())
 }
 
-// 重置VAD处理器状态
+// 获取TTS播放队列状态
 #[command]
-fn reset_vad_state() -> Result<String, String> {
-    println!("[信息] 重置VAD状态");
-    
-    // 获取VAD处理器并重置
-    let vad_processor = get_vad_processor();
-    let result = match vad_processor.lock() {
-        Ok(mut processor) => {
-            // 创建一个全新的处理器实例
-            *processor = VadProcessor::new();
-            println!("[信息] VAD状态已重置");
-            Ok("VAD状态已重置".to_string())
-        },
+async fn get_tts_playback_status(state: State<'_, AppState>) -> Result<TtsPlaybackStatus, String> {
+    let queue = &state.tts_queue;
+    let queue_guard = match queue.lock() {
+        Ok(guard) => guard,
         Err(e) => {
-            let error_msg = format!("获取VAD处理器锁失败: {}", e);
-            println!("[错误] {}", error_msg);
-            Err(error_msg)
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 同时重置状态机
-    let vad_state_machine = get_vad_state_machine();
-    if let Ok(mut state_machine) = vad_state_machine.lock() {
-        state_machine.reset_to_initial();
-        println!("[信息] VAD状态机已重置到初始状态");
-    }
-    
-    result
+    Ok(queue_guard.status())
 }
 
-// 停止VAD处理
+// 设置TTS打断策略: "current_only" 只打断当前条, "clear_queue" 清空整个队列
 #[command]
-fn stop_vad_processing() -> Result<String, String> {
-    println!("[信息] 停止VAD处理");
+async fn set_tts_interrupt_policy(state: State<'_, AppState>, policy: String) -> Result<String, String> {
+    let interrupt_policy = match InterruptPolicy::from_name(&policy) {
+        Some(p) => p,
+        None => return Err(format!("未知的打断策略: {}，可选值: current_only, clear_queue", policy)),
+    };
     
-    // 获取VAD处理器
-    let vad_processor = get_vad_processor();
-    let result = match vad_processor.lock() {
-        Ok(mut processor) => {
-            // 手动触发语音结束事件
-            if processor.is_speaking {
-                processor.is_speaking = false;
-                processor.silence_frames = 30; // 设置足够的静音帧以确保语音结束
-                println!("[信息] 手动触发语音结束事件");
-            }
-            
-            // 获取SocketManager
-            let socket_manager = get_socket_manager();
-            let mut socket_manager_guard = match socket_manager.lock() {
-                Ok(guard) => guard,
-                Err(e) => {
-                    let error_msg = format!("获取Socket管理器锁失败: {}", e);
-                    println!("[错误] {}", error_msg);
-                    return Err(error_msg);
-                }
-            };
-            
-            // 停止缓冲并处理最后的数据，但不要清除已保存的发送到Python的语音段
-            socket_manager_guard.stop_buffering();
-            
-            // 保存发送到Python的语音段数量
-            let sent_segments_count = socket_manager_guard.sent_to_python_segments.len();
-            println!("[信息] 当前已保存{}个发送到Python的语音段", sent_segments_count);
-            
-            println!("[信息] VAD处理已停止");
-            Ok(format!("VAD处理已停止，有{}个语音段可供播放", sent_segments_count))
-        },
+    let queue = &state.tts_queue;
+    let mut queue_guard = match queue.lock() {
+        Ok(guard) => guard,
         Err(e) => {
-            let error_msg = format!("获取VAD处理器锁失败: {}", e);
-            println!("[错误] {}", error_msg);
-            Err(error_msg)
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 同时重置状态机
-    let vad_state_machine = get_vad_state_machine();
-    if let Ok(mut state_machine) = vad_state_machine.lock() {
-        state_machine.reset_to_initial();
-        println!("[信息] VAD状态机已重置到初始状态");
-    }
-    
-    result
+    queue_guard.set_interrupt_policy(interrupt_policy);
+    info!("[信息] TTS打断策略已设置为: {:?}", interrupt_policy);
+    Ok(format!("TTS打断策略已设置为: {}", policy))
 }
 
-// 添加新命令获取合并后的语音段
+// 获取TTS块间间隙填充的配置和统计
 #[command]
-async fn get_combined_speech_segment() -> Result<AudioSegment, String> {
-    println!("[调试] 获取合并后的语音识别段");
-    
-    let socket_manager = get_socket_manager();
-    let socket_manager_guard = match socket_manager.lock() {
+async fn get_tts_gap_fill_status(state: State<'_, AppState>) -> Result<GapFillStatus, String> {
+    let queue = &state.tts_queue;
+    let queue_guard = match queue.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
-            return Err(format!("获取SocketManager失败: {}", e));
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 获取合并后的语音段
-    let combined = socket_manager_guard.get_combined_speech_segment();
-    
-    if combined.is_empty() {
-        println!("[调试] 没有可用的语音识别段可合并");
-        return Err("没有可用的语音识别段可合并".into());
-    }
-    
-    println!("[重要] 合并后的语音识别段长度: {}个样本", combined.len());
-    
-    // 创建AudioSegment
-    let audio_segment = AudioSegment {
-        samples: combined,
-        sample_rate: SAMPLE_RATE,
-    };
-    
-    Ok(audio_segment)
+    Ok(queue_guard.gap_filler.status())
 }
 
-// 新增：前端重置事件处理命令
+// 设置TTS块间间隙填充: strategy 为 "off" / "silence" / "hold_tail"，阈值缺省时保持当前值
 #[command]
-async fn reset_vad_session() -> Result<String, String> {
-    //println!("[状态机] 收到前端重置事件，执行后端结束session");
-    
-    // 获取VAD状态机
-    let vad_state_machine = get_vad_state_machine();
-    let mut state_machine = match vad_state_machine.lock() {
-        Ok(guard) => guard,
-        Err(e) => {
-            println!("[错误] 获取VAD状态机锁失败: {}", e);
-            return Err(format!("获取VAD状态机失败: {}", e));
-        }
+async fn set_tts_gap_fill(state: State<'_, AppState>, strategy: String, min_gap_ms: Option<u64>, max_fill_ms: Option<u64>) -> Result<String, String> {
+    let strategy = match GapFillStrategy::from_name(&strategy) {
+        Some(s) => s,
+        None => return Err(format!("未知的填充策略: {}，可选值: off, silence, hold_tail", strategy)),
     };
     
-    // 获取SocketManager
-    let socket_manager = get_socket_manager();
-    let mut socket_manager_guard = match socket_manager.lock() {
+    let queue = &state.tts_queue;
+    let mut queue_guard = match queue.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
-            return Err(format!("获取SocketManager失败: {}", e));
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 发送后端结束session事件到状态机
-    let _should_send_to_python = state_machine.process_event(
-        VadStateMachineEvent::BackendEndSession, 
-        &mut socket_manager_guard
-    );
+    let current = queue_guard.gap_filler.status().settings;
+    let settings = GapFillSettings {
+        strategy,
+        min_gap_ms: min_gap_ms.unwrap_or(current.min_gap_ms),
+        max_fill_ms: max_fill_ms.unwrap_or(current.max_fill_ms),
+    };
+    if settings.min_gap_ms > settings.max_fill_ms {
+        return Err(format!("min_gap_ms({}) 不能大于 max_fill_ms({})", settings.min_gap_ms, settings.max_fill_ms));
+    }
     
-    //println!("[状态机] 前端重置事件处理完成，状态机已重置到初始状态");
-    Ok("VAD session已重置".to_string())
+    queue_guard.gap_filler.set_settings(settings);
+    info!("[信息] TTS间隙填充已设置为: {:?}", settings);
+    Ok(format!("TTS间隙填充已设置为: {:?}", settings))
 }
 
-// 新增：处理后端控制消息的命令
+// 获取TTS接收速率保护的配置和统计
 #[command]
-async fn handle_backend_control(action: String, data: String) -> Result<String, String> {
-    //println!("[状态机] 收到后端控制消息: action={}, data={}", action, data);
-    
-    // 获取VAD状态机
-    let vad_state_machine = get_vad_state_machine();
-    let mut state_machine = match vad_state_machine.lock() {
+async fn get_tts_flow_control(state: State<'_, AppState>) -> Result<FlowStatus, String> {
+    let queue = &state.tts_queue;
+    let queue_guard = match queue.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取VAD状态机锁失败: {}", e);
-            return Err(format!("获取VAD状态机失败: {}", e));
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 获取SocketManager
-    let socket_manager = get_socket_manager();
-    let mut socket_manager_guard = match socket_manager.lock() {
+    Ok(queue_guard.flow.status())
+}
+
+// 设置TTS接收速率保护: 接收速度上限（实时速率的倍数）与排队音频上限，缺省时保持当前值
+#[command]
+async fn set_tts_flow_control(state: State<'_, AppState>, max_speedup: Option<f64>, max_buffered_ms: Option<u64>) -> Result<FlowStatus, String> {
+    let queue = &state.tts_queue;
+    let mut queue_guard = match queue.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
-            return Err(format!("获取SocketManager失败: {}", e));
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 根据控制消息类型处理
-    let event = match action.as_str() {
-        "reset_to_initial" => {
-            //println!("[状态机] 执行后端请求的重置到初始状态");
-            VadStateMachineEvent::BackendResetToInitial
-        },
-        "end_session" => {
-            //println!("[状态机] 执行后端请求的结束session");
-            VadStateMachineEvent::BackendEndSession
-        },
-        "interrupt" => {
-            println!("[状态机] 执行用户打断操作");
-            // 如果在播放音频状态，先发送AudioPlaybackEnd事件
-            if *state_machine.get_current_state() == VadState::Listening {
-                state_machine.process_event(VadStateMachineEvent::AudioPlaybackEnd, &mut socket_manager_guard);
-            }
-            // 然后重置到初始状态
-            VadStateMachineEvent::BackendResetToInitial
-        },
-        _ => {
-            println!("[警告] 未知的后端控制动作: {}", action);
-            return Err(format!("未知的控制动作: {}", action));
-        }
+    let current = queue_guard.flow.settings();
+    let settings = FlowSettings {
+        max_speedup: max_speedup.unwrap_or(current.max_speedup),
+        max_buffered_ms: max_buffered_ms.unwrap_or(current.max_buffered_ms),
     };
+    settings.validate()?;
     
-    // 发送事件到状态机
-    let _should_send_to_python = state_machine.process_event(event, &mut socket_manager_guard);
-    
-    //println!("[状态机] 后端控制消息处理完成");
-    Ok(format!("后端控制消息 '{}' 处理完成", action))
+    queue_guard.flow.set_settings(settings);
+    info!("[信息] TTS接收速率保护已设置为: {:?}", settings);
+    Ok(queue_guard.flow.status())
 }
 
-// 新增：音频播放开始事件处理
+// 获取TTS中断流检测的设置与统计
 #[command]
-async fn audio_playback_started() -> Result<String, String> {
-    //println!("[状态机] 收到音频播放开始事件");
-    
-    // 获取VAD状态机
-    let vad_state_machine = get_vad_state_machine();
-    let mut state_machine = match vad_state_machine.lock() {
+async fn get_tts_stall_detection(state: State<'_, AppState>) -> Result<StallStatus, String> {
+    let queue = &state.tts_queue;
+    let queue_guard = match queue.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取VAD状态机锁失败: {}", e);
-            return Err(format!("获取VAD状态机失败: {}", e));
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 获取SocketManager
-    let socket_manager = get_socket_manager();
-    let mut socket_manager_guard = match socket_manager.lock() {
+    Ok(queue_guard.stall.status())
+}
+
+// 设置TTS中断流检测: 多久没有新音频提示中断流，中断流持续多久视为播放结束，缺省时保持当前值
+#[command]
+async fn set_tts_stall_detection(state: State<'_, AppState>, stall_ms: Option<u64>, give_up_ms: Option<u64>) -> Result<StallStatus, String> {
+    let queue = &state.tts_queue;
+    let mut queue_guard = match queue.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
-            return Err(format!("获取SocketManager失败: {}", e));
+            error!("[错误] 获取TTS播放队列锁失败: {}", e);
+            return Err(format!("获取TTS播放队列失败: {}", e));
         }
     };
     
-    // 发送音频播放开始事件到状态机
-    let _should_send_to_python = state_machine.process_event(
-        VadStateMachineEvent::AudioPlaybackStart, 
-        &mut socket_manager_guard
-    );
+    let current = queue_guard.stall.settings();
+    let settings = StallSettings {
+        stall_ms: stall_ms.unwrap_or(current.stall_ms),
+        give_up_ms: give_up_ms.unwrap_or(current.give_up_ms),
+    };
+    settings.validate()?;
     
-    //println!("[状态机] 音频播放开始事件处理完成");
-    Ok("音频播放开始".to_string())
+    queue_guard.stall.set_settings(settings);
+    info!("[信息] TTS中断流检测已设置为: {:?}", settings);
+    Ok(queue_guard.stall.status())
 }
 
-// 新增：音频播放结束事件处理
+// 导出状态机的状态图，format 为 "dot" 或 "mermaid"，由转移表生成
 #[command]
-async fn audio_playback_ended() -> Result<String, String> {
-    //println!("[状态机] 收到音频播放结束事件");
-    
-    // 获取VAD状态机
-    let vad_state_machine = get_vad_state_machine();
-    let mut state_machine = match vad_state_machine.lock() {
+async fn export_state_diagram(format: String) -> Result<String, String> {
+    state_diagram::export(&format)
+}
+
+// 对用户可见的VAD状态与麦克风静音状态
+#[derive(Serialize, Clone, Debug)]
+struct VadStateView {
+    state: String, // Initial / Speaking / Waiting / Listening，临界态时为上一个可见状态
+    muted: bool,   // 麦克风是否静音
+}
+
+fn vad_state_view(state: &AppState) -> Result<VadStateView, String> {
+    let state_machine = match state.sm.lock() {
         Ok(guard) => guard,
         Err(e) => {
-            println!("[错误] 获取VAD状态机锁失败: {}", e);
+            error!("[错误] 获取VAD状态机锁失败: {}", e);
             return Err(format!("获取VAD状态机失败: {}", e));
         }
     };
+    Ok(VadStateView {
+        state: format!("{:?}", state_machine.visible_state()),
+        muted: state_machine.muted,
+    })
+}
+
+// 新增：获取当前状态机状态
+#[command]
+async fn get_vad_state(state: State<'_, AppState>) -> Result<VadStateView, String> {
+    vad_state_view(&state)
+}
+
+// 诊断信息：应用信息、VAD状态快照（含麦克风静音）、连接状态与运行时指标，一次取回，便于排查问题。
+// 不含任何转写文本与音频
+#[derive(Serialize, Clone, Debug)]
+struct Diagnostics {
+    app: AppInfo,
+    vad_state: VadStateSnapshot,
+    connection: ConnectionStatus,
+    metrics: MetricsReport,
+}
+
+fn collect_diagnostics(state: &AppState) -> Result<Diagnostics, String> {
+    publish_vad_state(state);
+    let vad_state = state.vad_state_stream.borrow().clone();
+    let connection = state.socket.lock()
+        .map_err(|e| format!("获取SocketManager失败: {}", e))?
+        .connection_status();
+    let metrics = state.metrics.lock()
+        .map_err(|e| format!("获取运行时指标失败: {}", e))?
+        .report();
+    Ok(Diagnostics {
+        app: app_info(state),
+        vad_state,
+        connection,
+        metrics,
+    })
+}
+
+#[command]
+async fn get_diagnostics(state: State<'_, AppState>) -> Result<Diagnostics, String> {
+    collect_diagnostics(&state)
+}
+
+// 显示器布局与本窗口内容区的位置、缩放比，供前端按 CSS 像素绘制框选截图的遮罩
+#[derive(Serialize, Clone, Debug)]
+struct DisplayLayout {
+    displays: Vec<DisplayInfo>,
+    window: WindowGeometry,
+}
+
+fn display_layout(window: &tauri::Window) -> Result<DisplayLayout, String> {
+    let primary_position = window
+        .primary_monitor()
+        .map_err(|e| format!("获取主显示器失败: {}", e))?
+        .map(|monitor| *monitor.position());
+    let monitors = window.available_monitors().map_err(|e| format!("获取显示器列表失败: {}", e))?;
+    let displays = monitors
+        .iter()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let position = monitor.position();
+            let size = monitor.size();
+            let physical = PhysicalRect {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            };
+            let primary = primary_position.is_some_and(|primary| primary.x == position.x && primary.y == position.y);
+            DisplayInfo::new(index, monitor.name().cloned(), physical, monitor.scale_factor(), primary)
+        })
+        .collect();
     
-    // 获取SocketManager
-    let socket_manager = get_socket_manager();
-    let mut socket_manager_guard = match socket_manager.lock() {
-        Ok(guard) => guard,
-        Err(e) => {
-            println!("[错误] 获取SocketManager锁失败: {}", e);
-            return Err(format!("获取SocketManager失败: {}", e));
-        }
-    };
-    
-    // 发送音频播放结束事件到状态机
-    let _should_send_to_python = state_machine.process_event(
-        VadStateMachineEvent::AudioPlaybackEnd, 
-        &mut socket_manager_guard
-    );
-    
-    //println!("[状态机] 音频播放结束事件处理完成");
-    Ok("音频播放结束".to_string())
+    let inner = window.inner_position().map_err(|e| format!("获取窗口位置失败: {}", e))?;
+    let scale_factor = window.scale_factor().map_err(|e| format!("获取窗口缩放比失败: {}", e))?;
+    Ok(DisplayLayout {
+        displays,
+        window: WindowGeometry {
+            inner_x: inner.x,
+            inner_y: inner.y,
+            scale_factor,
+        },
+    })
 }
 
-// 新增：获取当前状态机状态
+// 获取显示器布局：各显示器的物理/逻辑边界与缩放比，以及本窗口内容区的物理位置与缩放比
 #[command]
-async fn get_vad_state() -> Result<String, String> {
-    let vad_state_machine = get_vad_state_machine();
-    let state_machine = match vad_state_machine.lock() {
-        Ok(guard) => guard,
-        Err(e) => {
-            println!("[错误] 获取VAD状态机锁失败: {}", e);
-            return Err(format!("获取VAD状态机失败: {}", e));
-        }
+async fn get_display_layout(window: tauri::Window) -> Result<DisplayLayout, String> {
+    display_layout(&window)
+}
+
+// 一次选区截图的结果
+#[derive(Serialize, Clone, Debug)]
+struct RegionCapture {
+    path: String,
+    region: ResolvedRegion, // 实际截取的区域，跨显示器的选区已裁剪到单台显示器
+    image_width: u32,
+    image_height: u32,
+}
+
+// 按选区截图：coordinate_space 为 "css_pixels"（缺省，前端窗口内的坐标）或 "physical_pixels"（虚拟桌面设备像素）。
+// 选区换算到物理像素后裁剪到重叠最多的显示器，截取该显示器并裁出选区，保存为PNG（与截图插件同一目录），返回路径与实际区域
+#[command]
+async fn capture_region(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    region: Region,
+    coordinate_space: Option<String>,
+) -> Result<RegionCapture, String> {
+    let space = match coordinate_space.as_deref() {
+        None => CoordinateSpace::CssPixels,
+        Some(name) => CoordinateSpace::from_name(name)
+            .ok_or_else(|| format!("未知的坐标系: {}，可选值: css_pixels, physical_pixels", name))?,
     };
     
-    // 检查当前状态是否为临界态，如果是则返回上一个可见状态
-    let state = match state_machine.get_current_state() {
-        // 如果是临界态，返回上一个可见状态
-        s @ VadState::TransitionBuffer => &state_machine.last_user_visible_state,
-        // 其他状态直接返回
-        s => s,
-    };
+    let layout = display_layout(&window)?;
+    let rect = display::to_physical(&region, space, &layout.window)?;
+    let resolved = display::resolve_region(&rect, &layout.displays)?;
+    let display_info = &layout.displays[resolved.display_index];
+    if resolved.clipped {
+        info!("[信息] 选区 {:?} 跨出显示器 {}，已裁剪为 {:?}", rect, resolved.display_index, resolved.desktop);
+    }
     
-    let state_str = match state {
-        VadState::Initial => "Initial",
-        VadState::Speaking => "Speaking",
-        VadState::Waiting => "Waiting",
-        VadState::Listening => "Listening",
-        VadState::TransitionBuffer => "TransitionBuffer", // 这里不应该出现，因为上面已经处理了临界态
-    };
+    let image = capture_display(display_info)?;
+    let crop = display::scale_to_image(&resolved.local, &display_info.physical, image.width(), image.height());
+    if crop.width == 0 || crop.height == 0 {
+        return Err(format!("选区 {:?} 在截图中为空", resolved.desktop));
+    }
+    let cropped = xcap::image::imageops::crop_imm(&image, crop.x as u32, crop.y as u32, crop.width, crop.height).to_image();
+    
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("获取应用数据目录失败: {}", e))?
+        .join("tauri-plugin-screenshots");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建截图目录 {} 失败: {}", dir.display(), e))?;
+    let path = dir.join(format!("region-{}.png", journal::now_unix_ms()));
+    cropped.save(&path).map_err(|e| format!("保存截图 {} 失败: {}", path.display(), e))?;
     
-    Ok(state_str.to_string())
+    info!("[信息] 已截取显示器 {} 的区域 {:?}: {}", resolved.display_index, resolved.desktop, path.display());
+    Ok(RegionCapture {
+        path: path.display().to_string(),
+        region: resolved,
+        image_width: crop.width,
+        image_height: crop.height,
+    })
+}
+
+// 截取与 display 对应的显示器。按左上角位置匹配 xcap 的显示器：
+// macOS 上 xcap 的坐标是逻辑点，乘以缩放比后才能与 tauri 报告的物理坐标比较
+fn capture_display(display_info: &DisplayInfo) -> Result<xcap::image::RgbaImage, String> {
+    let monitors = xcap::Monitor::all().map_err(|e| format!("枚举可截图的显示器失败: {}", e))?;
+    let monitor = monitors
+        .iter()
+        .min_by_key(|monitor| {
+            let scale = if cfg!(target_os = "macos") { monitor.scale_factor() as f64 } else { 1.0 };
+            let dx = monitor.x() as f64 * scale - display_info.physical.x as f64;
+            let dy = monitor.y() as f64 * scale - display_info.physical.y as f64;
+            (dx.abs() + dy.abs()) as u64
+        })
+        .ok_or_else(|| "没有可截图的显示器".to_string())?;
+    monitor
+        .capture_image()
+        .map_err(|e| format!("截取显示器 {} 失败: {}", monitor.name(), e))
 }
 
 // #[tauri::command]
@@ -1899,31 +8318,1049 @@ async fn get_vad_state() -> Result<String, String> {
 // }
 
 
+// 查询开机自启的注册状态、本次是否最小化启动与上次注册失败的原因
+#[command]
+async fn get_autostart_status(state: State<'_, AppState>) -> Result<AutostartStatus, String> {
+    let autostart = match state.autostart.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取开机自启状态锁失败: {}", e);
+            return Err(format!("获取开机自启状态失败: {}", e));
+        }
+    };
+    
+    Ok(autostart.status())
+}
+
+// 注册或取消开机自启；start_minimized 为真（缺省）时启动项带 --minimized，开机后只显示托盘图标
+#[command]
+async fn set_autostart(state: State<'_, AppState>, enabled: bool, start_minimized: Option<bool>) -> Result<AutostartStatus, String> {
+    let mut autostart = match state.autostart.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("[错误] 获取开机自启状态锁失败: {}", e);
+            return Err(format!("获取开机自启状态失败: {}", e));
+        }
+    };
+    
+    let start_minimized = start_minimized.unwrap_or(true);
+    if let Err(e) = autostart.set_enabled(enabled, start_minimized) {
+        error!("[错误] {}", e);
+        return Err(e);
+    }
+    info!("[信息] 开机自启: {}，最小化启动: {}", enabled, enabled && start_minimized);
+    Ok(autostart.status())
+}
+
+// 显示并聚焦主窗口
+fn show_main_window(app_handle: &tauri::AppHandle) {
+    let window = match app_handle.get_webview_window(MAIN_WINDOW_LABEL) {
+        Some(window) => window,
+        None => {
+            warn!("[警告] 找不到主窗口");
+            return;
+        }
+    };
+    if let Err(e) = window.show().and_then(|_| window.set_focus()) {
+        error!("[错误] 显示主窗口失败: {}", e);
+    }
+}
+
+// 最小化启动时只建托盘图标，菜单可以显示主窗口或退出
+fn build_tray(app: &tauri::App) -> tauri::Result<()> {
+    let show = MenuItem::with_id(app, TRAY_MENU_SHOW, "显示 Lumina", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, TRAY_MENU_QUIT, "退出", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&show, &quit])?;
+    let mut builder = TrayIconBuilder::with_id(MAIN_WINDOW_LABEL)
+        .tooltip("Lumina")
+        .menu(&menu)
+        .on_menu_event(|app_handle, event| match event.id().as_ref() {
+            TRAY_MENU_SHOW => show_main_window(app_handle),
+            TRAY_MENU_QUIT => app_handle.exit(0),
+            _ => {}
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    Ok(())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    println!("[信息] Lumina VAD 应用启动中...");
-    
+    logging::init();
+    info!("[信息] Lumina VAD 应用启动中...");
+
+    // 全局状态统一由 Tauri 管理，后台线程在启动时拿到克隆的 Arc
+    let app_state = AppState::new();
+    let setup_state = app_state.clone();
+    let exit_state = app_state.clone();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_screenshots::init())
+        .manage(app_state)
+        .setup(move |app| {
+            if let Err(e) = init_backend_endpoints(app.handle(), &setup_state) {
+                error!("[错误] 初始化后端端点失败: {}", e);
+            }
+            if let Err(e) = init_config(app.handle(), &setup_state) {
+                error!("[错误] 应用配置失败，使用默认配置: {}", e);
+            }
+            if let Err(e) = init_journal(app.handle(), &setup_state) {
+                error!("[错误] 初始化崩溃恢复journal失败: {}", e);
+            }
+            if let Err(e) = init_send_queue(app.handle(), &setup_state) {
+                error!("[错误] 初始化持久化待发队列失败: {}", e);
+            }
+            
+            // 主窗口默认不可见：正常启动时在这里显示，开机自启的最小化启动只建托盘
+            let launched_minimized = setup_state.autostart.lock().map(|autostart| autostart.launched_minimized()).unwrap_or(false);
+            if launched_minimized {
+                info!("[信息] 以最小化方式启动，只显示托盘图标");
+                if let Err(e) = build_tray(app) {
+                    error!("[错误] 创建托盘图标失败，改为显示主窗口: {}", e);
+                    show_main_window(app.handle());
+                }
+            } else {
+                show_main_window(app.handle());
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(focused) = event {
+                let state = window.app_handle().state::<AppState>();
+                handle_window_focus(window.app_handle(), &state, *focused);
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             greet, 
+            get_app_info,
             process_audio_frame,
             start_stt_result_listener,
             start_tts_audio_listener,
+            shutdown,
             get_speech_segments,
+            export_speech_segments_wav,
+            start_streaming_wav,
+            stop_streaming_wav,
             get_combined_speech_segment,
+            get_segment_waveform_thumbnail,
+            get_session_timeline_audio,
             clear_speech_segments,
             create_test_speech_segment,
             reset_vad_state,
+            reset_vad_to_defaults,
             stop_vad_processing,
             reset_vad_session,
             handle_backend_control,
+            get_control_actions,
             audio_playback_started,
             audio_playback_ended,
+            export_state_diagram,
             get_vad_state,
+            get_vad_state_detail,
+            get_diagnostics,
+            subscribe_vad_state,
+            get_tts_playback_status,
+            set_tts_interrupt_policy,
+            get_tts_gap_fill_status,
+            set_tts_gap_fill,
+            get_tts_flow_control,
+            set_tts_flow_control,
+            get_tts_stall_detection,
+            set_tts_stall_detection,
+            set_sample_format,
+            set_audio_codec,
+            set_pre_context_fade,
+            set_backend_endpoints,
+            set_transport,
+            diagnose,
+            get_connection_status,
+            set_protocol_trace,
+            get_protocol_trace,
+            get_command_audit,
+            set_command_audit_slow_threshold,
+            set_command_audit_sampling,
+            cancel_tts_playback,
+            set_ephemeral_mode,
+            delete_transcript,
+            get_metrics,
+            simulate_backend_message,
+            get_effective_parameters,
+            set_user_parameter,
+            set_timing_profile,
+            set_interaction_mode,
+            get_input_level_status,
+            set_input_level_thresholds,
+            reload_config,
+            get_display_layout,
+            capture_region,
+            get_config,
+            set_config,
+            get_crash_recovery_report,
+            export_vad_annotations,
+            get_recording_indicator,
+            set_focus_policy,
+            set_vad_mode,
+            set_vad_backend,
+            set_denoise,
+            set_sample_rate,
+            get_sample_rate_check,
+            set_sample_rate_auto_correct,
+            get_pipeline,
+            set_pipeline,
+            set_vad_input_filters,
+            set_finalize_on_silence,
+            set_max_utterance_seconds,
+            set_local_only_mode,
+            set_heartbeat_interval,
+            set_microphone_muted,
+            set_state_mirror,
+            set_telemetry,
+            get_telemetry_preview,
+            get_trace_status,
+            calibrate_noise_floor,
+            check_speech_detection,
+            set_capture_mode,
+            set_input_mode,
+            ptt_pressed,
+            ptt_released,
+            ptt_start,
+            ptt_stop,
+            set_send_queue_persistence,
+            get_vad_config,
+            get_vad_statistics,
+            set_audio_level_interval,
+            set_stt_partial_throttle,
+            reset_vad_statistics,
+            set_vad_thresholds,
+            set_silence_threshold_frames,
+            get_conversation_history_entries,
+            get_conversation_turns,
+            export_conversation_report,
+            get_autostart_status,
+            set_autostart,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |_app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown_journal(&exit_state);
+                // 正常退出时删除状态镜像，残留的镜像文件只可能来自崩溃
+                if let Ok(mut mirror) = exit_state.mirror.lock() {
+                    mirror.disable();
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 以socketpair充当后端：返回已完成握手、协商为 version 的 SocketManager 与后端一端
+    #[cfg(unix)]
+    fn connected_manager(version: u32) -> (SocketManager, UnixStream) {
+        let (client, backend) = UnixStream::pair().unwrap();
+        backend.set_nonblocking(true).unwrap();
+        let mut manager = SocketManager::new();
+        manager.stream = Some(client);
+        manager.protocol_version = Some(version);
+        (manager, backend)
+    }
+
+    // 读出后端一端目前收到的全部字节
+    #[cfg(unix)]
+    fn drain(backend: &mut UnixStream) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            match backend.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => panic!("读取失败: {}", e),
+            }
+        }
+        received
+    }
+
+    // AudioWorklet 的256样本块不是合法帧长：切出的帧依次拼接再加上残余样本，应与送入的样本完全一致
+    #[test]
+    fn take_frames_neither_drops_nor_duplicates_samples() {
+        let mut processor = VadProcessor::new();
+        let frame_samples = processor.default_frame_samples();
+        let input: Vec<i16> = (0..256 * 37).map(|i| i as i16).collect();
+
+        let mut output = Vec::new();
+        for chunk in input.chunks(256) {
+            for frame in processor.take_frames(chunk) {
+                assert_eq!(frame.len(), frame_samples);
+                output.extend(frame);
+            }
+        }
+        assert!(processor.session.residual.len() < frame_samples);
+        output.extend_from_slice(&processor.session.residual);
+        assert_eq!(output, input);
+    }
+
+    // 停止处理时取出的待发音频：前瞻延迟线中的帧在前、残余样本在后，取出后两者都已清空
+    #[test]
+    fn pending_audio_keeps_capture_order() {
+        let mut processor = VadProcessor::new();
+        processor.look_ahead.set_depth(2);
+        let now = Instant::now();
+        assert!(processor.look_ahead.push(vec![1; 4], now, false, false).is_empty());
+        assert!(processor.look_ahead.push(vec![2; 4], now, false, false).is_empty());
+        processor.session.residual = vec![3; 3];
+
+        assert_eq!(processor.take_pending_audio(), vec![1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3]);
+        assert!(processor.session.residual.is_empty());
+        assert!(processor.take_pending_audio().is_empty());
+    }
+
+    // 连接断开期间送往后端的帧不会丢：经 forward_vad_frame 发送失败的帧留在补发队列中，
+    // 重连后先按原顺序补发，再发送新帧，每帧只经过一次处理链与保存
+    #[cfg(unix)]
+    #[test]
+    fn frames_sent_while_disconnected_are_resent_in_order() {
+        let state = AppState::new();
+        let (client, dead_backend) = UnixStream::pair().unwrap();
+        drop(dead_backend);
+        {
+            let mut manager = state.socket.lock().unwrap();
+            manager.stream = Some(client);
+            manager.protocol_version = Some(protocol::PROTOCOL_VERSION_LEGACY);
+            // 写入失败后不会立即重连
+            manager.last_reconnect_attempt = Instant::now();
+            manager.reconnect_delay_ms = 60_000;
+        }
+        let frames: Vec<Vec<i16>> = (1..=3).map(|mark| vec![mark * 100; 320]).collect();
+        let now = Instant::now();
+        for frame in &frames {
+            forward_vad_frame(None, &state, frame, true, now);
+        }
+        {
+            let manager = state.socket.lock().unwrap();
+            assert!(manager.stream.is_none());
+            assert_eq!(manager.speech_segments, frames);
+        }
+
+        let (client, mut backend) = UnixStream::pair().unwrap();
+        backend.set_nonblocking(true).unwrap();
+        {
+            let mut manager = state.socket.lock().unwrap();
+            manager.stream = Some(client);
+            manager.protocol_version = Some(protocol::PROTOCOL_VERSION_LEGACY);
+        }
+        let next = vec![400; 320];
+        forward_vad_frame(None, &state, &next, true, now);
+
+        let mut expected = Vec::new();
+        for frame in frames.iter().chain(std::iter::once(&next)) {
+            expected.extend(encode_audio_packet(frame, SampleFormat::Pcm16));
+        }
+        assert_eq!(drain(&mut backend), expected);
+        let manager = state.socket.lock().unwrap();
+        assert!(manager.speech_segments.is_empty());
+        let kept: Vec<i16> = manager.sent_to_python_segments.iter().map(|segment| segment.samples[0]).collect();
+        assert_eq!(kept, vec![100, 200, 300, 400]);
+    }
+
+    // 重新加载配置：新参数立即生效，进行中的会话（会话序号、状态、已收集与待补发的语音段、连接）保持不变；
+    // 校验失败的配置整体不生效
+    #[cfg(unix)]
+    #[test]
+    fn reload_config_applies_parameters_and_keeps_session() {
+        let dir = std::env::temp_dir().join(format!("lumina-reload-{}-{}", std::process::id(), journal::now_unix_ms()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+
+        let state = AppState::new();
+        let (manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION_LEGACY);
+        *state.socket.lock().unwrap() = manager;
+        {
+            let mut socket_manager = state.socket.lock().unwrap();
+            socket_manager.complete_speech_segments.push_back(VoiceSegment { id: 1, samples: vec![7; 320], started_at: None });
+            socket_manager.speech_segments.push(vec![8; 320]);
+        }
+        state.sm.lock().unwrap().session_id = 5;
+        let vad_state = state.sm.lock().unwrap().get_current_state().clone();
+
+        std::fs::write(&path, r#"{"parameters": {"speech_end_silence_ms": 800}, "heartbeat_interval_secs": 10}"#).unwrap();
+        let loaded = reload_config_from(None, &state, &path).unwrap();
+        assert_eq!(loaded.heartbeat_interval_secs, Some(10));
+        assert_eq!(state.vad.lock().unwrap().speech_end_silence_frames, 40);
+        assert_eq!(state.sm.lock().unwrap().speech_end_threshold_ms.load(Ordering::Relaxed), 800);
+
+        {
+            let state_machine = state.sm.lock().unwrap();
+            assert_eq!(state_machine.session_id, 5);
+            assert_eq!(*state_machine.get_current_state(), vad_state);
+        }
+        {
+            let socket_manager = state.socket.lock().unwrap();
+            assert_eq!(socket_manager.heartbeat_interval_secs, 10);
+            assert!(socket_manager.stream.is_some());
+            assert_eq!(socket_manager.complete_speech_segments.len(), 1);
+            assert_eq!(socket_manager.speech_segments, vec![vec![8; 320]]);
+        }
+        assert!(drain(&mut backend).is_empty());
+
+        std::fs::write(&path, r#"{"parameters": {"speech_end_silence_ms": 400, "no_such_param": 1}}"#).unwrap();
+        assert!(reload_config_from(None, &state, &path).is_err());
+        assert_eq!(state.sm.lock().unwrap().speech_end_threshold_ms.load(Ordering::Relaxed), 800);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // get_config 的返回中保存的配置字段保持在顶层，同时能看到不留痕模式是否开启
+    #[test]
+    fn config_view_exposes_ephemeral_mode() {
+        let state = AppState::new();
+        let config = LuminaConfig {
+            heartbeat_interval_secs: Some(10),
+            ..LuminaConfig::default()
+        };
+        let view = serde_json::to_value(config_view(&state, config.clone())).unwrap();
+        assert_eq!(view["heartbeat_interval_secs"], 10);
+        assert_eq!(view["privacy"]["ephemeral"], false);
+
+        state.privacy.lock().unwrap().set_ephemeral(true);
+        let view = serde_json::to_value(config_view(&state, config)).unwrap();
+        assert_eq!(view["privacy"]["ephemeral"], true);
+        assert!(view["privacy"]["changed_at_ms"].is_u64());
+    }
+
+    // 仅本地模式：已建立的发送连接与监听器连接都被断开，之后各发送路径不向后端写入任何字节
+    #[cfg(unix)]
+    #[test]
+    fn local_only_mode_writes_nothing_to_backend() {
+        let state = AppState::new();
+        let (manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION);
+        *state.socket.lock().unwrap() = manager;
+        let (listener_stream, mut listener_backend) = UnixStream::pair().unwrap();
+        let listener = state.lifecycle.register("stt_result_listener");
+        listener.watch_stream(StreamCloser::new(&listener_stream));
+
+        apply_local_only_mode(&state, true).unwrap();
+
+        let now = Instant::now();
+        for mark in 1..=5 {
+            forward_vad_frame(None, &state, &vec![mark * 100; 320], true, now);
+        }
+        {
+            let mut manager = state.socket.lock().unwrap();
+            assert!(!manager.send_end_of_utterance(0, 1200));
+            assert!(!manager.send_playback_inquiry(1, 3000));
+            assert!(!manager.send_finalize_request(1));
+            // 开启前就在等待补发的段同样留在本地
+            manager.speech_segments.push(vec![1; 320]);
+            assert!(!manager.send_speech_segments());
+            assert_eq!(manager.speech_segments.len(), 1);
+            manager.heartbeat(now + Duration::from_secs(3600));
+            assert!(manager.stream.is_none());
+        }
+
+        // 连接已关闭：读到的是 EOF 而不是数据
+        assert!(drain(&mut backend).is_empty());
+        let mut buf = [0u8; 16];
+        assert_eq!(listener_backend.read(&mut buf).unwrap(), 0);
+        assert!((&listener_stream).write(&[1]).is_err());
+    }
+
+    // 开启持久化待发队列时，发送失败的帧由待发队列补发，不再进入内存补发队列
+    #[cfg(unix)]
+    #[test]
+    fn persisted_frames_are_not_requeued_in_memory() {
+        let dir = std::env::temp_dir().join(format!("lumina-requeue-{}-{}", std::process::id(), journal::now_unix_ms()));
+        let mut manager = SocketManager::new();
+        manager.send_queue = Some(SendQueue::open(&dir).unwrap());
+        manager.last_reconnect_attempt = Instant::now();
+        manager.reconnect_delay_ms = 60_000;
+        assert!(!manager.send_speech_segment(&[1, 2, 3]));
+        assert!(manager.speech_segments.is_empty());
+        assert!(manager.send_queue.as_ref().unwrap().has_unsent());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // 停止处理时经 flush 收尾：连接不上或写入失败时积压的段按原顺序留在队列中，不会被丢弃
+    #[cfg(unix)]
+    #[test]
+    fn flush_on_stop_keeps_unsent_segments() {
+        let mut manager = SocketManager::new();
+        manager.speech_segments = vec![vec![1], vec![2], vec![3]];
+        manager.last_reconnect_attempt = Instant::now();
+        manager.reconnect_delay_ms = 60_000;
+        assert!(!manager.flush());
+        assert_eq!(manager.speech_segments, vec![vec![1], vec![2], vec![3]]);
+
+        // 后端已关闭连接：第一段写入失败，全部放回
+        let (client, backend) = UnixStream::pair().unwrap();
+        drop(backend);
+        manager.stream = Some(client);
+        manager.protocol_version = Some(protocol::PROTOCOL_VERSION_LEGACY);
+        assert!(!manager.flush());
+        assert_eq!(manager.speech_segments, vec![vec![1], vec![2], vec![3]]);
+        assert!(manager.stream.is_none());
+    }
+
+    // 临界态停留超过超时时间后，下一个事件按超时处理，回到进入临界态之前的可见状态
+    #[test]
+    fn transition_buffer_times_out_to_last_visible_state() {
+        let mut machine = VadStateMachine::new(Arc::new(Mutex::new(SocketManager::new())));
+        let mut socket_manager = SocketManager::new();
+        let entered = Instant::now();
+        let timeout = Duration::from_millis(machine.transition_timeout_ms);
+
+        assert!(machine.process_event_at(VadStateMachineEvent::VoiceFrame, &mut socket_manager, entered));
+        assert_eq!(machine.current_state, VadState::TransitionBuffer);
+        assert_eq!(machine.last_user_visible_state, VadState::Initial);
+
+        // 未超时的静音帧保持临界态
+        assert!(machine.process_event_at(VadStateMachineEvent::SilenceFrame, &mut socket_manager, entered + timeout));
+        assert_eq!(machine.current_state, VadState::TransitionBuffer);
+
+        let expired = entered + timeout + Duration::from_millis(1);
+        assert!(!machine.process_event_at(VadStateMachineEvent::SilenceFrame, &mut socket_manager, expired));
+        assert_eq!(machine.current_state, VadState::Initial);
+        assert_eq!(machine.transition_start_time, None);
+    }
+
+    // 从等待中进入的临界态超时后回到等待中，而不是初始
+    #[test]
+    fn transition_buffer_from_waiting_falls_back_to_waiting() {
+        let mut machine = VadStateMachine::new(Arc::new(Mutex::new(SocketManager::new())));
+        let mut socket_manager = SocketManager::new();
+        machine.current_state = VadState::Waiting;
+        let entered = Instant::now();
+        let timeout = Duration::from_millis(machine.transition_timeout_ms);
+
+        assert!(machine.process_event_at(VadStateMachineEvent::VoiceFrame, &mut socket_manager, entered));
+        assert_eq!(machine.current_state, VadState::TransitionBuffer);
+
+        let expired = entered + timeout + Duration::from_millis(1);
+        machine.process_event_at(VadStateMachineEvent::SilenceFrame, &mut socket_manager, expired);
+        assert_eq!(machine.current_state, VadState::Waiting);
+    }
+
+    // 超时时间取自 transition_timeout_ms 参数；同一次临界态只回退一次，之后的事件按回退后的状态处理
+    #[test]
+    fn configured_transition_timeout_fires_exactly_once() {
+        let mut machine = VadStateMachine::new(Arc::new(Mutex::new(SocketManager::new())));
+        let mut socket_manager = SocketManager::new();
+        machine.transition_timeout_ms = TRANSITION_BUFFER_TIMEOUT_MS * 3;
+        machine.current_state = VadState::Waiting;
+        let entered = Instant::now();
+
+        machine.process_event_at(VadStateMachineEvent::VoiceFrame, &mut socket_manager, entered);
+        // 超过默认超时但未超过配置值，仍在临界态
+        let past_default = entered + Duration::from_millis(TRANSITION_BUFFER_TIMEOUT_MS * 2);
+        assert!(machine.process_event_at(VadStateMachineEvent::SilenceFrame, &mut socket_manager, past_default));
+        assert_eq!(machine.current_state, VadState::TransitionBuffer);
+
+        let mut fallbacks = 0;
+        for step in 1..=5u64 {
+            let now = entered + Duration::from_millis(machine.transition_timeout_ms + step * 100);
+            let before = machine.current_state.clone();
+            machine.process_event_at(VadStateMachineEvent::SilenceFrame, &mut socket_manager, now);
+            if before == VadState::TransitionBuffer && machine.current_state != VadState::TransitionBuffer {
+                fallbacks += 1;
+            }
+            assert_eq!(machine.current_state, VadState::Waiting);
+            assert_eq!(machine.transition_start_time, None);
+        }
+        assert_eq!(fallbacks, 1);
+    }
+
+    // 按给定幅度合成一段语音会话（440Hz 正弦），逐帧按 frame_rms 计入响度统计
+    fn speak_session(monitor: &mut InputLevelMonitor, amplitude: f32) -> SessionLoudnessSummary {
+        for frame in 0..MIN_LOUDNESS_SESSION_FRAMES * 2 {
+            let samples: Vec<i16> = (0..320)
+                .map(|n| {
+                    let t = (frame * 320 + n) as f32 / 16000.0;
+                    audio_utils::f32_to_i16(amplitude * (2.0 * std::f32::consts::PI * 440.0 * t).sin())
+                })
+                .collect();
+            monitor.record_voice_frame(audio_utils::frame_rms(&samples));
+        }
+        monitor.finish_session().expect("语音帧足够时应返回统计结果")
+    }
+
+    // 以正常说话响度校准后，远离麦克风的低幅度会话触发提示，正常幅度不触发
+    #[test]
+    fn low_amplitude_session_triggers_hint_after_calibration() {
+        let mut monitor = InputLevelMonitor::new();
+        // 幅度0.2的正弦 RMS 约为0.14
+        monitor.calibrate_reference(0.2 / std::f32::consts::SQRT_2);
+
+        let normal = speak_session(&mut monitor, 0.2);
+        assert!(!normal.is_low, "正常幅度不应判为偏低: {:?}", normal);
+
+        let low = speak_session(&mut monitor, 0.03);
+        assert!(low.is_low, "低幅度应判为偏低: {:?}", low);
+        assert!(monitor.status().suggestion.is_none());
+
+        for _ in 1..LOW_INPUT_LEVEL_SUGGEST_SESSIONS {
+            speak_session(&mut monitor, 0.03);
+        }
+        assert!(monitor.status().suggestion.is_some());
+
+        // 正常幅度的会话清零连续计数
+        speak_session(&mut monitor, 0.2);
+        assert!(monitor.status().suggestion.is_none());
+    }
+
+    // 重新校准参考值后，按旧参考值累计的偏低计数不再生效
+    #[test]
+    fn recalibration_resets_low_level_streak() {
+        let mut monitor = InputLevelMonitor::new();
+        for _ in 0..LOW_INPUT_LEVEL_SUGGEST_SESSIONS {
+            assert!(speak_session(&mut monitor, 0.01).is_low);
+        }
+        assert!(monitor.suggestion().is_some());
+
+        monitor.calibrate_reference(0.01 / std::f32::consts::SQRT_2);
+        assert!(monitor.suggestion().is_none());
+        assert!(!speak_session(&mut monitor, 0.01).is_low);
+    }
+
+    // 削波比例刚好等于阈值时不提示，超过时提示并带出计数
+    #[test]
+    fn mic_clipping_fires_above_ratio() {
+        let mut frame = vec![0.2f32; 320];
+        let threshold = (320.0 * MIC_CLIPPING_WARNING_RATIO) as usize;
+        for sample in frame.iter_mut().take(threshold) {
+            *sample = 1.0;
+        }
+        assert!(MicClippingEvent::check(&frame).is_none());
+
+        frame[threshold] = -1.7;
+        let clipping = MicClippingEvent::check(&frame).expect("超过阈值应提示削波");
+        assert_eq!(clipping.clipped_samples, threshold + 1);
+        assert_eq!(clipping.total_samples, 320);
+        assert!(clipping.ratio > MIC_CLIPPING_WARNING_RATIO);
+
+        assert!(MicClippingEvent::check(&[]).is_none());
+    }
+
+    // 过载的正弦（幅度 1.5）转换后削成平顶，不会回绕成反相的尖峰
+    #[test]
+    fn overdriven_input_clips_without_wraparound() {
+        let frame: Vec<f32> = (0..320).map(|n| 1.5 * (2.0 * std::f32::consts::PI * n as f32 / 80.0).sin()).collect();
+        let clipping = MicClippingEvent::check(&frame).expect("过载输入应提示削波");
+        assert!(clipping.ratio > 0.4, "{}", clipping.ratio);
+
+        for &sample in &frame {
+            let converted = audio_utils::f32_to_i16(sample);
+            if sample >= 1.0 {
+                assert_eq!(converted, i16::MAX, "{}", sample);
+            } else if sample <= -1.0 {
+                assert_eq!(converted, i16::MIN, "{}", sample);
+            } else {
+                assert!(converted as f32 * sample >= 0.0, "{} -> {}", sample, converted);
+            }
+        }
+    }
+
+    // 静音状态同时出现在 get_vad_state 与诊断信息中；静音时状态机回到初始状态
+    #[test]
+    fn mute_state_is_reported_by_vad_state_and_diagnostics() {
+        let state = AppState::new();
+        let view = vad_state_view(&state).unwrap();
+        assert_eq!(view.state, "Initial");
+        assert!(!view.muted);
+        assert!(!collect_diagnostics(&state).unwrap().vad_state.muted);
+
+        state.sm.lock().unwrap().current_state = VadState::Listening;
+        assert!(apply_microphone_mute(&state, true).unwrap());
+        let view = vad_state_view(&state).unwrap();
+        assert_eq!(view.state, "Initial");
+        assert!(view.muted);
+        let diagnostics = collect_diagnostics(&state).unwrap();
+        assert!(diagnostics.vad_state.muted);
+        assert!(!diagnostics.connection.connected);
+
+        assert!(apply_microphone_mute(&state, false).unwrap());
+        assert!(!vad_state_view(&state).unwrap().muted);
+        assert!(!collect_diagnostics(&state).unwrap().vad_state.muted);
+    }
+
+    // 内容未变化时不推送，序号不变
+    #[test]
+    fn vad_state_snapshot_only_advances_on_change() {
+        let state = AppState::new();
+        let mut receiver = state.vad_state_stream.subscribe();
+        publish_vad_state(&state);
+        publish_vad_state(&state);
+        assert!(!receiver.has_changed().unwrap());
+        assert_eq!(receiver.borrow_and_update().seq, 0);
+
+        state.sm.lock().unwrap().muted = true;
+        publish_vad_state(&state);
+        publish_vad_state(&state);
+        assert!(receiver.has_changed().unwrap());
+        let snapshot = receiver.borrow_and_update().clone();
+        assert_eq!(snapshot.seq, 1);
+        assert!(snapshot.muted);
+    }
+
+    // 多个线程快速连续改变状态：每个订阅者收到的序号严格递增，停止变化后收到的快照与实际状态一致
+    #[test]
+    fn rapid_state_changes_reach_subscribers_monotonically() {
+        let state = AppState::new();
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let subscribers: Vec<_> = (0..2)
+            .map(|_| {
+                let mut receiver = state.vad_state_stream.subscribe();
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    let mut received = vec![receiver.borrow_and_update().clone()];
+                    loop {
+                        let finished = done.load(std::sync::atomic::Ordering::SeqCst);
+                        if receiver.has_changed().unwrap() {
+                            received.push(receiver.borrow_and_update().clone());
+                        } else if finished {
+                            return received;
+                        } else {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let state = state.clone();
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        {
+                            let mut state_machine = state.sm.lock().unwrap();
+                            state_machine.session_id += 1;
+                            state_machine.muted = (writer + i) % 3 == 0;
+                        }
+                        publish_vad_state(&state);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+
+        let actual = current_vad_state(&state).unwrap();
+        assert_eq!(actual.session_id, 800);
+        for subscriber in subscribers {
+            // 订阅线程可能在所有写入结束后才开始读取，只能收到最终快照，因此不要求收到多条
+            let received = subscriber.join().unwrap();
+            assert!(received.windows(2).all(|pair| pair[0].seq < pair[1].seq), "序号应严格递增");
+            assert!(received.windows(2).all(|pair| pair[0].session_id <= pair[1].session_id), "会话序号不应回退");
+            let last = received.last().unwrap();
+            assert!(last.same_content(&actual), "最终快照 {:?} 与实际状态 {:?} 不一致", last, actual);
+        }
+    }
+
+    // 按交互模式合成参数并下发后，用能量后端处理 300ms 语音加 4 秒静音，返回判定语音结束时已静音的毫秒数
+    fn end_of_speech_after_ms(mode: Option<InteractionMode>) -> Option<u64> {
+        let state = AppState::new();
+        state.params.lock().unwrap().mode = mode;
+        refresh_effective_parameters(None, &state).unwrap();
+
+        let mut processor = state.vad.lock().unwrap();
+        processor.set_backend(VadBackendKind::Energy, SileroSettings::default()).unwrap();
+        let voice: Vec<i16> = (0..320).map(|n| if n % 2 == 0 { 12000 } else { -12000 }).collect();
+        let silence = vec![0i16; 320];
+
+        let mut started = false;
+        for _ in 0..15 {
+            started |= matches!(processor.process_frame(&voice), Some((VadEvent::SpeechStart, _, _)));
+        }
+        assert!(started, "语音开始应被判定");
+        for frame in 1..=200u64 {
+            if let Some((VadEvent::SpeechEnd, _, _)) = processor.process_frame(&silence) {
+                return Some(frame * 20);
+            }
+        }
+        None
+    }
+
+    // 同样一段停顿：命令模式 0.5 秒即结束，对话模式 1.5 秒，听写模式 3 秒，未选模式时按默认值
+    #[test]
+    fn interaction_modes_end_speech_after_different_pauses() {
+        let command = end_of_speech_after_ms(Some(InteractionMode::Command)).unwrap();
+        let conversation = end_of_speech_after_ms(Some(InteractionMode::Conversation)).unwrap();
+        let dictation = end_of_speech_after_ms(Some(InteractionMode::Dictation)).unwrap();
+        assert_eq!(command, 500);
+        assert_eq!(conversation, 1500);
+        assert_eq!(dictation, 3000);
+        assert_eq!(end_of_speech_after_ms(None).unwrap(), DEFAULT_SPEECH_END_SILENCE_MS);
+
+        // 1 秒的思考停顿：命令模式下已经结束，对话与听写模式下仍在说话
+        assert!(command <= 1000 && conversation > 1000 && dictation > 1000);
+    }
+
+    // 用户显式设置的语音结束时长不受模式切换影响
+    #[test]
+    fn user_setting_overrides_interaction_mode_end_of_speech() {
+        let state = AppState::new();
+        {
+            let mut layers = state.params.lock().unwrap();
+            layers.mode = Some(InteractionMode::Command);
+            layers.user.insert(ParamKey::SpeechEndSilenceMs, 800.0);
+        }
+        refresh_effective_parameters(None, &state).unwrap();
+        assert_eq!(state.vad.lock().unwrap().speech_end_silence_frames, 40);
+        assert_eq!(state.sm.lock().unwrap().speech_end_threshold_ms.load(Ordering::Relaxed), 800);
+    }
+
+    // 模拟后端消息与真实Socket消息走同一分发路径：依次注入识别结果、TTS起止与控制消息，
+    // 状态机、对话历史与 journal 的变化与真实后端一致；过期会话的结果与无效消息不改变状态
+    #[cfg(unix)]
+    #[test]
+    fn simulated_messages_drive_state_through_dispatch() {
+        let state = AppState::new();
+        let (manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION_LEGACY);
+        *state.socket.lock().unwrap() = manager;
+        {
+            let mut state_machine = state.sm.lock().unwrap();
+            let mut socket_manager = state.socket.lock().unwrap();
+            state_machine.process_event(VadStateMachineEvent::VoiceFrame, &mut socket_manager);
+        }
+        drain(&mut backend);
+        let session_id = state.sm.lock().unwrap().session_id;
+        assert_eq!(*state.sm.lock().unwrap().get_current_state(), VadState::TransitionBuffer);
+
+        let inject = |kind: &str, payload: &str| {
+            let message = parse_simulated_message(kind, payload)?;
+            dispatch_incoming_message(None, &state, message)
+        };
+
+        // 中间结果确认有效语音，临界态进入说话中
+        inject("stt_partial", &format!(r#"{{"text": "打开客厅", "session_id": {}}}"#, session_id)).unwrap();
+        assert_eq!(*state.sm.lock().unwrap().get_current_state(), VadState::Speaking);
+
+        // 上一会话迟到的最终结果被丢弃
+        inject("stt_final", &format!(r#"{{"text": "上一句", "session_id": {}}}"#, session_id - 1)).unwrap();
+        assert!(state.history.lock().unwrap().entries().is_empty());
+
+        // 最终结果按轮次登记到对话历史与 journal
+        inject("stt_final", &format!(r#"{{"text": "打开客厅的灯", "session_id": {}}}"#, session_id)).unwrap();
+        let entries = state.history.lock().unwrap().entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text, "打开客厅的灯");
+        let transcripts = state.journal.lock().unwrap().transcripts();
+        assert_eq!(transcripts.len(), 1);
+        assert_eq!(transcripts[0].session_id, Some(session_id));
+        assert_eq!(transcripts[0].turn_id, entries[0].turn_id);
+
+        inject("tts_begin", "").unwrap();
+        assert_eq!(*state.sm.lock().unwrap().get_current_state(), VadState::Listening);
+        inject("tts_end", "").unwrap();
+        assert_eq!(*state.sm.lock().unwrap().get_current_state(), VadState::Initial);
+
+        // 无效的控制消息与未知类型返回错误，状态不变
+        assert!(inject("control", r#"{"action": "no_such_action"}"#).is_err());
+        assert!(inject("stt_final", "not json").is_err());
+        assert!(inject("unknown", "{}").is_err());
+        assert_eq!(*state.sm.lock().unwrap().get_current_state(), VadState::Initial);
+
+        inject("tts_begin", "").unwrap();
+        inject("control", r#"{"action": "end_session"}"#).unwrap();
+        assert_eq!(*state.sm.lock().unwrap().get_current_state(), VadState::Initial);
+        assert_eq!(state.sm.lock().unwrap().session_id, session_id);
+        assert!(state.socket.lock().unwrap().stream.is_some());
+    }
+
+    // 按 apply_stt_result 的方式把一条最终识别结果登记到 journal 与对话历史
+    fn record_final_transcript(state: &AppState, text: &str, turn_id: u64) {
+        state.history.lock().unwrap().record_user(text, turn_id);
+        state.journal.lock().unwrap().record_transcript(SttResult {
+            text: text.to_string(),
+            is_final: true,
+            session_id: Some(1),
+            trace_id: None,
+            turn_id: Some(turn_id),
+        });
+    }
+
+    // 构造两轮对话及其在各存储位置的关联数据，journal 写在临时目录中
+    fn state_with_two_turns(dir: &std::path::Path) -> AppState {
+        let state = AppState::new();
+        state.journal.lock().unwrap().open(dir.to_path_buf()).unwrap();
+        let mut turn_ids = Vec::new();
+        {
+            let mut tracker = state.turns.lock().unwrap();
+            tracker.user_speech_started(1000);
+            tracker.audio_segments_finished(&[1, 2]);
+            tracker.user_speech_ended(3000);
+            turn_ids.push(tracker.transcript("打开客厅的灯", 3100).0);
+            tracker.reply_started(7);
+            tracker.reply(AssistantReply { text: "好的".to_string(), utterance_id: Some(7), interrupted: false }, false, 4000);
+            tracker.user_speech_started(5000);
+            tracker.audio_segments_finished(&[3]);
+            turn_ids.push(tracker.transcript("明天天气怎么样", 6000).0);
+        }
+        for (text, turn_id) in ["打开客厅的灯", "明天天气怎么样"].into_iter().zip(turn_ids) {
+            record_final_transcript(&state, text, turn_id);
+        }
+        {
+            let mut socket_manager = state.socket.lock().unwrap();
+            for id in 1..=3u64 {
+                let samples: Vec<i16> = (0..640).map(|n| ((n as i64 * id as i64 * 37) % 20000) as i16).collect();
+                socket_manager.complete_speech_segments.push_back(VoiceSegment { id, samples: samples.clone(), started_at: None });
+                socket_manager.sent_to_python_segments.push_back(SentSegment { samples, voice_segment_id: id });
+            }
+            socket_manager.waveform_thumbnail(Some(0), 64).unwrap();
+        }
+        state
+    }
+
+    // 级联删除：转写从对话历史、journal 与磁盘 checkpoint 中消失，关联的语音段、发送段与缩略图一并清理，
+    // 轮次保留为"已删除"占位；另一轮的数据不受影响
+    #[test]
+    fn cascade_delete_clears_every_store() {
+        let dir = std::env::temp_dir().join(format!("lumina-cascade-{}-{}", std::process::id(), journal::now_unix_ms()));
+        let state = state_with_two_turns(&dir);
+
+        let report = delete_transcript_data(&state, 1, true).unwrap();
+        assert_eq!(report.history_entries, 1);
+        assert_eq!(report.journal_transcripts, 1);
+        assert_eq!(report.voice_segments, 2);
+        assert_eq!(report.sent_segments, 2);
+        assert!(report.thumbnails_invalidated);
+        assert!(report.checkpoint_rewritten);
+
+        let turns = state.turns.lock().unwrap().snapshot();
+        assert!(turns[0].user.deleted);
+        assert_eq!(turns[0].user.transcript, None);
+        assert!(turns[0].user.audio_segment_ids.is_empty());
+        assert_eq!(turns[0].replies.len(), 1);
+        assert!(!turns[1].user.deleted);
+        assert_eq!(turns[1].user.transcript.as_deref(), Some("明天天气怎么样"));
+        assert_eq!(turns[1].user.audio_segment_ids, vec![3]);
+
+        let history: Vec<String> = state.history.lock().unwrap().entries().into_iter().map(|entry| entry.text).collect();
+        assert_eq!(history, vec!["明天天气怎么样".to_string()]);
+        let journaled: Vec<String> = state.journal.lock().unwrap().transcripts().into_iter().map(|result| result.text).collect();
+        assert_eq!(journaled, vec!["明天天气怎么样".to_string()]);
+
+        {
+            let mut socket_manager = state.socket.lock().unwrap();
+            let voice_ids: Vec<u64> = socket_manager.complete_speech_segments.iter().map(|segment| segment.id).collect();
+            assert_eq!(voice_ids, vec![3]);
+            let sent_ids: Vec<u64> = socket_manager.sent_to_python_segments.iter().map(|segment| segment.voice_segment_id).collect();
+            assert_eq!(sent_ids, vec![3]);
+            // 缩略图缓存已失效：位置 0 现在对应剩下的段，不会返回被删除段的旧波形
+            let remaining = socket_manager.sent_to_python_segments[0].samples.clone();
+            assert_eq!(socket_manager.waveform_thumbnail(Some(0), 64).unwrap(), waveform::thumbnail(&remaining, 64));
+        }
+
+        let raw = std::fs::read_to_string(dir.join("checkpoint.json")).unwrap();
+        assert!(!raw.contains("打开客厅的灯"));
+        let checkpoint = journal::load_checkpoint(&dir.join("checkpoint.json")).unwrap();
+        assert!(checkpoint.turns[0].user.deleted);
+        assert_eq!(checkpoint.transcripts.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // 不级联时只删除文本，音频与缩略图保留
+    #[test]
+    fn delete_without_cascade_keeps_audio() {
+        let dir = std::env::temp_dir().join(format!("lumina-delete-{}-{}", std::process::id(), journal::now_unix_ms()));
+        let state = state_with_two_turns(&dir);
+
+        let report = delete_transcript_data(&state, 1, false).unwrap();
+        assert_eq!(report.history_entries, 1);
+        assert_eq!(report.voice_segments, 0);
+        assert_eq!(report.sent_segments, 0);
+        assert!(!report.thumbnails_invalidated);
+        assert_eq!(state.turns.lock().unwrap().snapshot()[0].user.audio_segment_ids, vec![1, 2]);
+        assert_eq!(state.socket.lock().unwrap().complete_speech_segments.len(), 3);
+
+        assert!(delete_transcript_data(&state, 99, true).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // 按轮次 id 删除：其他轮次中恰好是被删转写一部分的短文本（如"客厅"）不受影响；
+    // 被删除的轮次登记到诊断排除列表，导出会话报告时整轮跳过
+    #[test]
+    fn delete_matches_turn_id_and_excludes_turn_from_diagnostics() {
+        let dir = std::env::temp_dir().join(format!("lumina-delete-id-{}-{}", std::process::id(), journal::now_unix_ms()));
+        let state = state_with_two_turns(&dir);
+        let short_turn = {
+            let mut tracker = state.turns.lock().unwrap();
+            tracker.reply_started(8);
+            tracker.reply(AssistantReply { text: "晴".to_string(), utterance_id: Some(8), interrupted: false }, false, 6500);
+            tracker.user_speech_started(7000);
+            tracker.transcript("客厅", 8000).0
+        };
+        record_final_transcript(&state, "客厅", short_turn);
+
+        let report = delete_transcript_data(&state, 1, false).unwrap();
+        assert_eq!(report.history_entries, 1);
+        assert_eq!(report.journal_transcripts, 1);
+        assert!(report.diagnostics_excluded);
+
+        let history: Vec<String> = state.history.lock().unwrap().entries().into_iter().map(|entry| entry.text).collect();
+        assert_eq!(history, vec!["明天天气怎么样".to_string(), "客厅".to_string()]);
+        let journaled: Vec<String> = state.journal.lock().unwrap().transcripts().into_iter().map(|result| result.text).collect();
+        assert_eq!(journaled, vec!["明天天气怎么样".to_string(), "客厅".to_string()]);
+
+        let exported: Vec<u64> = excluding_deleted_turns(&state, state.turns.lock().unwrap().snapshot())
+            .unwrap()
+            .into_iter()
+            .map(|turn| turn.turn_id)
+            .collect();
+        assert_eq!(short_turn, 3);
+        assert_eq!(exported, vec![2, 3]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finalize_request_is_not_sent_to_older_backends() {
+        let (mut manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION_LEGACY);
+        assert!(!manager.send_finalize_request(1));
+        assert!(drain(&mut backend).is_empty());
+
+        let (mut manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION_FINALIZE);
+        assert!(manager.send_finalize_request(7));
+        let received = drain(&mut backend);
+        assert_eq!(&received[..4], &protocol::CONTROL_MESSAGE_HEADER.to_le_bytes());
+        assert_eq!(received[4], protocol::CONTROL_FINALIZE);
+        assert_eq!(&received[5..], &7u64.to_le_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn f32_audio_falls_back_to_pcm16_for_older_backends() {
+        // 握手完成前设置为f32，协商出v1后按PCM16发送
+        let (mut manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION_LEGACY);
+        manager.protocol_version = None;
+        manager.set_sample_format(SampleFormat::F32).unwrap();
+        manager.protocol_version = Some(protocol::PROTOCOL_VERSION_LEGACY);
+        assert!(manager.write_audio_packet(&[1000, -1000]));
+        assert_eq!(drain(&mut backend), encode_audio_packet(&[1000, -1000], SampleFormat::Pcm16));
+
+        // 已协商出v1时直接拒绝
+        assert!(manager.set_sample_format(SampleFormat::F32).is_err());
+
+        let (mut manager, mut backend) = connected_manager(protocol::PROTOCOL_VERSION_AUDIO_F32);
+        manager.set_sample_format(SampleFormat::F32).unwrap();
+        assert!(manager.write_audio_packet(&[1000, -1000]));
+        let received = drain(&mut backend);
+        assert!(received.ends_with(&encode_audio_packet(&[1000, -1000], SampleFormat::F32)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn opus_is_rejected_by_older_backends() {
+        let (mut manager, _backend) = connected_manager(protocol::PROTOCOL_VERSION_AUDIO_F32);
+        let error = manager.set_codec(AudioCodec::Opus).unwrap_err();
+        assert!(error.contains(&format!("v{}", protocol::PROTOCOL_VERSION_CODED_AUDIO)));
+        assert!(manager.opus.is_none());
+    }
 }
@@ -0,0 +1,174 @@
+// 后台任务生命周期
+// 各后台循环（语音段补发、checkpoint、STT/TTS 监听器）启动时在这里登记，退出时由 TaskGuard 注销。
+// shutdown 置位关闭标志并关闭登记的连接：阻塞在读取上的监听器因连接关闭而返回，
+// 睡眠中的循环按 SLEEP_SLICE_MS 分片检查标志，随后各自退出；shutdown 等到登记表清空再返回。
+// 关闭完成后清除标志，之后再调用 start_* 等命令即可重新启动这些任务。
+
+use log::error;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::endpoints::StreamCloser;
+
+// 睡眠分片：后台循环最迟在这段时间内察觉关闭请求
+const SLEEP_SLICE_MS: u64 = 100;
+// 等待后台任务退出的最长时间，超时的任务在报告中列出
+pub const SHUTDOWN_TIMEOUT_MS: u64 = 3000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ShutdownReport {
+    pub stopped_tasks: Vec<&'static str>,
+    pub timed_out_tasks: Vec<&'static str>, // 超时仍未退出的任务
+    pub closed_streams: usize,
+    pub elapsed_ms: u64,
+}
+
+pub struct Lifecycle {
+    shutting_down: AtomicBool,
+    next_id: AtomicU64,
+    tasks: Mutex<BTreeMap<u64, &'static str>>,
+    streams: Mutex<BTreeMap<u64, StreamCloser>>, // 任务当前持有的连接，键为任务id
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self {
+            shutting_down: AtomicBool::new(false),
+            next_id: AtomicU64::new(1),
+            tasks: Mutex::new(BTreeMap::new()),
+            streams: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // 登记一个后台任务；返回的 guard 被丢弃（任务退出）时注销
+    pub fn register(self: &Arc<Self>, name: &'static str) -> TaskGuard {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        match self.tasks.lock() {
+            Ok(mut tasks) => {
+                tasks.insert(id, name);
+            }
+            Err(e) => error!("[错误] 获取后台任务表锁失败: {}", e),
+        }
+        TaskGuard { lifecycle: Arc::clone(self), id }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    // 同名任务是否在运行，用于避免重复启动
+    pub fn is_running(&self, name: &str) -> bool {
+        match self.tasks.lock() {
+            Ok(tasks) => tasks.values().any(|task| *task == name),
+            Err(_) => false,
+        }
+    }
+
+    pub fn running_tasks(&self) -> Vec<&'static str> {
+        match self.tasks.lock() {
+            Ok(tasks) => tasks.values().copied().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // 置位关闭标志并关闭所有登记的连接，返回关闭的连接数
+    pub fn begin_shutdown(&self) -> usize {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        self.close_streams()
+    }
+
+    // 只关闭登记的连接、不置位关闭标志（如开启仅本地模式时），任务按各自的规则决定是否重连；返回关闭的连接数
+    pub fn close_streams(&self) -> usize {
+        match self.streams.lock() {
+            Ok(mut streams) => {
+                let count = streams.len();
+                for closer in streams.values() {
+                    closer.close();
+                }
+                streams.clear();
+                count
+            }
+            Err(e) => {
+                error!("[错误] 获取连接表锁失败: {}", e);
+                0
+            }
+        }
+    }
+
+    // 关闭完成，允许重新启动后台任务
+    pub fn finish_shutdown(&self) {
+        self.shutting_down.store(false, Ordering::SeqCst);
+    }
+
+    // 睡眠指定时长，期间收到关闭请求则提前返回 false
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let mut remaining = duration;
+        while !remaining.is_zero() {
+            if self.is_shutting_down() {
+                return false;
+            }
+            let slice = remaining.min(Duration::from_millis(SLEEP_SLICE_MS));
+            std::thread::sleep(slice);
+            remaining -= slice;
+        }
+        !self.is_shutting_down()
+    }
+
+    pub async fn sleep_async(&self, duration: Duration) -> bool {
+        let mut remaining = duration;
+        while !remaining.is_zero() {
+            if self.is_shutting_down() {
+                return false;
+            }
+            let slice = remaining.min(Duration::from_millis(SLEEP_SLICE_MS));
+            tokio::time::sleep(slice).await;
+            remaining -= slice;
+        }
+        !self.is_shutting_down()
+    }
+}
+
+pub struct TaskGuard {
+    lifecycle: Arc<Lifecycle>,
+    id: u64,
+}
+
+impl TaskGuard {
+    // 登记任务当前使用的连接，关闭时由 shutdown 关闭；不支持从其他线程关闭的连接（Windows 命名管道）为 None
+    pub fn watch_stream(&self, closer: Option<StreamCloser>) {
+        let closer = match closer {
+            Some(closer) => closer,
+            None => return,
+        };
+        // 登记前已开始关闭时直接关闭，避免错过唤醒
+        if self.lifecycle.is_shutting_down() {
+            closer.close();
+            return;
+        }
+        match self.lifecycle.streams.lock() {
+            Ok(mut streams) => {
+                streams.insert(self.id, closer);
+            }
+            Err(e) => error!("[错误] 获取连接表锁失败: {}", e),
+        }
+    }
+
+    // 连接断开后注销
+    pub fn unwatch_stream(&self) {
+        if let Ok(mut streams) = self.lifecycle.streams.lock() {
+            streams.remove(&self.id);
+        }
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.unwatch_stream();
+        if let Ok(mut tasks) = self.lifecycle.tasks.lock() {
+            tasks.remove(&self.id);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// 日志初始化
+// 控制台输出统一走 log 门面，级别与消息中的分类标签对应：[错误] → error，[警告] → warn，
+// [信息]/[重要] → info，[调试]/[状态机]/[TTS音频] → debug。标签保留在消息开头，便于按类别检索。
+// 日志目标是模块路径（crate 名 + 模块名），RUST_LOG 中以 lumina 开头的指令会改写为本 crate 的路径，
+// 因此 RUST_LOG=lumina=warn 只保留警告和错误，RUST_LOG=lumina=debug 打开 VAD 状态转移等调试输出，
+// 也可以按模块细分，例如 RUST_LOG=lumina=info,lumina::config=debug。
+// 未设置 RUST_LOG 时本 crate 输出 info 及以上，依赖库只输出 warn 及以上。
+
+// 以 lumina 开头的指令改写时使用的 crate 名
+const CRATE_TARGET: &str = env!("CARGO_CRATE_NAME");
+// RUST_LOG 中代表本 crate 的别名
+const TARGET_ALIAS: &str = "lumina";
+
+pub fn init() {
+    let filters = match std::env::var("RUST_LOG") {
+        Ok(spec) if !spec.trim().is_empty() => rewrite_filters(&spec),
+        _ => format!("warn,{}=info", CRATE_TARGET),
+    };
+    // 重复初始化（例如被其他入口先初始化过）时保留已有的 logger
+    let _ = env_logger::Builder::new()
+        .parse_filters(&filters)
+        .format_timestamp_millis()
+        .try_init();
+}
+
+// 把 lumina、lumina::xxx、lumina=level 形式的指令改写为本 crate 的模块路径；
+// 其余指令（包括 lumina_macros 这类仅前缀相同的名字）原样保留
+fn rewrite_filters(spec: &str) -> String {
+    spec.split(',')
+        .map(|directive| {
+            let trimmed = directive.trim();
+            match trimmed.strip_prefix(TARGET_ALIAS) {
+                Some(rest) if rest.is_empty() || rest.starts_with("::") || rest.starts_with('=') => {
+                    format!("{}{}", CRATE_TARGET, rest)
+                }
+                _ => trimmed.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -0,0 +1,130 @@
+// VAD 判定前瞻（look-ahead）
+// webrtc_vad 逐帧即时判定，判定语音开始时首音素的起始往往已经过去。帧在送往状态机与后端之前先经过一条 K 帧的延迟线，
+// 判定语音开始时，延迟线里尚未送出的前 K 帧一并标记为语音，语音起始因此整体提前 K 帧，代价是下游固定延迟 K 帧。
+// 只在语音开始时回补，单个误判为语音的噪声帧不会把前面的帧一起带成语音。
+// K 为 0 时帧原样直通，语音开始前的音频仍只由前置上下文补齐。
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// 延迟线上的一帧
+pub struct DelayedFrame {
+    pub samples: Vec<i16>,
+    pub captured_at: Instant, // 该帧在本地时钟上的采集时刻，送出时沿用
+    pub is_voice: bool,
+}
+
+pub struct LookAhead {
+    frames: VecDeque<DelayedFrame>,
+    depth: usize,
+}
+
+impl LookAhead {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            depth,
+        }
+    }
+
+    // 修改延迟帧数；变小时多出的帧在下一次 push 时一并送出
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+    }
+
+    // 放入一帧，返回按顺序可以送往下游的帧；speech_start 表示这一帧判定了语音开始
+    pub fn push(&mut self, samples: Vec<i16>, captured_at: Instant, is_voice: bool, speech_start: bool) -> Vec<DelayedFrame> {
+        if speech_start {
+            for frame in self.frames.iter_mut() {
+                frame.is_voice = true;
+            }
+        }
+        self.frames.push_back(DelayedFrame {
+            samples,
+            captured_at,
+            is_voice,
+        });
+
+        let ready = self.frames.len().saturating_sub(self.depth);
+        self.frames.drain(..ready).collect()
+    }
+
+    // 取出尚未送出的帧（停止处理时由调用方按需发送）
+    pub fn drain(&mut self) -> Vec<DelayedFrame> {
+        self.frames.drain(..).collect()
+    }
+
+    // 丢弃尚未送出的帧（停止处理或采样率变化时）
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每帧样本值即帧序号，便于核对顺序
+    fn frame(index: i16) -> Vec<i16> {
+        vec![index; 4]
+    }
+
+    // 首音素能量低、VAD 未判为语音的帧，在语音开始时应随延迟线一并作为语音送出
+    #[test]
+    fn speech_start_releases_onset_frames_as_voice() {
+        let mut look_ahead = LookAhead::new(3);
+        let now = Instant::now();
+        let mut out = Vec::new();
+
+        // 0、1 为静音，2、3、4 为首音素起始（VAD 仍判为非语音），5 判定语音开始
+        for index in 0..5 {
+            out.extend(look_ahead.push(frame(index), now, false, false));
+        }
+        out.extend(look_ahead.push(frame(5), now, true, true));
+        for index in 6..9 {
+            out.extend(look_ahead.push(frame(index), now, true, false));
+        }
+
+        let order: Vec<i16> = out.iter().map(|f| f.samples[0]).collect();
+        assert_eq!(order, vec![0, 1, 2, 3, 4, 5]);
+        let voiced: Vec<bool> = out.iter().map(|f| f.is_voice).collect();
+        assert_eq!(voiced, vec![false, false, true, true, true, true]);
+    }
+
+    // 没有语音开始时，单个误判为语音的帧不会把前面的帧带成语音
+    #[test]
+    fn isolated_voice_frame_does_not_backfill() {
+        let mut look_ahead = LookAhead::new(2);
+        let now = Instant::now();
+        let mut out = Vec::new();
+        out.extend(look_ahead.push(frame(0), now, false, false));
+        out.extend(look_ahead.push(frame(1), now, false, false));
+        out.extend(look_ahead.push(frame(2), now, true, false));
+        out.extend(look_ahead.push(frame(3), now, false, false));
+        out.extend(look_ahead.push(frame(4), now, false, false));
+
+        let voiced: Vec<(i16, bool)> = out.iter().map(|f| (f.samples[0], f.is_voice)).collect();
+        assert_eq!(voiced, vec![(0, false), (1, false), (2, true)]);
+    }
+
+    #[test]
+    fn zero_depth_passes_frames_through() {
+        let mut look_ahead = LookAhead::new(0);
+        let out = look_ahead.push(frame(7), Instant::now(), false, false);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].samples, frame(7));
+    }
+
+    // 缩小延迟时积压的帧按顺序一次送出，不丢帧
+    #[test]
+    fn shrinking_depth_flushes_in_order() {
+        let mut look_ahead = LookAhead::new(4);
+        let now = Instant::now();
+        for index in 0..4 {
+            assert!(look_ahead.push(frame(index), now, false, false).is_empty());
+        }
+        look_ahead.set_depth(1);
+        let order: Vec<i16> = look_ahead.push(frame(4), now, false, false).iter().map(|f| f.samples[0]).collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,510 @@
+// 运行时指标
+// 同时维护自启动以来的累计值和最近 METRICS_WINDOW_SECS 秒的滑动窗口值，
+// 窗口由按秒划分的环形桶实现，过期的桶在写入时被复用。
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+pub const METRICS_WINDOW_SECS: u64 = 10;
+// 相邻两帧间隔超过该值时视为抖动告警（正常约20ms一帧）
+pub const FRAME_JITTER_WARNING_MS: f64 = 100.0;
+// 相邻两帧间隔超过该值时视为采集暂停/重启，不计入抖动统计
+const FRAME_GAP_RESET_MS: f64 = 2000.0;
+// 单帧处理耗时超过该值视为慢帧（一帧音频约20ms）
+pub const SLOW_FRAME_WARNING_MS: f64 = 10.0;
+// 计算耗时分位数所用的最近帧数
+const FRAME_TIMING_SAMPLES: usize = 1000;
+// 单帧处理耗时直方图的桶上界（毫秒），最后一个桶收纳超过最大上界的帧
+pub const FRAME_TIMING_BUCKETS_MS: [f64; 7] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+pub const FRAME_TIMING_BUCKET_COUNT: usize = FRAME_TIMING_BUCKETS_MS.len() + 1;
+// 首响应延迟直方图的桶上界（毫秒），最后一个桶收纳超过最大上界的会话
+pub const FIRST_RESPONSE_BUCKETS_MS: [f64; 8] = [200.0, 300.0, 500.0, 750.0, 1000.0, 1500.0, 2000.0, 3000.0];
+// 计算首响应延迟分位数所用的最近会话数（每个交互模式分别保留）
+const FIRST_RESPONSE_SAMPLES: usize = 200;
+
+// 一组原始计数
+#[derive(Default, Clone, Copy, Debug)]
+struct Counters {
+    frames_processed: u64,
+    voice_frames: u64,
+    frames_dropped: u64,
+    frames_sent: u64,
+    send_failures: u64,
+    fallback_frames: u64, // webrtc_vad出错、按能量判定的帧
+    duplicate_frames: u64, // 帧序号重复或回退、被跳过的帧
+}
+
+impl Counters {
+    fn add(&mut self, other: &Counters) {
+        self.frames_processed += other.frames_processed;
+        self.voice_frames += other.voice_frames;
+        self.frames_dropped += other.frames_dropped;
+        self.frames_sent += other.frames_sent;
+        self.send_failures += other.send_failures;
+        self.fallback_frames += other.fallback_frames;
+        self.duplicate_frames += other.duplicate_frames;
+    }
+}
+
+// 环形桶中的一秒
+#[derive(Default, Clone, Copy, Debug)]
+struct Bucket {
+    second: u64, // 该桶对应的秒序号（自启动起）
+    counters: Counters,
+}
+
+// 对外返回的指标快照
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MetricsSnapshot {
+    pub duration_secs: f64,
+    pub frames_processed: u64,
+    pub voice_frames: u64,
+    pub frames_dropped: u64,
+    pub frames_sent: u64,
+    pub send_failures: u64,
+    #[serde(default)]
+    pub fallback_frames: u64,   // 按能量兜底判定的帧数
+    #[serde(default)]
+    pub duplicate_frames: u64,  // 前端重发（帧序号重复或回退）而被跳过的帧数
+    pub frame_rate: f64,        // 每秒处理帧数
+    pub voice_ratio: f64,       // 语音帧占比
+    pub send_success_rate: f64, // 发送成功率
+    pub drop_rate: f64,         // 丢帧率
+}
+
+// 相邻 process_audio_frame 调用间隔的统计
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrameIntervalSnapshot {
+    pub intervals: u64,
+    pub mean_ms: f64,
+    pub jitter_ms: f64, // 间隔标准差
+    pub max_ms: f64,
+    pub warnings: u64,  // 超过 FRAME_JITTER_WARNING_MS 的次数
+}
+
+// 单帧处理耗时分布（基于最近 FRAME_TIMING_SAMPLES 帧，max 与慢帧数为累计值）
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrameTimingSnapshot {
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub slow_frames: u64,
+    pub slow_threshold_ms: f64,
+    #[serde(default)]
+    pub histogram: Vec<u64>, // 累计耗时直方图，第i项为不超过 FRAME_TIMING_BUCKETS_MS[i] 的帧数，末项为更慢的帧
+}
+
+// 单次慢帧告警的内容
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SlowFrameWarning {
+    pub elapsed_ms: f64,
+    pub threshold_ms: f64,
+}
+
+// 单次抖动告警的内容
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrameJitterWarning {
+    pub interval_ms: f64,
+    pub mean_ms: f64,
+    pub jitter_ms: f64,
+    pub threshold_ms: f64,
+}
+
+// 某一交互模式下的首响应延迟分布（送出第一帧音频到收到第一个非空识别文本）；
+// 分位数基于最近 FIRST_RESPONSE_SAMPLES 个会话，其余为累计值
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FirstResponseSnapshot {
+    pub mode: String,
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub max_ms: f64,
+    pub histogram: Vec<u64>, // 第i项为不超过 FIRST_RESPONSE_BUCKETS_MS[i] 的会话数，末项为更慢的会话
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MetricsReport {
+    pub cumulative: MetricsSnapshot,
+    pub window: MetricsSnapshot,
+    pub window_secs: u64,
+    pub frame_interval: FrameIntervalSnapshot,
+    pub frame_timing: FrameTimingSnapshot,
+    #[serde(default)]
+    pub first_response: Vec<FirstResponseSnapshot>, // 按交互模式分别统计
+}
+
+// 帧间隔的在线统计（Welford算法）
+#[derive(Default)]
+struct IntervalStats {
+    last_frame_at: Option<Instant>,
+    count: u64,
+    mean: f64,
+    m2: f64,
+    max: f64,
+    warnings: u64,
+}
+
+impl IntervalStats {
+    fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        }
+    }
+}
+
+// 单帧处理耗时的统计
+#[derive(Default)]
+struct TimingStats {
+    recent_ms: VecDeque<f64>,
+    max_ms: f64,
+    slow_frames: u64,
+    histogram: [u64; FRAME_TIMING_BUCKET_COUNT],
+}
+
+// 单一交互模式下的首响应延迟统计
+#[derive(Default)]
+struct FirstResponseStats {
+    recent_ms: VecDeque<f64>,
+    count: u64,
+    total_ms: f64,
+    max_ms: f64,
+    histogram: [u64; FIRST_RESPONSE_BUCKETS_MS.len() + 1],
+}
+
+// 前端可选携带的帧序号，用于识别重发的同一帧
+#[derive(Default)]
+struct FrameSequence {
+    last_id: Option<u64>,
+    last_at: Option<Instant>,
+}
+
+pub struct Metrics {
+    started_at: Instant,
+    total: Counters,
+    buckets: [Bucket; METRICS_WINDOW_SECS as usize],
+    intervals: IntervalStats,
+    timing: TimingStats,
+    sequence: FrameSequence,
+    first_response: BTreeMap<String, FirstResponseStats>, // 键为交互模式名
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            total: Counters::default(),
+            buckets: [Bucket::default(); METRICS_WINDOW_SECS as usize],
+            intervals: IntervalStats::default(),
+            timing: TimingStats::default(),
+            sequence: FrameSequence::default(),
+            first_response: BTreeMap::new(),
+        }
+    }
+
+    // 核对帧序号：严格递增时接受；重复或回退时计为重复帧并返回 false。
+    // 距上一个带序号的帧超过 FRAME_GAP_RESET_MS 时视为采集重新开始（如页面刷新后序号从头计），任何序号都接受
+    pub fn accept_frame_id(&mut self, frame_id: u64, now: Instant) -> bool {
+        let restarted = self
+            .sequence
+            .last_at
+            .is_none_or(|last| now.saturating_duration_since(last).as_secs_f64() * 1000.0 > FRAME_GAP_RESET_MS);
+        let accepted = restarted || self.sequence.last_id.is_none_or(|last| frame_id > last);
+        if accepted {
+            self.sequence.last_id = Some(frame_id);
+            self.sequence.last_at = Some(now);
+        } else {
+            self.record(|c| c.duplicate_frames += 1);
+        }
+        accepted
+    }
+
+    // 记录一帧的处理耗时，超过慢帧阈值时返回告警
+    pub fn record_frame_timing(&mut self, elapsed: Duration) -> Option<SlowFrameWarning> {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        let timing = &mut self.timing;
+        if timing.recent_ms.len() >= FRAME_TIMING_SAMPLES {
+            timing.recent_ms.pop_front();
+        }
+        timing.recent_ms.push_back(elapsed_ms);
+        timing.max_ms = timing.max_ms.max(elapsed_ms);
+        let bucket = FRAME_TIMING_BUCKETS_MS
+            .iter()
+            .position(|bound| elapsed_ms <= *bound)
+            .unwrap_or(FRAME_TIMING_BUCKETS_MS.len());
+        timing.histogram[bucket] += 1;
+
+        if elapsed_ms > SLOW_FRAME_WARNING_MS {
+            timing.slow_frames += 1;
+            return Some(SlowFrameWarning {
+                elapsed_ms,
+                threshold_ms: SLOW_FRAME_WARNING_MS,
+            });
+        }
+        None
+    }
+
+    // 记录一帧的到达时刻，间隔超过阈值时返回告警
+    pub fn record_frame_arrival(&mut self, now: Instant) -> Option<FrameJitterWarning> {
+        let last = self.intervals.last_frame_at.replace(now)?;
+        let interval_ms = now.duration_since(last).as_secs_f64() * 1000.0;
+        if interval_ms > FRAME_GAP_RESET_MS {
+            // 采集暂停后重新开始，不是调度抖动
+            return None;
+        }
+
+        let stats = &mut self.intervals;
+        stats.count += 1;
+        let delta = interval_ms - stats.mean;
+        stats.mean += delta / stats.count as f64;
+        stats.m2 += delta * (interval_ms - stats.mean);
+        stats.max = stats.max.max(interval_ms);
+
+        if interval_ms > FRAME_JITTER_WARNING_MS {
+            stats.warnings += 1;
+            return Some(FrameJitterWarning {
+                interval_ms,
+                mean_ms: stats.mean,
+                jitter_ms: stats.std_dev(),
+                threshold_ms: FRAME_JITTER_WARNING_MS,
+            });
+        }
+        None
+    }
+
+    // 记录一帧处理结果；dropped 表示该帧未能得出VAD结论而被丢弃
+    pub fn record_frame(&mut self, is_voice: bool, dropped: bool) {
+        self.record(|c| {
+            if dropped {
+                c.frames_dropped += 1;
+            } else {
+                c.frames_processed += 1;
+                if is_voice {
+                    c.voice_frames += 1;
+                }
+            }
+        });
+    }
+
+    // 记录一帧由能量兜底判定（webrtc_vad出错）
+    pub fn record_fallback_frame(&mut self) {
+        self.record(|c| c.fallback_frames += 1);
+    }
+
+    // 记录一次向后端发送音频帧的结果
+    pub fn record_send(&mut self, success: bool) {
+        self.record(|c| {
+            if success {
+                c.frames_sent += 1;
+            } else {
+                c.send_failures += 1;
+            }
+        });
+    }
+
+    // 记录一次会话的首响应延迟，按交互模式分别统计
+    pub fn record_first_response(&mut self, mode: &str, latency_ms: f64) {
+        let stats = self.first_response.entry(mode.to_string()).or_default();
+        if stats.recent_ms.len() >= FIRST_RESPONSE_SAMPLES {
+            stats.recent_ms.pop_front();
+        }
+        stats.recent_ms.push_back(latency_ms);
+        stats.count += 1;
+        stats.total_ms += latency_ms;
+        stats.max_ms = stats.max_ms.max(latency_ms);
+        let bucket = FIRST_RESPONSE_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(FIRST_RESPONSE_BUCKETS_MS.len());
+        stats.histogram[bucket] += 1;
+    }
+
+    // 累计的单帧处理耗时直方图，桶划分见 FRAME_TIMING_BUCKETS_MS
+    pub fn timing_histogram(&self) -> [u64; FRAME_TIMING_BUCKET_COUNT] {
+        self.timing.histogram
+    }
+
+    pub fn report(&self) -> MetricsReport {
+        let now_second = self.current_second();
+
+        let mut window = Counters::default();
+        for bucket in self.buckets.iter() {
+            if now_second - bucket.second < METRICS_WINDOW_SECS {
+                window.add(&bucket.counters);
+            }
+        }
+
+        // 启动未满一个窗口时按实际时长计算速率
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let window_duration = elapsed.min(METRICS_WINDOW_SECS as f64);
+
+        MetricsReport {
+            cumulative: snapshot(&self.total, elapsed),
+            window: snapshot(&window, window_duration),
+            window_secs: METRICS_WINDOW_SECS,
+            frame_interval: FrameIntervalSnapshot {
+                intervals: self.intervals.count,
+                mean_ms: self.intervals.mean,
+                jitter_ms: self.intervals.std_dev(),
+                max_ms: self.intervals.max,
+                warnings: self.intervals.warnings,
+            },
+            frame_timing: self.timing_snapshot(),
+            first_response: self.first_response_snapshots(),
+        }
+    }
+
+    fn first_response_snapshots(&self) -> Vec<FirstResponseSnapshot> {
+        self.first_response
+            .iter()
+            .map(|(mode, stats)| {
+                let mut sorted: Vec<f64> = stats.recent_ms.iter().copied().collect();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                FirstResponseSnapshot {
+                    mode: mode.clone(),
+                    count: stats.count,
+                    mean_ms: if stats.count > 0 { stats.total_ms / stats.count as f64 } else { 0.0 },
+                    p50_ms: percentile(&sorted, 0.50),
+                    p90_ms: percentile(&sorted, 0.90),
+                    max_ms: stats.max_ms,
+                    histogram: stats.histogram.to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    fn timing_snapshot(&self) -> FrameTimingSnapshot {
+        let mut sorted: Vec<f64> = self.timing.recent_ms.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        FrameTimingSnapshot {
+            samples: sorted.len(),
+            p50_ms: percentile(&sorted, 0.50),
+            p99_ms: percentile(&sorted, 0.99),
+            max_ms: self.timing.max_ms,
+            slow_frames: self.timing.slow_frames,
+            slow_threshold_ms: SLOW_FRAME_WARNING_MS,
+            histogram: self.timing.histogram.to_vec(),
+        }
+    }
+
+    fn current_second(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+
+    fn record<F: Fn(&mut Counters)>(&mut self, update: F) {
+        update(&mut self.total);
+
+        let second = self.current_second();
+        let bucket = &mut self.buckets[(second % METRICS_WINDOW_SECS) as usize];
+        if bucket.second != second {
+            // 桶已过期，复用为当前秒
+            *bucket = Bucket {
+                second,
+                counters: Counters::default(),
+            };
+        }
+        update(&mut bucket.counters);
+    }
+}
+
+// 已排序序列的分位数（最近秩法）
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+fn snapshot(counters: &Counters, duration_secs: f64) -> MetricsSnapshot {
+    let frame_rate = if duration_secs > 0.0 {
+        counters.frames_processed as f64 / duration_secs
+    } else {
+        0.0
+    };
+
+    MetricsSnapshot {
+        duration_secs,
+        frames_processed: counters.frames_processed,
+        voice_frames: counters.voice_frames,
+        frames_dropped: counters.frames_dropped,
+        frames_sent: counters.frames_sent,
+        send_failures: counters.send_failures,
+        fallback_frames: counters.fallback_frames,
+        duplicate_frames: counters.duplicate_frames,
+        frame_rate,
+        voice_ratio: ratio(counters.voice_frames, counters.frames_processed),
+        send_success_rate: ratio(
+            counters.frames_sent,
+            counters.frames_sent + counters.send_failures,
+        ),
+        drop_rate: ratio(
+            counters.frames_dropped,
+            counters.frames_processed + counters.frames_dropped,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 前端重发同一帧、或重发更早的帧时跳过并计数，之后的新帧照常接受
+    #[test]
+    fn duplicate_frame_ids_are_rejected_and_counted() {
+        let mut metrics = Metrics::new();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        assert!(metrics.accept_frame_id(1, at(0)));
+        assert!(metrics.accept_frame_id(2, at(20)));
+        assert!(!metrics.accept_frame_id(2, at(40)));
+        assert!(!metrics.accept_frame_id(1, at(60)));
+        assert!(metrics.accept_frame_id(3, at(80)));
+        // 序号可以跳过（前端丢帧），不算重复
+        assert!(metrics.accept_frame_id(7, at(100)));
+        assert!(!metrics.accept_frame_id(7, at(120)));
+
+        let report = metrics.report();
+        assert_eq!(report.cumulative.duplicate_frames, 3);
+        assert_eq!(report.window.duplicate_frames, 3);
+    }
+
+    // 重复帧不推进序号：连续重发多次都被跳过
+    #[test]
+    fn repeated_resubmission_stays_rejected() {
+        let mut metrics = Metrics::new();
+        let start = Instant::now();
+        assert!(metrics.accept_frame_id(10, start));
+        for i in 1..=5 {
+            assert!(!metrics.accept_frame_id(10, start + Duration::from_millis(i * 10)));
+        }
+        assert!(metrics.accept_frame_id(11, start + Duration::from_millis(100)));
+        assert_eq!(metrics.report().cumulative.duplicate_frames, 5);
+    }
+
+    // 间隔超过 FRAME_GAP_RESET_MS 视为采集重新开始，序号从头计也接受
+    #[test]
+    fn frame_ids_restart_after_capture_gap() {
+        let mut metrics = Metrics::new();
+        let start = Instant::now();
+        assert!(metrics.accept_frame_id(500, start));
+        assert!(!metrics.accept_frame_id(1, start + Duration::from_millis(FRAME_GAP_RESET_MS as u64)));
+        assert!(metrics.accept_frame_id(1, start + Duration::from_millis(FRAME_GAP_RESET_MS as u64 + 1)));
+        assert!(metrics.accept_frame_id(2, start + Duration::from_millis(FRAME_GAP_RESET_MS as u64 + 21)));
+        assert_eq!(metrics.report().cumulative.duplicate_frames, 1);
+    }
+}
@@ -0,0 +1,443 @@
+// 参数解析层
+// 所有时序/阈值参数统一按 "默认值 → 交互模式预设 → 用户配置 → profile 倍率 → 后端 hint 覆盖" 的顺序合成生效值，
+// 并保留每个参数的来源链，便于排障时回答"现在到底用的是多少"。
+// 时长类参数一律以毫秒表达，下发时按当前实际帧长换算为帧数，采样率或帧长变化时时间语义保持不变。
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::{
+    DEFAULT_ENERGY_FALLBACK_THRESHOLD, DEFAULT_LOOKAHEAD_MS, DEFAULT_MIN_SPEECH_MS, DEFAULT_PRE_CONTEXT_MS, DEFAULT_SPEECH_END_SILENCE_MS, DEFAULT_SPEECH_START_MS,
+    DEFAULT_WAITING_SILENCE_MS, FINALIZE_TIMEOUT_MS, SILENCE_REPORT_INTERVAL_MS,
+    MAX_LOOKAHEAD_MS, TRANSITION_BUFFER_TIMEOUT_MS,
+};
+
+// 旧版按帧数表达的参数名以每帧20ms换算为毫秒
+const LEGACY_FRAME_MS: f64 = 20.0;
+
+// 可合成的参数
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamKey {
+    SpeechStartMs,           // VAD判定语音开始所需的连续语音时长
+    SpeechEndSilenceMs,      // VAD判定语音结束所需的连续静音时长
+    WaitingMs,               // 状态机从说话中进入等待中所需的连续静音时长
+    TransitionTimeoutMs,     // 临界转移状态超时时间
+    PreContextMs,            // 语音开始前补发的前置上下文时长
+    SilenceReportIntervalMs, // 静音事件上报间隔
+    FinalizeTimeoutMs,       // 请求最终识别后等待结果的超时时间
+    EnergyFallbackThreshold, // webrtc_vad出错时按能量判定语音的RMS阈值，也是能量后端的最低阈值（0~1）
+    LookaheadMs,             // VAD判定前瞻时长，语音开始时回补的音频时长，0为不延迟
+    MinSpeechMs,             // 语音持续该时长后才向状态机报告为语音，滤掉按键声、咳嗽等短促声响，0为不过滤
+}
+
+pub const ALL_PARAM_KEYS: [ParamKey; 10] = [
+    ParamKey::SpeechStartMs,
+    ParamKey::SpeechEndSilenceMs,
+    ParamKey::WaitingMs,
+    ParamKey::TransitionTimeoutMs,
+    ParamKey::PreContextMs,
+    ParamKey::SilenceReportIntervalMs,
+    ParamKey::FinalizeTimeoutMs,
+    ParamKey::EnergyFallbackThreshold,
+    ParamKey::LookaheadMs,
+    ParamKey::MinSpeechMs,
+];
+
+impl ParamKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            ParamKey::SpeechStartMs => "speech_start_ms",
+            ParamKey::SpeechEndSilenceMs => "speech_end_silence_ms",
+            ParamKey::WaitingMs => "waiting_ms",
+            ParamKey::TransitionTimeoutMs => "transition_timeout_ms",
+            ParamKey::PreContextMs => "pre_context_ms",
+            ParamKey::SilenceReportIntervalMs => "silence_report_interval_ms",
+            ParamKey::FinalizeTimeoutMs => "finalize_timeout_ms",
+            ParamKey::EnergyFallbackThreshold => "energy_fallback_threshold",
+            ParamKey::LookaheadMs => "lookahead_ms",
+            ParamKey::MinSpeechMs => "min_speech_ms",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALL_PARAM_KEYS.iter().copied().find(|key| key.name() == name)
+    }
+
+    // 旧版按帧数表达的参数名，仅用于兼容已有的配置文件与后端hint
+    fn from_legacy_frames_name(name: &str) -> Option<Self> {
+        match name {
+            "speech_start_frames" => Some(ParamKey::SpeechStartMs),
+            "speech_end_silence_frames" => Some(ParamKey::SpeechEndSilenceMs),
+            "waiting_silence_frames" => Some(ParamKey::WaitingMs),
+            "pre_context_frames" => Some(ParamKey::PreContextMs),
+            "lookahead_frames" => Some(ParamKey::LookaheadMs),
+            _ => None,
+        }
+    }
+
+    pub fn default_value(self) -> f64 {
+        match self {
+            ParamKey::SpeechStartMs => DEFAULT_SPEECH_START_MS as f64,
+            ParamKey::SpeechEndSilenceMs => DEFAULT_SPEECH_END_SILENCE_MS as f64,
+            ParamKey::WaitingMs => DEFAULT_WAITING_SILENCE_MS as f64,
+            ParamKey::TransitionTimeoutMs => TRANSITION_BUFFER_TIMEOUT_MS as f64,
+            ParamKey::PreContextMs => DEFAULT_PRE_CONTEXT_MS as f64,
+            ParamKey::SilenceReportIntervalMs => SILENCE_REPORT_INTERVAL_MS as f64,
+            ParamKey::FinalizeTimeoutMs => FINALIZE_TIMEOUT_MS as f64,
+            ParamKey::EnergyFallbackThreshold => DEFAULT_ENERGY_FALLBACK_THRESHOLD as f64,
+            ParamKey::LookaheadMs => DEFAULT_LOOKAHEAD_MS as f64,
+            ParamKey::MinSpeechMs => DEFAULT_MIN_SPEECH_MS as f64,
+        }
+    }
+
+    // profile 倍率只作用于"等待多久"类的时长参数
+    pub fn scales_with_profile(self) -> bool {
+        matches!(
+            self,
+            ParamKey::SpeechEndSilenceMs
+                | ParamKey::WaitingMs
+                | ParamKey::TransitionTimeoutMs
+                | ParamKey::FinalizeTimeoutMs
+        )
+    }
+}
+
+// 交互模式：按使用场景预设语音结束相关的阈值。
+// 预设位于默认值之上、用户配置之下，用户显式设置过的参数不受模式切换影响
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InteractionMode {
+    Command,      // 短指令：0.5秒静音即结束
+    Conversation, // 对话：允许1.5秒的思考停顿
+    Dictation,    // 听写：长段口述，停顿3秒才结束
+}
+
+impl InteractionMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "command" => Some(InteractionMode::Command),
+            "conversation" => Some(InteractionMode::Conversation),
+            "dictation" => Some(InteractionMode::Dictation),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InteractionMode::Command => "command",
+            InteractionMode::Conversation => "conversation",
+            InteractionMode::Dictation => "dictation",
+        }
+    }
+
+    // 语音结束静音时长、进入等待的静音时长（停止发送前的拖尾）、等待识别文本确认的超时，单位均为毫秒
+    pub fn preset(self, key: ParamKey) -> Option<f64> {
+        let (speech_end, waiting, transition_ms) = match self {
+            InteractionMode::Command => (500.0, 100.0, 300.0),
+            InteractionMode::Conversation => (1500.0, 300.0, 500.0),
+            InteractionMode::Dictation => (3000.0, 500.0, 800.0),
+        };
+        match key {
+            ParamKey::SpeechEndSilenceMs => Some(speech_end),
+            ParamKey::WaitingMs => Some(waiting),
+            ParamKey::TransitionTimeoutMs => Some(transition_ms),
+            _ => None,
+        }
+    }
+}
+
+// 时序 profile：对时长类参数整体乘以倍率（例如说话较慢的用户放宽停顿）
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TimingProfile {
+    pub name: String,
+    pub multiplier: f64,
+}
+
+// 参数来源链中的一环
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "layer", rename_all = "snake_case")]
+pub enum ParamSource {
+    Default { value: f64 },
+    InteractionMode { mode: InteractionMode, value: f64 },
+    User { value: f64 },
+    Profile { name: String, multiplier: f64, value: f64 },
+    BackendHint { value: f64 },
+}
+
+impl ParamSource {
+    pub fn layer_name(&self) -> &'static str {
+        match self {
+            ParamSource::Default { .. } => "default",
+            ParamSource::InteractionMode { .. } => "interaction_mode",
+            ParamSource::User { .. } => "user",
+            ParamSource::Profile { .. } => "profile",
+            ParamSource::BackendHint { .. } => "backend_hint",
+        }
+    }
+}
+
+// 单个参数的生效值及来源链（按合成顺序）
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EffectiveParameter {
+    pub key: ParamKey,
+    pub value: f64,
+    pub sources: Vec<ParamSource>,
+}
+
+// 各层的原始输入
+#[derive(Default, Clone, Debug)]
+pub struct ParameterLayers {
+    pub mode: Option<InteractionMode>,
+    pub user: BTreeMap<ParamKey, f64>,
+    pub profile: Option<TimingProfile>,
+    pub hints: BTreeMap<ParamKey, f64>,
+    pub hint_expiry: BTreeMap<ParamKey, Instant>, // 带有效期的hint到期时刻，到期后撤销回落到下层
+}
+
+impl ParameterLayers {
+    // 撤销已到期的hint，返回被撤销的参数
+    pub fn expire_hints(&mut self, now: Instant) -> Vec<ParamKey> {
+        let expired: Vec<ParamKey> = self
+            .hint_expiry
+            .iter()
+            .filter(|(_, &expires_at)| expires_at <= now)
+            .map(|(&key, _)| key)
+            .collect();
+        for key in &expired {
+            self.hint_expiry.remove(key);
+            self.hints.remove(key);
+        }
+        expired
+    }
+}
+
+// 合成单个参数
+pub fn resolve_param(layers: &ParameterLayers, key: ParamKey) -> EffectiveParameter {
+    let mut value = key.default_value();
+    let mut sources = vec![ParamSource::Default { value }];
+
+    if let Some(mode) = layers.mode {
+        if let Some(preset) = mode.preset(key) {
+            value = preset;
+            sources.push(ParamSource::InteractionMode { mode, value });
+        }
+    }
+
+    if let Some(&user_value) = layers.user.get(&key) {
+        value = user_value;
+        sources.push(ParamSource::User { value });
+    }
+
+    if let Some(profile) = &layers.profile {
+        if key.scales_with_profile() {
+            value *= profile.multiplier;
+            sources.push(ParamSource::Profile {
+                name: profile.name.clone(),
+                multiplier: profile.multiplier,
+                value,
+            });
+        }
+    }
+
+    // 后端 hint 是针对当前对话的直接覆盖，优先级最高，不再乘倍率
+    if let Some(&hint_value) = layers.hints.get(&key) {
+        value = hint_value;
+        sources.push(ParamSource::BackendHint { value });
+    }
+
+    EffectiveParameter { key, value, sources }
+}
+
+// 合成全部参数
+pub fn resolve_all(layers: &ParameterLayers) -> Vec<EffectiveParameter> {
+    ALL_PARAM_KEYS
+        .iter()
+        .map(|&key| resolve_param(layers, key))
+        .collect()
+}
+
+// 按名称查找参数，返回参数及其数值换算到毫秒的倍率；旧版帧数参数名按每帧20ms换算
+pub fn lookup(name: &str) -> Option<(ParamKey, f64)> {
+    if let Some(key) = ParamKey::from_name(name) {
+        return Some((key, 1.0));
+    }
+    ParamKey::from_legacy_frames_name(name).map(|key| (key, LEGACY_FRAME_MS))
+}
+
+// 毫秒换算为给定帧长下的帧数，向上取整保证实际时长不短于设定值（浮点误差内的整除不进位）
+pub fn ms_to_frames(ms: f64, frame_ms: f64) -> usize {
+    if frame_ms <= 0.0 {
+        return 0;
+    }
+    (ms / frame_ms - 1e-6).ceil().max(0.0) as usize
+}
+
+// 帧数换算为给定帧长下的毫秒数
+pub fn frames_to_ms(frames: usize, frame_ms: f64) -> f64 {
+    frames as f64 * frame_ms
+}
+
+// 校验参数值：必须为正的有限数（前瞻时长与最短语音时长可以为0），归一化阈值不超过1
+pub fn validate_value(key: ParamKey, value: f64) -> Result<(), String> {
+    if key == ParamKey::LookaheadMs {
+        if !value.is_finite() || value < 0.0 || value > MAX_LOOKAHEAD_MS as f64 {
+            return Err(format!("参数 {} 的值必须在0~{}ms之间: {}", key.name(), MAX_LOOKAHEAD_MS, value));
+        }
+        return Ok(());
+    }
+    if key == ParamKey::MinSpeechMs {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("参数 {} 的值不能为负数: {}", key.name(), value));
+        }
+        return Ok(());
+    }
+    if !value.is_finite() || value <= 0.0 {
+        return Err(format!("参数 {} 的值必须为正数: {}", key.name(), value));
+    }
+    if key == ParamKey::EnergyFallbackThreshold && value > 1.0 {
+        return Err(format!("参数 {} 的值不能超过1: {}", key.name(), value));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 用户配置、profile 倍率、后端 hint、期望生效值、期望来源链
+    type PrecedenceCase = (Option<f64>, Option<f64>, Option<f64>, f64, &'static [&'static str]);
+
+    fn layers(user: Option<f64>, profile: Option<f64>, hint: Option<f64>) -> ParameterLayers {
+        let key = ParamKey::SpeechEndSilenceMs;
+        let mut layers = ParameterLayers::default();
+        if let Some(value) = user {
+            layers.user.insert(key, value);
+        }
+        if let Some(multiplier) = profile {
+            layers.profile = Some(TimingProfile { name: "slow".to_string(), multiplier });
+        }
+        if let Some(value) = hint {
+            layers.hints.insert(key, value);
+        }
+        layers
+    }
+
+    // 默认值 → 用户配置 → profile 倍率 → 后端 hint，逐层覆盖，来源链按合成顺序记录
+    #[test]
+    fn layers_apply_in_precedence_order() {
+        let default = ParamKey::SpeechEndSilenceMs.default_value();
+        let cases: [PrecedenceCase; 6] = [
+            (None, None, None, default, &["default"]),
+            (Some(800.0), None, None, 800.0, &["default", "user"]),
+            (None, Some(2.0), None, default * 2.0, &["default", "profile"]),
+            (Some(800.0), Some(1.5), None, 1200.0, &["default", "user", "profile"]),
+            (Some(800.0), None, Some(400.0), 400.0, &["default", "user", "backend_hint"]),
+            // hint 是直接覆盖，不再乘 profile 倍率
+            (Some(800.0), Some(1.5), Some(400.0), 400.0, &["default", "user", "profile", "backend_hint"]),
+        ];
+
+        for (user, profile, hint, expected, chain) in cases {
+            let resolved = resolve_param(&layers(user, profile, hint), ParamKey::SpeechEndSilenceMs);
+            assert_eq!(resolved.value, expected, "user={:?} profile={:?} hint={:?}", user, profile, hint);
+            let layer_names: Vec<&str> = resolved.sources.iter().map(|source| source.layer_name()).collect();
+            assert_eq!(layer_names, chain);
+        }
+    }
+
+    // 交互模式预设位于默认值与用户配置之间
+    #[test]
+    fn interaction_mode_sits_between_default_and_user() {
+        let mut layers = ParameterLayers { mode: Some(InteractionMode::Command), ..Default::default() };
+        assert_eq!(resolve_param(&layers, ParamKey::SpeechEndSilenceMs).value, 500.0);
+
+        layers.user.insert(ParamKey::SpeechEndSilenceMs, 900.0);
+        let resolved = resolve_param(&layers, ParamKey::SpeechEndSilenceMs);
+        assert_eq!(resolved.value, 900.0);
+        let layer_names: Vec<&str> = resolved.sources.iter().map(|source| source.layer_name()).collect();
+        assert_eq!(layer_names, ["default", "interaction_mode", "user"]);
+    }
+
+    // profile 倍率不作用于非时长类参数
+    #[test]
+    fn profile_skips_non_duration_params() {
+        let layers = layers(None, Some(3.0), None);
+        let resolved = resolve_param(&layers, ParamKey::EnergyFallbackThreshold);
+        assert_eq!(resolved.value, ParamKey::EnergyFallbackThreshold.default_value());
+        assert_eq!(resolved.sources.len(), 1);
+    }
+
+    // 各采样率下常见的帧长（样本数, 采样率）换算出的毫秒帧长
+    fn frame_lengths_ms() -> Vec<f64> {
+        [(160, 8000), (320, 16000), (160, 16000), (480, 16000), (512, 16000), (480, 48000), (1440, 48000), (441, 44100)]
+            .iter()
+            .map(|&(samples, rate)| samples as f64 * 1000.0 / rate as f64)
+            .collect()
+    }
+
+    #[test]
+    fn ms_to_frames_rounds_up() {
+        assert_eq!(ms_to_frames(500.0, 20.0), 25);
+        assert_eq!(ms_to_frames(500.0, 10.0), 50);
+        assert_eq!(ms_to_frames(500.0, 30.0), 17);
+        assert_eq!(ms_to_frames(40.0, 32.0), 2);
+        assert_eq!(ms_to_frames(1.0, 20.0), 1);
+        assert_eq!(ms_to_frames(0.0, 20.0), 0);
+        // 帧长无效时不换算
+        assert_eq!(ms_to_frames(500.0, 0.0), 0);
+        assert_eq!(ms_to_frames(500.0, -20.0), 0);
+    }
+
+    // 浮点误差内的整除不进位：0.1 的倍数等不能精确表示的时长也换算出整帧数
+    #[test]
+    fn exact_multiples_do_not_round_up() {
+        assert_eq!(ms_to_frames(0.3 * 1000.0, 10.0), 30);
+        assert_eq!(ms_to_frames(60.000000001, 20.0), 3);
+        assert_eq!(ms_to_frames(1000.0 / 3.0, 1000.0 / 3.0 / 7.0), 7);
+    }
+
+    // 任意帧长下：换算出的帧数对应的时长不短于设定值，且多出的不到一帧
+    #[test]
+    fn converted_duration_never_shorter_than_setting() {
+        for frame_ms in frame_lengths_ms() {
+            for ms in (0..=5000).step_by(7).map(|ms| ms as f64) {
+                let actual = frames_to_ms(ms_to_frames(ms, frame_ms), frame_ms);
+                assert!(actual >= ms - 1e-6, "{}ms @ {}ms/帧 -> {}ms", ms, frame_ms, actual);
+                assert!(actual < ms + frame_ms, "{}ms @ {}ms/帧 -> {}ms", ms, frame_ms, actual);
+            }
+        }
+    }
+
+    // 帧数 → 毫秒 → 帧数 保持不变
+    #[test]
+    fn frames_round_trip_through_ms() {
+        for frame_ms in frame_lengths_ms() {
+            for frames in 0..=300 {
+                assert_eq!(ms_to_frames(frames_to_ms(frames, frame_ms), frame_ms), frames, "{}帧 @ {}ms/帧", frames, frame_ms);
+            }
+        }
+    }
+
+    // 采样率或帧长变化后，同一毫秒设定的实际时长保持不变（误差不到一帧）
+    #[test]
+    fn time_semantics_survive_frame_length_changes() {
+        let setting = ParamKey::SpeechEndSilenceMs.default_value();
+        for frame_ms in frame_lengths_ms() {
+            let actual = frames_to_ms(ms_to_frames(setting, frame_ms), frame_ms);
+            assert!((actual - setting).abs() < frame_ms, "{}ms/帧 -> {}ms", frame_ms, actual);
+        }
+    }
+
+    // 旧版帧数参数名按每帧 20ms 换算
+    #[test]
+    fn legacy_frame_names_convert_at_20ms() {
+        assert_eq!(lookup("speech_end_silence_ms"), Some((ParamKey::SpeechEndSilenceMs, 1.0)));
+        assert_eq!(lookup("speech_end_silence_frames"), Some((ParamKey::SpeechEndSilenceMs, LEGACY_FRAME_MS)));
+        assert_eq!(lookup("waiting_silence_frames"), Some((ParamKey::WaitingMs, LEGACY_FRAME_MS)));
+        let (key, scale) = lookup("speech_start_frames").unwrap();
+        assert_eq!(key, ParamKey::SpeechStartMs);
+        assert_eq!(ms_to_frames(2.0 * scale, crate::DEFAULT_FRAME_MS), 2);
+        assert_eq!(lookup("speech_end_frames"), None);
+    }
+}
@@ -0,0 +1,621 @@
+// 发送路径的音频处理链
+// 每个处理环节实现 AudioStage，Pipeline 按配置的顺序对发往后端的每一帧原地处理。
+// 环节可以带状态（滤波器历史、当前增益），状态跨帧保留；重新配置或采样率变化时整条链重建。
+// 重新配置时先校验并构建完整的新链再整体替换，任一环节非法时保留原有处理链。
+// VAD 判定使用未经处理链的音频（只经过判定前的高通滤波），处理链只影响后端收到的数据。
+// 高通/低通/自定义系数的二阶滤波环节也可以单独配置在 VAD 判定之前（见 VadProcessor::input_filters），
+// 此时判定与发往后端的音频都经过滤波。
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+// 各环节参数的默认值
+const DEFAULT_DC_REMOVAL_CUTOFF_HZ: f32 = 20.0;
+const DEFAULT_PREEMPHASIS_COEFFICIENT: f32 = 0.97;
+const DEFAULT_AGC_TARGET_RMS: f32 = 0.1; // 相对满幅
+const DEFAULT_AGC_MAX_GAIN: f32 = 10.0;
+const DEFAULT_LIMITER_THRESHOLD: f32 = 0.89; // 约 -1dBFS
+const DEFAULT_HIGH_PASS_CUTOFF_HZ: f32 = 80.0; // 去除工频以下的低频震动与嗡声
+const DEFAULT_LOW_PASS_CUTOFF_HZ: f32 = 7000.0;
+const DEFAULT_FILTER_Q: f32 = 0.707; // 巴特沃斯响应，通带无起伏
+// 截止频率上限相对采样率的比例，与奈奎斯特频率保持余量
+const MAX_CUTOFF_RATIO: f32 = 0.45;
+// AGC 增益下限
+const AGC_MIN_GAIN: f32 = 0.1;
+// RMS 低于该值视为静音，不调整增益，避免把底噪放大
+const AGC_SILENCE_RMS: f32 = 0.003;
+// 每帧向目标增益靠拢的比例：降增益快、升增益慢
+const AGC_ATTACK: f32 = 0.5;
+const AGC_RELEASE: f32 = 0.05;
+// 限幅器每帧的增益恢复量
+const LIMITER_RELEASE: f32 = 0.05;
+
+pub trait AudioStage: Send {
+    fn process(&mut self, samples: &mut [i16]);
+
+    // 带自适应增益的环节返回当前增益
+    fn current_gain(&self) -> Option<f32> {
+        None
+    }
+}
+
+// 单个环节的配置，JSON 形如 {"stage": "agc", "target_rms": 0.1}，省略的参数取默认值
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum StageConfig {
+    DcRemoval {
+        #[serde(default = "default_dc_removal_cutoff_hz")]
+        cutoff_hz: f32,
+    },
+    Agc {
+        #[serde(default = "default_agc_target_rms")]
+        target_rms: f32,
+        #[serde(default = "default_agc_max_gain")]
+        max_gain: f32,
+    },
+    Preemphasis {
+        #[serde(default = "default_preemphasis_coefficient")]
+        coefficient: f32,
+    },
+    Limiter {
+        #[serde(default = "default_limiter_threshold")]
+        threshold: f32,
+    },
+    HighPass {
+        #[serde(default = "default_high_pass_cutoff_hz")]
+        cutoff_hz: f32,
+        #[serde(default = "default_filter_q")]
+        q: f32,
+    },
+    LowPass {
+        #[serde(default = "default_low_pass_cutoff_hz")]
+        cutoff_hz: f32,
+        #[serde(default = "default_filter_q")]
+        q: f32,
+    },
+    // 直接给出归一化（a0 = 1）的二阶滤波系数
+    Biquad {
+        b0: f32,
+        b1: f32,
+        b2: f32,
+        a1: f32,
+        a2: f32,
+    },
+}
+
+fn default_dc_removal_cutoff_hz() -> f32 {
+    DEFAULT_DC_REMOVAL_CUTOFF_HZ
+}
+
+fn default_agc_target_rms() -> f32 {
+    DEFAULT_AGC_TARGET_RMS
+}
+
+fn default_agc_max_gain() -> f32 {
+    DEFAULT_AGC_MAX_GAIN
+}
+
+fn default_preemphasis_coefficient() -> f32 {
+    DEFAULT_PREEMPHASIS_COEFFICIENT
+}
+
+fn default_limiter_threshold() -> f32 {
+    DEFAULT_LIMITER_THRESHOLD
+}
+
+fn default_high_pass_cutoff_hz() -> f32 {
+    DEFAULT_HIGH_PASS_CUTOFF_HZ
+}
+
+fn default_low_pass_cutoff_hz() -> f32 {
+    DEFAULT_LOW_PASS_CUTOFF_HZ
+}
+
+fn default_filter_q() -> f32 {
+    DEFAULT_FILTER_Q
+}
+
+impl StageConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StageConfig::DcRemoval { .. } => "dc_removal",
+            StageConfig::Agc { .. } => "agc",
+            StageConfig::Preemphasis { .. } => "preemphasis",
+            StageConfig::Limiter { .. } => "limiter",
+            StageConfig::HighPass { .. } => "high_pass",
+            StageConfig::LowPass { .. } => "low_pass",
+            StageConfig::Biquad { .. } => "biquad",
+        }
+    }
+
+    // 线性滤波环节，可以放在 VAD 判定之前
+    pub fn is_filter(&self) -> bool {
+        matches!(self, StageConfig::HighPass { .. } | StageConfig::LowPass { .. } | StageConfig::Biquad { .. })
+    }
+
+    // 校验参数范围；与采样率相关的参数按当前采样率校验
+    fn validate(&self, sample_rate: u32) -> Result<(), String> {
+        let in_range = |value: f32, min: f32, max: f32, field: &str| {
+            if value.is_finite() && value > min && value <= max {
+                Ok(())
+            } else {
+                Err(format!("处理环节 {} 的参数 {} 超出范围 ({}, {}]: {}", self.name(), field, min, max, value))
+            }
+        };
+        match *self {
+            StageConfig::DcRemoval { cutoff_hz } => in_range(cutoff_hz, 0.0, sample_rate as f32 / 20.0, "cutoff_hz"),
+            StageConfig::Agc { target_rms, max_gain } => {
+                in_range(target_rms, 0.0, 1.0, "target_rms")?;
+                in_range(max_gain, AGC_MIN_GAIN, 100.0, "max_gain")
+            }
+            StageConfig::Preemphasis { coefficient } => in_range(coefficient, 0.0, 1.0, "coefficient"),
+            StageConfig::Limiter { threshold } => in_range(threshold, 0.0, 1.0, "threshold"),
+            StageConfig::HighPass { cutoff_hz, q } | StageConfig::LowPass { cutoff_hz, q } => {
+                in_range(cutoff_hz, 0.0, sample_rate as f32 * MAX_CUTOFF_RATIO, "cutoff_hz")?;
+                in_range(q, 0.0, 10.0, "q")
+            }
+            StageConfig::Biquad { b0, b1, b2, a1, a2 } => {
+                if [b0, b1, b2, a1, a2].iter().any(|value| !value.is_finite()) {
+                    return Err(format!("处理环节 {} 的系数必须是有限数值", self.name()));
+                }
+                // 极点都在单位圆内才稳定（稳定三角形）
+                if a2.abs() >= 1.0 || a1.abs() >= 1.0 + a2 {
+                    return Err(format!("处理环节 {} 的系数不稳定: a1={}, a2={}", self.name(), a1, a2));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn build(self, sample_rate: u32) -> Box<dyn AudioStage> {
+        match self {
+            StageConfig::DcRemoval { cutoff_hz } => Box::new(DcRemoval::new(cutoff_hz, sample_rate)),
+            StageConfig::Agc { target_rms, max_gain } => Box::new(Agc::new(target_rms, max_gain)),
+            StageConfig::Preemphasis { coefficient } => Box::new(Preemphasis::new(coefficient)),
+            StageConfig::Limiter { threshold } => Box::new(Limiter::new(threshold)),
+            StageConfig::HighPass { cutoff_hz, q } => Box::new(Biquad::high_pass(cutoff_hz, q, sample_rate)),
+            StageConfig::LowPass { cutoff_hz, q } => Box::new(Biquad::low_pass(cutoff_hz, q, sample_rate)),
+            StageConfig::Biquad { b0, b1, b2, a1, a2 } => Box::new(Biquad::from_coefficients([b0, b1, b2], [1.0, a1, a2])),
+        }
+    }
+}
+
+// 校验环节列表：参数在范围内，同一环节只能出现一次
+pub fn validate(configs: &[StageConfig], sample_rate: u32) -> Result<(), String> {
+    for (i, config) in configs.iter().enumerate() {
+        config.validate(sample_rate)?;
+        if configs[..i].iter().any(|other| other.name() == config.name()) {
+            return Err(format!("处理链中环节重复: {}", config.name()));
+        }
+    }
+    Ok(())
+}
+
+// 校验 VAD 判定前的滤波环节：只允许线性滤波环节，其余规则与处理链相同
+pub fn validate_filters(configs: &[StageConfig], sample_rate: u32) -> Result<(), String> {
+    if let Some(config) = configs.iter().find(|config| !config.is_filter()) {
+        return Err(format!("VAD判定前只能配置滤波环节（high_pass / low_pass / biquad）: {}", config.name()));
+    }
+    validate(configs, sample_rate)
+}
+
+// 单个环节的当前状态
+#[derive(Serialize, Clone, Debug)]
+pub struct StageStatus {
+    #[serde(flatten)]
+    pub config: StageConfig,
+    pub current_gain: Option<f32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PipelineStatus {
+    pub sample_rate: u32,
+    pub stages: Vec<StageStatus>,
+}
+
+pub struct Pipeline {
+    configs: Vec<StageConfig>,
+    stages: Vec<Box<dyn AudioStage>>,
+    sample_rate: u32,
+}
+
+impl Pipeline {
+    // 空处理链，音频原样发送
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            configs: Vec::new(),
+            stages: Vec::new(),
+            sample_rate,
+        }
+    }
+
+    // 替换环节列表，各环节状态从零开始；校验失败时保持原处理链不变
+    pub fn configure(&mut self, configs: Vec<StageConfig>) -> Result<(), String> {
+        validate(&configs, self.sample_rate)?;
+        self.stages = configs.iter().map(|config| config.build(self.sample_rate)).collect();
+        self.configs = configs;
+        Ok(())
+    }
+
+    // 采样率变化后滤波器系数失效，按新采样率重建
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate == self.sample_rate {
+            return;
+        }
+        self.sample_rate = sample_rate;
+        self.reset();
+    }
+
+    // 按当前配置重建各环节，清空滤波器历史与自适应增益
+    pub fn reset(&mut self) {
+        self.stages = self.configs.iter().map(|config| config.build(self.sample_rate)).collect();
+    }
+
+    pub fn configs(&self) -> &[StageConfig] {
+        &self.configs
+    }
+
+    pub fn status(&self) -> PipelineStatus {
+        PipelineStatus {
+            sample_rate: self.sample_rate,
+            stages: self
+                .configs
+                .iter()
+                .zip(self.stages.iter())
+                .map(|(config, stage)| StageStatus {
+                    config: *config,
+                    current_gain: stage.current_gain(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [i16]) {
+        for stage in self.stages.iter_mut() {
+            stage.process(samples);
+        }
+    }
+}
+
+fn to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+fn to_i16(value: f32) -> i16 {
+    (value * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+// 一阶高通去除直流偏置：y[n] = x[n] - x[n-1] + r * y[n-1]
+// 也用作VAD判定前的高通滤波（见 VadProcessor）
+pub struct DcRemoval {
+    r: f32,
+    prev_input: Option<f32>, // 以第一个样本作为初始历史，带直流偏置的输入开头不会产生阶跃
+    prev_output: f32,
+}
+
+impl DcRemoval {
+    pub fn new(cutoff_hz: f32, sample_rate: u32) -> Self {
+        Self {
+            r: 1.0 - 2.0 * PI * cutoff_hz / sample_rate as f32,
+            prev_input: None,
+            prev_output: 0.0,
+        }
+    }
+}
+
+impl AudioStage for DcRemoval {
+    fn process(&mut self, samples: &mut [i16]) {
+        for sample in samples.iter_mut() {
+            let x = to_f32(*sample);
+            let prev_input = *self.prev_input.get_or_insert(x);
+            let y = x - prev_input + self.r * self.prev_output;
+            self.prev_input = Some(x);
+            self.prev_output = y;
+            *sample = to_i16(y);
+        }
+    }
+}
+
+// 二阶 IIR 滤波，高通/低通系数按 RBJ Audio EQ Cookbook 计算；转置直接II型实现，状态跨帧保留。
+// 截止频率超过当前采样率允许的上限时（如切换到更低采样率后）按上限计算
+pub struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2], // a1, a2（已按 a0 归一化）
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn high_pass(cutoff_hz: f32, q: f32, sample_rate: u32) -> Self {
+        let (cos_w0, alpha) = Self::design(cutoff_hz, q, sample_rate);
+        let b = (1.0 + cos_w0) / 2.0;
+        Self::from_coefficients([b, -(1.0 + cos_w0), b], [1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha])
+    }
+
+    pub fn low_pass(cutoff_hz: f32, q: f32, sample_rate: u32) -> Self {
+        let (cos_w0, alpha) = Self::design(cutoff_hz, q, sample_rate);
+        let b = (1.0 - cos_w0) / 2.0;
+        Self::from_coefficients([b, 1.0 - cos_w0, b], [1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha])
+    }
+
+    // a 为 [a0, a1, a2]，各系数按 a0 归一化
+    pub fn from_coefficients(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn design(cutoff_hz: f32, q: f32, sample_rate: u32) -> (f32, f32) {
+        let cutoff_hz = cutoff_hz.min(sample_rate as f32 * MAX_CUTOFF_RATIO);
+        let w0 = 2.0 * PI * cutoff_hz / sample_rate as f32;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+}
+
+impl AudioStage for Biquad {
+    fn process(&mut self, samples: &mut [i16]) {
+        for sample in samples.iter_mut() {
+            let x = to_f32(*sample);
+            let y = self.b[0] * x + self.z1;
+            self.z1 = self.b[1] * x - self.a[0] * y + self.z2;
+            self.z2 = self.b[2] * x - self.a[1] * y;
+            *sample = to_i16(y);
+        }
+    }
+}
+
+// 预加重：y[n] = x[n] - a * x[n-1]，提升高频便于识别
+struct Preemphasis {
+    coefficient: f32,
+    prev_input: f32,
+}
+
+impl Preemphasis {
+    fn new(coefficient: f32) -> Self {
+        Self {
+            coefficient,
+            prev_input: 0.0,
+        }
+    }
+}
+
+impl AudioStage for Preemphasis {
+    fn process(&mut self, samples: &mut [i16]) {
+        for sample in samples.iter_mut() {
+            let x = to_f32(*sample);
+            *sample = to_i16(x - self.coefficient * self.prev_input);
+            self.prev_input = x;
+        }
+    }
+}
+
+// 按帧RMS自动调整增益，使语音电平接近目标值
+struct Agc {
+    target_rms: f32,
+    max_gain: f32,
+    gain: f32,
+}
+
+impl Agc {
+    fn new(target_rms: f32, max_gain: f32) -> Self {
+        Self {
+            target_rms,
+            max_gain,
+            gain: 1.0,
+        }
+    }
+}
+
+impl AudioStage for Agc {
+    fn process(&mut self, samples: &mut [i16]) {
+        let rms = crate::audio_utils::frame_rms(samples);
+        if rms > AGC_SILENCE_RMS {
+            let desired = (self.target_rms / rms).clamp(AGC_MIN_GAIN, self.max_gain);
+            let rate = if desired < self.gain { AGC_ATTACK } else { AGC_RELEASE };
+            self.gain += (desired - self.gain) * rate;
+        }
+        for sample in samples.iter_mut() {
+            *sample = to_i16(to_f32(*sample) * self.gain);
+        }
+    }
+
+    fn current_gain(&self) -> Option<f32> {
+        Some(self.gain)
+    }
+}
+
+// 峰值限幅：帧峰值超过阈值时立即压低增益，之后逐帧恢复
+struct Limiter {
+    threshold: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    fn new(threshold: f32) -> Self {
+        Self { threshold, gain: 1.0 }
+    }
+}
+
+impl AudioStage for Limiter {
+    fn process(&mut self, samples: &mut [i16]) {
+        let peak = samples.iter().map(|&s| to_f32(s).abs()).fold(0.0, f32::max);
+        let required = if peak > self.threshold { self.threshold / peak } else { 1.0 };
+        if required < self.gain {
+            self.gain = required;
+        } else {
+            self.gain = (self.gain + LIMITER_RELEASE).min(required);
+        }
+        if self.gain < 1.0 {
+            for sample in samples.iter_mut() {
+                *sample = to_i16(to_f32(*sample) * self.gain);
+            }
+        }
+    }
+
+    fn current_gain(&self) -> Option<f32> {
+        Some(self.gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_utils::frame_rms;
+
+    const RATE: u32 = 16000;
+
+    fn sine(freq_hz: f32, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|n| (0.5 * (2.0 * PI * freq_hz * n as f32 / RATE as f32).sin() * 32767.0) as i16)
+            .collect()
+    }
+
+    // 滤一秒正弦，跳过前半秒的瞬态，返回后半秒输出与输入的 RMS 之比
+    fn gain_at(mut stage: impl AudioStage, freq_hz: f32) -> f32 {
+        let input = sine(freq_hz, RATE as usize);
+        let mut output = input.clone();
+        stage.process(&mut output);
+        let half = input.len() / 2;
+        frame_rms(&output[half..]) / frame_rms(&input[half..])
+    }
+
+    // 默认 80Hz 高通：50Hz 工频嗡声明显衰减，1kHz 语音频段基本不变
+    #[test]
+    fn high_pass_attenuates_hum_but_keeps_voice_band() {
+        let hum = gain_at(Biquad::high_pass(DEFAULT_HIGH_PASS_CUTOFF_HZ, DEFAULT_FILTER_Q, RATE), 50.0);
+        let voice = gain_at(Biquad::high_pass(DEFAULT_HIGH_PASS_CUTOFF_HZ, DEFAULT_FILTER_Q, RATE), 1000.0);
+        assert!(hum < 0.5, "50Hz 增益 {}", hum);
+        assert!((voice - 1.0).abs() < 0.05, "1kHz 增益 {}", voice);
+        assert!(voice / hum > 2.5);
+    }
+
+    #[test]
+    fn low_pass_attenuates_above_cutoff() {
+        let pass = gain_at(Biquad::low_pass(1000.0, DEFAULT_FILTER_Q, RATE), 100.0);
+        let stop = gain_at(Biquad::low_pass(1000.0, DEFAULT_FILTER_Q, RATE), 4000.0);
+        assert!((pass - 1.0).abs() < 0.05, "100Hz 增益 {}", pass);
+        assert!(stop < 0.1, "4kHz 增益 {}", stop);
+    }
+
+    // 巴特沃斯响应在截止频率处约为 -3dB
+    #[test]
+    fn butterworth_is_half_power_at_cutoff() {
+        let high = gain_at(Biquad::high_pass(500.0, DEFAULT_FILTER_Q, RATE), 500.0);
+        let low = gain_at(Biquad::low_pass(500.0, DEFAULT_FILTER_Q, RATE), 500.0);
+        for gain in [high, low] {
+            assert!((gain - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.05, "截止频率处增益 {}", gain);
+        }
+    }
+
+    // 自定义系数按 a0 归一化：b = a 时为直通
+    #[test]
+    fn custom_coefficients_are_normalized() {
+        let gain = gain_at(Biquad::from_coefficients([2.0, 0.0, 0.0], [2.0, 0.0, 0.0]), 1000.0);
+        assert!((gain - 1.0).abs() < 0.01, "直通增益 {}", gain);
+    }
+
+    // 滤波器状态跨帧保留：分帧处理与整段处理结果一致
+    #[test]
+    fn filter_state_carries_across_frames() {
+        let input = sine(50.0, 3200);
+        let mut whole = input.clone();
+        Biquad::high_pass(DEFAULT_HIGH_PASS_CUTOFF_HZ, DEFAULT_FILTER_Q, RATE).process(&mut whole);
+
+        let mut framed = input;
+        let mut stage = Biquad::high_pass(DEFAULT_HIGH_PASS_CUTOFF_HZ, DEFAULT_FILTER_Q, RATE);
+        for frame in framed.chunks_mut(160) {
+            stage.process(frame);
+        }
+        assert_eq!(framed, whole);
+    }
+
+    // 截止频率超过上限时按上限设计，不会在奈奎斯特频率附近发散
+    #[test]
+    fn cutoff_is_clamped_below_nyquist() {
+        let clamped = gain_at(Biquad::low_pass(20000.0, DEFAULT_FILTER_Q, RATE), 1000.0);
+        let at_limit = gain_at(Biquad::low_pass(RATE as f32 * MAX_CUTOFF_RATIO, DEFAULT_FILTER_Q, RATE), 1000.0);
+        assert!(clamped.is_finite());
+        assert_eq!(clamped, at_limit);
+    }
+
+    // 与 set_pipeline 收到的参数相同：前端传入的 JSON
+    fn stages_from_json(json: serde_json::Value) -> Vec<StageConfig> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    // 设置后取回的环节列表与设置的一致，顺序保持，省略的参数补为默认值
+    #[test]
+    fn configured_stages_round_trip_through_status() {
+        let stages = stages_from_json(serde_json::json!([
+            {"stage": "dc_removal"},
+            {"stage": "high_pass", "cutoff_hz": 120.0},
+            {"stage": "agc", "target_rms": 0.2},
+            {"stage": "limiter", "threshold": 0.5},
+        ]));
+        let mut pipeline = Pipeline::new(RATE);
+        pipeline.configure(stages.clone()).unwrap();
+
+        assert_eq!(pipeline.configs(), stages.as_slice());
+        let status = pipeline.status();
+        assert_eq!(status.sample_rate, RATE);
+        let returned: Vec<StageConfig> = status.stages.iter().map(|stage| stage.config).collect();
+        assert_eq!(returned, stages);
+        assert_eq!(returned[0], StageConfig::DcRemoval { cutoff_hz: DEFAULT_DC_REMOVAL_CUTOFF_HZ });
+        assert_eq!(returned[1], StageConfig::HighPass { cutoff_hz: 120.0, q: DEFAULT_FILTER_Q });
+        assert_eq!(returned[2], StageConfig::Agc { target_rms: 0.2, max_gain: DEFAULT_AGC_MAX_GAIN });
+
+        // get_pipeline 返回的 JSON 可以原样再传给 set_pipeline
+        let json = serde_json::to_value(&status).unwrap();
+        let again = stages_from_json(json["stages"].clone());
+        assert_eq!(again, stages);
+        // 只有带增益的环节报告当前增益
+        let gains: Vec<bool> = status.stages.iter().map(|stage| stage.current_gain.is_some()).collect();
+        assert_eq!(gains, vec![false, false, true, true]);
+    }
+
+    // 非法环节或参数：返回错误，原处理链与其状态保持不变
+    #[test]
+    fn invalid_configuration_keeps_previous_pipeline() {
+        let mut pipeline = Pipeline::new(RATE);
+        let original = vec![StageConfig::Limiter { threshold: 0.5 }];
+        pipeline.configure(original.clone()).unwrap();
+
+        let invalid = [
+            vec![StageConfig::Limiter { threshold: 1.5 }],
+            vec![StageConfig::HighPass { cutoff_hz: 9000.0, q: DEFAULT_FILTER_Q }],
+            vec![StageConfig::Agc { target_rms: 0.1, max_gain: 10.0 }, StageConfig::Agc { target_rms: 0.2, max_gain: 10.0 }],
+            vec![StageConfig::Biquad { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 1.0 }],
+        ];
+        for stages in invalid {
+            assert!(pipeline.configure(stages.clone()).is_err(), "{:?}", stages);
+            assert_eq!(pipeline.configs(), original.as_slice());
+        }
+
+        assert!(serde_json::from_value::<Vec<StageConfig>>(serde_json::json!([{"stage": "reverb"}])).is_err());
+        assert!(serde_json::from_value::<Vec<StageConfig>>(serde_json::json!([{"stage": "agc", "target_rms": "loud"}])).is_err());
+    }
+
+    // 设置后的处理行为与配置一致：空链直通，限幅器把峰值压到阈值
+    #[test]
+    fn configured_pipeline_processes_audio() {
+        let mut pipeline = Pipeline::new(RATE);
+        let input = sine(1000.0, 320);
+        let mut passthrough = input.clone();
+        pipeline.process(&mut passthrough);
+        assert_eq!(passthrough, input);
+
+        pipeline.configure(vec![StageConfig::Limiter { threshold: 0.25 }]).unwrap();
+        let mut limited = input.clone();
+        pipeline.process(&mut limited);
+        let peak = limited.iter().map(|&s| (s as f32 / 32768.0).abs()).fold(0.0, f32::max);
+        assert!((peak - 0.25).abs() < 0.01, "限幅后峰值 {}", peak);
+        assert!((pipeline.status().stages[0].current_gain.unwrap() - 0.5).abs() < 0.01);
+
+        // 重新配置后状态从零开始
+        pipeline.configure(vec![StageConfig::Limiter { threshold: 0.25 }]).unwrap();
+        assert_eq!(pipeline.status().stages[0].current_gain, Some(1.0));
+    }
+}
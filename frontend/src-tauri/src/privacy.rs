@@ -0,0 +1,274 @@
+// 不留痕模式
+// 开启后任何音频与文字都不落盘：各持久化模块通过 Suspendable 挂起写盘（配置保持不变，关闭后恢复原有行为），
+// 导出音频或文字的命令直接拒绝；内存中的音频与文字只保留到当前会话结束。
+// 模式只在本次运行内有效，不写入配置；开关的变更追加到审计日志，只记录时间与开关值。
+// 删除转写同样记入审计日志，只记录被删除的轮次与各处清理的数量，不含内容；
+// 被删除的轮次登记到诊断排除列表，导出会话报告等诊断数据时整轮跳过。
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::journal::now_unix_ms;
+
+const AUDIT_LOG_FILE_NAME: &str = "privacy_audit.log";
+
+// 会把音频或文字写入磁盘的模块；挂起期间不写入任何内容，恢复后照常工作
+pub trait Suspendable {
+    fn suspend(&mut self);
+    fn resume(&mut self);
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PrivacyStatus {
+    pub ephemeral: bool,
+    pub changed_at_ms: Option<u64>, // 最近一次切换的时间，本次运行未切换过时为None
+}
+
+// 一次转写删除在各存储位置清理的结果
+#[derive(Serialize, Clone, Debug)]
+pub struct DeletionReport {
+    pub transcript_id: u64, // 转写所属的轮次 id
+    pub cascade: bool,
+    pub history_entries: usize,     // 对话历史中删除的用户记录
+    pub journal_transcripts: usize, // journal（含恢复报告）中删除的识别结果
+    pub voice_segments: usize,      // 删除的完整语音段
+    pub sent_segments: usize,       // 删除的发送段（回放与导出使用）
+    pub thumbnails_invalidated: bool,
+    pub checkpoint_rewritten: bool, // 磁盘上的 checkpoint 已按删除后的内容重写
+    pub diagnostics_excluded: bool, // 轮次已登记到诊断排除列表
+}
+
+pub struct PrivacyMode {
+    ephemeral: bool,
+    changed_at_ms: Option<u64>,
+    excluded_turns: BTreeSet<u64>, // 诊断排除列表：用户删除过转写的轮次，本次运行内有效
+}
+
+impl PrivacyMode {
+    pub fn new() -> Self {
+        Self {
+            ephemeral: false,
+            changed_at_ms: None,
+            excluded_turns: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    // 返回是否发生了变化
+    pub fn set_ephemeral(&mut self, enabled: bool) -> bool {
+        if self.ephemeral == enabled {
+            return false;
+        }
+        self.ephemeral = enabled;
+        self.changed_at_ms = Some(now_unix_ms());
+        true
+    }
+
+    pub fn status(&self) -> PrivacyStatus {
+        PrivacyStatus {
+            ephemeral: self.ephemeral,
+            changed_at_ms: self.changed_at_ms,
+        }
+    }
+
+    // 把轮次登记到诊断排除列表，重复登记无影响
+    pub fn exclude_from_diagnostics(&mut self, turn_id: u64) {
+        self.excluded_turns.insert(turn_id);
+    }
+
+    pub fn is_excluded_from_diagnostics(&self, turn_id: u64) -> bool {
+        self.excluded_turns.contains(&turn_id)
+    }
+
+    // 写盘前检查，不留痕模式下返回错误
+    pub fn ensure_persistence_allowed(&self, what: &str) -> Result<(), String> {
+        if self.ephemeral {
+            return Err(format!("不留痕模式已开启，不能{}", what));
+        }
+        Ok(())
+    }
+}
+
+// 追加一条审计记录：Unix毫秒时间戳与开关值，不含任何其他内容
+pub fn append_audit(dir: &Path, enabled: bool) -> Result<(), String> {
+    append_line(dir, &format!("ephemeral={}", enabled))
+}
+
+// 追加一条删除记录：被删除的轮次与各处清理的数量
+pub fn append_deletion_audit(dir: &Path, report: &DeletionReport) -> Result<(), String> {
+    append_line(
+        dir,
+        &format!(
+            "delete_transcript={}\tcascade={}\thistory={}\tjournal={}\tvoice_segments={}\tsent_segments={}\tdiagnostics_excluded={}",
+            report.transcript_id,
+            report.cascade,
+            report.history_entries,
+            report.journal_transcripts,
+            report.voice_segments,
+            report.sent_segments,
+            report.diagnostics_excluded
+        ),
+    )
+}
+
+fn append_line(dir: &Path, line: &str) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("创建审计日志目录 {} 失败: {}", dir.display(), e))?;
+    let path = dir.join(AUDIT_LOG_FILE_NAME);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("打开审计日志 {} 失败: {}", path.display(), e))?;
+    writeln!(file, "{}\t{}", now_unix_ms(), line)
+        .map_err(|e| format!("写入审计日志 {} 失败: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::{Checkpoint, Journal, SessionMeta};
+    use crate::protocol_trace::{Direction, ProtocolTrace, TraceChannel};
+    use crate::send_queue::SendQueue;
+    use crate::wav_stream::StreamingWav;
+    use crate::SttResult;
+    use std::path::PathBuf;
+
+    // 每个测试使用独立的临时目录，结束时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("lumina-privacy-{}-{}-{}", name, std::process::id(), now_unix_ms()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn file_len(path: &Path) -> u64 {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+
+    fn dir_size(dir: &Path) -> u64 {
+        fs::read_dir(dir).unwrap().map(|entry| file_len(&entry.unwrap().path())).sum()
+    }
+
+    fn transcript(text: &str) -> SttResult {
+        SttResult {
+            text: text.to_string(),
+            is_final: true,
+            session_id: Some(1),
+            trace_id: None,
+            turn_id: None,
+        }
+    }
+
+    #[test]
+    fn ephemeral_mode_rejects_persistence() {
+        let mut mode = PrivacyMode::new();
+        assert!(mode.ensure_persistence_allowed("导出录音").is_ok());
+        assert!(mode.set_ephemeral(true));
+        assert!(!mode.set_ephemeral(true));
+        let error = mode.ensure_persistence_allowed("导出录音").unwrap_err();
+        assert!(error.contains("导出录音"), "{}", error);
+        assert!(mode.set_ephemeral(false));
+        assert!(mode.ensure_persistence_allowed("导出录音").is_ok());
+    }
+
+    // 挂起期间待发队列不落盘，恢复后照常写入
+    #[test]
+    fn suspended_send_queue_writes_nothing() {
+        let temp = TempDir::new("send-queue");
+        let mut queue = SendQueue::open(&temp.0).unwrap();
+        let before = dir_size(&temp.0);
+
+        queue.suspend();
+        assert_eq!(queue.append(&[1000; 320], 16000).unwrap(), None);
+        assert_eq!(dir_size(&temp.0), before);
+
+        queue.resume();
+        assert!(queue.append(&[1000; 320], 16000).unwrap().is_some());
+        assert!(dir_size(&temp.0) > before);
+    }
+
+    // 挂起期间流式WAV只保留文件头
+    #[test]
+    fn suspended_streaming_wav_keeps_header_only() {
+        let temp = TempDir::new("wav");
+        let path = temp.0.join("session.wav");
+        let mut wav = StreamingWav::create(&path, 16000).unwrap();
+        let header_len = file_len(&path);
+        assert_eq!(header_len, 44);
+
+        wav.suspend();
+        wav.append(&[1000; 320]).unwrap();
+        assert!(wav.status().suspended);
+        assert_eq!(wav.status().samples, 0);
+        assert_eq!(file_len(&path), header_len);
+
+        wav.resume();
+        wav.append(&[1000; 320]).unwrap();
+        assert_eq!(file_len(&path), header_len + 640);
+    }
+
+    // 挂起期间 journal 不收集转写，checkpoint 只写会话元数据
+    #[test]
+    fn suspended_journal_checkpoint_has_no_content() {
+        let temp = TempDir::new("journal");
+        let mut journal = Journal::new();
+        journal.open(temp.0.clone()).unwrap();
+
+        journal.suspend();
+        journal.record_transcript(transcript("不该留下的话"));
+        assert!(journal.transcripts().is_empty());
+
+        let checkpoint = Checkpoint {
+            saved_at_ms: now_unix_ms(),
+            session: SessionMeta {
+                vad_state: "Waiting".to_string(),
+                started_at_ms: journal.started_at_ms(),
+                metrics: crate::metrics::Metrics::new().report().cumulative,
+            },
+            pending_segments: vec![vec![1000; 320]],
+            transcripts: vec![transcript("不该留下的话")],
+            turns: Vec::new(),
+        };
+        journal.write_checkpoint(&checkpoint).unwrap();
+
+        let saved = crate::journal::load_checkpoint(&temp.0.join("checkpoint.json")).unwrap();
+        assert!(saved.pending_segments.is_empty());
+        assert!(saved.transcripts.is_empty());
+        assert_eq!(saved.session.vad_state, "Waiting");
+        let raw = fs::read_to_string(temp.0.join("checkpoint.json")).unwrap();
+        assert!(!raw.contains("不该留下的话"));
+
+        journal.resume();
+        journal.record_transcript(transcript("可以保留的话"));
+        assert_eq!(journal.transcripts().len(), 1);
+    }
+
+    // 挂起期间协议追踪不记录任何消息
+    #[test]
+    fn suspended_protocol_trace_records_nothing() {
+        let mut trace = ProtocolTrace::new();
+        trace.set_enabled(true);
+        trace.suspend();
+        trace.record(Direction::Outgoing, TraceChannel::Stt, "audio_pcm16", 644, true);
+        assert!(trace.report().entries.is_empty());
+
+        trace.resume();
+        trace.record(Direction::Outgoing, TraceChannel::Stt, "audio_pcm16", 644, true);
+        assert_eq!(trace.report().entries.len(), 1);
+    }
+}
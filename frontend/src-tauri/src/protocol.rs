@@ -0,0 +1,272 @@
+// 发往后端的控制消息编码与协议版本
+// 控制消息格式：特殊长度头(0xFFFFFFFF) + 1字节消息类型 + 负载。
+// 每次连上音频上行通道先发握手：控制消息头 + 0x08 + 本端协议版本(u32) + 采样率(u32)，
+// 后端在同一连接上回复 控制消息头 + 0x08 + 后端协议版本(u32)。收到确认之前不发送任何音频与控制消息，
+// 版本不兼容或超时未确认时断开连接并通过 backend-error 报告，避免向旧后端推送它无法解析的数据。
+// 静音事件按与后端协商出的协议版本二选一编码，同一版本下只会发其中一种：
+//   v1（旧后端）: 静音时长(u64)，共 4+1+8 字节
+//   v2: 静音时长(u64) + 状态(u8) + 会话序号(u64) + 上一段发言时长ms(u64) + 本会话第几段发言(u32)，共 4+1+29 字节
+// v3 起支持显式的发言结束消息（0x0A），协商版本更低时只在本地结束发言。
+// v4 起每个音频段之前带一条会话标记（0x0C），标明随后的音频属于哪个会话；后端在识别结果中回传 session_id，
+// 前端据此丢弃上一会话迟到的结果（如用户打断之后）。协商版本更低时不发送会话标记。
+// v5 起会话标记之后可以再带一条 trace 上下文（0x0D），后端据此把自己的 span 挂到前端的 trace 下，并在识别结果中回传 trace_id。
+// 只有启用 otel feature 的构建才发送，后端不能假定一定会收到。
+// v6 起回复播放中断流超时时发送播放询问（0x0E），后端据此确认该回复是否还有后续音频；协商版本更低时不发送。
+// v7 起支持最终识别请求（0x07，负载为请求序号u64），协商版本更低时因静音结束说话不再请求最终结果，只等后端自行输出。
+// v8 起支持f32样本格式的音频（0x06），协商版本更低时即使设置为f32也按PCM16发送。
+// v9 起支持编码后的音频（0x09，目前为Opus），协商版本更低时即使设置为Opus也按PCM发送。
+
+use serde::{Deserialize, Serialize};
+
+use crate::codec::AudioCodec;
+use crate::VadState;
+
+pub const CONTROL_MESSAGE_HEADER: u32 = 0xFFFFFFFF;
+pub const CONTROL_SILENCE_EVENT: u8 = 0x01; // 负载见上方说明，随协议版本不同
+// 0x02~0x05 已被后端占用（结束会话/重置/开始会话/打断）
+// f32音频：负载为 样本数(u32) + [-1,1] f32小端样本
+pub const CONTROL_AUDIO_F32: u8 = 0x06;
+// 最终识别请求：负载为 请求序号(u64)，要求后端立即输出最终识别结果
+pub const CONTROL_FINALIZE: u8 = 0x07;
+pub const CONTROL_HANDSHAKE: u8 = 0x08;
+// 编码后的音频：负载为 编码标识(u8) + 解码后的样本数(u32) + 字节数(u32) + 编码数据
+pub const CONTROL_AUDIO_CODED: u8 = 0x09;
+// 发言结束：负载为 原因(u8) + 发言时长ms(u64)
+pub const CONTROL_END_OF_UTTERANCE: u8 = 0x0A;
+pub const END_OF_UTTERANCE_MAX_DURATION: u8 = 0x01; // 连续说话达到最长发言时长
+// 心跳：无负载，空闲时定期发送以尽早发现已失效的连接，后端收到后直接忽略
+pub const CONTROL_HEARTBEAT: u8 = 0x0B;
+// 会话标记：负载为 会话序号(u64)，与随后的音频包在同一次写入中发出
+pub const CONTROL_SESSION_TAG: u8 = 0x0C;
+// trace 上下文：负载为 会话序号(u64) + trace_id(16字节) + 父span_id(8字节) + trace标志(u8)，与会话标记在同一次写入中发出
+#[cfg(feature = "otel")]
+pub const CONTROL_TRACE_CONTEXT: u8 = 0x0D;
+// trace 标志：已采样
+#[cfg(feature = "otel")]
+const TRACE_FLAG_SAMPLED: u8 = 0x01;
+// 播放询问：负载为 会话序号(u64) + utterance_id(u64，未知时为0) + 中断时长ms(u64)
+pub const CONTROL_PLAYBACK_INQUIRY: u8 = 0x0E;
+// 握手确认的长度：控制消息头 + 消息类型 + 版本
+pub const HANDSHAKE_ACK_LEN: usize = 4 + 1 + 4;
+
+pub const PROTOCOL_VERSION_LEGACY: u32 = 1;
+pub const PROTOCOL_VERSION_SILENCE_CONTEXT: u32 = 2;
+pub const PROTOCOL_VERSION_END_OF_UTTERANCE: u32 = 3;
+pub const PROTOCOL_VERSION_SESSION_TAG: u32 = 4;
+pub const PROTOCOL_VERSION_TRACE_CONTEXT: u32 = 5;
+pub const PROTOCOL_VERSION_PLAYBACK_INQUIRY: u32 = 6;
+pub const PROTOCOL_VERSION_FINALIZE: u32 = 7;
+pub const PROTOCOL_VERSION_AUDIO_F32: u32 = 8;
+pub const PROTOCOL_VERSION_CODED_AUDIO: u32 = 9;
+// 本端支持的最高协议版本
+pub const PROTOCOL_VERSION: u32 = PROTOCOL_VERSION_CODED_AUDIO;
+
+// 静音上报时的状态机上下文，后端据此判断是否主动接话
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SilenceContext {
+    pub silence_ms: u64,
+    pub state: VadState,
+    pub session_id: u64,                 // 会话序号，每次开始新的发言（进入临界态）时递增
+    pub last_utterance_duration_ms: u64, // 静音之前那段发言的时长
+    pub turn_index: u32,                 // 本会话中第几段发言，从1开始
+}
+
+pub fn encode_handshake(sample_rate: u32) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 4 + 4);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_HANDSHAKE);
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    packet.extend_from_slice(&sample_rate.to_le_bytes());
+    packet
+}
+
+pub fn encode_audio_f32(samples: &[f32]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 4 + samples.len() * 4);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_AUDIO_F32);
+    packet.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    for &sample in samples {
+        packet.extend_from_slice(&sample.to_le_bytes());
+    }
+    packet
+}
+
+pub fn encode_finalize(finalize_id: u64) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 8);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_FINALIZE);
+    packet.extend_from_slice(&finalize_id.to_le_bytes());
+    packet
+}
+
+pub fn encode_coded_audio(codec: AudioCodec, samples: usize, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 1 + 4 + 4 + payload.len());
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_AUDIO_CODED);
+    packet.push(codec.wire_id());
+    packet.extend_from_slice(&(samples as u32).to_le_bytes());
+    packet.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+pub fn encode_end_of_utterance(reason: u8, duration_ms: u64) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 1 + 8);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_END_OF_UTTERANCE);
+    packet.push(reason);
+    packet.extend_from_slice(&duration_ms.to_le_bytes());
+    packet
+}
+
+pub fn encode_heartbeat() -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_HEARTBEAT);
+    packet
+}
+
+pub fn encode_session_tag(session_id: u64) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 8);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_SESSION_TAG);
+    packet.extend_from_slice(&session_id.to_le_bytes());
+    packet
+}
+
+pub fn encode_playback_inquiry(session_id: u64, utterance_id: u64, stalled_ms: u64) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 8 + 8 + 8);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_PLAYBACK_INQUIRY);
+    packet.extend_from_slice(&session_id.to_le_bytes());
+    packet.extend_from_slice(&utterance_id.to_le_bytes());
+    packet.extend_from_slice(&stalled_ms.to_le_bytes());
+    packet
+}
+
+#[cfg(feature = "otel")]
+pub fn encode_trace_context(session_id: u64, trace_id: &[u8; 16], parent_span_id: &[u8; 8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 8 + 16 + 8 + 1);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_TRACE_CONTEXT);
+    packet.extend_from_slice(&session_id.to_le_bytes());
+    packet.extend_from_slice(trace_id);
+    packet.extend_from_slice(parent_span_id);
+    packet.push(TRACE_FLAG_SAMPLED);
+    packet
+}
+
+// 解析握手确认，返回后端声明的协议版本
+pub fn decode_handshake_ack(ack: &[u8]) -> Result<u32, String> {
+    if ack.len() != HANDSHAKE_ACK_LEN
+        || ack[..4] != CONTROL_MESSAGE_HEADER.to_le_bytes()
+        || ack[4] != CONTROL_HANDSHAKE
+    {
+        return Err(format!("后端返回的不是握手确认: {:02x?}", ack));
+    }
+    Ok(u32::from_le_bytes([ack[5], ack[6], ack[7], ack[8]]))
+}
+
+// 按后端声明的版本协商：取双方都支持的最高版本
+pub fn negotiate(peer_version: u32) -> Result<u32, String> {
+    if peer_version < PROTOCOL_VERSION_LEGACY {
+        return Err(format!("后端协议版本 {} 与本端（v{}）不兼容", peer_version, PROTOCOL_VERSION));
+    }
+    Ok(peer_version.min(PROTOCOL_VERSION))
+}
+
+pub fn encode_silence_event(context: &SilenceContext, version: u32) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 1 + 29);
+    packet.extend_from_slice(&CONTROL_MESSAGE_HEADER.to_le_bytes());
+    packet.push(CONTROL_SILENCE_EVENT);
+    packet.extend_from_slice(&context.silence_ms.to_le_bytes());
+    if version >= PROTOCOL_VERSION_SILENCE_CONTEXT {
+        packet.push(state_code(&context.state));
+        packet.extend_from_slice(&context.session_id.to_le_bytes());
+        packet.extend_from_slice(&context.last_utterance_duration_ms.to_le_bytes());
+        packet.extend_from_slice(&context.turn_index.to_le_bytes());
+    }
+    packet
+}
+
+fn state_code(state: &VadState) -> u8 {
+    match state {
+        VadState::Initial => 0,
+        VadState::Speaking => 1,
+        VadState::Waiting => 2,
+        VadState::Listening => 3,
+        VadState::TransitionBuffer => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> SilenceContext {
+        SilenceContext {
+            silence_ms: 1500,
+            state: VadState::Waiting,
+            session_id: 7,
+            last_utterance_duration_ms: 2300,
+            turn_index: 2,
+        }
+    }
+
+    // v1 只有静音时长，共 4+1+8 字节，不含任何上下文字段
+    #[test]
+    fn silence_event_v1_is_legacy_layout() {
+        let packet = encode_silence_event(&context(), PROTOCOL_VERSION_LEGACY);
+        let mut expected = vec![0xFF, 0xFF, 0xFF, 0xFF, CONTROL_SILENCE_EVENT];
+        expected.extend_from_slice(&1500u64.to_le_bytes());
+        assert_eq!(packet, expected);
+    }
+
+    // v2 在同样的前缀之后带完整上下文，共 4+1+29 字节
+    #[test]
+    fn silence_event_v2_appends_context() {
+        let packet = encode_silence_event(&context(), PROTOCOL_VERSION_SILENCE_CONTEXT);
+        let mut expected = vec![0xFF, 0xFF, 0xFF, 0xFF, CONTROL_SILENCE_EVENT];
+        expected.extend_from_slice(&1500u64.to_le_bytes());
+        expected.push(2); // Waiting
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        expected.extend_from_slice(&2300u64.to_le_bytes());
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        assert_eq!(packet, expected);
+        assert_eq!(packet.len(), 4 + 1 + 29);
+    }
+
+    // f32音频：控制消息头 + 0x06 + 样本数 + f32小端样本
+    #[test]
+    fn audio_f32_layout() {
+        let packet = encode_audio_f32(&[0.5, -1.0]);
+        let mut expected = vec![0xFF, 0xFF, 0xFF, 0xFF, CONTROL_AUDIO_F32];
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(&0.5f32.to_le_bytes());
+        expected.extend_from_slice(&(-1.0f32).to_le_bytes());
+        assert_eq!(packet, expected);
+    }
+
+    // 最终识别请求：控制消息头 + 0x07 + 请求序号
+    #[test]
+    fn finalize_layout() {
+        let mut expected = vec![0xFF, 0xFF, 0xFF, 0xFF, CONTROL_FINALIZE];
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(encode_finalize(7), expected);
+    }
+
+    // 同一协商版本只会产生一种布局：v1 以下都是旧布局，v2 及以上都是带上下文的布局，两者长度不同，后端不会误读
+    #[test]
+    fn silence_event_layouts_are_mutually_exclusive() {
+        let legacy = encode_silence_event(&context(), PROTOCOL_VERSION_LEGACY);
+        for version in PROTOCOL_VERSION_LEGACY..=PROTOCOL_VERSION {
+            let packet = encode_silence_event(&context(), version);
+            if version >= PROTOCOL_VERSION_SILENCE_CONTEXT {
+                assert_eq!(packet.len(), 4 + 1 + 29, "v{}", version);
+                assert_eq!(&packet[..legacy.len()], &legacy[..]);
+            } else {
+                assert_eq!(packet, legacy, "v{}", version);
+            }
+        }
+    }
+}
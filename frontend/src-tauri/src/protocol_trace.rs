@@ -0,0 +1,120 @@
+// 协议级调试记录
+// 开启后把每条发往后端与从后端收到的消息（类型、线上字节数、序号、时间戳、是否成功）记入环形缓冲，
+// 对接新后端时用来区分"本端发送顺序有问题"还是"对端解析有问题"。默认关闭，关闭时不产生任何记录。
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::privacy::Suspendable;
+
+// 最多保留的记录条数，超出后丢弃最旧的
+const MAX_TRACE_ENTRIES: usize = 2000;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceChannel {
+    Stt,       // 音频上行与控制消息
+    SttResult, // 识别结果与后端控制消息
+    Tts,       // TTS音频
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TraceEntry {
+    pub seq: u64,         // 全局序号，反映本端处理的先后
+    pub channel_seq: u64, // 同一通道同一方向内的序号，对照对端日志时按它核对是否丢失或乱序
+    pub direction: Direction,
+    pub channel: TraceChannel,
+    pub kind: &'static str, // 消息类型，如 audio_pcm16 / silence / finalize / stt_result / tts_audio
+    pub bytes: usize,       // 线上字节数（含长度头）
+    pub timestamp_ms: u64,
+    pub ok: bool, // 发送是否成功 / 收到的消息能否解析
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ProtocolTraceReport {
+    pub enabled: bool,
+    pub dropped: u64, // 因超出容量被丢弃的记录数
+    pub entries: Vec<TraceEntry>,
+}
+
+pub struct ProtocolTrace {
+    enabled: bool,
+    entries: VecDeque<TraceEntry>,
+    next_seq: u64,
+    channel_seqs: [[u64; 3]; 2], // [方向][通道]
+    dropped: u64,
+    suspended: bool, // 不留痕模式下不记录
+}
+
+impl ProtocolTrace {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            entries: VecDeque::new(),
+            next_seq: 0,
+            channel_seqs: [[0; 3]; 2],
+            dropped: 0,
+            suspended: false,
+        }
+    }
+
+    // 开启时从空记录开始，关闭时保留已有记录供导出
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.entries.clear();
+            self.next_seq = 0;
+            self.channel_seqs = [[0; 3]; 2];
+            self.dropped = 0;
+        }
+        self.enabled = enabled;
+    }
+
+    pub fn record(&mut self, direction: Direction, channel: TraceChannel, kind: &'static str, bytes: usize, ok: bool) {
+        if !self.enabled || self.suspended {
+            return;
+        }
+
+        let channel_seq = &mut self.channel_seqs[direction as usize][channel as usize];
+        *channel_seq += 1;
+        self.next_seq += 1;
+        if self.entries.len() >= MAX_TRACE_ENTRIES {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(TraceEntry {
+            seq: self.next_seq,
+            channel_seq: *channel_seq,
+            direction,
+            channel,
+            kind,
+            bytes,
+            timestamp_ms: crate::journal::now_unix_ms(),
+            ok,
+        });
+    }
+
+    pub fn report(&self) -> ProtocolTraceReport {
+        ProtocolTraceReport {
+            enabled: self.enabled,
+            dropped: self.dropped,
+            entries: self.entries.iter().cloned().collect(),
+        }
+    }
+}
+
+impl Suspendable for ProtocolTrace {
+    fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+}
@@ -0,0 +1,160 @@
+// 输入采样率核对
+// 前端声明的采样率与实际送来的数据不符时（例如声称16kHz实际是48kHz），帧数与时长对不上，VAD会悄悄失准。
+// 这里用帧的采集时刻与样本数反推实际采样率：累计一个窗口内到达的样本数与经过的时间，
+// 偏差超过阈值且连续多个窗口指向同一采样率时判定为不匹配并告警；
+// 开启自动纠正后按检测到的采样率（取最接近的常见采样率）重采样，前端恢复正常后自动撤销。
+// 前端暂停采集会在帧之间留下长间隔，让估计偏低，遇到时重新开始窗口。
+
+use log::info;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+// 每个估计窗口的时长
+const WINDOW: Duration = Duration::from_secs(3);
+// 相邻两帧间隔超过该值视为采集中断，重新开始窗口
+const MAX_FRAME_GAP: Duration = Duration::from_millis(500);
+// 实测值与声明值的相对偏差超过该比例才视为不匹配
+const MAX_DEVIATION: f64 = 0.1;
+// 连续多少个窗口指向同一采样率才判定为不匹配
+const CONFIRM_WINDOWS: u32 = 2;
+const COMMON_RATES: [u32; 8] = [8000, 11025, 16000, 22050, 24000, 32000, 44100, 48000];
+
+// 采样率不匹配事件
+#[derive(Serialize, Clone, Debug)]
+pub struct RateMismatch {
+    pub declared_rate: u32,
+    pub detected_rate: u32, // 最接近实测值的常见采样率
+    pub measured_rate: f64,
+    pub corrected: bool, // 是否已按检测到的采样率重采样
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RateCheckStatus {
+    pub auto_correct: bool,
+    pub declared_rate: Option<u32>,
+    pub measured_rate: Option<f64>, // 最近一个完整窗口的实测值
+    pub mismatch: Option<RateMismatch>,
+}
+
+pub struct RateCheck {
+    auto_correct: bool,
+    declared_rate: Option<u32>,
+    window_start: Option<Instant>,
+    window_samples: usize, // 窗口起点之后到达的样本数，起点那一帧采集于起点之前，不计入
+    last_frame: Option<Instant>,
+    measured_rate: Option<f64>,
+    candidate: Option<(u32, u32)>, // 待确认的检测结果与连续命中的窗口数
+    mismatch: Option<RateMismatch>,
+}
+
+impl RateCheck {
+    pub fn new() -> Self {
+        Self {
+            auto_correct: false,
+            declared_rate: None,
+            window_start: None,
+            window_samples: 0,
+            last_frame: None,
+            measured_rate: None,
+            candidate: None,
+            mismatch: None,
+        }
+    }
+
+    pub fn set_auto_correct(&mut self, enabled: bool) {
+        self.auto_correct = enabled;
+        if let Some(mismatch) = self.mismatch.as_mut() {
+            mismatch.corrected = enabled;
+        }
+    }
+
+    // 记录一帧，返回应当按哪个采样率解释这一帧，以及新判定出的不匹配（每次判定只报告一次）
+    pub fn observe(&mut self, samples: usize, declared_rate: u32, at: Instant) -> (u32, Option<RateMismatch>) {
+        // 声明的采样率变化后重新核对
+        if self.declared_rate != Some(declared_rate) {
+            *self = Self {
+                auto_correct: self.auto_correct,
+                declared_rate: Some(declared_rate),
+                ..Self::new()
+            };
+        }
+
+        let continuous = matches!(self.last_frame, Some(last) if at.saturating_duration_since(last) <= MAX_FRAME_GAP);
+        self.last_frame = Some(at);
+        let window_start = match self.window_start {
+            Some(start) if continuous => start,
+            _ => {
+                self.window_start = Some(at);
+                self.window_samples = 0;
+                return (self.effective_rate(declared_rate), None);
+            }
+        };
+
+        self.window_samples += samples;
+        let elapsed = at.saturating_duration_since(window_start);
+        if elapsed < WINDOW {
+            return (self.effective_rate(declared_rate), None);
+        }
+
+        let measured = self.window_samples as f64 / elapsed.as_secs_f64();
+        self.measured_rate = Some(measured);
+        self.window_start = Some(at);
+        self.window_samples = 0;
+        let mismatch = self.evaluate(declared_rate, measured);
+        (self.effective_rate(declared_rate), mismatch)
+    }
+
+    pub fn status(&self) -> RateCheckStatus {
+        RateCheckStatus {
+            auto_correct: self.auto_correct,
+            declared_rate: self.declared_rate,
+            measured_rate: self.measured_rate,
+            mismatch: self.mismatch.clone(),
+        }
+    }
+
+    fn effective_rate(&self, declared_rate: u32) -> u32 {
+        match &self.mismatch {
+            Some(mismatch) if self.auto_correct => mismatch.detected_rate,
+            _ => declared_rate,
+        }
+    }
+
+    fn evaluate(&mut self, declared_rate: u32, measured: f64) -> Option<RateMismatch> {
+        let deviation = (measured - declared_rate as f64).abs() / declared_rate as f64;
+        let detected = nearest_common_rate(measured);
+        if deviation <= MAX_DEVIATION || detected == declared_rate {
+            self.candidate = None;
+            if self.mismatch.take().is_some() {
+                info!("[信息] 输入采样率与声明的{}Hz一致，撤销采样率纠正", declared_rate);
+            }
+            return None;
+        }
+
+        let hits = match self.candidate {
+            Some((rate, hits)) if rate == detected => hits + 1,
+            _ => 1,
+        };
+        self.candidate = Some((detected, hits));
+        if hits < CONFIRM_WINDOWS || matches!(&self.mismatch, Some(m) if m.detected_rate == detected) {
+            return None;
+        }
+
+        let mismatch = RateMismatch {
+            declared_rate,
+            detected_rate: detected,
+            measured_rate: measured,
+            corrected: self.auto_correct,
+        };
+        self.mismatch = Some(mismatch.clone());
+        Some(mismatch)
+    }
+}
+
+fn nearest_common_rate(measured: f64) -> u32 {
+    COMMON_RATES
+        .iter()
+        .copied()
+        .min_by(|a, b| (*a as f64 - measured).abs().total_cmp(&(*b as f64 - measured).abs()))
+        .unwrap_or(COMMON_RATES[0])
+}
@@ -0,0 +1,193 @@
+// 输入重采样
+// 浏览器采集的音频常为 44.1k/48k，VAD 与后端都按 16k 处理。
+// 降采样前先过一个加窗 sinc 低通滤波器抗混叠，再按固定步长线性插值；
+// 滤波历史与插值位置跨调用保留，保证逐块处理时输出连续。
+
+use std::f64::consts::PI;
+
+// 支持的输入采样率范围
+pub const MIN_INPUT_SAMPLE_RATE: u32 = 8000;
+pub const MAX_INPUT_SAMPLE_RATE: u32 = 192000;
+// 抗混叠滤波器阶数（奇数，群延迟为 (N-1)/2 个输入样本）
+const LOWPASS_TAPS: usize = 63;
+// 截止频率相对于输出奈奎斯特频率的比例，留出过渡带
+const LOWPASS_CUTOFF_RATIO: f64 = 0.9;
+// 单次输入块时长的合理范围，超出说明声明的采样率与实际数据不符
+const MIN_INPUT_CHUNK_MS: f64 = 1.0;
+const MAX_INPUT_CHUNK_MS: f64 = 250.0;
+
+// 校验声明的采样率，以及块长在该采样率下对应的时长是否合理
+pub fn validate_input(len: usize, sample_rate: u32) -> Result<(), String> {
+    if !(MIN_INPUT_SAMPLE_RATE..=MAX_INPUT_SAMPLE_RATE).contains(&sample_rate) {
+        return Err(format!(
+            "不支持的输入采样率: {}Hz，支持范围 {}~{}Hz",
+            sample_rate, MIN_INPUT_SAMPLE_RATE, MAX_INPUT_SAMPLE_RATE
+        ));
+    }
+
+    let duration_ms = len as f64 * 1000.0 / sample_rate as f64;
+    if !(MIN_INPUT_CHUNK_MS..=MAX_INPUT_CHUNK_MS).contains(&duration_ms) {
+        return Err(format!(
+            "音频帧长度与声明的采样率不匹配: {}个样本在{}Hz下为{:.1}ms，应在{}~{}ms之间",
+            len, sample_rate, duration_ms, MIN_INPUT_CHUNK_MS, MAX_INPUT_CHUNK_MS
+        ));
+    }
+    Ok(())
+}
+
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    taps: Vec<f32>,     // 低通滤波器系数，升采样时为空
+    history: Vec<f32>,  // 上一块末尾的输入样本，供滤波器跨块使用
+    last_filtered: f32, // 上一块最后一个滤波后的样本，供插值跨块使用
+    position: f64,      // 下一个输出样本在 [last_filtered, 本块...] 上的位置
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let taps = if input_rate > output_rate {
+            lowpass_taps(LOWPASS_CUTOFF_RATIO * output_rate as f64 / 2.0 / input_rate as f64)
+        } else {
+            Vec::new()
+        };
+        let history_len = taps.len().saturating_sub(1);
+        Self {
+            input_rate,
+            output_rate,
+            taps,
+            history: vec![0.0; history_len],
+            last_filtered: 0.0,
+            position: 1.0, // 第一块没有上一块的样本，从本块第一个样本开始
+        }
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if input.is_empty() {
+            return Vec::new();
+        }
+
+        let filtered = self.filter(input);
+
+        // 插值缓冲：上一块的最后一个样本 + 本块
+        let mut buffer = Vec::with_capacity(filtered.len() + 1);
+        buffer.push(self.last_filtered);
+        buffer.extend_from_slice(&filtered);
+
+        let step = self.input_rate as f64 / self.output_rate as f64;
+        let mut output = Vec::with_capacity((input.len() as f64 / step) as usize + 1);
+        while self.position + 1.0 < buffer.len() as f64 {
+            let index = self.position.floor() as usize;
+            let frac = (self.position - index as f64) as f32;
+            output.push(buffer[index] + (buffer[index + 1] - buffer[index]) * frac);
+            self.position += step;
+        }
+
+        // 下一块的缓冲以本块最后一个样本为起点
+        self.position -= (buffer.len() - 1) as f64;
+        self.last_filtered = buffer[buffer.len() - 1];
+        output
+    }
+
+    // FIR 低通滤波，输出与输入等长
+    fn filter(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.taps.is_empty() {
+            return input.to_vec();
+        }
+
+        let mut extended = Vec::with_capacity(self.history.len() + input.len());
+        extended.extend_from_slice(&self.history);
+        extended.extend_from_slice(input);
+
+        let filtered = (0..input.len())
+            .map(|i| {
+                self.taps
+                    .iter()
+                    .zip(&extended[i..i + self.taps.len()])
+                    .map(|(tap, sample)| tap * sample)
+                    .sum()
+            })
+            .collect();
+
+        let keep = self.history.len();
+        self.history.clear();
+        self.history.extend_from_slice(&extended[extended.len() - keep..]);
+        filtered
+    }
+}
+
+// Hamming 窗 sinc 低通，cutoff 为归一化到输入采样率的截止频率（周期/样本）
+fn lowpass_taps(cutoff: f64) -> Vec<f32> {
+    let center = (LOWPASS_TAPS - 1) as f64 / 2.0;
+    let mut taps: Vec<f64> = (0..LOWPASS_TAPS)
+        .map(|i| {
+            let x = i as f64 - center;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            let window = 0.54 - 0.46 * (2.0 * PI * i as f64 / (LOWPASS_TAPS - 1) as f64).cos();
+            sinc * window
+        })
+        .collect();
+
+    // 归一化直流增益为1
+    let sum: f64 = taps.iter().sum();
+    for tap in taps.iter_mut() {
+        *tap /= sum;
+    }
+    taps.into_iter().map(|t| t as f32).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按 chunk 个样本一块送入 seconds 秒的正弦波，返回输出的总样本数
+    fn resampled_len(input_rate: u32, chunk: usize, seconds: usize) -> usize {
+        let mut resampler = Resampler::new(input_rate, 16000);
+        let input: Vec<f32> = (0..input_rate as usize * seconds)
+            .map(|n| (2.0 * PI * 440.0 * n as f64 / input_rate as f64).sin() as f32 * 0.5)
+            .collect();
+        input.chunks(chunk).map(|block| resampler.process(block).len()).sum()
+    }
+
+    // 44.1k/48k 降到 16k：不论整段处理还是逐块（10ms、非整数比例的块长）处理，输出长度都符合采样率之比
+    #[test]
+    fn output_length_matches_rate_ratio() {
+        for input_rate in [44100u32, 48000] {
+            for chunk in [input_rate as usize * 2, input_rate as usize / 100, 1000, 333] {
+                let len = resampled_len(input_rate, chunk, 2);
+                assert!(len.abs_diff(32000) <= 1, "{}Hz 按 {} 个样本一块处理得到 {} 个样本", input_rate, chunk, len);
+            }
+        }
+    }
+
+    // 长时间逐块处理时插值位置不漂移：1 分钟的 44.1k 音频按 10ms 一块处理，输出与 16k 下的时长一致
+    #[test]
+    fn chunked_output_does_not_drift() {
+        let len = resampled_len(44100, 441, 60);
+        assert!(len.abs_diff(16000 * 60) <= 1, "输出 {} 个样本", len);
+    }
+
+    // 滤波器直流增益为1：恒定输入稳定后输出相同的值
+    #[test]
+    fn constant_input_keeps_its_level() {
+        let mut resampler = Resampler::new(44100, 16000);
+        let output: Vec<f32> = (0..10).flat_map(|_| resampler.process(&[0.25; 441])).collect();
+        assert!(output[LOWPASS_TAPS..].iter().all(|sample| (sample - 0.25).abs() < 1e-4));
+    }
+
+    #[test]
+    fn validate_input_rejects_mismatched_chunks() {
+        assert!(validate_input(441, 44100).is_ok());
+        assert!(validate_input(480, 48000).is_ok());
+        assert!(validate_input(480, 4000).is_err());
+        assert!(validate_input(48000, 48000).is_err());
+    }
+}
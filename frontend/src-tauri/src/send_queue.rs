@@ -0,0 +1,388 @@
+// 持久化待发队列（WAL）
+// 开启后每个音频段在写入socket之前先追加到磁盘日志，写入成功后追加一条确认记录；发送失败的段留在队列中，
+// 连接恢复后按原顺序补发。启动时回放日志，没有确认记录的段视为未送达，同样进入补发。
+// 所有段都已确认时截断日志；日志过大时只保留未确认的段重写。默认关闭：每段多一次磁盘写入，
+// 只在对数据完整性要求高的场景开启。日志不做fsync，覆盖应用崩溃而不是掉电。
+//
+// 记录格式（小端）：
+//   入队: 0x01 + 序号(u64) + 采样率(u32) + 样本数(u32) + 样本(i16 * 样本数)
+//   确认: 0x02 + 序号(u64)
+// 崩溃可能留下写了一半的末尾记录，回放时忽略。
+
+use log::warn;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::privacy::Suspendable;
+
+const SEND_QUEUE_DIR_NAME: &str = "send_queue";
+const SEND_QUEUE_FILE_NAME: &str = "send_queue.wal";
+const RECORD_ENQUEUE: u8 = 0x01;
+const RECORD_ACK: u8 = 0x02;
+// 未确认的段超过该数量时丢弃最旧的（20ms一帧约1分钟音频）
+const MAX_PENDING_SEGMENTS: usize = 3000;
+// 日志超过该大小且仍有未确认的段时重写，只保留未确认的段
+const COMPACT_THRESHOLD_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SendQueueStatus {
+    pub enabled: bool,
+    pub pending: usize,   // 已落盘、尚未确认的段
+    pub unsent: usize,    // 其中等待补发的段（发送失败或启动时恢复）
+    pub recovered: usize, // 启动时从日志恢复的段数
+}
+
+struct PendingSegment {
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+pub struct SendQueue {
+    path: PathBuf,
+    file: File,
+    written_bytes: u64,
+    next_seq: u64,
+    pending: BTreeMap<u64, PendingSegment>,
+    unsent: VecDeque<u64>, // 等待补发的段序号，按入队顺序
+    recovered: usize,
+    suspended: bool, // 不留痕模式：不落盘，段照常直接发送
+}
+
+pub fn send_queue_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(SEND_QUEUE_DIR_NAME)
+}
+
+impl SendQueueStatus {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            pending: 0,
+            unsent: 0,
+            recovered: 0,
+        }
+    }
+}
+
+impl SendQueue {
+    // 打开目录下的日志并回放，未确认的段进入补发队列
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("创建待发队列目录 {} 失败: {}", dir.display(), e))?;
+        let path = dir.join(SEND_QUEUE_FILE_NAME);
+        let pending = if path.exists() {
+            let content = fs::read(&path).map_err(|e| format!("读取待发队列 {} 失败: {}", path.display(), e))?;
+            replay(&content)
+        } else {
+            BTreeMap::new()
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("打开待发队列 {} 失败: {}", path.display(), e))?;
+        let mut queue = Self {
+            path,
+            file,
+            written_bytes: 0,
+            next_seq: pending.keys().next_back().map_or(1, |&seq| seq + 1),
+            unsent: pending.keys().copied().collect(),
+            recovered: pending.len(),
+            pending,
+            suspended: false,
+        };
+        // 回放后的日志只保留未确认的段
+        queue.rewrite()?;
+        Ok(queue)
+    }
+
+    // 落盘一个即将发送的段，返回序号；挂起时不落盘，返回None
+    pub fn append(&mut self, samples: &[i16], sample_rate: u32) -> Result<Option<u64>, String> {
+        if self.suspended {
+            return Ok(None);
+        }
+        if self.pending.len() >= MAX_PENDING_SEGMENTS {
+            if let Some(oldest) = self.unsent.pop_front() {
+                warn!("[警告] 待发队列已满，丢弃最旧的未送达段: {}", oldest);
+                self.ack(oldest)?;
+            }
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let record = encode_enqueue(seq, sample_rate, samples);
+        self.write_record(&record)?;
+        self.pending.insert(seq, PendingSegment {
+            sample_rate,
+            samples: samples.to_vec(),
+        });
+        Ok(Some(seq))
+    }
+
+    // 段已成功写入socket
+    pub fn ack(&mut self, seq: u64) -> Result<(), String> {
+        if self.pending.remove(&seq).is_none() {
+            return Ok(());
+        }
+        self.unsent.retain(|&unsent| unsent != seq);
+
+        if self.pending.is_empty() {
+            return self.truncate();
+        }
+        let mut record = Vec::with_capacity(9);
+        record.push(RECORD_ACK);
+        record.extend_from_slice(&seq.to_le_bytes());
+        self.write_record(&record)?;
+        if self.written_bytes > COMPACT_THRESHOLD_BYTES {
+            self.rewrite()?;
+        }
+        Ok(())
+    }
+
+    // 段发送失败，等待补发
+    pub fn mark_unsent(&mut self, seq: u64) {
+        if self.pending.contains_key(&seq) && !self.unsent.contains(&seq) {
+            self.unsent.push_back(seq);
+        }
+    }
+
+    // 下一个等待补发的段：(序号, 采样率, 样本)
+    pub fn next_unsent(&self) -> Option<(u64, u32, Vec<i16>)> {
+        let seq = *self.unsent.front()?;
+        self.pending
+            .get(&seq)
+            .map(|segment| (seq, segment.sample_rate, segment.samples.clone()))
+    }
+
+    pub fn has_unsent(&self) -> bool {
+        !self.unsent.is_empty()
+    }
+
+    pub fn status(&self) -> SendQueueStatus {
+        SendQueueStatus {
+            enabled: true,
+            pending: self.pending.len(),
+            unsent: self.unsent.len(),
+            recovered: self.recovered,
+        }
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> Result<(), String> {
+        self.file
+            .write_all(record)
+            .map_err(|e| format!("写入待发队列 {} 失败: {}", self.path.display(), e))?;
+        self.written_bytes += record.len() as u64;
+        Ok(())
+    }
+
+    fn truncate(&mut self) -> Result<(), String> {
+        self.file
+            .set_len(0)
+            .map_err(|e| format!("截断待发队列 {} 失败: {}", self.path.display(), e))?;
+        self.written_bytes = 0;
+        Ok(())
+    }
+
+    // 只保留未确认的段重写日志：先写临时文件再 rename
+    fn rewrite(&mut self) -> Result<(), String> {
+        let mut content = Vec::new();
+        for (&seq, segment) in &self.pending {
+            content.extend(encode_enqueue(seq, segment.sample_rate, &segment.samples));
+        }
+        let tmp_path = self.path.with_extension("wal.tmp");
+        fs::write(&tmp_path, &content)
+            .map_err(|e| format!("写入临时待发队列 {} 失败: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("替换待发队列 {} 失败: {}", self.path.display(), e))?;
+        self.file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("打开待发队列 {} 失败: {}", self.path.display(), e))?;
+        self.written_bytes = content.len() as u64;
+        Ok(())
+    }
+}
+
+impl Suspendable for SendQueue {
+    fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+}
+
+fn encode_enqueue(seq: u64, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(1 + 8 + 4 + 4 + samples.len() * 2);
+    record.push(RECORD_ENQUEUE);
+    record.extend_from_slice(&seq.to_le_bytes());
+    record.extend_from_slice(&sample_rate.to_le_bytes());
+    record.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    for sample in samples {
+        record.extend_from_slice(&sample.to_le_bytes());
+    }
+    record
+}
+
+// 回放日志，返回未确认的段；遇到未知记录或不完整的末尾记录时停止
+fn replay(content: &[u8]) -> BTreeMap<u64, PendingSegment> {
+    let mut pending = BTreeMap::new();
+    let mut offset = 0;
+    while let Some((record, next)) = read_record(content, offset) {
+        match record {
+            Record::Enqueue(seq, segment) => {
+                pending.insert(seq, segment);
+            }
+            Record::Ack(seq) => {
+                pending.remove(&seq);
+            }
+        }
+        offset = next;
+    }
+    pending
+}
+
+enum Record {
+    Enqueue(u64, PendingSegment),
+    Ack(u64),
+}
+
+// 读取offset处的一条记录，返回记录与下一条记录的位置
+fn read_record(content: &[u8], offset: usize) -> Option<(Record, usize)> {
+    let tag = *content.get(offset)?;
+    let seq = u64::from_le_bytes(content.get(offset + 1..offset + 9)?.try_into().ok()?);
+    match tag {
+        RECORD_ENQUEUE => {
+            let sample_rate = u32::from_le_bytes(content.get(offset + 9..offset + 13)?.try_into().ok()?);
+            let len = u32::from_le_bytes(content.get(offset + 13..offset + 17)?.try_into().ok()?) as usize;
+            let start = offset + 17;
+            let bytes = content.get(start..start + len * 2)?;
+            let samples = bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+            Some((Record::Enqueue(seq, PendingSegment { sample_rate, samples }), start + len * 2))
+        }
+        RECORD_ACK => Some((Record::Ack(seq), offset + 9)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("lumina-send-queue-{}-{}-{}", name, std::process::id(), nanos));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // 每段样本值即段标记，便于核对补发顺序
+    fn segment(mark: i16) -> Vec<i16> {
+        vec![mark; 320]
+    }
+
+    // 发送途中崩溃（直接丢弃队列，不做任何收尾）：重启后未确认的段按原顺序补发，已确认的段不再出现
+    #[test]
+    fn unacked_segments_are_resent_in_order_after_crash() {
+        let dir = temp_dir("crash");
+        {
+            let mut queue = SendQueue::open(&dir).unwrap();
+            let first = queue.append(&segment(1), 16000).unwrap().unwrap();
+            let second = queue.append(&segment(2), 16000).unwrap().unwrap();
+            queue.append(&segment(3), 8000).unwrap().unwrap();
+            queue.ack(first).unwrap();
+            // 第二段写socket失败，第三段还没来得及发送
+            queue.mark_unsent(second);
+        }
+
+        let mut queue = SendQueue::open(&dir).unwrap();
+        let status = queue.status();
+        assert_eq!(status.recovered, 2);
+        assert_eq!(status.pending, 2);
+        assert_eq!(status.unsent, 2);
+
+        let mut resent = Vec::new();
+        while let Some((seq, sample_rate, samples)) = queue.next_unsent() {
+            resent.push((seq, sample_rate, samples[0], samples.len()));
+            queue.ack(seq).unwrap();
+        }
+        assert_eq!(resent, vec![(2, 16000, 2, 320), (3, 8000, 3, 320)]);
+        assert!(!queue.has_unsent());
+        assert_eq!(queue.status().pending, 0);
+
+        // 序号在重启后继续递增，不与恢复的段冲突
+        assert_eq!(queue.append(&segment(4), 16000).unwrap(), Some(4));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 补发途中再次崩溃：已补发确认的段不重复，剩下的段在下一次启动时继续补发
+    #[test]
+    fn repeated_crashes_do_not_duplicate_segments() {
+        let dir = temp_dir("repeat");
+        {
+            let mut queue = SendQueue::open(&dir).unwrap();
+            for mark in 1..=3 {
+                queue.append(&segment(mark), 16000).unwrap();
+            }
+        }
+        {
+            let mut queue = SendQueue::open(&dir).unwrap();
+            let (seq, _, samples) = queue.next_unsent().unwrap();
+            assert_eq!(samples[0], 1);
+            queue.ack(seq).unwrap();
+        }
+
+        let queue = SendQueue::open(&dir).unwrap();
+        assert_eq!(queue.status().recovered, 2);
+        assert_eq!(queue.next_unsent().map(|(seq, _, samples)| (seq, samples[0])), Some((2, 2)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 崩溃留下写了一半的末尾记录：回放时忽略，之前完整的段照常补发，重写后的日志可以继续追加
+    #[test]
+    fn truncated_tail_record_is_ignored() {
+        let dir = temp_dir("truncated");
+        {
+            let mut queue = SendQueue::open(&dir).unwrap();
+            queue.append(&segment(1), 16000).unwrap();
+            queue.append(&segment(2), 16000).unwrap();
+        }
+        let path = dir.join(SEND_QUEUE_FILE_NAME);
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 5]).unwrap();
+
+        {
+            let mut queue = SendQueue::open(&dir).unwrap();
+            assert_eq!(queue.status().recovered, 1);
+            assert_eq!(queue.next_unsent(), Some((1, 16000, segment(1))));
+            assert_eq!(queue.append(&segment(5), 16000).unwrap(), Some(2));
+        }
+
+        let queue = SendQueue::open(&dir).unwrap();
+        assert_eq!(queue.status().recovered, 2);
+        assert_eq!(queue.next_unsent(), Some((1, 16000, segment(1))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 全部确认后日志被截断，重启时没有需要补发的段
+    #[test]
+    fn fully_acked_log_is_truncated() {
+        let dir = temp_dir("acked");
+        {
+            let mut queue = SendQueue::open(&dir).unwrap();
+            let first = queue.append(&segment(1), 16000).unwrap().unwrap();
+            let second = queue.append(&segment(2), 16000).unwrap().unwrap();
+            queue.ack(second).unwrap();
+            queue.ack(first).unwrap();
+        }
+        assert_eq!(fs::metadata(dir.join(SEND_QUEUE_FILE_NAME)).unwrap().len(), 0);
+
+        let queue = SendQueue::open(&dir).unwrap();
+        assert_eq!(queue.status().recovered, 0);
+        assert!(!queue.has_unsent());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
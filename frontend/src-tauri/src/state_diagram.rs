@@ -0,0 +1,150 @@
+// 状态机转移表与状态图导出
+// TRANSITIONS 登记状态机中所有会改变状态的转移（保持原状态的事件不列出），
+// export_state_diagram 据此生成 DOT / Mermaid 文本，用于文档与调试。
+// 调试构建下状态机每次改变状态都会核对该表，未登记的转移打印警告，避免表与实现脱节。
+
+use crate::{VadState, VadStateMachineEvent};
+
+// 转移的目标状态
+pub enum Target {
+    State(VadState),
+    PreviousVisible, // 回到进入临界态之前的可见状态
+}
+
+pub struct Transition {
+    pub from: VadState,
+    pub event: VadStateMachineEvent,
+    pub guard: Option<&'static str>, // 转移条件，无条件时为None
+    pub to: Target,
+}
+
+const fn transition(from: VadState, event: VadStateMachineEvent, to: VadState) -> Transition {
+    Transition { from, event, guard: None, to: Target::State(to) }
+}
+
+const fn guarded(from: VadState, event: VadStateMachineEvent, guard: &'static str, to: VadState) -> Transition {
+    Transition { from, event, guard: Some(guard), to: Target::State(to) }
+}
+
+pub const TRANSITIONS: &[Transition] = &[
+    // 初始
+    transition(VadState::Initial, VadStateMachineEvent::VoiceFrame, VadState::TransitionBuffer),
+    transition(VadState::Initial, VadStateMachineEvent::AudioPlaybackStart, VadState::Listening),
+    // 临界转移
+    transition(VadState::TransitionBuffer, VadStateMachineEvent::BackendReturnText, VadState::Speaking),
+    guarded(VadState::TransitionBuffer, VadStateMachineEvent::VoiceFrame, "仅本地模式下持续有语音", VadState::Speaking),
+    transition(VadState::TransitionBuffer, VadStateMachineEvent::BackendEndSession, VadState::Initial),
+    transition(VadState::TransitionBuffer, VadStateMachineEvent::BackendResetToInitial, VadState::Initial),
+    transition(VadState::TransitionBuffer, VadStateMachineEvent::AudioPlaybackStart, VadState::Listening),
+    Transition {
+        from: VadState::TransitionBuffer,
+        event: VadStateMachineEvent::TransitionTimeout,
+        guard: None,
+        to: Target::PreviousVisible,
+    },
+    // 说话中
+    guarded(VadState::Speaking, VadStateMachineEvent::SilenceFrame, "连续静音达到阈值", VadState::Waiting),
+    guarded(VadState::Speaking, VadStateMachineEvent::SilenceFrame, "达到最长发言时长", VadState::Waiting),
+    guarded(VadState::Speaking, VadStateMachineEvent::VoiceFrame, "达到最长发言时长", VadState::Waiting),
+    transition(VadState::Speaking, VadStateMachineEvent::BackendEndSession, VadState::Initial),
+    transition(VadState::Speaking, VadStateMachineEvent::BackendResetToInitial, VadState::Initial),
+    transition(VadState::Speaking, VadStateMachineEvent::AudioPlaybackStart, VadState::Listening),
+    // 等待中
+    transition(VadState::Waiting, VadStateMachineEvent::VoiceFrame, VadState::TransitionBuffer),
+    transition(VadState::Waiting, VadStateMachineEvent::BackendEndSession, VadState::Initial),
+    transition(VadState::Waiting, VadStateMachineEvent::BackendResetToInitial, VadState::Initial),
+    transition(VadState::Waiting, VadStateMachineEvent::AudioPlaybackStart, VadState::Listening),
+    // 听音中
+    guarded(VadState::Listening, VadStateMachineEvent::VoiceFrame, "用户打断", VadState::TransitionBuffer),
+    transition(VadState::Listening, VadStateMachineEvent::AudioPlaybackEnd, VadState::Initial),
+    transition(VadState::Listening, VadStateMachineEvent::BackendEndSession, VadState::Initial),
+    transition(VadState::Listening, VadStateMachineEvent::BackendResetToInitial, VadState::Initial),
+    // 按键说话
+    transition(VadState::Initial, VadStateMachineEvent::PttPressed, VadState::Speaking),
+    transition(VadState::TransitionBuffer, VadStateMachineEvent::PttPressed, VadState::Speaking),
+    transition(VadState::Waiting, VadStateMachineEvent::PttPressed, VadState::Speaking),
+    transition(VadState::Listening, VadStateMachineEvent::PttPressed, VadState::Speaking),
+    transition(VadState::Speaking, VadStateMachineEvent::PttReleased, VadState::Waiting),
+    transition(VadState::TransitionBuffer, VadStateMachineEvent::PttReleased, VadState::Waiting),
+];
+
+const ALL_STATES: [VadState; 5] = [
+    VadState::Initial,
+    VadState::TransitionBuffer,
+    VadState::Speaking,
+    VadState::Waiting,
+    VadState::Listening,
+];
+
+// 一次实际发生的状态变化是否已登记；临界态超时回到的状态由运行时决定，只核对来源与事件
+pub fn is_listed(from: &VadState, event: &VadStateMachineEvent, to: &VadState) -> bool {
+    TRANSITIONS.iter().any(|t| {
+        t.from == *from
+            && t.event == *event
+            && match &t.to {
+                Target::State(state) => state == to,
+                Target::PreviousVisible => true,
+            }
+    })
+}
+
+// 生成状态图文本，format 为 "dot" 或 "mermaid"
+pub fn export(format: &str) -> Result<String, String> {
+    let edges = edges();
+    match format {
+        "dot" => Ok(to_dot(&edges)),
+        "mermaid" => Ok(to_mermaid(&edges)),
+        _ => Err(format!("未知的状态图格式: {}，可选值: dot, mermaid", format)),
+    }
+}
+
+// 合并同一对状态之间的转移，按转移表顺序输出
+fn edges() -> Vec<(String, String, Vec<String>)> {
+    let mut edges: Vec<(String, String, Vec<String>)> = Vec::new();
+    for t in TRANSITIONS {
+        let label = match t.guard {
+            Some(guard) => format!("{:?} [{}]", t.event, guard),
+            None => format!("{:?}", t.event),
+        };
+        // 临界态超时回到进入临界态之前的状态，展开为指向每个可能来源的边
+        let targets: Vec<&VadState> = match &t.to {
+            Target::State(state) => vec![state],
+            Target::PreviousVisible => ALL_STATES
+                .iter()
+                .filter(|state| {
+                    TRANSITIONS.iter().any(|entry| {
+                        entry.from == **state && matches!(&entry.to, Target::State(to) if *to == t.from)
+                    })
+                })
+                .collect(),
+        };
+        for to in targets {
+            let (from, to) = (format!("{:?}", t.from), format!("{:?}", to));
+            match edges.iter_mut().find(|(f, t, _)| *f == from && *t == to) {
+                Some((_, _, labels)) => labels.push(label.clone()),
+                None => edges.push((from, to, vec![label.clone()])),
+            }
+        }
+    }
+    edges
+}
+
+fn to_dot(edges: &[(String, String, Vec<String>)]) -> String {
+    let mut out = String::from("digraph VadStateMachine {\n    rankdir=LR;\n");
+    out.push_str(&format!("    {:?} [shape=doublecircle];\n", VadState::Initial));
+    out.push_str(&format!("    {:?} [style=dashed];\n", VadState::TransitionBuffer));
+    for (from, to, labels) in edges {
+        out.push_str(&format!("    {} -> {} [label=\"{}\"];\n", from, to, labels.join("\\n")));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn to_mermaid(edges: &[(String, String, Vec<String>)]) -> String {
+    let mut out = String::from("stateDiagram-v2\n");
+    out.push_str(&format!("    [*] --> {:?}\n", VadState::Initial));
+    for (from, to, labels) in edges {
+        out.push_str(&format!("    {} --> {} : {}\n", from, to, labels.join(" / ")));
+    }
+    out
+}
@@ -0,0 +1,226 @@
+// 本地状态镜像
+// 开启后把 VAD 状态、麦克风静音与会话序号以 JSON 写入一个固定路径的小文件，供其他本地程序（如直播插件）
+// 直接读取，不必经过前端。状态变化时更新，两次写入至少间隔 MIN_WRITE_INTERVAL_MS，
+// 间隔内的变化只保留最新一次，到期后补写。
+// 写入先落临时文件再 rename，读取方不会读到半个文件（Windows 下 rename 同样覆盖目标文件）。
+// 应用崩溃时文件不会被删除，读取方按 updated_at 判断内容是否过期；关闭镜像或正常退出时删除文件。
+//
+// 文件格式：
+//   {"state": "Speaking", "mic_muted": false, "session_id": 3, "updated_at": 1760000000000}
+// state 为对用户可见的状态（Initial / Speaking / Waiting / Listening），updated_at 为 Unix 毫秒时间戳。
+
+use log::error;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use crate::endpoints;
+use crate::journal::now_unix_ms;
+
+const DEFAULT_MIRROR_FILE_NAME: &str = "lumina_vad_state.json";
+// 每秒最多写入10次
+const MIN_WRITE_INTERVAL_MS: u64 = 100;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MirroredState {
+    pub state: String,
+    pub mic_muted: bool,
+    pub session_id: u64,
+    pub updated_at: u64,
+}
+
+// 缺省路径：与后端 socket 同在每用户私有的运行时目录（见 endpoints::default_socket_dir），
+// 其他用户无法预先放置同名文件或符号链接；读取方按同样的规则找到文件，无需配置
+#[cfg(unix)]
+pub fn default_path() -> Result<PathBuf, String> {
+    let dir = endpoints::default_socket_dir();
+    endpoints::prepare_socket_dir(&dir)?;
+    Ok(dir.join(DEFAULT_MIRROR_FILE_NAME))
+}
+
+// Windows 下系统临时目录本就按用户区分
+#[cfg(windows)]
+pub fn default_path() -> Result<PathBuf, String> {
+    Ok(std::env::temp_dir().join(DEFAULT_MIRROR_FILE_NAME))
+}
+
+pub struct StateMirror {
+    path: Option<PathBuf>, // None为关闭
+    last_write: Option<Instant>,
+    pending: Option<MirroredState>, // 间隔内到达、尚未写入的最新状态
+    flush_scheduled: bool,
+}
+
+impl StateMirror {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            last_write: None,
+            pending: None,
+            flush_scheduled: false,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // 开启或改到新路径；改路径时删除旧文件
+    pub fn enable(&mut self, path: PathBuf) {
+        if self.path.as_ref().is_some_and(|old| *old != path) {
+            self.remove_file();
+        }
+        self.path = Some(path);
+        self.last_write = None;
+    }
+
+    // 关闭并删除文件
+    pub fn disable(&mut self) {
+        self.remove_file();
+        self.path = None;
+        self.pending = None;
+    }
+
+    // 状态变化：间隔已到则立即写入；否则暂存，返回需要等待的时长（已安排补写时为None）
+    pub fn offer(&mut self, state: MirroredState, now: Instant) -> Option<Duration> {
+        self.path.as_ref()?;
+        let wait = match self.last_write {
+            Some(last) => Duration::from_millis(MIN_WRITE_INTERVAL_MS).saturating_sub(now.saturating_duration_since(last)),
+            None => Duration::ZERO,
+        };
+        if wait.is_zero() {
+            self.pending = None;
+            self.write(&state, now);
+            return None;
+        }
+
+        self.pending = Some(state);
+        if self.flush_scheduled {
+            return None;
+        }
+        self.flush_scheduled = true;
+        Some(wait)
+    }
+
+    // 补写到期：写入暂存的最新状态
+    pub fn flush_due(&mut self, now: Instant) {
+        self.flush_scheduled = false;
+        if let Some(state) = self.pending.take() {
+            self.write(&state, now);
+        }
+    }
+
+    fn write(&mut self, state: &MirroredState, now: Instant) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        self.last_write = Some(now);
+        let state = MirroredState { updated_at: now_unix_ms(), ..state.clone() };
+        if let Err(e) = write_atomic(path, &state) {
+            error!("[错误] 写入状态镜像失败: {}", e);
+        }
+    }
+
+    fn remove_file(&self) {
+        if let Some(path) = &self.path {
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(path) {
+                    error!("[错误] 删除状态镜像文件 {} 失败: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+fn write_atomic(path: &Path, state: &MirroredState) -> Result<(), String> {
+    let content = serde_json::to_string(state).map_err(|e| format!("序列化状态镜像失败: {}", e))?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content).map_err(|e| format!("写入临时文件 {} 失败: {}", tmp_path.display(), e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("替换状态镜像文件 {} 失败: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lumina-mirror-{}-{}-{}", name, std::process::id(), now_unix_ms()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(DEFAULT_MIRROR_FILE_NAME)
+    }
+
+    fn sample_state() -> MirroredState {
+        MirroredState {
+            state: "Speaking".to_string(),
+            mic_muted: false,
+            session_id: 3,
+            updated_at: 0,
+        }
+    }
+
+    // 读取示例：外部程序只依赖文档中的四个字段及其类型，按通用 JSON 读取即可；字段增删或改名都会让这里失败
+    #[test]
+    fn reader_sees_stable_format() {
+        let path = temp_path("format");
+        let mut mirror = StateMirror::new();
+        mirror.enable(path.clone());
+        mirror.offer(sample_state(), Instant::now());
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        let mut keys: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec!["mic_muted", "session_id", "state", "updated_at"]);
+        assert_eq!(value["state"], "Speaking");
+        assert_eq!(value["mic_muted"], false);
+        assert_eq!(value["session_id"], 3);
+        assert!(value["updated_at"].as_u64().unwrap() > 0);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        // 与文件头注释中的示例逐字节一致
+        let documented = MirroredState { updated_at: 1760000000000, ..sample_state() };
+        assert_eq!(
+            serde_json::to_string(&documented).unwrap(),
+            r#"{"state":"Speaking","mic_muted":false,"session_id":3,"updated_at":1760000000000}"#
+        );
+
+        mirror.disable();
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    // 间隔内的变化只保留最新一次，到期补写
+    #[test]
+    fn changes_within_interval_are_coalesced() {
+        let path = temp_path("coalesce");
+        let mut mirror = StateMirror::new();
+        mirror.enable(path.clone());
+        let start = Instant::now();
+        assert_eq!(mirror.offer(sample_state(), start), None);
+
+        let wait = mirror.offer(MirroredState { session_id: 4, ..sample_state() }, start + Duration::from_millis(30));
+        assert_eq!(wait, Some(Duration::from_millis(MIN_WRITE_INTERVAL_MS - 30)));
+        assert_eq!(mirror.offer(MirroredState { session_id: 5, ..sample_state() }, start + Duration::from_millis(60)), None);
+        let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value["session_id"], 3);
+
+        mirror.flush_due(start + Duration::from_millis(MIN_WRITE_INTERVAL_MS));
+        let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value["session_id"], 5);
+        mirror.disable();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    // 缺省路径位于每用户 socket 目录中，目录只允许本用户访问
+    #[cfg(unix)]
+    #[test]
+    fn default_path_is_in_private_runtime_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = default_path().unwrap();
+        let dir = endpoints::default_socket_dir();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+    }
+}
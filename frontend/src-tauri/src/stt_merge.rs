@@ -0,0 +1,222 @@
+// STT 中间结果合并
+// 语音会话期间音频按帧持续发往后端，后端若对每段独立识别，中间结果会重复、回退或只含后半句。
+// 这里对 is_final=false 的中间结果做增量合并，只向前端发出稳定增长的文本；is_final 的结果原样确认并结束本句。
+// 后端的中间结果可能每几十毫秒一条，合并后再按时间窗口节流：窗口内最多发出一条，
+// 窗口内后到的结果暂存，窗口结束时发出最新的一条；最终结果不节流，并丢弃尚未发出的中间结果。
+
+use std::time::{Duration, Instant};
+
+use crate::SttResult;
+
+pub const DEFAULT_PARTIAL_THROTTLE_MS: u64 = 100;
+
+// 节流后的处理方式
+pub enum PartialEmit {
+    Now(SttResult),
+    Later(Duration), // 暂存为待发，调用方需在该时长后调用 take_due 发出
+    Held,            // 已有待发结果与定时，只更新了内容
+}
+
+pub struct PartialTranscriptMerger {
+    current: String, // 本句目前合并出的中间文本
+    throttle: Option<Duration>, // None 表示不节流
+    last_emit: Option<Instant>,
+    pending: Option<SttResult>, // 节流窗口内暂存的最新中间结果
+    flush_scheduled: bool,
+}
+
+impl PartialTranscriptMerger {
+    pub fn new() -> Self {
+        Self {
+            current: String::new(),
+            throttle: Some(Duration::from_millis(DEFAULT_PARTIAL_THROTTLE_MS)),
+            last_emit: None,
+            pending: None,
+            flush_scheduled: false,
+        }
+    }
+
+    // 0 表示不节流
+    pub fn set_throttle_ms(&mut self, throttle_ms: u64) {
+        self.throttle = (throttle_ms > 0).then(|| Duration::from_millis(throttle_ms));
+    }
+
+    // 丢弃本句的中间文本（会话结束或重置时）
+    pub fn reset(&mut self) {
+        self.current.clear();
+        self.pending = None;
+    }
+
+    // 对合并后的结果节流
+    pub fn throttle(&mut self, result: SttResult, now: Instant) -> PartialEmit {
+        if result.is_final {
+            self.pending = None;
+            return PartialEmit::Now(result);
+        }
+
+        let wait = match (self.throttle, self.last_emit) {
+            (Some(throttle), Some(last)) => throttle.saturating_sub(now.saturating_duration_since(last)),
+            _ => Duration::ZERO,
+        };
+        if wait.is_zero() {
+            self.pending = None;
+            self.last_emit = Some(now);
+            return PartialEmit::Now(result);
+        }
+
+        self.pending = Some(result);
+        if self.flush_scheduled {
+            return PartialEmit::Held;
+        }
+        self.flush_scheduled = true;
+        PartialEmit::Later(wait)
+    }
+
+    // 定时到期：取出暂存的中间结果，期间已被最终结果或重置取代时为None
+    pub fn take_due(&mut self, now: Instant) -> Option<SttResult> {
+        self.flush_scheduled = false;
+        let pending = self.pending.take()?;
+        self.last_emit = Some(now);
+        Some(pending)
+    }
+
+    // 合并一条识别结果，返回应发给前端的结果；重复或回退的中间结果返回 None
+    pub fn ingest(&mut self, result: SttResult) -> Option<SttResult> {
+        if result.is_final {
+            self.current.clear();
+            return Some(result);
+        }
+
+        let text = result.text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let merged = merge_partial(&self.current, text)?;
+        self.current = merged;
+        Some(SttResult {
+            text: self.current.clone(),
+            is_final: false,
+            session_id: result.session_id,
+            trace_id: result.trace_id,
+            turn_id: None,
+        })
+    }
+}
+
+// 按字符合并：文本没有变长时返回 None
+fn merge_partial(current: &str, text: &str) -> Option<String> {
+    if current.is_empty() || text.starts_with(current) {
+        // 前缀增长
+        return if text == current { None } else { Some(text.to_string()) };
+    }
+    if current.starts_with(text) || current.ends_with(text) {
+        // 重复或回退到更短的版本，保留已有文本
+        return None;
+    }
+
+    let current_chars: Vec<char> = current.chars().collect();
+    let text_chars: Vec<char> = text.chars().collect();
+
+    let common_prefix = current_chars
+        .iter()
+        .zip(text_chars.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common_prefix > 0 {
+        // 同一句的修订版本，以最新的为准
+        return Some(text.to_string());
+    }
+
+    // 独立识别出的后续片段：去掉与已有文本末尾重叠的部分后拼接
+    let max_overlap = current_chars.len().min(text_chars.len());
+    let overlap = (1..=max_overlap)
+        .rev()
+        .find(|&n| current_chars[current_chars.len() - n..] == text_chars[..n])
+        .unwrap_or(0);
+    let mut merged = current.to_string();
+    merged.extend(text_chars[overlap..].iter());
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(text: &str) -> SttResult {
+        SttResult {
+            text: text.to_string(),
+            is_final: false,
+            session_id: Some(1),
+            trace_id: None,
+            turn_id: None,
+        }
+    }
+
+    fn final_result(text: &str) -> SttResult {
+        SttResult { is_final: true, ..partial(text) }
+    }
+
+    fn ingest_text(merger: &mut PartialTranscriptMerger, result: SttResult) -> Option<String> {
+        merger.ingest(result).map(|result| result.text)
+    }
+
+    // 重复的中间结果不再发出
+    #[test]
+    fn duplicate_partials_are_dropped() {
+        let mut merger = PartialTranscriptMerger::new();
+        assert_eq!(ingest_text(&mut merger, partial("打开")).as_deref(), Some("打开"));
+        assert_eq!(ingest_text(&mut merger, partial("打开")), None);
+        assert_eq!(ingest_text(&mut merger, partial(" 打开 ")), None);
+        assert_eq!(ingest_text(&mut merger, partial("")), None);
+        assert_eq!(ingest_text(&mut merger, partial("打开客厅")).as_deref(), Some("打开客厅"));
+    }
+
+    // 回退到更短的版本（前缀或只剩后半句）时保留已有文本，之后继续按已有文本增长
+    #[test]
+    fn regressing_partials_keep_the_longer_text() {
+        let mut merger = PartialTranscriptMerger::new();
+        assert_eq!(ingest_text(&mut merger, partial("打开客厅")).as_deref(), Some("打开客厅"));
+        assert_eq!(ingest_text(&mut merger, partial("打开")), None);
+        assert_eq!(ingest_text(&mut merger, partial("客厅")), None);
+        assert_eq!(ingest_text(&mut merger, partial("打开客厅的灯")).as_deref(), Some("打开客厅的灯"));
+    }
+
+    // 同一句的修订以最新的为准；独立识别出的后续片段去掉重叠后拼接
+    #[test]
+    fn revisions_replace_and_continuations_append() {
+        let mut merger = PartialTranscriptMerger::new();
+        ingest_text(&mut merger, partial("打开客厅"));
+        assert_eq!(ingest_text(&mut merger, partial("打开卧室")).as_deref(), Some("打开卧室"));
+        assert_eq!(ingest_text(&mut merger, partial("卧室的灯")).as_deref(), Some("打开卧室的灯"));
+        assert_eq!(ingest_text(&mut merger, partial("谢谢")).as_deref(), Some("打开卧室的灯谢谢"));
+    }
+
+    // 最终结果原样发出并结束本句：不与之前的中间文本合并，下一句从头开始
+    #[test]
+    fn final_result_overrides_partials() {
+        let mut merger = PartialTranscriptMerger::new();
+        ingest_text(&mut merger, partial("打开客厅的等"));
+        let confirmed = merger.ingest(final_result("打开客厅的灯。")).unwrap();
+        assert!(confirmed.is_final);
+        assert_eq!(confirmed.text, "打开客厅的灯。");
+        assert_eq!(ingest_text(&mut merger, partial("明天")).as_deref(), Some("明天"));
+    }
+
+    // 节流窗口内的中间结果暂存，到期发出最新一条；窗口内到达的最终结果立即发出并丢弃暂存的中间结果
+    #[test]
+    fn final_result_discards_throttled_partial() {
+        let mut merger = PartialTranscriptMerger::new();
+        let start = Instant::now();
+        assert!(matches!(merger.throttle(partial("打开"), start), PartialEmit::Now(_)));
+        let later = start + Duration::from_millis(20);
+        assert!(matches!(merger.throttle(partial("打开客厅"), later), PartialEmit::Later(wait) if wait == Duration::from_millis(80)));
+        assert!(matches!(merger.throttle(partial("打开客厅的"), later), PartialEmit::Held));
+        assert_eq!(merger.take_due(start + Duration::from_millis(100)).map(|result| result.text).as_deref(), Some("打开客厅的"));
+
+        let next = start + Duration::from_millis(120);
+        assert!(matches!(merger.throttle(partial("打开客厅的灯"), next), PartialEmit::Later(_)));
+        assert!(matches!(merger.throttle(final_result("打开客厅的灯"), next), PartialEmit::Now(result) if result.is_final));
+        assert!(merger.take_due(start + Duration::from_millis(200)).is_none());
+    }
+}
@@ -0,0 +1,442 @@
+// 匿名遥测
+// 默认关闭，只有用户通过 set_telemetry 显式开启后才上报：每 REPORT_INTERVAL_SECS 把纯数值的聚合指标
+// （处理管线计数、单帧耗时直方图、VAD误触发估计、崩溃次数）连同匿名安装 id POST 到配置的端点，
+// 失败静默丢弃、不重试。不留痕模式与仅本地模式下暂停上报。get_telemetry_preview 返回下一次将要上报的内容。
+//
+// 绝不上传音频或文本，由两层保证：
+// - 编译期：上报内容只能由实现了 TelemetryValue 的类型组成。该 trait 是封闭的，只为数值、数值数组
+//   和本模块用 telemetry_record! 定义的结构体实现；宏为每个字段生成类型约束，字段类型若是 String、
+//   音频缓冲或其他模块的结构体则无法编译。安装 id 是 InstallId newtype，只能由本模块生成或按格式校验后构造。
+// - 运行时：发送前把序列化结果重新解析，除 install_id 外出现任何非数值叶子，本次上报即作废。
+// 需要启用 telemetry feature（ureq）才能开启上报；未启用时仍可预览。
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::journal::now_unix_ms;
+use crate::metrics::FRAME_TIMING_BUCKET_COUNT;
+
+// 上报间隔
+pub const REPORT_INTERVAL_SECS: u64 = 300;
+// 上报请求的超时时间
+#[cfg(feature = "telemetry")]
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+// 上报内容的格式版本，字段变化时递增
+const SCHEMA_VERSION: u64 = 1;
+// 安装 id：32位小写十六进制
+const INSTALL_ID_LEN: usize = 32;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+// 允许进入遥测序列化路径的类型；封闭 trait，模块外无法为其他类型实现
+pub trait TelemetryValue: Serialize + sealed::Sealed {}
+
+macro_rules! telemetry_scalar {
+    ($($ty:ty),*) => {
+        $(
+            impl sealed::Sealed for $ty {}
+            impl TelemetryValue for $ty {}
+        )*
+    };
+}
+
+telemetry_scalar!(u64, f64);
+
+impl<T: TelemetryValue, const N: usize> sealed::Sealed for [T; N] {}
+impl<T: TelemetryValue, const N: usize> TelemetryValue for [T; N] where [T; N]: Serialize {}
+
+// 定义一个只含遥测值的结构体：每个字段的类型都必须实现 TelemetryValue，否则编译失败
+macro_rules! telemetry_record {
+    (pub struct $name:ident { $($field:ident: $ty:ty,)* }) => {
+        #[derive(Serialize, Clone, Debug)]
+        pub struct $name {
+            $(pub $field: $ty,)*
+        }
+
+        impl sealed::Sealed for $name {}
+        impl TelemetryValue for $name {}
+
+        const _: () = {
+            fn assert_telemetry_value<T: TelemetryValue>() {}
+            #[allow(dead_code)]
+            fn assert_fields() {
+                $(assert_telemetry_value::<$ty>();)*
+            }
+        };
+    };
+}
+
+// 匿名安装 id，与用户、设备信息无关；私有字段，只能由 generate 或 parse 构造
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstallId(String);
+
+impl InstallId {
+    // 用系统随机种子生成，不含任何设备信息
+    pub fn generate() -> Self {
+        let mut id = String::with_capacity(INSTALL_ID_LEN);
+        for _ in 0..INSTALL_ID_LEN / 16 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(now_unix_ms());
+            hasher.write_u32(std::process::id());
+            id.push_str(&format!("{:016x}", hasher.finish()));
+        }
+        Self(id)
+    }
+
+    // 从配置读取时校验格式，不合法的值被丢弃并重新生成
+    pub fn parse(value: &str) -> Option<Self> {
+        is_install_id(value).then(|| Self(value.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_install_id(value: &str) -> bool {
+    value.len() == INSTALL_ID_LEN && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl Serialize for InstallId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl sealed::Sealed for InstallId {}
+impl TelemetryValue for InstallId {}
+
+telemetry_record! {
+    pub struct PipelineTelemetry {
+        frames_processed: u64,
+        voice_frames: u64,
+        frames_dropped: u64,
+        frames_sent: u64,
+        send_failures: u64,
+        fallback_frames: u64,
+        speech_started: u64,
+        speech_ended: u64,
+    }
+}
+
+telemetry_record! {
+    pub struct LatencyTelemetry {
+        // 单帧处理耗时直方图，桶划分见 metrics::FRAME_TIMING_BUCKETS_MS
+        frame_timing_histogram: [u64; FRAME_TIMING_BUCKET_COUNT],
+        frame_timing_p50_ms: f64,
+        frame_timing_p99_ms: f64,
+        frame_interval_jitter_ms: f64,
+    }
+}
+
+telemetry_record! {
+    pub struct VadTelemetry {
+        completed_turns: u64,
+        // 误触发估计：已完成但没有得到任何识别文本的轮次
+        empty_turns: u64,
+        false_trigger_ratio: f64,
+    }
+}
+
+telemetry_record! {
+    pub struct TelemetryReport {
+        install_id: InstallId,
+        schema_version: u64,
+        uptime_secs: u64,
+        pipeline: PipelineTelemetry,
+        latency: LatencyTelemetry,
+        vad: VadTelemetry,
+        // 上次成功上报以来检测到的异常退出次数
+        crashes: u64,
+    }
+}
+
+// 持久化在配置中的遥测设置
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    pub install_id: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    pub install_id: Option<String>,
+    pub available: bool,               // 本次构建是否启用了 telemetry feature
+    pub reports_sent: u64,
+    pub last_sent_at_ms: Option<u64>,
+}
+
+pub fn validate_endpoint(endpoint: &str) -> Result<(), String> {
+    if !(endpoint.starts_with("https://") || endpoint.starts_with("http://")) {
+        return Err(format!("遥测端点必须是 http(s) 地址: {}", endpoint));
+    }
+    Ok(())
+}
+
+pub struct Telemetry {
+    enabled: bool,
+    endpoint: Option<String>,
+    install_id: Option<InstallId>,
+    pending_crashes: u64, // 尚未成功上报的异常退出次数
+    reports_sent: u64,
+    last_sent_at_ms: Option<u64>,
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            install_id: None,
+            pending_crashes: 0,
+            reports_sent: 0,
+            last_sent_at_ms: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref()
+    }
+
+    // 应用设置；开启时若还没有安装 id 则生成一个。返回应写回配置的设置
+    pub fn configure(&mut self, settings: &TelemetrySettings) -> Result<TelemetrySettings, String> {
+        if let Some(endpoint) = &settings.endpoint {
+            validate_endpoint(endpoint)?;
+        }
+        if settings.enabled && settings.endpoint.is_none() {
+            return Err("开启遥测需要指定上报端点".to_string());
+        }
+        if self.install_id.is_none() {
+            self.install_id = settings.install_id.as_deref().and_then(InstallId::parse);
+        }
+        if settings.enabled && self.install_id.is_none() {
+            self.install_id = Some(InstallId::generate());
+        }
+        self.enabled = settings.enabled;
+        self.endpoint = settings.endpoint.clone();
+        Ok(self.settings())
+    }
+
+    pub fn settings(&self) -> TelemetrySettings {
+        TelemetrySettings {
+            enabled: self.enabled,
+            endpoint: self.endpoint.clone(),
+            install_id: self.install_id.as_ref().map(|id| id.as_str().to_string()),
+        }
+    }
+
+    pub fn record_crash(&mut self) {
+        self.pending_crashes += 1;
+    }
+
+    pub fn pending_crashes(&self) -> u64 {
+        self.pending_crashes
+    }
+
+    // 预览时尚未生成安装 id，用全零占位
+    pub fn install_id(&self) -> InstallId {
+        self.install_id.clone().unwrap_or_else(|| InstallId("0".repeat(INSTALL_ID_LEN)))
+    }
+
+    // 上报成功：已报告的崩溃次数清零
+    pub fn mark_sent(&mut self, crashes_reported: u64) {
+        self.pending_crashes = self.pending_crashes.saturating_sub(crashes_reported);
+        self.reports_sent += 1;
+        self.last_sent_at_ms = Some(now_unix_ms());
+    }
+
+    pub fn status(&self) -> TelemetryStatus {
+        TelemetryStatus {
+            enabled: self.enabled,
+            endpoint: self.endpoint.clone(),
+            install_id: self.install_id.as_ref().map(|id| id.as_str().to_string()),
+            available: cfg!(feature = "telemetry"),
+            reports_sent: self.reports_sent,
+            last_sent_at_ms: self.last_sent_at_ms,
+        }
+    }
+}
+
+pub fn build_report(
+    install_id: InstallId,
+    uptime_secs: u64,
+    pipeline: PipelineTelemetry,
+    latency: LatencyTelemetry,
+    vad: VadTelemetry,
+    crashes: u64,
+) -> TelemetryReport {
+    TelemetryReport {
+        install_id,
+        schema_version: SCHEMA_VERSION,
+        uptime_secs,
+        pipeline,
+        latency,
+        vad,
+        crashes,
+    }
+}
+
+// 序列化并做运行时检查：除 install_id 外只允许数值
+pub fn serialize_report(report: &TelemetryReport) -> Result<serde_json::Value, String> {
+    let value = serde_json::to_value(report).map_err(|e| format!("序列化遥测报告失败: {}", e))?;
+    ensure_numeric(&value, "")?;
+    Ok(value)
+}
+
+fn ensure_numeric(value: &serde_json::Value, path: &str) -> Result<(), String> {
+    match value {
+        serde_json::Value::Number(_) => Ok(()),
+        serde_json::Value::String(text) if path == "install_id" && is_install_id(text) => Ok(()),
+        serde_json::Value::Array(items) => items.iter().try_for_each(|item| ensure_numeric(item, path)),
+        serde_json::Value::Object(fields) => fields.iter().try_for_each(|(key, field)| {
+            let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            ensure_numeric(field, &child)
+        }),
+        _ => Err(format!("遥测报告字段 {} 不是数值，已拒绝上报", path)),
+    }
+}
+
+// POST 到端点；调用方负责静默丢弃失败
+#[cfg(feature = "telemetry")]
+pub fn send(endpoint: &str, payload: &serde_json::Value) -> Result<(), String> {
+    ureq::post(endpoint)
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .send_json(payload)
+        .map(|_| ())
+        .map_err(|e| format!("上报遥测失败: {}", e))
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub fn send(_endpoint: &str, _payload: &serde_json::Value) -> Result<(), String> {
+    Err(crate::features::disabled_error(crate::features::Feature::Telemetry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 在编译期判断类型是否实现了 TelemetryValue：约束满足时取 Probe 的固有常量，否则退回 trait 的默认值
+    trait NotTelemetryValue {
+        const IS_TELEMETRY_VALUE: bool = false;
+    }
+    impl<T: ?Sized> NotTelemetryValue for T {}
+
+    struct Probe<T: ?Sized>(std::marker::PhantomData<T>);
+
+    impl<T: TelemetryValue> Probe<T> {
+        const IS_TELEMETRY_VALUE: bool = true;
+    }
+
+    macro_rules! is_telemetry_value {
+        ($ty:ty) => {
+            <Probe<$ty>>::IS_TELEMETRY_VALUE
+        };
+    }
+
+    // 编译期断言：文本与音频类型都不能进入遥测结构体，不成立时测试无法编译
+    const _: () = {
+        // 文本
+        assert!(!is_telemetry_value!(String));
+        assert!(!is_telemetry_value!(&'static str));
+        assert!(!is_telemetry_value!(Option<String>));
+        assert!(!is_telemetry_value!([String; 1]));
+        assert!(!is_telemetry_value!(crate::SttResult));
+        assert!(!is_telemetry_value!(crate::turns::Turn));
+        // 音频：PCM 样本、缓冲与编码后的字节
+        assert!(!is_telemetry_value!(i16));
+        assert!(!is_telemetry_value!(f32));
+        assert!(!is_telemetry_value!([i16; 320]));
+        assert!(!is_telemetry_value!(Vec<i16>));
+        assert!(!is_telemetry_value!(Vec<f32>));
+        assert!(!is_telemetry_value!(Vec<u8>));
+        assert!(!is_telemetry_value!(Vec<u64>));
+        // 任意 JSON 值可以夹带文本
+        assert!(!is_telemetry_value!(serde_json::Value));
+    };
+
+    // 编译期断言：纯数值的类型与 telemetry_record! 定义的结构体可以上报
+    const _: () = {
+        assert!(is_telemetry_value!(u64));
+        assert!(is_telemetry_value!(f64));
+        assert!(is_telemetry_value!([u64; FRAME_TIMING_BUCKET_COUNT]));
+        assert!(is_telemetry_value!(InstallId));
+        assert!(is_telemetry_value!(PipelineTelemetry));
+        assert!(is_telemetry_value!(LatencyTelemetry));
+        assert!(is_telemetry_value!(VadTelemetry));
+        assert!(is_telemetry_value!(TelemetryReport));
+    };
+
+    fn sample_report() -> TelemetryReport {
+        build_report(
+            InstallId::generate(),
+            600,
+            PipelineTelemetry {
+                frames_processed: 1000,
+                voice_frames: 400,
+                frames_dropped: 0,
+                frames_sent: 400,
+                send_failures: 0,
+                fallback_frames: 0,
+                speech_started: 5,
+                speech_ended: 5,
+            },
+            LatencyTelemetry {
+                frame_timing_histogram: [1; FRAME_TIMING_BUCKET_COUNT],
+                frame_timing_p50_ms: 0.4,
+                frame_timing_p99_ms: 2.5,
+                frame_interval_jitter_ms: 1.2,
+            },
+            VadTelemetry {
+                completed_turns: 5,
+                empty_turns: 1,
+                false_trigger_ratio: 0.2,
+            },
+            0,
+        )
+    }
+
+    // 序列化结果中只有 install_id 是字符串
+    #[test]
+    fn serialized_report_is_numeric_except_install_id() {
+        let value = serialize_report(&sample_report()).unwrap();
+        assert!(value["install_id"].is_string());
+        assert_eq!(value["schema_version"], json!(SCHEMA_VERSION));
+        assert_eq!(value["pipeline"]["voice_frames"], json!(400));
+    }
+
+    // 运行时检查：任何位置出现的文本都会让本次上报作废
+    #[test]
+    fn runtime_check_rejects_text_leaves() {
+        let id = "0123456789abcdef0123456789abcdef";
+        assert!(ensure_numeric(&json!({"install_id": id, "crashes": 0}), "").is_ok());
+
+        let error = ensure_numeric(&json!({"install_id": id, "vad": {"last_text": "你好"}}), "").unwrap_err();
+        assert!(error.contains("vad.last_text"), "{}", error);
+        assert!(ensure_numeric(&json!({"install_id": "不是安装id"}), "").is_err());
+        assert!(ensure_numeric(&json!({"pipeline": {"install_id": id}}), "").is_err());
+        assert!(ensure_numeric(&json!({"samples": [1, 2, "3"]}), "").is_err());
+        assert!(ensure_numeric(&json!({"flag": true}), "").is_err());
+        assert!(ensure_numeric(&json!({"missing": null}), "").is_err());
+    }
+
+    #[test]
+    fn install_id_is_validated() {
+        let generated = InstallId::generate();
+        assert!(is_install_id(generated.as_str()));
+        assert_eq!(InstallId::parse(generated.as_str()), Some(generated));
+        assert_eq!(InstallId::parse("0123456789ABCDEF0123456789ABCDEF"), None);
+        assert_eq!(InstallId::parse("user@example.com"), None);
+    }
+}
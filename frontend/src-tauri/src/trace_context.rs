@@ -0,0 +1,467 @@
+// 客户端链路追踪（OpenTelemetry 风格的 span）
+// 每个会话（一次发言及其回复）生成一个 trace，根 span 为 utterance，子 span 依次为：
+//   capture：进入临界态到语音结束；send：第一个音频包发出到语音结束；
+//   await_stt：语音结束到最终识别结果；await_tts：最终识别结果到回复开始播放；playback：回复开始播放到全部播完。
+// trace 上下文随会话标记通过 0x0D 控制消息（W3C traceparent 的二进制形式）传给后端，后端把自己的 span 挂到同一条 trace 下，
+// 并在识别结果中回传 trace_id；前端核对后记入 await_stt 的属性，get_trace_status 可查看最近一次核对结果。
+// 会话结束（回复播完或开始下一个会话）时，已结束的 span 以 [追踪] 标签写入日志（debug 级别）；
+// 设置了 OTEL_EXPORTER_OTLP_ENDPOINT 时另外按 OTLP/HTTP JSON 上报到 {endpoint}/v1/traces，失败只记日志。
+// 需要启用 otel feature；未启用时 SessionTracer 是零大小类型、方法均为空，调用处不产生任何开销，也不发送 0x0D。
+
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TraceStatus {
+    pub enabled: bool,                   // 本次构建是否启用了 otel feature
+    pub session_id: u64,                 // 当前 trace 所属的会话序号，尚未开始会话时为0
+    pub trace_id: Option<String>,        // 当前 trace 的 id（32位十六进制）
+    pub echo_matched: Option<bool>,      // 最近一次后端回传的 trace_id 是否与发出的一致，尚未收到回传时为None
+    pub exporter_endpoint: Option<String>,
+    pub exported_traces: u64,            // 已交给 OTLP 上报的 trace 数
+}
+
+#[cfg(feature = "otel")]
+pub use enabled::SessionTracer;
+
+#[cfg(not(feature = "otel"))]
+pub struct SessionTracer;
+
+#[cfg(not(feature = "otel"))]
+impl SessionTracer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[inline(always)]
+    pub fn begin(&mut self, _session_id: u64) {}
+
+    #[inline(always)]
+    pub fn wire_context(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    #[inline(always)]
+    pub fn audio_sent(&mut self) {}
+
+    #[inline(always)]
+    pub fn speech_ended(&mut self) {}
+
+    #[inline(always)]
+    pub fn transcript_final(&mut self, _echoed_trace_id: Option<&str>) {}
+
+    #[inline(always)]
+    pub fn playback_started(&mut self) {}
+
+    #[inline(always)]
+    pub fn playback_finished(&mut self, _more_pending: bool) {}
+
+    pub fn status(&self) -> TraceStatus {
+        TraceStatus {
+            enabled: false,
+            session_id: 0,
+            trace_id: None,
+            echo_matched: None,
+            exporter_endpoint: None,
+            exported_traces: 0,
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+mod enabled {
+    use log::{debug, warn};
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::TraceStatus;
+    use crate::protocol;
+
+    // 上报端点的环境变量，与 OpenTelemetry SDK 的约定一致
+    const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+    const OTLP_TIMEOUT_SECS: u64 = 5;
+    const SERVICE_NAME: &str = "lumina-frontend";
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum SpanKind {
+        Capture,
+        Send,
+        AwaitStt,
+        AwaitTts,
+        Playback,
+    }
+
+    impl SpanKind {
+        fn name(self) -> &'static str {
+            match self {
+                SpanKind::Capture => "capture",
+                SpanKind::Send => "send",
+                SpanKind::AwaitStt => "await_stt",
+                SpanKind::AwaitTts => "await_tts",
+                SpanKind::Playback => "playback",
+            }
+        }
+    }
+
+    struct OpenSpan {
+        kind: Option<SpanKind>, // None 为根 span
+        span_id: [u8; 8],
+        start_unix_nanos: u128,
+        attributes: Vec<(&'static str, String)>,
+    }
+
+    struct FinishedSpan {
+        name: &'static str,
+        span_id: [u8; 8],
+        parent_span_id: Option<[u8; 8]>,
+        start_unix_nanos: u128,
+        end_unix_nanos: u128,
+        attributes: Vec<(&'static str, String)>,
+    }
+
+    struct ActiveTrace {
+        session_id: u64,
+        trace_id: [u8; 16],
+        root: OpenSpan,
+        open: Vec<OpenSpan>,
+        finished: Vec<FinishedSpan>,
+    }
+
+    pub struct SessionTracer {
+        active: Option<ActiveTrace>,
+        echo_matched: Option<bool>,
+        endpoint: Option<String>,
+        exported_traces: u64,
+    }
+
+    impl SessionTracer {
+        pub fn new() -> Self {
+            Self {
+                active: None,
+                echo_matched: None,
+                endpoint: std::env::var(OTLP_ENDPOINT_ENV).ok().filter(|endpoint| !endpoint.trim().is_empty()),
+                exported_traces: 0,
+            }
+        }
+
+        // 新会话开始：结束上一条 trace，开启新的根 span 与 capture
+        pub fn begin(&mut self, session_id: u64) {
+            self.finish();
+            let mut root = open_span(None);
+            root.attributes.push(("lumina.session_id", session_id.to_string()));
+            let mut trace = ActiveTrace {
+                session_id,
+                trace_id: random_trace_id(),
+                root,
+                open: Vec::new(),
+                finished: Vec::new(),
+            };
+            trace.start(SpanKind::Capture);
+            self.active = Some(trace);
+            self.echo_matched = None;
+        }
+
+        // 随会话标记发给后端的 trace 上下文，父 span 为根 span
+        pub fn wire_context(&self) -> Option<Vec<u8>> {
+            self.active
+                .as_ref()
+                .map(|trace| protocol::encode_trace_context(trace.session_id, &trace.trace_id, &trace.root.span_id))
+        }
+
+        pub fn audio_sent(&mut self) {
+            if let Some(trace) = self.active.as_mut() {
+                trace.start(SpanKind::Send);
+            }
+        }
+
+        pub fn speech_ended(&mut self) {
+            if let Some(trace) = self.active.as_mut() {
+                trace.end(SpanKind::Capture);
+                trace.end(SpanKind::Send);
+                trace.start(SpanKind::AwaitStt);
+            }
+        }
+
+        // 最终识别结果到达；后端回传了 trace_id 时核对是否与本会话一致
+        pub fn transcript_final(&mut self, echoed_trace_id: Option<&str>) {
+            let trace = match self.active.as_mut() {
+                Some(trace) => trace,
+                None => return,
+            };
+            if let Some(echoed) = echoed_trace_id {
+                let expected = hex(&trace.trace_id);
+                let matched = echoed.eq_ignore_ascii_case(&expected);
+                if !matched {
+                    warn!("[警告] 后端回传的trace_id与本会话不一致: 期望{}，收到{}", expected, echoed);
+                }
+                trace.attribute(SpanKind::AwaitStt, "lumina.trace_echo", if matched { "matched" } else { "mismatched" });
+                self.echo_matched = Some(matched);
+            }
+            trace.end(SpanKind::AwaitStt);
+            trace.start(SpanKind::AwaitTts);
+        }
+
+        pub fn playback_started(&mut self) {
+            if let Some(trace) = self.active.as_mut() {
+                trace.end(SpanKind::AwaitTts);
+                trace.start(SpanKind::Playback);
+            }
+        }
+
+        // 一条回复播完；没有后续回复时本会话的 trace 结束
+        pub fn playback_finished(&mut self, more_pending: bool) {
+            if !more_pending {
+                self.finish();
+            }
+        }
+
+        pub fn status(&self) -> TraceStatus {
+            TraceStatus {
+                enabled: true,
+                session_id: self.active.as_ref().map_or(0, |trace| trace.session_id),
+                trace_id: self.active.as_ref().map(|trace| hex(&trace.trace_id)),
+                echo_matched: self.echo_matched,
+                exporter_endpoint: self.endpoint.clone(),
+                exported_traces: self.exported_traces,
+            }
+        }
+
+        // 结束仍在进行的 span 与根 span，写日志并上报
+        fn finish(&mut self) {
+            let mut trace = match self.active.take() {
+                Some(trace) => trace,
+                None => return,
+            };
+            let now = unix_nanos();
+            for span in std::mem::take(&mut trace.open) {
+                trace.close(span, now);
+            }
+            let root = std::mem::replace(&mut trace.root, open_span(None));
+            trace.finished.push(FinishedSpan {
+                name: "utterance",
+                span_id: root.span_id,
+                parent_span_id: None,
+                start_unix_nanos: root.start_unix_nanos,
+                end_unix_nanos: now,
+                attributes: root.attributes,
+            });
+
+            let trace_id = hex(&trace.trace_id);
+            for span in &trace.finished {
+                debug!(
+                    "[追踪] trace={} span={} parent={} name={} duration_ms={:.1} {:?}",
+                    trace_id,
+                    hex(&span.span_id),
+                    span.parent_span_id.as_ref().map(|id| hex(id)).unwrap_or_default(),
+                    span.name,
+                    span.end_unix_nanos.saturating_sub(span.start_unix_nanos) as f64 / 1_000_000.0,
+                    span.attributes
+                );
+            }
+
+            if let Some(endpoint) = self.endpoint.clone() {
+                let payload = otlp_payload(&trace_id, &trace.finished);
+                self.exported_traces += 1;
+                // 上报在后台线程中进行，不阻塞音频与状态机
+                std::thread::spawn(move || {
+                    if let Err(e) = export(&endpoint, &payload) {
+                        warn!("[警告] 上报追踪数据失败: {}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    impl ActiveTrace {
+        // 同一类 span 每个会话只开启一次，重复调用忽略
+        fn start(&mut self, kind: SpanKind) {
+            let started = self.open.iter().any(|span| span.kind == Some(kind))
+                || self.finished.iter().any(|span| span.name == kind.name());
+            if !started {
+                self.open.push(open_span(Some(kind)));
+            }
+        }
+
+        fn end(&mut self, kind: SpanKind) {
+            if let Some(index) = self.open.iter().position(|span| span.kind == Some(kind)) {
+                let span = self.open.remove(index);
+                self.close(span, unix_nanos());
+            }
+        }
+
+        fn attribute(&mut self, kind: SpanKind, key: &'static str, value: &str) {
+            if let Some(span) = self.open.iter_mut().find(|span| span.kind == Some(kind)) {
+                span.attributes.push((key, value.to_string()));
+            }
+        }
+
+        fn close(&mut self, span: OpenSpan, end_unix_nanos: u128) {
+            self.finished.push(FinishedSpan {
+                name: span.kind.map_or("utterance", SpanKind::name),
+                span_id: span.span_id,
+                parent_span_id: Some(self.root.span_id),
+                start_unix_nanos: span.start_unix_nanos,
+                end_unix_nanos,
+                attributes: span.attributes,
+            });
+        }
+    }
+
+    fn open_span(kind: Option<SpanKind>) -> OpenSpan {
+        OpenSpan {
+            kind,
+            span_id: random_u64().to_be_bytes(),
+            start_unix_nanos: unix_nanos(),
+            attributes: Vec::new(),
+        }
+    }
+
+    fn unix_nanos() -> u128 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+    }
+
+    // 用系统随机种子生成，不含任何设备信息
+    fn random_u64() -> u64 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(unix_nanos());
+        hasher.finish()
+    }
+
+    fn random_trace_id() -> [u8; 16] {
+        let mut id = [0u8; 16];
+        id[..8].copy_from_slice(&random_u64().to_be_bytes());
+        id[8..].copy_from_slice(&random_u64().to_be_bytes());
+        id
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // OTLP/HTTP JSON 编码：id 为十六进制，时间为字符串形式的纳秒
+    fn otlp_payload(trace_id: &str, spans: &[FinishedSpan]) -> serde_json::Value {
+        let spans: Vec<serde_json::Value> = spans
+            .iter()
+            .map(|span| {
+                let attributes: Vec<serde_json::Value> = span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| serde_json::json!({ "key": key, "value": { "stringValue": value } }))
+                    .collect();
+                serde_json::json!({
+                    "traceId": trace_id,
+                    "spanId": hex(&span.span_id),
+                    "parentSpanId": span.parent_span_id.as_ref().map(|id| hex(id)).unwrap_or_default(),
+                    "name": span.name,
+                    "kind": 1,
+                    "startTimeUnixNano": span.start_unix_nanos.to_string(),
+                    "endTimeUnixNano": span.end_unix_nanos.to_string(),
+                    "attributes": attributes,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{ "key": "service.name", "value": { "stringValue": SERVICE_NAME } }]
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "lumina" },
+                    "spans": spans,
+                }]
+            }]
+        })
+    }
+
+    fn export(endpoint: &str, payload: &serde_json::Value) -> Result<(), String> {
+        let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        ureq::post(&url)
+            .timeout(Duration::from_secs(OTLP_TIMEOUT_SECS))
+            .send_json(payload)
+            .map(|_| ())
+            .map_err(|e| format!("{}: {}", url, e))
+    }
+
+    #[cfg(all(test, unix))]
+    mod tests {
+        use super::*;
+        use crate::SttResult;
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        // 模拟后端：读取一条 0x0D 追踪上下文，把 echo 给出的 trace_id（缺省为上下文中的）放进最终识别结果回传
+        fn echo_backend(mut stream: UnixStream, echo: Option<String>) -> std::thread::JoinHandle<()> {
+            std::thread::spawn(move || {
+                let mut packet = [0u8; 4 + 1 + 8 + 16 + 8 + 1];
+                stream.read_exact(&mut packet).unwrap();
+                assert_eq!(packet[4], protocol::CONTROL_TRACE_CONTEXT);
+                let trace_id = echo.unwrap_or_else(|| hex(&packet[13..29]));
+                let result = serde_json::json!({ "text": "打开客厅的灯", "is_final": true, "trace_id": trace_id });
+                writeln!(stream, "{}", result).unwrap();
+            })
+        }
+
+        // 前端按监听器的方式发出上下文、读取回传的识别结果并核对，返回 await_stt span 上的核对属性
+        fn round_trip(tracer: &mut SessionTracer, echo: Option<String>) -> Option<String> {
+            let (mut client, backend) = UnixStream::pair().unwrap();
+            let handle = echo_backend(backend, echo);
+            client.write_all(&tracer.wire_context().unwrap()).unwrap();
+            tracer.audio_sent();
+            tracer.speech_ended();
+
+            let mut line = String::new();
+            BufReader::new(&client).read_line(&mut line).unwrap();
+            handle.join().unwrap();
+            let result: SttResult = serde_json::from_str(&line).unwrap();
+            tracer.transcript_final(result.trace_id.as_deref());
+
+            let trace = tracer.active.as_ref().unwrap();
+            let span = trace.finished.iter().find(|span| span.name == "await_stt").unwrap();
+            assert_eq!(span.parent_span_id, Some(trace.root.span_id));
+            span.attributes
+                .iter()
+                .find(|(key, _)| *key == "lumina.trace_echo")
+                .map(|(_, value)| value.clone())
+        }
+
+        // 后端原样回传 trace_id：核对一致，结果记在本会话的 await_stt span 上
+        #[test]
+        fn echoed_trace_id_matches_its_span() {
+            let mut tracer = SessionTracer::new();
+            tracer.begin(3);
+            assert_eq!(round_trip(&mut tracer, None).as_deref(), Some("matched"));
+            let status = tracer.status();
+            assert_eq!(status.echo_matched, Some(true));
+            assert_eq!(status.session_id, 3);
+        }
+
+        // 后端回传的是上一会话的 trace_id：标记为不一致
+        #[test]
+        fn stale_trace_id_is_reported_as_mismatch() {
+            let mut tracer = SessionTracer::new();
+            tracer.begin(1);
+            let previous = tracer.status().trace_id.unwrap();
+            tracer.begin(2);
+            assert_ne!(tracer.status().trace_id.as_deref(), Some(previous.as_str()));
+            assert_eq!(round_trip(&mut tracer, Some(previous)).as_deref(), Some("mismatched"));
+            assert_eq!(tracer.status().echo_matched, Some(false));
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "otel")))]
+mod tests {
+    use super::*;
+
+    // 未启用 otel feature 时不生成追踪上下文，也就不会发送 0x0D
+    #[test]
+    fn disabled_tracer_sends_no_context() {
+        let mut tracer = SessionTracer::new();
+        tracer.begin(1);
+        tracer.transcript_final(Some("00000000000000000000000000000001"));
+        assert!(tracer.wire_context().is_none());
+        let status = tracer.status();
+        assert!(!status.enabled);
+        assert_eq!(status.echo_matched, None);
+    }
+}
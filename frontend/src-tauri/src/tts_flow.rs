@@ -0,0 +1,288 @@
+// TTS 接收速率保护
+// 后端异常时可能一次性灌下远超实时的音频，全部读入再转发会让内存暴涨、前端编码卡死。
+// 接收路径按音频时长做令牌桶整形：桶按 max_speedup 倍实时速率回填，每收到一块扣除该块时长，
+// 扣成负数时暂停读取 socket，直到按节奏回填为止；排队中的音频超过 max_buffered_ms 时同样暂停读取，
+// 让 TCP 背压传回后端。单块时长超过 max_buffered_ms 的块不丢弃，按整帧切成若干段逐段入队，
+// 每段入队前同样等待整形放行；字节数超过上限的块不整块读入内存，边读边切。打断时整形状态立即清空。
+
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+pub const DEFAULT_TTS_MAX_SPEEDUP: f64 = 4.0;
+pub const DEFAULT_TTS_MAX_BUFFERED_MS: u64 = 60_000;
+// 令牌桶容量：正常回复一次下发的整句音频不受整形影响
+const BURST_ALLOWANCE_MS: f64 = 10_000.0;
+// 读取长度头后按最高码率（48kHz 双声道 16 位）估算块的字节上限，超出的块不整块分配内存，边读边切段
+const MAX_BYTES_PER_SECOND: u64 = 48_000 * 2 * 2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FlowSettings {
+    pub max_speedup: f64,     // 允许的接收速度，相对实时速率的倍数
+    pub max_buffered_ms: u64, // 排队音频的上限
+}
+
+impl Default for FlowSettings {
+    fn default() -> Self {
+        Self {
+            max_speedup: DEFAULT_TTS_MAX_SPEEDUP,
+            max_buffered_ms: DEFAULT_TTS_MAX_BUFFERED_MS,
+        }
+    }
+}
+
+impl FlowSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.max_speedup.is_finite() || self.max_speedup < 1.0 || self.max_speedup > 100.0 {
+            return Err(format!("max_speedup必须在1~100之间: {}", self.max_speedup));
+        }
+        if !(1_000..=600_000).contains(&self.max_buffered_ms) {
+            return Err(format!("max_buffered_ms必须在1000~600000之间: {}", self.max_buffered_ms));
+        }
+        Ok(())
+    }
+
+    pub fn max_chunk_bytes(&self) -> usize {
+        (self.max_buffered_ms * MAX_BYTES_PER_SECOND / 1000) as usize
+    }
+
+    // 超大块切分后每段的时长上限：取缓冲上限的一半，单段入队后排队总时长不会远超上限
+    pub fn piece_ms(&self) -> u64 {
+        self.max_buffered_ms / 2
+    }
+}
+
+// 暂停读取或切分音频的原因
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FloodReason {
+    RateLimited,    // 接收速度超过实时速率的 max_speedup 倍
+    BufferFull,     // 排队音频超过上限
+    OversizedChunk, // 单块音频超过上限，已切分为多段按节奏入队
+}
+
+// tts-flood-warning 事件内容
+#[derive(Serialize, Clone, Debug)]
+pub struct FloodWarning {
+    pub reason: FloodReason,
+    pub buffered_ms: u64,
+    pub chunk_ms: Option<u64>, // 仅 OversizedChunk
+    pub settings: FlowSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowStatus {
+    pub settings: FlowSettings,
+    pub holding: Option<FloodReason>, // 当前暂停读取的原因
+    pub rate_limited: u64,            // 因速率暂停读取的次数
+    pub buffer_full: u64,             // 因缓冲已满暂停读取的次数
+    pub split_chunks: u64,            // 因过大被切分的块数
+}
+
+pub struct TtsFlowControl {
+    settings: FlowSettings,
+    credit_ms: f64,
+    refilled_at: Instant,
+    holding: Option<FloodReason>,
+    warned: Option<FloodReason>, // 本轮洪泛已告警的原因，额度完全恢复且排队音频回落到上限一半以下后清除，避免持续洪泛时反复告警
+    rate_limited: u64,
+    buffer_full: u64,
+    split_chunks: u64,
+}
+
+impl TtsFlowControl {
+    pub fn new() -> Self {
+        Self {
+            settings: FlowSettings::default(),
+            credit_ms: BURST_ALLOWANCE_MS,
+            refilled_at: Instant::now(),
+            holding: None,
+            warned: None,
+            rate_limited: 0,
+            buffer_full: 0,
+            split_chunks: 0,
+        }
+    }
+
+    pub fn settings(&self) -> FlowSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: FlowSettings) {
+        self.settings = settings;
+    }
+
+    // 收到一块音频，扣除其时长
+    pub fn on_chunk(&mut self, now: Instant, duration_ms: u64) {
+        self.refill(now);
+        self.credit_ms -= duration_ms as f64;
+    }
+
+    pub fn record_split(&mut self) {
+        self.split_chunks += 1;
+    }
+
+
+    // 读取下一块之前检查是否需要暂停。返回 (暂停原因, 是否需要告警)，告警由调用方发出
+    pub fn hold(&mut self, now: Instant, buffered_ms: u64) -> (Option<FloodReason>, bool) {
+        self.refill(now);
+        let reason = if buffered_ms > self.settings.max_buffered_ms {
+            Some(FloodReason::BufferFull)
+        } else if self.credit_ms < 0.0 {
+            Some(FloodReason::RateLimited)
+        } else {
+            None
+        };
+
+        let entered = reason.is_some() && reason != self.holding;
+        match reason {
+            Some(FloodReason::BufferFull) if entered => self.buffer_full += 1,
+            Some(FloodReason::RateLimited) if entered => self.rate_limited += 1,
+            // 缓冲已满时按实时速率放行，额度会回满，排队音频回落之前不算洪泛结束
+            None if self.credit_ms >= BURST_ALLOWANCE_MS && buffered_ms <= self.settings.max_buffered_ms / 2 => {
+                self.warned = None
+            }
+            _ => {}
+        }
+        let warn = reason.is_some() && reason != self.warned;
+        if warn {
+            self.warned = reason;
+        }
+        self.holding = reason;
+        (reason, warn)
+    }
+
+    // 打断后排队的音频已作废，欠下的额度一并清空，新回复不受之前的洪泛影响
+    pub fn reset(&mut self) {
+        self.credit_ms = BURST_ALLOWANCE_MS;
+        self.refilled_at = Instant::now();
+        self.holding = None;
+        self.warned = None;
+    }
+
+    pub fn status(&self) -> FlowStatus {
+        FlowStatus {
+            settings: self.settings,
+            holding: self.holding,
+            rate_limited: self.rate_limited,
+            buffer_full: self.buffer_full,
+            split_chunks: self.split_chunks,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed_ms = now.saturating_duration_since(self.refilled_at).as_secs_f64() * 1000.0;
+        self.credit_ms = (self.credit_ms + elapsed_ms * self.settings.max_speedup).min(BURST_ALLOWANCE_MS);
+        self.refilled_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts_queue::{chunk_info, estimate_duration_ms};
+    use std::time::Duration;
+
+    // 与监听器暂停期间的检查间隔一致
+    const POLL_MS: u64 = 50;
+
+    // 模拟后端：全速写入共 total_bytes 字节、每块 chunk_bytes 的长度前缀裸 PCM，
+    // socket 缓冲写满后阻塞在写入上，即 TCP 背压
+    #[cfg(unix)]
+    fn flood_backend(total_bytes: usize, chunk_bytes: usize) -> (std::os::unix::net::UnixStream, std::thread::JoinHandle<()>) {
+        use std::io::Write;
+        let (client, mut backend) = std::os::unix::net::UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut remaining = total_bytes;
+            while remaining > 0 {
+                let len = remaining.min(chunk_bytes);
+                backend.write_all(&(len as u32).to_le_bytes()).unwrap();
+                backend.write_all(&vec![0u8; len]).unwrap();
+                remaining -= len;
+            }
+        });
+        (client, handle)
+    }
+
+    // 后端全速灌入 10MB 音频：按监听器的方式读取（暂停时每 50ms 检查一次，前端按实时速率播放），
+    // 排队音频始终不超过上限加一块，爆发额度用完后每秒转发到前端的块数不超过 max_speedup 倍实时速率，
+    // 也不出现整秒停顿；每种暂停原因只告警一次
+    #[cfg(unix)]
+    #[test]
+    fn flood_of_10mb_is_paced_with_bounded_buffer() {
+        use std::io::Read;
+        let bytes_per_second = chunk_info(&[]).bytes_per_second() as usize;
+        let chunk_bytes = bytes_per_second / 10;
+        let chunk_ms = estimate_duration_ms(&vec![0u8; chunk_bytes]);
+        let total_bytes = 10 * 1024 * 1024;
+        let (mut stream, backend) = flood_backend(total_bytes, chunk_bytes);
+
+        let mut flow = TtsFlowControl::new();
+        let settings = flow.settings();
+        let start = flow.refilled_at;
+        let mut now = start;
+        let mut buffered_ms = 0u64; // 前端播放队列中的音频
+        let mut peak_buffered_ms = 0u64;
+        let mut warnings = 0;
+        let mut released_per_second: Vec<u64> = Vec::new(); // 每个模拟秒内转发到前端的块数
+        let mut received = 0;
+        let mut len_buf = [0u8; 4];
+        while received < total_bytes {
+            let (reason, warn) = flow.hold(now, buffered_ms);
+            if warn {
+                warnings += 1;
+            }
+            if reason.is_some() {
+                now += Duration::from_millis(POLL_MS);
+                buffered_ms = buffered_ms.saturating_sub(POLL_MS);
+                continue;
+            }
+            stream.read_exact(&mut len_buf).unwrap();
+            let mut chunk = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+            stream.read_exact(&mut chunk).unwrap();
+            received += chunk.len();
+
+            let duration_ms = estimate_duration_ms(&chunk);
+            flow.on_chunk(now, duration_ms);
+            buffered_ms += duration_ms;
+            peak_buffered_ms = peak_buffered_ms.max(buffered_ms);
+            let second = now.duration_since(start).as_secs() as usize;
+            if released_per_second.len() <= second {
+                released_per_second.resize(second + 1, 0);
+            }
+            released_per_second[second] += 1;
+        }
+        backend.join().unwrap();
+
+        assert!(peak_buffered_ms <= settings.max_buffered_ms + chunk_ms, "排队音频峰值 {}ms", peak_buffered_ms);
+        let max_per_second = (settings.max_speedup * 1000.0 / chunk_ms as f64) as u64 + 2;
+        let paced = &released_per_second[1..released_per_second.len() - 1];
+        assert!(!paced.is_empty());
+        assert!(paced.iter().all(|&count| count > 0 && count <= max_per_second), "每秒转发块数 {:?}", paced);
+
+        let status = flow.status();
+        assert!(status.rate_limited >= 1);
+        assert!(status.buffer_full >= 1);
+        assert_eq!(warnings, 2);
+    }
+
+    // 打断后整形状态清空：欠下的额度恢复，新回复立即放行
+    #[test]
+    fn reset_clears_debt() {
+        let mut flow = TtsFlowControl::new();
+        let now = flow.refilled_at;
+        flow.on_chunk(now, 60_000);
+        assert_eq!(flow.hold(now, 0).0, Some(FloodReason::RateLimited));
+
+        flow.reset();
+        assert_eq!(flow.hold(flow.refilled_at, 0), (None, false));
+        assert_eq!(flow.status().holding, None);
+    }
+
+    #[test]
+    fn settings_validation_bounds() {
+        assert!(FlowSettings::default().validate().is_ok());
+        assert!(FlowSettings { max_speedup: 0.5, ..FlowSettings::default() }.validate().is_err());
+        assert!(FlowSettings { max_buffered_ms: 500, ..FlowSettings::default() }.validate().is_err());
+        assert_eq!(FlowSettings::default().piece_ms(), DEFAULT_TTS_MAX_BUFFERED_MS / 2);
+    }
+}
@@ -0,0 +1,173 @@
+// TTS 块间间隙填充
+// 后端合成跟不上播放时，前一块播完、下一块还没到，前端会出现可闻的断裂。
+// 这里按到达时刻估计"欠载"时长，并在之后的块尾部追加等长的填充（静音或前一块尾部电平的衰减），
+// 让下一块在填充播放期间到达。超过 max_fill_ms 的间隙视为真实的句间停顿，不参与估计也不被填充。
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::tts_queue::{chunk_info, estimate_duration_ms, AudioChunkInfo};
+
+pub const DEFAULT_GAP_FILL_MIN_GAP_MS: u64 = 20;
+pub const DEFAULT_GAP_FILL_MAX_FILL_MS: u64 = 250;
+// 块按时到达时，填充时长估计每块衰减的比例
+const PAD_ESTIMATE_DECAY: f64 = 0.5;
+
+// 填充策略
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GapFillStrategy {
+    Off,      // 不填充
+    Silence,  // 追加静音
+    HoldTail, // 从前一块尾部电平线性衰减到0，避免突变产生爆音
+}
+
+impl GapFillStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(GapFillStrategy::Off),
+            "silence" => Some(GapFillStrategy::Silence),
+            "hold_tail" => Some(GapFillStrategy::HoldTail),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct GapFillSettings {
+    pub strategy: GapFillStrategy,
+    pub min_gap_ms: u64,  // 小于该值的间隙忽略
+    pub max_fill_ms: u64, // 大于该值的间隙视为真实停顿
+}
+
+impl Default for GapFillSettings {
+    fn default() -> Self {
+        Self {
+            strategy: GapFillStrategy::HoldTail,
+            min_gap_ms: DEFAULT_GAP_FILL_MIN_GAP_MS,
+            max_fill_ms: DEFAULT_GAP_FILL_MAX_FILL_MS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GapFillStatus {
+    pub settings: GapFillSettings,
+    pub pad_estimate_ms: f64,
+    pub underruns: u64,     // 检测到的欠载次数
+    pub padded_chunks: u64, // 追加过填充的块数
+}
+
+pub struct TtsGapFiller {
+    settings: GapFillSettings,
+    expected_end: Option<Instant>, // 按到达时刻推算的上一块播完时刻
+    pad_estimate_ms: f64,
+    underruns: u64,
+    padded_chunks: u64,
+}
+
+impl TtsGapFiller {
+    pub fn new() -> Self {
+        Self {
+            settings: GapFillSettings::default(),
+            expected_end: None,
+            pad_estimate_ms: 0.0,
+            underruns: 0,
+            padded_chunks: 0,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: GapFillSettings) {
+        self.settings = settings;
+        self.pad_estimate_ms = 0.0;
+    }
+
+    pub fn status(&self) -> GapFillStatus {
+        GapFillStatus {
+            settings: self.settings,
+            pad_estimate_ms: self.pad_estimate_ms,
+            underruns: self.underruns,
+            padded_chunks: self.padded_chunks,
+        }
+    }
+
+    // 处理新到达的块，返回（可能追加了填充的）音频数据
+    pub fn process(&mut self, now: Instant, mut data: Vec<u8>) -> Vec<u8> {
+        if self.settings.strategy == GapFillStrategy::Off {
+            self.expected_end = None;
+            return data;
+        }
+
+        if let Some(expected_end) = self.expected_end {
+            let gap_ms = now.saturating_duration_since(expected_end).as_millis() as u64;
+            if gap_ms < self.settings.min_gap_ms {
+                // 按时到达，逐步收回填充
+                self.pad_estimate_ms *= PAD_ESTIMATE_DECAY;
+            } else if gap_ms <= self.settings.max_fill_ms {
+                self.underruns += 1;
+                self.pad_estimate_ms = self.pad_estimate_ms.max(gap_ms as f64);
+                debug!("[调试] TTS块间出现欠载间隙: {}ms", gap_ms);
+            }
+            // 超过 max_fill_ms 的是真实停顿，保持估计不变
+        }
+
+        let info = chunk_info(&data);
+        let pad_ms = self.pad_estimate_ms.round() as u64;
+        if pad_ms >= self.settings.min_gap_ms && append_fill(&mut data, &info, pad_ms, self.settings.strategy) {
+            self.padded_chunks += 1;
+        }
+
+        // 下一块最早在本块（含填充）播完时才需要
+        let duration_ms = estimate_duration_ms(&data);
+        let start = match self.expected_end {
+            Some(expected_end) if expected_end > now => expected_end,
+            _ => now,
+        };
+        self.expected_end = Some(start + Duration::from_millis(duration_ms));
+
+        data
+    }
+}
+
+// 在 PCM 数据尾部追加填充，仅支持16位且 data 块位于末尾的音频；成功返回 true
+fn append_fill(data: &mut Vec<u8>, info: &AudioChunkInfo, pad_ms: u64, strategy: GapFillStrategy) -> bool {
+    let channels = info.channels as usize;
+    if info.bits_per_sample != 16 || channels == 0 || info.data_offset + info.data_len != data.len() {
+        return false;
+    }
+
+    let frame_bytes = channels * 2;
+    let pad_frames = (info.sample_rate as u64 * pad_ms / 1000) as usize;
+    if pad_frames == 0 {
+        return false;
+    }
+
+    // 前一块最后一帧各声道的电平
+    let mut tail = vec![0i16; channels];
+    if strategy == GapFillStrategy::HoldTail && info.data_len >= frame_bytes {
+        let last_frame = data.len() - frame_bytes;
+        for (ch, value) in tail.iter_mut().enumerate() {
+            let pos = last_frame + ch * 2;
+            *value = i16::from_le_bytes([data[pos], data[pos + 1]]);
+        }
+    }
+
+    data.reserve(pad_frames * frame_bytes);
+    for i in 0..pad_frames {
+        let gain = 1.0 - (i + 1) as f32 / pad_frames as f32;
+        for &level in tail.iter() {
+            let sample = (level as f32 * gain) as i16;
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+
+    if info.is_wav {
+        // 同步修正 RIFF 和 data 块的长度字段
+        let data_size = (info.data_len + pad_frames * frame_bytes) as u32;
+        let riff_size = (data.len() - 8) as u32;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        data[info.data_offset - 4..info.data_offset].copy_from_slice(&data_size.to_le_bytes());
+    }
+    true
+}
@@ -0,0 +1,570 @@
+// TTS 播放队列
+// 后端每次通过 TTS Socket 下发的一帧即一条完整的 utterance（WAV），
+// 这里按到达顺序排队，同一时刻只放行一条给前端播放，前一条播完或被打断后再放行下一条。
+// 接收速率与排队总时长由 flow 约束，超限时接收路径暂停读取。
+// 取消播放后，在新一轮回复开始前收到的音频都属于被取消的回复，接收路径读完后直接丢弃。
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::tts_flow::TtsFlowControl;
+use crate::tts_gap_fill::TtsGapFiller;
+use crate::tts_stall::TtsStallMonitor;
+
+// 后端 TTS 默认输出参数（与 send_tts.py 中 pcm_to_wav 的默认值一致）
+const DEFAULT_TTS_SAMPLE_RATE: u32 = 32000;
+const DEFAULT_TTS_CHANNELS: u16 = 1;
+const DEFAULT_TTS_BITS_PER_SAMPLE: u16 = 16;
+
+// 打断策略
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum InterruptPolicy {
+    CurrentOnly, // 只打断当前条，队列中的后续条目继续放行
+    ClearQueue,  // 打断当前条并清空整个队列
+}
+
+impl InterruptPolicy {
+    pub fn from_name(policy: &str) -> Option<Self> {
+        match policy {
+            "current_only" => Some(InterruptPolicy::CurrentOnly),
+            "clear_queue" => Some(InterruptPolicy::ClearQueue),
+            _ => None,
+        }
+    }
+}
+
+// 排队中的单条 utterance
+#[derive(Debug, Clone)]
+pub struct QueuedUtterance {
+    pub utterance_id: u64,
+    pub data: Vec<u8>,
+    pub duration_ms: u64,
+    pub caption: Option<String>, // 后端下发的字幕文本
+}
+
+// 对外展示的 utterance 摘要（不含音频数据）
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UtteranceInfo {
+    pub utterance_id: u64,
+    pub duration_ms: u64,
+    pub bytes: usize,
+    pub caption: Option<String>,
+}
+
+// 被打断的回复：按已播放时长把字幕切分为已播/未播两部分，没有字幕时只有时长
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InterruptedReplyRemainder {
+    pub utterance_id: u64,
+    pub spoken_text: Option<String>,
+    pub remaining_text: Option<String>,
+    pub played_ms: u64,
+    pub remaining_ms: u64,
+}
+
+// 一次打断的结果
+pub struct InterruptOutcome {
+    pub dropped: Vec<u64>, // 被打断/丢弃的 utterance_id（当前条在前）
+    pub remainder: Option<InterruptedReplyRemainder>,
+}
+
+// 播放队列状态快照
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TtsPlaybackStatus {
+    pub playing: Option<UtteranceInfo>,
+    pub queued: Vec<UtteranceInfo>,
+    pub queued_count: usize,
+    pub queued_duration_ms: u64,
+    pub interrupt_policy: InterruptPolicy,
+}
+
+pub struct TtsPlaybackQueue {
+    next_utterance_id: u64,
+    playing: Option<UtteranceInfo>,
+    playing_started_at: Option<Instant>, // 前端确认当前条开始播放的时刻
+    pending: VecDeque<QueuedUtterance>,
+    pending_caption: Option<String>,     // 先于音频到达的字幕，挂到下一条入队的 utterance 上
+    interrupt_policy: InterruptPolicy,
+    cancelled: bool,                     // 已取消播放，新一轮回复开始前到达的音频丢弃
+    pub gap_filler: TtsGapFiller, // 到达时检测块间间隙并按需在尾部填充
+    pub flow: TtsFlowControl,     // 接收速率与缓冲上限
+    pub stall: TtsStallMonitor,   // 播放期间音频到达的连续性
+}
+
+impl TtsPlaybackQueue {
+    pub fn new() -> Self {
+        Self {
+            next_utterance_id: 1,
+            playing: None,
+            playing_started_at: None,
+            pending: VecDeque::new(),
+            pending_caption: None,
+            interrupt_policy: InterruptPolicy::ClearQueue,
+            cancelled: false,
+            gap_filler: TtsGapFiller::new(),
+            flow: TtsFlowControl::new(),
+            stall: TtsStallMonitor::new(),
+        }
+    }
+
+    // 新的 utterance 入队，返回分配的 utterance_id
+    pub fn enqueue(&mut self, data: Vec<u8>) -> u64 {
+        let utterance_id = self.next_utterance_id;
+        self.next_utterance_id += 1;
+
+        let data = self.gap_filler.process(Instant::now(), data);
+        let duration_ms = estimate_duration_ms(&data);
+        self.pending.push_back(QueuedUtterance {
+            utterance_id,
+            data,
+            duration_ms,
+            caption: self.pending_caption.take(),
+        });
+        utterance_id
+    }
+
+    // 设置字幕：指定 id 时挂到对应条目；否则挂到最近入队且还没有字幕的条目，
+    // 都没有时暂存给下一条。返回挂上的 utterance_id
+    pub fn set_caption(&mut self, utterance_id: Option<u64>, text: String) -> Option<u64> {
+        if let Some(id) = utterance_id {
+            if let Some(playing) = self.playing.as_mut().filter(|p| p.utterance_id == id) {
+                playing.caption = Some(text);
+                return Some(id);
+            }
+            let queued = self.pending.iter_mut().find(|u| u.utterance_id == id)?;
+            queued.caption = Some(text);
+            return Some(id);
+        }
+
+        if let Some(queued) = self.pending.iter_mut().rev().find(|u| u.caption.is_none()) {
+            queued.caption = Some(text);
+            return Some(queued.utterance_id);
+        }
+        if let Some(playing) = self.playing.as_mut().filter(|p| p.caption.is_none()) {
+            playing.caption = Some(text);
+            return Some(playing.utterance_id);
+        }
+        self.pending_caption = Some(text);
+        None
+    }
+
+    // 前端开始播放当前条
+    pub fn mark_playback_started(&mut self, now: Instant) {
+        if let Some(info) = &self.playing {
+            self.playing_started_at = Some(now);
+            self.stall.on_playback_started(now, info.duration_ms);
+        }
+    }
+
+    // 当前没有正在播放的条目时，取出队首放行；否则返回 None
+    pub fn release_next(&mut self) -> Option<QueuedUtterance> {
+        if self.playing.is_some() {
+            return None;
+        }
+
+        let next = self.pending.pop_front()?;
+        self.playing = Some(UtteranceInfo {
+            utterance_id: next.utterance_id,
+            duration_ms: next.duration_ms,
+            bytes: next.data.len(),
+            caption: next.caption.clone(),
+        });
+        self.playing_started_at = None;
+        Some(next)
+    }
+
+    // 当前条播放结束，返回结束的条目
+    pub fn finish_current(&mut self) -> Option<UtteranceInfo> {
+        self.playing_started_at = None;
+        self.playing.take()
+    }
+
+    // 按打断策略处理，当前条已开始播放时按已播时长计算剩余部分
+    pub fn interrupt(&mut self, now: Instant) -> InterruptOutcome {
+        self.interrupt_with(now, self.interrupt_policy)
+    }
+
+    // 取消播放：不论打断策略，打断当前条并清空队列，之后到达的音频丢弃，直到 clear_cancelled
+    pub fn cancel(&mut self, now: Instant) -> InterruptOutcome {
+        self.cancelled = true;
+        self.interrupt_with(now, InterruptPolicy::ClearQueue)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    // 新一轮回复开始，恢复接收；返回之前是否处于取消状态
+    pub fn clear_cancelled(&mut self) -> bool {
+        std::mem::replace(&mut self.cancelled, false)
+    }
+
+    fn interrupt_with(&mut self, now: Instant, policy: InterruptPolicy) -> InterruptOutcome {
+        let started_at = self.playing_started_at;
+        let mut dropped = Vec::new();
+        let mut remainder = None;
+        if let Some(info) = self.finish_current() {
+            let played_ms = started_at
+                .map(|t| now.duration_since(t).as_millis() as u64)
+                .unwrap_or(0);
+            remainder = Some(split_remainder(&info, played_ms));
+            dropped.push(info.utterance_id);
+        }
+        if policy == InterruptPolicy::ClearQueue {
+            dropped.extend(self.pending.drain(..).map(|u| u.utterance_id));
+        }
+        self.flow.reset();
+        InterruptOutcome { dropped, remainder }
+    }
+
+    pub fn playing_id(&self) -> Option<u64> {
+        self.playing.as_ref().map(|info| info.utterance_id)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // 排队中（不含正在播放）的音频总时长
+    pub fn queued_duration_ms(&self) -> u64 {
+        self.pending.iter().map(|u| u.duration_ms).sum()
+    }
+
+    pub fn set_interrupt_policy(&mut self, policy: InterruptPolicy) {
+        self.interrupt_policy = policy;
+    }
+
+    pub fn status(&self) -> TtsPlaybackStatus {
+        let queued: Vec<UtteranceInfo> = self
+            .pending
+            .iter()
+            .map(|u| UtteranceInfo {
+                utterance_id: u.utterance_id,
+                duration_ms: u.duration_ms,
+                bytes: u.data.len(),
+                caption: u.caption.clone(),
+            })
+            .collect();
+
+        TtsPlaybackStatus {
+            playing: self.playing.clone(),
+            queued_count: queued.len(),
+            queued_duration_ms: self.queued_duration_ms(),
+            queued,
+            interrupt_policy: self.interrupt_policy,
+        }
+    }
+}
+
+// 按已播时长占比切分字幕
+fn split_remainder(info: &UtteranceInfo, played_ms: u64) -> InterruptedReplyRemainder {
+    let played_ms = played_ms.min(info.duration_ms);
+    let remaining_ms = info.duration_ms - played_ms;
+
+    let (spoken_text, remaining_text) = match &info.caption {
+        Some(caption) => {
+            let chars: Vec<char> = caption.chars().collect();
+            let split = (chars.len() as u64 * played_ms)
+                .checked_div(info.duration_ms)
+                .map_or(chars.len(), |n| n as usize);
+            (
+                Some(chars[..split].iter().collect()),
+                Some(chars[split..].iter().collect()),
+            )
+        }
+        None => (None, None),
+    };
+
+    InterruptedReplyRemainder {
+        utterance_id: info.utterance_id,
+        spoken_text,
+        remaining_text,
+        played_ms,
+        remaining_ms,
+    }
+}
+
+// TTS 音频块的格式信息
+#[derive(Debug, Clone, Copy)]
+pub struct AudioChunkInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    pub is_wav: bool,
+    pub data_offset: usize, // PCM 数据在块中的起始位置
+    pub data_len: usize,    // PCM 数据长度（字节）
+}
+
+impl AudioChunkInfo {
+    pub fn bytes_per_second(&self) -> u64 {
+        self.sample_rate as u64 * self.channels as u64 * (self.bits_per_sample as u64 / 8)
+    }
+}
+
+// 识别音频块格式：WAV 按头解析，否则按后端默认的 PCM 参数处理
+pub fn chunk_info(data: &[u8]) -> AudioChunkInfo {
+    match parse_wav_header(data) {
+        Some((sample_rate, channels, bits_per_sample, data_offset, data_len)) => AudioChunkInfo {
+            sample_rate,
+            channels,
+            bits_per_sample,
+            is_wav: true,
+            data_offset,
+            data_len,
+        },
+        None => AudioChunkInfo {
+            sample_rate: DEFAULT_TTS_SAMPLE_RATE,
+            channels: DEFAULT_TTS_CHANNELS,
+            bits_per_sample: DEFAULT_TTS_BITS_PER_SAMPLE,
+            is_wav: false,
+            data_offset: 0,
+            data_len: data.len(),
+        },
+    }
+}
+
+// 估算音频块时长
+pub fn estimate_duration_ms(data: &[u8]) -> u64 {
+    let info = chunk_info(data);
+    let bytes_per_second = info.bytes_per_second();
+    if bytes_per_second == 0 {
+        return 0;
+    }
+    info.data_len as u64 * 1000 / bytes_per_second
+}
+
+// 超过缓冲上限的音频块的切分：按块开头识别格式，把 PCM 数据按整帧切成每段不超过 piece_ms 的若干段，
+// WAV 块的每段沿用原块 data 之前的头部并改写长度字段，切出的每段都是独立可播放的一块。
+// 块开头需包含完整的 WAV 头，超大块只需先读入开头即可开始切分
+pub struct ChunkSplitter {
+    header: Vec<u8>,    // 原块 data 之前的头部，裸 PCM 时为空
+    data_offset: usize, // PCM 数据在块中的起始位置
+    data_len: usize,    // 块中 PCM 数据的总长度
+    piece_bytes: usize, // 每段 PCM 的字节数，为整帧
+}
+
+impl ChunkSplitter {
+    // head 为块开头已读到的字节，chunk_len 为整块长度
+    pub fn new(head: &[u8], chunk_len: usize, piece_ms: u64) -> Self {
+        let info = chunk_info(head);
+        let (header, data_len) = if info.is_wav {
+            // data 块声明的长度可能大于实际（流式 WAV 常写为 0xFFFFFFFF），以块长度为准
+            let declared = u32::from_le_bytes(head[info.data_offset - 4..info.data_offset].try_into().unwrap()) as usize;
+            (head[..info.data_offset].to_vec(), declared.min(chunk_len - info.data_offset))
+        } else {
+            (Vec::new(), chunk_len)
+        };
+        let frame_bytes = (info.channels as usize * (info.bits_per_sample as usize / 8)).max(1);
+        let frames = (info.bytes_per_second() * piece_ms / 1000) as usize / frame_bytes;
+        Self {
+            header,
+            data_offset: info.data_offset,
+            data_len,
+            piece_bytes: frames.max(1) * frame_bytes,
+        }
+    }
+
+    pub fn data_offset(&self) -> usize {
+        self.data_offset
+    }
+
+    // PCM 数据在块中的结束位置，之后的字节（WAV 尾部的其他块）不属于音频
+    pub fn data_end(&self) -> usize {
+        self.data_offset + self.data_len
+    }
+
+    pub fn piece_bytes(&self) -> usize {
+        self.piece_bytes
+    }
+
+    // 把一段 PCM 包装成独立的一块
+    pub fn wrap(&self, pcm: &[u8]) -> Vec<u8> {
+        let mut piece = Vec::with_capacity(self.header.len() + pcm.len());
+        piece.extend_from_slice(&self.header);
+        if !self.header.is_empty() {
+            let riff_len = (self.header.len() - 8 + pcm.len()) as u32;
+            piece[4..8].copy_from_slice(&riff_len.to_le_bytes());
+            let data_len_at = self.header.len() - 4;
+            piece[data_len_at..].copy_from_slice(&(pcm.len() as u32).to_le_bytes());
+        }
+        piece.extend_from_slice(pcm);
+        piece
+    }
+}
+
+// 整块已在内存中时直接切分
+pub fn split_chunk(data: &[u8], piece_ms: u64) -> Vec<Vec<u8>> {
+    let splitter = ChunkSplitter::new(data, data.len(), piece_ms);
+    let end = splitter.data_end().min(data.len());
+    data[splitter.data_offset().min(end)..end]
+        .chunks(splitter.piece_bytes())
+        .map(|pcm| splitter.wrap(pcm))
+        .collect()
+}
+
+// 解析 RIFF/WAVE 头，返回(采样率, 声道数, 位深, data 块起始位置, data 块长度)
+fn parse_wav_header(data: &[u8]) -> Option<(u32, u16, u16, usize, usize)> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut format: Option<(u32, u16, u16)> = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let chunk_id = &data[pos..pos + 4];
+        let chunk_size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = pos + 8;
+
+        if chunk_id == b"fmt " && body + 16 <= data.len() {
+            let channels = u16::from_le_bytes(data[body + 2..body + 4].try_into().ok()?);
+            let sample_rate = u32::from_le_bytes(data[body + 4..body + 8].try_into().ok()?);
+            let bits_per_sample = u16::from_le_bytes(data[body + 14..body + 16].try_into().ok()?);
+            format = Some((sample_rate, channels, bits_per_sample));
+        } else if chunk_id == b"data" {
+            let (sample_rate, channels, bits_per_sample) = format?;
+            let data_len = std::cmp::min(chunk_size, data.len() - body);
+            return Some((sample_rate, channels, bits_per_sample, body, data_len));
+        }
+
+        // RIFF 块按偶数字节对齐
+        pos = body.saturating_add(chunk_size).saturating_add(chunk_size & 1);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_utils::encode_wav_pcm16;
+
+    // 超过缓冲上限的块切成若干段：每段都是可独立解析的 WAV，时长不超过上限，拼回去与原 PCM 一致
+    #[test]
+    fn oversized_chunk_is_split_without_losing_audio() {
+        let sample_rate = 16_000;
+        let samples: Vec<i16> = (0..sample_rate * 70).map(|i| (i % 2000) as i16).collect();
+        let wav = encode_wav_pcm16(&samples, sample_rate as u32);
+
+        let pieces = split_chunk(&wav, 30_000);
+        assert_eq!(pieces.len(), 3);
+        let mut joined = Vec::new();
+        for piece in &pieces {
+            let info = chunk_info(piece);
+            assert!(info.is_wav);
+            assert_eq!(info.sample_rate, sample_rate as u32);
+            assert_eq!(info.data_offset + info.data_len, piece.len());
+            assert!(estimate_duration_ms(piece) <= 30_000);
+            joined.extend_from_slice(&piece[info.data_offset..]);
+        }
+        assert_eq!(joined, wav[44..]);
+    }
+
+    // 只读入块开头时按整块长度切分，声明长度未知（0xFFFFFFFF）的流式 WAV 以块长度为准
+    #[test]
+    fn splitter_uses_chunk_length_for_streaming_wav() {
+        let mut wav = encode_wav_pcm16(&vec![1i16; 48_000], 16_000);
+        wav[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let splitter = ChunkSplitter::new(&wav[..100], wav.len(), 1_000);
+        assert_eq!(splitter.data_offset(), 44);
+        assert_eq!(splitter.data_end(), wav.len());
+        assert_eq!(splitter.piece_bytes(), 32_000);
+
+        let piece = splitter.wrap(&wav[44..44 + 32_000]);
+        assert_eq!(estimate_duration_ms(&piece), 1_000);
+        assert_eq!(u32::from_le_bytes(piece[4..8].try_into().unwrap()) as usize, piece.len() - 8);
+    }
+
+    // 模拟后端 TTS 流：依次下发给定时长（毫秒）的 WAV 块，每块入队一次，返回分配的 id
+    fn feed_stream(queue: &mut TtsPlaybackQueue, durations_ms: &[u64]) -> Vec<u64> {
+        durations_ms
+            .iter()
+            .map(|&ms| queue.enqueue(encode_wav_pcm16(&vec![0i16; (16 * ms) as usize], 16_000)))
+            .collect()
+    }
+
+    // 模拟前端播放器：每次放行一条并播完，返回放行顺序
+    fn play_all(queue: &mut TtsPlaybackQueue) -> Vec<u64> {
+        let mut order = Vec::new();
+        while let Some(next) = queue.release_next() {
+            // 当前条未播完前不会放行下一条
+            assert!(queue.release_next().is_none());
+            order.push(next.utterance_id);
+            assert_eq!(queue.finish_current().map(|info| info.utterance_id), Some(next.utterance_id));
+        }
+        order
+    }
+
+    #[test]
+    fn utterances_play_in_arrival_order() {
+        let mut queue = TtsPlaybackQueue::new();
+        let ids = feed_stream(&mut queue, &[300, 1200, 50, 800]);
+        assert_eq!(queue.queued_duration_ms(), 2350);
+        assert_eq!(play_all(&mut queue), ids);
+        assert!(!queue.has_pending());
+    }
+
+    // 只打断当前条：按已播时长切分字幕，队列中的后续条目照常放行
+    #[test]
+    fn current_only_interrupt_keeps_queued_utterances() {
+        let mut queue = TtsPlaybackQueue::new();
+        queue.set_interrupt_policy(InterruptPolicy::CurrentOnly);
+        let ids = feed_stream(&mut queue, &[1000, 500, 500]);
+        queue.set_caption(Some(ids[0]), "你好世界".to_string());
+
+        let started = Instant::now();
+        assert_eq!(queue.release_next().map(|u| u.utterance_id), Some(ids[0]));
+        queue.mark_playback_started(started);
+        let outcome = queue.interrupt(started + std::time::Duration::from_millis(250));
+
+        assert_eq!(outcome.dropped, vec![ids[0]]);
+        let remainder = outcome.remainder.expect("当前条已开始播放");
+        assert_eq!(remainder.played_ms, 250);
+        assert_eq!(remainder.remaining_ms, 750);
+        assert_eq!(remainder.spoken_text.as_deref(), Some("你"));
+        assert_eq!(remainder.remaining_text.as_deref(), Some("好世界"));
+        assert_eq!(play_all(&mut queue), ids[1..]);
+    }
+
+    // 清空队列的打断丢弃当前条与全部排队条目，当前条尚未开始播放时已播时长为0
+    #[test]
+    fn clear_queue_interrupt_drops_everything() {
+        let mut queue = TtsPlaybackQueue::new();
+        let ids = feed_stream(&mut queue, &[400, 400, 400]);
+        queue.release_next();
+
+        let outcome = queue.interrupt(Instant::now());
+        assert_eq!(outcome.dropped, ids);
+        assert_eq!(outcome.remainder.map(|r| r.played_ms), Some(0));
+        assert!(queue.playing_id().is_none());
+        assert!(play_all(&mut queue).is_empty());
+    }
+
+    // 取消后直到新一轮回复开始前都处于取消状态；之后到达的音频照常按序播放，id 不复用
+    #[test]
+    fn cancel_holds_until_next_reply() {
+        let mut queue = TtsPlaybackQueue::new();
+        queue.set_interrupt_policy(InterruptPolicy::CurrentOnly);
+        let first = feed_stream(&mut queue, &[400, 400]);
+        queue.release_next();
+
+        let outcome = queue.cancel(Instant::now());
+        assert_eq!(outcome.dropped, first);
+        assert!(queue.is_cancelled());
+        assert!(queue.clear_cancelled());
+        assert!(!queue.clear_cancelled());
+
+        let second = feed_stream(&mut queue, &[200]);
+        assert!(second[0] > first[1]);
+        assert_eq!(play_all(&mut queue), second);
+    }
+
+    // 裸 PCM 按后端默认格式切分，段边界落在整个样本上
+    #[test]
+    fn raw_pcm_pieces_align_to_samples() {
+        let pcm = vec![0u8; 32_000 * 2 * 3 + 2];
+        let pieces = split_chunk(&pcm, 1_000);
+        assert_eq!(pieces.len(), 4);
+        assert!(pieces.iter().all(|piece| piece.len() % 2 == 0));
+        assert_eq!(pieces.iter().map(Vec::len).sum::<usize>(), pcm.len());
+    }
+}
@@ -0,0 +1,160 @@
+// TTS 播放中断流检测
+// 长回复播放期间（Listening）监测音频到达的连续性，用两个时钟共同判断，任一未到期都不算中断流：
+// - 单调时钟：最近一次收到音频块或前端开始播放一条 utterance 的时刻；
+// - 音频时钟：已收到/已开始播放的音频按时长排到的播放结束时刻，当前条按时长还没播完时不会到期。
+// 两者都超过 stall_ms、队列中没有待放行的条目且未收到播放结束时判定为中断流，之后有新块到达即恢复。
+// 中断流持续超过 give_up_ms 时视为播放已结束，由调用方注入播放结束事件并向后端询问。
+// 离开 Listening 时清空检测状态，下一条开始播放时重新计时。
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TTS_STALL_MS: u64 = 3_000;
+pub const DEFAULT_TTS_STALL_GIVE_UP_MS: u64 = 15_000;
+// 后台检查间隔
+pub const STALL_CHECK_INTERVAL_MS: u64 = 250;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StallSettings {
+    pub stall_ms: u64,   // 没有新音频多久判定为中断流
+    pub give_up_ms: u64, // 中断流持续多久视为播放结束
+}
+
+impl Default for StallSettings {
+    fn default() -> Self {
+        Self {
+            stall_ms: DEFAULT_TTS_STALL_MS,
+            give_up_ms: DEFAULT_TTS_STALL_GIVE_UP_MS,
+        }
+    }
+}
+
+impl StallSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(500..=60_000).contains(&self.stall_ms) {
+            return Err(format!("stall_ms必须在500~60000之间: {}", self.stall_ms));
+        }
+        if !(1_000..=600_000).contains(&self.give_up_ms) {
+            return Err(format!("give_up_ms必须在1000~600000之间: {}", self.give_up_ms));
+        }
+        Ok(())
+    }
+}
+
+// 检测结果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StallEvent {
+    Stalled { silent_ms: u64 }, // 进入中断流，silent_ms 为距最近一次音频活动的时长
+    Resumed { stalled_ms: u64 }, // 中断流期间又收到音频，或已离开 Listening
+    GaveUp { stalled_ms: u64 },  // 中断流超过 give_up_ms，视为播放结束
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StallStatus {
+    pub settings: StallSettings,
+    pub stalled: bool,
+    pub stalls: u64,   // 判定为中断流的次数
+    pub give_ups: u64, // 中断流超时视为播放结束的次数
+}
+
+pub struct TtsStallMonitor {
+    settings: StallSettings,
+    last_activity: Option<Instant>, // 单调时钟
+    audio_until: Option<Instant>,   // 音频时钟
+    stalled_since: Option<Instant>,
+    gave_up: bool, // 本次中断流已超时，不再重复上报
+    stalls: u64,
+    give_ups: u64,
+}
+
+impl TtsStallMonitor {
+    pub fn new() -> Self {
+        Self {
+            settings: StallSettings::default(),
+            last_activity: None,
+            audio_until: None,
+            stalled_since: None,
+            gave_up: false,
+            stalls: 0,
+            give_ups: 0,
+        }
+    }
+
+    pub fn settings(&self) -> StallSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: StallSettings) {
+        self.settings = settings;
+    }
+
+    // 收到新的音频块；处于中断流时返回 Resumed
+    pub fn on_chunk(&mut self, now: Instant, duration_ms: u64) -> Option<StallEvent> {
+        self.last_activity = Some(now);
+        let start = self.audio_until.map_or(now, |until| until.max(now));
+        self.audio_until = Some(start + Duration::from_millis(duration_ms));
+        self.take_resumed(now)
+    }
+
+    // 前端开始播放一条 utterance，音频时钟至少延续到它按时长播完
+    pub fn on_playback_started(&mut self, now: Instant, duration_ms: u64) {
+        self.last_activity = Some(now);
+        let end = now + Duration::from_millis(duration_ms);
+        self.audio_until = Some(self.audio_until.map_or(end, |until| until.max(end)));
+    }
+
+    // 定期检查；listening 为状态机是否处于 Listening，queue_pending 为队列中是否还有待放行的条目
+    pub fn check(&mut self, now: Instant, listening: bool, queue_pending: bool) -> Option<StallEvent> {
+        if !listening {
+            let resumed = self.take_resumed(now);
+            self.last_activity = None;
+            self.audio_until = None;
+            return resumed;
+        }
+        // 下一条还在排队，放行后会重新计时
+        if queue_pending {
+            return None;
+        }
+        let last_activity = self.last_activity?;
+
+        if let Some(since) = self.stalled_since {
+            let stalled_ms = now.saturating_duration_since(since).as_millis() as u64;
+            if !self.gave_up && stalled_ms >= self.settings.give_up_ms {
+                self.gave_up = true;
+                self.give_ups += 1;
+                return Some(StallEvent::GaveUp { stalled_ms });
+            }
+            return None;
+        }
+
+        let threshold = Duration::from_millis(self.settings.stall_ms);
+        let silent = now.saturating_duration_since(last_activity);
+        let overdue = now.saturating_duration_since(self.audio_until.unwrap_or(last_activity));
+        if silent >= threshold && overdue >= threshold {
+            self.stalled_since = Some(now);
+            self.stalls += 1;
+            return Some(StallEvent::Stalled { silent_ms: silent.as_millis() as u64 });
+        }
+        None
+    }
+
+    // 结束中断流；已超时放弃的不再报告恢复
+    fn take_resumed(&mut self, now: Instant) -> Option<StallEvent> {
+        let since = self.stalled_since.take()?;
+        if std::mem::replace(&mut self.gave_up, false) {
+            return None;
+        }
+        Some(StallEvent::Resumed {
+            stalled_ms: now.saturating_duration_since(since).as_millis() as u64,
+        })
+    }
+
+    pub fn status(&self) -> StallStatus {
+        StallStatus {
+            settings: self.settings,
+            stalled: self.stalled_since.is_some(),
+            stalls: self.stalls,
+            give_ups: self.give_ups,
+        }
+    }
+}
@@ -0,0 +1,377 @@
+// 对话轮次
+// 一个轮次由用户的一次输入（最终识别文本、对应的语音段、起止时间）和助手对它的回复组成，
+// 由状态机与各监听器的事件统一驱动构建。事件到达顺序不固定，最终识别结果可能晚于 TTS 回复，
+// 因此轮次要同时满足"已有用户文本"和"回复已结束或已被下一轮取代"才算完成。
+// 回复按开始播放时所属的轮次归属，用户打断播放时被打断的回复仍归到上一轮。
+// 用户在助手回复之前再次开口视为同一轮的继续，不开启新轮次。
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// 最多保留的轮次数
+const MAX_TURNS: usize = 200;
+// 已被取代的轮次等待最终识别结果与回复结束的最长时间，超时后按已有内容直接完成
+const TRANSCRIPT_WAIT_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserInput {
+    pub transcript: Option<String>,
+    pub audio_segment_ids: Vec<u64>, // 本轮收集到的完整语音段序号
+    pub started_at_ms: Option<u64>,
+    pub ended_at_ms: Option<u64>,
+    #[serde(default)]
+    pub deleted: bool, // 用户删除了本轮的转写，transcript 只保留为空的占位
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssistantReply {
+    pub text: String,
+    pub utterance_id: Option<u64>,
+    pub interrupted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Turn {
+    pub turn_id: u64,
+    pub user: UserInput,
+    pub replies: Vec<AssistantReply>,
+    pub completed: bool,
+    #[serde(default)]
+    replies_done: bool, // 回复已播完或被打断，且没有排队中的后续回复
+    #[serde(default)]
+    superseded_at_ms: Option<u64>, // 下一轮开始的时刻
+    #[serde(default)]
+    playing_utterances: Vec<u64>, // 已开始播放、尚未结束的回复
+}
+
+impl Turn {
+    fn new(turn_id: u64) -> Self {
+        Self {
+            turn_id,
+            user: UserInput::default(),
+            replies: Vec::new(),
+            completed: false,
+            replies_done: false,
+            superseded_at_ms: None,
+            playing_utterances: Vec::new(),
+        }
+    }
+
+    fn ready(&self, now_ms: u64) -> bool {
+        let overdue = matches!(
+            self.superseded_at_ms,
+            Some(at) if now_ms.saturating_sub(at) >= TRANSCRIPT_WAIT_TIMEOUT_MS
+        );
+        let settled = (self.replies_done || self.superseded_at_ms.is_some()) && self.playing_utterances.is_empty();
+        overdue || (settled && self.user.transcript.is_some())
+    }
+}
+
+pub struct TurnTracker {
+    turns: VecDeque<Turn>,
+    next_turn_id: u64,
+}
+
+impl TurnTracker {
+    pub fn new() -> Self {
+        Self {
+            turns: VecDeque::new(),
+            next_turn_id: 1,
+        }
+    }
+
+    // 用户开始说话：上一轮已有回复或回复正在播放（用户打断）时开启新轮次，否则继续当前轮次
+    pub fn user_speech_started(&mut self, at_ms: u64) -> Vec<Turn> {
+        let continues = matches!(
+            self.turns.back(),
+            Some(turn) if !turn.completed && turn.replies.is_empty() && turn.playing_utterances.is_empty()
+        );
+        if !continues {
+            if let Some(turn) = self.turns.back_mut() {
+                if turn.superseded_at_ms.is_none() {
+                    turn.superseded_at_ms = Some(at_ms);
+                }
+            }
+            self.open_turn();
+        }
+
+        let turn = self.latest_open_mut();
+        if turn.user.started_at_ms.is_none() {
+            turn.user.started_at_ms = Some(at_ms);
+        }
+        self.collect_completed(at_ms)
+    }
+
+    pub fn user_speech_ended(&mut self, at_ms: u64) {
+        if let Some(turn) = self.turns.back_mut().filter(|turn| !turn.completed) {
+            turn.user.ended_at_ms = Some(at_ms);
+        }
+    }
+
+    pub fn audio_segments_finished(&mut self, segment_ids: &[u64]) {
+        if segment_ids.is_empty() {
+            return;
+        }
+        if let Some(turn) = self.turns.back_mut().filter(|turn| !turn.completed) {
+            turn.user.audio_segment_ids.extend_from_slice(segment_ids);
+        }
+    }
+
+    // 最终识别结果：交给最早一个还没有用户文本的轮次；都有文本时追加到最新的未完成轮次。
+    // 返回接收文本的轮次 id 与因此完成的轮次
+    pub fn transcript(&mut self, text: &str, now_ms: u64) -> (u64, Vec<Turn>) {
+        let waiting = self
+            .turns
+            .iter_mut()
+            .find(|turn| !turn.completed && turn.user.transcript.is_none());
+        let turn_id = match waiting {
+            Some(turn) => {
+                turn.user.transcript = Some(text.to_string());
+                turn.turn_id
+            }
+            None => {
+                let turn = self.latest_open_mut();
+                match &mut turn.user.transcript {
+                    Some(existing) => existing.push_str(text),
+                    None => turn.user.transcript = Some(text.to_string()),
+                }
+                turn.turn_id
+            }
+        };
+        (turn_id, self.collect_completed(now_ms))
+    }
+
+    // 一条助手回复开始播放，归属当前最新的轮次
+    pub fn reply_started(&mut self, utterance_id: u64) {
+        let turn = self.latest_open_mut();
+        if !turn.playing_utterances.contains(&utterance_id) {
+            turn.playing_utterances.push(utterance_id);
+        }
+    }
+
+    // 一条助手回复结束（播完或被打断）；more_pending 表示还有排队中的后续回复
+    pub fn reply(&mut self, reply: AssistantReply, more_pending: bool, now_ms: u64) -> Vec<Turn> {
+        let owner = reply.utterance_id.and_then(|id| {
+            self.turns
+                .iter()
+                .position(|turn| !turn.completed && turn.playing_utterances.contains(&id))
+        });
+        let turn = match owner {
+            Some(index) => &mut self.turns[index],
+            None => self.latest_open_mut(),
+        };
+        if let Some(id) = reply.utterance_id {
+            turn.playing_utterances.retain(|&playing| playing != id);
+        }
+        turn.replies.push(reply);
+        turn.replies_done = !more_pending;
+        self.collect_completed(now_ms)
+    }
+
+    // 按时间顺序分页查询
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<Turn> {
+        self.turns.iter().skip(offset).take(limit).cloned().collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<Turn> {
+        self.turns.iter().cloned().collect()
+    }
+
+    // 删除某一轮的用户转写，轮次本身保留并标记为已删除；drop_audio 时同时解除语音段关联。
+    // 返回被删除的转写文本与语音段序号，轮次不存在时返回None
+    pub fn delete_transcript(&mut self, turn_id: u64, drop_audio: bool) -> Option<(Option<String>, Vec<u64>)> {
+        let turn = self.turns.iter_mut().find(|turn| turn.turn_id == turn_id)?;
+        turn.user.deleted = true;
+        let transcript = turn.user.transcript.take();
+        let segment_ids = if drop_audio {
+            std::mem::take(&mut turn.user.audio_segment_ids)
+        } else {
+            Vec::new()
+        };
+        Some((transcript, segment_ids))
+    }
+
+    // 清空已有轮次，轮次编号继续递增
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+
+    // 从checkpoint恢复：恢复出的未完成轮次不会再收到后续事件，一律视为已完成
+    pub fn restore(&mut self, turns: &[Turn]) {
+        for turn in turns {
+            let mut turn = turn.clone();
+            turn.completed = true;
+            self.next_turn_id = self.next_turn_id.max(turn.turn_id + 1);
+            self.push(turn);
+        }
+    }
+
+    fn open_turn(&mut self) {
+        let turn = Turn::new(self.next_turn_id);
+        self.next_turn_id += 1;
+        self.push(turn);
+    }
+
+    fn push(&mut self, turn: Turn) {
+        if self.turns.len() >= MAX_TURNS {
+            self.turns.pop_front();
+        }
+        self.turns.push_back(turn);
+    }
+
+    // 最新的未完成轮次，没有时新开一轮（如助手主动发言、无语音的文本输入）
+    fn latest_open_mut(&mut self) -> &mut Turn {
+        if !matches!(self.turns.back(), Some(turn) if !turn.completed) {
+            self.open_turn();
+        }
+        let last = self.turns.len() - 1;
+        &mut self.turns[last]
+    }
+
+    fn collect_completed(&mut self, now_ms: u64) -> Vec<Turn> {
+        let mut completed = Vec::new();
+        for turn in self.turns.iter_mut() {
+            if !turn.completed && turn.ready(now_ms) {
+                turn.completed = true;
+                completed.push(turn.clone());
+            }
+        }
+        completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug)]
+    enum Event {
+        SpeechStarted,
+        SpeechEnded,
+        Segments(u64),
+        Transcript(&'static str),
+        ReplyStarted(u64),
+        ReplyEnded(u64, &'static str),
+    }
+
+    // 两轮对话的事件及其先后依赖：麦克风与播放事件按发生顺序到达，
+    // 最终识别结果只在本轮说完之后、且在上一条识别结果之后到达，回复结束只在其开始播放之后到达
+    const EVENTS: [(Event, &[usize]); 12] = [
+        (Event::SpeechStarted, &[]),
+        (Event::Segments(1), &[0]),
+        (Event::SpeechEnded, &[1]),
+        (Event::ReplyStarted(10), &[2]),
+        (Event::SpeechStarted, &[3]),
+        (Event::Segments(2), &[4]),
+        (Event::SpeechEnded, &[5]),
+        (Event::ReplyStarted(11), &[6]),
+        (Event::Transcript("打开客厅的灯"), &[2]),
+        (Event::Transcript("谢谢"), &[6, 8]),
+        (Event::ReplyEnded(10, "好的"), &[3]),
+        (Event::ReplyEnded(11, "不客气"), &[7]),
+    ];
+
+    // 按种子随机挑选满足依赖的事件，得到一种合法的到达顺序
+    fn shuffled_order(seed: u64) -> Vec<usize> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let mut order = Vec::new();
+        while order.len() < EVENTS.len() {
+            let available: Vec<usize> = (0..EVENTS.len())
+                .filter(|index| !order.contains(index))
+                .filter(|&index| EVENTS[index].1.iter().all(|dep| order.contains(dep)))
+                .collect();
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            order.push(available[(state >> 33) as usize % available.len()]);
+        }
+        order
+    }
+
+    // 转写、语音段、回复（文本与 utterance_id）、是否完成
+    type TurnSummary<'a> = (Option<&'a str>, &'a [u64], Vec<(&'a str, Option<u64>)>, bool);
+
+    // 依次应用事件，返回最终的轮次与每次完成事件中的轮次编号
+    fn replay(order: &[usize]) -> (Vec<Turn>, Vec<u64>) {
+        let mut tracker = TurnTracker::new();
+        let mut completed = Vec::new();
+        for (position, &index) in order.iter().enumerate() {
+            let now = 1000 + position as u64 * 100;
+            let done = match EVENTS[index].0 {
+                Event::SpeechStarted => tracker.user_speech_started(now),
+                Event::SpeechEnded => {
+                    tracker.user_speech_ended(now);
+                    Vec::new()
+                },
+                Event::Segments(id) => {
+                    tracker.audio_segments_finished(&[id]);
+                    Vec::new()
+                },
+                Event::Transcript(text) => tracker.transcript(text, now).1,
+                Event::ReplyStarted(id) => {
+                    tracker.reply_started(id);
+                    Vec::new()
+                },
+                Event::ReplyEnded(id, text) => {
+                    let reply = AssistantReply { text: text.to_string(), utterance_id: Some(id), interrupted: false };
+                    tracker.reply(reply, false, now)
+                },
+            };
+            completed.extend(done.iter().map(|turn| turn.turn_id));
+        }
+        (tracker.snapshot(), completed)
+    }
+
+    // 打乱事件到达顺序（最终识别结果晚于TTS、回复在下一轮开口后才结束等），构建出的轮次保持一致
+    #[test]
+    fn shuffled_events_build_the_same_turns() {
+        for seed in 0..500 {
+            let order = shuffled_order(seed);
+            let (turns, completed) = replay(&order);
+            let summary: Vec<TurnSummary> = turns
+                .iter()
+                .map(|turn| {
+                    let replies = turn.replies.iter().map(|reply| (reply.text.as_str(), reply.utterance_id)).collect();
+                    (turn.user.transcript.as_deref(), turn.user.audio_segment_ids.as_slice(), replies, turn.completed)
+                })
+                .collect();
+            assert_eq!(
+                summary,
+                vec![
+                    (Some("打开客厅的灯"), &[1u64][..], vec![("好的", Some(10))], true),
+                    (Some("谢谢"), &[2u64][..], vec![("不客气", Some(11))], true),
+                ],
+                "到达顺序: {:?}",
+                order.iter().map(|&index| EVENTS[index].0).collect::<Vec<_>>()
+            );
+            // 每一轮只完成一次
+            let mut sorted = completed.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, vec![1, 2], "完成顺序: {:?}", completed);
+        }
+    }
+
+    // 识别结果晚到时，已被下一轮取代的轮次等待结果而不是提前完成
+    #[test]
+    fn superseded_turn_waits_for_late_transcript() {
+        let mut tracker = TurnTracker::new();
+        tracker.user_speech_started(0);
+        tracker.reply_started(1);
+        assert!(tracker.reply(AssistantReply { text: "好的".to_string(), utterance_id: Some(1), interrupted: false }, false, 100).is_empty());
+        assert!(tracker.user_speech_started(200).is_empty());
+        let (turn_id, completed) = tracker.transcript("打开客厅的灯", 300);
+        assert_eq!(completed.len(), 1);
+        assert_eq!(turn_id, completed[0].turn_id);
+        assert_eq!(completed[0].user.transcript.as_deref(), Some("打开客厅的灯"));
+    }
+
+    // 超时仍没有识别结果的轮次按已有内容完成
+    #[test]
+    fn superseded_turn_completes_after_timeout() {
+        let mut tracker = TurnTracker::new();
+        tracker.user_speech_started(0);
+        tracker.reply(AssistantReply { text: "好的".to_string(), utterance_id: None, interrupted: false }, false, 100);
+        assert!(tracker.user_speech_started(200).is_empty());
+        let completed = tracker.user_speech_started(200 + TRANSCRIPT_WAIT_TIMEOUT_MS);
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].user.transcript, None);
+    }
+}
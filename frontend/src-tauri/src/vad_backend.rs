@@ -0,0 +1,261 @@
+// VAD 判定后端
+// VadProcessor 只负责切帧、计数、滞回与标注，单帧"是否语音"的判定交给可替换的后端。
+// 默认使用 webrtc_vad；启用 silero-vad feature 时可以切换为 Silero 模型（onnxruntime 推理），
+// 对气声与远场麦克风更稳。后端出错时由 VadProcessor 退回能量判定。
+// 能量后端按帧RMS与自适应底噪判定，不依赖任何库，webrtc_vad 无法初始化的精简构建中作为兜底，
+// 也适合音量很小、webrtc_vad 总判为静音的麦克风（调低 energy_fallback_threshold 即可）。
+
+use serde::{Deserialize, Serialize};
+use webrtc_vad::Vad;
+
+use crate::{webrtc_sample_rate, VadAggressiveness, SUPPORTED_SAMPLE_RATES};
+
+// 未指定模型路径时读取的环境变量
+#[cfg(feature = "silero-vad")]
+const SILERO_MODEL_ENV: &str = "LUMINA_SILERO_MODEL";
+pub const DEFAULT_SILERO_THRESHOLD: f32 = 0.5;
+// 能量后端：帧RMS需超过底噪的倍数
+const ENERGY_FLOOR_MARGIN: f32 = 3.0;
+// 底噪下降快、上升慢，避免说话时的能量把底噪抬高
+const ENERGY_FLOOR_FALL: f32 = 0.1;
+const ENERGY_FLOOR_RISE: f32 = 0.002;
+
+#[derive(Debug)]
+pub enum VadError {
+    Backend(String), // 后端内部错误
+}
+
+impl std::fmt::Display for VadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VadError::Backend(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+pub trait VadBackend: Send {
+    fn is_voice(&mut self, frame: &[i16]) -> Result<bool, VadError>;
+
+    // 灵敏度档位只对 webrtc_vad 有意义，其他后端忽略
+    fn set_aggressiveness(&mut self, _mode: VadAggressiveness) {}
+
+    // 能量阈值只对能量后端有意义，其他后端忽略
+    fn set_energy_threshold(&mut self, _threshold: f32) {}
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VadBackendKind {
+    Webrtc,
+    Silero,
+    Energy,
+}
+
+impl VadBackendKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "webrtc" => Some(VadBackendKind::Webrtc),
+            "silero" => Some(VadBackendKind::Silero),
+            "energy" => Some(VadBackendKind::Energy),
+            _ => None,
+        }
+    }
+}
+
+// Silero 后端的设置
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SileroSettings {
+    pub model_path: Option<String>, // 缺省时读取 LUMINA_SILERO_MODEL
+    pub threshold: f32,             // 语音概率超过该值判为语音
+}
+
+impl Default for SileroSettings {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            threshold: DEFAULT_SILERO_THRESHOLD,
+        }
+    }
+}
+
+impl SileroSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.threshold.is_finite() || self.threshold <= 0.0 || self.threshold >= 1.0 {
+            return Err(format!("Silero阈值必须在0~1之间: {}", self.threshold));
+        }
+        Ok(())
+    }
+}
+
+// 按类型构建后端；采样率不受该后端支持、feature 未启用或模型加载失败时返回错误
+pub fn build(
+    kind: VadBackendKind,
+    sample_rate: u32,
+    mode: VadAggressiveness,
+    silero: &SileroSettings,
+    energy_threshold: f32,
+) -> Result<Box<dyn VadBackend>, String> {
+    match kind {
+        VadBackendKind::Webrtc => Ok(Box::new(WebrtcBackend::new(sample_rate, mode)?)),
+        VadBackendKind::Silero => build_silero(sample_rate, silero),
+        VadBackendKind::Energy => Ok(Box::new(EnergyBackend::new(energy_threshold))),
+    }
+}
+
+#[cfg(feature = "silero-vad")]
+fn build_silero(sample_rate: u32, settings: &SileroSettings) -> Result<Box<dyn VadBackend>, String> {
+    settings.validate()?;
+    let model_path = match &settings.model_path {
+        Some(path) => path.clone(),
+        None => std::env::var(SILERO_MODEL_ENV)
+            .map_err(|_| format!("未指定Silero模型路径，请传入model_path或设置环境变量{}", SILERO_MODEL_ENV))?,
+    };
+    Ok(Box::new(silero::SileroBackend::new(&model_path, sample_rate, settings.threshold)?))
+}
+
+#[cfg(not(feature = "silero-vad"))]
+fn build_silero(_sample_rate: u32, _settings: &SileroSettings) -> Result<Box<dyn VadBackend>, String> {
+    Err(crate::features::disabled_error(crate::features::Feature::SileroVad))
+}
+
+pub struct WebrtcBackend {
+    vad: Vad,
+}
+
+// SAFETY: webrtc_vad::Vad 只是 libfvad 实例（Fvad *）的独占包装，因裸指针字段而不会自动实现 Send。
+// 把它移到另一个线程是安全的：
+// - 该指针由 Vad::new_with_rate_and_mode 中的 fvad_new 分配，只归这一个 Vad 所有，Drop 时 fvad_free，没有别名；
+// - libfvad 的全部状态都在该实例内，库里没有可变全局变量或线程局部存储，也不要求在创建它的线程上调用；
+// - Vad 的所有方法都取 &mut self，调用方必须独占访问。
+// 这里只实现 Send 不实现 Sync：后端经 Box<dyn VadBackend> 放在 AppState 中 VadProcessor 的 Mutex 里，同一时刻只有一个线程调用它。
+unsafe impl Send for WebrtcBackend {}
+
+impl WebrtcBackend {
+    fn new(sample_rate: u32, mode: VadAggressiveness) -> Result<Self, String> {
+        let rate = webrtc_sample_rate(sample_rate).ok_or_else(|| {
+            format!("不支持的采样率: {}Hz，可选值: {:?}", sample_rate, SUPPORTED_SAMPLE_RATES)
+        })?;
+        Ok(Self {
+            vad: Vad::new_with_rate_and_mode(rate, mode.to_vad_mode()),
+        })
+    }
+}
+
+impl VadBackend for WebrtcBackend {
+    fn is_voice(&mut self, frame: &[i16]) -> Result<bool, VadError> {
+        self.vad
+            .is_voice_segment(frame)
+            .map_err(|_| VadError::Backend("webrtc_vad处理失败".to_string()))
+    }
+
+    fn set_aggressiveness(&mut self, mode: VadAggressiveness) {
+        self.vad.set_mode(mode.to_vad_mode());
+    }
+}
+
+// 能量判定：帧RMS同时超过 min_rms 与底噪的 ENERGY_FLOOR_MARGIN 倍时判为语音，
+// 底噪只在判为静音的帧上更新
+pub struct EnergyBackend {
+    min_rms: f32,
+    noise_floor: Option<f32>,
+}
+
+impl EnergyBackend {
+    pub fn new(min_rms: f32) -> Self {
+        Self {
+            min_rms,
+            noise_floor: None,
+        }
+    }
+}
+
+impl VadBackend for EnergyBackend {
+    fn is_voice(&mut self, frame: &[i16]) -> Result<bool, VadError> {
+        let rms = crate::audio_utils::frame_rms(frame);
+        let floor = *self.noise_floor.get_or_insert(rms.min(self.min_rms));
+        let is_voice = rms > self.min_rms && rms > floor * ENERGY_FLOOR_MARGIN;
+        if !is_voice {
+            let rate = if rms < floor { ENERGY_FLOOR_FALL } else { ENERGY_FLOOR_RISE };
+            self.noise_floor = Some(floor + (rms - floor) * rate);
+        }
+        Ok(is_voice)
+    }
+
+    fn set_energy_threshold(&mut self, threshold: f32) {
+        self.min_rms = threshold;
+    }
+}
+
+// Silero VAD（v4 ONNX 模型）：按30ms窗口推理，输出语音概率；
+// 前端送来的帧先累积，凑满一个窗口推理一次，未凑满时沿用上一个窗口的结果
+#[cfg(feature = "silero-vad")]
+mod silero {
+    use ort::session::Session;
+    use ort::value::Tensor;
+
+    use super::{VadBackend, VadError};
+
+    // 模型 LSTM 状态的形状 [2, 1, 64]
+    const STATE_LEN: usize = 2 * 64;
+
+    pub struct SileroBackend {
+        session: Session,
+        sample_rate: i64,
+        window_samples: usize,
+        threshold: f32,
+        pending: Vec<f32>, // 尚未凑满窗口的样本
+        h: Vec<f32>,
+        c: Vec<f32>,
+        last_voice: bool,
+    }
+
+    impl SileroBackend {
+        pub fn new(model_path: &str, sample_rate: u32, threshold: f32) -> Result<Self, String> {
+            if sample_rate != 8000 && sample_rate != 16000 {
+                return Err(format!("Silero VAD只支持8000/16000Hz，当前采样率: {}Hz", sample_rate));
+            }
+            let session = Session::builder()
+                .and_then(|builder| builder.commit_from_file(model_path))
+                .map_err(|e| format!("加载Silero模型 {} 失败: {}", model_path, e))?;
+            Ok(Self {
+                session,
+                sample_rate: sample_rate as i64,
+                window_samples: (sample_rate as usize) * 30 / 1000,
+                threshold,
+                pending: Vec::new(),
+                h: vec![0.0; STATE_LEN],
+                c: vec![0.0; STATE_LEN],
+                last_voice: false,
+            })
+        }
+
+        fn infer(&mut self, window: Vec<f32>) -> Result<f32, ort::Error> {
+            let input = Tensor::from_array(([1usize, window.len()], window.into_boxed_slice()))?;
+            let sr = Tensor::from_array(([1usize], vec![self.sample_rate].into_boxed_slice()))?;
+            let h = Tensor::from_array(([2usize, 1, 64], self.h.clone().into_boxed_slice()))?;
+            let c = Tensor::from_array(([2usize, 1, 64], self.c.clone().into_boxed_slice()))?;
+            let outputs = self.session.run(ort::inputs!["input" => input, "sr" => sr, "h" => h, "c" => c]?)?;
+
+            let (_, probability) = outputs["output"].try_extract_raw_tensor::<f32>()?;
+            let (_, hn) = outputs["hn"].try_extract_raw_tensor::<f32>()?;
+            let (_, cn) = outputs["cn"].try_extract_raw_tensor::<f32>()?;
+            self.h.copy_from_slice(hn);
+            self.c.copy_from_slice(cn);
+            Ok(probability.first().copied().unwrap_or(0.0))
+        }
+    }
+
+    impl VadBackend for SileroBackend {
+        fn is_voice(&mut self, frame: &[i16]) -> Result<bool, VadError> {
+            self.pending.extend(frame.iter().map(|&s| s as f32 / 32768.0));
+            while self.pending.len() >= self.window_samples {
+                let window: Vec<f32> = self.pending.drain(..self.window_samples).collect();
+                let probability = self
+                    .infer(window)
+                    .map_err(|e| VadError::Backend(format!("Silero推理失败: {}", e)))?;
+                self.last_voice = probability > self.threshold;
+            }
+            Ok(self.last_voice)
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// VAD 处理统计
+// 由 VadProcessor 维护的累计计数，供调试时查看判定结果分布与单帧判定耗时，不必临时加打印再重新编译。
+// 判定耗时只计判定后端本身（webrtc_vad / Silero），不含切帧、状态机与发送，便于在低配机器上发现判定的性能回退。
+// 重置 VAD 状态不会清空统计，只有 reset_vad_statistics 会。
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VadStatistics {
+    pub frames_processed: u64,
+    pub voice_frames: u64,
+    pub silence_frames: u64,
+    pub speech_started: u64,
+    pub speech_ended: u64,
+    pub avg_detect_us: f64, // 单帧判定平均耗时（微秒）
+    pub max_detect_us: f64,
+    pub size_adjusted_chunks: u64, // 长度不是合法帧长、需要重新切帧的输入块数
+    pub since_secs: f64,           // 距上次重置的时长
+}
+
+pub struct VadStats {
+    frames_processed: u64,
+    voice_frames: u64,
+    speech_started: u64,
+    speech_ended: u64,
+    detect_total: Duration,
+    detect_max: Duration,
+    size_adjusted_chunks: u64,
+    since: Instant,
+}
+
+impl VadStats {
+    pub fn new() -> Self {
+        Self {
+            frames_processed: 0,
+            voice_frames: 0,
+            speech_started: 0,
+            speech_ended: 0,
+            detect_total: Duration::ZERO,
+            detect_max: Duration::ZERO,
+            size_adjusted_chunks: 0,
+            since: Instant::now(),
+        }
+    }
+
+    pub fn record_frame(&mut self, is_voice: bool, detect_time: Duration) {
+        self.frames_processed += 1;
+        if is_voice {
+            self.voice_frames += 1;
+        }
+        self.detect_total += detect_time;
+        self.detect_max = self.detect_max.max(detect_time);
+    }
+
+    pub fn record_speech_started(&mut self) {
+        self.speech_started += 1;
+    }
+
+    pub fn record_speech_ended(&mut self) {
+        self.speech_ended += 1;
+    }
+
+    pub fn record_size_adjusted(&mut self) {
+        self.size_adjusted_chunks += 1;
+    }
+
+    pub fn snapshot(&self) -> VadStatistics {
+        let avg_detect_us = if self.frames_processed > 0 {
+            self.detect_total.as_secs_f64() * 1_000_000.0 / self.frames_processed as f64
+        } else {
+            0.0
+        };
+        VadStatistics {
+            frames_processed: self.frames_processed,
+            voice_frames: self.voice_frames,
+            silence_frames: self.frames_processed - self.voice_frames,
+            speech_started: self.speech_started,
+            speech_ended: self.speech_ended,
+            avg_detect_us,
+            max_detect_us: self.detect_max.as_secs_f64() * 1_000_000.0,
+            size_adjusted_chunks: self.size_adjusted_chunks,
+            since_secs: self.since.elapsed().as_secs_f64(),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// 会话音频的流式 WAV 录制
+// start_streaming_wav 打开文件并写入长度为0的 WAV 头，之后每个发往后端的音频段（经过处理链之后）立即追加到末尾，
+// 每隔 HEADER_UPDATE_INTERVAL_SECS 回填一次 RIFF/data 长度，stop_streaming_wav 时再回填并刷盘。
+// 应用崩溃时文件只缺少最后一次回填之后的长度，播放器最多丢失末尾几秒。
+// 不留痕模式下挂起写入：文件保持打开，期间的音频不写入，恢复后继续追加。
+
+use log::warn;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::audio_utils;
+use crate::privacy::Suspendable;
+
+// 定期回填长度的间隔
+const HEADER_UPDATE_INTERVAL_SECS: u64 = 1;
+// data 块长度字段为 u32，RIFF 长度还要加上头部的36字节
+const MAX_DATA_BYTES: u64 = u32::MAX as u64 - 36;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StreamingWavStatus {
+    pub path: String,
+    pub sample_rate: u32,
+    pub samples: u64,
+    pub duration_ms: u64,
+    pub suspended: bool, // 不留痕模式下挂起，期间的音频未写入
+    pub full: bool,      // 已达到 WAV 的 4GB 上限，之后的音频未写入
+}
+
+pub struct StreamingWav {
+    file: File,
+    path: PathBuf,
+    sample_rate: u32,
+    data_bytes: u64,
+    last_header_update: Instant,
+    suspended: bool,
+    full: bool,
+}
+
+impl StreamingWav {
+    // 创建文件并写入长度为0的 WAV 头；文件已存在时覆盖
+    pub fn create(path: &Path, sample_rate: u32) -> Result<Self, String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("创建目录 {} 失败: {}", dir.display(), e))?;
+        }
+        let mut file = File::create(path).map_err(|e| format!("创建WAV文件 {} 失败: {}", path.display(), e))?;
+        file.write_all(&audio_utils::encode_wav_pcm16(&[], sample_rate))
+            .map_err(|e| format!("写入WAV头 {} 失败: {}", path.display(), e))?;
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            sample_rate,
+            data_bytes: 0,
+            last_header_update: Instant::now(),
+            suspended: false,
+            full: false,
+        })
+    }
+
+    // 追加一段音频，距上次回填超过间隔时顺带回填长度
+    pub fn append(&mut self, samples: &[i16]) -> Result<(), String> {
+        if self.suspended || self.full || samples.is_empty() {
+            return Ok(());
+        }
+
+        let room = (MAX_DATA_BYTES - self.data_bytes) / 2;
+        let samples = if samples.len() as u64 > room {
+            warn!("[警告] 流式WAV {} 已达到4GB上限，之后的音频不再写入", self.path.display());
+            self.full = true;
+            &samples[..room as usize]
+        } else {
+            samples
+        };
+        let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+        self.file
+            .write_all(&bytes)
+            .map_err(|e| format!("追加写入WAV文件 {} 失败: {}", self.path.display(), e))?;
+        self.data_bytes += bytes.len() as u64;
+
+        if self.full || self.last_header_update.elapsed() >= Duration::from_secs(HEADER_UPDATE_INTERVAL_SECS) {
+            self.update_header()?;
+        }
+        Ok(())
+    }
+
+    // 回填 RIFF 与 data 长度，写完后回到文件末尾继续追加
+    fn update_header(&mut self) -> Result<(), String> {
+        let data_len = self.data_bytes as u32;
+        let result = (|| {
+            self.file.seek(SeekFrom::Start(4))?;
+            self.file.write_all(&(36 + data_len).to_le_bytes())?;
+            self.file.seek(SeekFrom::Start(40))?;
+            self.file.write_all(&data_len.to_le_bytes())?;
+            self.file.seek(SeekFrom::End(0))?;
+            self.file.flush()
+        })();
+        self.last_header_update = Instant::now();
+        result.map_err(|e| format!("回填WAV长度 {} 失败: {}", self.path.display(), e))
+    }
+
+    // 结束录制：回填最终长度并刷盘
+    pub fn finish(mut self) -> Result<StreamingWavStatus, String> {
+        self.update_header()?;
+        self.file
+            .sync_all()
+            .map_err(|e| format!("刷写WAV文件 {} 失败: {}", self.path.display(), e))?;
+        Ok(self.status())
+    }
+
+    pub fn status(&self) -> StreamingWavStatus {
+        let samples = self.data_bytes / 2;
+        StreamingWavStatus {
+            path: self.path.display().to_string(),
+            sample_rate: self.sample_rate,
+            samples,
+            duration_ms: samples * 1000 / self.sample_rate.max(1) as u64,
+            suspended: self.suspended,
+            full: self.full,
+        }
+    }
+}
+
+impl Suspendable for StreamingWav {
+    fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+}
@@ -0,0 +1,84 @@
+// 波形缩略图
+// 回放段列表中每段显示一个小波形：把样本按宽度分桶，每桶取 min/max，归一化到 -127..127，
+// 返回 [min0, max0, min1, max1, ...]，长度为宽度的两倍，前端直接画到 canvas 上。
+// 结果按 (来源, 宽度) 缓存；单段的来源是段 id，段被删除后对应的缓存随之失效；
+// 合并段的来源带上所含段的 id 范围，段增删后旧的合并段缓存不再命中并被清掉。
+
+use std::collections::HashMap;
+
+pub const MAX_THUMBNAIL_WIDTH: usize = 4096;
+// 缓存条目上限，超出时整体清空
+const MAX_CACHED_THUMBNAILS: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThumbnailSource {
+    Segment(u64),       // 单个语音段的 id
+    Combined(u64, u64), // 合并段：所含段的 id 范围 [start, end)
+}
+
+pub struct ThumbnailCache {
+    entries: HashMap<(ThumbnailSource, usize), Vec<i8>>,
+}
+
+impl ThumbnailCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    // 命中时直接返回缓存，否则计算并缓存
+    pub fn get_or_compute(&mut self, source: ThumbnailSource, width: usize, samples: &[i16]) -> Vec<i8> {
+        if let Some(cached) = self.entries.get(&(source, width)) {
+            return cached.clone();
+        }
+
+        if let ThumbnailSource::Combined(..) = source {
+            // 同一时刻只有一种合并段是有效的
+            self.entries
+                .retain(|(cached, _), _| !matches!(cached, ThumbnailSource::Combined(..)));
+        }
+        if self.entries.len() >= MAX_CACHED_THUMBNAILS {
+            self.entries.clear();
+        }
+        let thumbnail = thumbnail(samples, width);
+        self.entries.insert((source, width), thumbnail.clone());
+        thumbnail
+    }
+
+    // 段的位置序号整体失效（如从中间删除了段）
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // id 小于 first_id 的段已被删除
+    pub fn invalidate_before(&mut self, first_id: u64) {
+        self.entries.retain(|(source, _), _| match source {
+            ThumbnailSource::Segment(id) => *id >= first_id,
+            ThumbnailSource::Combined(start, _) => *start >= first_id,
+        });
+    }
+}
+
+// 分桶 min/max 降采样；空段或样本数不足宽度的超短段返回全零
+pub fn thumbnail(samples: &[i16], width: usize) -> Vec<i8> {
+    let mut out = vec![0i8; width * 2];
+    if samples.len() < width {
+        return out;
+    }
+
+    for bucket in 0..width {
+        let start = bucket * samples.len() / width;
+        let end = (bucket + 1) * samples.len() / width;
+        let chunk = &samples[start..end];
+        let min = chunk.iter().copied().min().unwrap_or(0);
+        let max = chunk.iter().copied().max().unwrap_or(0);
+        out[bucket * 2] = scale(min);
+        out[bucket * 2 + 1] = scale(max);
+    }
+    out
+}
+
+fn scale(sample: i16) -> i8 {
+    (sample as i32 * 127 / 32768).clamp(-127, 127) as i8
+}
@@ -362,11 +362,11 @@ class AudioCaptureService implements AudioCaptureInterface {
               const audioArray = Array.from(event.data.audioData);
               
               // 调用Rust后端处理音频，并接收返回的VAD事件
-              const eventResult = await tauriApi.invoke('process_audio_frame', {
+              const eventResult = await tauriApi.invoke<string[]>('process_audio_frame', {
                 audioData: audioArray
               });
               
-              if (eventResult !== 'Processing') {
+              if (eventResult.length > 0) {
                 logDebug('处理结果', { eventResult });
               }
             }
@@ -840,11 +840,11 @@ class AudioCaptureService implements AudioCaptureInterface {
               const audioArray = Array.from(event.data.audioData);
               
               // 调用Rust后端处理音频，并接收返回的VAD事件
-              const eventResult = await tauriApi.invoke('process_audio_frame', {
+              const eventResult = await tauriApi.invoke<string[]>('process_audio_frame', {
                 audioData: audioArray
               });
               
-              if (eventResult !== 'Processing') {
+              if (eventResult.length > 0) {
                 logDebug('处理结果', { eventResult });
               }
             }
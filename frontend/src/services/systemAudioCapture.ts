@@ -284,11 +284,11 @@ class SystemAudioCaptureService implements SystemAudioCaptureInterface {
       
       // 调用Tauri后端API，直接发送Float32Array格式的PCM数据
       try {
-        const eventResult = await tauriApi.invoke('process_audio_frame', {
+        const eventResult = await tauriApi.invoke<string[]>('process_audio_frame', {
           audioData: Array.from(audioData) // 转换为普通数组
         });
         
-        if (eventResult !== 'Processing') {
+        if (eventResult.length > 0) {
           logDebug('处理结果', { eventResult });
         }
       } catch (error) {